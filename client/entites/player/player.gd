@@ -415,7 +415,7 @@ func load_weapon(weapon_id: int) -> void:
 		push_warning("Weapon data not found for weapon_id: " + str(weapon_id))
 		return
 	
-	var scene_path = weapon_data.get("scene_path", "")
+	var scene_path = weapon_data.get("cosmetics", {}).get("scene_path", "")
 	if scene_path.is_empty():
 		push_warning("Weapon scene_path is empty for weapon_id: " + str(weapon_id))
 		return
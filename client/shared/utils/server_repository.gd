@@ -18,6 +18,7 @@ var callbacks: Node  # ServerCallbacks instance
 # Lobby state
 var current_lobby: Dictionary = {}
 var player_id: int = -1
+var session_token: String = ""  # Lets the server accept us again if our UDP address changes
 var connected_players: Dictionary = {}
 var _last_joined_lobby_code: String = ""
 
@@ -99,13 +100,22 @@ func join_lobby(code: String) -> void:
 	var headers = ["Content-Type: application/json"]
 	var player_name = "Player_" + str(Time.get_ticks_msec() % 10000)
 	var body = JSON.stringify({
-		"player_name": player_name
+		"player_name": player_name,
+		"settings": _client_settings()
 	})
 	print("Request URL: ", url)
 	print("Request body: ", body)
 
 	_make_request(url, headers, HTTPClient.METHOD_POST, body, "join_lobby")
 
+# FOV and aim assist attested at join, checked against the lobby's rules
+func _client_settings() -> Dictionary:
+	var fov = 75.0
+	var camera = get_viewport().get_camera_3d()
+	if camera:
+		fov = camera.fov
+	return {"fov": fov, "aim_assist": false}
+
 func get_lobby_info(code: String) -> void:
 	var url = SERVER_URL + "/lobbies/" + code
 	_make_request(url, [], HTTPClient.METHOD_GET, "", "get_lobby_info")
@@ -169,6 +179,7 @@ func _handle_join_lobby_response(response_code: int, data: Dictionary) -> void:
 		print("Join successful!")
 		current_lobby = data.get("lobby", {})
 		player_id = data.get("player_id", -1)
+		session_token = data.get("session_token", "")
 
 		print("Parsed lobby data - code:", current_lobby.get("code", "none"), " player_id:", player_id)
 
@@ -248,7 +259,8 @@ func send_join_packet() -> void:
 	var packet = {
 		"type": "join",
 		"lobby_code": current_lobby.get("code", ""),
-		"player_id": player_id
+		"player_id": player_id,
+		"session_token": session_token
 	}
 
 	print("Sending join packet to server - lobby: ", current_lobby.get("code", ""), " player_id: ", player_id)
@@ -289,6 +301,19 @@ func send_weapon_switch(weapon_id: int) -> void:
 
 	adaptor.send_udp_packet(packet)
 
+## Tell the server the scene is loaded; until then we can't act and others can't see us
+func send_loading_complete() -> void:
+	if not adaptor or not adaptor.is_udp_connected():
+		return
+
+	var packet = {
+		"type": "loading_complete",
+		"lobby_code": current_lobby.get("code", ""),
+		"player_id": player_id
+	}
+
+	adaptor.send_udp_packet(packet)
+
 func _on_udp_packet_received(data: Dictionary) -> void:
 	# Update activity timestamp for any UDP packet
 	last_udp_activity = Time.get_ticks_msec() / 1000.0
@@ -314,21 +339,23 @@ func _on_udp_packet_received(data: Dictionary) -> void:
 			callbacks.on_connection_confirmed()
 			print("UDP connection fully established!")
 
-		"position_update":
-			var remote_player_id = data.get("player_id", -1)
-			var pos_data = data.get("position", {})
-			var position = Vector3(
-				pos_data.get("x", 0.0),
-				pos_data.get("y", 0.0),
-				pos_data.get("z", 0.0)
-			)
-			var rot_data = data.get("rotation", {})
-			var rotation = Vector3(
-				rot_data.get("x", 0.0),
-				rot_data.get("y", 0.0),
-				rot_data.get("z", 0.0)
-			)
-			callbacks.on_position_update_received(remote_player_id, position, rotation)
+		"positions":
+			# Every player that moved this tick, in one packet
+			for entry in data.get("players", []):
+				var remote_player_id = entry.get("player_id", -1)
+				var pos_data = entry.get("position", {})
+				var position = Vector3(
+					pos_data.get("x", 0.0),
+					pos_data.get("y", 0.0),
+					pos_data.get("z", 0.0)
+				)
+				var rot_data = entry.get("rotation", {})
+				var rotation = Vector3(
+					rot_data.get("x", 0.0),
+					rot_data.get("y", 0.0),
+					rot_data.get("z", 0.0)
+				)
+				callbacks.on_position_update_received(remote_player_id, position, rotation)
 
 		"player_joined":
 			var player_data = data.get("player", {})
@@ -364,6 +391,21 @@ func _on_udp_packet_received(data: Dictionary) -> void:
 			var attacker_id = data.get("attacker_id", -1)
 			callbacks.on_player_damaged(damaged_player_id, damage_amount, attacker_id)
 
+		"migrate_to":
+			# Our lobby was merged into another one on the same server; our player carried over
+			print("Lobby ", data.get("from", ""), " merged into ", data.get("lobby_code", ""))
+			current_lobby["code"] = data.get("lobby_code", current_lobby.get("code", ""))
+			current_lobby["scene"] = data.get("scene", current_lobby.get("scene", ""))
+			current_lobby["mode"] = data.get("mode", current_lobby.get("mode", ""))
+			for remote_player_id in connected_players.keys():
+				callbacks.on_player_left(remote_player_id)
+			connected_players.clear()
+
+		"session_moved":
+			# Our session was taken over from another address with our session token
+			push_warning("Session for player " + str(data.get("player_id", -1)) + " moved to another address")
+			_set_connection_state(callbacks.ConnectionState.DISCONNECTED)
+
 # Utility methods
 
 func is_connected_to_lobby() -> bool:
@@ -392,6 +434,7 @@ func leave_current_lobby() -> void:
 
 	current_lobby.clear()
 	player_id = -1
+	session_token = ""
 	connected_players.clear()
 
 	_set_connection_state(callbacks.ConnectionState.CONNECTED_HTTP)
@@ -513,7 +556,8 @@ func _send_keepalive() -> void:
 			var packet = {
 				"type": "keepalive",
 				"lobby_code": current_lobby.get("code", ""),
-				"player_id": player_id
+				"player_id": player_id,
+				"session_token": session_token
 			}
 			adaptor.send_udp_packet(packet)
 
@@ -132,6 +132,9 @@ func _on_lobby_joined(lobby_data: Dictionary) -> void:
 	# Spawn server dummy
 	spawn_server_dummy()
 
+	# Scene is ready: let the server put us into play
+	ServerRepository.send_loading_complete()
+
 	# Spawn existing remote players (excluding ourselves)
 	for player_data in lobby_data.get("players", []):
 		var player_id = player_data.get("id", -1)
@@ -0,0 +1,45 @@
+//! Stamping one broadcast event for every client of a lobby, the tick loop's send path:
+//! the event is serialized once, then each client's copy gets its own sequence numbers
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use gungame_core::net::reliable::{ReliableSender, SerializedPacket};
+use gungame_core::utils::config::Config;
+
+fn kill_event() -> serde_json::Value {
+    serde_json::json!({
+        "type": "player_killed",
+        "killer_id": 3,
+        "victim_id": 7,
+        "weapon_id": 2,
+        "headshot": true,
+        "position": {"x": 12.5, "y": 1.0, "z": -3.25},
+        "killer_level": 4
+    })
+}
+
+fn bench_broadcast_stamp(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reliable_stamp");
+    for clients in [8u32, 32, 64] {
+        group.throughput(Throughput::Elements(clients as u64));
+        group.bench_with_input(BenchmarkId::new("broadcast", clients), &clients, |b, &clients| {
+            let mut sender = ReliableSender::new(&Config::default());
+            let event = kill_event();
+            let mut tick = 0;
+            b.iter(|| {
+                tick += 1;
+                sender.start_tick(tick);
+                let packet = SerializedPacket::new(&event);
+                for player_id in 1..=clients {
+                    criterion::black_box(sender.stamp(player_id, &packet, true, true));
+                }
+                // Everyone acks, so pending queues stay the size they'd be in a healthy match
+                for player_id in 1..=clients {
+                    sender.ack(player_id, &[tick]);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_broadcast_stamp);
+criterion_main!(benches);
@@ -0,0 +1,138 @@
+//! UDP receive throughput of the real server: one port served by 1, 2 and 4 SO_REUSEPORT receivers
+//! Clients in one lobby send reliable position updates and wait for each ack, so every packet goes
+//! through the server's receive loop (`receive_datagrams` → `handle_datagram`): rate limiting,
+//! parsing, the ack, session checks and the hand-off to the lobby's tick loop
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::runtime::Runtime;
+use gungame_core::utils::config::Config;
+use gungame_core::utils::weapondb::WeaponDb;
+use gungame_core::{server, GameServer, GameServerBuilder};
+
+const CLIENTS: usize = 64;
+const PACKETS_PER_CLIENT: usize = 50;
+const LOBBY: &str = "bench";
+
+/// Start a server with `receivers` receive tasks and a lobby with room for every client
+/// The lobby's tick loop sends from its own socket, which the connected clients never hear from
+fn start_server(runtime: &Runtime, receivers: usize) -> GameServer {
+    runtime.block_on(async {
+        let config = Config {
+            udp_receivers: receivers,
+            udp_rate_limit_pps: 0,
+            max_sessions_per_ip: 0,
+            player_id_state_path: None,
+            reports_path: None,
+            storage_path: None,
+            tunables_path: None,
+            weapons_path: None,
+            ladder_presets_path: None,
+            lobby_analytics_path: None,
+            log_file: None,
+            ..Config::default()
+        };
+        let server = GameServerBuilder::new(config).http_port(0).udp_port(0).start().await.unwrap();
+        let tick_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        server::create_lobby_with_tick(server.state().clone(), LOBBY.to_string(), CLIENTS as u32, "world".to_string(), weapons, server.config().clone(), tick_socket)
+            .await
+            .unwrap();
+        server
+    })
+}
+
+/// Wait for a packet of type `wanted`, skipping anything else the server sends
+async fn recv(socket: &UdpSocket, wanted: &str) -> Option<serde_json::Value> {
+    let mut buf = [0u8; 1500];
+    loop {
+        let len = tokio::time::timeout(Duration::from_millis(100), socket.recv(&mut buf)).await.ok()?.ok()?;
+        let Ok(packet) = serde_json::from_slice::<serde_json::Value>(&buf[..len]) else { continue };
+        if packet["type"] == wanted {
+            return Some(packet);
+        }
+    }
+}
+
+/// Join every client to the lobby over UDP
+async fn join_clients(server: SocketAddr) -> Vec<(u32, Arc<UdpSocket>)> {
+    let mut clients = Vec::new();
+    for index in 0..CLIENTS {
+        let player_id = 10_000 + index as u32;
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server).await.unwrap();
+        let join = serde_json::json!({"type": "join", "lobby_code": LOBBY, "player_id": player_id, "player_name": format!("bench{}", index)});
+        loop {
+            socket.send(join.to_string().as_bytes()).await.unwrap();
+            if recv(&socket, "welcome").await.is_some() {
+                break;
+            }
+        }
+        clients.push((player_id, Arc::new(socket)));
+    }
+    clients
+}
+
+/// Every client sends its packets one at a time, resending when an ack is late
+async fn run_clients(clients: &[(u32, Arc<UdpSocket>)], round: u64) {
+    let started = Instant::now();
+    let mut tasks = Vec::new();
+    for (player_id, socket) in clients {
+        let (player_id, socket) = (*player_id, socket.clone());
+        tasks.push(tokio::spawn(async move {
+            for i in 0..PACKETS_PER_CLIENT as u64 {
+                let seq = round * PACKETS_PER_CLIENT as u64 + i + 1;
+                let packet = serde_json::json!({
+                    "type": "position_update",
+                    "player_id": player_id,
+                    "reliable": true,
+                    "seq": seq,
+                    "client_time": started.elapsed().as_millis() as u64,
+                    "position": {"x": i as f64 * 0.1, "y": 1.0, "z": -3.5},
+                    "rotation": {"x": 0.0, "y": 1.57, "z": 0.0}
+                })
+                .to_string();
+                loop {
+                    socket.send(packet.as_bytes()).await.unwrap();
+                    if recv(&socket, "ack").await.is_some_and(|ack| ack["ack"] == seq) {
+                        break;
+                    }
+                }
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+fn bench_udp_receive(c: &mut Criterion) {
+    // Clients get their own threads so the server is what's measured
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(4).enable_all().build().unwrap();
+    let clients = tokio::runtime::Builder::new_multi_thread().worker_threads(2).enable_all().build().unwrap();
+    let mut group = c.benchmark_group("udp_receive");
+    group.throughput(Throughput::Elements((CLIENTS * PACKETS_PER_CLIENT) as u64));
+    group.sample_size(20);
+    for receivers in [1, 2, 4] {
+        let server = start_server(&runtime, receivers);
+        let sockets = clients.block_on(join_clients(server.udp_addr()));
+        let mut round = 0;
+        group.bench_with_input(BenchmarkId::new("receivers", receivers), &sockets, |b, sockets| {
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    clients.block_on(run_clients(sockets, round));
+                    round += 1;
+                }
+                start.elapsed()
+            })
+        });
+        runtime.block_on(server.shutdown());
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_udp_receive);
+criterion_main!(benches);
@@ -0,0 +1,458 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use crate::http;
+
+/// Walking speed in units per second
+const WALK_SPEED: f32 = 5.0;
+
+/// Half the width of the square bots walk in, around the origin
+const ARENA_HALF_SIZE: f32 = 40.0;
+
+/// Chance per update of picking a new heading
+const TURN_CHANCE: f32 = 0.05;
+
+/// Players closer than this get shot at
+const SHOOT_RANGE: f32 = 25.0;
+
+/// Time between shots; anything faster than the weapon's fire rate is dropped by the server anyway
+const SHOT_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Random aim error, in units at one unit of distance
+const AIM_SPREAD: f32 = 0.05;
+
+/// Time between keepalives, each one timing a round trip
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wait this long for the lobby to confirm the join before sending it again
+const JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Joins sent before giving up
+const JOIN_ATTEMPTS: u32 = 3;
+
+/// Event sequence numbers remembered behind the newest one, to tell resends from late arrivals
+const SEQ_WINDOW: u64 = 64;
+
+const MAX_DATAGRAM: usize = 65_536;
+
+/// Where one bot connects and how it plays
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    pub server: String, // HTTP host:port; UDP goes to the same host
+    pub lobby: String,
+    pub name: String,
+    pub rate_hz: u32, // Position updates per second
+    pub duration: Duration,
+    pub seed: u64,
+}
+
+/// What one bot measured from its side of the connection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BotStats {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub events_received: u64, // Sequenced server events, resends left out
+    pub events_missed: u64, // Gaps in the event sequence numbers
+    pub keepalives_sent: u64,
+    pub keepalives_lost: u64, // Never acked, either way
+    pub round_trips_ms: Vec<f64>, // Keepalive sent to ack received
+    pub shots: u64,
+    pub kills: u32,
+    pub deaths: u32,
+    pub kicked: bool,
+}
+
+/// Join `config.lobby` over HTTP and UDP, play until `config.duration` is up, then leave
+pub async fn run(config: BotConfig) -> Result<BotStats, String> {
+    let (status, reply) = http::post_json(
+        &config.server,
+        &format!("/lobbies/{}/join", config.lobby),
+        &json!({"player_name": config.name}),
+    ).await?;
+    if status != 200 {
+        return Err(format!("Join refused with {}: {}", status, reply));
+    }
+    let player_id = reply["player_id"].as_u64().and_then(|id| u32::try_from(id).ok()).ok_or("Join reply without player_id")?;
+    let udp_port = reply["lobby"]["udp_port"].as_u64().ok_or("Join reply without udp_port")?;
+    let host = config.server.rsplit_once(':').map_or(config.server.as_str(), |(host, _)| host);
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| format!("Can't open a UDP socket: {}", e))?;
+    socket.connect(format!("{}:{}", host, udp_port)).await.map_err(|e| format!("Can't reach UDP port {}: {}", udp_port, e))?;
+
+    let mut bot = Bot::new(player_id, config.seed);
+    let join = json!({
+        "type": "join",
+        "lobby_code": config.lobby,
+        "player_name": config.name,
+        "session_token": reply["session_token"]
+    });
+    let mut buffer = vec![0u8; MAX_DATAGRAM];
+    // The port answers `welcome` at once; `udp_connected` comes once the lobby has taken the player in,
+    // and commands sent before that are dropped
+    let mut connected = false;
+    for _ in 0..JOIN_ATTEMPTS {
+        bot.send(&socket, join.clone()).await;
+        let deadline = tokio::time::Instant::now() + JOIN_TIMEOUT;
+        while let Ok(Ok(len)) = tokio::time::timeout_at(deadline, socket.recv(&mut buffer)).await {
+            bot.stats.packets_received += 1;
+            let Ok(packet) = serde_json::from_slice::<Value>(&buffer[..len]) else {
+                continue;
+            };
+            bot.receive(&packet, Instant::now());
+            match packet["type"].as_str() {
+                Some("udp_connected") => connected = true,
+                Some("error") => return Err(format!("UDP join refused: {}", packet["code"])),
+                _ => {}
+            }
+            if connected {
+                break;
+            }
+        }
+        if connected {
+            break;
+        }
+    }
+    if !connected {
+        return Err("The lobby never confirmed the UDP join".to_string());
+    }
+    bot.send(&socket, json!({"type": "loading_complete"})).await;
+
+    let dt = 1.0 / config.rate_hz.max(1) as f32;
+    let mut updates = tokio::time::interval(Duration::from_secs_f32(dt));
+    let mut keepalives = tokio::time::interval(KEEPALIVE_INTERVAL);
+    let finished = tokio::time::sleep(config.duration);
+    tokio::pin!(finished);
+    loop {
+        tokio::select! {
+            _ = &mut finished => break,
+            _ = updates.tick() => {
+                for packet in bot.step(dt, Instant::now()) {
+                    bot.send(&socket, packet).await;
+                }
+            }
+            _ = keepalives.tick() => {
+                let packet = bot.keepalive(Instant::now());
+                bot.send(&socket, packet).await;
+            }
+            received = socket.recv(&mut buffer) => {
+                // Errors are ICMP unreachables from a server that went away; keep trying until the end
+                let Ok(len) = received else {
+                    continue;
+                };
+                bot.stats.packets_received += 1;
+                if let Ok(packet) = serde_json::from_slice::<Value>(&buffer[..len]) {
+                    bot.receive(&packet, Instant::now());
+                }
+                if bot.stats.kicked {
+                    break;
+                }
+            }
+        }
+    }
+    if !bot.stats.kicked {
+        bot.send(&socket, json!({"type": "leave"})).await;
+    }
+    // Keepalives sent just before the end may still be on their way
+    let in_flight = bot.pending_keepalives.values().filter(|sent| sent.elapsed() < KEEPALIVE_INTERVAL).count() as u64;
+    bot.stats.keepalives_sent -= in_flight;
+    bot.stats.keepalives_lost = bot.pending_keepalives.len() as u64 - in_flight;
+    Ok(bot.stats)
+}
+
+/// A bot's view of the game, advanced by updates and server packets (no I/O)
+struct Bot {
+    id: u32,
+    position: (f32, f32, f32),
+    heading: f32, // Radians around the y axis
+    ammo: Option<u32>, // Unknown until the server reports it
+    reloading: bool,
+    others: HashMap<u32, (f32, f32, f32)>, // Live players by last known position
+    last_shot: Option<Instant>,
+    keepalive_seq: u64,
+    pending_keepalives: HashMap<u64, Instant>, // Unacked keepalives by seq
+    events: SeqTracker,
+    rng: Rng,
+    stats: BotStats,
+}
+
+impl Bot {
+    fn new(id: u32, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let position = ((rng.next_f32() * 2.0 - 1.0) * ARENA_HALF_SIZE, 1.0, (rng.next_f32() * 2.0 - 1.0) * ARENA_HALF_SIZE);
+        Self {
+            id,
+            position,
+            heading: rng.next_f32() * std::f32::consts::TAU,
+            ammo: None,
+            reloading: false,
+            others: HashMap::new(),
+            last_shot: None,
+            keepalive_seq: 0,
+            pending_keepalives: HashMap::new(),
+            events: SeqTracker::default(),
+            rng,
+            stats: BotStats::default(),
+        }
+    }
+
+    /// Walk for `dt` seconds and shoot at the nearest player in range; returns the packets to send
+    fn step(&mut self, dt: f32, now: Instant) -> Vec<Value> {
+        if self.rng.next_f32() < TURN_CHANCE {
+            self.heading = self.rng.next_f32() * std::f32::consts::TAU;
+        }
+        let (x, y, z) = self.position;
+        let (x, z) = (x + self.heading.sin() * WALK_SPEED * dt, z + self.heading.cos() * WALK_SPEED * dt);
+        if x.abs() > ARENA_HALF_SIZE || z.abs() > ARENA_HALF_SIZE {
+            self.heading += std::f32::consts::PI; // Back into the arena next update
+        } else {
+            self.position = (x, y, z);
+        }
+        let (x, y, z) = self.position;
+        let mut packets = vec![json!({
+            "type": "position_update",
+            "position": {"x": x, "y": y, "z": z},
+            "rotation": {"x": 0.0, "y": self.heading, "z": 0.0}
+        })];
+
+        if self.ammo == Some(0) {
+            if !self.reloading {
+                self.reloading = true;
+                packets.push(json!({"type": "reload"}));
+            }
+            return packets;
+        }
+        if self.last_shot.is_some_and(|at| now.duration_since(at) < SHOT_INTERVAL) {
+            return packets;
+        }
+        let target = self
+            .others
+            .values()
+            .map(|&target| (target, distance(self.position, target)))
+            .filter(|(_, distance)| *distance <= SHOOT_RANGE)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some(((tx, ty, tz), range)) = target {
+            let spread = AIM_SPREAD * range.max(1.0);
+            let aim = (
+                tx - x + (self.rng.next_f32() - 0.5) * spread,
+                ty - y + (self.rng.next_f32() - 0.5) * spread,
+                tz - z + (self.rng.next_f32() - 0.5) * spread,
+            );
+            let length = distance(aim, (0.0, 0.0, 0.0)).max(f32::EPSILON);
+            packets.push(json!({
+                "type": "shoot",
+                "origin": {"x": x, "y": y, "z": z},
+                "direction": {"x": aim.0 / length, "y": aim.1 / length, "z": aim.2 / length}
+            }));
+            self.last_shot = Some(now);
+            self.stats.shots += 1;
+        }
+        packets
+    }
+
+    /// A reliable keepalive; the server acks it straight away, which times the round trip
+    fn keepalive(&mut self, now: Instant) -> Value {
+        self.keepalive_seq += 1;
+        self.pending_keepalives.insert(self.keepalive_seq, now);
+        self.stats.keepalives_sent += 1;
+        json!({"type": "keepalive", "reliable": true, "seq": self.keepalive_seq})
+    }
+
+    /// Take in a JSON packet from the server
+    fn receive(&mut self, packet: &Value, now: Instant) {
+        if let Some(seq) = packet["seq"].as_u64() {
+            if self.events.receive(seq) {
+                self.stats.events_received += 1;
+            }
+        }
+        let player_id = packet["player_id"].as_u64().and_then(|id| u32::try_from(id).ok());
+        match packet["type"].as_str() {
+            Some("ack") => {
+                let (Some(ack), ack_bits) = (packet["ack"].as_u64(), packet["ack_bits"].as_u64().unwrap_or(0)) else {
+                    return;
+                };
+                self.pending_keepalives.retain(|seq, sent| {
+                    let acked = *seq == ack || (*seq < ack && ack - seq <= 32 && ack_bits & (1 << (ack - seq - 1)) != 0);
+                    if acked {
+                        self.stats.round_trips_ms.push(now.duration_since(*sent).as_secs_f64() * 1000.0);
+                    }
+                    !acked
+                });
+            }
+            Some("player_respawned") => {
+                let Some(position) = vector(&packet["position"]) else {
+                    return;
+                };
+                match player_id {
+                    Some(id) if id == self.id => {
+                        self.position = position;
+                        self.reloading = false;
+                        self.ammo = packet["ammo"].as_u64().map(|ammo| ammo as u32).or(self.ammo);
+                    }
+                    Some(id) => {
+                        self.others.insert(id, position);
+                    }
+                    None => {}
+                }
+            }
+            Some("positions") => {
+                for player in packet["players"].as_array().into_iter().flatten() {
+                    if let (Some(id), Some(position)) = (player["player_id"].as_u64(), vector(&player["position"])) {
+                        self.others.insert(id as u32, position);
+                    }
+                }
+            }
+            Some("player_list") => {
+                for player in packet["players"].as_array().into_iter().flatten() {
+                    if let (Some(id), Some(position)) = (player["id"].as_u64(), vector(&player["position"])) {
+                        self.others.insert(id as u32, position);
+                    }
+                }
+            }
+            Some("player_state_update") if player_id == Some(self.id) => {
+                if let Some(ammo) = packet["ammo"].as_u64() {
+                    self.ammo = Some(ammo as u32);
+                }
+            }
+            Some("reload_finished") if player_id == Some(self.id) => self.reloading = false,
+            Some("player_died") => {
+                let victim = packet["victim_id"].as_u64().map(|id| id as u32);
+                if packet["killer_id"].as_u64() == Some(self.id as u64) && victim != Some(self.id) {
+                    self.stats.kills += 1;
+                }
+                match victim {
+                    Some(id) if id == self.id => self.stats.deaths += 1,
+                    Some(id) => {
+                        self.others.remove(&id);
+                    }
+                    None => {}
+                }
+            }
+            Some("player_left") => {
+                if let Some(id) = player_id {
+                    self.others.remove(&id);
+                }
+            }
+            Some("kicked") => self.stats.kicked = true,
+            _ => {}
+        }
+        self.stats.events_missed = self.events.missed();
+    }
+
+    /// Send a command stamped with the bot's id and clock
+    async fn send(&mut self, socket: &UdpSocket, mut packet: Value) {
+        packet["player_id"] = json!(self.id);
+        packet["client_time"] = json!(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0));
+        if socket.send(packet.to_string().as_bytes()).await.is_ok() {
+            self.stats.packets_sent += 1;
+        }
+    }
+}
+
+/// Event sequence numbers seen so far, to count the ones that never arrived
+#[derive(Debug, Default)]
+struct SeqTracker {
+    latest: u64,
+    window: u64, // Bit i set = `latest - 1 - i` received
+    received: u64,
+}
+
+impl SeqTracker {
+    /// Record a sequence number; false for duplicates and ones too far behind to tell
+    fn receive(&mut self, seq: u64) -> bool {
+        if seq > self.latest {
+            let shift = seq - self.latest;
+            self.window = if shift > SEQ_WINDOW { 0 } else { self.window.checked_shl(shift as u32).unwrap_or(0) | 1 << (shift - 1) };
+            self.latest = seq;
+        } else {
+            let behind = self.latest - seq;
+            if behind == 0 || behind > SEQ_WINDOW || self.window & 1 << (behind - 1) != 0 {
+                return false;
+            }
+            self.window |= 1 << (behind - 1);
+        }
+        self.received += 1;
+        true
+    }
+
+    /// Sequence numbers up to the newest one that haven't arrived
+    fn missed(&self) -> u64 {
+        self.latest.saturating_sub(self.received)
+    }
+}
+
+/// xorshift64*, seeded per bot so a run can be repeated
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn vector(value: &Value) -> Option<(f32, f32, f32)> {
+    Some((value["x"].as_f64()? as f32, value["y"].as_f64().unwrap_or(0.0) as f32, value["z"].as_f64()? as f32))
+}
+
+fn distance(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_events_are_counted_once() {
+        let mut events = SeqTracker::default();
+        for seq in [1, 2, 4, 4, 7, 3] {
+            events.receive(seq);
+        }
+        assert_eq!((events.received, events.missed()), (5, 2)); // 5 and 6 never came
+        assert!(!events.receive(3));
+    }
+
+    #[test]
+    fn test_bot_shoots_players_in_range_and_reloads_when_empty() {
+        let start = Instant::now();
+        let mut bot = Bot::new(1, 7);
+        bot.receive(&json!({"type": "player_respawned", "player_id": 1, "position": {"x": 0.0, "y": 1.0, "z": 0.0}, "ammo": 2}), start);
+        bot.receive(&json!({"type": "positions", "players": [{"player_id": 2, "position": {"x": 0.0, "y": 1.0, "z": -10.0}}]}), start);
+
+        let packets = bot.step(0.05, start);
+        let shot = packets.iter().find(|p| p["type"] == "shoot").expect("a shot at player 2");
+        assert!(shot["direction"]["z"].as_f64().unwrap() < -0.9);
+        // Not again before the shot interval is up
+        assert!(bot.step(0.05, start + Duration::from_millis(100)).iter().all(|p| p["type"] != "shoot"));
+
+        bot.receive(&json!({"type": "player_state_update", "player_id": 1, "ammo": 0}), start);
+        let later = start + Duration::from_secs(1);
+        assert!(bot.step(0.05, later).iter().any(|p| p["type"] == "reload"));
+        assert!(bot.step(0.05, later).iter().all(|p| p["type"] != "reload" && p["type"] != "shoot"));
+
+        bot.receive(&json!({"type": "player_died", "killer_id": 1, "victim_id": 2}), later);
+        assert_eq!(bot.stats.kills, 1);
+        assert!(bot.others.is_empty());
+    }
+
+    #[test]
+    fn test_acked_keepalives_time_the_round_trip() {
+        let start = Instant::now();
+        let mut bot = Bot::new(1, 7);
+        for _ in 0..3 {
+            bot.keepalive(start);
+        }
+        // 3 and 1 acked (bit 1 = seq 1), 2 lost so far
+        bot.receive(&json!({"type": "ack", "ack": 3, "ack_bits": 2}), start + Duration::from_millis(40));
+        assert_eq!(bot.stats.round_trips_ms, vec![40.0, 40.0]);
+        assert_eq!(bot.pending_keepalives.keys().collect::<Vec<_>>(), vec![&2]);
+    }
+}
@@ -0,0 +1,62 @@
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Longest one request may take, connecting included
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// POST a JSON body to `server` (host:port); returns the status and the reply (a string when it isn't JSON)
+pub async fn post_json(server: &str, path: &str, body: &Value) -> Result<(u16, Value), String> {
+    tokio::time::timeout(REQUEST_TIMEOUT, post(server, path, body))
+        .await
+        .map_err(|_| format!("POST {} timed out", path))?
+}
+
+async fn post(server: &str, path: &str, body: &Value) -> Result<(u16, Value), String> {
+    let body = body.to_string();
+    let mut stream = TcpStream::connect(server).await.map_err(|e| format!("Can't reach {}: {}", server, e))?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        server,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.map_err(|e| format!("POST {} failed: {}", path, e))?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.map_err(|e| format!("POST {} failed: {}", path, e))?;
+    parse_response(&response)
+}
+
+/// Status and JSON body of an HTTP/1.1 response read up to the server closing the connection
+/// The server's JSON replies have a length, so chunked bodies aren't handled
+fn parse_response(response: &[u8]) -> Result<(u16, Value), String> {
+    let text = std::str::from_utf8(response).map_err(|_| "Response isn't UTF-8".to_string())?;
+    let (head, body) = text.split_once("\r\n\r\n").ok_or("Truncated response")?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or("Malformed status line")?;
+    let body = match body.trim() {
+        "" => Value::Null,
+        text => serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())),
+    };
+    Ok((status, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_response() {
+        let response = b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 15\r\n\r\n{\"player_id\":7}";
+        assert_eq!(parse_response(response), Ok((200, json!({"player_id": 7}))));
+        assert_eq!(parse_response(b"HTTP/1.1 409 Conflict\r\n\r\n"), Ok((409, Value::Null)));
+        assert_eq!(parse_response(b"HTTP/1.1 413 Payload Too Large\r\n\r\nToo big"), Ok((413, json!("Too big"))));
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+}
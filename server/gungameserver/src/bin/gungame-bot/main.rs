@@ -0,0 +1,225 @@
+//! Headless bot clients for load testing: each bot joins over HTTP and UDP like a game client,
+//! walks around, shoots whoever is near and keeps its session alive, measuring what it receives
+mod client;
+mod http;
+
+use std::time::Duration;
+use serde_json::json;
+use client::{BotConfig, BotStats};
+
+const USAGE: &str = "Usage: gungame-bot [--server host:port] [--bots N] [--lobby-size N] [--lobby-prefix CODE] \
+                     [--duration SECS] [--rate HZ] [--ramp-ms MS] [--seed N]";
+
+/// Options for a load test
+#[derive(Debug, Clone, PartialEq)]
+struct LoadTestOptions {
+    server: String, // HTTP address of the game server
+    bots: u32,
+    lobby_size: u32, // Bots per lobby; lobbies are created as needed
+    lobby_prefix: String, // Lobbies are `<prefix>1`, `<prefix>2`...
+    duration: Duration, // How long each bot plays
+    rate_hz: u32, // Position updates per second per bot
+    ramp: Duration, // Between bot starts, so the joins don't all land in one tick
+    seed: u64,
+}
+
+impl Default for LoadTestOptions {
+    fn default() -> Self {
+        Self {
+            server: "127.0.0.1:8080".to_string(),
+            bots: 10,
+            lobby_size: 8,
+            lobby_prefix: "LOADTEST".to_string(),
+            duration: Duration::from_secs(60),
+            rate_hz: 20,
+            ramp: Duration::from_millis(20),
+            seed: 1,
+        }
+    }
+}
+
+impl LoadTestOptions {
+    /// Parse the flags in `USAGE`
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let value = iter.next().ok_or_else(|| format!("Missing value for {}", flag))?;
+            let number = || value.parse::<u64>().map_err(|_| format!("Invalid value for {}: {}", flag, value));
+            match flag.as_str() {
+                "--server" => options.server = value.clone(),
+                "--bots" => options.bots = number()? as u32,
+                "--lobby-size" => options.lobby_size = number()? as u32,
+                "--lobby-prefix" => options.lobby_prefix = value.clone(),
+                "--duration" => options.duration = Duration::from_secs(number()?),
+                "--rate" => options.rate_hz = number()? as u32,
+                "--ramp-ms" => options.ramp = Duration::from_millis(number()?),
+                "--seed" => options.seed = number()?,
+                _ => return Err(format!("Unknown flag: {}", flag)),
+            }
+        }
+        if options.bots == 0 || options.lobby_size == 0 || options.rate_hz == 0 {
+            return Err("--bots, --lobby-size and --rate must be at least 1".to_string());
+        }
+        Ok(options)
+    }
+
+    fn lobby_count(&self) -> u32 {
+        self.bots.div_ceil(self.lobby_size)
+    }
+
+    fn lobby_code(&self, lobby: u32) -> String {
+        format!("{}{}", self.lobby_prefix, lobby + 1)
+    }
+}
+
+/// Totals over every bot that got in
+#[derive(Debug, Default)]
+struct LoadTestReport {
+    joined: u32,
+    failures: Vec<String>,
+    kicked: u32,
+    totals: BotStats,
+}
+
+impl LoadTestReport {
+    fn add(&mut self, result: Result<BotStats, String>) {
+        let stats = match result {
+            Ok(stats) => stats,
+            Err(e) => {
+                self.failures.push(e);
+                return;
+            }
+        };
+        self.joined += 1;
+        self.kicked += stats.kicked as u32;
+        let totals = &mut self.totals;
+        totals.packets_sent += stats.packets_sent;
+        totals.packets_received += stats.packets_received;
+        totals.events_received += stats.events_received;
+        totals.events_missed += stats.events_missed;
+        totals.keepalives_sent += stats.keepalives_sent;
+        totals.keepalives_lost += stats.keepalives_lost;
+        totals.round_trips_ms.extend(stats.round_trips_ms);
+        totals.shots += stats.shots;
+        totals.kills += stats.kills;
+        totals.deaths += stats.deaths;
+    }
+
+    fn print(&mut self, options: &LoadTestOptions) {
+        let totals = &mut self.totals;
+        println!("Bots: {} of {} joined, {} kicked", self.joined, options.bots, self.kicked);
+        for failure in self.failures.iter().take(5) {
+            println!("  join failed: {}", failure);
+        }
+        println!("Packets: {} sent, {} received", totals.packets_sent, totals.packets_received);
+        println!(
+            "Events: {} received, {} missed ({:.2}% loss)",
+            totals.events_received,
+            totals.events_missed,
+            percent(totals.events_missed, totals.events_received + totals.events_missed)
+        );
+        println!(
+            "Keepalives: {} sent, {} unacked ({:.2}% loss)",
+            totals.keepalives_sent,
+            totals.keepalives_lost,
+            percent(totals.keepalives_lost, totals.keepalives_sent)
+        );
+        totals.round_trips_ms.sort_by(f64::total_cmp);
+        if let Some(max) = totals.round_trips_ms.last() {
+            println!(
+                "Round trip: p50 {:.1} ms, p95 {:.1} ms, p99 {:.1} ms, max {:.1} ms",
+                percentile(&totals.round_trips_ms, 0.50),
+                percentile(&totals.round_trips_ms, 0.95),
+                percentile(&totals.round_trips_ms, 0.99),
+                max
+            );
+        }
+        println!("Combat: {} shots, {} kills, {} deaths", totals.shots, totals.kills, totals.deaths);
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 { 0.0 } else { part as f64 * 100.0 / whole as f64 }
+}
+
+/// Value at `fraction` of a sorted, non-empty list
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    sorted[((sorted.len() - 1) as f64 * fraction).round() as usize]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let options = LoadTestOptions::from_args(&args).map_err(|e| format!("{}\n{}", e, USAGE))?;
+
+    for lobby in 0..options.lobby_count() {
+        let code = options.lobby_code(lobby);
+        let body = json!({"code": code, "max_players": options.lobby_size});
+        match http::post_json(&options.server, "/lobbies", &body).await? {
+            (200 | 201, _) => {}
+            (409, _) => println!("Lobby {} is left over from an earlier run; joining it as it is", code),
+            (status, reply) => return Err(format!("Can't create lobby {}: {} {}", code, status, reply).into()),
+        }
+    }
+    println!(
+        "Starting {} bots in {} lobbies on {} for {}s",
+        options.bots,
+        options.lobby_count(),
+        options.server,
+        options.duration.as_secs()
+    );
+
+    let mut bots = Vec::new();
+    for bot in 0..options.bots {
+        let config = BotConfig {
+            server: options.server.clone(),
+            lobby: options.lobby_code(bot / options.lobby_size),
+            name: format!("bot{}", bot + 1),
+            rate_hz: options.rate_hz,
+            duration: options.duration,
+            seed: options.seed.wrapping_add(bot as u64),
+        };
+        bots.push(tokio::spawn(client::run(config)));
+        tokio::time::sleep(options.ramp).await;
+    }
+
+    let mut report = LoadTestReport::default();
+    for bot in bots {
+        report.add(bot.await.unwrap_or_else(|e| Err(format!("Bot task failed: {}", e))));
+    }
+    report.print(&options);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_options_from_args() {
+        let options = LoadTestOptions::from_args(&args("--bots 250 --lobby-size 16 --duration 30 --server 10.0.0.5:9000")).unwrap();
+        assert_eq!((options.bots, options.lobby_size, options.duration.as_secs()), (250, 16, 30));
+        assert_eq!(options.server, "10.0.0.5:9000");
+        assert_eq!((options.lobby_count(), options.lobby_code(15).as_str()), (16, "LOADTEST16"));
+
+        assert!(LoadTestOptions::from_args(&args("--bots")).is_err());
+        assert!(LoadTestOptions::from_args(&args("--bots many")).is_err());
+        assert!(LoadTestOptions::from_args(&args("--rate 0")).is_err());
+        assert!(LoadTestOptions::from_args(&args("--speed 3")).is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!((percentile(&sorted, 0.5), percentile(&sorted, 0.99)), (51.0, 99.0));
+    }
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::simulate::{self, SimulationMode, SimulationOptions, SimulationReport, CHECKPOINT_TICKS};
+use crate::utils::config::Config;
+use crate::utils::weapondb::WeaponDb;
+
+/// Identifies a demo file (first field of the JSON document)
+pub const DEMO_FORMAT: &str = "gungame-demo";
+
+/// Bumped whenever a field changes meaning; older versions are refused, not guessed at
+pub const DEMO_VERSION: u32 = 1;
+
+/// Recorded result of the match, compared after re-simulation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DemoOutcome {
+    pub total_kills: u32,
+    pub kills_by_weapon: BTreeMap<String, u32>,
+    pub ladders_completed: u32,
+    pub hash: String, // Final state hash
+}
+
+/// Versioned, documented demo file (see docs/networking.md, "Match Demos")
+/// Matches are deterministic, so the demo stores the setup and outcome hashes instead of every frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Demo {
+    pub format: String,
+    pub version: u32,
+    pub match_id: String,
+    pub mode: String,
+    pub bots: u32,
+    pub ticks: u64,
+    pub tick_interval_ms: u64,
+    pub checkpoint_ticks: u64,
+    pub checkpoints: Vec<String>, // Hex state hashes, one per checkpoint_ticks ticks
+    pub outcome: DemoOutcome,
+}
+
+fn hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+impl Demo {
+    /// Convert a recorded match into the demo format
+    pub fn export(match_id: &str, options: &SimulationOptions, report: &SimulationReport, config: &Config) -> Self {
+        Self {
+            format: DEMO_FORMAT.to_string(),
+            version: DEMO_VERSION,
+            match_id: match_id.to_string(),
+            mode: options.mode.as_str().to_string(),
+            bots: options.bots,
+            ticks: options.ticks,
+            tick_interval_ms: config.tick_interval_ms(),
+            checkpoint_ticks: CHECKPOINT_TICKS,
+            checkpoints: report.checkpoints.iter().copied().map(hex).collect(),
+            outcome: DemoOutcome {
+                total_kills: report.total_kills,
+                kills_by_weapon: report.kills_by_weapon.clone(),
+                ladders_completed: report.matches_won,
+                hash: hex(report.final_hash),
+            },
+        }
+    }
+
+    /// Parse a demo, refusing other formats and versions
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let demo: Demo = serde_json::from_slice(bytes).map_err(|e| format!("Invalid demo: {}", e))?;
+        if demo.format != DEMO_FORMAT {
+            return Err(format!("Not a demo file (format {:?})", demo.format));
+        }
+        if demo.version != DEMO_VERSION {
+            return Err(format!("Unsupported demo version {} (expected {})", demo.version, DEMO_VERSION));
+        }
+        Ok(demo)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&bytes)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Simulation setup the demo was recorded with
+    pub fn options(&self) -> Result<SimulationOptions, String> {
+        let mode = SimulationMode::parse(&self.mode).ok_or_else(|| format!("Unknown mode in demo: {}", self.mode))?;
+        Ok(SimulationOptions { bots: self.bots, mode, ticks: self.ticks })
+    }
+
+    /// Re-simulate the match and check every recorded hash matches
+    pub fn verify(&self, weapons: &WeaponDb) -> Result<(), String> {
+        if self.tick_interval_ms == 0 || self.checkpoint_ticks != CHECKPOINT_TICKS {
+            return Err("Demo was recorded with an incompatible tick setup".to_string());
+        }
+        let config = Config {
+            tick_rate_hz: (1000 / self.tick_interval_ms) as u32,
+            ..Config::default()
+        };
+        let options = self.options()?;
+        let report = simulate::run(&options, weapons, &config)?;
+        let replayed = Self::export(&self.match_id, &options, &report, &config);
+
+        if let Some(index) = self.checkpoints.iter().zip(&replayed.checkpoints).position(|(a, b)| a != b) {
+            return Err(format!("Desync at tick {}", (index as u64 + 1) * self.checkpoint_ticks));
+        }
+        if self.checkpoints.len() != replayed.checkpoints.len() {
+            return Err("Checkpoint count does not match the recorded tick count".to_string());
+        }
+        if self.outcome != replayed.outcome {
+            return Err("Recorded outcome does not match the re-simulated match".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Where a match's demo is stored, or None for ids that aren't plain uuids
+pub fn demo_path(dir: &str, match_id: &str) -> Option<PathBuf> {
+    let valid = !match_id.is_empty()
+        && match_id.len() <= 64
+        && match_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then(|| Path::new(dir).join(format!("{}.demo.json", match_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Demo {
+        let weapons = WeaponDb::load();
+        let config = Config::default();
+        let options = SimulationOptions { bots: 4, mode: SimulationMode::GunGame, ticks: 2_500 };
+        let report = simulate::run(&options, &weapons, &config).unwrap();
+        Demo::export("match-1", &options, &report, &config)
+    }
+
+    #[test]
+    fn test_export_roundtrip_and_verify() {
+        let demo = record();
+        assert_eq!(demo.format, DEMO_FORMAT);
+        assert_eq!(demo.checkpoints.len(), 2);
+        assert_eq!(demo.outcome.hash.len(), 16);
+
+        let bytes = serde_json::to_vec(&demo).unwrap();
+        let parsed = Demo::parse(&bytes).unwrap();
+        assert_eq!(parsed, demo);
+        assert!(parsed.verify(&WeaponDb::load()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let weapons = WeaponDb::load();
+
+        let mut demo = record();
+        demo.checkpoints[1] = hex(0);
+        assert_eq!(demo.verify(&weapons), Err("Desync at tick 2000".to_string()));
+
+        let mut demo = record();
+        demo.outcome.total_kills += 1;
+        assert!(demo.verify(&weapons).is_err());
+    }
+
+    #[test]
+    fn test_parse_refuses_other_versions() {
+        let mut demo = record();
+        demo.version = DEMO_VERSION + 1;
+        assert!(Demo::parse(&serde_json::to_vec(&demo).unwrap()).is_err());
+        assert!(Demo::parse(b"{\"format\": \"other\"}").is_err());
+    }
+
+    #[test]
+    fn test_demo_path_rejects_traversal() {
+        assert_eq!(demo_path("demos", "abc-123"), Some(PathBuf::from("demos/abc-123.demo.json")));
+        assert_eq!(demo_path("demos", "../secret"), None);
+        assert_eq!(demo_path("demos", ""), None);
+    }
+}
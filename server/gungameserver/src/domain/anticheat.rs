@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Instant;
+use crate::domain::simulator;
+use crate::state::lobby::Lobby;
+use crate::utils::weapondb::{WeaponData, WeaponDb};
+
+/// Shots of a burst checked together against the recoil pattern
+const RECOIL_WINDOW_SHOTS: usize = 5;
+
+/// Shots further apart than this many fire intervals start a new burst
+const BURST_GAP_INTERVALS: f32 = 1.5;
+
+/// Aim changes below this (degrees) between every shot of a window count as no movement at all
+const NO_RECOIL_EPSILON_DEG: f32 = 0.05;
+
+/// Shots kept per player for reports
+const SHOT_AUDIT_LEN: usize = 50;
+
+/// A hit on a target outside the attested FOV, turned onto faster than this, couldn't have been seen first
+const FOV_REACTION_MS: u128 = 150;
+
+/// A hit this close (degrees) to the target's center after a turn of at least `AIM_SNAP_MIN_DEG` is a snap
+const AIM_LOCK_DEG: f32 = 0.5;
+const AIM_SNAP_MIN_DEG: f32 = 10.0;
+
+/// Snaps in a row flagged as aim assistance from a client that attested none
+const AIM_SNAP_STREAK: u32 = 3;
+
+/// Kinds of suspicious client behaviour the server detects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    ClockManipulation, // Timestamps ahead of or drifting from the client clock
+    NoRecoil, // Aim perfectly still through an automatic burst
+    RecoilEnvelope, // Aim climb across a burst far from the weapon's recoil
+    FailedChallenge, // Verification challenge answered wrong, late or not at all
+    FovMismatch, // Hit a target outside the attested FOV before it could have been seen
+    AimAssist, // Aim snapping onto targets from a client that attested no aim assist
+}
+
+impl ViolationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ViolationKind::ClockManipulation => "clock_manipulation",
+            ViolationKind::NoRecoil => "no_recoil",
+            ViolationKind::RecoilEnvelope => "recoil_envelope",
+            ViolationKind::FailedChallenge => "failed_challenge",
+            ViolationKind::FovMismatch => "fov_mismatch",
+            ViolationKind::AimAssist => "aim_assist",
+        }
+    }
+}
+
+/// Violations flagged for one player
+#[derive(Debug, Clone, Default)]
+pub struct ViolationTracker {
+    counts: HashMap<ViolationKind, u32>,
+    pub last_at: Option<Instant>,
+}
+
+impl ViolationTracker {
+    /// Count a violation; returns how often this kind was flagged so far
+    pub fn record(&mut self, kind: ViolationKind, now: Instant) -> u32 {
+        self.last_at = Some(now);
+        let count = self.counts.entry(kind).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    pub fn count(&self, kind: ViolationKind) -> u32 {
+        self.counts.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.values().sum()
+    }
+
+    /// Count per kind name, for reports
+    pub fn summary(&self) -> BTreeMap<String, u32> {
+        self.counts.iter().map(|(kind, count)| (kind.as_str().to_string(), *count)).collect()
+    }
+}
+
+/// A fired shot as seen by the server
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditedShot {
+    pub at_ms: u64, // Unix time in milliseconds
+    pub weapon_id: u32,
+    pub target_id: Option<u32>,
+    pub hit: bool,
+    pub yaw: f32, // Aim in radians
+    pub pitch: f32,
+}
+
+/// A player's most recent shots, attached to reports about them
+#[derive(Debug, Clone, Default)]
+pub struct ShotAudit {
+    shots: VecDeque<AuditedShot>, // Oldest first
+}
+
+impl ShotAudit {
+    pub fn record(&mut self, shot: AuditedShot) {
+        if self.shots.len() == SHOT_AUDIT_LEN {
+            self.shots.pop_front();
+        }
+        self.shots.push_back(shot);
+    }
+
+    pub fn recent(&self) -> Vec<AuditedShot> {
+        self.shots.iter().cloned().collect()
+    }
+}
+
+/// Aim of the shots in the current burst of an automatic weapon
+#[derive(Debug, Clone, Default)]
+pub struct RecoilTracker {
+    weapon_id: u32,
+    last_shot: Option<Instant>,
+    aims: Vec<(f32, f32)>, // (yaw, pitch) in radians at each shot
+}
+
+impl RecoilTracker {
+    /// Take a fired shot's aim; checks each full window of a burst against the weapon's recoil
+    /// Weapons without a recoil pattern (semi-auto, melee) are not checked
+    pub fn observe(&mut self, weapon: &WeaponData, aim: (f32, f32), now: Instant) -> Result<(), ViolationKind> {
+        let Some(recoil) = &weapon.recoil else {
+            self.aims.clear();
+            return Ok(());
+        };
+
+        let max_gap = BURST_GAP_INTERVALS / weapon.fire_rate;
+        let same_burst = self.weapon_id == weapon.id
+            && self.last_shot.is_some_and(|last| {
+                now.saturating_duration_since(last).as_secs_f32() <= max_gap
+            });
+        if !same_burst {
+            self.aims.clear();
+        }
+        self.weapon_id = weapon.id;
+        self.last_shot = Some(now);
+        self.aims.push(aim);
+
+        if self.aims.len() < RECOIL_WINDOW_SHOTS {
+            return Ok(());
+        }
+        let window = std::mem::take(&mut self.aims);
+        self.aims.push(aim); // The next window continues from this shot
+
+        let deltas: Vec<(f32, f32)> = window
+            .windows(2)
+            .map(|pair| ((pair[1].0 - pair[0].0).to_degrees(), (pair[1].1 - pair[0].1).to_degrees()))
+            .collect();
+
+        let still = deltas
+            .iter()
+            .all(|(yaw, pitch)| yaw.abs() < NO_RECOIL_EPSILON_DEG && pitch.abs() < NO_RECOIL_EPSILON_DEG);
+        if still {
+            return Err(ViolationKind::NoRecoil);
+        }
+
+        let climb_per_shot = deltas.iter().map(|(_, pitch)| pitch).sum::<f32>() / deltas.len() as f32;
+        if (climb_per_shot - recoil.kick_deg).abs() > recoil.tolerance_deg {
+            return Err(ViolationKind::RecoilEnvelope);
+        }
+        Ok(())
+    }
+}
+
+/// Competitive integrity settings of a lobby, which clients attest to at join
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntegrityRules {
+    pub max_fov: f32, // Widest client field of view allowed, in degrees
+    pub aim_assist: bool, // Whether clients may use aim assistance
+}
+
+impl Default for IntegrityRules {
+    fn default() -> Self {
+        Self { max_fov: 110.0, aim_assist: false }
+    }
+}
+
+/// Settings a client attests to when joining
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClientSettings {
+    pub fov: f32, // Field of view in degrees
+    #[serde(default)]
+    pub aim_assist: bool,
+}
+
+/// Aim the client reported last, and how its hits line up with it
+#[derive(Debug, Clone, Default)]
+pub struct AimTracker {
+    last_aim: Option<((f32, f32), Instant)>, // (yaw, pitch) in radians and when it was reported
+    snaps: u32, // Hits in a row that snapped dead onto their target
+}
+
+impl AimTracker {
+    pub fn note(&mut self, aim: (f32, f32), now: Instant) {
+        self.last_aim = Some((aim, now));
+    }
+
+    /// Take a hit at `aim`, `offset_deg` from the target's center, against the attested settings
+    pub fn observe_hit(&mut self, settings: &ClientSettings, aim: (f32, f32), offset_deg: f32, now: Instant) -> Result<(), ViolationKind> {
+        let previous = self.last_aim.replace((aim, now));
+        let Some((last, at)) = previous else {
+            return Ok(());
+        };
+        let turn_deg = turn_between(last, aim).to_degrees();
+
+        if turn_deg > settings.fov / 2.0 && now.saturating_duration_since(at).as_millis() < FOV_REACTION_MS {
+            self.snaps = 0;
+            return Err(ViolationKind::FovMismatch);
+        }
+
+        if settings.aim_assist || turn_deg < AIM_SNAP_MIN_DEG || offset_deg > AIM_LOCK_DEG {
+            self.snaps = 0;
+            return Ok(());
+        }
+        self.snaps += 1;
+        if self.snaps >= AIM_SNAP_STREAK {
+            self.snaps = 0;
+            return Err(ViolationKind::AimAssist);
+        }
+        Ok(())
+    }
+}
+
+/// Angle in radians between two (yaw, pitch) aims
+fn turn_between(from: (f32, f32), to: (f32, f32)) -> f32 {
+    let a = simulator::facing_direction(from.0, from.1);
+    let b = simulator::facing_direction(to.0, to.1);
+    (a.0 * b.0 + a.1 * b.1 + a.2 * b.2).clamp(-1.0, 1.0).acos()
+}
+
+/// Check a joining client's settings against the lobby's rules and keep them on the player
+pub fn attest(lobby: &mut Lobby, player_id: u32, settings: ClientSettings) -> Result<(), &'static str> {
+    let rules = lobby.rules.integrity;
+    if !settings.fov.is_finite() || settings.fov <= 0.0 {
+        return Err("Invalid FOV");
+    }
+    if settings.fov > rules.max_fov {
+        return Err("FOV above the lobby's limit");
+    }
+    if settings.aim_assist && !rules.aim_assist {
+        return Err("Aim assist not allowed in this lobby");
+    }
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    player.attested = Some(settings);
+    Ok(())
+}
+
+/// Remember the aim a client reported (from position updates and shots)
+pub fn observe_aim(lobby: &mut Lobby, player_id: u32, now: Instant) {
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        let aim = (player.yaw(), player.pitch());
+        player.aim.note(aim, now);
+    }
+}
+
+/// Check a hit fired from `origin` at the shooter's current aim against their attested settings
+/// Players who attested nothing are not checked; a violation is recorded on the player and returned
+pub fn observe_hit(
+    lobby: &mut Lobby,
+    player_id: u32,
+    origin: (f32, f32, f32),
+    target_position: (f32, f32, f32),
+    now: Instant,
+) -> Option<ViolationKind> {
+    let player = lobby.players.get_mut(&player_id)?;
+    let aim = (player.yaw(), player.pitch());
+    let Some(settings) = player.attested else {
+        player.aim.note(aim, now);
+        return None;
+    };
+    let facing = simulator::facing_direction(aim.0, aim.1);
+    let offset_deg = simulator::aim_angle(facing, origin, target_position).unwrap_or(0.0).to_degrees();
+    let kind = player.aim.observe_hit(&settings, aim, offset_deg, now).err()?;
+    player.violations.record(kind, now);
+    Some(kind)
+}
+
+/// Check a fired shot against the shooter's recoil pattern at their current aim
+/// A violation is recorded on the player and returned
+pub fn observe_shot(lobby: &mut Lobby, weapons: &WeaponDb, player_id: u32, now: Instant) -> Option<ViolationKind> {
+    let player = lobby.players.get_mut(&player_id)?;
+    let weapon = weapons.get(player.current_weapon_id)?;
+    let aim = (player.yaw(), player.pitch());
+    let kind = player.recoil.observe(weapon, aim, now).err()?;
+    player.violations.record(kind, now);
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn burst(weapon: &WeaponData, pitches_deg: &[f32]) -> Vec<Result<(), ViolationKind>> {
+        let mut tracker = RecoilTracker::default();
+        let start = crate::testutil::epoch() + Duration::from_secs(100);
+        let interval = Duration::from_secs_f32(1.0 / weapon.fire_rate);
+        pitches_deg
+            .iter()
+            .enumerate()
+            .map(|(i, pitch)| tracker.observe(weapon, (0.0, pitch.to_radians()), start + interval * i as u32))
+            .collect()
+    }
+
+    #[test]
+    fn test_burst_following_recoil_passes() {
+        let weapons = WeaponDb::load();
+        let weapon = weapons.get(1).unwrap();
+        let kick = weapon.recoil.as_ref().unwrap().kick_deg;
+        // Climbs by the kick with some human wobble
+        let pitches: Vec<f32> = (0..9).map(|i| i as f32 * kick + if i % 2 == 0 { 0.3 } else { -0.2 }).collect();
+        assert!(burst(weapon, &pitches).iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn test_perfectly_still_aim_is_flagged() {
+        let weapons = WeaponDb::load();
+        let results = burst(weapons.get(1).unwrap(), &[2.0; 5]);
+        assert_eq!(results.last(), Some(&Err(ViolationKind::NoRecoil)));
+    }
+
+    #[test]
+    fn test_climb_outside_envelope_is_flagged() {
+        let weapons = WeaponDb::load();
+        // Pulled down hard on every shot
+        let pitches: Vec<f32> = (0..5).map(|i| i as f32 * -3.0).collect();
+        assert_eq!(burst(weapons.get(1).unwrap(), &pitches).last(), Some(&Err(ViolationKind::RecoilEnvelope)));
+    }
+
+    #[test]
+    fn test_semi_auto_weapons_and_pauses_are_not_checked() {
+        let weapons = WeaponDb::load();
+        assert!(burst(weapons.get(2).unwrap(), &[2.0; 6]).iter().all(|r| r.is_ok()));
+
+        // A pause between shots starts a new burst
+        let weapon = weapons.get(1).unwrap();
+        let mut tracker = RecoilTracker::default();
+        let start = crate::testutil::epoch();
+        for i in 0..8u64 {
+            assert!(tracker.observe(weapon, (0.0, 0.0), start + Duration::from_secs(i)).is_ok());
+        }
+
+        let mut violations = ViolationTracker::default();
+        violations.record(ViolationKind::NoRecoil, start);
+        violations.record(ViolationKind::NoRecoil, start);
+        assert_eq!(violations.count(ViolationKind::NoRecoil), 2);
+        assert_eq!(violations.total(), 2);
+    }
+
+    #[test]
+    fn test_hit_outside_attested_fov_is_flagged() {
+        let settings = ClientSettings { fov: 90.0, aim_assist: false };
+        let start = crate::testutil::epoch();
+        let mut tracker = AimTracker::default();
+        tracker.note((0.0, 0.0), start);
+        // Turned 120 degrees and hit within 50ms
+        let hit = tracker.observe_hit(&settings, (120f32.to_radians(), 0.0), 2.0, start + Duration::from_millis(50));
+        assert_eq!(hit, Err(ViolationKind::FovMismatch));
+
+        // The same turn taken at a human pace is fine
+        tracker.note((0.0, 0.0), start);
+        assert!(tracker.observe_hit(&settings, (120f32.to_radians(), 0.0), 2.0, start + Duration::from_millis(400)).is_ok());
+    }
+
+    #[test]
+    fn test_snapping_hits_are_flagged_without_aim_assist() {
+        let start = crate::testutil::epoch();
+        let snaps = |settings: ClientSettings| {
+            let mut tracker = AimTracker::default();
+            tracker.note((0.0, 0.0), start);
+            (1..=AIM_SNAP_STREAK)
+                .map(|i| {
+                    let yaw = if i % 2 == 0 { 0.0 } else { 20f32.to_radians() };
+                    tracker.observe_hit(&settings, (yaw, 0.0), 0.1, start + Duration::from_secs(i as u64))
+                })
+                .collect::<Vec<_>>()
+        };
+        let results = snaps(ClientSettings { fov: 90.0, aim_assist: false });
+        assert_eq!(results.last(), Some(&Err(ViolationKind::AimAssist)));
+        assert!(snaps(ClientSettings { fov: 90.0, aim_assist: true }).iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn test_attest_checks_lobby_rules() {
+        use crate::testutil::{LobbyBuilder, PlayerBuilder};
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        lobby.rules.integrity = IntegrityRules { max_fov: 100.0, aim_assist: false };
+
+        assert!(attest(&mut lobby, 1, ClientSettings { fov: 120.0, aim_assist: false }).is_err());
+        assert!(attest(&mut lobby, 1, ClientSettings { fov: 90.0, aim_assist: true }).is_err());
+        assert!(attest(&mut lobby, 1, ClientSettings { fov: f32::NAN, aim_assist: false }).is_err());
+        assert!(lobby.players[&1].attested.is_none());
+
+        let settings = ClientSettings { fov: 90.0, aim_assist: false };
+        assert!(attest(&mut lobby, 1, settings).is_ok());
+        assert_eq!(lobby.players[&1].attested, Some(settings));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::domain::{lobbies, logic, pickups, simulator, teams};
+use crate::state::lobby::{Lobby, Player};
+use crate::utils::weapondb::WeaponDb;
+use std::time::Instant;
+
+/// Below this fraction of max health a bot goes for a health pickup
+const LOW_HEALTH_FRACTION: f32 = 0.35;
+
+/// At or below this fraction of the magazine a bot reloads when nobody can see it
+const LOW_AMMO_FRACTION: f32 = 0.25;
+
+/// How far a bot looks for a spot hidden from its attacker
+const COVER_SEARCH_RADIUS: f32 = 8.0;
+const COVER_SEARCH_DIRECTIONS: usize = 8;
+
+/// Per-bot settings, kept in the lobby next to the bot's Player entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BotBrain {
+    pub primary_weapon_id: u32, // Weapon to return to after using the knife
+    pub melee_switching: bool, // Allowed to pull the knife at close range
+}
+
+/// What a bot decided to do this tick
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotAction {
+    Idle,
+    MoveTo((f32, f32, f32)),
+    SeekPickup { pickup_id: u32, position: (f32, f32, f32) },
+    TakeCover((f32, f32, f32)),
+    Reload,
+    SwitchWeapon(u32),
+    Attack { target_id: u32 },
+}
+
+fn is_alive(player: &Player) -> bool {
+    !player.is_dead && player.current_health > 0
+}
+
+/// Closest living opponent
+fn nearest_enemy<'a>(lobby: &'a Lobby, bot: &Player) -> Option<&'a Player> {
+    lobby
+        .players
+        .values()
+        .filter(|p| p.id != bot.id && is_alive(p) && !p.is_loading() && !teams::are_teammates(lobby, bot.id, p.id))
+        .min_by(|a, b| {
+            simulator::distance(bot.position, a.position)
+                .total_cmp(&simulator::distance(bot.position, b.position))
+                .then(a.id.cmp(&b.id))
+        })
+}
+
+/// Closest living opponent that can see `position` and reach it with their weapon
+fn nearest_threat<'a>(lobby: &'a Lobby, weapons: &WeaponDb, bot_id: u32, position: (f32, f32, f32)) -> Option<&'a Player> {
+    lobby
+        .players
+        .values()
+        .filter(|p| p.id != bot_id && is_alive(p) && !p.is_loading() && !teams::are_teammates(lobby, bot_id, p.id))
+        .filter(|p| {
+            let range = weapons.get(p.current_weapon_id).map(|w| w.range).unwrap_or(0.0);
+            simulator::distance(p.position, position) <= range
+                && simulator::check_line_of_sight(&lobby.collision, p.position, position)
+        })
+        .min_by(|a, b| {
+            simulator::distance(position, a.position)
+                .total_cmp(&simulator::distance(position, b.position))
+                .then(a.id.cmp(&b.id))
+        })
+}
+
+/// Keep a destination inside the lobby's map bounds
+fn clamp_to_bounds(lobby: &Lobby, position: (f32, f32, f32)) -> (f32, f32, f32) {
+    match &lobby.bounds {
+        Some(bounds) => bounds.clamp_point(position),
+        None => position,
+    }
+}
+
+/// Find a nearby spot the threat has no line of sight to
+/// Falls back to backing straight away from the threat
+fn find_cover(lobby: &Lobby, from: (f32, f32, f32), threat: (f32, f32, f32)) -> (f32, f32, f32) {
+    for radius in [COVER_SEARCH_RADIUS / 2.0, COVER_SEARCH_RADIUS] {
+        for i in 0..COVER_SEARCH_DIRECTIONS {
+            let angle = i as f32 * std::f32::consts::TAU / COVER_SEARCH_DIRECTIONS as f32;
+            let candidate = clamp_to_bounds(
+                lobby,
+                (from.0 + radius * angle.cos(), from.1, from.2 + radius * angle.sin()),
+            );
+            if !simulator::check_line_of_sight(&lobby.collision, threat, candidate) {
+                return candidate;
+            }
+        }
+    }
+
+    let away = (from.0 - threat.0, from.2 - threat.2);
+    let length = (away.0 * away.0 + away.1 * away.1).sqrt().max(f32::EPSILON);
+    clamp_to_bounds(
+        lobby,
+        (
+            from.0 + away.0 / length * COVER_SEARCH_RADIUS,
+            from.1,
+            from.2 + away.1 / length * COVER_SEARCH_RADIUS,
+        ),
+    )
+}
+
+/// Decide what a bot should do this tick
+///
+/// Priorities: heal when low, knife at close range, reload only when unseen
+/// (otherwise run for cover if the magazine is empty), shoot visible enemies
+/// in range, and otherwise close the distance.
+pub fn decide(lobby: &Lobby, weapons: &WeaponDb, bot_id: u32, now: Instant) -> BotAction {
+    let bot = match lobby.players.get(&bot_id) {
+        Some(bot) if is_alive(bot) => bot,
+        _ => return BotAction::Idle,
+    };
+    let weapon = match weapons.get(bot.current_weapon_id) {
+        Some(weapon) => weapon,
+        None => return BotAction::Idle,
+    };
+    let brain = lobby
+        .bots
+        .get(&bot_id)
+        .copied()
+        .unwrap_or(BotBrain { primary_weapon_id: bot.current_weapon_id, melee_switching: true });
+
+    let enemy = nearest_enemy(lobby, bot);
+    let enemy_distance = enemy.map(|e| simulator::distance(bot.position, e.position));
+
+    if (bot.current_health as f32) < bot.max_health as f32 * LOW_HEALTH_FRACTION {
+        if let Some(pickup) = pickups::nearest_available(lobby, bot.position, now) {
+            return BotAction::SeekPickup { pickup_id: pickup.id, position: pickup.position };
+        }
+    }
+
+    if let (Some(melee), Some(distance), true) = (weapons.melee_weapon(), enemy_distance, brain.melee_switching) {
+        if !weapon.is_melee() && distance <= melee.range {
+            return BotAction::SwitchWeapon(melee.id);
+        }
+        // Hysteresis so a bot doesn't flip weapons at the edge of knife range
+        if weapon.is_melee() && distance > melee.range * 2.0 && brain.primary_weapon_id != melee.id {
+            return BotAction::SwitchWeapon(brain.primary_weapon_id);
+        }
+    }
+
+    if !weapon.is_melee() {
+        let threat = nearest_threat(lobby, weapons, bot_id, bot.position);
+        let low_ammo = bot.current_ammo as f32 <= bot.max_ammo as f32 * LOW_AMMO_FRACTION;
+
+        match threat {
+            None if low_ammo && !bot.is_reloading => return BotAction::Reload,
+            Some(threat) if bot.current_ammo == 0 || bot.is_reloading => {
+                return BotAction::TakeCover(find_cover(lobby, bot.position, threat.position));
+            }
+            _ => {}
+        }
+    }
+
+    if let (Some(enemy), Some(distance)) = (enemy, enemy_distance) {
+        let can_fire = !bot.is_reloading && (weapon.is_melee() || bot.current_ammo > 0);
+        if can_fire
+            && distance <= weapon.range
+            && simulator::check_line_of_sight(&lobby.collision, bot.position, enemy.position)
+        {
+            return BotAction::Attack { target_id: enemy.id };
+        }
+        return BotAction::MoveTo(enemy.position);
+    }
+
+    BotAction::Idle
+}
+
+/// Move a bot towards `destination` by one tick's worth of travel, facing where it goes
+fn move_towards(lobby: &mut Lobby, bot_id: u32, destination: (f32, f32, f32), dt: f32) -> Result<(), &'static str> {
+    let bot = lobby.players.get(&bot_id).ok_or("Bot not found")?;
+    let destination = lobby.dimensions.flatten(clamp_to_bounds(lobby, destination));
+    let remaining = simulator::distance(bot.position, destination);
+    let step = (lobby.tunables.bot_move_speed * dt).min(remaining);
+    if step <= f32::EPSILON {
+        return Ok(());
+    }
+
+    let t = step / remaining;
+    let position = clamp_to_bounds(
+        lobby,
+        (
+            bot.position.0 + (destination.0 - bot.position.0) * t,
+            bot.position.1 + (destination.1 - bot.position.1) * t,
+            bot.position.2 + (destination.2 - bot.position.2) * t,
+        ),
+    );
+    let (yaw, _) = simulator::yaw_pitch_towards(bot.position, destination).unwrap_or((bot.yaw(), 0.0));
+    lobbies::update_position(lobby, bot_id, position, Player::rotation_from_yaw_pitch(yaw, 0.0))
+}
+
+/// Run one tick of bot AI: decide, then apply the action through the same
+/// domain rules human players go through
+pub fn step(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    bot_id: u32,
+    dt: f32,
+    now: Instant,
+) -> Result<BotAction, &'static str> {
+    let action = decide(lobby, weapons, bot_id, now);
+
+    match &action {
+        BotAction::Idle => {}
+        BotAction::MoveTo(destination) | BotAction::TakeCover(destination) => {
+            move_towards(lobby, bot_id, *destination, dt)?;
+        }
+        BotAction::SeekPickup { pickup_id, position } => {
+            move_towards(lobby, bot_id, *position, dt)?;
+            // Ignore "out of reach" while still walking there
+            let _ = pickups::collect(lobby, weapons, bot_id, *pickup_id, now);
+        }
+        BotAction::Reload => logic::start_reload_at(lobby, weapons, bot_id, now)?,
+        BotAction::SwitchWeapon(weapon_id) => logic::switch_weapon(lobby, weapons, bot_id, *weapon_id)?,
+        BotAction::Attack { target_id } => {
+            let (position, target) = {
+                let bot = lobby.players.get(&bot_id).ok_or("Bot not found")?;
+                let target = lobby.players.get(target_id).ok_or("Target not found")?;
+                (bot.position, target.position)
+            };
+            if let Some((yaw, pitch)) = simulator::yaw_pitch_towards(position, target) {
+                lobbies::update_position(lobby, bot_id, position, Player::rotation_from_yaw_pitch(yaw, pitch))?;
+            }
+            if logic::try_shoot_at(lobby, weapons, bot_id, now)? {
+                let hit = logic::validate_hit(lobby, weapons, bot_id, *target_id).is_ok();
+                logic::record_shot(lobby, bot_id, Some(*target_id), hit);
+                if hit {
+                    // Bots aim at the body
+                    let distance = simulator::distance(position, target);
+                    logic::resolve_hit_at(lobby, weapons, bot_id, *target_id, simulator::HitZone::Body, distance, now)?;
+                }
+            }
+        }
+    }
+
+    // Bots never send heartbeats
+    if let Some(bot) = lobby.players.get_mut(&bot_id) {
+        bot.last_update = now;
+    }
+
+    Ok(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::collision::{Aabb, CollisionWorld};
+    use crate::domain::pickups::Pickup;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    const BOT: u32 = 1;
+    const ENEMY: u32 = 2;
+
+    fn setup(enemy_position: (f32, f32, f32)) -> (Lobby, WeaponDb) {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().id(ENEMY).position(enemy_position))
+            .build();
+        lobbies::add_bot(&mut lobby, BOT, "Bot".to_string(), &weapons).unwrap();
+        (lobby, weapons)
+    }
+
+    fn wall() -> CollisionWorld {
+        // Wall between the bot (z = 0) and an enemy at z = -20, spanning x in [-2, 2]
+        CollisionWorld { occluders: vec![Aabb { min: (-2.0, 0.0, -11.0), max: (2.0, 5.0, -10.0) }] }
+    }
+
+    #[test]
+    fn test_low_health_seeks_pickup() {
+        let (mut lobby, weapons) = setup((0.0, 1.0, -20.0));
+        lobby.players.get_mut(&BOT).unwrap().current_health = 20;
+        lobby.pickups.push(Pickup { id: 7, position: (1.0, 1.0, 0.0), heal_amount: 50, weapon_id: None, available_at: crate::testutil::epoch() });
+
+        let action = step(&mut lobby, &weapons, BOT, 1.0, Instant::now()).unwrap();
+        assert_eq!(action, BotAction::SeekPickup { pickup_id: 7, position: (1.0, 1.0, 0.0) });
+        // Close enough to walk onto it in one step and collect
+        assert_eq!(lobby.players.get(&BOT).unwrap().current_health, 70);
+    }
+
+    #[test]
+    fn test_reloads_only_when_unseen() {
+        let (mut lobby, weapons) = setup((0.0, 1.0, -20.0));
+        lobby.players.get_mut(&BOT).unwrap().current_ammo = 2;
+
+        // Enemy can see the bot: keep fighting
+        assert_eq!(decide(&lobby, &weapons, BOT, Instant::now()), BotAction::Attack { target_id: ENEMY });
+
+        // Wall in between: safe to reload
+        lobby.collision = wall();
+        assert_eq!(step(&mut lobby, &weapons, BOT, 0.02, Instant::now()).unwrap(), BotAction::Reload);
+        assert!(lobby.players.get(&BOT).unwrap().is_reloading);
+    }
+
+    #[test]
+    fn test_empty_and_exposed_takes_cover() {
+        let (mut lobby, weapons) = setup((0.0, 1.0, -20.0));
+        lobby.collision = CollisionWorld { occluders: vec![Aabb { min: (3.0, 0.0, -6.0), max: (6.0, 5.0, -5.0) }] };
+        lobby.players.get_mut(&BOT).unwrap().current_ammo = 0;
+
+        match decide(&lobby, &weapons, BOT, Instant::now()) {
+            BotAction::TakeCover(spot) => {
+                let enemy = lobby.players.get(&ENEMY).unwrap().position;
+                assert!(!simulator::check_line_of_sight(&lobby.collision, enemy, spot));
+            }
+            other => panic!("expected cover, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_switches_to_knife_and_back() {
+        let (mut lobby, weapons) = setup((0.0, 1.0, -2.0));
+        assert_eq!(step(&mut lobby, &weapons, BOT, 0.02, Instant::now()).unwrap(), BotAction::SwitchWeapon(3));
+        assert_eq!(decide(&lobby, &weapons, BOT, Instant::now()), BotAction::Attack { target_id: ENEMY });
+
+        lobby.players.get_mut(&ENEMY).unwrap().position = (0.0, 1.0, -30.0);
+        assert_eq!(decide(&lobby, &weapons, BOT, Instant::now()), BotAction::SwitchWeapon(1));
+    }
+
+    #[test]
+    fn test_attack_damages_target() {
+        let (mut lobby, weapons) = setup((0.0, 1.0, 20.0)); // Behind the bot: it turns to face
+        assert_eq!(step(&mut lobby, &weapons, BOT, 0.02, Instant::now()).unwrap(), BotAction::Attack { target_id: ENEMY });
+        assert_eq!(lobby.players.get(&ENEMY).unwrap().current_health, 80);
+    }
+
+    #[test]
+    fn test_chase_stays_in_bounds() {
+        let (mut lobby, weapons) = setup((0.0, 1.0, -500.0)); // Out of range
+        lobby.bounds = Some(Aabb { min: (-10.0, 0.0, -10.0), max: (10.0, 10.0, 10.0) });
+
+        for _ in 0..200 {
+            assert!(matches!(step(&mut lobby, &weapons, BOT, 0.1, Instant::now()).unwrap(), BotAction::MoveTo(_)));
+        }
+        let position = lobby.players.get(&BOT).unwrap().position;
+        assert!((position.2 + 10.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+use crate::domain::anticheat::ViolationKind;
+use crate::net::crypto;
+use crate::state::lobby::{Connection, Lobby};
+
+/// A signed nonce the client has to echo before `deadline`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub nonce: String,
+    pub signature: String, // Binds the nonce to the lobby's key and the player
+    pub deadline: Instant,
+}
+
+/// Verification challenges sent to one player
+#[derive(Debug, Clone, Default)]
+pub struct ChallengeState {
+    pub pending: Option<Challenge>,
+    pub next_at: Option<Instant>, // None until the first challenge is scheduled
+    pub passed: u32,
+    pub failed: u32,
+}
+
+/// Hex signature of a nonce for a player, keyed by the lobby's challenge key
+pub fn sign(key: &str, player_id: u32, nonce: &str) -> String {
+    let digest = crypto::sha1(format!("{}:{}:{}", key, player_id, nonce).as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn fail(lobby: &mut Lobby, player_id: u32, now: Instant) {
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.challenge.failed += 1;
+        player.violations.record(ViolationKind::FailedChallenge, now);
+    }
+}
+
+/// Fail unanswered challenges past their deadline and issue those that are due
+/// Only connected humans are challenged, every `challenge_interval_secs` (0 turns challenges off)
+/// Returns the challenges to send and the players that just failed one
+pub fn advance(lobby: &mut Lobby, now: Instant) -> (Vec<(u32, Challenge)>, Vec<u32>) {
+    let interval = Duration::from_secs(lobby.tunables.challenge_interval_secs);
+    let timeout = Duration::from_millis(lobby.tunables.challenge_timeout_ms);
+    let mut issued = Vec::new();
+    let mut failed = Vec::new();
+    if interval.is_zero() {
+        return (issued, failed);
+    }
+
+    let mut player_ids: Vec<u32> = lobby.players.keys().copied().filter(|id| !lobby.bots.contains_key(id)).collect();
+    player_ids.sort_unstable();
+    for player_id in player_ids {
+        let reachable = lobby.client_addresses.contains_key(&player_id);
+        let key = lobby.challenge_key.clone();
+        let Some(player) = lobby.players.get_mut(&player_id) else {
+            continue;
+        };
+        let state = &mut player.challenge;
+        // A client that went away can't answer; its challenge is dropped, not failed
+        if !reachable || player.connection != Connection::Connected {
+            state.pending = None;
+            continue;
+        }
+        if state.pending.as_ref().is_some_and(|c| now > c.deadline) {
+            state.pending = None;
+            state.next_at = Some(now + interval);
+            failed.push(player_id);
+            continue;
+        }
+        match state.next_at {
+            None => state.next_at = Some(now + interval),
+            Some(next_at) if now >= next_at && state.pending.is_none() => {
+                let nonce = crypto::generate_token();
+                let challenge = Challenge { signature: sign(&key, player_id, &nonce), nonce, deadline: now + timeout };
+                state.pending = Some(challenge.clone());
+                state.next_at = Some(now + interval);
+                issued.push((player_id, challenge));
+            }
+            Some(_) => {}
+        }
+    }
+    for player_id in &failed {
+        fail(lobby, *player_id, now);
+    }
+    (issued, failed)
+}
+
+/// Check a client's echo of its pending challenge; wrong or late answers count as failures
+pub fn answer(lobby: &mut Lobby, player_id: u32, nonce: &str, signature: &str, now: Instant) -> Result<(), &'static str> {
+    let key = lobby.challenge_key.clone();
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    let challenge = player.challenge.pending.take().ok_or("No challenge pending")?;
+
+    let result = if !crypto::tokens_match(nonce, &challenge.nonce) || !crypto::tokens_match(signature, &sign(&key, player_id, nonce)) {
+        Err("Wrong answer")
+    } else if now > challenge.deadline {
+        Err("Answered too late")
+    } else {
+        player.challenge.passed += 1;
+        Ok(())
+    };
+    if result.is_err() {
+        fail(lobby, player_id, now);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{epoch, LobbyBuilder, PlayerBuilder};
+    use std::sync::Arc;
+
+    fn lobby() -> Lobby {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().id(1)).build();
+        lobby.client_addresses.insert(1, "127.0.0.1:4001".parse().unwrap());
+        let tunables = Arc::make_mut(&mut lobby.tunables);
+        tunables.challenge_interval_secs = 30;
+        tunables.challenge_timeout_ms = 2000;
+        lobby
+    }
+
+    #[test]
+    fn test_challenges_are_issued_and_answered() {
+        let t0 = epoch();
+        let mut lobby = lobby();
+
+        // The first one is scheduled a full interval after joining
+        assert_eq!(advance(&mut lobby, t0), (vec![], vec![]));
+        let (issued, _) = advance(&mut lobby, t0 + Duration::from_secs(30));
+        let (player_id, challenge) = issued[0].clone();
+        assert_eq!(player_id, 1);
+        assert_eq!(challenge.signature, sign(&lobby.challenge_key, 1, &challenge.nonce));
+
+        let now = t0 + Duration::from_secs(31);
+        assert_eq!(answer(&mut lobby, 1, &challenge.nonce, &challenge.signature, now), Ok(()));
+        assert_eq!(answer(&mut lobby, 1, &challenge.nonce, &challenge.signature, now), Err("No challenge pending"));
+        assert_eq!(lobby.players[&1].challenge.passed, 1);
+        assert_eq!(lobby.players[&1].violations.total(), 0);
+    }
+
+    #[test]
+    fn test_wrong_late_and_missing_answers_fail() {
+        let t0 = epoch();
+        let mut lobby = lobby();
+        advance(&mut lobby, t0);
+
+        let (issued, _) = advance(&mut lobby, t0 + Duration::from_secs(30));
+        let challenge = issued[0].1.clone();
+        let forged = sign("guessed key", 1, &challenge.nonce);
+        assert_eq!(answer(&mut lobby, 1, &challenge.nonce, &forged, t0 + Duration::from_secs(31)), Err("Wrong answer"));
+
+        let (issued, _) = advance(&mut lobby, t0 + Duration::from_secs(60));
+        let challenge = issued[0].1.clone();
+        let late = t0 + Duration::from_secs(63);
+        assert_eq!(answer(&mut lobby, 1, &challenge.nonce, &challenge.signature, late), Err("Answered too late"));
+
+        // Never answered: fails once the deadline passes
+        advance(&mut lobby, t0 + Duration::from_secs(90));
+        assert_eq!(advance(&mut lobby, t0 + Duration::from_secs(93)), (vec![], vec![1]));
+
+        let player = &lobby.players[&1];
+        assert_eq!(player.challenge.failed, 3);
+        assert_eq!(player.violations.count(ViolationKind::FailedChallenge), 3);
+    }
+
+    #[test]
+    fn test_bots_and_disabled_lobbies_are_not_challenged() {
+        let t0 = epoch();
+        let mut lobby = lobby();
+        lobby.bots.insert(1, crate::domain::bot::BotBrain { primary_weapon_id: 1, melee_switching: true });
+        advance(&mut lobby, t0);
+        assert_eq!(advance(&mut lobby, t0 + Duration::from_secs(30)), (vec![], vec![]));
+
+        let mut lobby = self::lobby();
+        Arc::make_mut(&mut lobby.tunables).challenge_interval_secs = 0;
+        advance(&mut lobby, t0);
+        assert_eq!(advance(&mut lobby, t0 + Duration::from_secs(30)), (vec![], vec![]));
+    }
+}
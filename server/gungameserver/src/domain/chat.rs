@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crate::state::lobby::Lobby;
+
+/// Longest chat message in characters
+pub const MAX_CHAT_LENGTH: usize = 200;
+/// Messages a player may send within `CHAT_WINDOW`
+const CHAT_BURST: usize = 5;
+const CHAT_WINDOW: Duration = Duration::from_secs(10);
+
+/// A validated message ready to relay
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub player_id: u32,
+    pub name: String,
+    pub text: String,
+    pub team_id: Option<u32>, // Only this team receives it (team chat)
+}
+
+/// When a player's recent messages were sent
+#[derive(Debug, Clone, Default)]
+pub struct ChatLimiter {
+    sent: VecDeque<Instant>, // Oldest first, within the window
+}
+
+impl ChatLimiter {
+    /// Whether another message may go out now; counts it if so
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while self.sent.front().is_some_and(|sent| now.saturating_duration_since(*sent) >= CHAT_WINDOW) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= CHAT_BURST {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Moderation hook every message passes through before it is relayed
+/// Blocked words (case-insensitive, whole words) are masked with `*`
+#[derive(Debug, Clone, Default)]
+pub struct ChatFilter {
+    blocked: Vec<String>, // Lowercase
+}
+
+impl ChatFilter {
+    pub fn new(blocked: &[String]) -> Self {
+        Self { blocked: blocked.iter().map(|word| word.to_lowercase()).filter(|word| !word.is_empty()).collect() }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        if self.blocked.is_empty() {
+            return text.to_string();
+        }
+        let mut filtered = String::with_capacity(text.len());
+        let mut word = String::new();
+        let flush = |word: &mut String, filtered: &mut String| {
+            if self.blocked.contains(&word.to_lowercase()) {
+                filtered.extend(word.chars().map(|_| '*'));
+            } else {
+                filtered.push_str(word);
+            }
+            word.clear();
+        };
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut filtered);
+                filtered.push(c);
+            }
+        }
+        flush(&mut word, &mut filtered);
+        filtered
+    }
+}
+
+/// Validate, rate limit and filter a player's message
+/// `team_only` keeps it to the sender's team, and is refused in lobbies without teams
+pub fn send(
+    lobby: &mut Lobby,
+    filter: &ChatFilter,
+    player_id: u32,
+    text: &str,
+    team_only: bool,
+    now: Instant,
+) -> Result<ChatMessage, &'static str> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Empty message");
+    }
+    if text.chars().count() > MAX_CHAT_LENGTH {
+        return Err("Message too long");
+    }
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    let team_id = match (team_only, player.team_id) {
+        (false, _) => None,
+        (true, Some(team_id)) => Some(team_id),
+        (true, None) => return Err("No teams in this lobby"),
+    };
+    if !player.chat.allow(now) {
+        return Err("Sending too fast");
+    }
+    Ok(ChatMessage { player_id, name: player.name.clone(), text: filter.apply(text), team_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{epoch, LobbyBuilder, PlayerBuilder};
+
+    #[test]
+    fn test_send_validates_limits_and_filters() {
+        let t0 = epoch();
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().id(1)).build();
+        let filter = ChatFilter::new(&["darn".to_string()]);
+
+        let message = send(&mut lobby, &filter, 1, "  Darn, nice shot!darnit ", false, t0).unwrap();
+        assert_eq!(message, ChatMessage { player_id: 1, name: "Test".to_string(), text: "****, nice shot!darnit".to_string(), team_id: None });
+
+        assert_eq!(send(&mut lobby, &filter, 1, "   ", false, t0), Err("Empty message"));
+        assert_eq!(send(&mut lobby, &filter, 1, &"a".repeat(MAX_CHAT_LENGTH + 1), false, t0), Err("Message too long"));
+        assert_eq!(send(&mut lobby, &filter, 1, "gg", true, t0), Err("No teams in this lobby"));
+        assert_eq!(send(&mut lobby, &filter, 9, "gg", false, t0), Err("Player not found"));
+
+        for _ in 1..CHAT_BURST {
+            assert!(send(&mut lobby, &filter, 1, "gg", false, t0).is_ok());
+        }
+        assert_eq!(send(&mut lobby, &filter, 1, "gg", false, t0), Err("Sending too fast"));
+        assert!(send(&mut lobby, &filter, 1, "gg", false, t0 + CHAT_WINDOW).is_ok());
+
+        lobby.players.get_mut(&1).unwrap().team_id = Some(2);
+        assert_eq!(send(&mut lobby, &filter, 1, "push B", true, t0 + CHAT_WINDOW).unwrap().team_id, Some(2));
+    }
+}
@@ -0,0 +1,207 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Weight of a new drift measurement
+const SMOOTHING: f64 = 0.1;
+
+/// How fast the offset rises towards more delayed samples (route changes, clock corrections)
+const RISE: f64 = 0.01;
+
+/// A timestamp this far ahead of the estimated client clock can't come from network jitter
+const MAX_AHEAD_MS: f64 = 150.0;
+
+/// Offset change (ms per second) beyond which the client clock runs too fast or too slow
+/// Real clocks drift by well under 1 ms/s; speed hacks show up as tens to hundreds
+const MAX_DRIFT_MS_PER_SEC: f64 = 50.0;
+
+/// Drift is measured over at least this much server time
+const DRIFT_WINDOW_MS: u64 = 1_000;
+
+/// Drift measurements needed before drift is judged
+const MIN_DRIFT_WINDOWS: u32 = 3;
+
+/// Estimate of a client's clock, fed with the `client_time` of every command
+///
+/// The offset is server receive time minus client send time, so it includes the
+/// one-way network delay. It follows the lower envelope of the samples (the least
+/// delayed packets), so a command's `client_time + offset` is when it would have
+/// arrived without queueing delay or jitter.
+#[derive(Debug, Clone, Default)]
+pub struct ClientClock {
+    pub offset_ms: Option<f64>,
+    pub drift_ms_per_sec: f64, // Smoothed change of the offset; negative = client clock runs fast
+    pub samples: u64,
+    pub violations: u32, // Timestamps flagged as manipulated
+    drift_windows: u32,
+    window_start: Option<(u64, f64)>, // (server ms, offset) the current drift window started at
+}
+
+impl ClientClock {
+    /// Take a command's client timestamp received at `server_ms` (see `server_ms()`)
+    /// Errors (and counts a violation) when the timestamp looks manipulated
+    pub fn observe(&mut self, client_ms: u64, server_ms: u64) -> Result<(), &'static str> {
+        let sample = server_ms as f64 - client_ms as f64;
+        let Some(offset) = self.offset_ms else {
+            self.offset_ms = Some(sample);
+            self.window_start = Some((server_ms, sample));
+            self.samples = 1;
+            return Ok(());
+        };
+
+        if sample < offset - MAX_AHEAD_MS {
+            self.violations += 1;
+            return Err("Timestamp ahead of the client clock");
+        }
+
+        // Less delayed samples pull the offset down at once, more delayed ones slowly
+        let offset = if sample < offset { sample } else { offset + (sample - offset) * RISE };
+        self.offset_ms = Some(offset);
+        self.samples += 1;
+
+        if let Some((start_ms, start_offset)) = self.window_start {
+            let elapsed_ms = server_ms.saturating_sub(start_ms);
+            if elapsed_ms >= DRIFT_WINDOW_MS {
+                let rate = (offset - start_offset) / (elapsed_ms as f64 / 1000.0);
+                self.drift_ms_per_sec += (rate - self.drift_ms_per_sec) * SMOOTHING.max(1.0 / (self.drift_windows + 1) as f64);
+                self.drift_windows += 1;
+                self.window_start = Some((server_ms, offset));
+
+                if self.drift_windows >= MIN_DRIFT_WINDOWS && self.drift_ms_per_sec.abs() > MAX_DRIFT_MS_PER_SEC {
+                    self.violations += 1;
+                    // Start over so one episode is flagged once per few windows, not on every command
+                    self.drift_ms_per_sec = 0.0;
+                    self.drift_windows = 0;
+                    return Err("Client clock drifting");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Server time a client timestamp corresponds to (None before the first sample)
+    pub fn to_server_time(&self, client_ms: u64) -> Option<Instant> {
+        let server_ms = client_ms as f64 + self.offset_ms?;
+        (server_ms >= 0.0).then(|| server_start() + Duration::from_millis(server_ms as u64))
+    }
+}
+
+/// Where the simulation reads the time from: the system clock, or a `ManualClock` in tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to, so tests can skip fire-rate and reload waits
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    now: std::sync::Mutex<Instant>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(start: Instant) -> Self {
+        Self { now: std::sync::Mutex::new(start) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Start of the monotonic server clock (first use)
+fn server_start() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+    *START.get_or_init(Instant::now)
+}
+
+/// Milliseconds on the monotonic server clock (0 for times before it started)
+/// Unlike wall time it never jumps when the system clock is adjusted
+pub fn server_ms(time: Instant) -> u64 {
+    time.saturating_duration_since(server_start()).as_millis() as u64
+}
+
+/// Milliseconds since the epoch (0 for times before it)
+pub fn epoch_ms(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_START: u64 = 5_000; // Client clock reads 5s when the server reads 1_000_000ms
+    const SERVER_START: u64 = 1_000_000;
+
+    #[test]
+    fn test_offset_follows_least_delayed_packets() {
+        let mut clock = ClientClock::default();
+        // 40ms base delay with up to 30ms jitter, one command every 50ms
+        for i in 0..100u64 {
+            let jitter = (i * 7) % 31;
+            clock.observe(CLIENT_START + i * 50, SERVER_START + i * 50 + 40 + jitter).unwrap();
+        }
+        let offset = clock.offset_ms.unwrap();
+        assert!((offset - (SERVER_START - CLIENT_START + 40) as f64).abs() < 5.0, "offset {}", offset);
+        assert!(clock.drift_ms_per_sec.abs() < 5.0);
+        assert_eq!(clock.violations, 0);
+
+        let arrival = server_ms(clock.to_server_time(CLIENT_START + 1_000).unwrap());
+        assert!(arrival.abs_diff(SERVER_START + 1_040) < 5, "arrival {}", arrival);
+    }
+
+    #[test]
+    fn test_server_time_round_trips_through_the_monotonic_clock() {
+        let mut clock = ClientClock::default();
+        let now = server_ms(Instant::now());
+        clock.observe(CLIENT_START, now + 40).unwrap();
+        assert_eq!(server_ms(clock.to_server_time(CLIENT_START + 500).unwrap()), now + 540);
+    }
+
+    #[test]
+    fn test_manual_clock_moves_only_when_advanced() {
+        let start = Instant::now();
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::from_millis(250));
+        assert_eq!(clock.now(), start + Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_timestamp_from_the_future_is_flagged() {
+        let mut clock = ClientClock::default();
+        clock.observe(CLIENT_START, SERVER_START + 40).unwrap();
+        assert_eq!(
+            clock.observe(CLIENT_START + 1_000, SERVER_START + 90),
+            Err("Timestamp ahead of the client clock")
+        );
+        assert_eq!(clock.violations, 1);
+    }
+
+    #[test]
+    fn test_speed_hack_is_flagged_as_drift() {
+        let mut clock = ClientClock::default();
+        // Client clock runs 20% fast: 60ms pass on it for every 50ms on the server
+        let flagged = (0..200u64)
+            .map(|i| clock.observe(CLIENT_START + i * 60, SERVER_START + i * 50 + 40))
+            .any(|result| result == Err("Client clock drifting"));
+        assert!(flagged);
+        assert!(clock.violations > 0);
+    }
+}
@@ -0,0 +1,103 @@
+/// Axis-aligned box used as a world occluder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: (f32, f32, f32),
+    pub max: (f32, f32, f32),
+}
+
+impl Aabb {
+    /// Slab test: does the segment from `a` to `b` pass through the box?
+    pub fn intersects_segment(&self, a: (f32, f32, f32), b: (f32, f32, f32)) -> bool {
+        let origin = [a.0, a.1, a.2];
+        let dir = [b.0 - a.0, b.1 - a.1, b.2 - a.2];
+        let min = [self.min.0, self.min.1, self.min.2];
+        let max = [self.max.0, self.max.1, self.max.2];
+
+        let mut t_enter = 0.0f32;
+        let mut t_exit = 1.0f32;
+
+        for axis in 0..3 {
+            if dir[axis].abs() < f32::EPSILON {
+                // Parallel to this slab: must already be inside it
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return false;
+                }
+                continue;
+            }
+            let inv = 1.0 / dir[axis];
+            let mut t0 = (min[axis] - origin[axis]) * inv;
+            let mut t1 = (max[axis] - origin[axis]) * inv;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_enter = t_enter.max(t0);
+            t_exit = t_exit.min(t1);
+            if t_enter > t_exit {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Closest point inside the box
+    pub fn clamp_point(&self, p: (f32, f32, f32)) -> (f32, f32, f32) {
+        (
+            p.0.clamp(self.min.0, self.max.0),
+            p.1.clamp(self.min.1, self.max.1),
+            p.2.clamp(self.min.2, self.max.2),
+        )
+    }
+}
+
+/// Static collision geometry for a lobby's scene
+/// Empty by default (nothing blocks line of sight)
+#[derive(Debug, Clone, Default)]
+pub struct CollisionWorld {
+    pub occluders: Vec<Aabb>,
+}
+
+impl CollisionWorld {
+    /// Check whether any occluder blocks the segment between two points
+    pub fn segment_blocked(&self, from: (f32, f32, f32), to: (f32, f32, f32)) -> bool {
+        self.occluders.iter().any(|b| b.intersects_segment(from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall() -> Aabb {
+        // Thin wall across the Z axis at z = 5
+        Aabb { min: (-10.0, 0.0, 4.5), max: (10.0, 5.0, 5.5) }
+    }
+
+    #[test]
+    fn test_segment_through_wall() {
+        assert!(wall().intersects_segment((0.0, 1.0, 0.0), (0.0, 1.0, 10.0)));
+    }
+
+    #[test]
+    fn test_segment_misses_wall() {
+        // Over the top of the wall
+        assert!(!wall().intersects_segment((0.0, 6.0, 0.0), (0.0, 6.0, 10.0)));
+        // Stops short of the wall
+        assert!(!wall().intersects_segment((0.0, 1.0, 0.0), (0.0, 1.0, 4.0)));
+        // Parallel and beside it
+        assert!(!wall().intersects_segment((20.0, 1.0, 0.0), (20.0, 1.0, 10.0)));
+    }
+
+    #[test]
+    fn test_clamp_point() {
+        assert_eq!(wall().clamp_point((20.0, 1.0, 0.0)), (10.0, 1.0, 4.5));
+        assert_eq!(wall().clamp_point((0.0, 1.0, 5.0)), (0.0, 1.0, 5.0));
+    }
+
+    #[test]
+    fn test_world_blocking() {
+        let world = CollisionWorld { occluders: vec![wall()] };
+        assert!(world.segment_blocked((0.0, 1.0, 0.0), (0.0, 1.0, 10.0)));
+        assert!(!CollisionWorld::default().segment_blocked((0.0, 1.0, 0.0), (0.0, 1.0, 10.0)));
+    }
+}
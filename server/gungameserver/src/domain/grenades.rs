@@ -0,0 +1,249 @@
+use crate::domain::logic;
+use crate::domain::mutators;
+use crate::domain::simulator;
+use crate::domain::teams;
+use crate::state::lobby::{Dimensions, Lobby};
+use crate::utils::weapondb::WeaponDb;
+use std::time::{Duration, Instant};
+
+/// A thrown grenade, flying or resting until its fuse runs out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grenade {
+    pub id: u32,
+    pub owner_id: u32,
+    pub position: (f32, f32, f32),
+    pub velocity: (f32, f32, f32), // Units per second; zero once it came to rest against level geometry
+    pub gravity: f32,
+    pub detonates_at: Instant,
+}
+
+/// Grenade thrown or exploded since the tick loop last broadcast them
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrenadeEvent {
+    Thrown {
+        grenade_id: u32,
+        owner_id: u32,
+        position: (f32, f32, f32),
+        velocity: (f32, f32, f32),
+        gravity: f32,
+        fuse_ms: u64,
+    },
+    Exploded {
+        grenade_id: u32,
+        owner_id: u32,
+        position: (f32, f32, f32),
+        affected: Vec<(u32, u32)>, // (player id, damage dealt), nearest first
+    },
+}
+
+/// Throw a grenade from the player's hand along `direction`
+/// Players get `grenades_per_life` throws between respawns
+/// Returns the new grenade's id
+pub fn throw(
+    lobby: &mut Lobby,
+    owner_id: u32,
+    origin: (f32, f32, f32),
+    direction: (f32, f32, f32),
+    now: Instant,
+) -> Result<u32, &'static str> {
+    let tunables = lobby.tunables.clone();
+    let owner = lobby.players.get(&owner_id).ok_or("Player not found")?;
+    if owner.is_dead {
+        return Err("Player is dead");
+    }
+    if owner.grenades_thrown >= tunables.grenades_per_life {
+        return Err("No grenades left");
+    }
+    let direction = simulator::normalize(lobby.dimensions.flatten_direction(direction)).ok_or("Invalid direction")?;
+    let speed = tunables.grenade_throw_speed;
+
+    let grenade = Grenade {
+        id: lobby.next_grenade_id,
+        owner_id,
+        position: lobby.dimensions.flatten(logic::shot_origin(lobby, owner.position, origin)),
+        velocity: (direction.0 * speed, direction.1 * speed, direction.2 * speed),
+        // Gravity would pull grenades off a 2D lobby's plane
+        gravity: match lobby.dimensions {
+            Dimensions::Two => 0.0,
+            Dimensions::Three => tunables.grenade_gravity * mutators::gravity_scale(lobby),
+        },
+        detonates_at: now + Duration::from_millis(tunables.grenade_fuse_ms),
+    };
+    lobby.next_grenade_id = lobby.next_grenade_id.wrapping_add(1).max(1);
+    if let Some(owner) = lobby.players.get_mut(&owner_id) {
+        owner.grenades_thrown += 1;
+    }
+    lobby.grenade_feed.push(GrenadeEvent::Thrown {
+        grenade_id: grenade.id,
+        owner_id,
+        position: grenade.position,
+        velocity: grenade.velocity,
+        gravity: grenade.gravity,
+        fuse_ms: tunables.grenade_fuse_ms,
+    });
+    let id = grenade.id;
+    lobby.grenades.push(grenade);
+    Ok(id)
+}
+
+/// Move every grenade `dt` seconds along its arc and detonate those whose fuse ran out by `now`
+/// Grenades stop where they meet level geometry; those whose owner left are removed without an event
+pub fn step_at(lobby: &mut Lobby, weapons: &WeaponDb, dt: f32, now: Instant) {
+    let grenades = std::mem::take(&mut lobby.grenades);
+    for mut grenade in grenades {
+        if !lobby.players.contains_key(&grenade.owner_id) {
+            continue;
+        }
+        if now >= grenade.detonates_at {
+            detonate(lobby, weapons, &grenade, now);
+            continue;
+        }
+
+        if grenade.velocity != (0.0, 0.0, 0.0) {
+            grenade.velocity.1 -= grenade.gravity * dt;
+            let from = grenade.position;
+            let to = (
+                from.0 + grenade.velocity.0 * dt,
+                from.1 + grenade.velocity.1 * dt,
+                from.2 + grenade.velocity.2 * dt,
+            );
+            // Resting just short of the wall keeps the explosion on this side of it
+            if lobby.collision.segment_blocked(from, to) {
+                grenade.velocity = (0.0, 0.0, 0.0);
+            } else {
+                grenade.position = to;
+            }
+        }
+        lobby.grenades.push(grenade);
+    }
+}
+
+/// Damage everyone in the blast radius the explosion can see, falling off linearly to the edge
+/// Throwers aren't hurt by their own grenades; kills land in the kill feed
+fn detonate(lobby: &mut Lobby, weapons: &WeaponDb, grenade: &Grenade, now: Instant) {
+    let radius = lobby.tunables.grenade_radius;
+    let max_damage = lobby.tunables.grenade_damage.min(lobby.tunables.max_damage);
+    let mut in_blast: Vec<(u32, f32)> = lobby
+        .players
+        .values()
+        .filter(|p| p.id != grenade.owner_id && !p.is_dead && !p.is_loading())
+        .map(|p| (p.id, lobby.dimensions.distance(grenade.position, p.position)))
+        .filter(|(_, distance)| *distance <= radius)
+        .filter(|(id, _)| simulator::check_line_of_sight(&lobby.collision, grenade.position, lobby.players[id].position))
+        .filter(|(id, _)| teams::allows_damage(lobby, grenade.owner_id, *id))
+        .collect();
+    in_blast.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+    let mut affected = Vec::new();
+    for (target_id, distance) in in_blast {
+        let damage = ((max_damage as f32 * (1.0 - distance / radius)).round() as u32).max(1);
+        match logic::apply_damage(lobby, target_id, damage) {
+            Ok(dealt) => affected.push((target_id, dealt)),
+            Err(e) => {
+                log::debug!("Grenade {} damage on player {} not applied: {}", grenade.id, target_id, e);
+                continue;
+            }
+        }
+        if !logic::is_player_alive(lobby, target_id) {
+            if let Err(e) = logic::register_kill_at(lobby, weapons, grenade.owner_id, target_id, now) {
+                log::debug!("Grenade {} kill of player {} not registered: {}", grenade.id, target_id, e);
+            }
+        }
+    }
+    lobby.grenade_feed.push(GrenadeEvent::Exploded {
+        grenade_id: grenade.id,
+        owner_id: grenade.owner_id,
+        position: grenade.position,
+        affected,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::collision::{Aabb, CollisionWorld};
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+    use std::sync::Arc;
+
+    const DT: f32 = 0.02;
+
+    fn setup(targets: &[(u32, (f32, f32, f32))]) -> Lobby {
+        let mut builder = LobbyBuilder::new().with_player(PlayerBuilder::new().position((0.0, 1.0, 0.0)));
+        for (id, position) in targets {
+            builder = builder.with_player(PlayerBuilder::new().id(*id).position(*position));
+        }
+        builder.build()
+    }
+
+    fn run(lobby: &mut Lobby, weapons: &WeaponDb, start: Instant, ticks: std::ops::RangeInclusive<u32>) {
+        for i in ticks {
+            step_at(lobby, weapons, DT, start + Duration::from_secs_f32(DT * i as f32));
+        }
+    }
+
+    #[test]
+    fn test_grenade_arcs_and_explodes_on_its_fuse() {
+        let weapons = WeaponDb::load();
+        let mut lobby = setup(&[(2, (0.0, 1.0, -20.0))]);
+        lobby.collision = CollisionWorld { occluders: vec![Aabb { min: (-50.0, -1.0, -50.0), max: (50.0, 0.0, 50.0) }] };
+        let start = Instant::now();
+        let id = throw(&mut lobby, 1, (0.0, 1.0, 0.0), (0.0, 0.5, -1.0), start).unwrap();
+
+        // Rises while gravity slows it
+        run(&mut lobby, &weapons, start, 1..=25);
+        let (position, velocity) = (lobby.grenades[0].position, lobby.grenades[0].velocity);
+        assert!(position.1 > 2.0 && position.2 < -6.0, "{:?}", position);
+        assert!(velocity.1 > 0.0 && velocity.1 < 6.0, "{:?}", velocity);
+
+        // Comes to rest on the floor near the target and explodes when the fuse runs out
+        run(&mut lobby, &weapons, start, 26..=120);
+        assert_eq!(lobby.grenades[0].velocity, (0.0, 0.0, 0.0));
+        assert!((lobby.grenades[0].position.2 + 20.0).abs() < 1.0, "{:?}", lobby.grenades[0].position);
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 100);
+        run(&mut lobby, &weapons, start, 121..=130);
+        assert!(lobby.grenades.is_empty());
+        match lobby.grenade_feed.as_slice() {
+            [GrenadeEvent::Thrown { grenade_id, fuse_ms: 2500, .. }, GrenadeEvent::Exploded { grenade_id: exploded, affected, .. }] => {
+                assert_eq!((*grenade_id, *exploded), (id, id));
+                assert_eq!(affected.len(), 1);
+                assert_eq!(affected[0].0, 2);
+                assert_eq!(lobby.players.get(&2).unwrap().current_health, 100 - affected[0].1);
+            }
+            feed => panic!("unexpected feed {:?}", feed),
+        }
+    }
+
+    #[test]
+    fn test_explosion_damage_falls_off_and_walls_shield() {
+        let weapons = WeaponDb::load();
+        let mut lobby = setup(&[(2, (0.0, 1.0, -1.0)), (3, (0.0, 1.0, -4.0)), (4, (0.0, 1.0, -20.0)), (5, (3.0, 1.0, 0.0))]);
+        lobby.collision = CollisionWorld { occluders: vec![Aabb { min: (1.5, 0.0, -5.0), max: (2.0, 5.0, 5.0) }] };
+        let grenade = Grenade { id: 1, owner_id: 1, position: (0.0, 1.0, 0.0), velocity: (0.0, 0.0, 0.0), gravity: 0.0, detonates_at: Instant::now() };
+        detonate(&mut lobby, &weapons, &grenade, Instant::now());
+
+        // 80 at the center, none at the 5 unit edge; out of range and behind the wall untouched, nor the thrower
+        let health = |lobby: &Lobby, id: u32| lobby.players.get(&id).unwrap().current_health;
+        assert_eq!((health(&lobby, 2), health(&lobby, 3), health(&lobby, 4), health(&lobby, 5)), (36, 84, 100, 100));
+        assert_eq!(health(&lobby, 1), 100);
+        assert!(matches!(&lobby.grenade_feed[..], [GrenadeEvent::Exploded { affected, .. }] if affected == &vec![(2, 64), (3, 16)]));
+
+        // A second blast finishes the nearest player off
+        detonate(&mut lobby, &weapons, &grenade, Instant::now());
+        assert_eq!(lobby.kill_feed.len(), 1);
+        assert_eq!(lobby.kill_feed[0].victim_id, 2);
+    }
+
+    #[test]
+    fn test_throws_are_limited_per_life() {
+        let mut lobby = setup(&[]);
+        Arc::make_mut(&mut lobby.tunables).grenades_per_life = 1;
+        let now = Instant::now();
+        assert!(throw(&mut lobby, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), now).is_ok());
+        assert_eq!(throw(&mut lobby, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), now), Err("No grenades left"));
+        assert_eq!(throw(&mut lobby, 1, (0.0, 1.0, 0.0), (0.0, 0.0, 0.0), now), Err("No grenades left"));
+
+        logic::respawn_player(&mut lobby, 1).unwrap();
+        assert_eq!(throw(&mut lobby, 1, (0.0, 1.0, 0.0), (0.0, 0.0, 0.0), now), Err("Invalid direction"));
+        assert!(throw(&mut lobby, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), now).is_ok());
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use crate::state::lobby::Lobby;
+use crate::domain::logic;
+use crate::domain::horde::Horde;
+use crate::utils::weapondb::WeaponDb;
+
+/// Rules a lobby is played under
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum GameMode {
+    /// Free for all, everyone keeps their weapon
+    #[default]
+    Ffa,
+    /// Each kill advances the killer to the next weapon of a ladder ending with the knife
+    GunGame(GunGame),
+    /// Players team up against server-spawned waves of bots (see `domain::horde`)
+    Horde(Horde),
+}
+
+impl GameMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameMode::Ffa => "ffa",
+            GameMode::GunGame(_) => "gun_game",
+            GameMode::Horde(_) => "horde",
+        }
+    }
+
+    /// Parse a mode name; gun game uses `ladder` (see `build_ladder`)
+    pub fn parse(name: &str, ladder: Vec<u32>) -> Option<Self> {
+        match name {
+            "ffa" => Some(GameMode::Ffa),
+            "gun_game" => Some(GameMode::GunGame(GunGame::new(ladder))),
+            "horde" => Some(GameMode::Horde(Horde::default())),
+            _ => None,
+        }
+    }
+}
+
+/// Gun game progression: every player's position on the weapon ladder
+#[derive(Debug, Clone, PartialEq)]
+pub struct GunGame {
+    pub ladder: Vec<u32>, // Weapon ids, last one is the knife
+    levels: HashMap<u32, usize>, // Player ID -> ladder index
+}
+
+impl GunGame {
+    pub fn new(ladder: Vec<u32>) -> Self {
+        Self { ladder, levels: HashMap::new() }
+    }
+
+    /// Ladder index of a player (0 if unknown)
+    pub fn level(&self, player_id: u32) -> usize {
+        self.levels.get(&player_id).copied().unwrap_or(0)
+    }
+
+    /// Weapon a player should be holding at their level
+    pub fn weapon_for(&self, player_id: u32) -> Option<u32> {
+        self.ladder.get(self.level(player_id)).copied()
+    }
+
+    /// Players may only hold their ladder weapon or the knife
+    pub fn allows_weapon(&self, weapons: &WeaponDb, player_id: u32, weapon_id: u32) -> bool {
+        self.weapon_for(player_id) == Some(weapon_id)
+            || weapons.get(weapon_id).map(|w| w.is_melee()).unwrap_or(false)
+    }
+
+    pub fn remove(&mut self, player_id: u32) {
+        self.levels.remove(&player_id);
+    }
+}
+
+/// What a gun game kill did to the killer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Progress {
+    /// Moved up to the weapon at `level`
+    Advanced { player_id: u32, level: usize, weapon_id: u32 },
+    /// Killed with the last weapon: the match is won and everyone starts over
+    Won { player_id: u32 },
+}
+
+/// Default ladder: ranged weapons by id, then the knife
+pub fn default_ladder(weapons: &WeaponDb) -> Vec<u32> {
+    let mut ladder: Vec<u32> = weapons.iter().filter(|w| !w.is_melee()).map(|w| w.id).collect();
+    ladder.sort_unstable();
+    ladder.extend(weapons.melee_weapon().map(|w| w.id));
+    ladder
+}
+
+/// Ladder from configured weapon ids (empty = `default_ladder`)
+/// The knife is appended when the configured ladder doesn't already end with a melee weapon
+pub fn build_ladder(configured: &[u32], weapons: &WeaponDb) -> Result<Vec<u32>, &'static str> {
+    if configured.is_empty() {
+        return Ok(default_ladder(weapons));
+    }
+    if !configured.iter().all(|id| weapons.contains(*id)) {
+        return Err("Unknown weapon in gun game ladder");
+    }
+
+    let mut ladder = configured.to_vec();
+    let ends_with_melee = ladder.last().and_then(|id| weapons.get(*id)).map(|w| w.is_melee()).unwrap_or(false);
+    if !ends_with_melee {
+        ladder.push(weapons.melee_weapon().ok_or("No knife to finish the ladder")?.id);
+    }
+    Ok(ladder)
+}
+
+/// Hand a player the weapon for their ladder level (bots keep returning to it after knifing)
+/// It replaces their whole inventory; the knife is always at hand anyway
+fn arm(lobby: &mut Lobby, weapons: &WeaponDb, player_id: u32, weapon_id: u32) -> Result<(), &'static str> {
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.inventory.clear();
+    }
+    logic::equip_weapon(lobby, weapons, player_id, weapon_id)?;
+    if let Some(brain) = lobby.bots.get_mut(&player_id) {
+        brain.primary_weapon_id = weapon_id;
+    }
+    Ok(())
+}
+
+/// Put a player at the bottom of the ladder (no-op outside gun game)
+pub fn enroll(lobby: &mut Lobby, weapons: &WeaponDb, player_id: u32) -> Result<(), &'static str> {
+    let GameMode::GunGame(game) = &mut lobby.mode else {
+        return Ok(());
+    };
+    game.levels.insert(player_id, 0);
+    let weapon_id = *game.ladder.first().ok_or("Empty gun game ladder")?;
+    arm(lobby, weapons, player_id, weapon_id)
+}
+
+/// Carry a player's ladder level over to another game on the same ladder
+pub fn transfer(from: &mut GunGame, to: &mut GunGame, player_id: u32) {
+    if let Some(level) = from.levels.remove(&player_id) {
+        to.levels.insert(player_id, level);
+    }
+}
+
+/// Replace a gun game lobby's ladder; everyone starts the new one from the bottom
+/// `ladder` is expected to come from `build_ladder`
+pub fn set_ladder(lobby: &mut Lobby, weapons: &WeaponDb, ladder: Vec<u32>) -> Result<(), &'static str> {
+    let GameMode::GunGame(game) = &mut lobby.mode else {
+        return Err("Not a gun game lobby");
+    };
+    if ladder.is_empty() {
+        return Err("Empty gun game ladder");
+    }
+    *game = GunGame::new(ladder);
+    let player_ids: Vec<u32> = lobby.players.keys().copied().collect();
+    for player_id in player_ids {
+        enroll(lobby, weapons, player_id)?;
+    }
+    Ok(())
+}
+
+/// Advance the killer after a kill (None outside gun game)
+pub fn on_kill(lobby: &mut Lobby, weapons: &WeaponDb, killer_id: u32) -> Result<Option<Progress>, &'static str> {
+    let GameMode::GunGame(game) = &mut lobby.mode else {
+        return Ok(None);
+    };
+    let level = game.level(killer_id) + 1;
+
+    match game.ladder.get(level).copied() {
+        Some(weapon_id) => {
+            game.levels.insert(killer_id, level);
+            arm(lobby, weapons, killer_id, weapon_id)?;
+            Ok(Some(Progress::Advanced { player_id: killer_id, level, weapon_id }))
+        }
+        None => {
+            let player_ids: Vec<u32> = lobby.players.keys().copied().collect();
+            for player_id in player_ids {
+                enroll(lobby, weapons, player_id)?;
+            }
+            Ok(Some(Progress::Won { player_id: killer_id }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::lobbies;
+    use crate::testutil::LobbyBuilder;
+
+    fn gun_game_lobby(weapons: &WeaponDb) -> Lobby {
+        let mut lobby = LobbyBuilder::new().build();
+        lobby.mode = GameMode::GunGame(GunGame::new(default_ladder(weapons)));
+        lobbies::add_player(&mut lobby, 1, "Ada".to_string(), 2, weapons).unwrap();
+        lobbies::add_bot(&mut lobby, 2, "Bot".to_string(), weapons).unwrap();
+        lobby
+    }
+
+    #[test]
+    fn test_build_ladder() {
+        let weapons = WeaponDb::load();
+        assert_eq!(build_ladder(&[], &weapons).unwrap(), vec![1, 2, 3]);
+        assert_eq!(build_ladder(&[2, 1], &weapons).unwrap(), vec![2, 1, 3]);
+        assert_eq!(build_ladder(&[2, 3], &weapons).unwrap(), vec![2, 3]);
+        assert!(build_ladder(&[99], &weapons).is_err());
+    }
+
+    #[test]
+    fn test_kills_climb_the_ladder_and_win() {
+        let weapons = WeaponDb::load();
+        let mut lobby = gun_game_lobby(&weapons);
+        // Joining puts players on the first weapon whatever they asked for
+        assert_eq!(lobby.players.get(&1).unwrap().current_weapon_id, 1);
+
+        assert_eq!(
+            on_kill(&mut lobby, &weapons, 2).unwrap(),
+            Some(Progress::Advanced { player_id: 2, level: 1, weapon_id: 2 })
+        );
+        assert_eq!(lobby.players.get(&2).unwrap().current_weapon_id, 2);
+        assert_eq!(lobby.bots.get(&2).unwrap().primary_weapon_id, 2);
+
+        on_kill(&mut lobby, &weapons, 2).unwrap();
+        assert_eq!(lobby.players.get(&2).unwrap().current_weapon_id, 3);
+
+        // Knife kill on the last level wins and resets everyone
+        assert_eq!(on_kill(&mut lobby, &weapons, 2).unwrap(), Some(Progress::Won { player_id: 2 }));
+        assert!(lobby.players.values().all(|p| p.current_weapon_id == 1));
+    }
+
+    #[test]
+    fn test_new_ladder_restarts_everyone() {
+        let weapons = WeaponDb::load();
+        let mut lobby = gun_game_lobby(&weapons);
+        on_kill(&mut lobby, &weapons, 2).unwrap();
+
+        set_ladder(&mut lobby, &weapons, vec![2, 3]).unwrap();
+        assert!(lobby.players.values().all(|p| p.current_weapon_id == 2));
+        let GameMode::GunGame(game) = &lobby.mode else { unreachable!() };
+        assert_eq!(game.level(2), 0);
+
+        let mut ffa = LobbyBuilder::new().build();
+        assert_eq!(set_ladder(&mut ffa, &weapons, vec![2, 3]), Err("Not a gun game lobby"));
+    }
+
+    #[test]
+    fn test_allowed_weapons() {
+        let weapons = WeaponDb::load();
+        let lobby = gun_game_lobby(&weapons);
+        let GameMode::GunGame(game) = &lobby.mode else { unreachable!() };
+        assert!(game.allows_weapon(&weapons, 1, 1));
+        assert!(game.allows_weapon(&weapons, 1, 3)); // Knife is always allowed
+        assert!(!game.allows_weapon(&weapons, 1, 2));
+    }
+
+    #[test]
+    fn test_ffa_is_untouched() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new().build();
+        lobbies::add_player(&mut lobby, 1, "Ada".to_string(), 2, &weapons).unwrap();
+        assert_eq!(on_kill(&mut lobby, &weapons, 1).unwrap(), None);
+        assert_eq!(lobby.players.get(&1).unwrap().current_weapon_id, 2);
+    }
+}
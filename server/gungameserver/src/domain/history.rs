@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::domain::matches;
+use crate::domain::logic::KillEvent;
+use crate::state::lobby::Lobby;
+
+/// Most kills kept per match; later ones still count in the scores but aren't listed
+const MAX_RECORDED_KILLS: usize = 5000;
+
+/// A kill in a match record, `at_ms` after the match started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchKill {
+    pub at_ms: u64,
+    pub killer_id: u32,
+    pub victim_id: u32,
+    pub weapon_id: u32,
+}
+
+/// A gun game level-up in a match record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeaponStep {
+    pub at_ms: u64,
+    pub player_id: u32,
+    pub level: usize,
+    pub weapon_id: u32,
+}
+
+/// A player's result in a match record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchParticipant {
+    pub player_id: u32,
+    pub account_id: Option<i64>, // None for guests and bots
+    pub name: String,
+    pub bot: bool,
+    pub team_id: Option<u32>,
+    pub place: u32,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+}
+
+/// A finished match, as kept in match history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub match_id: String,
+    pub lobby_code: String,
+    pub scene: String,
+    pub mode: String,
+    pub started_at: u64, // Seconds since the Unix epoch
+    pub ended_at: u64,
+    pub duration_secs: u64,
+    pub winner_id: Option<u32>,
+    pub participants: Vec<MatchParticipant>, // By place
+    pub kills: Vec<MatchKill>,
+    pub progression: Vec<WeaponStep>, // Gun game only
+}
+
+/// The match being played in a lobby, recorded as it goes
+#[derive(Debug, Default)]
+pub struct MatchLog {
+    started: Option<(Instant, u64)>, // Monotonic and wall clock start; None between matches
+    kills: Vec<MatchKill>,
+    progression: Vec<WeaponStep>,
+}
+
+impl MatchLog {
+    fn elapsed_ms(&self, now: Instant) -> Option<u64> {
+        self.started.map(|(started, _)| now.saturating_duration_since(started).as_millis() as u64)
+    }
+}
+
+/// Start recording a match (no-op while one is being recorded, e.g. a referee restarting the round)
+pub fn begin(lobby: &mut Lobby, now: Instant) {
+    if lobby.match_log.started.is_none() {
+        lobby.match_log = MatchLog { started: Some((now, unix_now())), ..MatchLog::default() };
+    }
+}
+
+/// Record a kill; kills outside a match (warm-up, results screen) aren't recorded
+pub fn record_kill(lobby: &mut Lobby, kill: &KillEvent, now: Instant) {
+    let log = &mut lobby.match_log;
+    if let Some(at_ms) = log.elapsed_ms(now).filter(|_| log.kills.len() < MAX_RECORDED_KILLS) {
+        log.kills.push(MatchKill { at_ms, killer_id: kill.killer_id, victim_id: kill.victim_id, weapon_id: kill.weapon_id });
+    }
+}
+
+/// Record a gun game level-up
+pub fn record_progress(lobby: &mut Lobby, player_id: u32, level: usize, weapon_id: u32, now: Instant) {
+    let log = &mut lobby.match_log;
+    if let Some(at_ms) = log.elapsed_ms(now).filter(|_| log.progression.len() < MAX_RECORDED_KILLS) {
+        log.progression.push(WeaponStep { at_ms, player_id, level, weapon_id });
+    }
+}
+
+/// Close the match `winner_id` won and hand back its record; the log is cleared for the next match
+pub fn finish(lobby: &mut Lobby, winner_id: Option<u32>, now: Instant) -> MatchRecord {
+    let log = std::mem::take(&mut lobby.match_log);
+    let ended_at = unix_now();
+    let (started, started_at) = log.started.unwrap_or((now, ended_at));
+    let participants = matches::placements(lobby)
+        .into_iter()
+        .filter_map(|placement| {
+            let p = lobby.players.get(&placement.player_id)?;
+            Some(MatchParticipant {
+                player_id: p.id,
+                account_id: p.account_id,
+                name: p.name.clone(),
+                bot: lobby.bots.contains_key(&p.id),
+                team_id: p.team_id,
+                place: placement.place,
+                score: p.score,
+                kills: p.kills,
+                deaths: p.deaths,
+            })
+        })
+        .collect();
+    MatchRecord {
+        match_id: uuid::Uuid::new_v4().to_string(),
+        lobby_code: lobby.code.clone(),
+        scene: lobby.scene.clone(),
+        mode: lobby.mode.as_str().to_string(),
+        started_at,
+        ended_at,
+        duration_secs: now.saturating_duration_since(started).as_secs(),
+        winner_id,
+        participants,
+        kills: log.kills,
+        progression: log.progression,
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+    use std::time::Duration;
+
+    fn kill(killer_id: u32, victim_id: u32) -> KillEvent {
+        KillEvent {
+            killer_id,
+            killer_name: String::new(),
+            victim_id,
+            victim_name: String::new(),
+            weapon_id: 1,
+            weapon_name: String::new(),
+            killer_new_killstreak: 1,
+        }
+    }
+
+    #[test]
+    fn test_match_record_covers_only_the_match() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).with_player(PlayerBuilder::new().id(2)).build();
+        let start = crate::testutil::epoch();
+        record_kill(&mut lobby, &kill(1, 2), start); // Warm-up
+
+        begin(&mut lobby, start);
+        record_kill(&mut lobby, &kill(2, 1), start + Duration::from_millis(1500));
+        begin(&mut lobby, start + Duration::from_secs(2)); // Round restarted, same match
+        record_progress(&mut lobby, 2, 1, 2, start + Duration::from_millis(1500));
+        lobby.players.get_mut(&2).unwrap().score = 100;
+
+        let record = finish(&mut lobby, Some(2), start + Duration::from_secs(90));
+        assert_eq!(record.duration_secs, 90);
+        assert_eq!(record.kills, vec![MatchKill { at_ms: 1500, killer_id: 2, victim_id: 1, weapon_id: 1 }]);
+        assert_eq!(record.progression, vec![WeaponStep { at_ms: 1500, player_id: 2, level: 1, weapon_id: 2 }]);
+        assert_eq!(record.participants.iter().map(|p| (p.player_id, p.place)).collect::<Vec<_>>(), vec![(2, 1), (1, 2)]);
+
+        // The next match starts from a clean log
+        record_kill(&mut lobby, &kill(1, 2), start + Duration::from_secs(95));
+        assert!(finish(&mut lobby, None, start + Duration::from_secs(96)).kills.is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+use crate::domain::gungame::GameMode;
+use crate::domain::lobbies;
+use crate::state::lobby::{Lobby, MatchState};
+use crate::utils::weapondb::WeaponDb;
+
+/// Team of the players and any bots the lobby was created with
+pub const PLAYERS_TEAM: u32 = 1;
+
+/// Team of the wave bots
+pub const HORDE_TEAM: u32 = 2;
+
+/// Wave sizes, difficulty and lives of a horde match
+#[derive(Debug, Clone, PartialEq)]
+pub struct HordeRules {
+    pub waves: u32, // Clearing the last wave wins the match
+    pub lives: u32, // Respawns shared by the players' team
+    pub first_wave_enemies: u32,
+    pub enemies_per_wave: u32, // Added each wave
+    pub health_per_wave: u32, // Enemy max health added each wave
+    pub wave_break: Duration, // Before the first wave and between waves
+}
+
+impl Default for HordeRules {
+    fn default() -> Self {
+        Self {
+            waves: 5,
+            lives: 5,
+            first_wave_enemies: 3,
+            enemies_per_wave: 2,
+            health_per_wave: 20,
+            wave_break: Duration::from_secs(8),
+        }
+    }
+}
+
+/// Progress of a horde match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Horde {
+    pub wave: u32, // Current wave, 0 before the first
+    pub lives: u32, // Shared respawns left
+    enemies: BTreeSet<u32>, // Wave bots still alive
+    out: BTreeSet<u32>, // Died with no life left; they watch until the match ends
+    next_wave_at: Option<Instant>,
+}
+
+impl Horde {
+    pub fn is_enemy(&self, player_id: u32) -> bool {
+        self.enemies.contains(&player_id)
+    }
+
+    pub fn is_out(&self, player_id: u32) -> bool {
+        self.out.contains(&player_id)
+    }
+
+    pub fn enemies_left(&self) -> usize {
+        self.enemies.len()
+    }
+
+    pub fn remove(&mut self, player_id: u32) {
+        self.enemies.remove(&player_id);
+        self.out.remove(&player_id);
+    }
+}
+
+/// What happened in a horde match this tick
+#[derive(Debug, Clone, PartialEq)]
+pub enum HordeEvent {
+    WaveStarted { wave: u32, enemies: u32 },
+    WaveCleared { wave: u32, next_in: Duration },
+    LivesChanged { lives: u32 },
+    PlayerOut { player_id: u32 },
+    /// The last wave was cleared, or every player is out (`cleared` false)
+    Ended { wave: u32, cleared: bool },
+    /// Wave bots joining and leaving; broadcast like any player joining or leaving
+    EnemySpawned { player_id: u32, name: String },
+    EnemyRemoved { player_id: u32 },
+}
+
+/// Whether a player is a wave bot (false outside horde)
+pub fn is_enemy(lobby: &Lobby, player_id: u32) -> bool {
+    matches!(&lobby.mode, GameMode::Horde(horde) if horde.is_enemy(player_id))
+}
+
+/// Whether a player is out of the match for lack of lives (false outside horde)
+pub fn is_out(lobby: &Lobby, player_id: u32) -> bool {
+    matches!(&lobby.mode, GameMode::Horde(horde) if horde.is_out(player_id))
+}
+
+/// Wave bots in the lobby (0 outside horde); they don't take player slots
+pub fn enemy_count(lobby: &Lobby) -> usize {
+    match &lobby.mode {
+        GameMode::Horde(horde) => horde.enemies_left(),
+        _ => 0,
+    }
+}
+
+/// Put a joining player on the players' team (no-op outside horde)
+pub fn enlist(lobby: &mut Lobby, player_id: u32) {
+    if !matches!(lobby.mode, GameMode::Horde(_)) {
+        return;
+    }
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.team_id = Some(PLAYERS_TEAM);
+    }
+    lobby.mark_dirty(player_id);
+}
+
+/// Spend a shared life on a player's death; without one left the player is out
+pub fn on_death(lobby: &mut Lobby, player_id: u32) -> Option<HordeEvent> {
+    let GameMode::Horde(horde) = &mut lobby.mode else {
+        return None;
+    };
+    if horde.is_enemy(player_id) || !matches!(lobby.match_state, MatchState::InProgress { .. }) {
+        return None;
+    }
+    if horde.lives > 0 {
+        horde.lives -= 1;
+        Some(HordeEvent::LivesChanged { lives: horde.lives })
+    } else {
+        horde.out.insert(player_id);
+        Some(HordeEvent::PlayerOut { player_id })
+    }
+}
+
+/// Spawn the wave bots of `wave` at the scene's enemy spawn points
+fn spawn_wave(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    wave: u32,
+    next_id: &mut dyn FnMut() -> u32,
+) -> Result<Vec<HordeEvent>, &'static str> {
+    let rules = lobby.rules.horde.clone();
+    let count = rules.first_wave_enemies + rules.enemies_per_wave * (wave - 1);
+    let bonus_health = rules.health_per_wave * (wave - 1);
+    let spawns = if lobby.enemy_spawn_points.is_empty() { lobby.spawn_points.clone() } else { lobby.enemy_spawn_points.clone() };
+
+    let mut events = Vec::new();
+    for i in 0..count {
+        let player_id = next_id();
+        let name = format!("Wave {} #{}", wave, i + 1);
+        lobbies::add_wave_bot(lobby, player_id, name.clone(), weapons)?;
+        let position = spawns.get(i as usize % spawns.len().max(1)).map(|p| lobby.dimensions.flatten(*p));
+        if let Some(player) = lobby.players.get_mut(&player_id) {
+            player.team_id = Some(HORDE_TEAM);
+            player.max_health += bonus_health;
+            player.current_health = player.max_health;
+            if let Some(position) = position {
+                player.position = position;
+            }
+        }
+        lobby.mark_dirty(player_id);
+        if let GameMode::Horde(horde) = &mut lobby.mode {
+            horde.enemies.insert(player_id);
+        }
+        events.push(HordeEvent::EnemySpawned { player_id, name });
+    }
+    if let GameMode::Horde(horde) = &mut lobby.mode {
+        horde.wave = wave;
+        horde.next_wave_at = None;
+    }
+    events.push(HordeEvent::WaveStarted { wave, enemies: count });
+    Ok(events)
+}
+
+/// Advance a horde match: clear dead wave bots, start waves after a break, and end
+/// the match when the last wave is cleared or every player is out (no-op outside horde)
+/// `next_id` hands out player ids for wave bots
+pub fn advance(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    now: Instant,
+    next_id: &mut dyn FnMut() -> u32,
+) -> Result<Vec<HordeEvent>, &'static str> {
+    let in_progress = matches!(lobby.match_state, MatchState::InProgress { .. });
+    let lives = lobby.rules.horde.lives;
+    let GameMode::Horde(horde) = &mut lobby.mode else {
+        return Ok(Vec::new());
+    };
+
+    // Between matches nothing is left standing and lives are full again
+    let gone: Vec<u32> = if in_progress {
+        horde.enemies.iter().copied().filter(|id| lobby.players.get(id).is_none_or(|p| p.is_dead)).collect()
+    } else {
+        let leftover = horde.enemies.iter().copied().collect();
+        *horde = Horde { lives, ..Horde::default() };
+        leftover
+    };
+    let mut events = Vec::new();
+    for player_id in gone {
+        lobbies::remove_player(lobby, player_id);
+        events.push(HordeEvent::EnemyRemoved { player_id });
+    }
+    if !in_progress {
+        return Ok(events);
+    }
+
+    let team: Vec<u32> = lobby.players.keys().copied().filter(|id| !is_enemy(lobby, *id)).collect();
+    let GameMode::Horde(horde) = &mut lobby.mode else {
+        return Ok(events);
+    };
+    if !team.is_empty() && team.iter().all(|id| horde.is_out(*id)) {
+        events.push(HordeEvent::Ended { wave: horde.wave, cleared: false });
+        return Ok(events);
+    }
+    if !horde.enemies.is_empty() {
+        return Ok(events);
+    }
+    if horde.wave >= lobby.rules.horde.waves {
+        events.push(HordeEvent::Ended { wave: horde.wave, cleared: true });
+        return Ok(events);
+    }
+    match horde.next_wave_at {
+        None => {
+            let next_in = lobby.rules.horde.wave_break;
+            horde.next_wave_at = Some(now + next_in);
+            if horde.wave > 0 {
+                events.push(HordeEvent::WaveCleared { wave: horde.wave, next_in });
+            }
+        }
+        Some(at) if now >= at => {
+            let wave = horde.wave + 1;
+            events.extend(spawn_wave(lobby, weapons, wave, next_id)?);
+        }
+        Some(_) => {}
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::logic;
+
+    fn secs(s: u64) -> Instant {
+        crate::testutil::epoch() + Duration::from_secs(1_000 + s)
+    }
+
+    fn horde_lobby(weapons: &WeaponDb) -> Lobby {
+        let mut lobby = Lobby::new("TEST".to_string(), 2, "world".to_string());
+        lobby.mode = GameMode::Horde(Horde::default());
+        lobby.rules.horde = HordeRules { waves: 2, lives: 1, first_wave_enemies: 2, enemies_per_wave: 1, health_per_wave: 50, wave_break: Duration::from_secs(5) };
+        lobby.enemy_spawn_points = vec![(10.0, 1.0, 10.0)];
+        lobbies::add_player(&mut lobby, 1, "Ada".to_string(), 1, weapons).unwrap();
+        lobbies::add_player(&mut lobby, 2, "Bea".to_string(), 1, weapons).unwrap();
+        lobby
+    }
+
+    fn ids() -> impl FnMut() -> u32 {
+        let mut next = 100;
+        move || {
+            next += 1;
+            next
+        }
+    }
+
+    fn kill_all_enemies(lobby: &mut Lobby, weapons: &WeaponDb) {
+        let enemies: Vec<u32> = lobby.players.keys().copied().filter(|id| is_enemy(lobby, *id)).collect();
+        for id in enemies {
+            logic::register_kill(lobby, weapons, 1, id).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_waves_scale_and_clearing_the_last_one_ends_the_match() {
+        let weapons = WeaponDb::load();
+        let mut lobby = horde_lobby(&weapons);
+        let mut next_id = ids();
+        advance(&mut lobby, &weapons, secs(0), &mut next_id).unwrap();
+        lobby.match_state = MatchState::InProgress { round: 1, ends_at: secs(300) };
+
+        assert!(advance(&mut lobby, &weapons, secs(0), &mut next_id).unwrap().is_empty()); // Break before wave 1
+        let events = advance(&mut lobby, &weapons, secs(5), &mut next_id).unwrap();
+        assert_eq!(events.last(), Some(&HordeEvent::WaveStarted { wave: 1, enemies: 2 }));
+        assert_eq!(enemy_count(&lobby), 2);
+        let enemy = lobby.players.get(&101).unwrap();
+        assert_eq!((enemy.team_id, enemy.max_health, enemy.position), (Some(HORDE_TEAM), 100, (10.0, 1.0, 10.0)));
+        assert_eq!(lobby.players.get(&1).unwrap().team_id, Some(PLAYERS_TEAM));
+
+        kill_all_enemies(&mut lobby, &weapons);
+        let events = advance(&mut lobby, &weapons, secs(10), &mut next_id).unwrap();
+        assert!(events.contains(&HordeEvent::EnemyRemoved { player_id: 101 }));
+        assert_eq!(events.last(), Some(&HordeEvent::WaveCleared { wave: 1, next_in: Duration::from_secs(5) }));
+        assert_eq!(lobby.players.len(), 2);
+
+        let events = advance(&mut lobby, &weapons, secs(15), &mut next_id).unwrap();
+        assert_eq!(events.last(), Some(&HordeEvent::WaveStarted { wave: 2, enemies: 3 }));
+        assert_eq!(lobby.players.get(&103).unwrap().max_health, 150);
+
+        kill_all_enemies(&mut lobby, &weapons);
+        let events = advance(&mut lobby, &weapons, secs(20), &mut next_id).unwrap();
+        assert_eq!(events.last(), Some(&HordeEvent::Ended { wave: 2, cleared: true }));
+    }
+
+    #[test]
+    fn test_shared_lives_and_team_wipe() {
+        let weapons = WeaponDb::load();
+        let mut lobby = horde_lobby(&weapons);
+        let mut next_id = ids();
+        advance(&mut lobby, &weapons, secs(0), &mut next_id).unwrap();
+        lobby.match_state = MatchState::InProgress { round: 1, ends_at: secs(300) };
+        advance(&mut lobby, &weapons, secs(0), &mut next_id).unwrap();
+        advance(&mut lobby, &weapons, secs(5), &mut next_id).unwrap();
+
+        logic::register_kill(&mut lobby, &weapons, 101, 1).unwrap();
+        assert_eq!(on_death(&mut lobby, 1), Some(HordeEvent::LivesChanged { lives: 0 }));
+        logic::register_kill(&mut lobby, &weapons, 101, 2).unwrap();
+        assert_eq!(on_death(&mut lobby, 2), Some(HordeEvent::PlayerOut { player_id: 2 }));
+        assert!(is_out(&lobby, 2));
+
+        // Out players stay down; everyone else respawns as usual
+        let respawned = logic::respawn_due_players_at(&mut lobby, secs(60));
+        assert_eq!(respawned, vec![1]);
+
+        logic::register_kill(&mut lobby, &weapons, 101, 1).unwrap();
+        assert_eq!(on_death(&mut lobby, 1), Some(HordeEvent::PlayerOut { player_id: 1 }));
+        let events = advance(&mut lobby, &weapons, secs(61), &mut next_id).unwrap();
+        assert_eq!(events.last(), Some(&HordeEvent::Ended { wave: 1, cleared: false }));
+
+        // The next match starts over with full lives and no wave bots
+        lobby.match_state = MatchState::WaitingForPlayers;
+        advance(&mut lobby, &weapons, secs(62), &mut next_id).unwrap();
+        assert_eq!(enemy_count(&lobby), 0);
+        assert_eq!(lobby.players.len(), 2);
+        let GameMode::Horde(horde) = &lobby.mode else { unreachable!() };
+        assert_eq!((horde.wave, horde.lives, horde.is_out(1)), (0, 1, false));
+    }
+}
@@ -1,7 +1,19 @@
-use crate::state::lobby::{Lobby, LobbyCode, Player};
+use crate::state::lobby::{Connection, Lobby, LobbyCode, Magazine, Player, Spectator};
+use crate::net::quantize;
+use crate::domain::bot::BotBrain;
+use crate::domain::anticheat::{AimTracker, RecoilTracker, ShotAudit, ViolationTracker};
+use crate::domain::chat::ChatLimiter;
+use crate::domain::challenge::ChallengeState;
+use crate::domain::clock::ClientClock;
+use crate::domain::gungame::{self, GameMode};
+use crate::domain::horde;
+use crate::domain::logic;
+use crate::domain::owner;
+use crate::domain::teams;
+use crate::net::crypto;
 use crate::utils::weapondb::WeaponDb;
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
 /// Create a new lobby
 pub fn create_lobby(
@@ -25,10 +37,24 @@ pub fn add_player(
     default_weapon_id: u32,
     weapon_data: &WeaponDb,
 ) -> Result<(), &'static str> {
-    if lobby.players.len() >= lobby.max_players as usize {
+    if occupied_slots(lobby) + held_reservations(lobby, Instant::now()) >= lobby.max_players as usize {
         return Err("Lobby is full");
     }
+    insert_player(lobby, player_id, name, default_weapon_id, weapon_data)
+}
+
+/// Players taking a slot (horde wave bots don't)
+pub fn occupied_slots(lobby: &Lobby) -> usize {
+    lobby.players.len() - horde::enemy_count(lobby)
+}
 
+fn insert_player(
+    lobby: &mut Lobby,
+    player_id: u32,
+    name: String,
+    default_weapon_id: u32,
+    weapon_data: &WeaponDb,
+) -> Result<(), &'static str> {
     if lobby.players.contains_key(&player_id) {
         return Err("Player already exists");
     }
@@ -42,34 +68,206 @@ pub fn add_player(
         name: name.clone(),
         position: (0.0, 1.0, 0.0),
         rotation: (0.0, 0.0, 0.0),
-        last_update: SystemTime::now(),
+        last_update: Instant::now(),
         current_health: 100,
         max_health: 100,
         current_weapon_id: default_weapon_id,
         current_ammo: weapon.ammo,
         max_ammo: weapon.ammo,
+        inventory: std::collections::BTreeMap::from([(default_weapon_id, Magazine::full(weapon.ammo))]),
         is_reloading: false,
         reload_end_time: None,
-        last_shot_time: SystemTime::UNIX_EPOCH,
+        last_shot_time: None,
+        grenades_thrown: 0,
         kills: 0,
         deaths: 0,
         score: 0,
         killstreak: 0,
+        shots_fired: 0,
+        shots_hit: 0,
+        account_id: None,
         warned_at: None,
+        connection: Connection::Connected,
         is_dead: false,
         respawn_time: None,
+        team_id: None,
+        ready: false,
+        loading_until: None,
+        clock: ClientClock::default(),
+        recoil: RecoilTracker::default(),
+        attested: None,
+        aim: AimTracker::default(),
+        violations: ViolationTracker::default(),
+        shot_audit: ShotAudit::default(),
+        challenge: ChallengeState::default(),
+        chat: ChatLimiter::default(),
+        muted: std::collections::HashSet::new(),
     };
 
     lobby.players.insert(player_id, player);
+    lobby.analytics.peak_players = lobby.analytics.peak_players.max(lobby.players.len());
     lobby.mark_dirty(player_id);
+    teams::assign(lobby, player_id);
+    horde::enlist(lobby, player_id);
+    let spawn = logic::choose_spawn_point(lobby, player_id);
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.position = spawn;
+    }
+    gungame::enroll(lobby, weapon_data, player_id)
+}
+
+/// Add a server-driven bot player to a lobby
+pub fn add_bot(
+    lobby: &mut Lobby,
+    player_id: u32,
+    name: String,
+    weapon_data: &WeaponDb,
+) -> Result<(), &'static str> {
+    add_player(lobby, player_id, name, WeaponDb::default_weapon_id(), weapon_data)?;
+    make_bot(lobby, player_id);
     Ok(())
 }
 
+/// Add a horde wave bot; it doesn't need (or take) a free slot
+pub fn add_wave_bot(
+    lobby: &mut Lobby,
+    player_id: u32,
+    name: String,
+    weapon_data: &WeaponDb,
+) -> Result<(), &'static str> {
+    insert_player(lobby, player_id, name, WeaponDb::default_weapon_id(), weapon_data)?;
+    make_bot(lobby, player_id);
+    Ok(())
+}
+
+fn make_bot(lobby: &mut Lobby, player_id: u32) {
+    let primary_weapon_id = lobby.players.get(&player_id).map_or(WeaponDb::default_weapon_id(), |p| p.current_weapon_id);
+    lobby.bots.insert(player_id, BotBrain { primary_weapon_id, melee_switching: true });
+}
+
+/// Keep `bot_fill` players in the lobby: add bots while it's short, take them out again as players join
+/// Only bots added here are removed; returns the bots added (id, name) and removed
+pub fn fill_bots(
+    lobby: &mut Lobby,
+    weapon_data: &WeaponDb,
+    next_id: &mut impl FnMut() -> u32,
+) -> (Vec<(u32, String)>, Vec<u32>) {
+    let target = lobby.bot_fill as usize;
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    while occupied_slots(lobby) > target {
+        let Some(player_id) = lobby.fill_bots.pop_last() else { break };
+        remove_player(lobby, player_id);
+        removed.push(player_id);
+    }
+    while occupied_slots(lobby) < target {
+        let player_id = next_id();
+        let name = (1..)
+            .map(|n| format!("Bot {}", n))
+            .find(|name| lobby.players.values().all(|p| &p.name != name))
+            .unwrap_or_default();
+        if add_bot(lobby, player_id, name.clone(), weapon_data).is_err() {
+            break; // Slots held for a rematch
+        }
+        lobby.fill_bots.insert(player_id);
+        added.push((player_id, name));
+    }
+    (added, removed)
+}
+
 /// Remove a player from a lobby
 pub fn remove_player(lobby: &mut Lobby, player_id: u32) {
+    if lobby.spectators.remove(&player_id).is_some() {
+        lobby.client_addresses.remove(&player_id);
+        return;
+    }
+    owner::hand_over(lobby, player_id);
     lobby.players.remove(&player_id);
+    lobby.bots.remove(&player_id);
+    lobby.fill_bots.remove(&player_id);
     lobby.client_addresses.remove(&player_id);
     lobby.last_sync_state.remove(&player_id);
+    match &mut lobby.mode {
+        GameMode::GunGame(game) => game.remove(player_id),
+        GameMode::Horde(horde) => horde.remove(player_id),
+        GameMode::Ffa => {}
+    }
+}
+
+/// Add a spectator; full lobbies can still be watched up to `max_spectators`
+pub fn add_spectator(lobby: &mut Lobby, spectator_id: u32, name: String, now: Instant) -> Result<(), &'static str> {
+    if lobby.players.contains_key(&spectator_id) || lobby.spectators.contains_key(&spectator_id) {
+        return Err("Already in lobby");
+    }
+    if lobby.spectators.len() as u32 >= lobby.max_spectators {
+        return Err("Spectator slots full");
+    }
+    lobby.spectators.insert(spectator_id, Spectator { id: spectator_id, name, last_update: now, referee: false });
+    Ok(())
+}
+
+/// Add an admin as a referee: a spectator allowed to take referee actions (see `referee::apply`)
+/// Referees aren't turned away by a full spectator list
+pub fn add_referee(lobby: &mut Lobby, spectator_id: u32, name: String, now: Instant) -> Result<(), &'static str> {
+    if lobby.players.contains_key(&spectator_id) || lobby.spectators.contains_key(&spectator_id) {
+        return Err("Already in lobby");
+    }
+    lobby.spectators.insert(spectator_id, Spectator { id: spectator_id, name, last_update: now, referee: true });
+    Ok(())
+}
+
+/// Joined to watch rather than play
+pub fn is_spectator(lobby: &Lobby, id: u32) -> bool {
+    lobby.spectators.contains_key(&id)
+}
+
+/// Watching the match: spectators, and players eliminated for the rest of it (horde)
+pub fn is_spectating(lobby: &Lobby, id: u32) -> bool {
+    is_spectator(lobby, id) || horde::is_out(lobby, id)
+}
+
+/// Record a spectator's keepalive and where to reach them; false if they aren't spectating
+pub fn touch_spectator(lobby: &mut Lobby, spectator_id: u32, addr: SocketAddr, now: Instant) -> bool {
+    let Some(spectator) = lobby.spectators.get_mut(&spectator_id) else {
+        return false;
+    };
+    spectator.last_update = now;
+    lobby.client_addresses.insert(spectator_id, addr);
+    true
+}
+
+/// Remove spectators silent for `timeout_secs` (they hold no slot, so there's no warning or reconnect window)
+pub fn cleanup_spectators(lobby: &mut Lobby, timeout_secs: u64, now: Instant) -> Vec<u32> {
+    let silent: Vec<u32> = lobby
+        .spectators
+        .values()
+        .filter(|s| now.saturating_duration_since(s.last_update).as_secs() > timeout_secs)
+        .map(|s| s.id)
+        .collect();
+    for spectator_id in &silent {
+        remove_player(lobby, *spectator_id);
+    }
+    silent
+}
+
+/// Slowest and fastest game speed a lobby can be set to
+pub const MIN_TIME_SCALE: f32 = 0.1;
+pub const MAX_TIME_SCALE: f32 = 2.0;
+
+/// Change the game speed; running reload and respawn timers stretch (or shrink) to match
+pub fn set_time_scale(lobby: &mut Lobby, time_scale: f32, now: Instant) -> Result<(), &'static str> {
+    if !(MIN_TIME_SCALE..=MAX_TIME_SCALE).contains(&time_scale) {
+        return Err("Time scale out of range");
+    }
+    let stretch = lobby.time_scale / time_scale;
+    let rescale = |end: Instant| now + end.saturating_duration_since(now).mul_f32(stretch);
+    for player in lobby.players.values_mut() {
+        player.reload_end_time = player.reload_end_time.map(rescale);
+        player.respawn_time = player.respawn_time.map(rescale);
+    }
+    lobby.time_scale = time_scale;
+    lobby.lobby_events.push(owner::LobbyEvent::TimeScaleChanged { time_scale });
+    Ok(())
 }
 
 /// Update player position and rotation
@@ -84,9 +282,20 @@ pub fn update_position(
         .get_mut(&player_id)
         .ok_or("Player not found")?;
 
-    player.position = position;
-    player.rotation = rotation;
-    player.last_update = SystemTime::now();
+    // Frozen players stay put but still count as active
+    player.last_update = Instant::now();
+    if lobby.frozen {
+        return Ok(());
+    }
+
+    let position = lobby.dimensions.flatten(position);
+    if lobby.quantize_positions {
+        player.position = quantize::snap_position(position);
+        player.rotation = quantize::snap_rotation(rotation);
+    } else {
+        player.position = position;
+        player.rotation = rotation;
+    }
 
     lobby.mark_dirty(player_id);
     Ok(())
@@ -112,24 +321,21 @@ pub fn cleanup_inactive(
     timeout_secs: u64,
     warning_fraction: f64,
 ) -> (Vec<u32>, Vec<u32>) {
-    let now = SystemTime::now();
+    let now = Instant::now();
     let warning_threshold = (timeout_secs as f64 * warning_fraction) as u64;
     let mut inactive_players = Vec::new();
     let mut warned_players = Vec::new();
 
     for (player_id, player) in &lobby.players {
-        if *player_id == 999 {
+        if lobby.bots.contains_key(player_id) || player.connection != Connection::Connected {
             continue;
         }
 
-        if let Ok(duration) = now.duration_since(player.last_update) {
-            let elapsed_secs = duration.as_secs();
-
-            if elapsed_secs > timeout_secs {
-                inactive_players.push(*player_id);
-            } else if elapsed_secs > warning_threshold && player.warned_at.is_none() {
-                warned_players.push(*player_id);
-            }
+        let elapsed_secs = now.saturating_duration_since(player.last_update).as_secs();
+        if elapsed_secs > timeout_secs {
+            inactive_players.push(*player_id);
+        } else if elapsed_secs > warning_threshold && player.warned_at.is_none() {
+            warned_players.push(*player_id);
         }
     }
 
@@ -146,9 +352,192 @@ pub fn cleanup_inactive(
     (inactive_players, warned_players)
 }
 
+/// Hold the slots of players silent for `timeout_secs` instead of removing them: they are marked
+/// disconnected (and no longer sent to) until they `reconnect` or `window_secs` runs out
+/// Without a window nobody new is held (`cleanup_inactive` removes them) and anyone still held is removed
+/// Returns (newly disconnected, removed after the window)
+pub fn hold_disconnected(
+    lobby: &mut Lobby,
+    timeout_secs: u64,
+    window_secs: u64,
+    now: Instant,
+) -> (Vec<u32>, Vec<u32>) {
+    let mut disconnected = Vec::new();
+    let mut expired = Vec::new();
+    for (player_id, player) in &lobby.players {
+        if lobby.bots.contains_key(player_id) {
+            continue;
+        }
+        match player.connection {
+            Connection::Connected if window_secs > 0 && now.saturating_duration_since(player.last_update).as_secs() > timeout_secs => {
+                disconnected.push(*player_id);
+            }
+            Connection::Disconnected { since } if now.saturating_duration_since(since).as_secs() >= window_secs => {
+                expired.push(*player_id);
+            }
+            _ => {}
+        }
+    }
+
+    for player_id in &disconnected {
+        if let Some(player) = lobby.players.get_mut(player_id) {
+            player.connection = Connection::Disconnected { since: now };
+        }
+        lobby.client_addresses.remove(player_id);
+    }
+    for player_id in &expired {
+        remove_player(lobby, *player_id);
+    }
+    (disconnected, expired)
+}
+
+/// A (possibly disconnected) player's client is back at `addr`
+pub fn reconnect(lobby: &mut Lobby, player_id: u32, addr: SocketAddr, now: Instant) -> Result<(), &'static str> {
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    player.connection = Connection::Connected;
+    player.last_update = now;
+    player.warned_at = None;
+    lobby.client_addresses.insert(player_id, addr);
+    lobby.mark_dirty(player_id);
+    Ok(())
+}
+
+/// Hold a joining player back while their client loads the scene, for at most `timeout`
+/// Until `finish_loading` they can't be hit, aren't shown to the others and their commands are ignored
+/// Bots never load; a zero timeout skips the gate
+pub fn begin_loading(lobby: &mut Lobby, player_id: u32, timeout: Duration, now: Instant) {
+    if timeout.is_zero() || lobby.bots.contains_key(&player_id) {
+        return;
+    }
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.loading_until = Some(now + timeout);
+    }
+}
+
+/// Whether a player's client is still loading the scene
+pub fn is_loading(lobby: &Lobby, player_id: u32) -> bool {
+    lobby.players.get(&player_id).is_some_and(|p| p.is_loading())
+}
+
+/// The client finished loading; false if the player wasn't loading
+pub fn finish_loading(lobby: &mut Lobby, player_id: u32) -> bool {
+    let Some(player) = lobby.players.get_mut(&player_id) else {
+        return false;
+    };
+    if player.loading_until.take().is_none() {
+        return false;
+    }
+    // Everyone else gets the player's state now that they can see them
+    lobby.mark_dirty(player_id);
+    true
+}
+
+/// Force-complete loading for players past their deadline; returns them
+pub fn expire_loading(lobby: &mut Lobby, now: Instant) -> Vec<u32> {
+    let mut expired: Vec<u32> = lobby
+        .players
+        .values()
+        .filter(|p| p.loading_until.is_some_and(|deadline| now >= deadline))
+        .map(|p| p.id)
+        .collect();
+    expired.sort_unstable();
+    for player_id in &expired {
+        finish_loading(lobby, *player_id);
+    }
+    expired
+}
+
+/// Hold `count` slots until `expires`; each returned token claims one of them
+pub fn reserve_slots(lobby: &mut Lobby, count: usize, expires: Instant) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let token = crypto::generate_token();
+            lobby.reservations.insert(token.clone(), expires);
+            token
+        })
+        .collect()
+}
+
+/// Give up a held slot so its holder can join; false for unknown or expired tokens
+pub fn claim_reservation(lobby: &mut Lobby, token: &str, now: Instant) -> bool {
+    lobby.reservations.retain(|_, expires| *expires > now);
+    lobby.reservations.remove(token).is_some()
+}
+
+/// Slots still held for rematch players
+pub fn held_reservations(lobby: &Lobby, now: Instant) -> usize {
+    lobby.reservations.values().filter(|expires| **expires > now).count()
+}
+
+/// Move the human players and spectators of `source` into `target`, keeping their state
+/// (health, inventory, score, team, gun game level); bots stay behind with the source
+/// Both lobbies must play the same scene and mode, and `target` must have room for everyone
+/// Spectators come along even past `max_spectators`, like referees
+/// Returns the moved ids; each is queued in `target.arrivals` to be told `migrate_to`
+pub fn merge_into(source: &mut Lobby, target: &mut Lobby, now: Instant) -> Result<Vec<u32>, &'static str> {
+    if source.code == target.code {
+        return Err("Can't merge a lobby into itself");
+    }
+    if source.persistent {
+        return Err("Persistent lobbies can't be merged away");
+    }
+    if source.scene != target.scene || source.dimensions != target.dimensions {
+        return Err("Lobbies are on different scenes");
+    }
+    if source.mode.as_str() != target.mode.as_str() || source.team_count != target.team_count {
+        return Err("Lobbies play different modes");
+    }
+    match (&source.mode, &target.mode) {
+        (GameMode::GunGame(from), GameMode::GunGame(to)) if from.ladder != to.ladder => {
+            return Err("Lobbies use different gun game ladders");
+        }
+        (GameMode::Horde(_), _) => return Err("Horde lobbies can't be merged"),
+        _ => {}
+    }
+
+    let mut humans: Vec<u32> = source.players.keys().filter(|id| !source.bots.contains_key(id)).copied().collect();
+    humans.sort_unstable();
+    if occupied_slots(target) + held_reservations(target, now) + humans.len() > target.max_players as usize {
+        return Err("Not enough room in the target lobby");
+    }
+
+    for player_id in &humans {
+        let Some(mut player) = source.players.remove(player_id) else {
+            continue;
+        };
+        player.last_update = now;
+        target.players.insert(*player_id, player);
+        if let (GameMode::GunGame(from), GameMode::GunGame(to)) = (&mut source.mode, &mut target.mode) {
+            gungame::transfer(from, to, *player_id);
+        }
+        source.last_sync_state.remove(player_id);
+        target.mark_dirty(*player_id);
+    }
+    target.analytics.peak_players = target.analytics.peak_players.max(target.players.len());
+
+    let mut spectators: Vec<u32> = source.spectators.keys().copied().collect();
+    spectators.sort_unstable();
+    for spectator_id in &spectators {
+        if let Some(mut spectator) = source.spectators.remove(spectator_id) {
+            spectator.last_update = now;
+            target.spectators.insert(*spectator_id, spectator);
+        }
+    }
+
+    let moved: Vec<u32> = humans.into_iter().chain(spectators).collect();
+    for id in &moved {
+        if let Some(addr) = source.client_addresses.remove(id) {
+            target.client_addresses.insert(*id, addr);
+        }
+        target.arrivals.push((*id, source.code.clone()));
+    }
+    Ok(moved)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::scenedb::SceneDb;
     use crate::utils::weapondb::WeaponDb;
 
     #[test]
@@ -174,6 +563,40 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_reserved_slots_count_as_taken() {
+        let mut lobby = Lobby::new("TEST".to_string(), 2, "world".to_string());
+        let weapons = WeaponDb::load();
+        let now = Instant::now();
+        let tokens = reserve_slots(&mut lobby, 2, now + std::time::Duration::from_secs(60));
+
+        assert_eq!(add_player(&mut lobby, 1, "Stranger".to_string(), 1, &weapons), Err("Lobby is full"));
+        assert!(claim_reservation(&mut lobby, &tokens[0], now));
+        assert!(!claim_reservation(&mut lobby, &tokens[0], now));
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        assert_eq!(held_reservations(&lobby, now), 1);
+
+        // Expired slots are free again
+        assert_eq!(held_reservations(&lobby, now + std::time::Duration::from_secs(61)), 0);
+        assert!(!claim_reservation(&mut lobby, &tokens[1], now + std::time::Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_joining_players_spawn_apart() {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        lobby.spawn_points = SceneDb::load().spawn_points("world");
+        let weapons = WeaponDb::load();
+
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        add_player(&mut lobby, 2, "Player2".to_string(), 1, &weapons).unwrap();
+
+        let first = lobby.players.get(&1).unwrap().position;
+        let second = lobby.players.get(&2).unwrap().position;
+        assert!(lobby.spawn_points.contains(&first) && lobby.spawn_points.contains(&second));
+        // Across the map from each other
+        assert!(crate::domain::simulator::distance(first, second) >= 12.0);
+    }
+
     #[test]
     fn test_remove_player() {
         let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
@@ -186,6 +609,37 @@ mod tests {
         assert_eq!(lobby.players.len(), 0);
     }
 
+    #[test]
+    fn test_fill_bots_make_way_for_players() {
+        let mut lobby = Lobby::new("TEST".to_string(), 6, "world".to_string());
+        lobby.bot_fill = 3;
+        let weapons = WeaponDb::load();
+        let mut ids = 100..;
+        let mut next_id = || ids.next().unwrap();
+        add_bot(&mut lobby, 50, "Bot 1".to_string(), &weapons).unwrap(); // Added on creation, never removed
+
+        let (added, removed) = fill_bots(&mut lobby, &weapons, &mut next_id);
+        assert_eq!(added, vec![(100, "Bot 2".to_string()), (101, "Bot 3".to_string())]);
+        assert!(removed.is_empty());
+        assert_eq!(fill_bots(&mut lobby, &weapons, &mut next_id), (vec![], vec![]));
+
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        add_player(&mut lobby, 2, "Player2".to_string(), 1, &weapons).unwrap();
+        assert_eq!(fill_bots(&mut lobby, &weapons, &mut next_id), (vec![], vec![101, 100]));
+        assert_eq!(lobby.players.len(), 3);
+        add_player(&mut lobby, 3, "Player3".to_string(), 1, &weapons).unwrap();
+        assert_eq!(fill_bots(&mut lobby, &weapons, &mut next_id), (vec![], vec![]));
+        assert!(lobby.bots.contains_key(&50));
+
+        // Players leaving bring the bots back
+        remove_player(&mut lobby, 1);
+        remove_player(&mut lobby, 2);
+        remove_player(&mut lobby, 3);
+        let (added, _) = fill_bots(&mut lobby, &weapons, &mut next_id);
+        assert_eq!(added.len(), 2);
+        assert_eq!(lobby.fill_bots.len(), 2);
+    }
+
     #[test]
     fn test_update_position() {
         let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
@@ -201,6 +655,20 @@ mod tests {
         assert!(lobby.dirty_players.contains(&1));
     }
 
+    #[test]
+    fn test_update_position_quantized() {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        lobby.quantize_positions = true;
+        let weapons = WeaponDb::load();
+
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        update_position(&mut lobby, 1, (10.12345, 2.0, -5.0004), (0.0, std::f32::consts::FRAC_PI_2, 0.0)).unwrap();
+
+        let player = lobby.players.get(&1).unwrap();
+        assert_eq!(player.position, (10.123, 2.0, -5.0));
+        assert_eq!(player.rotation, (0.0, 1.571, 0.0));
+    }
+
     #[test]
     fn test_cleanup_inactive() {
         let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
@@ -210,7 +678,7 @@ mod tests {
 
         // Manually set old update time
         if let Some(player) = lobby.players.get_mut(&1) {
-            player.last_update = SystemTime::now() - std::time::Duration::from_secs(20);
+            player.last_update = Instant::now() - std::time::Duration::from_secs(20);
         }
 
         let (removed, _) = cleanup_inactive(&mut lobby, 15, 0.5);
@@ -218,4 +686,157 @@ mod tests {
         assert_eq!(removed[0], 1);
         assert_eq!(lobby.players.len(), 0);
     }
+
+    #[test]
+    fn test_silent_players_are_held_for_reconnect() {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let weapons = WeaponDb::load();
+        let now = Instant::now();
+        let addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        lobby.client_addresses.insert(1, addr);
+        lobby.players.get_mut(&1).unwrap().last_update = now - std::time::Duration::from_secs(20);
+
+        assert_eq!(hold_disconnected(&mut lobby, 15, 30, now), (vec![1], vec![]));
+        assert_eq!(lobby.players.get(&1).unwrap().connection, Connection::Disconnected { since: now });
+        assert!(!lobby.client_addresses.contains_key(&1));
+        // The inactivity cleanup leaves held players alone
+        assert_eq!(cleanup_inactive(&mut lobby, 15, 0.5).0, Vec::<u32>::new());
+
+        let moved: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        reconnect(&mut lobby, 1, moved, now + std::time::Duration::from_secs(10)).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().connection, Connection::Connected);
+        assert_eq!(lobby.client_addresses.get(&1), Some(&moved));
+
+        // Silent again, and this time the window runs out
+        let later = now + std::time::Duration::from_secs(30);
+        assert_eq!(hold_disconnected(&mut lobby, 15, 30, later), (vec![1], vec![]));
+        assert_eq!(hold_disconnected(&mut lobby, 15, 30, later + std::time::Duration::from_secs(30)), (vec![], vec![1]));
+        assert!(lobby.players.is_empty());
+        assert_eq!(reconnect(&mut lobby, 1, moved, later), Err("Player not found"));
+    }
+
+    #[test]
+    fn test_spectators_watch_full_lobbies() {
+        let mut lobby = Lobby::new("TEST".to_string(), 1, "world".to_string());
+        lobby.max_spectators = 1;
+        let weapons = WeaponDb::load();
+        let now = Instant::now();
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        assert!(add_player(&mut lobby, 2, "Player2".to_string(), 1, &weapons).is_err());
+
+        add_spectator(&mut lobby, 2, "Watcher".to_string(), now).unwrap();
+        assert_eq!(add_spectator(&mut lobby, 3, "Another".to_string(), now), Err("Spectator slots full"));
+        assert_eq!(add_spectator(&mut lobby, 1, "Player1".to_string(), now), Err("Already in lobby"));
+        assert!(is_spectator(&lobby, 2) && !lobby.players.contains_key(&2));
+
+        let addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        assert!(touch_spectator(&mut lobby, 2, addr, now));
+        assert!(!touch_spectator(&mut lobby, 1, addr, now));
+        assert_eq!(lobby.client_addresses.get(&2), Some(&addr));
+
+        assert!(cleanup_spectators(&mut lobby, 15, now + std::time::Duration::from_secs(10)).is_empty());
+        assert_eq!(cleanup_spectators(&mut lobby, 15, now + std::time::Duration::from_secs(20)), vec![2]);
+        assert!(lobby.spectators.is_empty() && !lobby.client_addresses.contains_key(&2));
+        assert_eq!(lobby.players.len(), 1);
+    }
+
+    #[test]
+    fn test_time_scale_stretches_timers() {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let weapons = WeaponDb::load();
+        let now = Instant::now();
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        lobby.players.get_mut(&1).unwrap().current_ammo = 0;
+
+        // Half speed: a reload takes twice as long, and one already running stretches too
+        logic::start_reload_at(&mut lobby, &weapons, 1, now).unwrap();
+        set_time_scale(&mut lobby, 0.5, now).unwrap();
+        let reload = weapons.get(1).unwrap().reload_time;
+        let end = lobby.players[&1].reload_end_time.unwrap();
+        assert!((end.duration_since(now).as_secs_f32() - reload * 2.0).abs() < 1e-3);
+        assert_eq!(lobby.scaled(std::time::Duration::from_secs(3)), std::time::Duration::from_secs(6));
+
+        assert_eq!(set_time_scale(&mut lobby, 0.0, now), Err("Time scale out of range"));
+        assert_eq!(set_time_scale(&mut lobby, f32::NAN, now), Err("Time scale out of range"));
+        assert_eq!(lobby.time_scale, 0.5);
+        assert_eq!(lobby.lobby_events, vec![owner::LobbyEvent::TimeScaleChanged { time_scale: 0.5 }]);
+    }
+
+    #[test]
+    fn test_loading_players_are_out_of_play_until_loaded() {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let weapons = WeaponDb::load();
+        let now = Instant::now();
+        add_player(&mut lobby, 1, "Player1".to_string(), 1, &weapons).unwrap();
+        add_player(&mut lobby, 2, "Player2".to_string(), 1, &weapons).unwrap();
+        begin_loading(&mut lobby, 2, Duration::from_secs(15), now);
+        begin_loading(&mut lobby, 1, Duration::ZERO, now); // No gate
+        assert!(is_loading(&lobby, 2) && !is_loading(&lobby, 1));
+
+        assert_eq!(logic::apply_damage(&mut lobby, 2, 25), Err("Player is still loading"));
+        assert_eq!(crate::domain::matches::ready_count(&lobby), (0, 1));
+
+        assert!(expire_loading(&mut lobby, now + Duration::from_secs(14)).is_empty());
+        lobby.dirty_players.clear();
+        assert!(finish_loading(&mut lobby, 2));
+        assert!(!finish_loading(&mut lobby, 2));
+        assert!(lobby.dirty_players.contains(&2));
+        assert!(logic::apply_damage(&mut lobby, 2, 25).is_ok());
+
+        // A client that never reports in is let in at the deadline
+        begin_loading(&mut lobby, 1, Duration::from_secs(15), now);
+        assert_eq!(expire_loading(&mut lobby, now + Duration::from_secs(15)), vec![1]);
+        assert!(!is_loading(&lobby, 1));
+    }
+
+    #[test]
+    fn test_merge_moves_humans_with_their_state() {
+        let weapons = WeaponDb::load();
+        let ladder = gungame::default_ladder(&weapons);
+        let now = Instant::now();
+        let mut source = Lobby::new("alpha".to_string(), 4, "world".to_string());
+        let mut target = Lobby::new("beta".to_string(), 4, "world".to_string());
+        source.mode = GameMode::GunGame(gungame::GunGame::new(ladder.clone()));
+        target.mode = GameMode::GunGame(gungame::GunGame::new(ladder.clone()));
+        add_player(&mut source, 1, "Ada".to_string(), 1, &weapons).unwrap();
+        add_bot(&mut source, 2, "Bot".to_string(), &weapons).unwrap();
+        source.max_spectators = 1;
+        add_spectator(&mut source, 3, "Watcher".to_string(), now).unwrap();
+        add_player(&mut target, 4, "Bob".to_string(), 1, &weapons).unwrap();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        set_player_address(&mut source, 1, addr).unwrap();
+        gungame::on_kill(&mut source, &weapons, 1).unwrap();
+        source.players.get_mut(&1).unwrap().kills = 1;
+
+        assert_eq!(merge_into(&mut source, &mut target, now), Ok(vec![1, 3]));
+        let ada = &target.players[&1];
+        assert_eq!((ada.kills, ada.current_weapon_id), (1, ladder[1]));
+        assert!(matches!(&target.mode, GameMode::GunGame(game) if game.level(1) == 1));
+        assert!(target.spectators.contains_key(&3));
+        assert_eq!(target.client_addresses.get(&1), Some(&addr));
+        assert_eq!(target.arrivals, vec![(1, "alpha".to_string()), (3, "alpha".to_string())]);
+        // The bot stays behind
+        assert_eq!(source.players.keys().collect::<Vec<_>>(), vec![&2]);
+        assert!(!target.players.contains_key(&2));
+    }
+
+    #[test]
+    fn test_merge_refuses_mismatched_or_full_lobbies() {
+        let weapons = WeaponDb::load();
+        let now = Instant::now();
+        let mut source = Lobby::new("alpha".to_string(), 4, "world".to_string());
+        add_player(&mut source, 1, "Ada".to_string(), 1, &weapons).unwrap();
+        add_player(&mut source, 2, "Cid".to_string(), 1, &weapons).unwrap();
+
+        let mut elsewhere = Lobby::new("beta".to_string(), 4, "test_world".to_string());
+        assert_eq!(merge_into(&mut source, &mut elsewhere, now), Err("Lobbies are on different scenes"));
+        let mut gun_game = Lobby::new("beta".to_string(), 4, "world".to_string());
+        gun_game.mode = GameMode::GunGame(gungame::GunGame::new(gungame::default_ladder(&weapons)));
+        assert_eq!(merge_into(&mut source, &mut gun_game, now), Err("Lobbies play different modes"));
+        let mut small = Lobby::new("beta".to_string(), 2, "world".to_string());
+        add_player(&mut small, 3, "Bob".to_string(), 1, &weapons).unwrap();
+        assert_eq!(merge_into(&mut source, &mut small, now), Err("Not enough room in the target lobby"));
+        assert_eq!(source.players.len(), 2);
+    }
 }
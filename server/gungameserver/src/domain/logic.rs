@@ -1,6 +1,21 @@
-use crate::state::lobby::{Lobby, PlayerSyncState};
+use crate::state::lobby::{Lobby, Magazine, PlayerSyncState};
+use crate::domain::anticheat::AuditedShot;
+use crate::domain::clock;
+use crate::domain::horde;
+use crate::domain::mutators;
+use crate::domain::simulator;
+use crate::domain::teams;
 use crate::utils::weapondb::WeaponDb;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
+
+/// Max angle between the shooter's facing and the target for a hitscan hit
+const MAX_HITSCAN_AIM_ANGLE_DEG: f32 = 45.0;
+
+/// Melee swings cover a wider arc than hitscan aim
+const MAX_MELEE_AIM_ANGLE_DEG: f32 = 75.0;
+
+/// Melee swings hit a bigger sphere than bullets
+const MELEE_HITBOX_SCALE: f32 = 2.0;
 
 /// Kill event data for broadcasting
 #[derive(Debug, Clone)]
@@ -14,75 +29,240 @@ pub struct KillEvent {
     pub killer_new_killstreak: u32,
 }
 
-/// Try to shoot - validates ammo, fire rate, reload state
+/// Shot fired this tick, echoed to other clients for effects
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShotEvent {
+    pub shooter_id: u32,
+    pub target_id: Option<u32>, // Player in the line of fire; None for a miss
+    pub weapon_id: u32,
+    pub hit: bool, // Damaged the target (false for misses and blocked team hits)
+}
+
+/// Damage a hit dealt this tick, broadcast so clients can show hit markers and damage numbers
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageEvent {
+    pub attacker_id: u32,
+    pub victim_id: u32,
+    pub damage: u32, // Health lost, after every multiplier
+    pub multiplier: f32, // Weapon factor for the hit zone and distance
+    pub zone: simulator::HitZone,
+    pub distance: f32,
+}
+
+/// Try to shoot at `now` - validates ammo, fire rate, reload state
 /// Returns true if shot was successful
-pub fn try_shoot(
+pub fn try_shoot_at(
     lobby: &mut Lobby,
     weapons: &WeaponDb,
     player_id: u32,
+    now: Instant,
 ) -> Result<bool, &'static str> {
+    let time_scale = lobby.time_scale;
     let player = lobby
         .players
         .get_mut(&player_id)
         .ok_or("Player not found")?;
 
-    // Check if player is reloading
-    if player.is_reloading {
-        return Ok(false);
-    }
-
-    // Check ammo
-    if player.current_ammo == 0 {
+    // Dead and reloading players can't fire
+    if player.is_dead || player.is_reloading {
         return Ok(false);
     }
 
-    // Check fire rate
     let weapon = weapons
         .get(player.current_weapon_id)
         .ok_or("Invalid weapon")?;
 
-    let now = SystemTime::now();
-    let time_since_last_shot = now
-        .duration_since(player.last_shot_time)
-        .map_err(|_| "Time error")?;
+    // Check ammo (melee weapons don't use any)
+    if !weapon.is_melee() && player.current_ammo == 0 {
+        return Ok(false);
+    }
+
+    // Check fire rate
 
-    if time_since_last_shot.as_secs_f32() < (1.0 / weapon.fire_rate) {
-        return Ok(false); // Too soon to shoot again
+    if let Some(last_shot) = player.last_shot_time {
+        if now.saturating_duration_since(last_shot).as_secs_f32() < (1.0 / weapon.fire_rate) / time_scale {
+            return Ok(false); // Too soon to shoot again
+        }
     }
 
     // Consume ammo
     player.current_ammo = player.current_ammo.saturating_sub(1);
-    player.last_shot_time = now;
+    player.last_shot_time = Some(now);
 
+    lobby.analytics.total_shots += 1;
     lobby.mark_dirty(player_id);
     Ok(true)
 }
 
+/// Validate a claimed hit: target must be within weapon range and in front of the shooter
+/// Uses the shooter's yaw/pitch facing; melee allows a wider arc than hitscan
+pub fn validate_hit(
+    lobby: &Lobby,
+    weapons: &WeaponDb,
+    shooter_id: u32,
+    target_id: u32,
+) -> Result<(), &'static str> {
+    let shooter = lobby.players.get(&shooter_id).ok_or("Shooter not found")?;
+    let target = lobby.players.get(&target_id).ok_or("Target not found")?;
+    let weapon = weapons
+        .get(shooter.current_weapon_id)
+        .ok_or("Invalid weapon")?;
+
+    if !teams::allows_damage(lobby, shooter_id, target_id) {
+        return Err("Friendly fire is off");
+    }
+
+    if simulator::distance(shooter.position, target.position) > weapon.range {
+        return Err("Target out of range");
+    }
+
+    let facing = simulator::facing_direction(shooter.yaw(), shooter.pitch());
+    let max_angle = if weapon.is_melee() {
+        MAX_MELEE_AIM_ANGLE_DEG
+    } else {
+        MAX_HITSCAN_AIM_ANGLE_DEG
+    };
+
+    // Overlapping players can always hit each other
+    if let Some(angle) = simulator::aim_angle(facing, shooter.position, target.position) {
+        if angle > max_angle.to_radians() {
+            return Err("Target not in front of shooter");
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve a client's shot server-side: the nearest live player whose hitbox the ray from
+/// `origin` along `direction` hits within the weapon's range, behind no occluder
+/// Targets are where they were at `rewind_to` (lag compensation), else where they are now
+pub fn hitscan_at(
+    lobby: &Lobby,
+    weapons: &WeaponDb,
+    shooter_id: u32,
+    origin: (f32, f32, f32),
+    direction: (f32, f32, f32),
+    rewind_to: Option<Instant>,
+) -> Result<Option<simulator::HitResult>, &'static str> {
+    let shooter = lobby.players.get(&shooter_id).ok_or("Shooter not found")?;
+    let weapon = weapons
+        .get(shooter.current_weapon_id)
+        .ok_or("Invalid weapon")?;
+
+    // 2D lobbies: the shot flies flat, so only x/z distances matter
+    let origin = lobby.dimensions.flatten(shot_origin(lobby, shooter.position, origin));
+    let direction = lobby.dimensions.flatten_direction(direction);
+    let radius = if weapon.is_melee() {
+        lobby.tunables.hitbox_radius * MELEE_HITBOX_SCALE
+    } else {
+        lobby.tunables.hitbox_radius
+    };
+
+    let targets = lobby
+        .players
+        .values()
+        .filter(|p| p.id != shooter_id && !p.is_dead && !p.is_loading())
+        .map(|p| {
+            let position = rewind_to
+                .and_then(|time| lobby.history.position_at(p.id, time))
+                .unwrap_or(p.position);
+            (p.id, lobby.dimensions.flatten(position))
+        });
+    Ok(simulator::perform_hitscan(&lobby.collision, targets, origin, direction, weapon.range, radius))
+}
+
+/// Where a shot starts: the client's eye position may differ a little from the server's,
+/// anything further is not trusted and the shooter's server position is used
+pub fn shot_origin(lobby: &Lobby, shooter_position: (f32, f32, f32), origin: (f32, f32, f32)) -> (f32, f32, f32) {
+    if lobby.dimensions.distance(origin, shooter_position) <= lobby.tunables.max_shot_origin_offset {
+        origin
+    } else {
+        shooter_position
+    }
+}
+
+/// Queue a fired shot (with the shooter's current weapon) for the effects broadcast
+/// and the shooter's shot audit
+pub fn record_shot(lobby: &mut Lobby, shooter_id: u32, target_id: Option<u32>, hit: bool) {
+    let Some(shooter) = lobby.players.get_mut(&shooter_id) else {
+        return;
+    };
+    let weapon_id = shooter.current_weapon_id;
+    let (yaw, pitch) = (shooter.yaw(), shooter.pitch());
+    shooter.shot_audit.record(AuditedShot { at_ms: clock::epoch_ms(SystemTime::now()), weapon_id, target_id, hit, yaw, pitch });
+    lobby.shot_feed.push(ShotEvent { shooter_id, target_id, weapon_id, hit });
+}
+
 /// Apply damage to a player
-pub fn apply_damage(lobby: &mut Lobby, target_id: u32, damage: u32) -> Result<(), &'static str> {
+/// Returns the health the player lost, after lobby-wide multipliers
+pub fn apply_damage(lobby: &mut Lobby, target_id: u32, damage: u32) -> Result<u32, &'static str> {
+    let max_damage = lobby.tunables.max_damage;
+    let multiplier = mutators::damage_multiplier(lobby);
     let player = lobby
         .players
         .get_mut(&target_id)
         .ok_or("Player not found")?;
 
+    if player.is_dead {
+        return Err("Player is dead");
+    }
+    if player.is_loading() {
+        return Err("Player is still loading");
+    }
+
     // Validate damage is reasonable
-    if damage == 0 || damage > 100 {
+    if damage == 0 || damage > max_damage {
         return Err("Invalid damage amount");
     }
 
     // Apply damage with underflow protection
-    player.current_health = player.current_health.saturating_sub(damage);
+    let damage = damage.saturating_mul(multiplier);
+    let dealt = damage.min(player.current_health);
+    player.current_health -= dealt;
 
     lobby.mark_dirty(target_id);
-    Ok(())
+    Ok(dealt)
 }
 
-/// Start player reload
-pub fn start_reload(
+/// Damage a target with the shooter's weapon after a successful, validated shot
+/// The weapon's zone multipliers and falloff scale the damage, up to the largest hit the server accepts
+/// Registers the kill when the target drops to 0 health
+pub fn resolve_hit_at(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    shooter_id: u32,
+    target_id: u32,
+    zone: simulator::HitZone,
+    distance: f32,
+    now: Instant,
+) -> Result<Option<KillEvent>, &'static str> {
+    if shooter_id == target_id {
+        return Err("Cannot hit yourself");
+    }
+    let max_damage = lobby.tunables.max_damage;
+    let (damage, multiplier) = lobby
+        .players
+        .get(&shooter_id)
+        .and_then(|shooter| weapons.get(shooter.current_weapon_id))
+        .map(|weapon| (weapon.hit_damage(zone, distance).min(max_damage).max(1), weapon.damage_multiplier(zone, distance)))
+        .ok_or("Shooter not found")?;
+
+    let dealt = apply_damage(lobby, target_id, damage)?;
+    lobby.damage_feed.push(DamageEvent { attacker_id: shooter_id, victim_id: target_id, damage: dealt, multiplier, zone, distance });
+    if is_player_alive(lobby, target_id) {
+        return Ok(None);
+    }
+    register_kill_at(lobby, weapons, shooter_id, target_id, now).map(Some)
+}
+
+/// Start player reload at `now`
+pub fn start_reload_at(
     lobby: &mut Lobby,
     weapons: &WeaponDb,
     player_id: u32,
+    now: Instant,
 ) -> Result<(), &'static str> {
+    let reload_scale = lobby.time_scale;
     let player = lobby
         .players
         .get_mut(&player_id)
@@ -99,16 +279,15 @@ pub fn start_reload(
 
     player.is_reloading = true;
     player.reload_end_time =
-        Some(SystemTime::now() + std::time::Duration::from_secs_f32(weapon.reload_time));
+        Some(now + std::time::Duration::from_secs_f32(weapon.reload_time / reload_scale));
 
     lobby.mark_dirty(player_id);
     Ok(())
 }
 
-/// Update reload states - check and complete finished reloads
+/// Update reload states - check and complete reloads finished by `now`
 /// Returns list of (player_id) that completed reload
-pub fn update_reload_states(lobby: &mut Lobby) -> Vec<u32> {
-    let now = SystemTime::now();
+pub fn update_reload_states_at(lobby: &mut Lobby, now: Instant) -> Vec<u32> {
     let mut completed_reloads = Vec::new();
 
     // First pass: update reload states
@@ -135,6 +314,7 @@ pub fn update_reload_states(lobby: &mut Lobby) -> Vec<u32> {
 }
 
 /// Switch player weapon
+/// Only owned weapons can be drawn; each keeps its magazine, so switching never refills ammo
 pub fn switch_weapon(
     lobby: &mut Lobby,
     weapons: &WeaponDb,
@@ -146,16 +326,22 @@ pub fn switch_weapon(
         .get_mut(&player_id)
         .ok_or("Player not found")?;
 
-    // Validate weapon exists
-    if !weapons.contains(weapon_id) {
-        return Err("Invalid weapon");
+    // Validate weapon exists and is owned
+    let weapon = weapons.get(weapon_id).ok_or("Invalid weapon")?;
+    if !weapon.is_melee() && !player.inventory.contains_key(&weapon_id) {
+        return Err("Weapon not owned");
     }
 
-    // Update player's weapon and reset ammo
-    let weapon = weapons.get(weapon_id).unwrap();
+    // Put the held weapon away with whatever is left in it
+    let held = Magazine { ammo: player.current_ammo, max_ammo: player.max_ammo };
+    if let Some(magazine) = player.inventory.get_mut(&player.current_weapon_id) {
+        *magazine = held;
+    }
+
+    let magazine = player.inventory.get(&weapon_id).copied().unwrap_or(Magazine::full(weapon.ammo));
     player.current_weapon_id = weapon_id;
-    player.current_ammo = weapon.ammo;
-    player.max_ammo = weapon.ammo;
+    player.current_ammo = magazine.ammo;
+    player.max_ammo = magazine.max_ammo;
 
     // Cancel any ongoing reload
     player.is_reloading = false;
@@ -165,6 +351,41 @@ pub fn switch_weapon(
     Ok(())
 }
 
+/// Give a player a weapon with a full magazine (refilling it if already owned)
+/// Doesn't draw it; a held weapon is refilled in hand
+pub fn grant_weapon(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    player_id: u32,
+    weapon_id: u32,
+) -> Result<(), &'static str> {
+    let weapon = weapons.get(weapon_id).ok_or("Invalid weapon")?;
+    let player = lobby
+        .players
+        .get_mut(&player_id)
+        .ok_or("Player not found")?;
+
+    player.inventory.insert(weapon_id, Magazine::full(weapon.ammo));
+    if player.current_weapon_id == weapon_id {
+        player.current_ammo = weapon.ammo;
+        player.max_ammo = weapon.ammo;
+    }
+
+    lobby.mark_dirty(player_id);
+    Ok(())
+}
+
+/// Grant a weapon and draw it straight away (server-driven loadouts)
+pub fn equip_weapon(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    player_id: u32,
+    weapon_id: u32,
+) -> Result<(), &'static str> {
+    grant_weapon(lobby, weapons, player_id, weapon_id)?;
+    switch_weapon(lobby, weapons, player_id, weapon_id)
+}
+
 /// Get player's current sync state
 pub fn get_player_state(lobby: &Lobby, player_id: u32) -> Result<PlayerSyncState, &'static str> {
     let player = lobby.players.get(&player_id).ok_or("Player not found")?;
@@ -187,10 +408,24 @@ pub fn register_kill(
     weapons: &WeaponDb,
     killer_id: u32,
     victim_id: u32,
+) -> Result<KillEvent, &'static str> {
+    register_kill_at(lobby, weapons, killer_id, victim_id, Instant::now())
+}
+
+/// `register_kill` at an explicit time (headless simulation)
+pub fn register_kill_at(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    killer_id: u32,
+    victim_id: u32,
+    now: Instant,
 ) -> Result<KillEvent, &'static str> {
     let (weapon_id, killer_name, victim_name, weapon_name, killer_killstreak) = {
         let killer = lobby.players.get(&killer_id).ok_or("Killer not found")?;
         let victim = lobby.players.get(&victim_id).ok_or("Victim not found")?;
+        if victim.is_dead {
+            return Err("Victim already dead");
+        }
         let weapon = weapons
             .get(killer.current_weapon_id)
             .ok_or("Invalid weapon")?;
@@ -204,19 +439,20 @@ pub fn register_kill(
         )
     };
 
+    let base_score = 100;
+    let killstreak_bonus = std::cmp::min(killer_killstreak, 5) * 25;
     {
         let killer = lobby
             .players
             .get_mut(&killer_id)
             .ok_or("Killer not found")?;
-        let base_score = 100;
-        let killstreak_bonus = std::cmp::min(killer_killstreak, 5) * 25;
-
         killer.kills += 1;
         killer.killstreak = killer_killstreak + 1;
         killer.score += base_score + killstreak_bonus;
     }
+    teams::on_kill(lobby, killer_id, victim_id, base_score + killstreak_bonus);
 
+    let respawn_delay = lobby.scaled(lobby.tunables.respawn_delay());
     {
         let victim = lobby
             .players
@@ -226,7 +462,9 @@ pub fn register_kill(
         victim.killstreak = 0;
         victim.current_health = 0;
         victim.is_dead = true;
-        victim.respawn_time = Some(SystemTime::now() + std::time::Duration::from_secs(3));
+        victim.respawn_time = Some(now + respawn_delay);
+        victim.is_reloading = false;
+        victim.reload_end_time = None;
     }
 
     let event = KillEvent {
@@ -239,34 +477,86 @@ pub fn register_kill(
         killer_new_killstreak: killer_killstreak + 1,
     };
 
+    lobby.kill_feed.push(event.clone());
+    lobby.analytics.total_kills += 1;
     lobby.mark_dirty(killer_id);
     lobby.mark_dirty(victim_id);
 
     Ok(event)
 }
 
-/// Respawn a player at default position
+/// Spawn point farthest from the nearest living opponent (default position without spawn points)
+/// Teammates don't count as opponents
+pub fn choose_spawn_point(lobby: &Lobby, player_id: u32) -> (f32, f32, f32) {
+    let opponents: Vec<(f32, f32, f32)> = lobby
+        .players
+        .values()
+        .filter(|p| p.id != player_id && !p.is_dead && !teams::are_teammates(lobby, player_id, p.id))
+        .map(|p| p.position)
+        .collect();
+    let safety = |point: &(f32, f32, f32)| {
+        opponents
+            .iter()
+            .map(|enemy| lobby.dimensions.distance(*point, *enemy))
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    let spawn = lobby
+        .spawn_points
+        .iter()
+        .copied()
+        .max_by(|a, b| safety(a).total_cmp(&safety(b)))
+        .unwrap_or((0.0, 1.0, 0.0));
+    lobby.dimensions.flatten(spawn)
+}
+
+/// Respawn a player: full health and ammo at a spawn point
 pub fn respawn_player(lobby: &mut Lobby, player_id: u32) -> Result<(), &'static str> {
+    let spawn = choose_spawn_point(lobby, player_id);
     let player = lobby
         .players
         .get_mut(&player_id)
         .ok_or("Player not found")?;
 
-    player.position = (0.0, 1.0, 0.0);
+    player.position = spawn;
     player.rotation = (0.0, 0.0, 0.0);
     player.current_health = player.max_health;
     player.current_ammo = player.max_ammo;
+    for magazine in player.inventory.values_mut() {
+        magazine.ammo = magazine.max_ammo;
+    }
+    player.grenades_thrown = 0;
     player.is_reloading = false;
     player.reload_end_time = None;
+    player.is_dead = false;
+    player.respawn_time = None;
 
     lobby.mark_dirty(player_id);
     Ok(())
 }
 
-/// Check if player is dead
+/// Respawn every dead player whose respawn timer has run out (horde players out of lives stay down)
+/// Returns the respawned player ids
+pub fn respawn_due_players_at(lobby: &mut Lobby, now: Instant) -> Vec<u32> {
+    let mut due: Vec<u32> = lobby
+        .players
+        .values()
+        .filter(|p| p.is_dead && p.respawn_time.map(|t| now >= t).unwrap_or(true))
+        .filter(|p| !horde::is_out(lobby, p.id))
+        .map(|p| p.id)
+        .collect();
+    due.sort_unstable();
+
+    for player_id in &due {
+        let _ = respawn_player(lobby, *player_id);
+    }
+    due
+}
+
+/// Check if player is alive
 pub fn is_player_alive(lobby: &Lobby, player_id: u32) -> bool {
     if let Some(player) = lobby.players.get(&player_id) {
-        player.current_health > 0
+        !player.is_dead && player.current_health > 0
     } else {
         false
     }
@@ -281,39 +571,19 @@ pub fn get_player_score(lobby: &Lobby, player_id: u32) -> Result<u32, &'static s
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::simulator::HitZone;
+    use crate::state::lobby::Player;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
     use crate::utils::weapondb::WeaponDb;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn test_try_shoot_success() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
         let weapons = WeaponDb::load();
 
-        // Add player with ammo
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now() - std::time::Duration::from_secs(1),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        lobby.players.insert(1, player);
-
-        let result = try_shoot(&mut lobby, &weapons, 1);
+        let result = try_shoot_at(&mut lobby, &weapons, 1, Instant::now());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), true);
 
@@ -322,33 +592,66 @@ mod tests {
     }
 
     #[test]
-    fn test_try_shoot_no_ammo() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+    fn test_try_shoot_melee_without_ammo() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().weapon(3).ammo(0)).build();
+        let weapons = WeaponDb::load();
+
+        assert!(try_shoot_at(&mut lobby, &weapons, 1, Instant::now()).unwrap());
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 0);
+    }
+
+    #[test]
+    fn test_validate_hit_facing_and_range() {
+        // Shooter at origin facing -Z (yaw 0)
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -10.0)))
+            .with_player(PlayerBuilder::new().id(3).position((0.0, 1.0, 10.0)))
+            .with_player(PlayerBuilder::new().id(4).position((0.0, 1.0, -500.0)))
+            .build();
         let weapons = WeaponDb::load();
 
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 0,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
+        assert!(validate_hit(&lobby, &weapons, 1, 2).is_ok());
+        assert_eq!(validate_hit(&lobby, &weapons, 1, 3), Err("Target not in front of shooter"));
+        assert_eq!(validate_hit(&lobby, &weapons, 1, 4), Err("Target out of range"));
+
+        // Turning around (yaw = PI) makes the player behind hittable
+        lobby.players.get_mut(&1).unwrap().rotation =
+            Player::rotation_from_yaw_pitch(std::f32::consts::PI, 0.0);
+        assert!(validate_hit(&lobby, &weapons, 1, 3).is_ok());
+    }
+
+    #[test]
+    fn test_hitscan_finds_the_target_server_side() {
+        let weapons = WeaponDb::load();
+        let hit = |lobby: &Lobby, origin, direction| {
+            hitscan_at(lobby, &weapons, 1, origin, direction, None).unwrap().map(|hit| hit.player_id)
         };
-        lobby.players.insert(1, player);
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -10.0)))
+            .with_player(PlayerBuilder::new().id(3).position((3.0, 1.0, -20.0)))
+            .build();
+
+        // The nearest player in the line of fire takes the shot
+        assert_eq!(hit(&lobby, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0)), Some(2));
+        assert_eq!(hit(&lobby, (0.0, 1.0, 0.0), (3.0, 0.0, -20.0)), Some(3));
+        assert_eq!(hit(&lobby, (0.0, 1.0, 0.0), (1.0, 0.0, -1.0)), None);
+
+        // An origin far from the shooter is replaced by the shooter's position
+        assert_eq!(hit(&lobby, (0.0, 1.0, -15.0), (0.0, 0.0, 1.0)), None);
+
+        // Knife swings hit a wider sphere, but only within the knife's range
+        lobby.players.get_mut(&1).unwrap().current_weapon_id = 3;
+        lobby.players.get_mut(&2).unwrap().position = (0.8, 1.0, -2.0);
+        assert_eq!(hit(&lobby, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0)), Some(2));
+        lobby.players.get_mut(&2).unwrap().position = (0.0, 1.0, -5.0);
+        assert_eq!(hit(&lobby, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0)), None);
+    }
+
+    #[test]
+    fn test_try_shoot_no_ammo() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().ammo(0)).build();
 
         let result = apply_damage(&mut lobby, 1, 25);
         assert!(result.is_ok());
@@ -356,31 +659,7 @@ mod tests {
 
     #[test]
     fn test_apply_damage() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
-
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        lobby.players.insert(1, player);
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
 
         let result = apply_damage(&mut lobby, 1, 25);
         assert!(result.is_ok());
@@ -391,34 +670,10 @@ mod tests {
 
     #[test]
     fn test_start_reload() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().ammo(10)).build();
         let weapons = WeaponDb::load();
 
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 10,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        lobby.players.insert(1, player);
-
-        let result = start_reload(&mut lobby, &weapons, 1);
+        let result = start_reload_at(&mut lobby, &weapons, 1, Instant::now());
         assert!(result.is_ok());
 
         let player = lobby.players.get(&1).unwrap();
@@ -428,38 +683,128 @@ mod tests {
 
     #[test]
     fn test_switch_weapon() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().ammo(10)).build();
         let weapons = WeaponDb::load();
 
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 10,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        lobby.players.insert(1, player);
-
+        assert_eq!(switch_weapon(&mut lobby, &weapons, 1, 2), Err("Weapon not owned"));
+        grant_weapon(&mut lobby, &weapons, 1, 2).unwrap();
         let result = switch_weapon(&mut lobby, &weapons, 1, 2);
         assert!(result.is_ok());
 
         let player = lobby.players.get(&1).unwrap();
         assert_eq!(player.current_weapon_id, 2);
         assert_eq!(player.current_ammo, 8); // Prototype ammo
+        assert_eq!(player.to_sync_state().weapons, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_switching_keeps_each_magazine() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        let weapons = WeaponDb::load();
+        grant_weapon(&mut lobby, &weapons, 1, 2).unwrap();
+        lobby.players.get_mut(&1).unwrap().current_ammo = 3;
+
+        // Switching away and back doesn't refill (the old reload exploit)
+        switch_weapon(&mut lobby, &weapons, 1, 2).unwrap();
+        switch_weapon(&mut lobby, &weapons, 1, 1).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 3);
+        switch_weapon(&mut lobby, &weapons, 1, 1).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 3);
+
+        // The knife is always at hand
+        switch_weapon(&mut lobby, &weapons, 1, 3).unwrap();
+        switch_weapon(&mut lobby, &weapons, 1, 1).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 3);
+
+        // Granting an owned weapon refills it; respawning refills everything
+        grant_weapon(&mut lobby, &weapons, 1, 1).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 20);
+        lobby.players.get_mut(&1).unwrap().inventory.get_mut(&2).unwrap().ammo = 0;
+        respawn_player(&mut lobby, 1).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().inventory[&2], Magazine::full(8));
+        assert_eq!(grant_weapon(&mut lobby, &weapons, 1, 99), Err("Invalid weapon"));
+    }
+
+    #[test]
+    fn test_hits_scale_with_zone_and_distance() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        let now = crate::testutil::epoch();
+
+        resolve_hit_at(&mut lobby, &weapons, 1, 2, HitZone::Head, 5.0, now).unwrap();
+        assert_eq!(lobby.players[&2].current_health, 70);
+        assert_eq!(
+            lobby.damage_feed,
+            vec![DamageEvent { attacker_id: 1, victim_id: 2, damage: 30, multiplier: 1.5, zone: HitZone::Head, distance: 5.0 }]
+        );
+
+        // Leg shots past the falloff deal less than the weapon's base damage
+        resolve_hit_at(&mut lobby, &weapons, 1, 2, HitZone::Legs, 90.0, now).unwrap();
+        assert_eq!(lobby.players[&2].current_health, 62);
+        assert_eq!(lobby.damage_feed[1].multiplier, 0.375);
+
+        // Never more than the largest hit the server accepts
+        Arc::make_mut(&mut lobby.tunables).max_damage = 25;
+        resolve_hit_at(&mut lobby, &weapons, 1, 2, HitZone::Head, 5.0, now).unwrap();
+        assert_eq!(lobby.players[&2].current_health, 37);
+    }
+
+    #[test]
+    fn test_lethal_hit_kills_and_respawn_restores() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -5.0)).health(15).ammo(3))
+            .build();
+        Arc::make_mut(&mut lobby.tunables).respawn_delay_ms = 5000;
+        lobby.spawn_points = vec![(0.0, 1.0, -4.0), (30.0, 1.0, 30.0)];
+        let now = crate::testutil::epoch() + Duration::from_secs(100);
+
+        let kill = resolve_hit_at(&mut lobby, &weapons, 1, 2, HitZone::Body, 5.0, now).unwrap().unwrap();
+        assert_eq!((kill.killer_id, kill.victim_id), (1, 2));
+        assert_eq!(lobby.kill_feed.len(), 1);
+
+        let victim = lobby.players.get(&2).unwrap();
+        assert!(victim.is_dead);
+        assert_eq!((victim.current_health, victim.deaths), (0, 1));
+        assert_eq!(lobby.players.get(&1).unwrap().kills, 1);
+
+        // Dead players can't be hit again or shoot
+        assert_eq!(resolve_hit_at(&mut lobby, &weapons, 1, 2, HitZone::Body, 5.0, now).unwrap_err(), "Player is dead");
+        assert!(!try_shoot_at(&mut lobby, &weapons, 2, now).unwrap());
+        assert_eq!(lobby.players.get(&1).unwrap().kills, 1);
+
+        assert!(respawn_due_players_at(&mut lobby, now + Duration::from_secs(4)).is_empty());
+        assert_eq!(respawn_due_players_at(&mut lobby, now + Duration::from_secs(5)), vec![2]);
+
+        let victim = lobby.players.get(&2).unwrap();
+        assert!(!victim.is_dead && victim.respawn_time.is_none());
+        assert_eq!(victim.current_health, victim.max_health);
+        assert_eq!(victim.current_ammo, victim.max_ammo);
+        assert_eq!(victim.position, (30.0, 1.0, 30.0)); // Farthest from the killer
+    }
+
+    #[test]
+    fn test_out_of_order_timestamps_dont_break_fire_rate() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().ammo(5)).build();
+        let now = crate::testutil::epoch() + Duration::from_secs(100);
+
+        assert!(try_shoot_at(&mut lobby, &weapons, 1, now).unwrap());
+        // A time before the last shot counts as too soon, not as an error
+        assert_eq!(try_shoot_at(&mut lobby, &weapons, 1, now - Duration::from_secs(10)), Ok(false));
+        assert!(try_shoot_at(&mut lobby, &weapons, 1, now + Duration::from_secs(2)).unwrap());
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 3);
+    }
+
+    #[test]
+    fn test_resolve_hit_refuses_self_damage() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        assert!(resolve_hit_at(&mut lobby, &weapons, 1, 1, HitZone::Body, 0.0, Instant::now()).is_err());
+        assert_eq!(lobby.players.get(&1).unwrap().current_health, 100);
     }
 }
@@ -0,0 +1,346 @@
+use std::time::Instant;
+use crate::domain::{gungame, horde, logic, teams};
+use crate::domain::gungame::GameMode;
+use crate::state::lobby::{Connection, Lobby, MatchState};
+use crate::utils::weapondb::WeaponDb;
+
+/// Match transition the tick loop broadcasts
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchEvent {
+    RoundStart { round: u32 },
+    RoundEnd { round: u32, leader_id: Option<u32> },
+    MatchEnd { winner_id: Option<u32> },
+}
+
+/// One account's part in a finished match, added to its lifetime stats
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountResult {
+    pub account_id: i64,
+    pub place: u32,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub won: bool, // The winner, or on the winner's team
+    pub shots: u32,
+    pub hits: u32,
+}
+
+/// Results of the players signed in to an account, for a match `winner_id` won
+pub fn account_results(lobby: &Lobby, winner_id: Option<u32>) -> Vec<AccountResult> {
+    let winning_team = winner_id.and_then(|id| lobby.players.get(&id)).and_then(|p| p.team_id);
+    placements(lobby)
+        .into_iter()
+        .filter_map(|placement| {
+            let p = lobby.players.get(&placement.player_id)?;
+            Some(AccountResult {
+                account_id: p.account_id?,
+                place: placement.place,
+                score: p.score,
+                kills: p.kills,
+                deaths: p.deaths,
+                won: winner_id == Some(p.id) || (winning_team.is_some() && p.team_id == winning_team),
+                shots: p.shots_fired,
+                hits: p.shots_hit,
+            })
+        })
+        .collect()
+}
+
+/// A player's standing when the match ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub player_id: u32,
+    pub place: u32, // 1 = best; equal scores share a place and the next place is skipped
+}
+
+/// Standings by score, kills breaking ties (horde enemies left out)
+pub fn placements(lobby: &Lobby) -> Vec<Placement> {
+    let mut players: Vec<_> = lobby.players.values().filter(|p| !horde::is_enemy(lobby, p.id)).collect();
+    players.sort_by(|a, b| b.score.cmp(&a.score).then(b.kills.cmp(&a.kills)).then(a.id.cmp(&b.id)));
+    let mut placements: Vec<Placement> = Vec::with_capacity(players.len());
+    for (i, player) in players.iter().enumerate() {
+        let tied = i > 0 && (players[i - 1].score, players[i - 1].kills) == (player.score, player.kills);
+        let place = if tied { placements[i - 1].place } else { i as u32 + 1 };
+        placements.push(Placement { player_id: player.id, place });
+    }
+    placements
+}
+
+/// Highest scoring player (lowest id on ties, for stable results)
+pub fn leader(lobby: &Lobby) -> Option<u32> {
+    lobby
+        .players
+        .values()
+        .max_by(|a, b| a.score.cmp(&b.score).then(b.id.cmp(&a.id)))
+        .map(|p| p.id)
+}
+
+fn score_limit_reached(lobby: &Lobby) -> bool {
+    lobby.rules.score_limit > 0 && lobby.players.values().any(|p| p.score >= lobby.rules.score_limit)
+}
+
+/// Humans that are ready and connected humans in the lobby (bots never ready up, loading players don't count)
+pub fn ready_count(lobby: &Lobby) -> (usize, usize) {
+    let humans = lobby
+        .players
+        .values()
+        .filter(|p| !lobby.bots.contains_key(&p.id) && p.connection == Connection::Connected && !p.is_loading());
+    humans.fold((0, 0), |(ready, total), p| (ready + p.ready as usize, total + 1))
+}
+
+/// Whether enough humans are ready for the countdown (always, with no ready-up)
+fn quorum_ready(lobby: &Lobby) -> bool {
+    if lobby.rules.ready_quorum == 0 {
+        return true;
+    }
+    let (ready, humans) = ready_count(lobby);
+    humans > 0 && ready * 100 >= lobby.rules.ready_quorum as usize * humans
+}
+
+/// Mark a player (un)ready for the next match; only before it starts
+pub fn set_ready(lobby: &mut Lobby, player_id: u32, ready: bool) -> Result<(), &'static str> {
+    if !matches!(lobby.match_state, MatchState::WaitingForPlayers | MatchState::Countdown { .. }) {
+        return Err("Match already started");
+    }
+    if lobby.bots.contains_key(&player_id) {
+        return Err("Bots can't ready up");
+    }
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    player.ready = ready;
+    Ok(())
+}
+
+/// Clear scores, gun game progress and readiness, and put everyone back on a spawn point
+fn reset_players(lobby: &mut Lobby, weapons: &WeaponDb) -> Result<(), &'static str> {
+    let player_ids: Vec<u32> = lobby.players.keys().copied().collect();
+    for player_id in player_ids {
+        if let Some(player) = lobby.players.get_mut(&player_id) {
+            player.kills = 0;
+            player.deaths = 0;
+            player.score = 0;
+            player.killstreak = 0;
+            player.shots_fired = 0;
+            player.shots_hit = 0;
+            player.ready = false;
+        }
+        gungame::enroll(lobby, weapons, player_id)?;
+        logic::respawn_player(lobby, player_id)?;
+    }
+    teams::reset_scores(lobby);
+    Ok(())
+}
+
+/// Respawn everyone for a fresh round
+fn start_round(lobby: &mut Lobby, round: u32, now: Instant) -> Result<MatchEvent, &'static str> {
+    let player_ids: Vec<u32> = lobby.players.keys().copied().collect();
+    for player_id in player_ids {
+        logic::respawn_player(lobby, player_id)?;
+    }
+    lobby.match_state = MatchState::InProgress { round, ends_at: now + lobby.rules.round_duration };
+    Ok(MatchEvent::RoundStart { round })
+}
+
+/// Start the current round over: everyone respawns and the round clock resets (scores stand)
+pub fn restart_round(lobby: &mut Lobby, now: Instant) -> Result<MatchEvent, &'static str> {
+    let MatchState::InProgress { round, .. } = lobby.match_state else {
+        return Err("No round in progress");
+    };
+    start_round(lobby, round, now)
+}
+
+/// End the match now (score limit, last round, or a gun game ladder finished)
+pub fn end_match(lobby: &mut Lobby, winner_id: Option<u32>, now: Instant) -> MatchEvent {
+    lobby.match_state = MatchState::MatchEnd { restart_at: now + lobby.rules.results_duration };
+    MatchEvent::MatchEnd { winner_id }
+}
+
+/// Advance the match state machine; returns the transitions that happened this tick
+pub fn advance(lobby: &mut Lobby, weapons: &WeaponDb, now: Instant) -> Result<Vec<MatchEvent>, &'static str> {
+    // Horde plays with any number of players against its own bots; players still loading don't count
+    let is_horde = matches!(lobby.mode, GameMode::Horde(_));
+    let in_play = lobby.players.values().filter(|p| !p.is_loading());
+    let enough_players = if is_horde {
+        in_play.clone().any(|p| !horde::is_enemy(lobby, p.id))
+    } else {
+        in_play.count() >= lobby.rules.min_players
+    };
+    let mut events = Vec::new();
+
+    match lobby.match_state {
+        MatchState::WaitingForPlayers => {
+            if enough_players && quorum_ready(lobby) {
+                lobby.match_state = MatchState::Countdown { ends_at: now + lobby.rules.countdown };
+            }
+        }
+        MatchState::Countdown { ends_at } => {
+            if !enough_players || !quorum_ready(lobby) {
+                lobby.match_state = MatchState::WaitingForPlayers;
+            } else if now >= ends_at {
+                // Warm-up kills don't count
+                reset_players(lobby, weapons)?;
+                events.push(start_round(lobby, 1, now)?);
+            }
+        }
+        MatchState::InProgress { round, ends_at } => {
+            if !enough_players {
+                events.push(end_match(lobby, leader(lobby), now));
+            } else if !is_horde && (now >= ends_at || score_limit_reached(lobby)) {
+                // Horde matches end on the last wave or a wipe instead (see `horde::advance`)
+                let leader_id = leader(lobby);
+                events.push(MatchEvent::RoundEnd { round, leader_id });
+                if round >= lobby.rules.rounds_per_match || score_limit_reached(lobby) {
+                    events.push(end_match(lobby, leader_id, now));
+                } else {
+                    lobby.match_state = MatchState::RoundEnd { round, next_at: now + lobby.rules.intermission };
+                }
+            }
+        }
+        MatchState::RoundEnd { round, next_at } => {
+            if !enough_players {
+                events.push(end_match(lobby, leader(lobby), now));
+            } else if now >= next_at {
+                events.push(start_round(lobby, round + 1, now)?);
+            }
+        }
+        MatchState::MatchEnd { restart_at } => {
+            if now >= restart_at {
+                reset_players(lobby, weapons)?;
+                lobby.match_state = MatchState::WaitingForPlayers;
+            }
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::lobby::MatchRules;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Instant {
+        crate::testutil::epoch() + Duration::from_secs(1_000 + s)
+    }
+
+    fn lobby() -> Lobby {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        lobby.rules = MatchRules {
+            min_players: 2,
+            ready_quorum: 0,
+            countdown: Duration::from_secs(5),
+            round_duration: Duration::from_secs(60),
+            rounds_per_match: 2,
+            score_limit: 500,
+            intermission: Duration::from_secs(3),
+            results_duration: Duration::from_secs(10),
+            horde: horde::HordeRules::default(),
+            ..MatchRules::default()
+        };
+        lobby
+    }
+
+    #[test]
+    fn test_full_match_cycle() {
+        let weapons = WeaponDb::load();
+        let mut lobby = lobby();
+        lobby.players.get_mut(&2).unwrap().score = 300; // Warm-up score
+
+        assert!(advance(&mut lobby, &weapons, secs(0)).unwrap().is_empty());
+        assert_eq!(lobby.match_state, MatchState::Countdown { ends_at: secs(5) });
+
+        assert_eq!(advance(&mut lobby, &weapons, secs(5)).unwrap(), vec![MatchEvent::RoundStart { round: 1 }]);
+        assert_eq!(lobby.players.get(&2).unwrap().score, 0);
+
+        lobby.players.get_mut(&2).unwrap().score = 100;
+        assert_eq!(
+            advance(&mut lobby, &weapons, secs(65)).unwrap(),
+            vec![MatchEvent::RoundEnd { round: 1, leader_id: Some(2) }]
+        );
+        assert_eq!(lobby.match_state, MatchState::RoundEnd { round: 1, next_at: secs(68) });
+
+        assert_eq!(advance(&mut lobby, &weapons, secs(68)).unwrap(), vec![MatchEvent::RoundStart { round: 2 }]);
+        assert_eq!(
+            advance(&mut lobby, &weapons, secs(128)).unwrap(),
+            vec![
+                MatchEvent::RoundEnd { round: 2, leader_id: Some(2) },
+                MatchEvent::MatchEnd { winner_id: Some(2) },
+            ]
+        );
+
+        advance(&mut lobby, &weapons, secs(138)).unwrap();
+        assert_eq!(lobby.match_state, MatchState::WaitingForPlayers);
+        assert_eq!(lobby.players.get(&2).unwrap().score, 0);
+    }
+
+    #[test]
+    fn test_score_limit_ends_match_early() {
+        let weapons = WeaponDb::load();
+        let mut lobby = lobby();
+        lobby.match_state = MatchState::InProgress { round: 1, ends_at: secs(60) };
+        lobby.players.get_mut(&1).unwrap().score = 500;
+
+        let events = advance(&mut lobby, &weapons, secs(10)).unwrap();
+        assert_eq!(events.last(), Some(&MatchEvent::MatchEnd { winner_id: Some(1) }));
+    }
+
+    #[test]
+    fn test_countdown_cancelled_when_players_leave() {
+        let weapons = WeaponDb::load();
+        let mut lobby = lobby();
+        advance(&mut lobby, &weapons, secs(0)).unwrap();
+        lobby.players.remove(&2);
+        advance(&mut lobby, &weapons, secs(1)).unwrap();
+        assert_eq!(lobby.match_state, MatchState::WaitingForPlayers);
+    }
+
+    #[test]
+    fn test_countdown_waits_for_ready_quorum() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        lobby.rules.ready_quorum = 60;
+        crate::domain::lobbies::add_bot(&mut lobby, 3, "Bot".to_string(), &weapons).unwrap();
+
+        assert_eq!(set_ready(&mut lobby, 3, true), Err("Bots can't ready up"));
+        set_ready(&mut lobby, 1, true).unwrap();
+        advance(&mut lobby, &weapons, secs(0)).unwrap();
+        assert_eq!(lobby.match_state, MatchState::WaitingForPlayers); // 1 of 2 humans
+
+        set_ready(&mut lobby, 2, true).unwrap();
+        advance(&mut lobby, &weapons, secs(1)).unwrap();
+        assert!(matches!(lobby.match_state, MatchState::Countdown { .. }));
+
+        set_ready(&mut lobby, 2, false).unwrap();
+        advance(&mut lobby, &weapons, secs(2)).unwrap();
+        assert_eq!(lobby.match_state, MatchState::WaitingForPlayers);
+
+        set_ready(&mut lobby, 2, true).unwrap();
+        advance(&mut lobby, &weapons, secs(3)).unwrap();
+        advance(&mut lobby, &weapons, secs(20)).unwrap();
+        assert!(matches!(lobby.match_state, MatchState::InProgress { round: 1, .. }));
+        assert_eq!(ready_count(&lobby), (0, 2)); // Ready again for the next match
+        assert_eq!(set_ready(&mut lobby, 1, true), Err("Match already started"));
+    }
+
+    #[test]
+    fn test_placements_share_tied_places() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .with_player(PlayerBuilder::new().id(3))
+            .with_player(PlayerBuilder::new().id(4))
+            .build();
+        for (id, score, kills) in [(1, 200, 2), (2, 500, 5), (3, 200, 2), (4, 200, 1)] {
+            let player = lobby.players.get_mut(&id).unwrap();
+            (player.score, player.kills) = (score, kills);
+        }
+        let places: Vec<(u32, u32)> = placements(&lobby).iter().map(|p| (p.player_id, p.place)).collect();
+        assert_eq!(places, vec![(2, 1), (1, 2), (3, 2), (4, 4)]);
+    }
+}
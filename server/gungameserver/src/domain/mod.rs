@@ -1,4 +1,23 @@
 pub mod lobbies;
 pub mod logic;
 pub mod simulator;
+pub mod collision;
 
+pub mod pickups;
+pub mod bot;
+pub mod gungame;
+pub mod matches;
+pub mod history;
+pub mod teams;
+pub mod clock;
+pub mod rewind;
+pub mod anticheat;
+pub mod projectiles;
+pub mod grenades;
+pub mod mutators;
+pub mod owner;
+pub mod horde;
+pub mod chat;
+pub mod voice;
+pub mod referee;
+pub mod challenge;
@@ -0,0 +1,253 @@
+use std::time::{Duration, Instant};
+use crate::domain::clock;
+use crate::domain::gungame::GameMode;
+use crate::domain::logic;
+use crate::state::lobby::{Lobby, MatchState};
+use crate::utils::weapondb::WeaponDb;
+
+/// Projectile gravity while low gravity is on (clients scale their own movement to match)
+pub const LOW_GRAVITY_SCALE: f32 = 0.4;
+
+/// A timed lobby-wide rule change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutator {
+    LowGravity,
+    DoubleDamage,
+    WeaponRoulette, // Everyone gets a random gun and can't switch until it ends
+}
+
+impl Mutator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mutator::LowGravity => "low_gravity",
+            Mutator::DoubleDamage => "double_damage",
+            Mutator::WeaponRoulette => "weapon_roulette",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "low_gravity" => Some(Mutator::LowGravity),
+            "double_damage" => Some(Mutator::DoubleDamage),
+            "weapon_roulette" => Some(Mutator::WeaponRoulette),
+            _ => None,
+        }
+    }
+}
+
+/// Xorshift generator for a lobby's random match decisions
+#[derive(Debug, Clone)]
+pub struct MatchRng(u64);
+
+impl MatchRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    /// Seeded from the lobby code and `now` on the server's clock, so an injected clock repeats the picks
+    pub fn for_lobby(code: &str, now: Instant) -> Self {
+        let code_hash = code.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3));
+        Self::new(clock::server_ms(now).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ code_hash)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform-ish index below `n` (n > 0)
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// The mutator running in a lobby and when it ends
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveMutator {
+    pub mutator: Mutator,
+    pub ends_at: Instant,
+}
+
+/// Mutator start/end the tick loop broadcasts
+#[derive(Debug, Clone, PartialEq)]
+pub enum MutatorEvent {
+    Started { mutator: Mutator, duration: Duration },
+    Ended { mutator: Mutator },
+}
+
+/// When a lobby's mutators run; an empty pool disables them
+#[derive(Debug, Clone)]
+pub struct MutatorSchedule {
+    pub pool: Vec<Mutator>, // Picked from at random
+    pub interval: Duration, // Between the end of one mutator and the start of the next
+    pub duration: Duration,
+    pub active: Option<ActiveMutator>,
+    next_at: Option<Instant>, // Next start; None until a round is in progress
+    rng: MatchRng,
+}
+
+impl MutatorSchedule {
+    pub fn new(pool: Vec<Mutator>, interval: Duration, duration: Duration, rng: MatchRng) -> Self {
+        Self { pool, interval, duration, active: None, next_at: None, rng }
+    }
+}
+
+impl Default for MutatorSchedule {
+    fn default() -> Self {
+        Self::new(Vec::new(), Duration::from_secs(120), Duration::from_secs(30), MatchRng::new(1))
+    }
+}
+
+fn is_active(lobby: &Lobby, mutator: Mutator) -> bool {
+    lobby.mutators.active.as_ref().is_some_and(|a| a.mutator == mutator)
+}
+
+/// Damage dealt per point of weapon damage
+pub fn damage_multiplier(lobby: &Lobby) -> u32 {
+    if is_active(lobby, Mutator::DoubleDamage) { 2 } else { 1 }
+}
+
+/// Scale applied to projectile gravity at launch
+pub fn gravity_scale(lobby: &Lobby) -> f32 {
+    if is_active(lobby, Mutator::LowGravity) { LOW_GRAVITY_SCALE } else { 1.0 }
+}
+
+/// Whether players are stuck with their current weapon
+pub fn locks_weapons(lobby: &Lobby) -> bool {
+    is_active(lobby, Mutator::WeaponRoulette)
+}
+
+/// Give every player a random gun
+fn spin_weapon_roulette(lobby: &mut Lobby, weapons: &WeaponDb) {
+    let mut guns: Vec<u32> = weapons.iter().filter(|w| !w.is_melee()).map(|w| w.id).collect();
+    guns.sort_unstable();
+    if guns.is_empty() {
+        return;
+    }
+    let mut player_ids: Vec<u32> = lobby.players.keys().copied().collect();
+    player_ids.sort_unstable();
+    for player_id in player_ids {
+        let weapon_id = guns[lobby.mutators.rng.below(guns.len())];
+        if let Err(e) = logic::equip_weapon(lobby, weapons, player_id, weapon_id) {
+            log::debug!("Weapon roulette failed for player {}: {}", player_id, e);
+        }
+    }
+}
+
+fn start(lobby: &mut Lobby, weapons: &WeaponDb, now: Instant) -> Option<MutatorEvent> {
+    // Gun game decides weapons itself
+    let gun_game = matches!(lobby.mode, GameMode::GunGame(_));
+    let choices: Vec<Mutator> = lobby
+        .mutators
+        .pool
+        .iter()
+        .copied()
+        .filter(|m| !(gun_game && *m == Mutator::WeaponRoulette))
+        .collect();
+    if choices.is_empty() {
+        return None;
+    }
+    let mutator = choices[lobby.mutators.rng.below(choices.len())];
+    let duration = lobby.mutators.duration;
+    lobby.mutators.active = Some(ActiveMutator { mutator, ends_at: now + duration });
+    if mutator == Mutator::WeaponRoulette {
+        spin_weapon_roulette(lobby, weapons);
+    }
+    Some(MutatorEvent::Started { mutator, duration })
+}
+
+/// Start and end mutators; they only run while a round is in progress
+pub fn advance(lobby: &mut Lobby, weapons: &WeaponDb, now: Instant) -> Vec<MutatorEvent> {
+    let mut events = Vec::new();
+    if lobby.mutators.pool.is_empty() {
+        return events;
+    }
+
+    let in_progress = matches!(lobby.match_state, MatchState::InProgress { .. });
+    let expired = lobby.mutators.active.as_ref().is_some_and(|a| !in_progress || now >= a.ends_at);
+    if expired {
+        if let Some(active) = lobby.mutators.active.take() {
+            events.push(MutatorEvent::Ended { mutator: active.mutator });
+        }
+    }
+    if !in_progress {
+        lobby.mutators.next_at = None;
+        return events;
+    }
+
+    let next_at = *lobby.mutators.next_at.get_or_insert(now + lobby.mutators.interval);
+    if lobby.mutators.active.is_none() && now >= next_at {
+        events.extend(start(lobby, weapons, now));
+        lobby.mutators.next_at = Some(now + lobby.mutators.duration + lobby.mutators.interval);
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    fn lobby(pool: Vec<Mutator>) -> Lobby {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).with_player(PlayerBuilder::new().id(2)).build();
+        lobby.mutators = MutatorSchedule::new(pool, Duration::from_secs(60), Duration::from_secs(30), MatchRng::new(7));
+        lobby
+    }
+
+    fn start_round(lobby: &mut Lobby, now: Instant) {
+        lobby.match_state = MatchState::InProgress { round: 1, ends_at: now + Duration::from_secs(300) };
+    }
+
+    #[test]
+    fn test_mutators_run_on_schedule_during_rounds() {
+        let weapons = WeaponDb::load();
+        let mut lobby = lobby(vec![Mutator::DoubleDamage]);
+        let t0 = crate::testutil::epoch() + Duration::from_secs(1000);
+        assert!(advance(&mut lobby, &weapons, t0).is_empty());
+
+        start_round(&mut lobby, t0);
+        assert!(advance(&mut lobby, &weapons, t0).is_empty());
+        let started = advance(&mut lobby, &weapons, t0 + Duration::from_secs(60));
+        assert_eq!(started, vec![MutatorEvent::Started { mutator: Mutator::DoubleDamage, duration: Duration::from_secs(30) }]);
+        assert_eq!(damage_multiplier(&lobby), 2);
+
+        let ended = advance(&mut lobby, &weapons, t0 + Duration::from_secs(90));
+        assert_eq!(ended, vec![MutatorEvent::Ended { mutator: Mutator::DoubleDamage }]);
+        assert_eq!(damage_multiplier(&lobby), 1);
+        assert!(advance(&mut lobby, &weapons, t0 + Duration::from_secs(149)).is_empty());
+        assert_eq!(advance(&mut lobby, &weapons, t0 + Duration::from_secs(150)).len(), 1);
+
+        // Round over: the running mutator ends with it
+        lobby.match_state = MatchState::RoundEnd { round: 1, next_at: t0 + Duration::from_secs(200) };
+        assert_eq!(advance(&mut lobby, &weapons, t0 + Duration::from_secs(151)), vec![MutatorEvent::Ended { mutator: Mutator::DoubleDamage }]);
+    }
+
+    #[test]
+    fn test_weapon_roulette_hands_out_guns() {
+        let weapons = WeaponDb::load();
+        let mut lobby = lobby(vec![Mutator::WeaponRoulette]);
+        let now = crate::testutil::epoch() + Duration::from_secs(1000);
+        start_round(&mut lobby, now);
+        advance(&mut lobby, &weapons, now);
+        advance(&mut lobby, &weapons, now + Duration::from_secs(60));
+
+        assert!(locks_weapons(&lobby));
+        for player in lobby.players.values() {
+            assert!(!weapons.get(player.current_weapon_id).unwrap().is_melee());
+        }
+    }
+
+    #[test]
+    fn test_lobby_rng_follows_the_clock() {
+        let t0 = crate::testutil::epoch() + Duration::from_secs(1000);
+        let picks = |code: &str, now: Instant| {
+            let mut rng = MatchRng::for_lobby(code, now);
+            (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(picks("ABC", t0), picks("ABC", t0));
+        assert_ne!(picks("ABC", t0), picks("ABC", t0 + Duration::from_secs(1)));
+        assert_ne!(picks("ABC", t0), picks("ABD", t0));
+    }
+}
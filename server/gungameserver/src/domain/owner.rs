@@ -0,0 +1,194 @@
+use crate::domain::lobbies;
+use crate::domain::logic;
+use crate::state::lobby::Lobby;
+use crate::utils::scenedb::SceneDb;
+use serde_json::{json, Value};
+use std::time::Instant;
+
+/// What kicked players are told
+pub const KICK_REASON: &str = "kicked by the lobby owner";
+
+/// Something only the lobby owner may do
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum OwnerAction {
+    Kick { target_id: u32 },
+    SetMaxPlayers { max_players: u32 },
+    SetScene { scene: String },
+    Lock { locked: bool }, // Locked lobbies refuse every join
+    TransferOwnership { target_id: u32 },
+    SetTimeScale { time_scale: f32 }, // Slow motion or fast forward, see `lobbies::set_time_scale`
+}
+
+/// A lobby change clients are told about in a `lobby_event` packet
+#[derive(Debug, Clone, PartialEq)]
+pub enum LobbyEvent {
+    OwnerChanged { owner_id: Option<u32> },
+    PlayerKicked { player_id: u32 },
+    MaxPlayersChanged { max_players: u32 },
+    SceneChanged { scene: String },
+    LockChanged { locked: bool },
+    ReadyChanged { player_id: u32, ready: bool },
+    TimeScaleChanged { time_scale: f32 },
+    FrozenChanged { frozen: bool },
+    PlayerTeleported { player_id: u32, position: (f32, f32, f32) },
+}
+
+impl LobbyEvent {
+    pub fn packet(&self) -> Value {
+        match self {
+            LobbyEvent::OwnerChanged { owner_id } => json!({"type": "lobby_event", "event": "owner_changed", "owner_id": owner_id}),
+            LobbyEvent::PlayerKicked { player_id } => json!({"type": "lobby_event", "event": "player_kicked", "player_id": player_id}),
+            LobbyEvent::MaxPlayersChanged { max_players } => json!({"type": "lobby_event", "event": "max_players_changed", "max_players": max_players}),
+            LobbyEvent::SceneChanged { scene } => json!({"type": "lobby_event", "event": "scene_changed", "scene": scene}),
+            LobbyEvent::LockChanged { locked } => json!({"type": "lobby_event", "event": "lock_changed", "locked": locked}),
+            LobbyEvent::ReadyChanged { player_id, ready } => json!({"type": "lobby_event", "event": "ready_changed", "player_id": player_id, "ready": ready}),
+            LobbyEvent::TimeScaleChanged { time_scale } => json!({"type": "lobby_event", "event": "time_scale_changed", "time_scale": time_scale}),
+            LobbyEvent::FrozenChanged { frozen } => json!({"type": "lobby_event", "event": "frozen_changed", "frozen": frozen}),
+            LobbyEvent::PlayerTeleported { player_id, position } => json!({
+                "type": "lobby_event",
+                "event": "player_teleported",
+                "player_id": player_id,
+                "position": {"x": position.0, "y": position.1, "z": position.2}
+            }),
+        }
+    }
+}
+
+/// Make a joining player the owner of a player-hosted lobby that has none
+pub fn claim(lobby: &mut Lobby, player_id: u32) {
+    if lobby.hosted && lobby.owner.is_none() && !lobby.bots.contains_key(&player_id) {
+        set_owner(lobby, Some(player_id));
+    }
+}
+
+/// Pass ownership on when the owner leaves: to the longest-present human (lowest id), or nobody
+pub fn hand_over(lobby: &mut Lobby, leaving_id: u32) {
+    if lobby.owner != Some(leaving_id) {
+        return;
+    }
+    let next = lobby
+        .players
+        .keys()
+        .copied()
+        .filter(|id| *id != leaving_id && !lobby.bots.contains_key(id))
+        .min();
+    set_owner(lobby, next);
+}
+
+fn set_owner(lobby: &mut Lobby, owner_id: Option<u32>) {
+    lobby.owner = owner_id;
+    lobby.lobby_events.push(LobbyEvent::OwnerChanged { owner_id });
+}
+
+/// Carry out an owner action for `actor`
+/// Kicks only pick the player (returned): the caller removes them like any kicked player
+pub fn apply(lobby: &mut Lobby, scenes: &SceneDb, actor: u32, action: OwnerAction) -> Result<Option<u32>, &'static str> {
+    if lobby.owner != Some(actor) {
+        return Err("Not the lobby owner");
+    }
+
+    match action {
+        OwnerAction::Kick { target_id } => {
+            if target_id == actor {
+                return Err("Can't kick yourself");
+            }
+            if !lobby.players.contains_key(&target_id) {
+                return Err("Player not found");
+            }
+            lobby.lobby_events.push(LobbyEvent::PlayerKicked { player_id: target_id });
+            return Ok(Some(target_id));
+        }
+        OwnerAction::SetMaxPlayers { max_players } => {
+            if (max_players as usize) < lobbies::occupied_slots(lobby) + lobbies::held_reservations(lobby, Instant::now()) {
+                return Err("Fewer slots than players");
+            }
+            lobby.max_players = max_players;
+            lobby.lobby_events.push(LobbyEvent::MaxPlayersChanged { max_players });
+        }
+        OwnerAction::SetScene { scene } => {
+            if scenes.get(&scene).is_none() {
+                return Err("Unknown scene");
+            }
+            lobby.spawn_points = scenes.spawn_points(&scene);
+            lobby.enemy_spawn_points = scenes.enemy_spawn_points(&scene);
+            lobby.scene = scene.clone();
+            // Nobody may stay at a position on the old map
+            let ids: Vec<u32> = lobby.players.keys().copied().collect();
+            for id in ids {
+                let spawn = logic::choose_spawn_point(lobby, id);
+                if let Some(player) = lobby.players.get_mut(&id) {
+                    player.position = spawn;
+                }
+                lobby.mark_dirty(id);
+            }
+            lobby.lobby_events.push(LobbyEvent::SceneChanged { scene });
+        }
+        OwnerAction::Lock { locked } => {
+            lobby.locked = locked;
+            lobby.lobby_events.push(LobbyEvent::LockChanged { locked });
+        }
+        OwnerAction::TransferOwnership { target_id } => {
+            if !lobby.players.contains_key(&target_id) || lobby.bots.contains_key(&target_id) {
+                return Err("Player not found");
+            }
+            set_owner(lobby, Some(target_id));
+        }
+        OwnerAction::SetTimeScale { time_scale } => lobbies::set_time_scale(lobby, time_scale, Instant::now())?,
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::weapondb::WeaponDb;
+
+    fn hosted_lobby() -> Lobby {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        lobby.hosted = true;
+        let weapons = WeaponDb::load();
+        lobbies::add_bot(&mut lobby, 1, "Bot".to_string(), &weapons).unwrap();
+        for id in [2, 3] {
+            lobbies::add_player(&mut lobby, id, format!("Player{}", id), 1, &weapons).unwrap();
+            claim(&mut lobby, id);
+        }
+        lobby
+    }
+
+    #[test]
+    fn test_first_human_owns_and_ownership_passes_on() {
+        let mut lobby = hosted_lobby();
+        assert_eq!(lobby.owner, Some(2));
+        assert_eq!(lobby.lobby_events, vec![LobbyEvent::OwnerChanged { owner_id: Some(2) }]);
+
+        lobbies::remove_player(&mut lobby, 2);
+        assert_eq!(lobby.owner, Some(3)); // Never the bot
+        lobbies::remove_player(&mut lobby, 3);
+        assert_eq!(lobby.owner, None);
+    }
+
+    #[test]
+    fn test_only_the_owner_acts() {
+        let mut lobby = hosted_lobby();
+        let scenes = SceneDb::load();
+        lobby.lobby_events.clear();
+
+        assert_eq!(apply(&mut lobby, &scenes, 3, OwnerAction::Lock { locked: true }), Err("Not the lobby owner"));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::Kick { target_id: 3 }), Ok(Some(3)));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::Kick { target_id: 2 }), Err("Can't kick yourself"));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::SetMaxPlayers { max_players: 2 }), Err("Fewer slots than players"));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::SetMaxPlayers { max_players: 6 }), Ok(None));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::SetScene { scene: "nowhere".to_string() }), Err("Unknown scene"));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::TransferOwnership { target_id: 1 }), Err("Player not found"));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::TransferOwnership { target_id: 3 }), Ok(None));
+        assert_eq!(apply(&mut lobby, &scenes, 2, OwnerAction::Lock { locked: true }), Err("Not the lobby owner"));
+
+        assert_eq!(lobby.max_players, 6);
+        assert_eq!(lobby.lobby_events, vec![
+            LobbyEvent::PlayerKicked { player_id: 3 },
+            LobbyEvent::MaxPlayersChanged { max_players: 6 },
+            LobbyEvent::OwnerChanged { owner_id: Some(3) },
+        ]);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::domain::logic;
+use crate::state::lobby::Lobby;
+use crate::utils::weapondb::WeaponDb;
+use std::time::{Duration, Instant};
+
+/// How close a player must be to collect a pickup
+pub const PICKUP_RADIUS: f32 = 1.5;
+
+/// Health pack or weapon placed in a lobby's scene
+#[derive(Debug, Clone)]
+pub struct Pickup {
+    pub id: u32,
+    pub position: (f32, f32, f32),
+    pub heal_amount: u32,
+    pub weapon_id: Option<u32>, // Granted (or refilled) on collection
+    pub available_at: Instant, // Collected pickups come back at this time
+}
+
+impl Pickup {
+    pub fn is_available(&self, now: Instant) -> bool {
+        now >= self.available_at
+    }
+}
+
+/// Find the closest available health pickup to a position
+pub fn nearest_available(lobby: &Lobby, position: (f32, f32, f32), now: Instant) -> Option<&Pickup> {
+    lobby
+        .pickups
+        .iter()
+        .filter(|p| p.is_available(now) && p.heal_amount > 0)
+        .min_by(|a, b| {
+            lobby.dimensions.distance(position, a.position).total_cmp(&lobby.dimensions.distance(position, b.position))
+        })
+}
+
+/// Collect a pickup: heals the player, hands over its weapon and starts the pickup's respawn timer
+/// Returns the amount of health restored
+pub fn collect(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    player_id: u32,
+    pickup_id: u32,
+    now: Instant,
+) -> Result<u32, &'static str> {
+    let respawn = Duration::from_secs(lobby.tunables.pickup_respawn_secs);
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    let pickup = lobby
+        .pickups
+        .iter_mut()
+        .find(|p| p.id == pickup_id)
+        .ok_or("Pickup not found")?;
+
+    if !pickup.is_available(now) {
+        return Err("Pickup not available");
+    }
+    if player.is_dead {
+        return Err("Player is dead");
+    }
+    if lobby.dimensions.distance(player.position, pickup.position) > PICKUP_RADIUS {
+        return Err("Pickup out of reach");
+    }
+
+    let healed = pickup.heal_amount.min(player.max_health - player.current_health);
+    player.current_health += healed;
+    pickup.available_at = now + respawn;
+    let weapon_id = pickup.weapon_id;
+
+    lobby.mark_dirty(player_id);
+    if let Some(weapon_id) = weapon_id {
+        logic::grant_weapon(lobby, weapons, player_id, weapon_id)?;
+        lobby.analytics.weapons.entry(weapon_id).or_default().pickups += 1;
+    }
+    Ok(healed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    fn health_pack(id: u32, position: (f32, f32, f32)) -> Pickup {
+        Pickup { id, position, heal_amount: 50, weapon_id: None, available_at: crate::testutil::epoch() }
+    }
+
+    fn setup() -> Lobby {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().health(30)).build();
+        lobby.pickups.push(health_pack(1, (0.0, 1.0, 1.0)));
+        lobby.pickups.push(health_pack(2, (0.0, 1.0, 30.0)));
+        lobby
+    }
+
+    #[test]
+    fn test_collect_heals_and_starts_cooldown() {
+        let mut lobby = setup();
+        assert_eq!(collect(&mut lobby, &WeaponDb::load(), 1, 1, Instant::now()), Ok(50));
+        assert_eq!(lobby.players.get(&1).unwrap().current_health, 80);
+
+        // Cooling down: can't collect again and no longer the nearest
+        assert!(collect(&mut lobby, &WeaponDb::load(), 1, 1, Instant::now()).is_err());
+        let nearest = nearest_available(&lobby, (0.0, 1.0, 0.0), Instant::now()).unwrap();
+        assert_eq!(nearest.id, 2);
+    }
+
+    #[test]
+    fn test_collect_caps_at_max_health() {
+        let mut lobby = setup();
+        lobby.players.get_mut(&1).unwrap().current_health = 90;
+        assert_eq!(collect(&mut lobby, &WeaponDb::load(), 1, 1, Instant::now()), Ok(10));
+        assert_eq!(lobby.players.get(&1).unwrap().current_health, 100);
+    }
+
+    #[test]
+    fn test_collect_out_of_reach() {
+        let mut lobby = setup();
+        assert_eq!(collect(&mut lobby, &WeaponDb::load(), 1, 2, Instant::now()), Err("Pickup out of reach"));
+    }
+
+    #[test]
+    fn test_weapon_pickup_grants_weapon() {
+        let mut lobby = setup();
+        lobby.pickups.push(Pickup { heal_amount: 0, weapon_id: Some(2), ..health_pack(3, (1.0, 1.0, 0.0)) });
+        assert_eq!(collect(&mut lobby, &WeaponDb::load(), 1, 3, Instant::now()), Ok(0));
+        assert_eq!(lobby.players.get(&1).unwrap().to_sync_state().weapons, vec![1, 2]);
+
+        // Weapon-only pickups are no use to a bot looking for health
+        lobby.pickups.retain(|p| p.id == 3);
+        lobby.pickups[0].available_at = crate::testutil::epoch();
+        assert!(nearest_available(&lobby, (0.0, 1.0, 0.0), Instant::now()).is_none());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::domain::logic;
+use crate::domain::mutators;
+use crate::domain::simulator;
+use crate::domain::teams;
+use crate::state::lobby::{Dimensions, Lobby};
+use crate::utils::weapondb::WeaponDb;
+use std::time::{Duration, Instant};
+
+/// A projectile in flight
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projectile {
+    pub id: u32,
+    pub owner_id: u32,
+    pub weapon_id: u32,
+    pub position: (f32, f32, f32),
+    pub velocity: (f32, f32, f32), // Units per second
+    pub gravity: f32,
+    pub expires_at: Instant,
+}
+
+/// Projectile launched or landed since the tick loop last broadcast them
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectileEvent {
+    Spawned {
+        projectile_id: u32,
+        owner_id: u32,
+        weapon_id: u32,
+        position: (f32, f32, f32),
+        velocity: (f32, f32, f32),
+    },
+    Hit {
+        projectile_id: u32,
+        owner_id: u32,
+        weapon_id: u32,
+        target_id: Option<u32>, // None when it struck level geometry
+        position: (f32, f32, f32),
+    },
+}
+
+/// Launch a projectile of the owner's current weapon along `direction`
+/// Returns the new projectile's id
+pub fn spawn(
+    lobby: &mut Lobby,
+    weapons: &WeaponDb,
+    owner_id: u32,
+    origin: (f32, f32, f32),
+    direction: (f32, f32, f32),
+    now: Instant,
+) -> Result<u32, &'static str> {
+    let owner = lobby.players.get(&owner_id).ok_or("Player not found")?;
+    let weapon = weapons.get(owner.current_weapon_id).ok_or("Invalid weapon")?;
+    let spec = weapon.projectile.as_ref().ok_or("Weapon is hitscan")?;
+    let direction = simulator::normalize(lobby.dimensions.flatten_direction(direction)).ok_or("Invalid direction")?;
+    if !(spec.lifetime_secs.is_finite() && spec.lifetime_secs > 0.0) {
+        return Err("Invalid projectile lifetime");
+    }
+
+    let projectile = Projectile {
+        id: lobby.next_projectile_id,
+        owner_id,
+        weapon_id: weapon.id,
+        position: lobby.dimensions.flatten(logic::shot_origin(lobby, owner.position, origin)),
+        velocity: (direction.0 * spec.speed, direction.1 * spec.speed, direction.2 * spec.speed),
+        // Gravity would pull projectiles off a 2D lobby's plane
+        gravity: match lobby.dimensions {
+            Dimensions::Two => 0.0,
+            Dimensions::Three => spec.gravity * mutators::gravity_scale(lobby),
+        },
+        expires_at: now + Duration::from_secs_f32(spec.lifetime_secs),
+    };
+    lobby.next_projectile_id = lobby.next_projectile_id.wrapping_add(1).max(1);
+    lobby.projectile_feed.push(ProjectileEvent::Spawned {
+        projectile_id: projectile.id,
+        owner_id,
+        weapon_id: projectile.weapon_id,
+        position: projectile.position,
+        velocity: projectile.velocity,
+    });
+    let id = projectile.id;
+    lobby.projectiles.push(projectile);
+    Ok(id)
+}
+
+/// Move every projectile `dt` seconds along its arc and resolve what it hits on the way
+/// Players hit take the weapon's damage (kills land in the kill feed); expired projectiles
+/// and those whose owner left are removed without an event
+pub fn step_at(lobby: &mut Lobby, weapons: &WeaponDb, dt: f32, now: Instant) {
+    let projectiles = std::mem::take(&mut lobby.projectiles);
+    for mut projectile in projectiles {
+        if now >= projectile.expires_at || !lobby.players.contains_key(&projectile.owner_id) {
+            continue;
+        }
+
+        projectile.velocity.1 -= projectile.gravity * dt;
+        let from = projectile.position;
+        let to = (
+            from.0 + projectile.velocity.0 * dt,
+            from.1 + projectile.velocity.1 * dt,
+            from.2 + projectile.velocity.2 * dt,
+        );
+        match first_player_hit(lobby, &projectile, from, to) {
+            Some((target_id, impact)) => {
+                hit_player(lobby, weapons, &projectile, target_id, now);
+                push_hit(lobby, &projectile, Some(target_id), impact);
+            }
+            // The position before the wall is close enough for impact effects
+            None if lobby.collision.segment_blocked(from, to) => push_hit(lobby, &projectile, None, from),
+            None => {
+                projectile.position = to;
+                lobby.projectiles.push(projectile);
+            }
+        }
+    }
+}
+
+/// Nearest live player (other than the owner) whose hitbox the segment enters unblocked
+fn first_player_hit(
+    lobby: &Lobby,
+    projectile: &Projectile,
+    from: (f32, f32, f32),
+    to: (f32, f32, f32),
+) -> Option<(u32, (f32, f32, f32))> {
+    let length = simulator::distance(from, to);
+    let direction = simulator::normalize((to.0 - from.0, to.1 - from.1, to.2 - from.2))?;
+    let (target_id, along) = lobby
+        .players
+        .values()
+        .filter(|p| p.id != projectile.owner_id && !p.is_dead && !p.is_loading())
+        .filter_map(|p| {
+            simulator::ray_sphere_distance(from, direction, p.position, lobby.tunables.hitbox_radius).map(|d| (p.id, d))
+        })
+        .filter(|(_, along)| *along <= length)
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    let impact = (from.0 + direction.0 * along, from.1 + direction.1 * along, from.2 + direction.2 * along);
+    simulator::check_line_of_sight(&lobby.collision, from, impact).then_some((target_id, impact))
+}
+
+fn hit_player(lobby: &mut Lobby, weapons: &WeaponDb, projectile: &Projectile, target_id: u32, now: Instant) {
+    if !teams::allows_damage(lobby, projectile.owner_id, target_id) {
+        return;
+    }
+    let Some(damage) = weapons.get(projectile.weapon_id).map(|w| w.damage) else {
+        return;
+    };
+    if let Err(e) = logic::apply_damage(lobby, target_id, damage) {
+        log::debug!("Projectile {} damage on player {} not applied: {}", projectile.id, target_id, e);
+        return;
+    }
+    if !logic::is_player_alive(lobby, target_id) {
+        if let Err(e) = logic::register_kill_at(lobby, weapons, projectile.owner_id, target_id, now) {
+            log::debug!("Projectile {} kill of player {} not registered: {}", projectile.id, target_id, e);
+        }
+    }
+}
+
+fn push_hit(lobby: &mut Lobby, projectile: &Projectile, target_id: Option<u32>, position: (f32, f32, f32)) {
+    lobby.projectile_feed.push(ProjectileEvent::Hit {
+        projectile_id: projectile.id,
+        owner_id: projectile.owner_id,
+        weapon_id: projectile.weapon_id,
+        target_id,
+        position,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::collision::{Aabb, CollisionWorld};
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+    use crate::utils::weapondb::ProjectileSpec;
+
+    const ROCKET: u32 = 10;
+    const DT: f32 = 0.02;
+
+    fn weapons() -> WeaponDb {
+        let mut rocket = WeaponDb::load().get(2).unwrap().clone();
+        rocket.id = ROCKET;
+        rocket.name = "Rocket".to_string();
+        rocket.damage = 60;
+        rocket.projectile = Some(ProjectileSpec { speed: 50.0, gravity: 0.0, lifetime_secs: 2.0 });
+        WeaponDb::load().with_weapon(rocket)
+    }
+
+    fn setup(target_at: (f32, f32, f32)) -> Lobby {
+        LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().weapon(ROCKET))
+            .with_player(PlayerBuilder::new().id(2).position(target_at))
+            .build()
+    }
+
+    fn fly(lobby: &mut Lobby, weapons: &WeaponDb, start: Instant, ticks: u32) {
+        for i in 1..=ticks {
+            step_at(lobby, weapons, DT, start + Duration::from_secs_f32(DT * i as f32));
+        }
+    }
+
+    #[test]
+    fn test_projectile_travels_and_hits() {
+        let weapons = weapons();
+        let mut lobby = setup((0.0, 1.0, -10.0));
+        let start = Instant::now();
+        let id = spawn(&mut lobby, &weapons, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), start).unwrap();
+
+        // 50 u/s covers 1 unit per tick: not there yet after 5 ticks
+        fly(&mut lobby, &weapons, start, 5);
+        assert_eq!(lobby.projectiles.len(), 1);
+        assert!((lobby.projectiles[0].position.2 + 5.0).abs() < 1e-3);
+
+        fly(&mut lobby, &weapons, start, 10);
+        assert!(lobby.projectiles.is_empty());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 40);
+        match lobby.projectile_feed.as_slice() {
+            [ProjectileEvent::Spawned { projectile_id, weapon_id: ROCKET, .. }, ProjectileEvent::Hit { target_id: Some(2), position, .. }] => {
+                assert_eq!(*projectile_id, id);
+                assert!((position.2 + 9.5).abs() < 1e-3, "impact at the hitbox surface, got {:?}", position);
+            }
+            feed => panic!("unexpected feed {:?}", feed),
+        }
+    }
+
+    #[test]
+    fn test_gravity_drops_projectile_under_target() {
+        let mut weapons = weapons();
+        let mut grenade = weapons.get(ROCKET).unwrap().clone();
+        grenade.projectile = Some(ProjectileSpec { speed: 20.0, gravity: 30.0, lifetime_secs: 2.0 });
+        weapons = weapons.with_weapon(grenade);
+        let mut lobby = setup((0.0, 1.0, -20.0));
+        let start = Instant::now();
+        spawn(&mut lobby, &weapons, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), start).unwrap();
+
+        fly(&mut lobby, &weapons, start, 60);
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 100);
+        // Still falling: nothing to land on in an empty world
+        assert!(lobby.projectiles[0].position.1 < -10.0);
+
+        // Expires after its lifetime
+        fly(&mut lobby, &weapons, start, 101);
+        assert!(lobby.projectiles.is_empty());
+        assert_eq!(lobby.projectile_feed.len(), 1);
+    }
+
+    #[test]
+    fn test_walls_stop_projectiles() {
+        let weapons = weapons();
+        let mut lobby = setup((0.0, 1.0, -10.0));
+        lobby.collision = CollisionWorld { occluders: vec![Aabb { min: (-5.0, 0.0, -5.5), max: (5.0, 5.0, -4.5) }] };
+        let start = Instant::now();
+        spawn(&mut lobby, &weapons, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), start).unwrap();
+
+        fly(&mut lobby, &weapons, start, 15);
+        assert!(lobby.projectiles.is_empty());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 100);
+        assert!(matches!(lobby.projectile_feed.last(), Some(ProjectileEvent::Hit { target_id: None, .. })));
+    }
+
+    #[test]
+    fn test_hitscan_weapons_spawn_nothing() {
+        let weapons = weapons();
+        let mut lobby = setup((0.0, 1.0, -10.0));
+        lobby.players.get_mut(&1).unwrap().current_weapon_id = 1;
+        assert_eq!(spawn(&mut lobby, &weapons, 1, (0.0, 1.0, 0.0), (0.0, 0.0, -1.0), Instant::now()), Err("Weapon is hitscan"));
+        assert!(lobby.projectile_feed.is_empty());
+    }
+}
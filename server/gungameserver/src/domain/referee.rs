@@ -0,0 +1,140 @@
+use crate::domain::matches::{self, MatchEvent};
+use crate::domain::owner::LobbyEvent;
+use crate::state::lobby::Lobby;
+use std::time::Instant;
+
+/// Where a referee sends a player
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Coordinates {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// Something only a referee (an admin spectating through the admin API) may do mid-match
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RefereeAction {
+    Freeze { frozen: bool }, // Players and bots hold still until unfrozen
+    Teleport { target_id: u32, position: Coordinates },
+    SetHealth { target_id: u32, health: u32 },
+    SetAmmo { target_id: u32, ammo: u32 },
+    RestartRound,
+}
+
+impl RefereeAction {
+    /// Short description for the audit trail
+    pub fn describe(&self) -> String {
+        match self {
+            RefereeAction::Freeze { frozen } => format!("freeze frozen={}", frozen),
+            RefereeAction::Teleport { target_id, position } => {
+                format!("teleport player {} to ({}, {}, {})", target_id, position.x, position.y, position.z)
+            }
+            RefereeAction::SetHealth { target_id, health } => format!("set_health player {} to {}", target_id, health),
+            RefereeAction::SetAmmo { target_id, ammo } => format!("set_ammo player {} to {}", target_id, ammo),
+            RefereeAction::RestartRound => "restart_round".to_string(),
+        }
+    }
+}
+
+/// Carry out a referee action for `actor`
+/// A restarted round is returned for the caller to broadcast like any round start
+pub fn apply(lobby: &mut Lobby, actor: u32, action: RefereeAction, now: Instant) -> Result<Option<MatchEvent>, &'static str> {
+    if !lobby.spectators.get(&actor).is_some_and(|s| s.referee) {
+        return Err("Not a referee");
+    }
+
+    match action {
+        RefereeAction::Freeze { frozen } => {
+            lobby.frozen = frozen;
+            lobby.lobby_events.push(LobbyEvent::FrozenChanged { frozen });
+        }
+        RefereeAction::Teleport { target_id, position } => {
+            let position = (position.x, position.y, position.z);
+            if ![position.0, position.1, position.2].iter().all(|c| c.is_finite()) {
+                return Err("Invalid position");
+            }
+            let player = lobby.players.get_mut(&target_id).ok_or("Player not found")?;
+            player.position = position;
+            lobby.mark_dirty(target_id);
+            lobby.lobby_events.push(LobbyEvent::PlayerTeleported { player_id: target_id, position });
+        }
+        RefereeAction::SetHealth { target_id, health } => {
+            let player = lobby.players.get_mut(&target_id).ok_or("Player not found")?;
+            if player.is_dead {
+                return Err("Player is dead");
+            }
+            if health == 0 || health > player.max_health {
+                return Err("Health out of range");
+            }
+            player.current_health = health;
+            lobby.mark_dirty(target_id);
+        }
+        RefereeAction::SetAmmo { target_id, ammo } => {
+            let player = lobby.players.get_mut(&target_id).ok_or("Player not found")?;
+            if ammo > player.max_ammo {
+                return Err("Ammo out of range");
+            }
+            player.current_ammo = ammo;
+            lobby.mark_dirty(target_id);
+        }
+        RefereeAction::RestartRound => return matches::restart_round(lobby, now).map(Some),
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::lobbies;
+    use crate::state::lobby::MatchState;
+    use crate::testutil::{epoch, LobbyBuilder, PlayerBuilder};
+
+    #[test]
+    fn test_only_referees_act() {
+        let now = epoch();
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().id(1)).build();
+        lobby.max_spectators = 1;
+        lobbies::add_spectator(&mut lobby, 8, "Watcher".to_string(), now).unwrap();
+        lobbies::add_referee(&mut lobby, 9, "Ref".to_string(), now).unwrap();
+        let freeze = RefereeAction::Freeze { frozen: true };
+
+        assert_eq!(apply(&mut lobby, 1, freeze.clone(), now), Err("Not a referee"));
+        assert_eq!(apply(&mut lobby, 8, freeze.clone(), now), Err("Not a referee"));
+        assert_eq!(apply(&mut lobby, 9, freeze, now), Ok(None));
+        assert!(lobby.frozen);
+
+        let teleport = RefereeAction::Teleport { target_id: 1, position: Coordinates { x: 5.0, y: 1.0, z: -3.0 } };
+        assert_eq!(apply(&mut lobby, 9, teleport, now), Ok(None));
+        assert_eq!(lobby.players[&1].position, (5.0, 1.0, -3.0));
+        let nowhere = RefereeAction::Teleport { target_id: 1, position: Coordinates { x: f32::NAN, y: 1.0, z: 0.0 } };
+        assert_eq!(apply(&mut lobby, 9, nowhere, now), Err("Invalid position"));
+
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::SetHealth { target_id: 1, health: 40 }, now), Ok(None));
+        assert_eq!(lobby.players[&1].current_health, 40);
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::SetHealth { target_id: 1, health: 0 }, now), Err("Health out of range"));
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::SetAmmo { target_id: 1, ammo: 5 }, now), Ok(None));
+        assert_eq!(lobby.players[&1].current_ammo, 5);
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::SetAmmo { target_id: 1, ammo: 21 }, now), Err("Ammo out of range"));
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::SetAmmo { target_id: 2, ammo: 1 }, now), Err("Player not found"));
+
+        assert_eq!(lobby.lobby_events, vec![
+            LobbyEvent::FrozenChanged { frozen: true },
+            LobbyEvent::PlayerTeleported { player_id: 1, position: (5.0, 1.0, -3.0) },
+        ]);
+    }
+
+    #[test]
+    fn test_restart_round() {
+        let now = epoch();
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new().id(1).health(30)).build();
+        lobbies::add_referee(&mut lobby, 9, "Ref".to_string(), now).unwrap();
+
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::RestartRound, now), Err("No round in progress"));
+
+        lobby.match_state = MatchState::InProgress { round: 2, ends_at: now };
+        assert_eq!(apply(&mut lobby, 9, RefereeAction::RestartRound, now), Ok(Some(MatchEvent::RoundStart { round: 2 })));
+        assert_eq!(lobby.match_state, MatchState::InProgress { round: 2, ends_at: now + lobby.rules.round_duration });
+        assert_eq!(lobby.players[&1].current_health, 100);
+    }
+}
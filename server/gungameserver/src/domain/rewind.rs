@@ -0,0 +1,67 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use crate::state::lobby::Player;
+
+/// How long positions are kept: shots are never rewound further than this
+/// (older shots use the oldest position kept)
+pub const HISTORY_WINDOW: Duration = Duration::from_secs(1);
+
+/// One recorded position and when it was taken
+type Sample = (Instant, (f32, f32, f32));
+
+/// Recent positions of every player, recorded once per tick, for lag compensated hits
+#[derive(Debug, Clone, Default)]
+pub struct PositionHistory {
+    positions: HashMap<u32, VecDeque<Sample>>, // Oldest first
+}
+
+impl PositionHistory {
+    /// Record where everyone is at `now` and drop samples older than `HISTORY_WINDOW`
+    pub fn record(&mut self, players: &HashMap<u32, Player>, now: Instant) {
+        self.positions.retain(|id, _| players.contains_key(id));
+        for (id, player) in players {
+            let samples = self.positions.entry(*id).or_default();
+            samples.push_back((now, player.position));
+            while samples.len() > 1 && samples.front().is_some_and(|(t, _)| *t + HISTORY_WINDOW < now) {
+                samples.pop_front();
+            }
+        }
+    }
+
+    /// Where a player was at `time`: the last sample at or before it, else the oldest kept
+    pub fn position_at(&self, player_id: u32, time: Instant) -> Option<(f32, f32, f32)> {
+        let samples = self.positions.get(&player_id)?;
+        samples
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= time)
+            .or(samples.front())
+            .map(|(_, position)| *position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::PlayerBuilder;
+
+    #[test]
+    fn test_position_at_rewinds_to_recorded_ticks() {
+        let start = crate::testutil::epoch() + Duration::from_secs(100);
+        let mut players = HashMap::new();
+        let mut history = PositionHistory::default();
+        for tick in 0..60u64 {
+            players.insert(1, PlayerBuilder::new().position((tick as f32, 1.0, 0.0)).build());
+            history.record(&players, start + Duration::from_millis(tick * 20));
+        }
+
+        let at = |ms| history.position_at(1, start + Duration::from_millis(ms)).unwrap().0;
+        assert_eq!(at(1180), 59.0);
+        assert_eq!(at(250), 12.0); // Between ticks: the earlier one
+        assert_eq!(at(0), 9.0); // Beyond HISTORY_WINDOW: oldest kept
+
+        players.clear();
+        history.record(&players, start + Duration::from_millis(1200));
+        assert_eq!(history.position_at(1, start), None);
+    }
+}
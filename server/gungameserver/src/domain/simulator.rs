@@ -1,29 +1,154 @@
+use crate::domain::collision::CollisionWorld;
+
 /// Hit result from hitscan
 #[derive(Debug, Clone)]
 pub struct HitResult {
     pub player_id: u32,
     pub distance: f32,
+    pub zone: HitZone, // Where the shot entered the hitbox
+}
+
+/// Hits entering a hitbox this far above its center (as a fraction of its radius) are headshots
+pub const HEADSHOT_HEIGHT: f32 = 0.5;
+
+/// Hits entering a hitbox this far below its center (as a fraction of its radius) hit the legs
+pub const LEGS_HEIGHT: f32 = 0.5;
+
+/// Part of a player's hitbox a shot hit, for the weapon's damage multipliers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitZone {
+    Head,
+    Body,
+    Legs,
+}
+
+impl HitZone {
+    /// Zone of an impact `height` above the hitbox center
+    pub fn at(height: f32, radius: f32) -> Self {
+        if height > radius * HEADSHOT_HEIGHT {
+            HitZone::Head
+        } else if height < -radius * LEGS_HEIGHT {
+            HitZone::Legs
+        } else {
+            HitZone::Body
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HitZone::Head => "head",
+            HitZone::Body => "body",
+            HitZone::Legs => "legs",
+        }
+    }
+}
+
+/// Unit facing direction for a yaw/pitch (Godot convention: forward is -Z)
+pub fn facing_direction(yaw: f32, pitch: f32) -> (f32, f32, f32) {
+    (
+        -yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
 }
 
-/// Check line of sight between two positions
-/// Stub: always returns true
+/// Angle in radians between a facing direction and the direction from `from_pos` to `to_pos`
+/// Returns None when the positions overlap (direction undefined)
+pub fn aim_angle(
+    facing: (f32, f32, f32),
+    from_pos: (f32, f32, f32),
+    to_pos: (f32, f32, f32),
+) -> Option<f32> {
+    let delta = (to_pos.0 - from_pos.0, to_pos.1 - from_pos.1, to_pos.2 - from_pos.2);
+    let length = distance(from_pos, to_pos);
+    if length < f32::EPSILON {
+        return None;
+    }
+    let dot = (facing.0 * delta.0 + facing.1 * delta.1 + facing.2 * delta.2) / length;
+    Some(dot.clamp(-1.0, 1.0).acos())
+}
+
+/// Yaw/pitch that faces from `from_pos` towards `to_pos` (inverse of `facing_direction`)
+/// Returns None when the positions overlap
+pub fn yaw_pitch_towards(from_pos: (f32, f32, f32), to_pos: (f32, f32, f32)) -> Option<(f32, f32)> {
+    let length = distance(from_pos, to_pos);
+    if length < f32::EPSILON {
+        return None;
+    }
+    let (dx, dy, dz) = (to_pos.0 - from_pos.0, to_pos.1 - from_pos.1, to_pos.2 - from_pos.2);
+    Some(((-dx).atan2(-dz), (dy / length).clamp(-1.0, 1.0).asin()))
+}
+
+/// Euclidean distance between two positions
+pub fn distance(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+    let (dx, dy, dz) = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+/// Check line of sight between two positions against the lobby's collision geometry
 pub fn check_line_of_sight(
-    _from_pos: (f32, f32, f32),
-    _to_pos: (f32, f32, f32),
+    world: &CollisionWorld,
+    from_pos: (f32, f32, f32),
+    to_pos: (f32, f32, f32),
 ) -> bool {
-    // TODO: Implement actual line-of-sight checking with collision mesh
-    true
+    !world.segment_blocked(from_pos, to_pos)
 }
 
-/// Perform hitscan from origin in direction
-/// Stub: returns None (no hit)
+/// Distance along a ray to where it enters a sphere (0 when the origin is inside it)
+/// `direction` must be unit length; None when the ray misses or the sphere is behind it
+pub fn ray_sphere_distance(
+    origin: (f32, f32, f32),
+    direction: (f32, f32, f32),
+    center: (f32, f32, f32),
+    radius: f32,
+) -> Option<f32> {
+    let to_center = (center.0 - origin.0, center.1 - origin.1, center.2 - origin.2);
+    let along = to_center.0 * direction.0 + to_center.1 * direction.1 + to_center.2 * direction.2;
+    let center_sq = to_center.0 * to_center.0 + to_center.1 * to_center.1 + to_center.2 * to_center.2;
+    let radius_sq = radius * radius;
+    if center_sq <= radius_sq {
+        return Some(0.0);
+    }
+    let miss_sq = center_sq - along * along; // Squared distance from the center to the ray's line
+    if along < 0.0 || miss_sq > radius_sq {
+        return None;
+    }
+    Some(along - (radius_sq - miss_sq).sqrt())
+}
+
+/// Unit vector in the same direction (None for a zero or non-finite vector)
+pub fn normalize(v: (f32, f32, f32)) -> Option<(f32, f32, f32)> {
+    let length = distance((0.0, 0.0, 0.0), v);
+    (length.is_finite() && length > f32::EPSILON).then(|| (v.0 / length, v.1 / length, v.2 / length))
+}
+
+/// Perform hitscan from origin in direction against sphere hitboxes at `targets`
+/// Returns the nearest target hit within `range` that no occluder blocks
 pub fn perform_hitscan(
-    _origin: (f32, f32, f32),
-    _direction: (f32, f32, f32),
-    _range: f32,
+    world: &CollisionWorld,
+    targets: impl IntoIterator<Item = (u32, (f32, f32, f32))>,
+    origin: (f32, f32, f32),
+    direction: (f32, f32, f32),
+    range: f32,
+    radius: f32,
 ) -> Option<HitResult> {
-    // TODO: Implement actual hitscan with player positions and collision
-    None
+    let direction = normalize(direction)?;
+    let (mut nearest, center) = targets
+        .into_iter()
+        .filter_map(|(player_id, center)| {
+            ray_sphere_distance(origin, direction, center, radius)
+                .map(|distance| (HitResult { player_id, distance, zone: HitZone::Body }, center))
+        })
+        .filter(|(hit, _)| hit.distance <= range)
+        .min_by(|a, b| a.0.distance.total_cmp(&b.0.distance))?;
+
+    let impact = (
+        origin.0 + direction.0 * nearest.distance,
+        origin.1 + direction.1 * nearest.distance,
+        origin.2 + direction.2 * nearest.distance,
+    );
+    nearest.zone = HitZone::at(impact.1 - center.1, radius);
+    check_line_of_sight(world, origin, impact).then_some(nearest)
 }
 
 /// Check if position collides with world geometry
@@ -40,16 +165,77 @@ pub fn check_collision(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_facing_direction() {
+        let forward = facing_direction(0.0, 0.0);
+        assert!((forward.2 + 1.0).abs() < 1e-6);
+
+        let left = facing_direction(std::f32::consts::FRAC_PI_2, 0.0);
+        assert!((left.0 + 1.0).abs() < 1e-6);
+
+        let up = facing_direction(0.0, std::f32::consts::FRAC_PI_2);
+        assert!((up.1 - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_yaw_pitch_towards_roundtrip() {
+        let from = (1.0, 1.0, 1.0);
+        let to = (4.0, 3.0, -5.0);
+        let (yaw, pitch) = yaw_pitch_towards(from, to).unwrap();
+        let angle = aim_angle(facing_direction(yaw, pitch), from, to).unwrap();
+        assert!(angle < 1e-3);
+        assert!(yaw_pitch_towards(from, from).is_none());
+    }
+
+    #[test]
+    fn test_aim_angle() {
+        let facing = facing_direction(0.0, 0.0);
+        let straight = aim_angle(facing, (0.0, 0.0, 0.0), (0.0, 0.0, -10.0)).unwrap();
+        assert!(straight.abs() < 1e-3);
+
+        let behind = aim_angle(facing, (0.0, 0.0, 0.0), (0.0, 0.0, 10.0)).unwrap();
+        assert!((behind - std::f32::consts::PI).abs() < 1e-3);
+
+        assert!(aim_angle(facing, (1.0, 1.0, 1.0), (1.0, 1.0, 1.0)).is_none());
+    }
+
     #[test]
     fn test_check_line_of_sight() {
-        let result = check_line_of_sight((0.0, 0.0, 0.0), (10.0, 0.0, 0.0));
-        assert!(result);
+        let open = CollisionWorld::default();
+        assert!(check_line_of_sight(&open, (0.0, 0.0, 0.0), (10.0, 0.0, 0.0)));
+
+        let walled = CollisionWorld {
+            occluders: vec![crate::domain::collision::Aabb { min: (4.0, -1.0, -1.0), max: (5.0, 1.0, 1.0) }],
+        };
+        assert!(!check_line_of_sight(&walled, (0.0, 0.0, 0.0), (10.0, 0.0, 0.0)));
     }
 
     #[test]
     fn test_perform_hitscan() {
-        let result = perform_hitscan((0.0, 0.0, 0.0), (1.0, 0.0, 0.0), 100.0);
+        let open = CollisionWorld::default();
+        let result = perform_hitscan(&open, Vec::new(), (0.0, 0.0, 0.0), (1.0, 0.0, 0.0), 100.0, 0.5);
         assert!(result.is_none());
+
+        // Nearest sphere along the ray wins; grazing within the radius still hits
+        let targets = vec![(2, (20.0, 0.0, 0.0)), (3, (10.0, 0.4, 0.0)), (4, (5.0, 2.0, 0.0))];
+        let hit = perform_hitscan(&open, targets.clone(), (0.0, 0.0, 0.0), (2.0, 0.0, 0.0), 100.0, 0.5).unwrap();
+        assert_eq!(hit.player_id, 3);
+        assert!((hit.distance - 9.7).abs() < 1e-3);
+        assert_eq!(hit.zone, HitZone::Legs); // Entered low on the hitbox
+
+        // Entering through the top of the hitbox is a headshot
+        let ducking = vec![(5, (10.0, -0.4, 0.0))];
+        assert_eq!(perform_hitscan(&open, ducking, (0.0, 0.0, 0.0), (1.0, 0.0, 0.0), 100.0, 0.5).unwrap().zone, HitZone::Head);
+        let level = vec![(6, (10.0, 0.0, 0.0))];
+        assert_eq!(perform_hitscan(&open, level, (0.0, 0.0, 0.0), (1.0, 0.0, 0.0), 100.0, 0.5).unwrap().zone, HitZone::Body);
+
+        assert!(perform_hitscan(&open, targets.clone(), (0.0, 0.0, 0.0), (1.0, 0.0, 0.0), 9.0, 0.5).is_none());
+        assert!(perform_hitscan(&open, targets.clone(), (0.0, 0.0, 0.0), (-1.0, 0.0, 0.0), 100.0, 0.5).is_none());
+
+        let walled = CollisionWorld {
+            occluders: vec![crate::domain::collision::Aabb { min: (4.0, -1.0, -1.0), max: (5.0, 1.0, 1.0) }],
+        };
+        assert!(perform_hitscan(&walled, targets, (0.0, 0.0, 0.0), (1.0, 0.0, 0.0), 100.0, 0.5).is_none());
     }
 
     #[test]
@@ -0,0 +1,142 @@
+use crate::state::lobby::Lobby;
+
+/// Most teams a lobby can be split into
+pub const MAX_TEAMS: u32 = 4;
+
+/// Players currently on `team_id`
+pub fn team_size(lobby: &Lobby, team_id: u32) -> usize {
+    lobby.players.values().filter(|p| p.team_id == Some(team_id)).count()
+}
+
+/// Smallest team (lowest id on ties), ignoring `player_id` itself
+fn smallest_team(lobby: &Lobby, player_id: u32) -> Option<u32> {
+    (1..=lobby.team_count).min_by_key(|team_id| {
+        lobby.players.values().filter(|p| p.id != player_id && p.team_id == Some(*team_id)).count()
+    })
+}
+
+/// Put a new player on the smallest team (no-op without teams)
+pub fn assign(lobby: &mut Lobby, player_id: u32) {
+    let team_id = smallest_team(lobby, player_id);
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.team_id = team_id;
+    }
+    lobby.mark_dirty(player_id);
+}
+
+/// Move a player to `team_id`; refused when it would leave that team two or more
+/// players bigger than the one being left
+pub fn switch_team(lobby: &mut Lobby, player_id: u32, team_id: u32) -> Result<(), &'static str> {
+    if lobby.team_count == 0 {
+        return Err("Lobby has no teams");
+    }
+    if team_id == 0 || team_id > lobby.team_count {
+        return Err("Invalid team");
+    }
+    let current = lobby.players.get(&player_id).ok_or("Player not found")?.team_id;
+    if current == Some(team_id) {
+        return Ok(());
+    }
+
+    let joined_size = team_size(lobby, team_id) + 1;
+    let left_size = current.map(|t| team_size(lobby, t) - 1).unwrap_or(0);
+    if current.is_some() && joined_size > left_size + 1 {
+        return Err("Teams would be unbalanced");
+    }
+
+    if let Some(player) = lobby.players.get_mut(&player_id) {
+        player.team_id = Some(team_id);
+    }
+    lobby.mark_dirty(player_id);
+    Ok(())
+}
+
+/// Both players are on the same team
+pub fn are_teammates(lobby: &Lobby, a: u32, b: u32) -> bool {
+    let team = |id| lobby.players.get(&id).and_then(|p| p.team_id);
+    a != b && team(a).is_some() && team(a) == team(b)
+}
+
+/// Hits between teammates are refused unless friendly fire is on
+pub fn allows_damage(lobby: &Lobby, shooter_id: u32, target_id: u32) -> bool {
+    lobby.friendly_fire || !are_teammates(lobby, shooter_id, target_id)
+}
+
+/// Credit the killer's team (team kills don't count)
+/// Horde sides aren't teams the lobby keeps score for
+pub fn on_kill(lobby: &mut Lobby, killer_id: u32, victim_id: u32, points: u32) {
+    if lobby.team_count == 0 || are_teammates(lobby, killer_id, victim_id) {
+        return;
+    }
+    if let Some(team_id) = lobby.players.get(&killer_id).and_then(|p| p.team_id) {
+        *lobby.team_scores.entry(team_id).or_insert(0) += points;
+        lobby.team_scores_dirty = true;
+    }
+}
+
+/// Zero every team's score for a new match
+pub fn reset_scores(lobby: &mut Lobby) {
+    if lobby.team_count == 0 {
+        return;
+    }
+    lobby.team_scores = (1..=lobby.team_count).map(|team_id| (team_id, 0)).collect();
+    lobby.team_scores_dirty = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::lobbies;
+    use crate::utils::weapondb::WeaponDb;
+    use crate::testutil::LobbyBuilder;
+
+    fn team_lobby(players: u32) -> Lobby {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new().build();
+        lobby.team_count = 2;
+        for id in 1..=players {
+            lobbies::add_player(&mut lobby, id, format!("P{}", id), 1, &weapons).unwrap();
+        }
+        lobby
+    }
+
+    #[test]
+    fn test_joins_are_balanced() {
+        let lobby = team_lobby(4);
+        assert_eq!(team_size(&lobby, 1), 2);
+        assert_eq!(team_size(&lobby, 2), 2);
+        assert_eq!(lobby.players.get(&1).unwrap().team_id, Some(1));
+        assert_eq!(lobby.players.get(&2).unwrap().team_id, Some(2));
+    }
+
+    #[test]
+    fn test_switch_team_keeps_balance() {
+        let mut lobby = team_lobby(3); // 1 and 3 on team 1, 2 on team 2
+        assert!(switch_team(&mut lobby, 1, 2).is_ok()); // 1 vs 2
+        assert_eq!(switch_team(&mut lobby, 3, 2), Err("Teams would be unbalanced")); // would be 0 vs 3
+        assert_eq!(switch_team(&mut lobby, 3, 5), Err("Invalid team"));
+    }
+
+    #[test]
+    fn test_friendly_fire_and_team_scores() {
+        let mut lobby = team_lobby(3);
+        assert!(!allows_damage(&lobby, 1, 3));
+        assert!(allows_damage(&lobby, 1, 2));
+        lobby.friendly_fire = true;
+        assert!(allows_damage(&lobby, 1, 3));
+
+        on_kill(&mut lobby, 1, 2, 100);
+        on_kill(&mut lobby, 1, 3, 100); // Team kill
+        assert_eq!(lobby.team_scores.get(&1), Some(&100));
+        assert!(lobby.team_scores_dirty);
+    }
+
+    #[test]
+    fn test_no_teams_in_free_for_all() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new().build();
+        lobbies::add_player(&mut lobby, 1, "Ada".to_string(), 1, &weapons).unwrap();
+        assert_eq!(lobby.players.get(&1).unwrap().team_id, None);
+        assert!(switch_team(&mut lobby, 1, 1).is_err());
+    }
+}
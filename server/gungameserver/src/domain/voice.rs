@@ -0,0 +1,77 @@
+use crate::state::lobby::Lobby;
+
+/// Largest voice payload relayed (encoded frame as sent by the client)
+pub const MAX_VOICE_PAYLOAD: usize = 4096;
+/// Proximity voice reaches players this close to the speaker
+pub const VOICE_RADIUS: f32 = 30.0;
+
+/// Who a player's voice frame goes to: everyone connected but the speaker and those who muted them
+/// With `proximity` only players within `VOICE_RADIUS` hear it; spectators hear everything
+/// Payloads are never decoded; only their size is checked
+pub fn recipients(lobby: &Lobby, speaker_id: u32, payload: &str, proximity: bool) -> Result<Vec<u32>, &'static str> {
+    if payload.is_empty() || payload.len() > MAX_VOICE_PAYLOAD {
+        return Err("Invalid voice payload");
+    }
+    let speaker = lobby.players.get(&speaker_id).ok_or("Player not found")?;
+    let mut recipients: Vec<u32> = lobby
+        .client_addresses
+        .keys()
+        .copied()
+        .filter(|id| *id != speaker_id)
+        .filter(|id| match lobby.players.get(id) {
+            Some(listener) => {
+                !listener.muted.contains(&speaker_id)
+                    && (!proximity || lobby.dimensions.distance(speaker.position, listener.position) <= VOICE_RADIUS)
+            }
+            None => lobby.spectators.contains_key(id),
+        })
+        .collect();
+    recipients.sort_unstable();
+    Ok(recipients)
+}
+
+/// Stop (or resume) relaying `target_id`'s voice to `player_id`
+pub fn set_muted(lobby: &mut Lobby, player_id: u32, target_id: u32, muted: bool) -> Result<(), &'static str> {
+    if player_id == target_id {
+        return Err("Can't mute yourself");
+    }
+    let player = lobby.players.get_mut(&player_id).ok_or("Player not found")?;
+    if muted {
+        player.muted.insert(target_id);
+    } else {
+        player.muted.remove(&target_id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn test_recipients_respect_mutes_and_proximity() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().id(1).position((0.0, 1.0, 0.0)))
+            .with_player(PlayerBuilder::new().id(2).position((10.0, 1.0, 0.0)))
+            .with_player(PlayerBuilder::new().id(3).position((100.0, 1.0, 0.0)))
+            .build();
+        for id in 1..=3 {
+            let addr: SocketAddr = format!("127.0.0.1:{}", 4000 + id).parse().unwrap();
+            lobby.client_addresses.insert(id, addr);
+        }
+
+        assert_eq!(recipients(&lobby, 1, "AAEC", false), Ok(vec![2, 3]));
+        assert_eq!(recipients(&lobby, 1, "AAEC", true), Ok(vec![2]));
+        assert_eq!(recipients(&lobby, 1, "", false), Err("Invalid voice payload"));
+        assert_eq!(recipients(&lobby, 1, &"A".repeat(MAX_VOICE_PAYLOAD + 1), false), Err("Invalid voice payload"));
+
+        set_muted(&mut lobby, 2, 1, true).unwrap();
+        assert_eq!(recipients(&lobby, 1, "AAEC", false), Ok(vec![3]));
+        assert_eq!(recipients(&lobby, 2, "AAEC", false), Ok(vec![1, 3])); // Muting is one way
+        set_muted(&mut lobby, 2, 1, false).unwrap();
+        assert_eq!(recipients(&lobby, 1, "AAEC", false), Ok(vec![2, 3]));
+        assert_eq!(set_muted(&mut lobby, 2, 2, true), Err("Can't mute yourself"));
+    }
+}
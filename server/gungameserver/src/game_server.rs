@@ -0,0 +1,287 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::task::JoinHandle;
+use crate::domain::{self, anticheat::IntegrityRules, gungame::GameMode};
+use crate::net;
+use crate::server;
+use crate::state::{self, lobby::{Dimensions, LobbySpec, Visibility}, server_state::ServerState, telemetry::TelemetrySink};
+use crate::storage::{self, Storage};
+use crate::utils::{config::Config, scaling, weapondb::WeaponDb};
+
+/// Sets up a game server to run inside the current tokio runtime
+/// Everything not set comes from the config (files and defaults loaded as the `gungameserver` binary would)
+pub struct GameServerBuilder {
+    config: Config,
+    weapons: Option<WeaponDb>,
+    storage: Option<Arc<Storage>>,
+}
+
+impl GameServerBuilder {
+    pub fn new(config: Config) -> Self {
+        Self { config, weapons: None, storage: None }
+    }
+
+    /// HTTP port to listen on; 0 picks a free one (see `GameServer::http_addr`)
+    pub fn http_port(mut self, port: u16) -> Self {
+        self.config.http_port = port;
+        self
+    }
+
+    /// UDP port to listen on; 0 picks a free one (see `GameServer::udp_addr`)
+    pub fn udp_port(mut self, port: u16) -> Self {
+        self.config.udp_port = port;
+        self
+    }
+
+    /// Weapon definitions to use instead of `weapons_path` or the bundled ones
+    pub fn weapons(mut self, weapons: WeaponDb) -> Self {
+        self.weapons = Some(weapons);
+        self
+    }
+
+    /// Checkpoint lobbies to this storage instead of opening `storage_path`
+    pub fn storage(mut self, storage: Arc<Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Bind the ports, restore checkpointed lobbies and start serving
+    /// Process-level concerns (logging, signals) are left to the caller
+    pub async fn start(self) -> Result<GameServer, Box<dyn std::error::Error>> {
+        let mut config = self.config;
+        let http_listener = TcpListener::bind(("0.0.0.0", config.http_port)).await?;
+        // The first UDP socket also sends for the lobby tick loops, extra ones share the port as receivers
+        let udp_sockets = net::reuseport::bind_shards(([0, 0, 0, 0], config.udp_port).into(), config.udp_receivers)?
+            .into_iter()
+            .map(|socket| UdpSocket::from_std(socket).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let udp_socket = udp_sockets[0].clone();
+        let http_addr = http_listener.local_addr()?;
+        let udp_addr = udp_socket.local_addr()?;
+        // Lobbies hand out the ports actually bound
+        config.http_port = http_addr.port();
+        config.udp_port = udp_addr.port();
+        config.validate()?;
+        let config = Arc::new(config);
+        log::info!("UDP socket bound to port {}", config.udp_port);
+        // Join responses carry session tokens and keys, and account logins carry passwords
+        if !config.behind_tls_proxy {
+            log::warn!("HTTP API on port {} is plaintext: serve it behind a TLS-terminating proxy and set behind_tls_proxy", config.http_port);
+        }
+
+        let state = Arc::new(ServerState::new());
+        state.ip_sessions.configure(config.max_sessions_per_ip, &config.session_limit_exempt_ips);
+        state.lobby_ports.configure(config.lobby_udp_port_min, config.lobby_udp_port_max);
+        state.set_message_text(config.message_text);
+        state.set_chat_filter(domain::chat::ChatFilter::new(&config.chat_blocked_words));
+        if let Some(path) = &config.player_id_state_path {
+            state.restore_player_ids(std::path::Path::new(path))?;
+        }
+        if let Some(path) = &config.reports_path {
+            state.reports.restore(std::path::Path::new(path))?;
+        }
+        if let Some(path) = &config.ladder_presets_path {
+            state.ladder_presets.restore(std::path::Path::new(path))?;
+        }
+        if let Some(path) = &config.tunables_path {
+            state.tunables.load(std::path::Path::new(path))?;
+        }
+        match self.weapons {
+            Some(weapons) => {
+                state.weapons.set(weapons);
+            }
+            None => {
+                if let Some(path) = &config.weapons_path {
+                    state.weapons.load(std::path::Path::new(path))?;
+                }
+            }
+        }
+        let weapons = state.weapons.get();
+
+        // Lobbies open at the last checkpoint come back first, before any bot takes a player id
+        let storage = match (self.storage, &config.storage_path) {
+            (Some(storage), _) => Some(storage),
+            (None, Some(path)) => Some(Arc::new(Storage::open(path).await?)),
+            (None, None) => None,
+        };
+        if let Some(storage) = &storage {
+            let ladder = domain::gungame::build_ladder(&config.gun_game_ladder, &weapons)?;
+            let records = storage.load(&ladder).await?;
+            let restored = storage::restore_lobbies(&state, records, weapons.clone(), config.clone(), udp_socket.clone()).await;
+            log::info!("Restored {} lobbies from storage", restored);
+            state.use_storage(storage.clone());
+            let secret = config.account_token_secret.clone().unwrap_or_else(|| {
+                log::warn!("account_token_secret is unset: account logins won't survive a restart");
+                net::crypto::generate_token()
+            });
+            state.use_account_tokens(net::auth::TokenIssuer::new(secret.as_bytes(), config.account_token_ttl_secs));
+        }
+
+        // Create default test lobby, with optional bots to play against
+        // Persistent: never closed for being empty and recreated if it's ever removed
+        let test_lobby = LobbySpec {
+            code: "test".to_string(),
+            max_players: 8,
+            scene: "test_world".to_string(),
+            mode: GameMode::Ffa,
+            team_count: 0,
+            dimensions: Dimensions::Three,
+            bots: config.test_lobby_bots,
+            bot_fill: 0,
+            tick_rate_hz: None,
+            persistent: true,
+            hide_enemy_state: config.hide_enemy_state,
+            integrity: IntegrityRules { max_fov: config.max_client_fov, aim_assist: config.aim_assist_allowed },
+            visibility: Visibility::Public,
+            password: None,
+            hosted: false,
+            record_replay: false,
+        };
+        if !state.lobby_exists(&test_lobby.code) {
+            server::create_lobby_from_spec(&state, test_lobby, weapons.clone(), config.clone(), udp_socket.clone())?;
+            log::info!("Created test lobby 'test'");
+        }
+
+        let mut tasks = Vec::new();
+        // Report load for external auto-scaling
+        if let Some(target) = &config.scaling_signal_target {
+            match scaling::ScalingSink::parse(target) {
+                Ok(sink) => {
+                    let interval = std::time::Duration::from_secs(config.scaling_signal_interval_secs);
+                    tasks.push(tokio::spawn(scaling::run_scaling_reporter(state.clone(), sink, interval)));
+                    log::info!("Emitting scaling signals to {}", target);
+                }
+                Err(e) => log::warn!("Scaling signals disabled: {}", e),
+            }
+        }
+
+        // Anonymized gameplay events for balance work, only when a target is configured
+        let mut telemetry_sink = None;
+        if let Some(target) = &config.telemetry_target {
+            match TelemetrySink::parse(target) {
+                Ok(sink) => {
+                    state.telemetry.configure(config.telemetry_sample_rate, config.telemetry_max_buffered);
+                    let interval = std::time::Duration::from_secs(config.telemetry_flush_secs.max(1));
+                    tasks.push(tokio::spawn(state::telemetry::run_telemetry_flusher(state.clone(), sink.clone(), interval)));
+                    telemetry_sink = Some(sink);
+                    log::info!("Writing telemetry to {} (sample rate {})", target, config.telemetry_sample_rate);
+                }
+                Err(e) => log::warn!("Telemetry disabled: {}", e),
+            }
+        }
+
+        if config.balance_report_secs > 0 && config.lobby_analytics_path.is_some() {
+            let interval = std::time::Duration::from_secs(config.balance_report_secs);
+            tasks.push(tokio::spawn(state::balance::run_balance_reporter(state.clone(), config.clone(), interval)));
+        }
+
+        if let Some(storage) = &storage {
+            if config.checkpoint_secs > 0 {
+                let interval = std::time::Duration::from_secs(config.checkpoint_secs);
+                tasks.push(tokio::spawn(storage::run_checkpointer(state.clone(), storage.clone(), interval)));
+            }
+        }
+
+        if config.empty_lobby_close_secs > 0 {
+            tasks.push(tokio::spawn(server::run_lobby_reaper(state.clone(), config.clone())));
+        }
+        tasks.push(tokio::spawn(server::run_persistent_lobbies(state.clone(), weapons.clone(), config.clone(), udp_socket.clone())));
+        if config.warm_pool_size > 0 {
+            tasks.push(tokio::spawn(server::run_warm_pool(state.clone(), weapons.clone(), config.clone(), udp_socket.clone())));
+        }
+
+        let servers = server::spawn_servers(state.clone(), weapons, config.clone(), http_listener, udp_sockets).await?;
+        Ok(GameServer { state, config, storage, telemetry_sink, http_addr, udp_addr, servers, tasks })
+    }
+}
+
+/// A running game server (see `GameServerBuilder`)
+pub struct GameServer {
+    state: Arc<ServerState>,
+    config: Arc<Config>,
+    storage: Option<Arc<Storage>>,
+    telemetry_sink: Option<TelemetrySink>,
+    http_addr: SocketAddr,
+    udp_addr: SocketAddr,
+    servers: Vec<JoinHandle<()>>, // HTTP, UDP and bus servers
+    tasks: Vec<JoinHandle<()>>, // Background jobs (reaper, checkpoints, reporters)
+}
+
+impl GameServer {
+    pub fn state(&self) -> &Arc<ServerState> {
+        &self.state
+    }
+
+    /// The config in effect, with the ports actually bound
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    pub fn http_addr(&self) -> SocketAddr {
+        self.http_addr
+    }
+
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+
+    /// Serve until the servers stop, which they only do on a fatal error
+    pub async fn run(&mut self) -> Result<(), String> {
+        for server in &mut self.servers {
+            server.await.map_err(|e| format!("Server task failed: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Shut down gracefully: notify lobbies, wait out `shutdown_grace_secs` for players to leave,
+    /// checkpoint and close every lobby, then stop serving
+    pub async fn shutdown(self) {
+        server::graceful_shutdown(&self.state, &self.config, self.storage.as_deref()).await;
+        if let Some(sink) = &self.telemetry_sink {
+            state::telemetry::flush_telemetry(&self.state, sink).await;
+        }
+        for task in self.tasks.iter().chain(&self.servers) {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embedded_server_serves_and_shuts_down() {
+        // No files: the test mustn't read or leave state in the working directory
+        let config = Config {
+            player_id_state_path: None,
+            reports_path: None,
+            storage_path: None,
+            tunables_path: None,
+            weapons_path: None,
+            ladder_presets_path: None,
+            lobby_analytics_path: None,
+            shutdown_grace_secs: 0,
+            ..Config::default()
+        };
+        let server = GameServerBuilder::new(config).http_port(0).udp_port(0).start().await.unwrap();
+        assert_ne!(server.http_addr().port(), 0);
+        assert_eq!(server.config().udp_port, server.udp_addr().port());
+
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", server.http_addr().port())).await.unwrap();
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        stream.write_all(b"GET /lobbies/test HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(&format!("\"udp_port\":{}", server.udp_addr().port())));
+
+        let state = server.state().clone();
+        let http_port = server.http_addr().port();
+        server.shutdown().await;
+        assert!(!state.lobby_exists("test"));
+        tokio::task::yield_now().await;
+        assert!(tokio::net::TcpListener::bind(("0.0.0.0", http_port)).await.is_ok()); // Port released
+    }
+}
@@ -1,70 +1,175 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
-use crate::handlers::models::{CreateLobbyRequest, JoinLobbyRequest, JoinLobbyResponse, LobbyInfo, PlayerInfo};
+use crate::handlers::models::{
+    AccountCredentials, AccountInfo, AccountLeaderboard, AccountLeaderboardEntry, AccountLeaderboardQuery, AccountSession, AdminBroadcastRequest, AdminKickRequest, AdminLobbyInfo, AuditQuery, BulkAdminResponse, CreateLobbyRequest, GrantWeapon, JoinFreeze, JoinLobbyRequest, JoinLobbyResponse, LadderResponse, ListLobbiesQuery, ListReportsQuery, LobbyChanges,
+    LobbyInfo, LogLevelsResponse, MatchHistory, MatchHistoryEntry, MatchHistoryQuery, MatchmakeRequest, MergeLobbyRequest, MergeLobbyResponse, OwnerActionRequest, PlayerInfo, PlayerSearchLobby, PlayerSearchQuery, PlayerSearchResult, ReloadResponse, RematchRequest, ResolveReportRequest, SaveLadderPresetRequest, ServerInfo,
+    SetLadderRequest, SetLogLevelRequest, SpectateRequest, SubmitReportRequest, TimeScale, SubmitReportResponse,
+};
+use crate::state::lobby::{Dimensions, Lobby, LobbySpec, PlayerSyncState, Visibility};
+use crate::state::commands::LobbyCommand;
 use crate::state::server_state::ServerState;
+use crate::state::snapshot::LobbyListing;
+use crate::state::reports::{self, PlayerReport, ReportEvidence, ReportStatus};
+use crate::state::audit::AuditEntry;
+use crate::state::balance::{self, BalanceReport};
+use crate::state::ladder_presets::LadderPresets;
+use crate::domain::anticheat::{self, IntegrityRules};
+use crate::domain::gungame::{self, GameMode};
+use crate::domain::history::MatchRecord;
+use crate::domain::horde::Horde;
 use crate::domain::lobbies;
-use crate::utils::weapondb::WeaponDb;
+use crate::domain::logic;
+use crate::domain::owner;
+use crate::net::auth::TokenIssuer;
+use crate::net::crypto::{self, PasswordHash};
+use crate::net::protocol::{self, ClientProtocol};
+use crate::net::rate_limit::LoginLimiter;
+use crate::domain::teams;
+use crate::utils::weapondb::{WeaponData, WeaponDb};
 use crate::utils::config::Config;
+use crate::storage::{Account, LeaderboardMetric, LeaderboardPeriod, Storage};
+use crate::utils::logging::{self, LogLevels};
+use crate::utils::tunables::Tunables;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 
 /// App state for HTTP handlers (includes server state and dependencies)
 #[derive(Clone)]
 pub struct AppState {
     pub state: Arc<ServerState>,
-    pub weapons: Arc<WeaponDb>,
     pub config: Arc<Config>,
     pub udp_socket: Arc<UdpSocket>,
+    pub login_limiter: Arc<LoginLimiter>,  // `/accounts/login` attempts per client IP and per username
 }
 
+/// Longest lobby password
+const MAX_PASSWORD_LENGTH: usize = 64;
+
+/// Longest admin announcement or kick reason
+const MAX_NOTICE_LENGTH: usize = 200;
+
+/// Account password length bounds
+const MIN_ACCOUNT_PASSWORD_LENGTH: usize = 8;
+const MAX_ACCOUNT_PASSWORD_LENGTH: usize = 128;
+
 /// Thin HTTP handler: Create lobby
+/// Persistent and recorded lobbies can only be created by admins (`POST /admin/lobbies`)
 pub async fn create_lobby(
     State(app_state): State<AppState>,
     Json(request): Json<CreateLobbyRequest>,
 ) -> Result<Json<LobbyInfo>, StatusCode> {
+    if request.persistent || request.record_replay {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    create_from_request(&app_state, request).await
+}
+
+/// Thin HTTP handler: Create a lobby as an admin, optionally persistent or recorded
+pub async fn admin_create_lobby(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateLobbyRequest>,
+) -> Result<Json<LobbyInfo>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    create_from_request(&app_state, request).await
+}
+
+async fn create_from_request(app_state: &AppState, request: CreateLobbyRequest) -> Result<Json<LobbyInfo>, StatusCode> {
+    if app_state.state.is_draining() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if !ServerState::is_valid_lobby_code(&request.code) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
     if app_state.state.lobby_exists(&request.code) {
         return Err(StatusCode::CONFLICT);
     }
 
     let max_players = request.max_players.unwrap_or(4);
     let scene = request.scene.unwrap_or_else(|| "world".to_string());
+    let ladder = gungame::build_ladder(&app_state.config.gun_game_ladder, &app_state.state.weapons.get()).map_err(|e| {
+        log::error!("Invalid gun game ladder: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let mode = GameMode::parse(request.mode.as_deref().unwrap_or("ffa"), ladder)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let team_count = request.teams.unwrap_or(0);
+    if team_count == 1 || team_count > teams::MAX_TEAMS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if team_count > 0 && matches!(mode, GameMode::Horde(_)) {
+        return Err(StatusCode::BAD_REQUEST); // Horde sides are fixed: players against the waves
+    }
+    let bots = request.bots.unwrap_or(0);
+    if bots >= max_players {
+        return Err(StatusCode::BAD_REQUEST); // At least one slot is left for a human
+    }
+    let bot_fill = request.bot_fill.unwrap_or(0);
+    if bot_fill >= max_players {
+        return Err(StatusCode::BAD_REQUEST); // A joining player needs a slot before a fill bot makes way
+    }
+    if (bots > 0 || bot_fill > 0) && matches!(mode, GameMode::Horde(_)) {
+        return Err(StatusCode::BAD_REQUEST); // The waves are the bots
+    }
+    let config = &app_state.config;
+    if request.tick_rate_hz.is_some_and(|hz| !(config.lobby_tick_rate_min_hz..=config.lobby_tick_rate_max_hz).contains(&hz)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let dimensions = Dimensions::parse(request.dimensions.unwrap_or(3)).ok_or(StatusCode::BAD_REQUEST)?;
+    let visibility = Visibility::parse(request.visibility.as_deref().unwrap_or("public")).ok_or(StatusCode::BAD_REQUEST)?;
+    let password = match &request.password {
+        Some(password) if password.is_empty() || password.chars().count() > MAX_PASSWORD_LENGTH => {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Some(password) => Some(PasswordHash::new_blocking(password.clone()).await),
+        None => None,
+    };
+    let integrity = IntegrityRules {
+        max_fov: request.max_fov.unwrap_or(app_state.config.max_client_fov),
+        aim_assist: request.aim_assist.unwrap_or(app_state.config.aim_assist_allowed),
+    };
+    if !(integrity.max_fov > 0.0 && integrity.max_fov <= 360.0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-    // Create lobby and spawn tick loop
-    if let Err(e) = crate::server::create_lobby_with_tick(
-        app_state.state.clone(),
-        request.code.clone(),
+    // Create lobby and spawn tick loop (mode and teams are set before anyone can join)
+    let spec = LobbySpec {
+        code: request.code.clone(),
         max_players,
-        scene.clone(),
-        app_state.weapons.clone(),
+        scene,
+        mode,
+        team_count,
+        dimensions,
+        bots,
+        bot_fill,
+        tick_rate_hz: request.tick_rate_hz,
+        persistent: request.persistent,
+        hide_enemy_state: request.hide_enemy_state.unwrap_or(app_state.config.hide_enemy_state),
+        integrity,
+        visibility,
+        password,
+        hosted: !request.persistent,
+        record_replay: request.record_replay,
+    };
+    if let Err(e) = crate::server::create_lobby_from_spec(
+        &app_state.state,
+        spec,
+        app_state.state.weapons.get(),
         app_state.config.clone(),
         app_state.udp_socket.clone(),
-    ).await {
+    ) {
         log::error!("Failed to create lobby: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
 
-    // Get lobby info
-    let lobby_arc = app_state.state.get_lobby(&request.code)
+    let listing = app_state.state.lobby_listing(&request.code)
         .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let lobby = lobby_arc.read().await;
-    let lobby_info = LobbyInfo {
-        code: lobby.code.clone(),
-        player_count: lobby.players.len(),
-        max_players: lobby.max_players,
-        players: lobby.players.values().map(|p| PlayerInfo {
-            id: p.id,
-            name: p.name.clone(),
-        }).collect(),
-        server_ip: "127.0.0.1".to_string(),
-        udp_port: app_state.config.udp_port,
-        scene: lobby.scene.clone(),
-    };
-
-    Ok(Json(lobby_info))
+    Ok(Json(lobby_info(&listing, app_state, true)))
 }
 
 /// Thin HTTP handler: Join lobby
@@ -73,89 +178,481 @@ pub async fn join_lobby(
     Path(code): Path<String>,
     Json(request): Json<JoinLobbyRequest>,
 ) -> Result<Json<JoinLobbyResponse>, StatusCode> {
-    let lobby_arc = app_state.state.get_lobby(&code)
+    if app_state.state.joins_frozen() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if !ServerState::is_valid_player_name(&request.player_name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // Warm pool lobbies are only reachable through matchmaking
+    if app_state.state.warm_pool.is_idle(&code) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    add_to_lobby(&app_state, &code, request).await
+}
+
+/// UDP protocol version a joining client will speak
+/// 426 when the client and server have no version in common, before it takes a slot
+fn negotiate_protocol(newest: Option<u32>, oldest: Option<u32>) -> Result<u32, StatusCode> {
+    ClientProtocol::negotiate(newest, oldest).ok_or_else(|| {
+        log::debug!(
+            "Refusing join: client speaks protocol v{:?} to v{:?}, server v{} to v{}",
+            oldest,
+            newest,
+            protocol::MIN_PROTOCOL_VERSION,
+            protocol::CURRENT_PROTOCOL_VERSION
+        );
+        StatusCode::UPGRADE_REQUIRED
+    })
+}
+
+/// Thin HTTP handler: Watch a lobby through its public spectate code
+/// The code admits spectators only (past locks and passwords, which guard playing) and the
+/// response carries the spectate code in place of the join code
+pub async fn spectate_lobby(
+    State(app_state): State<AppState>,
+    Path(spectate_code): Path<String>,
+    Json(request): Json<SpectateRequest>,
+) -> Result<Json<JoinLobbyResponse>, StatusCode> {
+    if app_state.state.joins_frozen() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if !ServerState::is_valid_player_name(&request.player_name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let protocol_version = negotiate_protocol(request.protocol_version, request.min_protocol_version)?;
+    let code = app_state.state.resolve_spectate_code(&spectate_code).ok_or(StatusCode::NOT_FOUND)?;
+    let lobby_arc = app_state.state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+
+    let player_id = app_state.state.next_player_id();
+    let player_uuid = app_state.state.player_directory.register(player_id);
+    let mut lobby = lobby_arc.write().await;
+    if let Err(e) = lobbies::add_spectator(&mut lobby, player_id, request.player_name, Instant::now()) {
+        log::debug!("Spectator {} refused in lobby {}: {}", player_id, code, e);
+        app_state.state.player_directory.forget(player_id);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut response = join_response(&app_state, &lobby, player_id, player_uuid.to_string(), request.encryption, true, protocol_version);
+    response.lobby.code = spectate_code;
+    Ok(Json(response))
+}
+
+/// Thin HTTP handler: Matchmake
+/// Fills lobbies matchmaking already opened for the scene, then takes one from the warm pool
+/// (created on the spot when the pool is empty)
+pub async fn matchmake(
+    State(app_state): State<AppState>,
+    Json(request): Json<MatchmakeRequest>,
+) -> Result<Json<JoinLobbyResponse>, StatusCode> {
+    if app_state.state.joins_frozen() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    if !ServerState::is_valid_player_name(&request.player_name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let scene = request.scene.unwrap_or_else(|| "world".to_string());
+    if app_state.state.scenes.get(&scene).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let join = JoinLobbyRequest {
+        player_name: request.player_name,
+        encryption: request.encryption,
+        team: None,
+        reservation: None,
+        password: None,
+        spectate: false,
+        settings: request.settings,
+        account_token: request.account_token,
+        protocol_version: request.protocol_version,
+        min_protocol_version: request.min_protocol_version,
+    };
+
+    for code in app_state.state.warm_pool.matched(&scene) {
+        let has_room = app_state.state.lobby_listing(&code)
+            .is_some_and(|listing| (listing.players.len() as u32) < listing.max_players);
+        if has_room {
+            // Lost a race for the last slot: try the next lobby
+            if let Ok(joined) = add_to_lobby(&app_state, &code, join.clone()).await {
+                return Ok(joined);
+            }
+        }
+    }
+
+    let code = match app_state.state.warm_pool.take(&scene) {
+        Some(code) => code,
+        None => {
+            log::debug!("Warm pool for scene {} is empty, creating a lobby on demand", scene);
+            crate::server::create_warm_lobby(
+                app_state.state.clone(),
+                scene.clone(),
+                app_state.state.weapons.get(),
+                app_state.config.clone(),
+                app_state.udp_socket.clone(),
+            );
+            app_state.state.warm_pool.take(&scene).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?
+        }
+    };
+    add_to_lobby(&app_state, &code, join).await
+}
+
+/// Add a player to a lobby and issue their session
+async fn add_to_lobby(
+    app_state: &AppState,
+    code: &str,
+    request: JoinLobbyRequest,
+) -> Result<Json<JoinLobbyResponse>, StatusCode> {
+    let protocol_version = negotiate_protocol(request.protocol_version, request.min_protocol_version)?;
+    let lobby_arc = app_state.state.get_lobby(code)
         .ok_or(StatusCode::NOT_FOUND)?;
+    let account_id = account_id(app_state, request.account_token.as_deref())?;
+
+    // Checked before taking the lock, which would otherwise be held through the slow hash
+    // (a lobby's password is fixed when it's created)
+    let password = lobby_arc.read().await.password.clone();
+    let password_ok = match (&request.reservation, password, &request.password) {
+        (None, Some(hash), Some(given)) => hash.verify_blocking(given.clone()).await,
+        _ => false,
+    };
 
     let player_id = app_state.state.next_player_id();
+    let player_uuid = app_state.state.player_directory.register(player_id);
     
     // Acquire lock, add player
     let mut lobby = lobby_arc.write().await;
+    if lobby.locked {
+        app_state.state.player_directory.forget(player_id);
+        return Err(StatusCode::LOCKED);
+    }
+    // A rematch reservation stands in for the password
+    let admitted = match (&request.reservation, &lobby.password) {
+        (Some(token), _) => lobbies::claim_reservation(&mut lobby, token, Instant::now()),
+        (None, Some(_)) => password_ok,
+        (None, None) => true,
+    };
+    if !admitted {
+        app_state.state.player_directory.forget(player_id);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Spectators take no player slot, so full lobbies can still be watched
+    if request.spectate {
+        if let Err(e) = lobbies::add_spectator(&mut lobby, player_id, request.player_name.clone(), Instant::now()) {
+            log::debug!("Spectator {} refused in lobby {}: {}", player_id, code, e);
+            app_state.state.player_directory.forget(player_id);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        return Ok(Json(join_response(app_state, &lobby, player_id, player_uuid.to_string(), request.encryption, true, protocol_version)));
+    }
     
     let default_weapon = WeaponDb::default_weapon_id();
     
-    match lobbies::add_player(&mut lobby, player_id, request.player_name.clone(), default_weapon, &app_state.weapons) {
+    match lobbies::add_player(&mut lobby, player_id, request.player_name.clone(), default_weapon, &app_state.state.weapons.get()) {
         Ok(()) => {
-            let lobby_info = LobbyInfo {
-                code: lobby.code.clone(),
-                player_count: lobby.players.len(),
-                max_players: lobby.max_players,
-                players: lobby.players.values().map(|p| PlayerInfo {
-                    id: p.id,
-                    name: p.name.clone(),
-                }).collect(),
-                server_ip: "127.0.0.1".to_string(),
-                udp_port: app_state.config.udp_port,
-                scene: lobby.scene.clone(),
-            };
+            if let Some(team_id) = request.team {
+                if teams::switch_team(&mut lobby, player_id, team_id).is_err() {
+                    lobbies::remove_player(&mut lobby, player_id);
+                    app_state.state.player_directory.forget(player_id);
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+            }
+            if let Some(settings) = request.settings {
+                if let Err(e) = anticheat::attest(&mut lobby, player_id, settings) {
+                    log::debug!("Player {} refused in lobby {}: {}", player_id, code, e);
+                    lobbies::remove_player(&mut lobby, player_id);
+                    app_state.state.player_directory.forget(player_id);
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+            }
+            if let Some(player) = lobby.players.get_mut(&player_id) {
+                player.account_id = account_id;
+            }
+            owner::claim(&mut lobby, player_id);
+            // Out of play until the client reports the scene loaded over UDP
+            let loading_timeout = Duration::from_secs(lobby.tunables.loading_timeout_secs);
+            lobbies::begin_loading(&mut lobby, player_id, loading_timeout, Instant::now());
+            Ok(Json(join_response(app_state, &lobby, player_id, player_uuid.to_string(), request.encryption, false, protocol_version)))
+        }
+        Err(_) => {
+            app_state.state.player_directory.forget(player_id);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// Session credentials and lobby state for someone who just joined
+/// Publishes the lobby's changed listing, so call it with the lobby lock still held
+fn join_response(
+    app_state: &AppState,
+    lobby: &Lobby,
+    player_id: u32,
+    player_uuid: String,
+    encryption: bool,
+    spectator: bool,
+    protocol_version: u32,
+) -> JoinLobbyResponse {
+    // The key is only as secret as this response: deploy behind a TLS terminator (`behind_tls_proxy`)
+    let session_key = (encryption && app_state.config.udp_encryption)
+        .then(|| app_state.state.create_session_key(player_id));
+
+    JoinLobbyResponse {
+        lobby: lobby_info(&app_state.state.publish_change(lobby), app_state, true),
+        player_id,
+        player_uuid,
+        session_token: app_state.state.create_session_token(player_id),
+        session_key,
+        spectator,
+        protocol_version,
+        min_protocol_version: protocol::MIN_PROTOCOL_VERSION,
+        max_protocol_version: protocol::CURRENT_PROTOCOL_VERSION,
+        features: protocol::capability_names(protocol::SERVER_CAPABILITIES).into_iter().map(String::from).collect(),
+    }
+}
+
+/// Thin HTTP handler: Lobby owner action (kick, resize, change scene, lock, hand over)
+pub async fn owner_action(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+    Json(req): Json<OwnerActionRequest>,
+) -> Result<Json<LobbyInfo>, StatusCode> {
+    let state = &app_state.state;
+    let player_id = state.player_directory.resolve(&req.player_uuid).ok_or(StatusCode::UNAUTHORIZED)?;
+    if !state.verify_session_token(player_id, &req.session_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
 
-            Ok(Json(JoinLobbyResponse {
-                lobby: lobby_info,
-                player_id,
-            }))
+    let lobby_arc = state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+    let mut lobby = lobby_arc.write().await;
+    match owner::apply(&mut lobby, &state.scenes, player_id, req.action) {
+        Ok(Some(kicked)) => {
+            if !state.inject(&code, LobbyCommand::Kick { player_id: kicked, reason: owner::KICK_REASON.to_string() }) {
+                log::warn!("Failed to queue kick of player {} in lobby {}", kicked, code);
+            }
         }
-        Err(_) => Err(StatusCode::BAD_REQUEST),
+        Ok(None) => {}
+        Err("Not the lobby owner") => return Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            log::debug!("Owner action by player {} in lobby {} refused: {}", player_id, code, e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    Ok(Json(lobby_info(&state.publish_change(&lobby), &app_state, true)))
+}
+
+/// Thin HTTP handler: Rematch
+/// Opens a lobby with the same settings and holds a slot for each of the lobby's players, who get
+/// the code and their reservation in a `rematch_available` packet. Asking again returns the same lobby
+pub async fn rematch(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+    Json(req): Json<RematchRequest>,
+) -> Result<Json<LobbyInfo>, StatusCode> {
+    let state = &app_state.state;
+    if state.is_draining() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    let player_id = state.player_directory.resolve(&req.player_uuid).ok_or(StatusCode::UNAUTHORIZED)?;
+    if !state.verify_session_token(player_id, &req.session_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Held throughout, so concurrent requests open a single rematch
+    let lobby_arc = state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+    let mut lobby = lobby_arc.write().await;
+    if !lobby.players.contains_key(&player_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if let Some(listing) = lobby.rematch.as_ref().and_then(|rematch| state.lobby_listing(rematch)) {
+        return Ok(Json(lobby_info(&listing, &app_state, true)));
+    }
+
+    let mut rematch_code = format!("rm-{}", &crate::net::crypto::generate_token()[..8]);
+    while state.lobby_exists(&rematch_code) {
+        rematch_code = format!("rm-{}", &crate::net::crypto::generate_token()[..8]);
+    }
+    let spec = LobbySpec {
+        code: rematch_code.clone(),
+        max_players: lobby.max_players,
+        scene: lobby.scene.clone(),
+        mode: match &lobby.mode {
+            GameMode::GunGame(game) => GameMode::GunGame(gungame::GunGame::new(game.ladder.clone())),
+            GameMode::Horde(_) => GameMode::Horde(Horde::default()),
+            GameMode::Ffa => GameMode::Ffa,
+        },
+        team_count: lobby.team_count,
+        dimensions: lobby.dimensions,
+        bots: (lobby.bots.len() - lobby.fill_bots.len()) as u32,
+        bot_fill: lobby.bot_fill,
+        tick_rate_hz: lobby.tick_rate_hz,
+        persistent: false,
+        hide_enemy_state: lobby.hide_enemy_state,
+        integrity: lobby.rules.integrity,
+        visibility: lobby.visibility,
+        password: lobby.password.clone(),
+        hosted: lobby.hosted,
+        record_replay: lobby.record_replay,
+    };
+    if let Err(e) = crate::server::create_lobby_from_spec(
+        state,
+        spec,
+        app_state.state.weapons.get(),
+        app_state.config.clone(),
+        app_state.udp_socket.clone(),
+    ) {
+        log::error!("Failed to create rematch lobby: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+
+    let mut players: Vec<u32> = lobby.players.keys().filter(|id| !lobby.bots.contains_key(id)).copied().collect();
+    players.sort_unstable();
+    let expires = Instant::now() + Duration::from_secs(app_state.config.rematch_reservation_secs);
+    let tokens = {
+        let new_lobby = state.get_lobby(&rematch_code).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut new_lobby = new_lobby.write().await;
+        lobbies::reserve_slots(&mut new_lobby, players.len(), expires)
+    };
+    lobby.rematch = Some(rematch_code.clone());
+    lobby.rematch_offers = players.into_iter().zip(tokens).collect();
+    log::info!("Lobby {} opened rematch lobby {}", code, rematch_code);
+
+    let listing = state.lobby_listing(&rematch_code).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(lobby_info(&listing, &app_state, true)))
 }
 
-/// Thin HTTP handler: Get lobby info
+/// Thin HTTP handler: Get lobby info (from the published listing, never the lobby lock)
 pub async fn get_lobby(
     State(app_state): State<AppState>,
     Path(code): Path<String>,
 ) -> Result<Json<LobbyInfo>, StatusCode> {
-    let lobby_arc = app_state.state.get_lobby(&code)
+    if app_state.state.warm_pool.is_idle(&code) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let listing = app_state.state.lobby_listing(&code)
         .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(lobby_info(&listing, &app_state, true)))
+}
 
-    let lobby = lobby_arc.read().await;
-    
-    let lobby_info = LobbyInfo {
-        code: lobby.code.clone(),
-        player_count: lobby.players.len(),
-        max_players: lobby.max_players,
-        players: lobby.players.values().map(|p| PlayerInfo {
+/// Fields `GET /lobbies?fields=` can select
+const LOBBY_FIELDS: [&str; 19] = [
+    "code", "player_count", "max_players", "players", "spectator_count", "max_spectators", "spectate_code", "server_ip", "udp_port", "scene", "mode", "match_state", "round",
+    "teams", "dimensions", "visibility", "password_protected", "owner_id", "locked",
+];
+
+/// Browser entry for a lobby (`with_players: false` skips building the player list)
+fn lobby_info(listing: &LobbyListing, app_state: &AppState, with_players: bool) -> LobbyInfo {
+    let players = if with_players {
+        listing.players.iter().map(|p| PlayerInfo {
             id: p.id,
+            uuid: app_state.state.player_directory.uuid(p.id).map(|uuid| uuid.to_string()),
             name: p.name.clone(),
-        }).collect(),
-        server_ip: "127.0.0.1".to_string(),
-        udp_port: app_state.config.udp_port,
-        scene: lobby.scene.clone(),
+            team_id: p.team_id,
+        }).collect()
+    } else {
+        Vec::new()
     };
 
-    Ok(Json(lobby_info))
+    LobbyInfo {
+        code: listing.code.clone(),
+        player_count: listing.players.len(),
+        max_players: listing.max_players,
+        players,
+        spectator_count: listing.spectators,
+        max_spectators: listing.max_spectators,
+        spectate_code: listing.spectate_code.clone(),
+        server_ip: "127.0.0.1".to_string(),
+        udp_port: listing.udp_port.unwrap_or(app_state.config.udp_port),
+        scene: listing.scene.clone(),
+        mode: listing.mode.to_string(),
+        match_state: listing.match_state.to_string(),
+        round: listing.round,
+        teams: listing.teams,
+        dimensions: listing.dimensions,
+        visibility: if listing.unlisted { Visibility::Unlisted } else { Visibility::Public }.as_str().to_string(),
+        password_protected: listing.password_protected,
+        owner_id: listing.owner_id,
+        locked: listing.locked,
+        max_fov: listing.max_fov,
+        aim_assist: listing.aim_assist,
+        tick_rate_hz: listing.tick_rate_hz.unwrap_or(app_state.config.tick_rate_hz),
+    }
+}
+
+/// Keep only the requested fields of a lobby entry
+fn project_lobby(info: LobbyInfo, fields: Option<&[String]>) -> serde_json::Value {
+    let mut value = serde_json::to_value(info).unwrap_or_default();
+    if let (Some(fields), Some(object)) = (fields, value.as_object_mut()) {
+        object.retain(|key, _| fields.iter().any(|field| field == key));
+    }
+    value
+}
+
+/// Whether an `If-None-Match` header matches the current ETag
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
 }
 
 /// Thin HTTP handler: List all lobbies
+/// Supports `If-None-Match`, a `fields=` projection and `since=` diffs against a browser revision
 pub async fn list_lobbies(
     State(app_state): State<AppState>,
-) -> Json<Vec<LobbyInfo>> {
-    let mut lobbies_info = Vec::new();
+    Query(query): Query<ListLobbiesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let fields: Option<Vec<String>> = match &query.fields {
+        Some(list) => {
+            let fields: Vec<String> = list.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect();
+            if fields.is_empty() || !fields.iter().all(|f| LOBBY_FIELDS.contains(&f.as_str())) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Some(fields)
+        }
+        None => None,
+    };
+
+    // Read the revision first: changes racing with this request are re-sent on the next poll
+    let revision = app_state.state.browser_revision();
+    let etag = format!("\"lobbies-{}\"", revision);
+    if etag_matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let removed = query.since.map(|since| app_state.state.removed_lobbies_since(since));
+    let changed_after = match removed {
+        Some(Some(_)) => query.since,
+        _ => None,
+    };
+    let with_players = fields.as_ref().is_none_or(|fields| fields.iter().any(|f| f == "players"));
 
+    let mut lobbies_info = Vec::new();
     for entry in app_state.state.iter_lobbies() {
-        let lobby = entry.lobby.read().await;
-        lobbies_info.push(LobbyInfo {
-            code: lobby.code.clone(),
-            player_count: lobby.players.len(),
-            max_players: lobby.max_players,
-            players: lobby.players.values().map(|p| PlayerInfo {
-                id: p.id,
-                name: p.name.clone(),
-            }).collect(),
-            server_ip: "127.0.0.1".to_string(),
-            udp_port: app_state.config.udp_port,
-            scene: lobby.scene.clone(),
-        });
+        if app_state.state.warm_pool.is_idle(entry.key()) {
+            continue;
+        }
+        let listing = entry.listing.load();
+        if listing.unlisted {
+            continue;
+        }
+        if changed_after.is_some_and(|since| app_state.state.lobby_revision(entry.key()) <= since) {
+            continue;
+        }
+        lobbies_info.push(project_lobby(lobby_info(&listing, &app_state, with_players), fields.as_deref()));
     }
 
-    Json(lobbies_info)
+    let headers = [(header::ETAG, etag)];
+    Ok(match removed {
+        None => (headers, Json(lobbies_info)).into_response(),
+        Some(removed) => (headers, Json(LobbyChanges {
+            revision,
+            full: removed.is_none(),
+            lobbies: lobbies_info,
+            removed: removed.unwrap_or_default(),
+        })).into_response(),
+    })
 }
 
 #[derive(serde::Serialize)]
@@ -185,7 +682,6 @@ pub async fn get_lobby_leaderboard(
     let lobby = lobby_arc.read().await;
 
     let mut entries: Vec<LeaderboardEntry> = lobby.players.values()
-        .filter(|p| p.id != 999) // Exclude dummy bot
         .map(|p| LeaderboardEntry {
             player_id: p.id,
             name: p.name.clone(),
@@ -204,6 +700,49 @@ pub async fn get_lobby_leaderboard(
     }))
 }
 
+#[derive(serde::Serialize)]
+pub struct PlayerStateResponse {
+    pub lobby_code: String,
+    pub state: PlayerSyncState,
+    pub is_dead: bool,
+    pub rank: usize, // 1 = highest score; ties share a rank
+    pub scoreboard: LeaderboardEntry,
+}
+
+/// Thin HTTP handler: One player's sync state and scoreboard row, for clients rebuilding their HUD
+/// Needs `Authorization: Bearer <session_token>` of that player, since it shows exact health and ammo
+pub async fn get_lobby_player(
+    State(app_state): State<AppState>,
+    Path((code, player_id)): Path<(String, u32)>,
+    headers: HeaderMap,
+) -> Result<Json<PlayerStateResponse>, StatusCode> {
+    let lobby_arc = app_state.state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+    let token = bearer_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    if !app_state.state.verify_session_token(player_id, token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let lobby = lobby_arc.read().await;
+    let state = logic::get_player_state(&lobby, player_id).map_err(|_| StatusCode::NOT_FOUND)?;
+    let player = &lobby.players[&player_id];
+    let rank = 1 + lobby.players.values().filter(|p| p.score > player.score).count();
+
+    Ok(Json(PlayerStateResponse {
+        lobby_code: code,
+        state,
+        is_dead: player.is_dead,
+        rank,
+        scoreboard: LeaderboardEntry {
+            player_id: player.id,
+            name: player.name.clone(),
+            score: player.score,
+            kills: player.kills,
+            deaths: player.deaths,
+            killstreak: player.killstreak,
+        },
+    }))
+}
+
 #[derive(serde::Serialize)]
 pub struct PlayerStats {
     pub player_id: u32,
@@ -283,11 +822,2347 @@ pub async fn get_global_leaderboard(
     Json(entries)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::state::lobby::Lobby;
+/// Most matches `GET /players` returns
+const MAX_PLAYER_SEARCH_RESULTS: usize = 50;
+
+/// Thin HTTP handler: Create an account and sign it in
+/// 409 if the username is taken (ignoring case), 503 without `storage_path`
+pub async fn register_account(
+    State(app_state): State<AppState>,
+    Json(request): Json<AccountCredentials>,
+) -> Result<(StatusCode, Json<AccountSession>), StatusCode> {
+    let (storage, tokens) = accounts(&app_state)?;
+    let username = request.username.trim();
+    let password_length = request.password.chars().count();
+    if !ServerState::is_valid_player_name(username)
+        || !(MIN_ACCOUNT_PASSWORD_LENGTH..=MAX_ACCOUNT_PASSWORD_LENGTH).contains(&password_length)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let account = storage
+        .create_account(username, &PasswordHash::new_blocking(request.password.clone()).await)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to register account {}: {}", username, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::CONFLICT)?;
+    Ok((StatusCode::CREATED, Json(account_session(tokens, account)?)))
+}
+
+/// Thin HTTP handler: Sign in to an account (401 for an unknown username or wrong password)
+/// Attempts are rate limited per client IP and per username (429), so passwords can't be brute forced
+pub async fn login_account(
+    State(app_state): State<AppState>,
+    peer: Option<ConnectInfo<std::net::SocketAddr>>,
+    Json(request): Json<AccountCredentials>,
+) -> Result<Json<AccountSession>, StatusCode> {
+    let (storage, tokens) = accounts(&app_state)?;
+    let username = request.username.trim();
+    if !app_state.login_limiter.allow(peer.map(|ConnectInfo(addr)| addr.ip()), username, Instant::now()) {
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+    let account = storage
+        .login(username, &request.password)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up account {}: {}", request.username, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    Ok(Json(account_session(tokens, account)?))
+}
+
+/// Thin HTTP handler: Lifetime stats of the account whose token is the bearer token
+pub async fn get_own_account(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<AccountInfo>, StatusCode> {
+    let (storage, _) = accounts(&app_state)?;
+    let account_id = account_id(&app_state, bearer_token(&headers))?.ok_or(StatusCode::UNAUTHORIZED)?;
+    let account = storage
+        .account(account_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    Ok(Json(AccountInfo {
+        account_id: account.account_id,
+        username: account.username.clone(),
+        kills: account.kills,
+        deaths: account.deaths,
+        wins: account.wins,
+        matches: account.matches,
+        accuracy: account.accuracy(),
+    }))
+}
+
+/// Default and largest page of `GET /leaderboards` and match history
+const DEFAULT_LEADERBOARD_PAGE: u32 = 20;
+const MAX_LEADERBOARD_PAGE: u32 = 100;
+
+/// Thin HTTP handler: Accounts ranked by kills, wins or K/D, all time or over the last week
+pub async fn get_account_leaderboard(
+    State(app_state): State<AppState>,
+    Query(query): Query<AccountLeaderboardQuery>,
+) -> Result<Json<AccountLeaderboard>, StatusCode> {
+    let (storage, _) = accounts(&app_state)?;
+    let metric = match query.metric.as_deref() {
+        Some(name) => LeaderboardMetric::parse(name).ok_or(StatusCode::BAD_REQUEST)?,
+        None => LeaderboardMetric::Kills,
+    };
+    let period = match query.period.as_deref() {
+        Some(name) => LeaderboardPeriod::parse(name).ok_or(StatusCode::BAD_REQUEST)?,
+        None => LeaderboardPeriod::AllTime,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LEADERBOARD_PAGE);
+    if limit == 0 || limit > MAX_LEADERBOARD_PAGE {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (total, rows) = storage.leaderboard(metric, period, limit, query.offset).await.map_err(|e| {
+        log::error!("Failed to read the {} {} leaderboard: {}", period.as_str(), metric.as_str(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let entries = rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| AccountLeaderboardEntry {
+            rank: query.offset + i as u32 + 1,
+            account_id: row.account_id,
+            kd: row.kd(),
+            username: row.username,
+            kills: row.kills,
+            deaths: row.deaths,
+            wins: row.wins,
+        })
+        .collect();
+    Ok(Json(AccountLeaderboard {
+        metric: metric.as_str().to_string(),
+        period: period.as_str().to_string(),
+        total,
+        offset: query.offset,
+        entries,
+    }))
+}
+
+/// Thin HTTP handler: A finished match from match history
+pub async fn get_match(
+    State(app_state): State<AppState>,
+    Path(match_id): Path<String>,
+) -> Result<Json<MatchRecord>, StatusCode> {
+    let storage = app_state.state.storage().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let record = storage.match_record(&match_id).await.map_err(|e| {
+        log::error!("Failed to read match {}: {}", match_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    record.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Thin HTTP handler: The matches an account played, newest first
+pub async fn get_account_matches(
+    State(app_state): State<AppState>,
+    Path(account_id): Path<i64>,
+    Query(query): Query<MatchHistoryQuery>,
+) -> Result<Json<MatchHistory>, StatusCode> {
+    let storage = app_state.state.storage().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LEADERBOARD_PAGE);
+    if limit == 0 || limit > MAX_LEADERBOARD_PAGE {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let internal = |e: sqlx::Error| {
+        log::error!("Failed to read the match history of account {}: {}", account_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    storage.account(account_id).await.map_err(internal)?.ok_or(StatusCode::NOT_FOUND)?;
+
+    let (total, rows) = storage.account_match_history(account_id, limit, query.offset).await.map_err(internal)?;
+    let matches = rows
+        .into_iter()
+        .map(|row| MatchHistoryEntry {
+            match_id: row.match_id,
+            lobby_code: row.lobby_code,
+            mode: row.mode,
+            started_at: row.started_at,
+            ended_at: row.ended_at,
+            place: row.place,
+            score: row.score,
+            kills: row.kills,
+            deaths: row.deaths,
+            won: row.won,
+        })
+        .collect();
+    Ok(Json(MatchHistory { account_id, total, offset: query.offset, matches }))
+}
+
+/// Account storage and token issuer; accounts need `storage_path`
+fn accounts(app_state: &AppState) -> Result<(&Storage, &TokenIssuer), StatusCode> {
+    let storage = app_state.state.storage().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let tokens = app_state.state.account_tokens().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok((storage, tokens))
+}
+
+fn account_session(tokens: &TokenIssuer, account: Account) -> Result<AccountSession, StatusCode> {
+    let token = tokens.issue(account.account_id, &account.username).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(AccountSession { account_id: account.account_id, username: account.username, token })
+}
+
+/// Account id of an optional account token; 401 if one is given but isn't valid
+fn account_id(app_state: &AppState, token: Option<&str>) -> Result<Option<i64>, StatusCode> {
+    let Some(token) = token else {
+        return Ok(None);
+    };
+    let tokens = app_state.state.account_tokens().ok_or(StatusCode::UNAUTHORIZED)?;
+    tokens.verify(token).map(|claims| Some(claims.sub)).map_err(|_| StatusCode::UNAUTHORIZED)
+}
+
+/// Thin HTTP handler: Find players by name across all lobbies (friend-finding, moderation)
+/// Players in unlisted lobbies are only shown to admins
+pub async fn search_players(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<PlayerSearchQuery>,
+) -> Result<Json<Vec<PlayerSearchResult>>, StatusCode> {
+    let name = query.name.trim();
+    if name.is_empty() || !ServerState::is_valid_player_name(name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let admin = authorize_admin(&headers, &app_state.config).is_ok();
+    let state = &app_state.state;
+
+    let mut results: Vec<PlayerSearchResult> = state
+        .search_players(name, MAX_PLAYER_SEARCH_RESULTS)
+        .into_iter()
+        .filter(|(listing, _)| admin || !listing.unlisted)
+        .filter_map(|(listing, player)| {
+            // Bots have no public identity and aren't anyone's friend
+            let uuid = state.player_directory.uuid(player.id)?;
+            Some(PlayerSearchResult {
+                id: player.id,
+                uuid: Some(uuid.to_string()),
+                name: player.name,
+                online: true,
+                lobby: Some(PlayerSearchLobby {
+                    code: listing.code.clone(),
+                    scene: listing.scene.clone(),
+                    mode: listing.mode.to_string(),
+                    match_state: listing.match_state.to_string(),
+                    team_id: player.team_id,
+                    password_protected: listing.password_protected,
+                }),
+            })
+        })
+        .collect();
+
+    if query.offline {
+        let remaining = MAX_PLAYER_SEARCH_RESULTS.saturating_sub(results.len());
+        let offline: Vec<PlayerSearchResult> = state
+            .global_stats
+            .search_by_name(name, MAX_PLAYER_SEARCH_RESULTS)
+            .into_iter()
+            .filter(|stats| !results.iter().any(|r| r.id == stats.player_id))
+            .take(remaining)
+            .map(|stats| PlayerSearchResult { id: stats.player_id, uuid: None, name: stats.name, online: false, lobby: None })
+            .collect();
+        results.extend(offline);
+    }
+    Ok(Json(results))
+}
+
+/// Thin HTTP handler: Weapon definitions, the authoritative copy clients load
+pub async fn list_weapons(State(app_state): State<AppState>) -> Json<Vec<WeaponData>> {
+    Json(app_state.state.weapons.get().sorted().into_iter().cloned().collect())
+}
+
+/// Optional behaviour turned on in the config, by name
+fn enabled_features(config: &Config) -> Vec<String> {
+    let flags = [
+        ("udp_encryption", config.udp_encryption),
+        ("quantize_positions", config.quantize_positions),
+        ("visibility_culling", config.visibility_culling),
+        ("lag_compensation", config.max_rewind_ms > 0),
+        ("friendly_fire", config.friendly_fire),
+        ("hide_enemy_state", config.hide_enemy_state),
+        ("mutators", !config.mutators.is_empty()),
+        ("warm_pool", config.warm_pool_size > 0),
+        ("admin_api", config.admin_token.is_some()),
+        ("message_text", config.message_text),
+    ];
+    flags.iter().filter(|(_, on)| *on).map(|(name, _)| name.to_string()).collect()
+}
+
+/// Thin HTTP handler: Build, version and uptime of the running server
+pub async fn get_server_info(State(app_state): State<AppState>) -> Json<ServerInfo> {
+    let config = &app_state.config;
+    Json(ServerInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: env!("GIT_HASH").to_string(),
+        protocol_version: protocol::CURRENT_PROTOCOL_VERSION,
+        min_protocol_version: protocol::MIN_PROTOCOL_VERSION,
+        capabilities: protocol::SERVER_CAPABILITIES,
+        uptime_secs: app_state.state.uptime().as_secs(),
+        tick_rate_hz: config.tick_rate_hz,
+        transport: config.transport.as_str().to_string(),
+        features: enabled_features(config),
+    })
+}
+
+/// Thin HTTP handler: Download a recorded match as a demo file
+pub async fn get_match_demo(
+    State(app_state): State<AppState>,
+    Path(match_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let path = crate::demo::demo_path(&app_state.config.demo_dir, &match_id)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let demo = tokio::fs::read(&path).await.map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let disposition = format!("attachment; filename=\"{}.demo.json\"", match_id);
+    Ok((
+        [(header::CONTENT_TYPE, "application/json".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        demo,
+    ))
+}
+
+/// Longest match id a report may reference
+const MAX_MATCH_ID_LENGTH: usize = 64;
+
+/// Thin HTTP handler: Report a player in the reporter's lobby for moderator review
+/// The server attaches the reported player's anti-cheat flags and recent shots
+pub async fn submit_report(
+    State(app_state): State<AppState>,
+    Json(req): Json<SubmitReportRequest>,
+) -> Result<Json<SubmitReportResponse>, StatusCode> {
+    let state = &app_state.state;
+    let details_ok = req.details.as_ref().is_none_or(|d| d.chars().count() <= reports::MAX_REPORT_DETAILS);
+    let match_id_ok = req.match_id.as_ref().is_none_or(|id| id.len() <= MAX_MATCH_ID_LENGTH);
+    if !reports::REPORT_REASONS.contains(&req.reason.as_str()) || !details_ok || !match_id_ok {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let reporter_id = state.player_directory.resolve(&req.player_uuid).ok_or(StatusCode::UNAUTHORIZED)?;
+    if !state.verify_session_token(reporter_id, &req.session_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let reported_id = state.player_directory.resolve(&req.reported_uuid).ok_or(StatusCode::NOT_FOUND)?;
+    if reported_id == reporter_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let report = {
+        let lobby_arc = state.get_lobby(&req.lobby_code).ok_or(StatusCode::NOT_FOUND)?;
+        let lobby = lobby_arc.read().await;
+        let reporter = lobby.players.get(&reporter_id).ok_or(StatusCode::NOT_FOUND)?;
+        let reported = lobby.players.get(&reported_id).ok_or(StatusCode::NOT_FOUND)?;
+        PlayerReport {
+            id: 0,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            reporter_uuid: req.player_uuid.clone(),
+            reporter_name: reporter.name.clone(),
+            reported_uuid: req.reported_uuid.clone(),
+            reported_name: reported.name.clone(),
+            reason: req.reason.clone(),
+            details: req.details.clone(),
+            lobby_code: req.lobby_code.clone(),
+            match_id: req.match_id.clone(),
+            evidence: ReportEvidence {
+                violations: reported.violations.summary(),
+                recent_shots: reported.shot_audit.recent(),
+            },
+            status: ReportStatus::Open,
+            resolution_note: None,
+        }
+    };
+
+    if state.reports.has_open(&report.reporter_uuid, &report.reported_uuid) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let report_id = state.reports.submit(report).map_err(|e| {
+        log::error!("Failed to save report: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    log::info!("Report {} filed against player {} in lobby {} ({})", report_id, reported_id, req.lobby_code, req.reason);
+    Ok(Json(SubmitReportResponse { report_id }))
+}
+
+/// Admin endpoints need `Authorization: Bearer <admin_token>`; they don't exist without a token
+fn authorize_admin(headers: &HeaderMap, config: &Config) -> Result<(), StatusCode> {
+    let expected = config.admin_token.as_deref().ok_or(StatusCode::NOT_FOUND)?;
+    if !bearer_token(headers).is_some_and(|token| crypto::tokens_match(token, expected)) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(())
+}
+
+/// The token of an `Authorization: Bearer <token>` header
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Thin HTTP handler: Every lobby, unlisted and warm pool ones included, with internal details
+pub async fn admin_list_lobbies(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<AdminLobbyInfo>>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let mut listings: Vec<Arc<LobbyListing>> = app_state.state.lobbies_where(|_| true).collect();
+    listings.sort_by(|a, b| a.code.cmp(&b.code));
+    let mut lobbies = Vec::with_capacity(listings.len());
+    for listing in listings {
+        if let Some(info) = admin_lobby_info(&app_state, &listing).await {
+            lobbies.push(info);
+        }
+    }
+    Ok(Json(lobbies))
+}
+
+/// Thin HTTP handler: One lobby with internal details, including its command queue depth
+pub async fn admin_get_lobby(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<AdminLobbyInfo>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let listing = app_state.state.lobby_listing(&code).ok_or(StatusCode::NOT_FOUND)?;
+    admin_lobby_info(&app_state, &listing).await.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
 
-    // Note: HTTP handler tests would require full AppState setup
-    // Integration tests are better suited for HTTP handlers
+/// Thin HTTP handler: Close a lobby now, whoever is in it; persistent lobbies are not recreated
+pub async fn admin_delete_lobby(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let state = &app_state.state;
+    state.persistent_lobbies.remove(&code);
+    crate::server::close_lobby(state, &code, &app_state.config).await.ok_or(StatusCode::NOT_FOUND)?;
+    log::info!("Admin deleted lobby {}", code);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Internal view of a lobby (None when it was closed meanwhile)
+async fn admin_lobby_info(app_state: &AppState, listing: &LobbyListing) -> Option<AdminLobbyInfo> {
+    let state = &app_state.state;
+    let lobby = state.get_lobby(&listing.code)?;
+    let (queued_commands, queue_capacity) = state.command_queue_depth(&listing.code)?;
+    let lobby = lobby.read().await;
+    Some(AdminLobbyInfo {
+        lobby: lobby_info(listing, app_state, true),
+        bots: lobby.bots.len(),
+        persistent: lobby.persistent,
+        warm: state.warm_pool.is_idle(&listing.code),
+        frozen: lobby.frozen,
+        time_scale: lobby.time_scale,
+        queued_commands,
+        queue_capacity,
+        tick_load: state.tick_load(&listing.code),
+    })
+}
+
+/// Thin HTTP handler: Kick a player id from every lobby it is in, optionally banning its IP
+pub async fn admin_kick(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminKickRequest>,
+) -> Result<Json<BulkAdminResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let reason = req.reason.unwrap_or_else(|| "kicked by an admin".to_string());
+    if reason.chars().count() > MAX_NOTICE_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let state = &app_state.state;
+    if let Some(ban_secs) = req.ban_secs {
+        let listings: Vec<Arc<LobbyListing>> = state.lobbies_where(|listing| listing.players.iter().any(|p| p.id == req.player_id)).collect();
+        for listing in listings {
+            let Some(lobby) = state.get_lobby(&listing.code) else {
+                continue;
+            };
+            let addr = lobby.read().await.client_addresses.get(&req.player_id).copied();
+            if let Some(addr) = addr {
+                state.bans.ban(addr.ip(), Duration::from_secs(ban_secs), Instant::now());
+                log::info!("Admin banned {} (player {}) for {}s", addr.ip(), req.player_id, ban_secs);
+            }
+        }
+    }
+    let mut lobbies: Vec<String> = state
+        .lobbies_where(|listing| listing.players.iter().any(|p| p.id == req.player_id))
+        .filter(|listing| state.inject(&listing.code, LobbyCommand::Kick { player_id: req.player_id, reason: reason.clone() }))
+        .map(|listing| listing.code.clone())
+        .collect();
+    lobbies.sort();
+    log::info!("Admin kicked player {} from {:?}: {}", req.player_id, lobbies, reason);
+    Ok(Json(BulkAdminResponse { lobbies }))
+}
+
+/// Thin HTTP handler: Show a notice in every lobby, or those on one scene
+pub async fn admin_broadcast(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<AdminBroadcastRequest>,
+) -> Result<Json<BulkAdminResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    if req.text.trim().is_empty() || req.text.chars().count() > MAX_NOTICE_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let state = &app_state.state;
+    let mut lobbies: Vec<String> = state
+        .lobbies_where(|listing| req.scene.as_deref().is_none_or(|scene| listing.scene == scene))
+        .filter(|listing| state.inject(&listing.code, LobbyCommand::Announce { text: req.text.clone() }))
+        .map(|listing| listing.code.clone())
+        .collect();
+    lobbies.sort();
+    log::info!("Admin announcement to {} lobbies: {}", lobbies.len(), req.text);
+    Ok(Json(BulkAdminResponse { lobbies }))
+}
+
+/// Thin HTTP handler: Close every lobby without human players now
+pub async fn admin_close_empty_lobbies(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BulkAdminResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let mut lobbies = crate::server::close_empty_lobbies(&app_state.state, &app_state.config, Duration::ZERO).await;
+    lobbies.sort();
+    log::info!("Admin closed {} empty lobbies", lobbies.len());
+    Ok(Json(BulkAdminResponse { lobbies }))
+}
+
+/// Thin HTTP handler: Merge an under-populated lobby into another and close it
+/// Its players keep their state and are told `migrate_to`; 409 when the lobbies don't match or won't fit
+pub async fn admin_merge_lobby(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<MergeLobbyRequest>,
+) -> Result<Json<MergeLobbyResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let state = &app_state.state;
+    if !state.lobby_exists(&code) || !state.lobby_exists(&req.into) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let moved = crate::server::merge_lobbies(state, &code, &req.into, &app_state.config)
+        .await
+        .map_err(|e| {
+            log::debug!("Merging lobby {} into {} refused: {}", code, req.into, e);
+            StatusCode::CONFLICT
+        })?;
+
+    let listing = state.lobby_listing(&req.into).ok_or(StatusCode::NOT_FOUND)?;
+    log::info!("Admin merged lobby {} into {} ({} moved)", code, req.into, moved.len());
+    Ok(Json(MergeLobbyResponse { lobby: lobby_info(&listing, &app_state, true), moved }))
+}
+
+/// Thin HTTP handler: Freeze or unfreeze joins on every lobby
+pub async fn set_join_freeze(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<JoinFreeze>,
+) -> Result<Json<JoinFreeze>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    app_state.state.set_joins_frozen(req.frozen);
+    log::info!("Joins {}", if req.frozen { "frozen" } else { "unfrozen" });
+    Ok(Json(req))
+}
+
+/// Thin HTTP handler: Slow down or speed up a lobby (debugging desyncs, cinematics)
+pub async fn set_time_scale(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+    Json(req): Json<TimeScale>,
+) -> Result<Json<TimeScale>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    if !(lobbies::MIN_TIME_SCALE..=lobbies::MAX_TIME_SCALE).contains(&req.time_scale) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !app_state.state.inject(&code, LobbyCommand::SetTimeScale { time_scale: req.time_scale }) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    log::info!("Admin set time scale of lobby {} to {}", code, req.time_scale);
+    Ok(Json(req))
+}
+
+/// Thin HTTP handler: Join a lobby as a referee, a spectator that may send `referee` actions over UDP
+/// Past locks, passwords and the spectator cap
+pub async fn admin_referee(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+    Json(request): Json<SpectateRequest>,
+) -> Result<Json<JoinLobbyResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    if !ServerState::is_valid_player_name(&request.player_name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let protocol_version = negotiate_protocol(request.protocol_version, request.min_protocol_version)?;
+    let lobby_arc = app_state.state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+
+    let player_id = app_state.state.next_player_id();
+    let player_uuid = app_state.state.player_directory.register(player_id);
+    let mut lobby = lobby_arc.write().await;
+    if let Err(e) = lobbies::add_referee(&mut lobby, player_id, request.player_name.clone(), Instant::now()) {
+        log::debug!("Referee {} refused in lobby {}: {}", player_id, code, e);
+        app_state.state.player_directory.forget(player_id);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    log::info!("Admin joined lobby {} as referee {} ({})", code, player_id, request.player_name);
+    Ok(Json(join_response(&app_state, &lobby, player_id, player_uuid.to_string(), request.encryption, true, protocol_version)))
+}
+
+/// Thin HTTP handler: Referee actions taken inside lobbies, oldest first
+pub async fn get_audit_log(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    Ok(Json(app_state.state.audit.entries(query.lobby.as_deref())))
+}
+
+/// Thin HTTP handler: Give a player a weapon (or refill it), e.g. for testing loadouts
+pub async fn grant_weapon(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path((code, player_id)): Path<(String, u32)>,
+    Json(req): Json<GrantWeapon>,
+) -> Result<Json<GrantWeapon>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    if !app_state.state.weapons.get().contains(req.weapon_id) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let lobby = app_state.state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+    if !lobby.read().await.players.contains_key(&player_id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !app_state.state.inject(&code, LobbyCommand::GrantWeapon { player_id, weapon_id: req.weapon_id }) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    log::info!("Admin granted weapon {} to player {} in lobby {}", req.weapon_id, player_id, code);
+    Ok(Json(req))
+}
+
+fn log_levels_response(levels: &LogLevels) -> LogLevelsResponse {
+    LogLevelsResponse {
+        default: levels.default.as_str().to_lowercase(),
+        modules: levels
+            .modules
+            .iter()
+            .map(|(module, level)| (module.clone(), level.as_str().to_lowercase()))
+            .collect(),
+    }
+}
+
+/// Thin HTTP handler: Current log levels
+pub async fn get_log_levels(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<LogLevelsResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    Ok(Json(log_levels_response(&logging::levels())))
+}
+
+/// Thin HTTP handler: Change the default or one module's log level live
+pub async fn set_log_level(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<SetLogLevelRequest>,
+) -> Result<Json<LogLevelsResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+
+    let level = req.level.as_deref().map(logging::parse_level).transpose().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut levels = logging::levels();
+    match (req.module.as_deref(), level) {
+        (Some(module), level) if !module.is_empty() => levels.set_module(module, level),
+        (None, Some(level)) => levels.default = level,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    }
+    log::info!("Log levels changed: {:?}", req);
+    logging::set_levels(levels.clone());
+
+    Ok(Json(log_levels_response(&levels)))
+}
+
+/// Thin HTTP handler: Tunables in effect
+pub async fn get_tunables(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Tunables>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    Ok(Json((*app_state.state.tunables.get()).clone()))
+}
+
+/// Thin HTTP handler: Change some tunables live (fields left out keep their value)
+/// Not written back to the tunables file
+pub async fn set_tunables(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Json(changes): Json<serde_json::Value>,
+) -> Result<Json<Tunables>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let tunables = app_state.state.tunables.get().merged(&changes).map_err(|e| {
+        log::debug!("Rejected tunables change: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    log::info!("Tunables changed: {}", changes);
+    Ok(Json((*app_state.state.tunables.set(tunables)).clone()))
+}
+
+/// Thin HTTP handler: Re-read the tunables file
+pub async fn reload_tunables(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Tunables>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    match app_state.state.tunables.reload() {
+        Ok(tunables) => {
+            log::info!("Tunables reloaded: {:?}", tunables);
+            Ok(Json((*tunables).clone()))
+        }
+        Err(e) => {
+            log::warn!("Tunables reload failed: {}", e);
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        }
+    }
+}
+
+/// Thin HTTP handler: Re-read the weapons and tunables files without a restart
+/// Both are validated first and applied together, or not at all; lobbies pick them up on their next tick
+pub async fn reload_data(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ReloadResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let state = &app_state.state;
+    let tunables = if state.tunables.has_file() { state.tunables.read().map(Some) } else { Ok(None) };
+    let (weapons, tunables) = match (state.weapons.read(), tunables) {
+        (Ok(weapons), Ok(tunables)) => (weapons, tunables),
+        (Err(e), _) | (_, Err(e)) => {
+            log::warn!("Reload failed, nothing changed: {}", e);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    };
+
+    let weapons = state.weapons.set(weapons);
+    let tunables = match tunables {
+        Some(tunables) => state.tunables.set(tunables),
+        None => state.tunables.get(),
+    };
+    log::info!("Reloaded {} weapons and tunables {:?}", weapons.sorted().len(), tunables);
+    Ok(Json(ReloadResponse {
+        weapons: weapons.sorted().into_iter().map(|w| w.id).collect(),
+        tunables: (*tunables).clone(),
+    }))
+}
+
+/// Thin HTTP handler: Per-weapon performance over closed lobbies, with outliers flagged
+/// Served from the last background build, or built now when there is none yet
+pub async fn get_balance_report(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BalanceReport>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    if app_state.config.lobby_analytics_path.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let mut report = app_state.state.balance_report.load();
+    if report.is_none() {
+        report = balance::refresh(&app_state.state, &app_state.config).map_err(|e| {
+            log::warn!("Failed to build the weapon balance report: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    }
+    report.as_ref().clone().map(Json).ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Thin HTTP handler: Moderator review queue (open reports unless `status` asks otherwise)
+pub async fn list_reports(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ListReportsQuery>,
+) -> Result<Json<Vec<PlayerReport>>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let status = ReportStatus::parse(query.status.as_deref().unwrap_or("open")).ok_or(StatusCode::BAD_REQUEST)?;
+    Ok(Json(app_state.state.reports.with_status(status)))
+}
+
+/// Thin HTTP handler: Close a report as dismissed or actioned
+pub async fn resolve_report(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(report_id): Path<u64>,
+    Json(req): Json<ResolveReportRequest>,
+) -> Result<Json<PlayerReport>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let status = ReportStatus::parse(&req.status)
+        .filter(|status| *status != ReportStatus::Open)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if req.note.as_ref().is_some_and(|note| note.chars().count() > reports::MAX_REPORT_DETAILS) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match app_state.state.reports.resolve(report_id, status, req.note) {
+        Ok(report) => Ok(Json(report)),
+        Err("Report not found") => Err(StatusCode::NOT_FOUND),
+        Err("Report already resolved") => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            log::error!("Failed to resolve report {}: {}", report_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Thin HTTP handler: A gun game lobby's weapon ladder
+pub async fn get_lobby_ladder(
+    State(app_state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<Json<LadderResponse>, StatusCode> {
+    if app_state.state.warm_pool.is_idle(&code) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let lobby = app_state.state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+    let lobby = lobby.read().await;
+    match &lobby.mode {
+        GameMode::GunGame(game) => Ok(Json(LadderResponse { ladder: game.ladder.clone() })),
+        GameMode::Ffa | GameMode::Horde(_) => Err(StatusCode::CONFLICT),
+    }
+}
+
+/// Thin HTTP handler: Replace a gun game lobby's ladder with weapon ids or a saved preset
+/// Everyone in the lobby restarts from the bottom of the new ladder
+pub async fn set_lobby_ladder(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+    Json(req): Json<SetLadderRequest>,
+) -> Result<Json<LadderResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    let ladder = match (req.weapons, req.preset) {
+        (Some(weapons), None) => gungame::build_ladder(&weapons, &app_state.state.weapons.get()).map_err(|_| StatusCode::BAD_REQUEST)?,
+        (None, Some(preset)) => app_state.state.ladder_presets.get(&preset).ok_or(StatusCode::NOT_FOUND)?,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    // Presets were validated when saved, but the weapon list may have changed since
+    if !ladder.iter().all(|id| app_state.state.weapons.get().contains(*id)) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let lobby = app_state.state.get_lobby(&code).ok_or(StatusCode::NOT_FOUND)?;
+    let mut lobby = lobby.write().await;
+    match gungame::set_ladder(&mut lobby, &app_state.state.weapons.get(), ladder.clone()) {
+        Ok(()) => {
+            log::info!("Lobby {} gun game ladder set to {:?}", code, ladder);
+            Ok(Json(LadderResponse { ladder }))
+        }
+        Err("Not a gun game lobby") => Err(StatusCode::CONFLICT),
+        Err(e) => {
+            log::error!("Failed to set ladder of lobby {}: {}", code, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Thin HTTP handler: Saved ladder presets by name
+pub async fn list_ladder_presets(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BTreeMap<String, Vec<u32>>>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    Ok(Json(app_state.state.ladder_presets.all()))
+}
+
+/// Thin HTTP handler: Create or replace a ladder preset
+pub async fn save_ladder_preset(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(req): Json<SaveLadderPresetRequest>,
+) -> Result<Json<LadderResponse>, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    if !LadderPresets::is_valid_name(&name) || req.weapons.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let ladder = gungame::build_ladder(&req.weapons, &app_state.state.weapons.get()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    app_state.state.ladder_presets.save(&name, ladder.clone()).map_err(|e| {
+        log::error!("Failed to save ladder preset {}: {}", name, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    log::info!("Ladder preset {} saved: {:?}", name, ladder);
+    Ok(Json(LadderResponse { ladder }))
+}
+
+/// Thin HTTP handler: Delete a ladder preset (lobbies using it keep their ladder)
+pub async fn delete_ladder_preset(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    authorize_admin(&headers, &app_state.config)?;
+    match app_state.state.ladder_presets.remove(&name) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            log::error!("Failed to delete ladder preset {}: {}", name, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    // API contract tests: exact status codes and JSON shapes the Godot client relies on
+
+    async fn test_app() -> (Router, Arc<ServerState>) {
+        test_app_with(Config::default()).await
+    }
+
+    async fn test_app_with(config: Config) -> (Router, Arc<ServerState>) {
+        let state = Arc::new(ServerState::new());
+        let app_state = AppState {
+            state: state.clone(),
+            login_limiter: Arc::new(LoginLimiter::new(&config)),
+            config: Arc::new(config),
+            udp_socket: Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()),
+        };
+        (crate::server::build_router(app_state), state)
+    }
+
+    async fn request(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let builder = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+            None => builder.body(Body::empty()).unwrap(),
+        };
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    async fn admin(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let builder = Request::builder().method(method).uri(uri).header(header::AUTHORIZATION, "Bearer secret");
+        let request = match body {
+            Some(body) => builder.header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())).unwrap(),
+            None => builder.body(Body::empty()).unwrap(),
+        };
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    async fn create(app: &Router, code: &str, max_players: u32) {
+        let (status, _) = request(app, "POST", "/lobbies", Some(json!({"code": code, "max_players": max_players}))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_create_lobby_contract() {
+        let (app, state) = test_app().await;
+
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "alpha"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({
+            "code": "alpha",
+            "player_count": 0,
+            "max_players": 4,
+            "players": [],
+            "spectator_count": 0,
+            "max_spectators": 8,
+            "spectate_code": state.lobby_listing("alpha").unwrap().spectate_code,
+            "server_ip": "127.0.0.1",
+            "udp_port": 8081,
+            "scene": "world",
+            "mode": "ffa",
+            "match_state": "waiting_for_players",
+            "round": 0,
+            "teams": 0,
+            "dimensions": 3,
+            "visibility": "public",
+            "password_protected": false,
+            "owner_id": null,
+            "locked": false,
+            "max_fov": 110.0,
+            "aim_assist": false,
+            "tick_rate_hz": 50
+        }));
+
+        let (status, body) = request(
+            &app,
+            "POST",
+            "/lobbies",
+            Some(json!({"code": "beta", "max_players": 8, "scene": "desert"})),
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["max_players"], 8);
+        assert_eq!(body["scene"], "desert");
+
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "gamma", "mode": "gun_game"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mode"], "gun_game");
+
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "waves", "mode": "horde"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mode"], "horde");
+
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "bots", "max_players": 4, "bots": 3}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["player_count"], 3);
+
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "slow", "tick_rate_hz": 20}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tick_rate_hz"], 20);
+    }
+
+    #[tokio::test]
+    async fn test_create_lobby_failures() {
+        let (app, _) = test_app().await;
+        create(&app, "alpha", 4).await;
+
+        let duplicate = request(&app, "POST", "/lobbies", Some(json!({"code": "alpha"}))).await;
+        assert_eq!(duplicate, (StatusCode::CONFLICT, Value::Null));
+
+        let invalid_code = request(&app, "POST", "/lobbies", Some(json!({"code": "no spaces!"}))).await;
+        assert_eq!(invalid_code, (StatusCode::BAD_REQUEST, Value::Null));
+
+        let invalid_mode = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "mode": "ctf"}))).await;
+        assert_eq!(invalid_mode, (StatusCode::BAD_REQUEST, Value::Null));
+
+        let no_room = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "max_players": 4, "bots": 4}))).await;
+        assert_eq!(no_room, (StatusCode::BAD_REQUEST, Value::Null));
+        let horde_bots = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "mode": "horde", "bots": 1}))).await;
+        assert_eq!(horde_bots, (StatusCode::BAD_REQUEST, Value::Null));
+        let fill_everything = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "max_players": 4, "bot_fill": 4}))).await;
+        assert_eq!(fill_everything, (StatusCode::BAD_REQUEST, Value::Null));
+        let horde_fill = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "mode": "horde", "bot_fill": 2}))).await;
+        assert_eq!(horde_fill, (StatusCode::BAD_REQUEST, Value::Null));
+        let too_fast = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "tick_rate_hz": 500}))).await;
+        assert_eq!(too_fast, (StatusCode::BAD_REQUEST, Value::Null));
+        let too_slow = request(&app, "POST", "/lobbies", Some(json!({"code": "beta", "tick_rate_hz": 1}))).await;
+        assert_eq!(too_slow, (StatusCode::BAD_REQUEST, Value::Null));
+
+        let (status, _) = request(&app, "POST", "/lobbies", Some(json!({"max_players": 4}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let no_body = Request::builder().method("POST").uri("/lobbies").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(no_body).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let malformed = Request::builder()
+            .method("POST")
+            .uri("/lobbies")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{not json"))
+            .unwrap();
+        let response = app.clone().oneshot(malformed).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_team_lobby_contract() {
+        let (app, _) = test_app().await;
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "teams", "teams": 2}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["teams"], 2);
+
+        let (_, ada) = request(&app, "POST", "/lobbies/teams/join", Some(json!({"player_name": "Ada"}))).await;
+        assert_eq!(ada["lobby"]["players"][0]["team_id"], 1);
+
+        // Joining the bigger team would unbalance it
+        let unbalanced = request(&app, "POST", "/lobbies/teams/join", Some(json!({"player_name": "Bob", "team": 1}))).await;
+        assert_eq!(unbalanced.0, StatusCode::BAD_REQUEST);
+        let (status, _) = request(&app, "POST", "/lobbies/teams/join", Some(json!({"player_name": "Bob", "team": 2}))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let invalid = request(&app, "POST", "/lobbies", Some(json!({"code": "solo", "teams": 1}))).await;
+        assert_eq!(invalid.0, StatusCode::BAD_REQUEST);
+        let horde = request(&app, "POST", "/lobbies", Some(json!({"code": "waves", "mode": "horde", "teams": 2}))).await;
+        assert_eq!(horde.0, StatusCode::BAD_REQUEST); // Horde sides are fixed
+    }
+
+    #[tokio::test]
+    async fn test_2d_lobby_contract() {
+        let (app, state) = test_app().await;
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "flat", "dimensions": 2}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["dimensions"], 2);
+
+        request(&app, "POST", "/lobbies/flat/join", Some(json!({"player_name": "Ada"}))).await;
+        let lobby = state.get_lobby("flat").unwrap();
+        let lobby = lobby.read().await;
+        assert_eq!(lobby.players.values().next().unwrap().position.1, crate::state::lobby::PLANE_HEIGHT);
+
+        let invalid = request(&app, "POST", "/lobbies", Some(json!({"code": "cube", "dimensions": 4}))).await;
+        assert_eq!(invalid.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_list_weapons_contract() {
+        let (app, _) = test_app().await;
+        let (status, body) = request(&app, "GET", "/weapons", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<u64> = body.as_array().unwrap().iter().map(|w| w["id"].as_u64().unwrap()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(body[0]["cosmetics"]["sound_id"], "shot_pistol");
+        assert_eq!(body[0]["cosmetics"]["tracer"]["width"], 0.02);
+        assert_eq!(body[2]["cosmetics"]["muzzle_flash_id"], Value::Null); // Knife
+        assert_eq!(body[2]["cosmetics"]["tracer"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_join_lobby_contract() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 4).await;
+
+        let (status, mut body) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let player_id = body["player_id"].as_u64().unwrap();
+        let player_uuid = body["player_uuid"].as_str().unwrap().to_string();
+        assert_eq!(state.player_directory.resolve(&player_uuid), Some(player_id as u32));
+        let token = body.as_object_mut().unwrap().remove("session_token").unwrap();
+        assert_eq!(token.as_str().unwrap().len(), 32);
+        assert_eq!(body, json!({
+            "lobby": {
+                "code": "alpha",
+                "player_count": 1,
+                "max_players": 4,
+                "players": [{"id": player_id, "uuid": player_uuid, "name": "Ada"}],
+                "spectator_count": 0,
+                "max_spectators": 8,
+                "spectate_code": state.lobby_listing("alpha").unwrap().spectate_code,
+                "server_ip": "127.0.0.1",
+                "udp_port": 8081,
+                "scene": "world",
+                "mode": "ffa",
+                "match_state": "waiting_for_players",
+                "round": 0,
+                "teams": 0,
+            "dimensions": 3,
+                "visibility": "public",
+                "password_protected": false,
+                "owner_id": player_id,
+                "locked": false,
+                "max_fov": 110.0,
+                "aim_assist": false,
+                "tick_rate_hz": 50
+            },
+            "player_id": player_id,
+            "player_uuid": player_uuid,
+            "spectator": false,
+            "protocol_version": 1,
+            "min_protocol_version": 1,
+            "max_protocol_version": 3,
+            "features": ["binary", "encryption", "batching", "reduced_rate", "reliable", "snapshots", "channels", "voice"]
+        }));
+
+        // The newest protocol version both speak, or 426 before taking a slot when there's none
+        let (_, body) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Lin", "protocol_version": 9}))).await;
+        assert_eq!(body["protocol_version"], protocol::CURRENT_PROTOCOL_VERSION);
+        let future = json!({"player_name": "Max", "protocol_version": 9, "min_protocol_version": 9});
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/join", Some(future)).await.0, StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(state.lobby_listing("alpha").unwrap().players.len(), 2);
+
+        // Opting into encryption adds a 32-byte hex session key
+        let (status, body) = request(
+            &app,
+            "POST",
+            "/lobbies/alpha/join",
+            Some(json!({"player_name": "Grace", "encryption": true})),
+        ).await;
+        assert_eq!(status, StatusCode::OK);
+        let key = body["session_key"].as_str().unwrap();
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[tokio::test]
+    async fn test_reads_skip_the_lobby_lock() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 4).await;
+        request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+
+        // A tick holding the write lock doesn't block the browser
+        let lobby = state.get_lobby("alpha").unwrap();
+        let _tick = lobby.write().await;
+        let read = async {
+            let (status, body) = request(&app, "GET", "/lobbies/alpha", None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["player_count"], 1);
+            let (status, body) = request(&app, "GET", "/lobbies", None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body[0]["players"][0]["name"], "Ada");
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(1), read).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_join_lobby_failures() {
+        let (app, _) = test_app().await;
+        create(&app, "tiny", 1).await;
+
+        let missing = request(&app, "POST", "/lobbies/nope/join", Some(json!({"player_name": "Ada"}))).await;
+        assert_eq!(missing, (StatusCode::NOT_FOUND, Value::Null));
+
+        let bad_name = request(&app, "POST", "/lobbies/tiny/join", Some(json!({"player_name": ""}))).await;
+        assert_eq!(bad_name, (StatusCode::BAD_REQUEST, Value::Null));
+
+        let (status, _) = request(&app, "POST", "/lobbies/tiny/join", Some(json!({}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = request(&app, "POST", "/lobbies/tiny/join", Some(json!({"player_name": "Ada"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let full = request(&app, "POST", "/lobbies/tiny/join", Some(json!({"player_name": "Grace"}))).await;
+        assert_eq!(full, (StatusCode::BAD_REQUEST, Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_join_attested_settings_must_fit_lobby_rules() {
+        let (app, state) = test_app().await;
+        let (status, body) = request(&app, "POST", "/lobbies", Some(json!({"code": "pro", "max_fov": 90.0}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["max_fov"].as_f64(), body["aim_assist"].as_bool()), (Some(90.0), Some(false)));
+
+        let wide = json!({"player_name": "Ada", "settings": {"fov": 120.0}});
+        assert_eq!(request(&app, "POST", "/lobbies/pro/join", Some(wide)).await, (StatusCode::BAD_REQUEST, Value::Null));
+        let assisted = json!({"player_name": "Ada", "settings": {"fov": 90.0, "aim_assist": true}});
+        assert_eq!(request(&app, "POST", "/lobbies/pro/join", Some(assisted)).await, (StatusCode::BAD_REQUEST, Value::Null));
+
+        let (status, body) = request(&app, "POST", "/lobbies/pro/join", Some(json!({"player_name": "Ada", "settings": {"fov": 80.0}}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["lobby"]["player_count"], 1);
+        let player_id = body["player_id"].as_u64().unwrap() as u32;
+        let lobby = state.get_lobby("pro").unwrap();
+        let attested = lobby.read().await.players[&player_id].attested;
+        assert_eq!(attested, Some(anticheat::ClientSettings { fov: 80.0, aim_assist: false }));
+    }
+
+    #[tokio::test]
+    async fn test_accounts_register_login_and_join() {
+        let (app, state) = test_app().await;
+        let credentials = json!({"username": "Ada", "password": "correct horse"});
+        assert_eq!(request(&app, "POST", "/accounts/register", Some(credentials.clone())).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        state.use_storage(Arc::new(crate::storage::Storage::in_memory().await));
+        state.use_account_tokens(TokenIssuer::new(b"secret", 60));
+        let (status, body) = request(&app, "POST", "/accounts/register", Some(credentials.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let account_id = body["account_id"].as_i64().unwrap();
+        assert_eq!(body["username"], "Ada");
+        let taken = json!({"username": "ada", "password": "something else"});
+        assert_eq!(request(&app, "POST", "/accounts/register", Some(taken)).await.0, StatusCode::CONFLICT);
+        let short = json!({"username": "Bob", "password": "short"});
+        assert_eq!(request(&app, "POST", "/accounts/register", Some(short)).await.0, StatusCode::BAD_REQUEST);
+
+        let wrong = json!({"username": "Ada", "password": "wrong horse"});
+        assert_eq!(request(&app, "POST", "/accounts/login", Some(wrong)).await.0, StatusCode::UNAUTHORIZED);
+        let (status, body) = request(&app, "POST", "/accounts/login", Some(credentials)).await;
+        assert_eq!(status, StatusCode::OK);
+        let token = body["token"].as_str().unwrap().to_string();
+
+        let me = Request::builder().uri("/accounts/me").header(header::AUTHORIZATION, format!("Bearer {}", token)).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(me).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let me: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(me, json!({"account_id": account_id, "username": "Ada", "kills": 0, "deaths": 0, "wins": 0, "matches": 0, "accuracy": 0.0}));
+        assert_eq!(request(&app, "GET", "/accounts/me", None).await.0, StatusCode::UNAUTHORIZED);
+
+        // Joining with the token links the player to the account; a bad token is refused
+        create(&app, "alpha", 4).await;
+        let forged = json!({"player_name": "Ada", "account_token": "forged"});
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/join", Some(forged)).await.0, StatusCode::UNAUTHORIZED);
+        let (status, body) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada", "account_token": token}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let player_id = body["player_id"].as_u64().unwrap() as u32;
+        let lobby = state.get_lobby("alpha").unwrap();
+        assert_eq!(lobby.read().await.players[&player_id].account_id, Some(account_id));
+    }
+
+    #[tokio::test]
+    async fn test_login_attempts_are_rate_limited() {
+        let (app, state) = test_app_with(Config { login_attempts_per_minute: 1, login_attempt_burst: 2, ..Config::default() }).await;
+        state.use_storage(Arc::new(crate::storage::Storage::in_memory().await));
+        state.use_account_tokens(TokenIssuer::new(b"secret", 60));
+        let credentials = json!({"username": "Ada", "password": "correct horse"});
+        assert_eq!(request(&app, "POST", "/accounts/register", Some(credentials.clone())).await.0, StatusCode::CREATED);
+
+        let login = |username: &str, ip: [u8; 4]| {
+            let body = json!({"username": username, "password": "wrong horse"});
+            Request::builder()
+                .method("POST")
+                .uri("/accounts/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .extension(ConnectInfo(std::net::SocketAddr::from((ip, 40000))))
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let status = |request: Request<Body>| {
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        // Per username (ignoring case), whichever address guesses
+        assert_eq!(status(login("Ada", [10, 0, 0, 1])).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(login("ada", [10, 0, 0, 2])).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(login("ADA", [10, 0, 0, 3])).await, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(request(&app, "POST", "/accounts/login", Some(credentials)).await.0, StatusCode::TOO_MANY_REQUESTS);
+
+        // Per address, whichever username it guesses
+        assert_eq!(status(login("Bob", [10, 0, 0, 9])).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(login("Cy", [10, 0, 0, 9])).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(login("Dee", [10, 0, 0, 9])).await, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(status(login("Eve", [10, 0, 0, 8])).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_account_leaderboard_contract() {
+        let (app, state) = test_app().await;
+        assert_eq!(request(&app, "GET", "/leaderboards", None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        let storage = Arc::new(crate::storage::Storage::in_memory().await);
+        state.use_storage(storage.clone());
+        state.use_account_tokens(TokenIssuer::new(b"secret", 60));
+        let mut results = Vec::new();
+        for (name, kills) in [("ada", 9), ("bob", 4), ("cy", 6)] {
+            let account = storage.create_account(name, &PasswordHash::new("password")).await.unwrap().unwrap();
+            results.push(crate::domain::matches::AccountResult {
+                account_id: account.account_id,
+                place: 1,
+                score: 0,
+                kills,
+                deaths: 2,
+                won: name == "bob",
+                shots: 0,
+                hits: 0,
+            });
+        }
+        let lobby = &mut crate::testutil::LobbyBuilder::new().build();
+        storage.record_match(&crate::domain::history::finish(lobby, None, Instant::now()), &results).await.unwrap();
+
+        let (status, body) = request(&app, "GET", "/leaderboards?metric=kd&period=weekly&limit=2&offset=1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 3);
+        assert_eq!(body["entries"], json!([
+            {"rank": 2, "account_id": results[2].account_id, "username": "cy", "kills": 6, "deaths": 2, "wins": 0, "kd": 3.0},
+            {"rank": 3, "account_id": results[1].account_id, "username": "bob", "kills": 4, "deaths": 2, "wins": 1, "kd": 2.0}
+        ]));
+        let (_, body) = request(&app, "GET", "/leaderboards?metric=wins", None).await;
+        assert_eq!((body["metric"].as_str(), body["period"].as_str()), (Some("wins"), Some("alltime")));
+        assert_eq!(body["entries"][0]["username"], "bob");
+        for query in ["metric=score", "period=daily", "limit=0", "limit=101"] {
+            assert_eq!(request(&app, "GET", &format!("/leaderboards?{}", query), None).await.0, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_history_contract() {
+        let (app, state) = test_app().await;
+        assert_eq!(request(&app, "GET", "/matches/abc", None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        let storage = Arc::new(crate::storage::Storage::in_memory().await);
+        state.use_storage(storage.clone());
+        let account_id = storage.create_account("ada", &PasswordHash::new("password")).await.unwrap().unwrap().account_id;
+        let mut lobby = crate::testutil::LobbyBuilder::new().with_player(crate::testutil::PlayerBuilder::new()).build();
+        lobby.players.get_mut(&1).unwrap().account_id = Some(account_id);
+        lobby.players.get_mut(&1).unwrap().kills = 4;
+        let record = crate::domain::history::finish(&mut lobby, Some(1), Instant::now());
+        let results = crate::domain::matches::account_results(&lobby, Some(1));
+        storage.record_match(&record, &results).await.unwrap();
+
+        let (status, body) = request(&app, "GET", &format!("/matches/{}", record.match_id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["lobby_code"], record.lobby_code.as_str());
+        assert_eq!(body["winner_id"], 1);
+        assert_eq!(body["participants"][0]["account_id"], account_id);
+        assert_eq!(request(&app, "GET", "/matches/abc", None).await.0, StatusCode::NOT_FOUND);
+
+        let (status, body) = request(&app, "GET", &format!("/players/{}/matches", account_id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["matches"][0]["match_id"], record.match_id.as_str());
+        assert_eq!((body["matches"][0]["place"].as_u64(), body["matches"][0]["kills"].as_u64()), (Some(1), Some(4)));
+        assert_eq!(body["matches"][0]["won"], true);
+        assert_eq!(request(&app, "GET", "/players/999/matches", None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(&app, "GET", &format!("/players/{}/matches?limit=0", account_id), None).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_spectate_full_lobby() {
+        let (app, _) = test_app().await;
+        create(&app, "tiny", 1).await;
+        request(&app, "POST", "/lobbies/tiny/join", Some(json!({"player_name": "Ada"}))).await;
+
+        let (status, body) = request(&app, "POST", "/lobbies/tiny/join", Some(json!({"player_name": "Watcher", "spectate": true}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["spectator"], true);
+        assert_eq!(body["lobby"]["player_count"], 1);
+        assert_eq!(body["lobby"]["spectator_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_spectate_code_only_admits_spectators() {
+        let (app, state) = test_app().await;
+        let (status, _) = request(&app, "POST", "/lobbies", Some(json!({"code": "secret", "password": "hunter2"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let spectate_code = state.lobby_listing("secret").unwrap().spectate_code.clone();
+        assert_ne!(spectate_code, "secret");
+
+        let (status, body) = request(&app, "POST", &format!("/spectate/{}", spectate_code), Some(json!({"player_name": "Caster"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["spectator"], true);
+        assert_eq!(body["lobby"]["code"], spectate_code.as_str());
+        assert_eq!(body["lobby"]["spectator_count"], 1);
+
+        // The spectate code is no join code, and unknown codes watch nothing
+        let join = request(&app, "POST", &format!("/lobbies/{}/join", spectate_code), Some(json!({"player_name": "Griefer"}))).await;
+        assert_eq!(join, (StatusCode::NOT_FOUND, Value::Null));
+        let unknown = request(&app, "POST", "/spectate/watch-nope", Some(json!({"player_name": "Caster"}))).await;
+        assert_eq!(unknown, (StatusCode::NOT_FOUND, Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_draining_refuses_new_sessions() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 4).await;
+        state.set_draining(true);
+
+        let refused = request(&app, "POST", "/lobbies", Some(json!({"code": "beta"}))).await;
+        assert_eq!(refused, (StatusCode::SERVICE_UNAVAILABLE, Value::Null));
+        let refused = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        assert_eq!(refused, (StatusCode::SERVICE_UNAVAILABLE, Value::Null));
+
+        // Existing lobbies stay visible while draining
+        let (status, _) = request(&app, "GET", "/lobbies/alpha", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_get_and_list_lobbies_contract() {
+        let (app, state) = test_app().await;
+
+        assert_eq!(request(&app, "GET", "/lobbies", None).await, (StatusCode::OK, json!([])));
+        assert_eq!(request(&app, "GET", "/lobbies/alpha", None).await, (StatusCode::NOT_FOUND, Value::Null));
+
+        create(&app, "alpha", 2).await;
+        let expected = json!({
+            "code": "alpha",
+            "player_count": 0,
+            "max_players": 2,
+            "players": [],
+            "spectator_count": 0,
+            "max_spectators": 8,
+            "spectate_code": state.lobby_listing("alpha").unwrap().spectate_code,
+            "server_ip": "127.0.0.1",
+            "udp_port": 8081,
+            "scene": "world",
+            "mode": "ffa",
+            "match_state": "waiting_for_players",
+            "round": 0,
+            "teams": 0,
+            "dimensions": 3,
+            "visibility": "public",
+            "password_protected": false,
+            "owner_id": null,
+            "locked": false,
+            "max_fov": 110.0,
+            "aim_assist": false,
+            "tick_rate_hz": 50
+        });
+        assert_eq!(request(&app, "GET", "/lobbies/alpha", None).await, (StatusCode::OK, expected.clone()));
+        assert_eq!(request(&app, "GET", "/lobbies", None).await, (StatusCode::OK, json!([expected])));
+    }
+
+    #[tokio::test]
+    async fn test_list_lobbies_etag_and_fields() {
+        let (app, _) = test_app().await;
+        create(&app, "alpha", 2).await;
+
+        let response = app.clone().oneshot(Request::get("/lobbies").body(Body::empty()).unwrap()).await.unwrap();
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert_eq!(etag, "\"lobbies-1\"");
+
+        let cached = Request::get("/lobbies").header(header::IF_NONE_MATCH, &etag).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(cached).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(to_bytes(response.into_body(), usize::MAX).await.unwrap().is_empty());
+
+        // A join changes the list, so the old ETag no longer matches
+        request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        let stale = Request::get("/lobbies").header(header::IF_NONE_MATCH, &etag).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(stale).await.unwrap().status(), StatusCode::OK);
+
+        let counts = request(&app, "GET", "/lobbies?fields=code,player_count", None).await;
+        assert_eq!(counts, (StatusCode::OK, json!([{"code": "alpha", "player_count": 1}])));
+        let unknown = request(&app, "GET", "/lobbies?fields=code,password", None).await;
+        assert_eq!(unknown, (StatusCode::BAD_REQUEST, Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_list_lobbies_since_revision() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 2).await;
+        create(&app, "beta", 2).await;
+        create(&app, "gamma", 2).await;
+
+        let (status, body) = request(&app, "GET", "/lobbies?since=0&fields=code", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["revision"], 3);
+        assert_eq!(body["full"], false);
+        assert_eq!(body["lobbies"].as_array().unwrap().len(), 3);
+
+        request(&app, "POST", "/lobbies/beta/join", Some(json!({"player_name": "Ada"}))).await;
+        state.remove_lobby("gamma");
+
+        let (_, body) = request(&app, "GET", "/lobbies?since=3&fields=code,player_count", None).await;
+        assert_eq!(body, json!({
+            "revision": 5,
+            "full": false,
+            "lobbies": [{"code": "beta", "player_count": 1}],
+            "removed": ["gamma"]
+        }));
+
+        // A revision from the future (e.g. before a restart) gets the full list
+        let (_, body) = request(&app, "GET", "/lobbies?since=99&fields=code", None).await;
+        assert_eq!(body["full"], true);
+        assert_eq!(body["lobbies"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_lobby_leaderboard_contract() {
+        let (app, _) = test_app().await;
+        assert_eq!(
+            request(&app, "GET", "/lobbies/alpha/leaderboard", None).await,
+            (StatusCode::NOT_FOUND, Value::Null)
+        );
+
+        create(&app, "alpha", 4).await;
+        let (_, joined) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+
+        let (status, body) = request(&app, "GET", "/lobbies/alpha/leaderboard", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({
+            "lobby_code": "alpha",
+            "entries": [{
+                "player_id": joined["player_id"],
+                "name": "Ada",
+                "score": 0,
+                "kills": 0,
+                "deaths": 0,
+                "killstreak": 0
+            }]
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_lobby_player_contract() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 4).await;
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        let (_, bob) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Bob"}))).await;
+        let id = ada["player_id"].as_u64().unwrap() as u32;
+        let bob_id = bob["player_id"].as_u64().unwrap() as u32;
+        state.get_lobby("alpha").unwrap().write().await.players.get_mut(&bob_id).unwrap().score = 100;
+
+        let get = |uri: String, token: &Value| {
+            let request = Request::builder()
+                .uri(uri)
+                .header(header::AUTHORIZATION, format!("Bearer {}", token.as_str().unwrap()))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<Value>(&bytes).unwrap_or(Value::Null))
+            }
+        };
+        let uri = format!("/lobbies/alpha/players/{}", id);
+
+        let (status, body) = get(uri.clone(), &ada["session_token"]).await;
+        assert_eq!(status, StatusCode::OK);
+        let weapon = &body["state"]["current_weapon_id"];
+        let ammo = &body["state"]["max_ammo"];
+        assert_eq!(body, json!({
+            "lobby_code": "alpha",
+            "state": {
+                "id": id,
+                "health": 100,
+                "max_health": 100,
+                "current_weapon_id": weapon,
+                "current_ammo": ammo,
+                "max_ammo": ammo,
+                "is_reloading": false,
+                "team_id": null,
+                "weapons": [weapon]
+            },
+            "is_dead": false,
+            "rank": 2,
+            "scoreboard": {"player_id": id, "name": "Ada", "score": 0, "kills": 0, "deaths": 0, "killstreak": 0}
+        }));
+
+        // Only the player's own session sees it
+        assert_eq!(request(&app, "GET", &uri, None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(uri, &bob["session_token"]).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(get(format!("/lobbies/beta/players/{}", id), &ada["session_token"]).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_global_leaderboard_contract() {
+        let (app, state) = test_app().await;
+        assert_eq!(request(&app, "GET", "/leaderboard", None).await, (StatusCode::OK, json!([])));
+
+        state.global_stats.record_session(7, "Ada", 4, 2, 450);
+        let (status, body) = request(&app, "GET", "/leaderboard", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([{
+            "player_id": 7,
+            "name": "Ada",
+            "total_kills": 4,
+            "total_deaths": 2,
+            "total_score": 450,
+            "games_played": 1,
+            "kdratio": 2.0
+        }]));
+    }
+
+    #[tokio::test]
+    async fn test_search_players_across_lobbies() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        let body = json!({"code": "hideout", "visibility": "unlisted"});
+        assert_eq!(request(&app, "POST", "/lobbies", Some(body)).await.0, StatusCode::OK);
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        request(&app, "POST", "/lobbies/hideout/join", Some(json!({"player_name": "adam"}))).await;
+        request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Bob"}))).await;
+
+        let (status, body) = request(&app, "GET", "/players?name=ADA", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([{
+            "id": ada["player_id"],
+            "uuid": ada["player_uuid"],
+            "name": "Ada",
+            "online": true,
+            "lobby": {
+                "code": "alpha",
+                "scene": "world",
+                "mode": "ffa",
+                "match_state": "waiting_for_players",
+                "password_protected": false
+            }
+        }]));
+
+        // Admins also see players in unlisted lobbies
+        let (_, body) = admin(&app, "GET", "/players?name=ada", None).await;
+        let lobbies: Vec<&Value> = body.as_array().unwrap().iter().map(|p| &p["lobby"]["code"]).collect();
+        assert_eq!(lobbies, vec!["alpha", "hideout"]);
+
+        // Offline players come from earlier sessions' stats
+        state.global_stats.record_session(99, "Adelaide", 1, 0, 100);
+        let (_, body) = request(&app, "GET", "/players?name=ad&offline=true", None).await;
+        let names: Vec<&Value> = body.as_array().unwrap().iter().map(|p| &p["name"]).collect();
+        assert_eq!(names, vec!["Ada", "Adelaide"]);
+        assert_eq!(body[1], json!({"id": 99, "name": "Adelaide", "online": false}));
+
+        assert_eq!(request(&app, "GET", "/players?name=", None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(&app, "GET", "/players?name=a%3Bb", None).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_admin_log_levels() {
+        // Without an admin token the endpoints don't exist
+        let (app, _) = test_app().await;
+        let (status, _) = request(&app, "GET", "/admin/log-levels", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (app, _) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        let (status, _) = request(&app, "GET", "/admin/log-levels", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let admin = |body: Value| {
+            Request::builder()
+                .method("PUT")
+                .uri("/admin/log-levels")
+                .header(header::AUTHORIZATION, "Bearer secret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(admin(json!({"module": "handlers::udp", "level": "trace"}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["modules"]["handlers::udp"], "trace");
+        assert_eq!(logging::levels().level_for("gungameserver::handlers::udp"), log::LevelFilter::Trace);
+
+        let response = app.clone().oneshot(admin(json!({"module": "handlers::udp", "level": "loud"}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app.clone().oneshot(admin(json!({"module": "handlers::udp", "level": null}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(logging::levels().modules.iter().all(|(module, _)| module != "handlers::udp"));
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_create_persistent_lobbies() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        let body = json!({"code": "lounge", "persistent": true});
+        let (status, _) = request(&app, "POST", "/lobbies", Some(body.clone())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = request(&app, "POST", "/lobbies", Some(json!({"code": "taped", "record_replay": true}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = request(&app, "POST", "/admin/lobbies", Some(body.clone())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let admin = Request::builder()
+            .method("POST")
+            .uri("/admin/lobbies")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(admin).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.get_lobby("lounge").unwrap().read().await.persistent);
+        assert!(state.persistent_lobbies.contains_key("lounge"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_tunables_change_live() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        let admin = |method: &str, uri: &str, body: Option<Value>| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::AUTHORIZATION, "Bearer secret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(admin("PUT", "/admin/tunables", Some(json!({"respawn_delay_ms": 1000})))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["respawn_delay_ms"], 1000);
+        assert_eq!(body["max_damage"], 100);
+
+        let response = app.clone().oneshot(admin("PUT", "/admin/tunables", Some(json!({"max_damage": 0})))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app.clone().oneshot(admin("GET", "/admin/tunables", None)).await.unwrap();
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["max_damage"], 100);
+
+        // The lobby picks the change up on its next tick
+        let lobby = state.get_lobby("alpha").unwrap();
+        let mut applied = false;
+        for _ in 0..50 {
+            if lobby.read().await.tunables.respawn_delay_ms == 1000 {
+                applied = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(applied);
+
+        // No tunables file was loaded
+        let response = app.clone().oneshot(admin("POST", "/admin/tunables/reload", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_admin_balance_report() {
+        let path = std::env::temp_dir().join(format!("balance-{}.jsonl", uuid::Uuid::new_v4()));
+        let config = Config {
+            admin_token: Some("secret".to_string()),
+            lobby_analytics_path: Some(path.to_str().unwrap().to_string()),
+            balance_min_kills: 5,
+            ..Config::default()
+        };
+        let (app, _) = test_app_with(config).await;
+        let (status, body) = admin(&app, "GET", "/admin/balance-report", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["lobbies"], &body["weapons"]), (&json!(0), &json!([])));
+
+        let mut lobby = Lobby::new("alpha".to_string(), 4, "world".to_string());
+        lobby.analytics.weapons.insert(1, crate::state::analytics::WeaponUsage { shots: 90, kills: 6, pickups: 0, timed_kills: 6, kill_time_ms: 6000 });
+        lobby.analytics.weapons.insert(2, crate::state::analytics::WeaponUsage { shots: 10, kills: 1, pickups: 1, timed_kills: 0, kill_time_ms: 0 });
+        crate::state::analytics::LobbySummary::from_lobby(&lobby, std::time::SystemTime::now()).append_to(path.to_str().unwrap()).unwrap();
+
+        // Served from the last build until the background job rebuilds it
+        assert_eq!(admin(&app, "GET", "/admin/balance-report", None).await.1["lobbies"], 0);
+        let (app, state) = test_app_with(Config { lobby_analytics_path: Some(path.to_str().unwrap().to_string()), balance_min_kills: 5, admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        let (_, body) = admin(&app, "GET", "/admin/balance-report", None).await;
+        assert_eq!(body["lobbies"], 1);
+        assert_eq!(body["weapons"][0], json!({
+            "weapon_id": 1,
+            "name": "Golden Friend",
+            "shots": 90,
+            "kills": 6,
+            "pickups": 0,
+            "usage_share": 0.9,
+            "kills_per_pickup": null,
+            "average_ttk_ms": 1000,
+            "flags": ["overused"]
+        }));
+        assert_eq!(body["weapons"][1]["flags"], json!([]));
+        assert!(state.balance_report.load().is_some());
+
+        let (app, _) = test_app_with(Config { admin_token: Some("secret".to_string()), lobby_analytics_path: None, ..Config::default() }).await;
+        assert_eq!(admin(&app, "GET", "/admin/balance-report", None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(&app, "GET", "/admin/balance-report", None).await.0, StatusCode::UNAUTHORIZED);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_reload_swaps_weapons_and_tunables() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        let dir = std::env::temp_dir().join(format!("reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (weapons_path, tunables_path) = (dir.join("weapons.json"), dir.join("tunables.json"));
+        state.weapons.load(&weapons_path).unwrap();
+        state.tunables.load(&tunables_path).unwrap();
+        create(&app, "alpha", 4).await;
+
+        let mut weapons: Value = serde_json::from_str(include_str!("../../../../shared/data/weapons.json")).unwrap();
+        weapons["data"][1]["damage"] = json!(35);
+        std::fs::write(&weapons_path, weapons.to_string()).unwrap();
+        std::fs::write(&tunables_path, json!({"respawn_delay_ms": 1000}).to_string()).unwrap();
+        let (status, body) = admin(&app, "POST", "/admin/reload", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["weapons"], &body["tunables"]["respawn_delay_ms"]), (&json!([1, 2, 3]), &json!(1000)));
+        assert_eq!(request(&app, "GET", "/weapons", None).await.1[1]["damage"], 35);
+
+        // Running lobbies pick the new weapons up on their next tick
+        let lobby = state.get_lobby("alpha").unwrap();
+        let mut applied = false;
+        for _ in 0..50 {
+            if lobby.read().await.tunables.respawn_delay_ms == 1000 {
+                applied = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(applied);
+
+        // A bad file changes nothing, not even the valid one next to it
+        std::fs::write(&tunables_path, json!({"respawn_delay_ms": 2000}).to_string()).unwrap();
+        weapons["data"][0]["fire_rate"] = json!(0.0);
+        std::fs::write(&weapons_path, weapons.to_string()).unwrap();
+        assert_eq!(admin(&app, "POST", "/admin/reload", None).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(state.tunables.get().respawn_delay_ms, 1000);
+
+        // Weapons lobbies may still hold can't disappear
+        weapons["data"].as_array_mut().unwrap().truncate(2);
+        weapons["data"][0]["fire_rate"] = json!(4.0);
+        std::fs::write(&weapons_path, weapons.to_string()).unwrap();
+        assert_eq!(admin(&app, "POST", "/admin/reload", None).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(state.weapons.get().contains(3));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bulk_admin_actions() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        create(&app, "beta", 4).await;
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        let (_, bob) = request(&app, "POST", "/lobbies/beta/join", Some(json!({"player_name": "Bob"}))).await;
+        assert_eq!(request(&app, "POST", "/admin/kick", Some(json!({"player_id": 1}))).await.0, StatusCode::UNAUTHORIZED);
+
+        let everywhere = json!({"lobbies": ["alpha", "beta"]});
+        assert_eq!(admin(&app, "POST", "/admin/broadcast", Some(json!({"text": "Restart in 5 minutes"}))).await, (StatusCode::OK, everywhere.clone()));
+        let elsewhere = json!({"text": "hi", "scene": "nowhere"});
+        assert_eq!(admin(&app, "POST", "/admin/broadcast", Some(elsewhere)).await, (StatusCode::OK, json!({"lobbies": []})));
+        assert_eq!(admin(&app, "POST", "/admin/broadcast", Some(json!({"text": " "}))).await.0, StatusCode::BAD_REQUEST);
+
+        let kick = json!({"player_id": ada["player_id"], "reason": "cheating"});
+        assert_eq!(admin(&app, "POST", "/admin/kick", Some(kick)).await, (StatusCode::OK, json!({"lobbies": ["alpha"]})));
+        let alpha = state.get_lobby("alpha").unwrap();
+        let mut removed = false;
+        for _ in 0..50 {
+            if alpha.read().await.players.is_empty() {
+                removed = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(removed);
+
+        // Joins are refused everywhere while frozen
+        assert_eq!(admin(&app, "PUT", "/admin/join-freeze", Some(json!({"frozen": true}))).await, (StatusCode::OK, json!({"frozen": true})));
+        let join = Some(json!({"player_name": "Cid"}));
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/join", join.clone()).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        admin(&app, "PUT", "/admin/join-freeze", Some(json!({"frozen": false}))).await;
+
+        // Only the lobby without human players is closed
+        assert_eq!(admin(&app, "POST", "/admin/lobbies/close-empty", None).await, (StatusCode::OK, json!({"lobbies": ["alpha"]})));
+        assert!(!state.lobby_exists("alpha"));
+        assert!(state.get_lobby("beta").unwrap().read().await.players.contains_key(&(bob["player_id"].as_u64().unwrap() as u32)));
+        assert_eq!(request(&app, "POST", "/lobbies/beta/join", join).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_lobby_details_delete_and_ban() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        let (status, _) = request(&app, "POST", "/lobbies", Some(json!({"code": "hidden", "visibility": "unlisted"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        assert_eq!(request(&app, "GET", "/admin/lobbies", None).await.0, StatusCode::UNAUTHORIZED);
+
+        // Unlisted lobbies are included
+        let (status, body) = admin(&app, "GET", "/admin/lobbies", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let codes: Vec<&str> = body.as_array().unwrap().iter().map(|l| l["code"].as_str().unwrap()).collect();
+        assert_eq!(codes, ["alpha", "hidden"]);
+
+        let (status, alpha) = admin(&app, "GET", "/admin/lobbies/alpha", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(alpha["player_count"], 1);
+        assert_eq!((alpha["persistent"].as_bool(), alpha["warm"].as_bool(), alpha["frozen"].as_bool()), (Some(false), Some(false), Some(false)));
+        assert_eq!(alpha["queue_capacity"], 1000);
+        assert!(alpha["queued_commands"].as_u64().unwrap() <= 1000);
+        assert_eq!(admin(&app, "GET", "/admin/lobbies/missing", None).await.0, StatusCode::NOT_FOUND);
+
+        // Banning takes the IP the player connected from
+        let ada_id = ada["player_id"].as_u64().unwrap() as u32;
+        let addr: std::net::SocketAddr = "10.0.0.7:5000".parse().unwrap();
+        state.get_lobby("alpha").unwrap().write().await.client_addresses.insert(ada_id, addr);
+        let kick = json!({"player_id": ada_id, "ban_secs": 600});
+        assert_eq!(admin(&app, "POST", "/admin/kick", Some(kick)).await, (StatusCode::OK, json!({"lobbies": ["alpha"]})));
+        assert!(state.bans.is_banned(&addr.ip(), Instant::now()));
+        assert!(!state.bans.is_banned(&"10.0.0.8".parse().unwrap(), Instant::now()));
+
+        // Force deleted even with players in it
+        request(&app, "POST", "/lobbies/hidden/join", Some(json!({"player_name": "Bob"}))).await;
+        assert_eq!(admin(&app, "DELETE", "/admin/lobbies/hidden", None).await.0, StatusCode::NO_CONTENT);
+        assert!(!state.lobby_exists("hidden"));
+        assert_eq!(admin(&app, "DELETE", "/admin/lobbies/hidden", None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_delete_frees_ip_slots_and_tells_players() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        state.ip_sessions.configure(1, &[]);
+        create(&app, "alpha", 4).await;
+        create(&app, "beta", 4).await;
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        let (_, bob) = request(&app, "POST", "/lobbies/beta/join", Some(json!({"player_name": "Bob"}))).await;
+        let (ada_id, bob_id) = (ada["player_id"].as_u64().unwrap() as u32, bob["player_id"].as_u64().unwrap() as u32);
+
+        // Ada's client holds the IP's only slot
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = client.local_addr().unwrap();
+        state.ip_sessions.try_acquire(addr.ip(), ada_id).unwrap();
+        state.get_lobby("alpha").unwrap().write().await.client_addresses.insert(ada_id, addr);
+        assert!(state.ip_sessions.try_acquire(addr.ip(), bob_id).is_err());
+
+        assert_eq!(admin(&app, "DELETE", "/admin/lobbies/alpha", None).await.0, StatusCode::NO_CONTENT);
+        let mut buf = [0u8; 512];
+        let len = tokio::time::timeout(std::time::Duration::from_secs(1), client.recv(&mut buf)).await.unwrap().unwrap();
+        let kicked: Value = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!((kicked["type"].as_str(), kicked["player_id"].as_u64()), (Some("kicked"), Some(ada_id as u64)));
+
+        // The slot and Ada's session went with the lobby
+        assert!(state.ip_sessions.try_acquire(addr.ip(), bob_id).is_ok());
+        assert!(!state.verify_session_token(ada_id, ada["session_token"].as_str().unwrap()));
+        assert!(state.find_lobby_by_player(ada_id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_admin_merge_lobby() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        create(&app, "beta", 4).await;
+        create(&app, "gamma", 1).await;
+        let (_, alice) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Alice"}))).await;
+        request(&app, "POST", "/lobbies/beta/join", Some(json!({"player_name": "Bob"}))).await;
+        request(&app, "POST", "/lobbies/gamma/join", Some(json!({"player_name": "Carol"}))).await;
+        let alice_id = alice["player_id"].as_u64().unwrap() as u32;
+        state.get_lobby("alpha").unwrap().write().await.players.get_mut(&alice_id).unwrap().kills = 3;
+
+        let into = |code: &str| Some(json!({"into": code}));
+        assert_eq!(admin(&app, "POST", "/admin/lobbies/alpha/merge", into("missing")).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(admin(&app, "POST", "/admin/lobbies/alpha/merge", into("gamma")).await.0, StatusCode::CONFLICT);
+        assert_eq!(request(&app, "POST", "/admin/lobbies/alpha/merge", into("beta")).await.0, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = admin(&app, "POST", "/admin/lobbies/alpha/merge", into("beta")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["moved"], json!([alice_id]));
+        assert_eq!(body["lobby"]["code"], "beta");
+        assert_eq!(body["lobby"]["player_count"], 2);
+        assert!(!state.lobby_exists("alpha"));
+        assert_eq!(state.find_lobby_by_player(alice_id).await.as_deref(), Some("beta"));
+        assert_eq!(state.get_lobby("beta").unwrap().read().await.players[&alice_id].kills, 3);
+    }
+
+    #[tokio::test]
+    async fn test_admin_time_scale() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+
+        let slow = Some(json!({"time_scale": 0.5}));
+        assert_eq!(admin(&app, "PUT", "/admin/lobbies/alpha/time-scale", slow.clone()).await, (StatusCode::OK, json!({"time_scale": 0.5})));
+        let alpha = state.get_lobby("alpha").unwrap();
+        let mut applied = false;
+        for _ in 0..50 {
+            if alpha.read().await.time_scale == 0.5 {
+                applied = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(applied);
+
+        let frozen = Some(json!({"time_scale": 0.0}));
+        assert_eq!(admin(&app, "PUT", "/admin/lobbies/alpha/time-scale", frozen).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(admin(&app, "PUT", "/admin/lobbies/nope/time-scale", slow).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_grant_weapon() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        let id = ada["player_id"].as_u64().unwrap() as u32;
+        let uri = format!("/admin/lobbies/alpha/players/{}/weapons", id);
+
+        let prototype = Some(json!({"weapon_id": 2}));
+        assert_eq!(admin(&app, "POST", &uri, prototype.clone()).await, (StatusCode::OK, json!({"weapon_id": 2})));
+        let alpha = state.get_lobby("alpha").unwrap();
+        let mut granted = false;
+        for _ in 0..50 {
+            if alpha.read().await.players[&id].inventory.contains_key(&2) {
+                granted = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(granted);
+
+        assert_eq!(admin(&app, "POST", &uri, Some(json!({"weapon_id": 99}))).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(admin(&app, "POST", "/admin/lobbies/alpha/players/999/weapons", prototype.clone()).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(admin(&app, "POST", "/admin/lobbies/nope/players/1/weapons", prototype.clone()).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(request(&app, "POST", &uri, prototype).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_referee_actions_are_audited() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        let (_, watcher) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Watcher", "spectate": true}))).await;
+        let watcher_id = watcher["player_id"].as_u64().unwrap() as u32;
+
+        let referee = Some(json!({"player_name": "Ref"}));
+        assert_eq!(request(&app, "POST", "/admin/lobbies/alpha/referee", referee.clone()).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(admin(&app, "POST", "/admin/lobbies/nope/referee", referee.clone()).await.0, StatusCode::NOT_FOUND);
+        let (status, body) = admin(&app, "POST", "/admin/lobbies/alpha/referee", referee).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["spectator"], true);
+        let referee_id = body["player_id"].as_u64().unwrap() as u32;
+
+        let freeze = || crate::domain::referee::RefereeAction::Freeze { frozen: true };
+        assert!(state.inject("alpha", LobbyCommand::Referee { player_id: watcher_id, action: freeze() }));
+        assert!(state.inject("alpha", LobbyCommand::Referee { player_id: referee_id, action: freeze() }));
+        let alpha = state.get_lobby("alpha").unwrap();
+        let mut frozen = false;
+        for _ in 0..50 {
+            if alpha.read().await.frozen {
+                frozen = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(frozen);
+
+        let (status, body) = admin(&app, "GET", "/admin/audit?lobby=alpha", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let entries = body.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["actor"], format!("referee {} (Watcher)", watcher_id));
+        assert_eq!(entries[0]["outcome"], "Not a referee");
+        assert_eq!(entries[1]["actor"], format!("referee {} (Ref)", referee_id));
+        assert_eq!(entries[1]["action"], "freeze frozen=true");
+        assert_eq!(entries[1]["outcome"], "ok");
+        assert_eq!(admin(&app, "GET", "/admin/audit?lobby=beta", None).await.1, json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_matchmake_fills_lobbies_from_warm_pool() {
+        let config = Config { matchmake_max_players: 2, ..Config::default() };
+        let (app, state) = test_app_with(config.clone()).await;
+        let code = crate::server::create_warm_lobby(
+            state.clone(),
+            "world".to_string(),
+            Arc::new(WeaponDb::load()),
+            Arc::new(config),
+            Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()),
+        );
+
+        // Idle pool lobbies are hidden from the browser and direct joins
+        let (_, body) = request(&app, "GET", "/lobbies", None).await;
+        assert_eq!(body, json!([]));
+        let (status, _) = request(&app, "GET", &format!("/lobbies/{}", code), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = request(&app, "POST", &format!("/lobbies/{}/join", code), Some(json!({"player_name": "Eve"}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = request(&app, "POST", "/matchmake", Some(json!({"player_name": "Alice"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["lobby"]["code"], code.as_str());
+        assert_eq!(state.warm_pool.idle_count("world"), 0);
+        let (_, body) = request(&app, "GET", "/lobbies", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        // Bob fills the matched lobby; Carol gets a lobby created on demand
+        let (_, body) = request(&app, "POST", "/matchmake", Some(json!({"player_name": "Bob"}))).await;
+        assert_eq!(body["lobby"]["code"], code.as_str());
+        assert_eq!(body["lobby"]["player_count"], 2);
+        let (status, body) = request(&app, "POST", "/matchmake", Some(json!({"player_name": "Carol"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(body["lobby"]["code"], code.as_str());
+        assert_eq!(body["lobby"]["scene"], "world");
+
+        let (status, _) = request(&app, "POST", "/matchmake", Some(json!({"player_name": "Dave", "scene": "nowhere"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_report_submission_and_review() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        create(&app, "alpha", 4).await;
+        let (_, alice) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Alice"}))).await;
+        let (_, mallory) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Mallory"}))).await;
+        {
+            let lobby = state.get_lobby("alpha").unwrap();
+            let mut lobby = lobby.write().await;
+            let mallory = lobby.players.get_mut(&(mallory["player_id"].as_u64().unwrap() as u32)).unwrap();
+            mallory.violations.record(crate::domain::anticheat::ViolationKind::NoRecoil, std::time::Instant::now());
+        }
+
+        let report = |token: &Value, reason: &str| json!({
+            "player_uuid": alice["player_uuid"],
+            "session_token": token,
+            "reported_uuid": mallory["player_uuid"],
+            "reason": reason,
+            "lobby_code": "alpha",
+            "match_id": "m-1"
+        });
+        let (status, _) = request(&app, "POST", "/reports", Some(report(&json!("forged"), "cheating"))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = request(&app, "POST", "/reports", Some(report(&alice["session_token"], "smelly"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = request(&app, "POST", "/reports", Some(report(&alice["session_token"], "cheating"))).await;
+        assert_eq!(status, StatusCode::OK);
+        let report_id = body["report_id"].as_u64().unwrap();
+        let (status, _) = request(&app, "POST", "/reports", Some(report(&alice["session_token"], "cheating"))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        // The review queue is admin only
+        let admin = |method: &str, uri: &str, body: Option<Value>| {
+            let builder = Request::builder().method(method).uri(uri).header(header::AUTHORIZATION, "Bearer secret");
+            match body {
+                Some(body) => builder.header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())).unwrap(),
+                None => builder.body(Body::empty()).unwrap(),
+            }
+        };
+        let (status, _) = request(&app, "GET", "/admin/reports", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(admin("GET", "/admin/reports", None)).await.unwrap();
+        let queue: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(queue.as_array().unwrap().len(), 1);
+        assert_eq!(queue[0]["reported_name"], "Mallory");
+        assert_eq!(queue[0]["evidence"]["violations"]["no_recoil"], 1);
+        assert_eq!(queue[0]["status"], "open");
+
+        let uri = format!("/admin/reports/{}/resolve", report_id);
+        let response = app.clone().oneshot(admin("POST", &uri, Some(json!({"status": "actioned", "note": "Banned"})))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(admin("POST", &uri, Some(json!({"status": "dismissed"})))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = app.clone().oneshot(admin("GET", "/admin/reports", None)).await.unwrap();
+        let queue: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert!(queue.as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_weapon_ladder_endpoints() {
+        let (app, state) = test_app_with(Config { admin_token: Some("secret".to_string()), ..Config::default() }).await;
+        let (status, _) = request(&app, "POST", "/lobbies", Some(json!({"code": "guns", "mode": "gun_game"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        create(&app, "alpha", 4).await;
+        let (_, alice) = request(&app, "POST", "/lobbies/guns/join", Some(json!({"player_name": "Alice"}))).await;
+
+        assert_eq!(request(&app, "GET", "/lobbies/guns/ladder", None).await, (StatusCode::OK, json!({"ladder": [1, 2, 3]})));
+        assert_eq!(request(&app, "GET", "/lobbies/alpha/ladder", None).await.0, StatusCode::CONFLICT);
+        assert_eq!(request(&app, "GET", "/lobbies/missing/ladder", None).await.0, StatusCode::NOT_FOUND);
+
+        // Changing a ladder is admin only and checked against the weapon list
+        let (status, _) = request(&app, "PUT", "/lobbies/guns/ladder", Some(json!({"weapons": [2]}))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(admin(&app, "PUT", "/lobbies/guns/ladder", Some(json!({"weapons": [2, 99]}))).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(admin(&app, "PUT", "/lobbies/alpha/ladder", Some(json!({"weapons": [2]}))).await.0, StatusCode::CONFLICT);
+        assert_eq!(admin(&app, "PUT", "/lobbies/guns/ladder", Some(json!({"weapons": [2]}))).await, (StatusCode::OK, json!({"ladder": [2, 3]})));
+        let alice_id = alice["player_id"].as_u64().unwrap() as u32;
+        assert_eq!(state.get_lobby("guns").unwrap().read().await.players.get(&alice_id).unwrap().current_weapon_id, 2);
+
+        // Presets
+        assert_eq!(admin(&app, "PUT", "/admin/ladders/pistol%20only", Some(json!({"weapons": [1]}))).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(admin(&app, "PUT", "/admin/ladders/reverse", Some(json!({"weapons": [2, 1]}))).await, (StatusCode::OK, json!({"ladder": [2, 1, 3]})));
+        assert_eq!(admin(&app, "GET", "/admin/ladders", None).await, (StatusCode::OK, json!({"reverse": [2, 1, 3]})));
+        assert_eq!(admin(&app, "PUT", "/lobbies/guns/ladder", Some(json!({"preset": "missing"}))).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(admin(&app, "PUT", "/lobbies/guns/ladder", Some(json!({"preset": "reverse"}))).await.0, StatusCode::OK);
+        assert_eq!(request(&app, "GET", "/lobbies/guns/ladder", None).await.1, json!({"ladder": [2, 1, 3]}));
+        assert_eq!(admin(&app, "DELETE", "/admin/ladders/reverse", None).await.0, StatusCode::NO_CONTENT);
+        assert_eq!(admin(&app, "DELETE", "/admin/ladders/reverse", None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_server_info_contract() {
+        let (app, _) = test_app().await;
+        let (status, body) = request(&app, "GET", "/info", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["git_hash"].as_str().is_some_and(|hash| !hash.is_empty()));
+        assert_eq!(body["protocol_version"], protocol::CURRENT_PROTOCOL_VERSION);
+        assert_eq!(body["tick_rate_hz"], 50);
+        assert_eq!(body["transport"], "json-udp");
+        assert!(body["uptime_secs"].is_u64());
+        assert_eq!(body["features"], json!(["udp_encryption", "quantize_positions", "visibility_culling", "lag_compensation", "warm_pool"]));
+    }
+
+    #[tokio::test]
+    async fn test_match_demo_download() {
+        let dir = std::env::temp_dir().join(format!("gungame-demos-{}", std::process::id()));
+        let config = Config { demo_dir: dir.to_str().unwrap().to_string(), ..Config::default() };
+        let (app, _) = test_app_with(config).await;
+
+        let options = crate::simulate::SimulationOptions { bots: 2, mode: crate::simulate::SimulationMode::Ffa, ticks: 100 };
+        let report = crate::simulate::run(&options, &WeaponDb::load(), &Config::default()).unwrap();
+        let demo = crate::demo::Demo::export("abc-123", &options, &report, &Config::default());
+        demo.save(&dir.join("abc-123.demo.json")).unwrap();
+
+        let (status, body) = request(&app, "GET", "/matches/abc-123/demo", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["format"], "gungame-demo");
+        assert_eq!(body["match_id"], "abc-123");
+
+        assert_eq!(request(&app, "GET", "/matches/missing/demo", None).await, (StatusCode::NOT_FOUND, Value::Null));
+        assert_eq!(request(&app, "GET", "/matches/..%2Fx/demo", None).await, (StatusCode::BAD_REQUEST, Value::Null));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rematch_reserves_slots_for_the_party() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 3).await;
+        let (_, alice) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Alice"}))).await;
+        request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Bob"}))).await;
+        let rematch = |token: &Value| json!({"player_uuid": alice["player_uuid"], "session_token": token});
+
+        let (status, _) = request(&app, "POST", "/lobbies/alpha/rematch", Some(rematch(&json!("forged")))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = request(&app, "POST", "/lobbies/alpha/rematch", Some(rematch(&alice["session_token"]))).await;
+        assert_eq!(status, StatusCode::OK);
+        let code = body["code"].as_str().unwrap().to_string();
+        assert_eq!(body["max_players"], 3);
+        let (_, again) = request(&app, "POST", "/lobbies/alpha/rematch", Some(rematch(&alice["session_token"]))).await;
+        assert_eq!(again["code"], code.as_str());
+
+        // Two of the three slots are held for Alice and Bob
+        let tokens: Vec<String> = state.get_lobby(&code).unwrap().read().await.reservations.keys().cloned().collect();
+        assert_eq!(tokens.len(), 2);
+        let join = |name: &str, reservation: Option<&String>| json!({"player_name": name, "reservation": reservation});
+        let uri = format!("/lobbies/{}/join", code);
+        assert_eq!(request(&app, "POST", &uri, Some(join("Carol", None))).await.0, StatusCode::OK);
+        assert_eq!(request(&app, "POST", &uri, Some(join("Dave", None))).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(request(&app, "POST", &uri, Some(join("Alice", Some(&tokens[0])))).await.0, StatusCode::OK);
+        assert_eq!(request(&app, "POST", &uri, Some(join("Mallory", Some(&tokens[0])))).await.0, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_lobby_owner_actions() {
+        let (app, state) = test_app().await;
+        create(&app, "alpha", 4).await;
+        let (_, ada) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Ada"}))).await;
+        let (_, bob) = request(&app, "POST", "/lobbies/alpha/join", Some(json!({"player_name": "Bob"}))).await;
+        assert_eq!(bob["lobby"]["owner_id"], ada["player_id"]);
+        let act = |who: &Value, action: Value| {
+            let mut body = json!({"player_uuid": who["player_uuid"], "session_token": who["session_token"]});
+            body.as_object_mut().unwrap().extend(action.as_object().unwrap().clone());
+            body
+        };
+
+        let lock = json!({"action": "lock", "locked": true});
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/owner", Some(act(&bob, lock.clone()))).await.0, StatusCode::FORBIDDEN);
+        let (status, info) = request(&app, "POST", "/lobbies/alpha/owner", Some(act(&ada, lock))).await;
+        assert_eq!((status, &info["locked"]), (StatusCode::OK, &json!(true)));
+        let carol = Some(json!({"player_name": "Carol"}));
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/join", carol).await.0, StatusCode::LOCKED);
+
+        let resize = json!({"action": "set_max_players", "max_players": 1});
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/owner", Some(act(&ada, resize))).await.0, StatusCode::BAD_REQUEST);
+        let scene = json!({"action": "set_scene", "scene": "world"});
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/owner", Some(act(&ada, scene))).await.0, StatusCode::OK);
+
+        // The new owner kicks the old one
+        let transfer = json!({"action": "transfer_ownership", "target_id": bob["player_id"]});
+        let (_, info) = request(&app, "POST", "/lobbies/alpha/owner", Some(act(&ada, transfer))).await;
+        assert_eq!(info["owner_id"], bob["player_id"]);
+        let kick = json!({"action": "kick", "target_id": ada["player_id"]});
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/owner", Some(act(&ada, kick.clone()))).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(&app, "POST", "/lobbies/alpha/owner", Some(act(&bob, kick))).await.0, StatusCode::OK);
+        let ada_id = ada["player_id"].as_u64().unwrap() as u32;
+        let lobby = state.get_lobby("alpha").unwrap();
+        let mut removed = false;
+        for _ in 0..50 {
+            if !lobby.read().await.players.contains_key(&ada_id) {
+                removed = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(removed);
+    }
+
+    #[tokio::test]
+    async fn test_private_lobbies_need_the_password_and_stay_unlisted() {
+        let (app, state) = test_app().await;
+        let body = json!({"code": "hideout", "password": "hunter2", "visibility": "unlisted"});
+        let (status, info) = request(&app, "POST", "/lobbies", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&info["visibility"], &info["password_protected"]), (&json!("unlisted"), &json!(true)));
+        let (status, _) = request(&app, "POST", "/lobbies", Some(json!({"code": "x", "visibility": "secret"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        create(&app, "alpha", 4).await;
+
+        let (_, lobbies) = request(&app, "GET", "/lobbies", None).await;
+        assert_eq!(lobbies.as_array().unwrap().iter().map(|l| l["code"].clone()).collect::<Vec<_>>(), vec![json!("alpha")]);
+        assert_eq!(request(&app, "GET", "/lobbies/hideout", None).await.0, StatusCode::OK);
+
+        let join = |password: Option<&str>| json!({"player_name": "Ada", "password": password});
+        assert_eq!(request(&app, "POST", "/lobbies/hideout/join", Some(join(None))).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(&app, "POST", "/lobbies/hideout/join", Some(join(Some("hunter3")))).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(request(&app, "POST", "/lobbies/hideout/join", Some(join(Some("hunter2")))).await.0, StatusCode::OK);
+        let lobby = state.get_lobby("hideout").unwrap();
+        assert!(lobby.read().await.password.as_ref().is_some_and(|hash| !format!("{:?}", hash).contains("hunter2")));
+    }
 }
@@ -1,3 +1,4 @@
 pub mod http;
 pub mod udp;
 pub mod models;
+pub mod websocket;
@@ -1,21 +1,109 @@
 use serde::{Deserialize, Serialize};
+use crate::domain::anticheat::ClientSettings;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateLobbyRequest {
     pub code: String,
     pub max_players: Option<u32>,
     pub scene: Option<String>,
+    pub mode: Option<String>, // "ffa" (default) or "gun_game"
+    pub teams: Option<u32>, // 2 to 4 teams; None or 0 = no teams
+    pub dimensions: Option<u8>, // 3 (default) or 2: positions on the x/z plane only
+    #[serde(default)]
+    pub persistent: bool, // Admin only: never closed when empty, recreated if removed
+    pub hide_enemy_state: Option<bool>, // Enemies only see each other's health bucket; None = server default
+    pub max_fov: Option<f32>, // Widest client FOV (degrees) joining players may attest; None = server default
+    pub aim_assist: Option<bool>, // Whether joining players may use aim assist; None = server default
+    pub password: Option<String>, // Required to join; only a salted hash is kept
+    pub visibility: Option<String>, // "public" (default) or "unlisted": left out of `GET /lobbies`
+    pub bots: Option<u32>, // Server-driven players added on creation; they take player slots
+    pub bot_fill: Option<u32>, // Keep this many players in by adding bots, which leave as players join
+    pub tick_rate_hz: Option<u32>, // Within the server's `lobby_tick_rate_min_hz`..`lobby_tick_rate_max_hz`; default `tick_rate_hz`
+    #[serde(default)]
+    pub record_replay: bool, // Admin only: write the lobby's commands to a replay file (see `replay_dir`)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoinLobbyRequest {
     pub player_name: String,
+    #[serde(default)]
+    pub encryption: bool, // Ask for a UDP session key (older clients omit this)
+    #[serde(default)]
+    pub team: Option<u32>, // Preferred team; the smallest team is picked otherwise
+    #[serde(default)]
+    pub reservation: Option<String>, // Rematch slot token from `rematch_available`
+    #[serde(default)]
+    pub password: Option<String>, // For password protected lobbies
+    #[serde(default)]
+    pub spectate: bool, // Watch instead of play: no player slot, no combat state
+    #[serde(default)]
+    pub settings: Option<ClientSettings>, // FOV and aim assist the client plays with, checked against the lobby's rules
+    #[serde(default)]
+    pub account_token: Option<String>, // From register/login: the match counts towards the account's stats
+    #[serde(default)]
+    pub protocol_version: Option<u32>, // Newest UDP protocol version the client speaks, checked before joining
+    #[serde(default)]
+    pub min_protocol_version: Option<u32>, // Oldest it speaks
+}
+
+/// `POST /spectate/{spectate_code}` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpectateRequest {
+    pub player_name: String,
+    #[serde(default)]
+    pub encryption: bool, // Ask for a UDP session key
+    #[serde(default)]
+    pub protocol_version: Option<u32>, // As for joining
+    #[serde(default)]
+    pub min_protocol_version: Option<u32>,
+}
+
+/// `POST /lobbies/{code}/rematch` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RematchRequest {
+    pub player_uuid: String, // A player of the lobby, as returned by join
+    pub session_token: String,
+}
+
+/// `POST /lobbies/{code}/owner` body: the owner's credentials and the action's fields
+#[derive(Debug, Clone, Deserialize)]
+pub struct OwnerActionRequest {
+    pub player_uuid: String,
+    pub session_token: String,
+    #[serde(flatten)]
+    pub action: crate::domain::owner::OwnerAction,
+}
+
+/// `POST /matchmake` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchmakeRequest {
+    pub player_name: String,
+    pub scene: Option<String>, // Defaults to "world"
+    #[serde(default)]
+    pub encryption: bool, // Ask for a UDP session key
+    #[serde(default)]
+    pub settings: Option<ClientSettings>, // As for joining
+    #[serde(default)]
+    pub account_token: Option<String>, // As for joining
+    #[serde(default)]
+    pub protocol_version: Option<u32>, // As for joining
+    #[serde(default)]
+    pub min_protocol_version: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoinLobbyResponse {
     pub lobby: LobbyInfo,
-    pub player_id: u32,
+    pub player_id: u32, // Numeric handle, valid until the player leaves
+    pub player_uuid: String, // Identifies the player on UDP (`player_uuid`) and in stats
+    pub session_token: String, // Presented on UDP packets from a new address (NAT rebinding)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_key: Option<String>, // Hex XChaCha20-Poly1305 key, only over HTTPS
+    pub spectator: bool, // Joined to watch; `player_id` is the spectator's handle
+    pub protocol_version: u32, // UDP protocol version to speak, the newest both sides do
+    pub min_protocol_version: u32, // Versions the server speaks
+    pub max_protocol_version: u32,
+    pub features: Vec<String>, // Protocol capabilities the server supports; the UDP join grants the ones asked for
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,13 +112,326 @@ pub struct LobbyInfo {
     pub player_count: usize,
     pub max_players: u32,
     pub players: Vec<PlayerInfo>,
+    pub spectator_count: usize,
+    pub max_spectators: u32,
+    pub spectate_code: String, // Public, watch-only: `POST /spectate/{spectate_code}` can't join as a player
     pub server_ip: String,
     pub udp_port: u16,
     pub scene: String,
+    pub mode: String,
+    pub match_state: String, // waiting_for_players, countdown, in_progress, round_end or match_end
+    pub round: u32, // Current round, 0 outside rounds
+    pub teams: u32, // Number of teams, 0 = no teams
+    pub dimensions: u8, // 3, or 2 for lobbies on the x/z plane
+    pub visibility: String, // public or unlisted
+    pub password_protected: bool,
+    pub owner_id: Option<u32>, // Player-created lobbies only
+    pub locked: bool, // Joins are refused
+    pub max_fov: f32, // Widest client FOV joining players may attest
+    pub aim_assist: bool, // Joining players may use aim assist
+    pub tick_rate_hz: u32, // Full-rate ticks per second of the lobby
+}
+
+/// Query string for `GET /lobbies`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ListLobbiesQuery {
+    pub fields: Option<String>, // Comma separated LobbyInfo fields, e.g. "code,player_count,max_players"
+    pub since: Option<u64>, // Only lobbies changed after this browser revision
+}
+
+/// `GET /lobbies?since=` response: changes after a browser revision
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyChanges {
+    pub revision: u64, // Pass as `since` on the next poll
+    pub full: bool, // `since` was too old to diff from: `lobbies` is the complete list
+    pub lobbies: Vec<serde_json::Value>,
+    pub removed: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerInfo {
-    pub id: u32,
+    pub id: u32, // Numeric handle used in game packets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>, // Public identity; bots have none
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<u32>,
+}
+
+/// `GET/PUT /admin/log-levels` response: the levels the running server logs at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLevelsResponse {
+    pub default: String,
+    pub modules: std::collections::BTreeMap<String, String>, // Module path -> level
+}
+
+/// `PUT /admin/log-levels` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetLogLevelRequest {
+    pub module: Option<String>, // e.g. "handlers::udp"; None sets the default level
+    pub level: Option<String>, // None removes the module's override
+}
+
+/// `POST /admin/kick` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminKickRequest {
+    pub player_id: u32,
+    pub reason: Option<String>, // Shown to the player
+    pub ban_secs: Option<u64>, // Also refuse UDP joins from the player's IP for this long
+}
+
+/// `POST /admin/broadcast` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminBroadcastRequest {
+    pub text: String,
+    pub scene: Option<String>, // Only lobbies on this scene; None = all lobbies
+}
+
+/// `PUT /admin/join-freeze` body and response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinFreeze {
+    pub frozen: bool,
+}
+
+/// `PUT /admin/lobbies/{code}/time-scale` body and response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeScale {
+    pub time_scale: f32, // 1.0 = normal speed
+}
+
+/// `POST /admin/lobbies/{code}/players/{id}/weapons` body and response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrantWeapon {
+    pub weapon_id: u32,
+}
+
+/// Lobbies a bulk admin action reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkAdminResponse {
+    pub lobbies: Vec<String>, // Sorted
+}
+
+/// `GET /admin/lobbies` entry: the browser view plus what only operators see
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminLobbyInfo {
+    #[serde(flatten)]
+    pub lobby: LobbyInfo,
+    pub bots: usize,
+    pub persistent: bool,
+    pub warm: bool, // Idle in the matchmaking warm pool
+    pub frozen: bool, // Referee freeze
+    pub time_scale: f32,
+    pub queued_commands: usize, // Waiting on the lobby's command queue
+    pub queue_capacity: usize,
+    pub tick_load: Option<f32>, // Smoothed share of the tick budget used; None before the first tick
+}
+
+/// `POST /admin/lobbies/:code/merge` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeLobbyRequest {
+    pub into: String, // Lobby the players move to; same scene and mode
+}
+
+/// Result of a lobby merge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeLobbyResponse {
+    pub lobby: LobbyInfo, // The target, with the moved players
+    pub moved: Vec<u32>, // Player and spectator ids now in the target
+}
+
+/// `POST /reports` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitReportRequest {
+    pub player_uuid: String, // Reporter, as returned by join
+    pub session_token: String, // Reporter's session token from join
+    pub reported_uuid: String,
+    pub reason: String, // cheating, griefing, abusive_chat, offensive_name or other
+    pub details: Option<String>,
+    pub lobby_code: String,
+    pub match_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitReportResponse {
+    pub report_id: u64,
+}
+
+/// Query string for `GET /admin/reports`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ListReportsQuery {
+    pub status: Option<String>, // open (default), dismissed or actioned
+}
+
+/// Query string for `GET /players`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PlayerSearchQuery {
+    pub name: String, // Name prefix, case-insensitive
+    #[serde(default)]
+    pub offline: bool, // Also search players seen in earlier sessions
+}
+
+/// One `GET /players` match
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerSearchResult {
+    pub id: u32, // Numeric handle (the leaderboard's `player_id` for offline players)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>, // Public identity while online
     pub name: String,
+    pub online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lobby: Option<PlayerSearchLobby>, // Where an online player is
+}
+
+/// The lobby an online search match is in
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerSearchLobby {
+    pub code: String,
+    pub scene: String,
+    pub mode: String,
+    pub match_state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<u32>,
+    pub password_protected: bool,
+}
+
+/// `POST /admin/reload` response: what is in effect after the reload
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadResponse {
+    pub weapons: Vec<u32>, // Weapon ids, ordered
+    pub tunables: crate::utils::tunables::Tunables,
+}
+
+/// Query string for `GET /admin/audit`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub lobby: Option<String>, // Only this lobby's entries
+}
+
+/// `POST /admin/reports/:id/resolve` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolveReportRequest {
+    pub status: String, // dismissed or actioned
+    pub note: Option<String>,
+}
+
+/// A gun game ladder: weapon ids in order, ending with the knife
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LadderResponse {
+    pub ladder: Vec<u32>,
+}
+
+/// `PUT /lobbies/:code/ladder` body: weapon ids, or the name of a saved preset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetLadderRequest {
+    pub weapons: Option<Vec<u32>>, // The knife is appended if missing; empty = all guns by id
+    pub preset: Option<String>,
+}
+
+/// `PUT /admin/ladders/:name` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveLadderPresetRequest {
+    pub weapons: Vec<u32>, // The knife is appended if missing
+}
+
+/// `GET /info`: which build is running and how it's configured
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub version: String, // Crate version
+    pub git_hash: String, // Commit built from ("unknown" outside a checkout)
+    pub protocol_version: u32, // Newest UDP protocol version spoken
+    pub min_protocol_version: u32, // Oldest UDP protocol version spoken
+    pub capabilities: u32, // Protocol capability flags the server supports
+    pub uptime_secs: u64,
+    pub tick_rate_hz: u32,
+    pub transport: String, // json-udp or renet
+    pub features: Vec<String>, // Optional behaviour turned on in the config
+}
+
+/// `POST /accounts/register` and `POST /accounts/login` body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// A signed-in account: pass `token` as `account_token` when joining
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSession {
+    pub account_id: i64,
+    pub username: String,
+    pub token: String, // JWT, valid for `account_token_ttl_secs`
+}
+
+/// `GET /accounts/me`: lifetime stats of the signed-in account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub account_id: i64,
+    pub username: String,
+    pub kills: u32,
+    pub deaths: u32,
+    pub wins: u32,
+    pub matches: u32,
+    pub accuracy: f32, // Share of shots that hit
+}
+
+/// `GET /leaderboards` query
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountLeaderboardQuery {
+    pub metric: Option<String>, // "kills" (default), "wins" or "kd"
+    pub period: Option<String>, // "alltime" (default) or "weekly"
+    pub limit: Option<u32>, // Page size, default 20, at most 100
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// One page of `GET /leaderboards`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountLeaderboard {
+    pub metric: String,
+    pub period: String,
+    pub total: u32, // Accounts ranked, across all pages
+    pub offset: u32,
+    pub entries: Vec<AccountLeaderboardEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountLeaderboardEntry {
+    pub rank: u32, // 1-based, across all pages
+    pub account_id: i64,
+    pub username: String,
+    pub kills: u32,
+    pub deaths: u32,
+    pub wins: u32,
+    pub kd: f32,
+}
+
+/// `GET /players/:id/matches` query
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatchHistoryQuery {
+    pub limit: Option<u32>, // Page size, default 20, at most 100
+    #[serde(default)]
+    pub offset: u32,
+}
+
+/// One page of an account's match history, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchHistory {
+    pub account_id: i64,
+    pub total: u32, // Matches played, across all pages
+    pub offset: u32,
+    pub matches: Vec<MatchHistoryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchHistoryEntry {
+    pub match_id: String, // Full record at `GET /matches/:id`
+    pub lobby_code: String,
+    pub mode: String,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub place: u32,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub won: bool,
 }
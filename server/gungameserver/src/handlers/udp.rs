@@ -2,42 +2,27 @@ use std::sync::Arc;
 use tokio::net::UdpSocket;
 use log::{info, warn, debug};
 use crate::state::server_state::ServerState;
+use crate::state::session_binding::SourceCheck;
 use crate::state::commands::LobbyCommand;
+use crate::net::binary::{self, BinaryPacket};
+use crate::net::quantize::{self, POSITION_SCALE, ROTATION_SCALE};
+use crate::net::protocol::{self, ClientProtocol};
+use crate::net::messages::{MessageCode, Notice};
+use crate::net::packets::{Envelope, InboundPacket, OutboundPacket, Vec3};
+use crate::net::reliable;
+use crate::net::crypto;
+use crate::state::lobby::Player;
 use crate::utils::weapondb::WeaponDb;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use crate::domain::clock;
+use crate::domain::owner::OwnerAction;
+use crate::domain::referee::RefereeAction;
 
 const MAX_PACKET_SIZE: usize = 1024;
-const RATE_LIMIT_WINDOW_MS: u64 = 1000;
-const MAX_PACKETS_PER_WINDOW: u64 = 100;
 
-struct RateLimiter {
-    packet_counts: HashMap<std::net::SocketAddr, AtomicU64>,
-}
-
-impl RateLimiter {
-    fn new() -> Self {
-        Self {
-            packet_counts: HashMap::new(),
-        }
-    }
-
-    fn check_rate_limit(&mut self, addr: &std::net::SocketAddr) -> bool {
-        let count = self.packet_counts
-            .entry(addr.clone())
-            .or_insert_with(|| AtomicU64::new(0));
-        
-        let current = count.fetch_add(1, Ordering::Relaxed);
-        current < MAX_PACKETS_PER_WINDOW
-    }
-
-    fn cleanup(&mut self) {
-    }
-}
-
-async fn send_packet(socket: &UdpSocket, addr: &std::net::SocketAddr, packet: &serde_json::Value) {
+async fn send_packet(socket: &UdpSocket, game_server: &ServerState, addr: &std::net::SocketAddr, packet: &OutboundPacket) {
     if let Ok(data) = serde_json::to_vec(packet) {
-        if let Err(e) = socket.send_to(&data, addr).await {
+        let data = game_server.seal_outbound(addr, &data).unwrap_or(data);
+        if let Err(e) = game_server.send_datagram(socket, *addr, &data).await {
             debug!("Failed to send packet to {}: {}", addr, e);
         }
     }
@@ -55,64 +40,372 @@ async fn broadcast_packet(socket: &UdpSocket, addresses: &[(u32, std::net::Socke
     }
 }
 
+/// Player handle a packet refers to: the public `player_uuid`, or the
+/// numeric `player_id` (clients from before UUIDs)
+fn packet_player_id(game_server: &ServerState, envelope: &Envelope) -> Option<u32> {
+    match &envelope.player_uuid {
+        Some(uuid) => game_server.player_directory.resolve(uuid),
+        None => envelope.player_id,
+    }
+}
+
+/// Apply the session binding rules to a packet's source address
+/// Returns false when the packet must be dropped
+async fn authorize_source(
+    socket: &UdpSocket,
+    game_server: &ServerState,
+    player_id: u32,
+    addr: std::net::SocketAddr,
+    token: Option<&str>,
+    encrypted: bool,
+) -> bool {
+    match game_server.authorize_source(player_id, addr, token, encrypted) {
+        Ok(SourceCheck::Rebind { previous }) => {
+            warn!("REBIND: player {} moved from {} to {}", player_id, previous, addr);
+            send_packet(socket, game_server, &previous, &OutboundPacket::SessionMoved { player_id }).await;
+            true
+        }
+        Ok(_) => true,
+        Err(e) => {
+            warn!("REBIND REJECTED: packet for player {} from {}: {}", player_id, addr, e);
+            false
+        }
+    }
+}
+
+/// Feed a command's `client_time` to the player's clock estimate
+/// Returns false when a v3 client left it out (the command is dropped)
+async fn observe_client_time(
+    client_time: Option<u64>,
+    addr: std::net::SocketAddr,
+    game_server: &ServerState,
+    player_id: u32,
+) -> bool {
+    let Some(client_ms) = client_time else {
+        if game_server.client_protocol(&addr).requires_timestamps() {
+            debug!("Dropping untimestamped command from player {} at {}", player_id, addr);
+            return false;
+        }
+        return true;
+    };
+    if let Some(lobby_code) = game_server.find_lobby_by_player(player_id).await {
+        if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+            let server_ms = clock::server_ms(std::time::Instant::now());
+            if let Err(e) = command_tx.send(LobbyCommand::ClockSample { player_id, client_ms, server_ms }).await {
+                warn!("Failed to send clock sample: {}", e);
+            }
+        }
+    }
+    true
+}
+
+/// The lobby a datagram is for: its sender's lobby, or the one a join names
+/// None when the datagram names no known player
+pub async fn datagram_lobby(data: &[u8], game_server: &ServerState) -> Option<String> {
+    let player_id = if crypto::is_encrypted(data) {
+        crypto::peek_player_id(data)?
+    } else if binary::is_binary(data) {
+        binary::decode(data).ok()?.player_id()
+    } else {
+        let envelope = Envelope::parse(data).ok()?;
+        if let InboundPacket::Join { lobby_code, .. } = envelope.packet {
+            return Some(lobby_code);
+        }
+        packet_player_id(game_server, &envelope)?
+    };
+    game_server.find_lobby_by_player(player_id).await
+}
+
+/// Dispatch one datagram (or WebSocket message) by its format: encrypted, binary or JSON
+pub async fn handle_datagram(
+    data: &[u8],
+    addr: std::net::SocketAddr,
+    socket: &UdpSocket,
+    game_server: &Arc<ServerState>,
+    weapons: &Arc<WeaponDb>,
+) {
+    if crypto::is_encrypted(data) {
+        handle_encrypted_packet(data, addr, socket, game_server, weapons).await;
+    } else if binary::is_binary(data) {
+        handle_binary_packet(data, addr, socket, game_server).await;
+    } else {
+        match Envelope::parse(data) {
+            Ok(envelope) => handle_udp_packet(envelope, addr, socket, game_server, weapons).await,
+            Err(e) => debug!("Dropping malformed packet from {}: {}", addr, e),
+        }
+    }
+}
+
 pub async fn handle_udp_packet(
-    packet: serde_json::Value,
+    envelope: Envelope,
     addr: std::net::SocketAddr,
     socket: &UdpSocket,
     game_server: &Arc<ServerState>,
     weapons: &Arc<WeaponDb>,
 ) {
-    let packet_type = packet.get("type").and_then(|v| v.as_str());
-    
-    debug!("UDP packet from {}: type={}", addr, packet_type.unwrap_or("unknown"));
+    debug!("UDP packet from {}: {:?}", addr, envelope.packet);
+
+    // Reliable client packets (join, leave...) are acked straight away; resends whose
+    // ack was lost are acked again but not handled twice
+    if envelope.reliable {
+        if let Some(seq) = envelope.seq {
+            let (fresh, (ack, ack_bits)) = game_server.receive_reliable(addr, seq);
+            send_packet(socket, game_server, &addr, &OutboundPacket::Ack { ack, ack_bits }).await;
+            if !fresh {
+                debug!("Dropping resent packet {} from {}", seq, addr);
+                return;
+            }
+        }
+    }
 
-    match packet_type {
-        Some("join") => {
-            handle_join_packet(&packet, addr, socket, game_server).await;
+    let player_id = packet_player_id(game_server, &envelope);
+    if let Some(player_id) = player_id {
+        if !authorize_source(socket, game_server, player_id, addr, envelope.session_token.as_deref(), false).await {
+            return;
+        }
+        // Acks are not commands and carry no client time
+        if envelope.packet.is_command() && !observe_client_time(envelope.client_time, addr, game_server, player_id).await {
+            return;
+        }
+    }
+
+    match envelope.packet {
+        InboundPacket::Join { lobby_code, player_name, protocol_version, min_protocol_version, capabilities } => {
+            let player_name = player_name.as_deref().unwrap_or("Unknown");
+            let versions = (protocol_version, min_protocol_version);
+            handle_join_packet(player_id, &lobby_code, player_name, versions, capabilities, addr, socket, game_server).await;
+        }
+        InboundPacket::Reconnect => {
+            handle_reconnect_packet(player_id, envelope.session_token.as_deref(), addr, socket, game_server).await;
+        }
+        InboundPacket::Leave => {
+            handle_leave_packet(player_id, addr, game_server).await;
+        }
+        InboundPacket::PositionUpdate { position, rotation, yaw, pitch } => {
+            handle_position_update_packet(player_id, position, rotation, yaw.map(|yaw| (yaw, pitch)), addr, game_server).await;
+        }
+        InboundPacket::Shoot { origin, direction } => {
+            handle_shoot_packet(player_id, origin, direction, envelope.client_time, game_server, weapons).await;
+        }
+        InboundPacket::Reload => {
+            handle_reload_packet(player_id, game_server).await;
+        }
+        InboundPacket::Throw { origin, direction } => {
+            handle_throw_packet(player_id, origin, direction, game_server).await;
+        }
+        InboundPacket::RequestState => {
+            handle_request_state_packet(player_id, addr, socket, game_server).await;
+        }
+        InboundPacket::WeaponSwitch { weapon_id } => {
+            handle_weapon_switch_packet(player_id, weapon_id, game_server).await;
+        }
+        InboundPacket::SwitchTeam { team_id } => {
+            handle_switch_team_packet(player_id, team_id, game_server).await;
+        }
+        InboundPacket::Ready { ready } => {
+            handle_ready_packet(player_id, ready, game_server).await;
+        }
+        InboundPacket::LoadingComplete => {
+            handle_loading_complete_packet(player_id, game_server).await;
+        }
+        InboundPacket::Chat { text, team_only } => {
+            handle_chat_packet(player_id, text, team_only, game_server).await;
+        }
+        InboundPacket::Voice { payload, proximity } => {
+            handle_voice_packet(player_id, payload, proximity, game_server).await;
         }
-        Some("leave") => {
-            handle_leave_packet(&packet, addr, socket, game_server).await;
+        InboundPacket::MutePlayer { target_id, muted } => {
+            handle_mute_packet(player_id, target_id, muted, game_server).await;
         }
-        Some("position_update") => {
-            handle_position_update_packet(&packet, addr, socket, game_server).await;
+        InboundPacket::OwnerAction(action) => {
+            handle_owner_action_packet(player_id, action, game_server).await;
         }
-        Some("shoot") => {
-            handle_shoot_packet(&packet, addr, socket, game_server, weapons).await;
+        InboundPacket::ChallengeResponse { nonce, signature } => {
+            handle_challenge_response_packet(player_id, nonce, signature, game_server).await;
         }
-        Some("reload") => {
-            handle_reload_packet(&packet, addr, socket, game_server).await;
+        InboundPacket::Referee(action) => {
+            handle_referee_packet(player_id, action, game_server).await;
         }
-        Some("request_state") => {
-            handle_request_state_packet(&packet, addr, socket, game_server).await;
+        InboundPacket::Keepalive => {
+            handle_keepalive_packet(player_id, addr, game_server).await;
         }
-        Some("weapon_switch") => {
-            handle_weapon_switch_packet(&packet, addr, socket, game_server).await;
+        InboundPacket::Ack { seqs, ack, ack_bits, snapshot_tick } => {
+            handle_ack_packet(player_id, seqs, ack.map(|ack| (ack, ack_bits)), snapshot_tick, game_server).await;
         }
-        Some("keepalive") => {
-            handle_keepalive_packet(&packet, addr, socket, game_server).await;
+    }
+}
+
+/// Handle an encrypted datagram: authenticate it with the sender's session key,
+/// then dispatch the plaintext like any other packet
+/// Packets claiming a different player id than the session are dropped
+pub async fn handle_encrypted_packet(
+    data: &[u8],
+    addr: std::net::SocketAddr,
+    socket: &UdpSocket,
+    game_server: &Arc<ServerState>,
+    weapons: &Arc<WeaponDb>,
+) {
+    let (session_player, plaintext) = match game_server.open_encrypted(addr, data) {
+        Ok(opened) => opened,
+        Err(e) => {
+            debug!("Dropping encrypted packet from {}: {}", addr, e);
+            return;
+        }
+    };
+
+    // The session key proves identity, so encrypted packets may rebind the address
+    if !authorize_source(socket, game_server, session_player, addr, None, true).await {
+        return;
+    }
+
+    if binary::is_binary(&plaintext) {
+        let claimed = match binary::decode(&plaintext) {
+            Ok(packet) => packet.player_id(),
+            Err(_) => return,
+        };
+        if claimed != session_player {
+            warn!("Encrypted packet from {} claims player {} but session is {}", addr, claimed, session_player);
+            return;
+        }
+        handle_binary_packet(&plaintext, addr, socket, game_server).await;
+    } else {
+        let envelope = match Envelope::parse(&plaintext) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                debug!("Dropping malformed encrypted packet from {}: {}", addr, e);
+                return;
+            }
+        };
+        let claimed = packet_player_id(game_server, &envelope);
+        if claimed != Some(session_player) {
+            warn!("Encrypted packet from {} claims player {:?} but session is {}", addr, claimed, session_player);
+            return;
         }
-        _ => {
-            debug!("Unknown packet type: {:?}", packet_type);
+        handle_udp_packet(envelope, addr, socket, game_server, weapons).await;
+    }
+}
+
+/// Handle a binary-encoded packet (quantized positions)
+/// Converts back to floats at the boundary and feeds the same command queue as JSON
+pub async fn handle_binary_packet(
+    data: &[u8],
+    addr: std::net::SocketAddr,
+    socket: &UdpSocket,
+    game_server: &Arc<ServerState>,
+) {
+    let packet = match binary::decode(data) {
+        Ok(packet) => packet,
+        Err(e) => {
+            debug!("Dropping binary packet from {}: {}", addr, e);
+            return;
+        }
+    };
+    if matches!(packet, BinaryPacket::Positions(_)) {
+        debug!("Dropping server-only positions packet from {}", addr);
+        return;
+    }
+
+    // Binary packets carry no token: they can't move a session to a new address
+    let player_id = packet.player_id();
+    if !authorize_source(socket, game_server, player_id, addr, None, false).await {
+        return;
+    }
+
+    game_server.mark_binary_client(addr);
+
+    let (position, rotation) = match packet {
+        BinaryPacket::PositionUpdate { position, rotation, .. } => (position.to_f32(POSITION_SCALE), rotation.to_f32(ROTATION_SCALE)),
+        BinaryPacket::PositionUpdateYawPitch { position, yaw, pitch, .. } => (
+            position.to_f32(POSITION_SCALE),
+            Player::rotation_from_yaw_pitch(quantize::dequantize_angle(yaw), quantize::dequantize_angle(pitch)),
+        ),
+        // Height is filled in by the lobby (2D lobbies ignore it)
+        BinaryPacket::PositionUpdate2d { x, z, yaw, .. } => (
+            (x as f32 / POSITION_SCALE, 0.0, z as f32 / POSITION_SCALE),
+            Player::rotation_from_yaw_pitch(quantize::dequantize_angle(yaw), 0.0),
+        ),
+        BinaryPacket::Positions(_) => return,
+    };
+
+    let Some(lobby_code) = game_server.find_lobby_by_player(player_id).await else {
+        warn!("No lobby found for player {}", player_id);
+        return;
+    };
+    if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+        let cmd = LobbyCommand::PositionUpdate { player_id, position, rotation, addr };
+        if let Err(e) = command_tx.send(cmd).await {
+            warn!("Failed to send position update: {}", e);
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_join_packet(
-    packet: &serde_json::Value,
+    player_id: Option<u32>,
+    code: &str,
+    player_name: &str,
+    (requested_version, oldest_version): (Option<u32>, Option<u32>),
+    requested_capabilities: Option<u32>,
     addr: std::net::SocketAddr,
     socket: &UdpSocket,
     game_server: &Arc<ServerState>,
 ) {
-    let lobby_code = packet.get("lobby_code").and_then(|v| v.as_str());
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
-    let player_name = packet.get("player_name").and_then(|v| v.as_str()).unwrap_or("Unknown");
+    info!("UDP JOIN: Player {:?} ({}) attempting to join lobby {} from {:?}", player_id, player_name, code, addr);
 
-    info!("UDP JOIN: Player {:?} ({}) attempting to join lobby {:?} from {:?}", player_id, player_name, lobby_code, addr);
+    if let Some(pid) = player_id {
+        // Spectators who only know the public spectate code connect with it
+        let command_tx = game_server
+            .get_lobby_tx(code)
+            .or_else(|| game_server.resolve_spectate_code(code).and_then(|code| game_server.get_lobby_tx(&code)));
+        if let Some(command_tx) = command_tx {
+            if game_server.bans.is_banned(&addr.ip(), std::time::Instant::now()) {
+                warn!("Refusing UDP join for player {} from {}: banned", pid, addr);
+                let notice = Notice::new(MessageCode::Banned, serde_json::Value::Null, game_server.message_text());
+                send_packet(socket, game_server, &addr, &OutboundPacket::Error { notice }).await;
+                return;
+            }
+            let Some(protocol_version) = ClientProtocol::negotiate(requested_version, oldest_version) else {
+                warn!(
+                    "Refusing UDP join for player {} from {}: speaks protocol v{:?} to v{:?}, server v{} to v{}",
+                    pid,
+                    addr,
+                    oldest_version,
+                    requested_version,
+                    protocol::MIN_PROTOCOL_VERSION,
+                    protocol::CURRENT_PROTOCOL_VERSION
+                );
+                let params = serde_json::json!({
+                    "min_version": protocol::MIN_PROTOCOL_VERSION,
+                    "max_version": protocol::CURRENT_PROTOCOL_VERSION
+                });
+                let notice = Notice::new(MessageCode::ProtocolUnsupported, params, game_server.message_text());
+                send_packet(socket, game_server, &addr, &OutboundPacket::Error { notice }).await;
+                return;
+            };
+            if let Err(e) = game_server.ip_sessions.try_acquire(addr.ip(), pid) {
+                warn!(
+                    "Refusing UDP join for player {} from {}: {} ({} active, {} refused in total)",
+                    pid,
+                    addr,
+                    e,
+                    game_server.ip_sessions.session_count(&addr.ip()),
+                    game_server.ip_sessions.rejections()
+                );
+                let notice = Notice::new(MessageCode::TooManySessions, serde_json::Value::Null, game_server.message_text());
+                send_packet(socket, game_server, &addr, &OutboundPacket::Error { notice }).await;
+                return;
+            }
 
-    if let (Some(code), Some(pid)) = (lobby_code, player_id) {
-        let pid = pid as u32;
+            game_server.set_protocol_version(addr, protocol_version);
+            let capabilities = ClientProtocol::negotiate_capabilities(requested_capabilities);
+            game_server.set_capabilities(addr, capabilities);
+            debug!(
+                "Player {} capabilities: requested {:?}, granted {:?}",
+                pid,
+                protocol::capability_names(requested_capabilities.unwrap_or(0)),
+                protocol::capability_names(capabilities)
+            );
 
-        if let Some(command_tx) = game_server.get_lobby_tx(code) {
             let cmd = LobbyCommand::UdpConnect {
                 player_id: pid,
                 name: player_name.to_string(),
@@ -123,39 +416,32 @@ async fn handle_join_packet(
                 warn!("Failed to send UDP connect command: {}", e);
             }
 
-            let response = serde_json::json!({
-                "type": "welcome",
-                "message": "Connected to lobby",
-                "player_id": pid,
-                "lobby_code": code
-            });
+            let response = OutboundPacket::Welcome {
+                player_id: pid,
+                player_uuid: game_server.player_directory.uuid(pid).map(|uuid| uuid.to_string()),
+                lobby_code: code.to_string(),
+                protocol_version,
+                min_protocol_version: protocol::MIN_PROTOCOL_VERSION,
+                max_protocol_version: protocol::CURRENT_PROTOCOL_VERSION,
+                capabilities,
+                features: protocol::capability_names(capabilities),
+                notice: Notice::new(MessageCode::Connected, serde_json::json!({"lobby_code": code}), game_server.message_text()),
+            };
 
-            send_packet(socket, &addr, &response).await;
+            send_packet(socket, game_server, &addr, &response).await;
             info!("Player {} ({}) successfully joined lobby {}", pid, player_name, code);
         } else {
-            let error_response = serde_json::json!({
-                "type": "error",
-                "message": "Lobby not found"
-            });
-            send_packet(socket, &addr, &error_response).await;
+            let notice = Notice::new(MessageCode::LobbyNotFound, serde_json::json!({"lobby_code": code}), game_server.message_text());
+            send_packet(socket, game_server, &addr, &OutboundPacket::Error { notice }).await;
             warn!("Lobby {} not found during UDP join", code);
         }
     }
 }
 
-async fn handle_leave_packet(
-    packet: &serde_json::Value,
-    _addr: std::net::SocketAddr,
-    _socket: &UdpSocket,
-    game_server: &Arc<ServerState>,
-) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
-
-    info!("UDP LEAVE: Player {:?} leaving from {:?}", player_id, _addr);
+async fn handle_leave_packet(player_id: Option<u32>, addr: std::net::SocketAddr, game_server: &Arc<ServerState>) {
+    info!("UDP LEAVE: Player {:?} leaving from {:?}", player_id, addr);
 
     if let Some(pid) = player_id {
-        let pid = pid as u32;
-
         if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
             if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
                 let cmd = LobbyCommand::PlayerLeave { player_id: pid };
@@ -168,29 +454,23 @@ async fn handle_leave_packet(
 }
 
 async fn handle_position_update_packet(
-    packet: &serde_json::Value,
+    player_id: Option<u32>,
+    position: Vec3,
+    rotation: Option<Vec3>,
+    yaw_pitch: Option<(f32, Option<f32>)>,
     addr: std::net::SocketAddr,
-    _socket: &UdpSocket,
     game_server: &Arc<ServerState>,
 ) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
-    let pos_data = packet.get("position");
-    let rot_data = packet.get("rotation");
-
-    // debug!("Received position update from {}: {:?}", addr, packet);
-
-    if let (Some(pid), Some(pos)) = (player_id, pos_data) {
-        let pid = pid as u32;
+    // debug!("Received position update from {}: {:?}", addr, position);
 
-        let x = pos.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
-        let y = pos.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
-        let z = pos.get("z").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
+    if let Some(pid) = player_id {
+        let (x, y, z) = position.to_tuple();
 
-        let (rx, ry, rz) = if let Some(rot) = rot_data {
-            let rx = rot.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
-            let ry = rot.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
-            let rz = rot.get("z").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
-            (rx, ry, rz)
+        let (rx, ry, rz) = if let Some((yaw, pitch)) = yaw_pitch {
+            // Compact v2 rotation
+            Player::rotation_from_yaw_pitch(yaw, pitch.unwrap_or(0.0))
+        } else if let Some(rotation) = rotation {
+            rotation.to_tuple()
         } else {
             (0.0, 0.0, 0.0)
         };
@@ -217,27 +497,25 @@ async fn handle_position_update_packet(
     }
 }
 
+/// Shots carry where they were fired from and where they were aimed; the server finds the target
 async fn handle_shoot_packet(
-    packet: &serde_json::Value,
-    _addr: std::net::SocketAddr,
-    _socket: &UdpSocket,
+    player_id: Option<u32>,
+    origin: Vec3,
+    direction: Vec3,
+    client_time: Option<u64>,
     _game_server: &Arc<ServerState>,
     _weapons: &Arc<WeaponDb>,
 ) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
-    let target_id = packet.get("target_id").and_then(|v| v.as_u64());
-
-    info!("UDP SHOOT: Player {:?} shooting at target {:?}", player_id, target_id);
-
-    if let (Some(pid), Some(tid)) = (player_id, target_id) {
-        let pid = pid as u32;
-        let tid = tid as u32;
+    debug!("UDP SHOOT: Player {:?} from {:?} towards {:?}", player_id, origin, direction);
 
+    if let Some(pid) = player_id {
         if let Some(lobby_code) = _game_server.find_lobby_by_player(pid).await {
             if let Some(command_tx) = _game_server.get_lobby_tx(&lobby_code) {
                 let cmd = LobbyCommand::Shoot {
                     player_id: pid,
-                    target_id: tid,
+                    origin: origin.to_tuple(),
+                    direction: direction.to_tuple(),
+                    client_time,
                 };
                 if let Err(e) = command_tx.send(cmd).await {
                     warn!("Failed to send shoot command: {}", e);
@@ -247,18 +525,10 @@ async fn handle_shoot_packet(
     }
 }
 
-async fn handle_reload_packet(
-    packet: &serde_json::Value,
-    _addr: std::net::SocketAddr,
-    _socket: &UdpSocket,
-    game_server: &Arc<ServerState>,
-) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
-
+async fn handle_reload_packet(player_id: Option<u32>, game_server: &Arc<ServerState>) {
     info!("UDP RELOAD: Player {:?} reloading", player_id);
 
     if let Some(pid) = player_id {
-        let pid = pid as u32;
 
         if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
             if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
@@ -271,58 +541,88 @@ async fn handle_reload_packet(
     }
 }
 
-async fn handle_request_state_packet(
-    packet: &serde_json::Value,
+async fn handle_throw_packet(player_id: Option<u32>, origin: Vec3, direction: Vec3, game_server: &Arc<ServerState>) {
+    debug!("UDP THROW: Player {:?} from {:?} towards {:?}", player_id, origin, direction);
+
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                let cmd = LobbyCommand::Throw { player_id: pid, origin: origin.to_tuple(), direction: direction.to_tuple() };
+                if let Err(e) = command_tx.send(cmd).await {
+                    warn!("Failed to send throw command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Reclaim a held slot: the tick loop restores the player and resends the lobby state
+async fn handle_reconnect_packet(
+    player_id: Option<u32>,
+    token: Option<&str>,
     addr: std::net::SocketAddr,
     socket: &UdpSocket,
     game_server: &Arc<ServerState>,
 ) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
 
+    info!("UDP RECONNECT: Player {:?} reconnecting from {}", player_id, addr);
+
+    // Sessions are released when the player is removed, so an expired window fails here
+    if let (Some(pid), Some(token)) = (player_id, token) {
+        if game_server.verify_session_token(pid, token) {
+            if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+                if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                    if let Err(e) = command_tx.send(LobbyCommand::Reconnect { player_id: pid, addr }).await {
+                        warn!("Failed to send reconnect command: {}", e);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    let notice = Notice::new(MessageCode::ReconnectFailed, serde_json::Value::Null, game_server.message_text());
+    send_packet(socket, game_server, &addr, &OutboundPacket::Error { notice }).await;
+}
+
+async fn handle_request_state_packet(
+    player_id: Option<u32>,
+    addr: std::net::SocketAddr,
+    socket: &UdpSocket,
+    game_server: &Arc<ServerState>,
+) {
     info!("UDP REQUEST STATE: Player {:?} requesting state", player_id);
 
     if let Some(pid) = player_id {
-        let pid = pid as u32;
 
         if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
             if let Some(lobby_handle) = game_server.get_lobby_handle(&lobby_code) {
                 let lobby = lobby_handle.read().await;
 
                 if let Some(player) = lobby.players.get(&pid) {
-                    let state_packet = serde_json::json!({
-                        "type": "player_state_update",
-                        "player_id": pid,
-                        "health": player.current_health,
-                        "max_health": player.max_health,
-                        "ammo": player.current_ammo,
-                        "max_ammo": player.max_ammo,
-                        "is_reloading": player.is_reloading,
-                        "weapon_id": player.current_weapon_id,
-                        "lobby_code": lobby_code,
-                        "lobby_players": lobby.players.len()
-                    });
-
-                    send_packet(socket, &addr, &state_packet).await;
+                    let state_packet = OutboundPacket::PlayerStateUpdate {
+                        player_id: pid,
+                        health: player.current_health,
+                        max_health: player.max_health,
+                        ammo: player.current_ammo,
+                        max_ammo: player.max_ammo,
+                        is_reloading: player.is_reloading,
+                        weapon_id: player.current_weapon_id,
+                        lobby_code: lobby_code.clone(),
+                        lobby_players: lobby.players.len(),
+                    };
+
+                    send_packet(socket, game_server, &addr, &state_packet).await;
                 }
             }
         }
     }
 }
 
-async fn handle_weapon_switch_packet(
-    packet: &serde_json::Value,
-    _addr: std::net::SocketAddr,
-    _socket: &UdpSocket,
-    game_server: &Arc<ServerState>,
-) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
-    let weapon_id = packet.get("weapon_id").and_then(|v| v.as_u64());
-
-    info!("UDP WEAPON SWITCH: Player {:?} switching to weapon {:?}", player_id, weapon_id);
+async fn handle_weapon_switch_packet(player_id: Option<u32>, wid: u32, game_server: &Arc<ServerState>) {
+    info!("UDP WEAPON SWITCH: Player {:?} switching to weapon {}", player_id, wid);
 
-    if let (Some(pid), Some(wid)) = (player_id, weapon_id) {
-        let pid = pid as u32;
-        let wid = wid as u32;
+    if let Some(pid) = player_id {
 
         if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
             if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
@@ -338,22 +638,132 @@ async fn handle_weapon_switch_packet(
     }
 }
 
-async fn handle_keepalive_packet(
-    packet: &serde_json::Value,
-    _addr: std::net::SocketAddr,
-    _socket: &UdpSocket,
-    game_server: &Arc<ServerState>,
-) {
-    let player_id = packet.get("player_id").and_then(|v| v.as_u64());
+async fn handle_switch_team_packet(player_id: Option<u32>, tid: u32, game_server: &Arc<ServerState>) {
+    info!("UDP SWITCH TEAM: Player {:?} switching to team {}", player_id, tid);
 
     if let Some(pid) = player_id {
-        let pid = pid as u32;
+
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                let cmd = LobbyCommand::SwitchTeam {
+                    player_id: pid,
+                    team_id: tid,
+                };
+                if let Err(e) = command_tx.send(cmd).await {
+                    warn!("Failed to send team switch command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_ready_packet(player_id: Option<u32>, ready: bool, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                if let Err(e) = command_tx.send(LobbyCommand::Ready { player_id: pid, ready }).await {
+                    warn!("Failed to send ready command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_loading_complete_packet(player_id: Option<u32>, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                if let Err(e) = command_tx.send(LobbyCommand::LoadingComplete { player_id: pid }).await {
+                    warn!("Failed to send loading complete command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_chat_packet(player_id: Option<u32>, text: String, team_only: bool, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                let cmd = LobbyCommand::Chat { player_id: pid, text, team_only };
+                if let Err(e) = command_tx.send(cmd).await {
+                    warn!("Failed to send chat command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_voice_packet(player_id: Option<u32>, payload: String, proximity: bool, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                let cmd = LobbyCommand::Voice { player_id: pid, payload, proximity };
+                // Voice is best effort: drop frames rather than wait on a full queue
+                if let Err(e) = command_tx.try_send(cmd) {
+                    debug!("Dropping voice frame from player {}: {}", pid, e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_mute_packet(player_id: Option<u32>, target_id: u32, muted: bool, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                if let Err(e) = command_tx.send(LobbyCommand::MutePlayer { player_id: pid, target_id, muted }).await {
+                    warn!("Failed to send mute command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_owner_action_packet(player_id: Option<u32>, action: OwnerAction, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                if let Err(e) = command_tx.send(LobbyCommand::OwnerAction { player_id: pid, action }).await {
+                    warn!("Failed to send owner action command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_challenge_response_packet(player_id: Option<u32>, nonce: String, signature: String, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                if let Err(e) = command_tx.send(LobbyCommand::ChallengeResponse { player_id: pid, nonce, signature }).await {
+                    warn!("Failed to send challenge response: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_referee_packet(player_id: Option<u32>, action: RefereeAction, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
+        if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
+            if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+                if let Err(e) = command_tx.send(LobbyCommand::Referee { player_id: pid, action }).await {
+                    warn!("Failed to send referee command: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_keepalive_packet(player_id: Option<u32>, addr: std::net::SocketAddr, game_server: &Arc<ServerState>) {
+    if let Some(pid) = player_id {
 
         if let Some(lobby_code) = game_server.find_lobby_by_player(pid).await {
             if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
                 let cmd = LobbyCommand::Heartbeat {
                     player_id: pid,
-                    addr: _addr,
+                    addr,
                 };
                 if let Err(e) = command_tx.send(cmd).await {
                     warn!("Failed to send heartbeat: {}", e);
@@ -362,3 +772,31 @@ async fn handle_keepalive_packet(
         }
     }
 }
+
+/// Reliable events the client received, by `seq`, and the newest snapshot it applied
+async fn handle_ack_packet(
+    player_id: Option<u32>,
+    mut seqs: Vec<u64>,
+    ack_header: Option<(u64, u32)>,
+    snapshot_tick: Option<u64>,
+    game_server: &Arc<ServerState>,
+) {
+    let Some(player_id) = player_id else {
+        return;
+    };
+    // Ack header form: the newest seq plus a bitfield of the 32 before it
+    if let Some((ack, ack_bits)) = ack_header {
+        seqs.extend(reliable::acked_seqs(ack, ack_bits));
+    }
+    if seqs.is_empty() && snapshot_tick.is_none() {
+        return;
+    }
+
+    if let Some(lobby_code) = game_server.find_lobby_by_player(player_id).await {
+        if let Some(command_tx) = game_server.get_lobby_tx(&lobby_code) {
+            if let Err(e) = command_tx.send(LobbyCommand::Ack { player_id, seqs, snapshot_tick }).await {
+                warn!("Failed to send ack: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, State,
+    },
+    response::Response,
+};
+use tokio::sync::mpsc;
+use crate::handlers::http::AppState;
+use crate::handlers::udp::handle_datagram;
+use crate::net::rate_limit::RateLimiter;
+
+/// Largest message accepted from a browser client (same as a UDP datagram)
+pub const MAX_MESSAGE_LEN: usize = 1024;
+
+/// Packets queued for one browser client; more are dropped like lost datagrams
+const SEND_QUEUE_LEN: usize = 256;
+
+/// Thin HTTP handler: Upgrade to a WebSocket for browser (HTML5) clients
+/// Each message is one packet of the UDP protocol, handled exactly like a datagram from the
+/// connection's address; packets for that address come back as WebSocket messages
+pub async fn websocket_upgrade(
+    State(app_state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade
+        .max_message_size(MAX_MESSAGE_LEN)
+        .max_frame_size(MAX_MESSAGE_LEN)
+        .on_failed_upgrade(move |e| log::debug!("WebSocket upgrade from {} failed: {}", addr, e))
+        .on_upgrade(move |socket| run_connection(socket, addr, app_state))
+}
+
+/// JSON goes out as text, binary and encrypted packets as binary
+fn packet_message(data: Vec<u8>) -> Message {
+    match String::from_utf8(data) {
+        Ok(text) if text.starts_with('{') => Message::Text(text),
+        Ok(text) => Message::Binary(text.into_bytes()),
+        Err(e) => Message::Binary(e.into_bytes()),
+    }
+}
+
+/// Feed a connection's messages to the packet handlers and send it the packets queued for it,
+/// until it closes; the player times out like a silent UDP client
+/// Messages are rate limited like datagrams from a UDP source
+async fn run_connection(mut socket: WebSocket, addr: SocketAddr, app_state: AppState) {
+    log::info!("WebSocket client connected from {}", addr);
+    let (packets_tx, mut packets_rx) = mpsc::channel::<Vec<u8>>(SEND_QUEUE_LEN);
+    app_state.state.register_web_client(addr, packets_tx);
+    let limiter = RateLimiter::new(&app_state.config);
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let data = match message {
+                    Some(Ok(Message::Text(text))) => text.into_bytes(),
+                    Some(Ok(Message::Binary(data))) => data,
+                    // Pings are answered by the WebSocket itself
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(e)) => {
+                        log::debug!("WebSocket client {} dropped: {}", addr, e);
+                        break;
+                    }
+                };
+                if limiter.allow(addr, Instant::now()) {
+                    handle_datagram(&data, addr, &app_state.udp_socket, &app_state.state, &app_state.state.weapons.get()).await;
+                }
+            }
+            Some(packet) = packets_rx.recv() => {
+                if socket.send(packet_message(packet)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    app_state.state.remove_web_client(&addr);
+    app_state.state.forget_client(&addr);
+    log::info!("WebSocket client {} disconnected", addr);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, UdpSocket};
+    use tokio_tungstenite::tungstenite;
+    use crate::net::rate_limit::LoginLimiter;
+    use crate::state::server_state::ServerState;
+    use crate::utils::config::Config;
+
+    type Client = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+    async fn connect(config: Config) -> Client {
+        let app_state = AppState {
+            state: Arc::new(ServerState::new()),
+            login_limiter: Arc::new(LoginLimiter::new(&config)),
+            config: Arc::new(config),
+            udp_socket: Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http_addr = listener.local_addr().unwrap();
+        let app = crate::server::build_router(app_state).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (client, response) = tokio_tungstenite::connect_async(format!("ws://{}/ws", http_addr)).await.unwrap();
+        assert_eq!(response.status(), 101);
+        client
+    }
+
+    async fn send_leave(client: &mut Client, seq: u64) {
+        let leave = format!(r#"{{"type": "leave", "reliable": true, "seq": {}}}"#, seq);
+        client.send(tungstenite::Message::Text(leave)).await.unwrap();
+    }
+
+    async fn recv_ack(client: &mut Client) -> serde_json::Value {
+        let tungstenite::Message::Text(ack) = client.next().await.unwrap().unwrap() else {
+            panic!("expected a text message");
+        };
+        serde_json::from_str(&ack).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_packets_round_trip_over_websocket() {
+        let mut client = connect(Config::default()).await;
+
+        // A reliable packet is acked over the same connection, as text
+        send_leave(&mut client, 1).await;
+        assert_eq!(recv_ack(&mut client).await, serde_json::json!({"type": "ack", "ack": 1, "ack_bits": 0}));
+
+        // Oversized messages close the connection
+        client.send(tungstenite::Message::Binary(vec![0; MAX_MESSAGE_LEN + 1])).await.unwrap();
+        assert!(!matches!(client.next().await, Some(Ok(tungstenite::Message::Text(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_messages_are_rate_limited() {
+        let mut client = connect(Config { udp_rate_limit_pps: 1, udp_rate_limit_burst: 2, ..Config::default() }).await;
+        for seq in 1..=4 {
+            send_leave(&mut client, seq).await;
+        }
+
+        // The burst is handled, the rest dropped like a flooding UDP source's datagrams
+        assert_eq!(recv_ack(&mut client).await["ack"], 1);
+        assert_eq!(recv_ack(&mut client).await["ack"], 2);
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(200), client.next()).await.is_err());
+    }
+}
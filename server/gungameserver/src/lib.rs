@@ -0,0 +1,19 @@
+//! GunGame server core: lobby state, game rules, networking and the HTTP/UDP servers
+//! The `gungameserver` binary is a thin frontend over this crate
+pub mod handlers;
+pub mod state;
+pub mod domain;
+pub mod tick;
+pub mod utils;
+pub mod net;
+pub mod server;
+pub mod simulate;
+pub mod demo;
+pub mod replay;
+pub mod storage;
+mod game_server;
+
+pub use game_server::{GameServer, GameServerBuilder};
+
+#[cfg(test)]
+mod testutil;
@@ -1,95 +1,175 @@
-mod handlers;
-mod state;
-mod domain;
-mod tick;
-mod utils;
-mod server;
-
-use fern;
-use chrono;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::signal;
-use crate::utils::weapondb::WeaponDb;
-use crate::utils::config::Config;
-use crate::state::server_state::ServerState;
+use gungame_core::{demo, replay, server, simulate, utils, GameServerBuilder};
+use gungame_core::utils::weapondb::WeaponDb;
+use gungame_core::utils::config::{Config, Role};
+use gungame_core::utils::config_loader::Flags;
+use gungame_core::state::server_state::ServerState;
 
 static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
 
+/// Ctrl+C, or SIGTERM (what process managers and orchestrators send)
 async fn shutdown_signal() {
-    signal::ctrl_c().await.unwrap();
+    #[cfg(unix)]
+    {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(e) => {
+                log::warn!("SIGTERM handler unavailable: {}", e);
+                let _ = signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = signal::ctrl_c().await;
     SHUTDOWN_REQUESTED.store(true, Ordering::Relaxed);
     log::info!("Shutdown signal received, initiating graceful shutdown...");
 }
 
+/// SIGUSR1 toggles drain mode: no new lobbies or joins, existing matches play out
+#[cfg(unix)]
+async fn drain_signal(state: Arc<ServerState>) {
+    let mut usr1 = match signal::unix::signal(signal::unix::SignalKind::user_defined1()) {
+        Ok(usr1) => usr1,
+        Err(e) => {
+            log::warn!("Drain signal unavailable: {}", e);
+            return;
+        }
+    };
+    while usr1.recv().await.is_some() {
+        let draining = !state.is_draining();
+        state.set_draining(draining);
+        log::info!("Drain mode {}", if draining { "enabled" } else { "disabled" });
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    setup_logging()?;
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("simulate-match") {
+        return simulate_match(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("verify-demo") {
+        return verify_demo(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("replay") {
+        return replay_lobby(&args[2..]);
+    }
+
+    // Server flags: `--config <path>`, `--<setting> <value>`, `--print-config`; `--help` lists them
+    let flags = Flags::parse_args(&args[1..]).unwrap_or_else(|e| e.exit());
+    let print_config = flags.print_config;
+    let config = Config::from_flags(flags, std::env::vars())?;
+    if print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+    utils::logging::setup(&config)?;
     
     log::info!("Starting GunGame Server...");
+
+    // Web tier: no lobbies here, the lobby API is forwarded to the simulation process
+    if config.role == Role::Web {
+        tokio::select! {
+            result = server::start_web_server(Arc::new(config)) => result?,
+            _ = shutdown_signal() => log::info!("Web tier shut down"),
+        }
+        return Ok(());
+    }
     
-    // Load immutable globals (zero contention)
-    let weapons = Arc::new(WeaponDb::load());
-    let config = Arc::new(Config::default());
-    
-    // Create server state (partitioned by lobby)
-    let state = Arc::new(ServerState::new());
-    
-    // Create UDP socket for lobby tick loops
-    let udp_socket = Arc::new(
-        tokio::net::UdpSocket::bind(format!("0.0.0.0:{}", config.udp_port)).await?
-    );
-    
-    log::info!("UDP socket bound to port {}", config.udp_port);
-    
-    // Create default test lobby
-    server::create_lobby_with_tick(
-        state.clone(),
-        "test".to_string(),
-        8,
-        "test_world".to_string(),
-        weapons.clone(),
-        config.clone(),
-        udp_socket.clone(),
-    ).await?;
-    
-    log::info!("Created test lobby 'test'");
-    
-    // Start HTTP and UDP servers
-    let server_result = server::start_servers(state, weapons, config, udp_socket);
-    
+    // Bind the ports, restore lobbies and start the HTTP and UDP servers
+    let mut server = GameServerBuilder::new(config).start().await?;
+
+    #[cfg(unix)]
+    tokio::spawn(drain_signal(server.state().clone()));
+
     // Wait for shutdown signal
     tokio::select! {
-        result = server_result => {
+        result = server.run() => {
             if let Err(e) = result {
                 log::error!("Server error: {}", e);
-                return Err(e);
+                return Err(e.into());
             }
         }
         _ = shutdown_signal() => {
             log::info!("Shutting down servers...");
-            // The servers will be dropped and their tasks will be cancelled
         }
     }
+
+    server.shutdown().await;
     
     log::info!("Server shutdown complete");
     Ok(())
 }
 
-fn setup_logging() -> Result<(), Box<dyn std::error::Error>> {
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{}[{}][{}] {}",
-                chrono::Utc::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                record.target(),
-                record.level(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Info)
-        .chain(std::io::stdout())
-        .chain(fern::log_file("gungame.log")?)
-        .apply()?;
+/// Weapon definitions from the configured file, else the bundled ones
+fn load_weapons(config: &Config) -> Result<WeaponDb, String> {
+    match &config.weapons_path {
+        Some(path) => WeaponDb::load_file(std::path::Path::new(path)),
+        None => Ok(WeaponDb::load()),
+    }
+}
+
+/// `simulate-match --bots 8 --mode gun_game --ticks 100000 [--record]`
+/// Headless bot-vs-bot match printing balance statistics, with the config file and environment applied
+/// `--record` saves the match as a demo in `demo_dir`
+fn simulate_match(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let record = args.iter().any(|arg| arg == "--record");
+    let args: Vec<String> = args.iter().filter(|arg| *arg != "--record").cloned().collect();
+    let options = simulate::SimulationOptions::from_args(&args)?;
+    let config = Config::load(&[], std::env::vars())?;
+    let weapons = load_weapons(&config)?;
+
+    let report = simulate::run(&options, &weapons, &config)?;
+    report.print(std::time::Duration::from_millis(config.tick_interval_ms()));
+
+    if record {
+        let match_id = uuid::Uuid::new_v4().to_string();
+        let path = demo::demo_path(&config.demo_dir, &match_id).ok_or("Invalid match id")?;
+        demo::Demo::export(&match_id, &options, &report, &config).save(&path)?;
+        println!("Recorded match {} to {}", match_id, path.display());
+    }
+    Ok(())
+}
+
+/// `verify-demo <file>`: re-simulate a demo and check its recorded hashes
+fn verify_demo(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args.first().ok_or("Usage: verify-demo <file>")?;
+    let demo = demo::Demo::load(std::path::Path::new(path))?;
+    demo.verify(&load_weapons(&Config::load(&[], std::env::vars())?)?)?;
+    println!("Demo {} verified: {} ticks, {} checkpoints match", demo.match_id, demo.ticks, demo.checkpoints.len());
+    Ok(())
+}
+
+/// `replay <file> [--until <tick>]`: re-simulate a recorded lobby, printing its kills and final scores
+/// Fails on the first tick whose state doesn't match the recording
+fn replay_lobby(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args.first().ok_or("Usage: replay <file> [--until <tick>]")?;
+    let until = match args.get(1).map(String::as_str) {
+        Some("--until") => Some(args.get(2).and_then(|tick| tick.parse().ok()).ok_or("Invalid --until tick")?),
+        Some(flag) => return Err(format!("Unknown flag {}", flag).into()),
+        None => None,
+    };
+    let replay = replay::Replay::load(std::path::Path::new(path))?;
+    let config = Config::load(&[], std::env::vars())?;
+    let report = replay.run(&load_weapons(&config)?, &config, until)?;
+
+    println!("Lobby {} ({}, {}), recorded at {}", replay.header.lobby_code, replay.header.mode, replay.header.scene, replay.header.started_at);
+    for kill in &report.kills {
+        println!("tick {:>8}  {} killed {} with {}", kill.tick, kill.killer_id, kill.victim_id, kill.weapon_name);
+    }
+    for (id, (name, score, kills, deaths)) in &report.players {
+        println!("player {:>6}  {:<16} score {:>6}  kills {:>4}  deaths {:>4}", id, name, score, kills, deaths);
+    }
+    if let Some(tick) = report.desync_at {
+        return Err(format!("Desync at tick {} ({} checkpoints matched before it)", tick, report.checkpoints).into());
+    }
+    println!("Replayed {} ticks, {} checkpoints match", report.ticks, report.checkpoints);
     Ok(())
 }
@@ -0,0 +1,66 @@
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Claims of an account token (HS256 JWT)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountClaims {
+    pub sub: i64, // Account id
+    pub name: String, // Username at login
+    pub exp: u64, // Expiry, seconds since the Unix epoch
+}
+
+/// Signs and checks account tokens with the server's secret
+pub struct TokenIssuer {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    ttl_secs: u64,
+}
+
+impl TokenIssuer {
+    pub fn new(secret: &[u8], ttl_secs: u64) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            ttl_secs,
+        }
+    }
+
+    /// Token for an account, valid for `ttl_secs` from now
+    pub fn issue(&self, account_id: i64, name: &str) -> Result<String, &'static str> {
+        let claims = AccountClaims { sub: account_id, name: name.to_string(), exp: unix_now() + self.ttl_secs };
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding).map_err(|_| "Failed to sign token")
+    }
+
+    /// Claims of a token this server signed and that hasn't expired
+    pub fn verify(&self, token: &str) -> Result<AccountClaims, &'static str> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        decode::<AccountClaims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|_| "Invalid or expired token")
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_verify_only_with_their_secret() {
+        let issuer = TokenIssuer::new(b"secret", 60);
+        let token = issuer.issue(42, "ada").unwrap();
+        let claims = issuer.verify(&token).unwrap();
+        assert_eq!((claims.sub, claims.name.as_str()), (42, "ada"));
+
+        assert!(TokenIssuer::new(b"other", 60).verify(&token).is_err());
+        assert!(issuer.verify("not.a.token").is_err());
+        let expired = AccountClaims { sub: 42, name: "ada".to_string(), exp: unix_now() - 1 };
+        let expired = encode(&Header::new(Algorithm::HS256), &expired, &EncodingKey::from_secret(b"secret")).unwrap();
+        assert!(issuer.verify(&expired).is_err());
+    }
+}
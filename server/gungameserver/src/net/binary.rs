@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use crate::net::quantize::{self, QuantizedVec3, POSITION_SCALE, ROTATION_SCALE};
+
+/// Leading byte of every binary packet
+/// JSON packets always start with '{' so the two formats never collide
+pub const BINARY_MAGIC: u8 = 0xB1;
+
+/// Leading byte of a binary packet carrying its movement channel sequence number (`CAP_CHANNELS`):
+/// `0xB2 | channel_seq u64 LE | binary packet`
+pub const SEQUENCED_MAGIC: u8 = 0xB2;
+
+/// Compact binary packets (bincode encoded, prefixed with BINARY_MAGIC)
+/// Positions and rotations travel as quantized integers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BinaryPacket {
+    PositionUpdate {
+        player_id: u32,
+        position: QuantizedVec3,
+        rotation: QuantizedVec3,
+    },
+    /// Protocol v2: rotation as yaw/pitch milliradians
+    PositionUpdateYawPitch {
+        player_id: u32,
+        position: QuantizedVec3,
+        yaw: i16,
+        pitch: i16,
+    },
+    /// 2D lobbies: x/z millimeters and yaw only
+    PositionUpdate2d {
+        player_id: u32,
+        x: i32,
+        z: i32,
+        yaw: i16,
+    },
+    /// Several players' position updates coalesced into one datagram
+    Positions(Vec<BinaryPacket>),
+}
+
+impl BinaryPacket {
+    /// Build a position update from float state
+    pub fn position_update(
+        player_id: u32,
+        position: (f32, f32, f32),
+        rotation: (f32, f32, f32),
+    ) -> Self {
+        BinaryPacket::PositionUpdate {
+            player_id,
+            position: QuantizedVec3::from_f32(position, POSITION_SCALE),
+            rotation: QuantizedVec3::from_f32(rotation, ROTATION_SCALE),
+        }
+    }
+
+    /// Build a compact yaw/pitch position update
+    pub fn position_update_yaw_pitch(
+        player_id: u32,
+        position: (f32, f32, f32),
+        yaw: f32,
+        pitch: f32,
+    ) -> Self {
+        BinaryPacket::PositionUpdateYawPitch {
+            player_id,
+            position: QuantizedVec3::from_f32(position, POSITION_SCALE),
+            yaw: quantize::quantize_angle(yaw),
+            pitch: quantize::quantize_angle(pitch),
+        }
+    }
+
+    /// Player the packet is about
+    pub fn player_id(&self) -> u32 {
+        match self {
+            BinaryPacket::PositionUpdate { player_id, .. }
+            | BinaryPacket::PositionUpdateYawPitch { player_id, .. }
+            | BinaryPacket::PositionUpdate2d { player_id, .. } => *player_id,
+            BinaryPacket::Positions(updates) => updates.first().map(BinaryPacket::player_id).unwrap_or_default(),
+        }
+    }
+
+    /// Build a 2D position update (height and pitch dropped)
+    pub fn position_update_2d(player_id: u32, position: (f32, f32, f32), yaw: f32) -> Self {
+        let position = QuantizedVec3::from_f32(position, POSITION_SCALE);
+        BinaryPacket::PositionUpdate2d { player_id, x: position.x, z: position.z, yaw: quantize::quantize_angle(yaw) }
+    }
+}
+
+/// Prefix an encoded binary packet with its channel sequence number
+pub fn with_channel_seq(data: &[u8], channel_seq: u64) -> Vec<u8> {
+    let mut sequenced = Vec::with_capacity(data.len() + 9);
+    sequenced.push(SEQUENCED_MAGIC);
+    sequenced.extend_from_slice(&channel_seq.to_le_bytes());
+    sequenced.extend_from_slice(data);
+    sequenced
+}
+
+/// Check whether a datagram uses the binary format
+pub fn is_binary(data: &[u8]) -> bool {
+    data.first() == Some(&BINARY_MAGIC)
+}
+
+/// Encode a packet with the magic prefix
+pub fn encode(packet: &BinaryPacket) -> Result<Vec<u8>, &'static str> {
+    let body = bincode::serialize(packet).map_err(|_| "Failed to encode binary packet")?;
+    let mut data = Vec::with_capacity(body.len() + 1);
+    data.push(BINARY_MAGIC);
+    data.extend_from_slice(&body);
+    Ok(data)
+}
+
+/// Decode a datagram previously checked with `is_binary`
+pub fn decode(data: &[u8]) -> Result<BinaryPacket, &'static str> {
+    if !is_binary(data) {
+        return Err("Missing binary magic byte");
+    }
+    bincode::deserialize(&data[1..]).map_err(|_| "Malformed binary packet")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let packet = BinaryPacket::position_update(7, (1.5, 2.0, -3.25), (0.0, 1.571, 0.0));
+        let data = encode(&packet).unwrap();
+        assert!(is_binary(&data));
+        assert_eq!(decode(&data).unwrap(), packet);
+    }
+
+    #[test]
+    fn test_yaw_pitch_smaller_than_euler() {
+        let euler = encode(&BinaryPacket::position_update(7, (1.0, 2.0, 3.0), (0.2, 1.5, 0.0))).unwrap();
+        let compact = encode(&BinaryPacket::position_update_yaw_pitch(7, (1.0, 2.0, 3.0), 1.5, 0.2)).unwrap();
+        assert!(compact.len() < euler.len());
+        assert!(matches!(decode(&compact).unwrap(), BinaryPacket::PositionUpdateYawPitch { yaw: 1500, pitch: 200, .. }));
+    }
+
+    #[test]
+    fn test_2d_smaller_than_yaw_pitch() {
+        let compact = encode(&BinaryPacket::position_update_yaw_pitch(7, (1.0, 2.0, 3.0), 1.5, 0.2)).unwrap();
+        let flat = encode(&BinaryPacket::position_update_2d(7, (1.0, 2.0, 3.0), 1.5)).unwrap();
+        assert!(flat.len() < compact.len());
+        assert_eq!(decode(&flat).unwrap(), BinaryPacket::PositionUpdate2d { player_id: 7, x: 1000, z: 3000, yaw: 1500 });
+    }
+
+    #[test]
+    fn test_binary_smaller_than_json() {
+        let packet = BinaryPacket::position_update(7, (123.456, 2.0, -3.25), (0.1, 1.571, 0.0));
+        let data = encode(&packet).unwrap();
+        let json = serde_json::to_vec(&serde_json::json!({
+            "type": "position_update",
+            "player_id": 7,
+            "position": {"x": 123.456, "y": 2.0, "z": -3.25},
+            "rotation": {"x": 0.1, "y": 1.571, "z": 0.0}
+        })).unwrap();
+        assert!(data.len() < json.len() / 2);
+    }
+
+    #[test]
+    fn test_decode_rejects_json_and_garbage() {
+        assert!(decode(b"{\"type\":\"keepalive\"}").is_err());
+        assert!(decode(&[BINARY_MAGIC, 0xFF]).is_err());
+    }
+}
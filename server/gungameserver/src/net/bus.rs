@@ -0,0 +1,331 @@
+use axum::body::{to_bytes, Body};
+use axum::http::{HeaderName, HeaderValue, Method, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tower::ServiceExt;
+
+/// Largest frame either side accepts (request and response bodies included)
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// A web tier request the simulation tier didn't answer in time
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait between attempts to reach the simulation tier
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Requests the web tier may have in flight before callers wait
+const MAX_QUEUED_REQUESTS: usize = 1024;
+
+/// Frame on the internal bus between the web tier and the simulation tier
+/// The web tier sends HTTP requests as commands; the simulation tier runs them through its
+/// lobby API router and sends each response back under the same id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BusFrame {
+    Request { id: u64, method: String, uri: String, headers: Vec<(String, String)>, body: Vec<u8> },
+    Response { id: u64, status: u16, headers: Vec<(String, String)>, body: Vec<u8> },
+}
+
+/// Write one length-prefixed bincode frame
+pub async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), frame: &BusFrame) -> std::io::Result<()> {
+    let data = bincode::serialize(frame).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    writer.write_u32(data.len() as u32).await?;
+    writer.write_all(&data).await?;
+    writer.flush().await
+}
+
+/// Read one frame; None when the peer closed the connection between frames
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> std::io::Result<Option<BusFrame>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Bus frame too large"));
+    }
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data).await?;
+    bincode::deserialize(&data)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Simulation tier: answer web tier requests arriving on `listener` with `router`
+pub async fn serve(listener: TcpListener, router: Router) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                log::info!("Web tier connected to the bus from {}", addr);
+                tokio::spawn(serve_connection(stream, router.clone()));
+            }
+            Err(e) => log::error!("Bus accept error: {}", e),
+        }
+    }
+}
+
+/// Run every request of one web tier connection concurrently, writing responses as they finish
+async fn serve_connection(stream: TcpStream, router: Router) {
+    let (mut reader, mut writer) = stream.into_split();
+    let (responses_tx, mut responses_rx) = mpsc::channel::<BusFrame>(MAX_QUEUED_REQUESTS);
+    let writer_task = tokio::spawn(async move {
+        while let Some(frame) = responses_rx.recv().await {
+            if let Err(e) = write_frame(&mut writer, &frame).await {
+                log::warn!("Bus write failed: {}", e);
+                break;
+            }
+        }
+    });
+
+    loop {
+        match read_frame(&mut reader).await {
+            Ok(Some(BusFrame::Request { id, method, uri, headers, body })) => {
+                let router = router.clone();
+                let responses_tx = responses_tx.clone();
+                tokio::spawn(async move {
+                    let response = dispatch(router, &method, &uri, headers, body).await;
+                    let _ = responses_tx.send(response.into_frame(id)).await;
+                });
+            }
+            Ok(Some(BusFrame::Response { id, .. })) => log::warn!("Unexpected bus response {} from the web tier", id),
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("Bus read failed: {}", e);
+                break;
+            }
+        }
+    }
+    drop(responses_tx);
+    let _ = writer_task.await;
+    log::info!("Web tier disconnected from the bus");
+}
+
+/// Response fields carried back over the bus
+struct BusResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl BusResponse {
+    fn status(status: StatusCode) -> Self {
+        Self { status: status.as_u16(), headers: Vec::new(), body: Vec::new() }
+    }
+
+    fn into_frame(self, id: u64) -> BusFrame {
+        BusFrame::Response { id, status: self.status, headers: self.headers, body: self.body }
+    }
+}
+
+/// Run one forwarded request through the lobby API router
+async fn dispatch(router: Router, method: &str, uri: &str, headers: Vec<(String, String)>, body: Vec<u8>) -> BusResponse {
+    let Ok(method) = Method::from_bytes(method.as_bytes()) else {
+        return BusResponse::status(StatusCode::BAD_REQUEST);
+    };
+    let mut builder = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        builder = builder.header(name, value);
+    }
+    let Ok(request) = builder.body(Body::from(body)) else {
+        return BusResponse::status(StatusCode::BAD_REQUEST);
+    };
+    let Ok(response) = router.oneshot(request).await;
+    let (parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, MAX_FRAME_LEN).await else {
+        return BusResponse::status(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+    BusResponse { status: parts.status.as_u16(), headers: header_pairs(&parts.headers), body: body.to_vec() }
+}
+
+/// Headers that are valid text, as name/value pairs
+fn header_pairs(headers: &axum::http::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| value.to_str().ok().map(|value| (name.to_string(), value.to_string())))
+        .collect()
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<BusResponse>>>>;
+
+/// Web tier end of the bus: one connection to the simulation tier, reopened whenever it drops
+/// Requests in flight when it drops fail with 502
+#[derive(Clone)]
+pub struct BusClient {
+    requests: mpsc::Sender<(BusFrame, oneshot::Sender<BusResponse>)>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl BusClient {
+    /// Start connecting to the simulation tier at `addr`
+    pub fn connect(addr: String) -> Self {
+        let (requests, requests_rx) = mpsc::channel(MAX_QUEUED_REQUESTS);
+        tokio::spawn(run_client(addr, requests_rx));
+        Self { requests, next_id: Arc::new(AtomicU64::new(1)) }
+    }
+
+    /// Forward a client's HTTP request and rebuild the simulation tier's response
+    pub async fn forward(&self, request: Request<Body>) -> Response {
+        let (parts, body) = request.into_parts();
+        let Ok(body) = to_bytes(body, MAX_FRAME_LEN).await else {
+            return status_response(StatusCode::PAYLOAD_TOO_LARGE);
+        };
+        let uri = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/").to_string();
+        let frame = BusFrame::Request {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            method: parts.method.to_string(),
+            uri,
+            headers: header_pairs(&parts.headers),
+            body: body.to_vec(),
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if self.requests.send((frame, reply_tx)).await.is_err() {
+            return status_response(StatusCode::BAD_GATEWAY);
+        }
+        let reply = match tokio::time::timeout(REQUEST_TIMEOUT, reply_rx).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => return status_response(StatusCode::BAD_GATEWAY),
+            Err(_) => return status_response(StatusCode::GATEWAY_TIMEOUT),
+        };
+
+        let mut response = Response::new(Body::from(reply.body));
+        *response.status_mut() = StatusCode::from_u16(reply.status).unwrap_or(StatusCode::BAD_GATEWAY);
+        for (name, value) in reply.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+fn status_response(status: StatusCode) -> Response {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+/// Keep a connection to the simulation tier open and pass requests and responses over it
+async fn run_client(addr: String, mut requests: mpsc::Receiver<(BusFrame, oneshot::Sender<BusResponse>)>) {
+    loop {
+        let stream = match TcpStream::connect(&addr).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("Simulation tier at {} unreachable: {}", addr, e);
+                // Callers get 502 rather than waiting on a connection that isn't there
+                while let Ok((_, reply)) = requests.try_recv() {
+                    drop(reply);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        log::info!("Connected to the simulation tier at {}", addr);
+
+        let pending: Pending = Arc::default();
+        let (mut reader, mut writer) = stream.into_split();
+        let reader_pending = pending.clone();
+        let mut reader_task = tokio::spawn(async move {
+            loop {
+                match read_frame(&mut reader).await {
+                    Ok(Some(BusFrame::Response { id, status, headers, body })) => {
+                        let reply = reader_pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+                        if let Some(reply) = reply {
+                            let _ = reply.send(BusResponse { status, headers, body });
+                        }
+                    }
+                    Ok(Some(BusFrame::Request { id, .. })) => log::warn!("Unexpected bus request {} from the simulation tier", id),
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::warn!("Bus read failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        loop {
+            tokio::select! {
+                request = requests.recv() => {
+                    let Some((frame, reply)) = request else {
+                        reader_task.abort();
+                        return;
+                    };
+                    if let BusFrame::Request { id, .. } = &frame {
+                        pending.lock().unwrap_or_else(|e| e.into_inner()).insert(*id, reply);
+                    }
+                    if let Err(e) = write_frame(&mut writer, &frame).await {
+                        log::warn!("Bus write failed: {}", e);
+                        break;
+                    }
+                }
+                _ = &mut reader_task => break,
+            }
+        }
+        reader_task.abort();
+        // Dropping the reply senders fails their requests with 502
+        pending.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        log::warn!("Lost the simulation tier at {}, reconnecting", addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::{get, post};
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let frame = BusFrame::Request {
+            id: 7,
+            method: "POST".to_string(),
+            uri: "/lobbies?x=1".to_string(),
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: b"{}".to_vec(),
+        };
+        write_frame(&mut client, &frame).await.unwrap();
+        assert_eq!(read_frame(&mut server).await.unwrap(), Some(frame));
+        drop(client);
+        assert_eq!(read_frame(&mut server).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_forwarded_to_the_simulation_tier() {
+        let router = Router::new()
+            .route("/info", get(|| async { "simulation" }))
+            .route("/echo", post(|body: String| async move { (StatusCode::CREATED, [("x-echo", "yes")], body) }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, router));
+
+        let client = BusClient::connect(addr.to_string());
+        let response = client.forward(Request::get("/info").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "simulation");
+
+        let response = client.forward(Request::post("/echo").body(Body::from("hello")).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["x-echo"], "yes");
+        assert_eq!(to_bytes(response.into_body(), usize::MAX).await.unwrap(), "hello");
+
+        let response = client.forward(Request::get("/missing").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_simulation_tier_is_a_bad_gateway() {
+        // Bound then dropped, so nothing listens there
+        let addr = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let client = BusClient::connect(addr.to_string());
+        let response = client.forward(Request::get("/info").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+}
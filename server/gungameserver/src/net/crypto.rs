@@ -0,0 +1,336 @@
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ring::{digest, pbkdf2};
+use std::num::NonZeroU32;
+
+/// Leading byte of an encrypted datagram
+pub const ENCRYPTED_MAGIC: u8 = 0xE1;
+
+/// magic (1) + player_id (4, LE) + counter (8, LE)
+const HEADER_LEN: usize = 13;
+
+/// Number of older counters accepted out of order (UDP reordering)
+const REPLAY_WINDOW: u64 = 64;
+
+/// Who sealed a packet - part of the nonce so both sides never reuse one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer = 0,
+    ServerToClient = 1,
+}
+
+/// Per-session XChaCha20-Poly1305 state
+///
+/// Nonces are `direction || zero padding || counter`, so a key never reuses a
+/// nonce as long as each side increments its counter. The header (magic,
+/// player id, counter) travels in the clear and is authenticated as AAD.
+pub struct SessionCipher {
+    cipher: XChaCha20Poly1305,
+    send_counter: u64,
+    recv_highest: u64,
+    recv_window: u64, // Bit i set = counter (recv_highest - i) already seen
+}
+
+impl std::fmt::Debug for SessionCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCipher")
+            .field("send_counter", &self.send_counter)
+            .field("recv_highest", &self.recv_highest)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionCipher {
+    /// Generate a fresh random session key
+    /// Returns the cipher and the hex-encoded key to hand to the client
+    pub fn generate() -> (Self, String) {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let hex = key.iter().map(|b| format!("{:02x}", b)).collect();
+        (Self::from_key(key.as_slice()), hex)
+    }
+
+    /// Build a cipher from raw key bytes (must be 32 bytes)
+    pub fn from_key(key: &[u8]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new_from_slice(key).expect("session key must be 32 bytes"),
+            send_counter: 0,
+            recv_highest: 0,
+            recv_window: 0,
+        }
+    }
+
+    fn nonce(direction: Direction, counter: u64) -> XNonce {
+        let mut nonce = [0u8; 24];
+        nonce[0] = direction as u8;
+        nonce[16..].copy_from_slice(&counter.to_le_bytes());
+        XNonce::from(nonce)
+    }
+
+    /// Encrypt a plaintext datagram
+    pub fn seal(&mut self, direction: Direction, player_id: u32, plaintext: &[u8]) -> Result<Vec<u8>, &'static str> {
+        self.send_counter += 1;
+        let counter = self.send_counter;
+
+        let mut data = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+        data.push(ENCRYPTED_MAGIC);
+        data.extend_from_slice(&player_id.to_le_bytes());
+        data.extend_from_slice(&counter.to_le_bytes());
+
+        let ciphertext = self
+            .cipher
+            .encrypt(&Self::nonce(direction, counter), Payload { msg: plaintext, aad: &data })
+            .map_err(|_| "Encryption failed")?;
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    /// Decrypt a datagram sealed by the other side, rejecting replays
+    pub fn open(&mut self, direction: Direction, data: &[u8]) -> Result<Vec<u8>, &'static str> {
+        if !is_encrypted(data) || data.len() < HEADER_LEN {
+            return Err("Not an encrypted packet");
+        }
+        let counter = u64::from_le_bytes(data[5..HEADER_LEN].try_into().unwrap());
+        if self.is_replay(counter) {
+            return Err("Replayed or stale packet");
+        }
+
+        let plaintext = self
+            .cipher
+            .decrypt(
+                &Self::nonce(direction, counter),
+                Payload { msg: &data[HEADER_LEN..], aad: &data[..HEADER_LEN] },
+            )
+            .map_err(|_| "Decryption failed")?;
+
+        // Only record the counter once the packet authenticated
+        self.mark_received(counter);
+        Ok(plaintext)
+    }
+
+    fn is_replay(&self, counter: u64) -> bool {
+        if counter == 0 {
+            return true;
+        }
+        if counter > self.recv_highest {
+            return false;
+        }
+        let age = self.recv_highest - counter;
+        age >= REPLAY_WINDOW || self.recv_window & (1 << age) != 0
+    }
+
+    fn mark_received(&mut self, counter: u64) {
+        if counter > self.recv_highest {
+            let shift = counter - self.recv_highest;
+            self.recv_window = if shift >= REPLAY_WINDOW { 0 } else { self.recv_window << shift };
+            self.recv_window |= 1;
+            self.recv_highest = counter;
+        } else {
+            self.recv_window |= 1 << (self.recv_highest - counter);
+        }
+    }
+}
+
+/// Random 128-bit session token as hex (proves a player's identity over plaintext UDP)
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare secrets without leaking the mismatch position through timing
+pub fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// SHA-1, for challenge signatures (no collision resistance needed there)
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut out = [0u8; 20];
+    out.copy_from_slice(digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data).as_ref());
+    out
+}
+
+/// PBKDF2-HMAC-SHA256 rounds for new password hashes; stored with each hash so it can be raised later
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// How a stored password digest was derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Pbkdf2Sha256 { iterations: NonZeroU32 },
+}
+
+/// Salted, stretched password digest; the password itself is never kept
+/// Hashing and verifying are slow on purpose, so keep them off the async runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordHash {
+    algorithm: Algorithm,
+    salt: String,
+    digest: Vec<u8>,
+}
+
+impl PasswordHash {
+    pub fn new(password: &str) -> Self {
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+        let salt = generate_token();
+        let mut digest = vec![0u8; digest::SHA256_OUTPUT_LEN];
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt.as_bytes(), password.as_bytes(), &mut digest);
+        Self { algorithm: Algorithm::Pbkdf2Sha256 { iterations }, salt, digest }
+    }
+
+    /// A fixed hash to check against when there is none stored, so a miss costs as much as a mismatch
+    pub fn dummy() -> Self {
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+        Self { algorithm: Algorithm::Pbkdf2Sha256 { iterations }, salt: "0".repeat(32), digest: vec![0u8; digest::SHA256_OUTPUT_LEN] }
+    }
+
+    /// Whether `password` is the one hashed (constant time)
+    pub fn verify(&self, password: &str) -> bool {
+        match self.algorithm {
+            Algorithm::Pbkdf2Sha256 { iterations } => pbkdf2::verify(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                iterations,
+                self.salt.as_bytes(),
+                password.as_bytes(),
+                &self.digest,
+            )
+            .is_ok(),
+        }
+    }
+
+    /// `new` on the blocking pool, for async callers
+    pub async fn new_blocking(password: String) -> Self {
+        tokio::task::spawn_blocking(move || Self::new(&password)).await.expect("password hashing panicked")
+    }
+
+    /// `verify` on the blocking pool, for async callers
+    pub async fn verify_blocking(self, password: String) -> bool {
+        tokio::task::spawn_blocking(move || self.verify(&password)).await.unwrap_or(false)
+    }
+
+    /// `pbkdf2-sha256$iterations$salt$digest` (hex) for storage
+    pub fn to_stored(&self) -> String {
+        let digest: String = self.digest.iter().map(|b| format!("{:02x}", b)).collect();
+        match self.algorithm {
+            Algorithm::Pbkdf2Sha256 { iterations } => format!("pbkdf2-sha256${}${}${}", iterations, self.salt, digest),
+        }
+    }
+
+    /// Read back `to_stored`
+    pub fn from_stored(stored: &str) -> Option<Self> {
+        let mut parts = stored.strip_prefix("pbkdf2-sha256$")?.splitn(3, '$');
+        let iterations = NonZeroU32::new(parts.next()?.parse().ok()?)?;
+        let (algorithm, salt, hex) = (Algorithm::Pbkdf2Sha256 { iterations }, parts.next()?, parts.next()?);
+        let len = digest::SHA256_OUTPUT_LEN;
+        if hex.len() != len * 2 || !hex.is_ascii() {
+            return None;
+        }
+        let digest = (0..len).map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()).collect::<Option<_>>()?;
+        Some(Self { algorithm, salt: salt.to_string(), digest })
+    }
+}
+
+/// Check whether a datagram is encrypted
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.first() == Some(&ENCRYPTED_MAGIC)
+}
+
+/// Read the cleartext player id from an encrypted datagram (to pick the session key)
+pub fn peek_player_id(data: &[u8]) -> Option<u32> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        return None;
+    }
+    Some(u32::from_le_bytes(data[1..5].try_into().unwrap()))
+}
+
+/// Decode a hex session key as returned by the HTTP join
+#[cfg(test)]
+pub fn decode_key(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() != 64 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> (SessionCipher, SessionCipher) {
+        let (server, key) = SessionCipher::generate();
+        let client = SessionCipher::from_key(&decode_key(&key).unwrap());
+        (server, client)
+    }
+
+    #[test]
+    fn test_roundtrip_both_directions() {
+        let (mut server, mut client) = pair();
+
+        let sealed = client.seal(Direction::ClientToServer, 42, b"{\"type\":\"keepalive\"}").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(peek_player_id(&sealed), Some(42));
+        assert_eq!(server.open(Direction::ClientToServer, &sealed).unwrap(), b"{\"type\":\"keepalive\"}");
+
+        let reply = server.seal(Direction::ServerToClient, 42, b"welcome").unwrap();
+        assert_eq!(client.open(Direction::ServerToClient, &reply).unwrap(), b"welcome");
+    }
+
+    #[test]
+    fn test_rejects_replay_and_allows_reorder() {
+        let (mut server, mut client) = pair();
+        let first = client.seal(Direction::ClientToServer, 1, b"a").unwrap();
+        let second = client.seal(Direction::ClientToServer, 1, b"b").unwrap();
+
+        // Out of order delivery is fine
+        assert!(server.open(Direction::ClientToServer, &second).is_ok());
+        assert!(server.open(Direction::ClientToServer, &first).is_ok());
+
+        // Replays are not
+        assert!(server.open(Direction::ClientToServer, &first).is_err());
+        assert!(server.open(Direction::ClientToServer, &second).is_err());
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_wrong_direction() {
+        let (mut server, mut client) = pair();
+        let mut sealed = client.seal(Direction::ClientToServer, 7, b"shoot").unwrap();
+
+        // Reflected packet (wrong direction) fails authentication
+        assert!(client.open(Direction::ServerToClient, &sealed).is_err());
+
+        // Changing the cleartext player id breaks the AAD
+        sealed[1] = 8;
+        assert!(server.open(Direction::ClientToServer, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_stale_counter_outside_window() {
+        let (mut server, mut client) = pair();
+        let old = client.seal(Direction::ClientToServer, 1, b"old").unwrap();
+        for _ in 0..REPLAY_WINDOW {
+            let newer = client.seal(Direction::ClientToServer, 1, b"new").unwrap();
+            server.open(Direction::ClientToServer, &newer).unwrap();
+        }
+        assert!(server.open(Direction::ClientToServer, &old).is_err());
+    }
+
+    #[test]
+    fn test_password_hash_is_salted() {
+        let hash = PasswordHash::new("hunter2");
+        assert!(hash.verify("hunter2"));
+        assert!(!hash.verify("hunter3"));
+        assert_ne!(hash, PasswordHash::new("hunter2"));
+        assert_eq!(sha1(b"abc")[..4], [0xA9, 0x99, 0x3E, 0x36]);
+
+        let stored = hash.to_stored();
+        assert!(stored.starts_with("pbkdf2-sha256$100000$"));
+        assert_eq!(PasswordHash::from_stored(&stored), Some(hash.clone()));
+        assert_eq!(PasswordHash::from_stored("pbkdf2-sha256$0$salt$00"), None);
+
+        // The dummy costs as many rounds as a real hash
+        assert_eq!(PasswordHash::dummy().algorithm, hash.algorithm);
+        assert!(!PasswordHash::dummy().verify("hunter2"));
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Stable codes for server notices in UDP packets
+/// Clients localize by code; the English text is only sent for debugging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageCode {
+    Connected,
+    LobbyNotFound,
+    TooManySessions,
+    Banned,
+    Kicked,
+    ReconnectFailed,
+    ChatRejected,
+    ProtocolUnsupported,
+}
+
+impl MessageCode {
+    /// Wire code (never rename: clients key translations on it)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageCode::Connected => "OK_CONNECTED",
+            MessageCode::LobbyNotFound => "ERR_LOBBY_NOT_FOUND",
+            MessageCode::TooManySessions => "ERR_TOO_MANY_SESSIONS",
+            MessageCode::Banned => "ERR_BANNED",
+            MessageCode::Kicked => "ERR_KICKED",
+            MessageCode::ReconnectFailed => "ERR_RECONNECT_FAILED",
+            MessageCode::ChatRejected => "ERR_CHAT_REJECTED",
+            MessageCode::ProtocolUnsupported => "ERR_PROTOCOL_UNSUPPORTED",
+        }
+    }
+
+    /// English template; `{name}` is replaced by the `name` parameter
+    fn english(&self) -> &'static str {
+        match self {
+            MessageCode::Connected => "Connected to lobby {lobby_code}",
+            MessageCode::LobbyNotFound => "Lobby {lobby_code} not found",
+            MessageCode::TooManySessions => "Too many sessions from this address",
+            MessageCode::Banned => "Banned from this server",
+            MessageCode::Kicked => "Removed from the lobby: {reason}",
+            MessageCode::ReconnectFailed => "Session expired, join the lobby again",
+            MessageCode::ChatRejected => "Message not sent: {reason}",
+            MessageCode::ProtocolUnsupported => "Client protocol not supported: this server speaks v{min_version} to v{max_version}",
+        }
+    }
+}
+
+/// Render the English text for a code and its parameters
+pub fn english_text(code: MessageCode, params: &Map<String, Value>) -> String {
+    params.iter().fold(code.english().to_string(), |text, (name, value)| {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        text.replace(&format!("{{{}}}", name), &value)
+    })
+}
+
+/// A server notice as packet fields: `code`, `params` and (when enabled) the English `message`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notice {
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub params: Map<String, Value>,
+}
+
+impl Notice {
+    pub fn new(code: MessageCode, params: Value, include_text: bool) -> Self {
+        let params = match params {
+            Value::Object(params) => params,
+            _ => Map::new(),
+        };
+        let message = include_text.then(|| english_text(code, &params));
+        Self { code: code.as_str(), message, params }
+    }
+}
+
+/// Add `code`, `params` and (when enabled) the English `message` to a packet
+pub fn insert_message(packet: &mut Value, code: MessageCode, params: Value, include_text: bool) {
+    let Some(fields) = packet.as_object_mut() else {
+        return;
+    };
+    let notice = Notice::new(code, params, include_text);
+    fields.insert("code".to_string(), Value::from(notice.code));
+    if let Some(message) = notice.message {
+        fields.insert("message".to_string(), Value::from(message));
+    }
+    if !notice.params.is_empty() {
+        fields.insert("params".to_string(), Value::Object(notice.params));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_insert_message_without_text() {
+        let mut packet = json!({"type": "error"});
+        insert_message(&mut packet, MessageCode::LobbyNotFound, json!({"lobby_code": "alpha"}), false);
+        assert_eq!(packet, json!({
+            "type": "error",
+            "code": "ERR_LOBBY_NOT_FOUND",
+            "params": {"lobby_code": "alpha"}
+        }));
+    }
+
+    #[test]
+    fn test_insert_message_with_text() {
+        let mut packet = json!({"type": "error"});
+        insert_message(&mut packet, MessageCode::TooManySessions, Value::Null, true);
+        assert_eq!(packet, json!({
+            "type": "error",
+            "code": "ERR_TOO_MANY_SESSIONS",
+            "message": "Too many sessions from this address"
+        }));
+    }
+
+    #[test]
+    fn test_english_text_fills_params() {
+        let params = json!({"lobby_code": "alpha"});
+        assert_eq!(
+            english_text(MessageCode::Connected, params.as_object().unwrap()),
+            "Connected to lobby alpha"
+        );
+    }
+}
@@ -0,0 +1,12 @@
+pub mod quantize;
+pub mod binary;
+pub mod protocol;
+pub mod crypto;
+pub mod messages;
+pub mod packets;
+pub mod reliable;
+pub mod renet_transport;
+pub mod rate_limit;
+pub mod reuseport;
+pub mod bus;
+pub mod auth;
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use crate::domain::owner::OwnerAction;
+use crate::domain::referee::RefereeAction;
+use crate::net::messages::Notice;
+
+/// `{x, y, z}` vector in a packet
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub fn to_tuple(self) -> (f32, f32, f32) {
+        (self.x, self.y, self.z)
+    }
+}
+
+/// A JSON client packet (UDP datagram or WebSocket message): the fields any packet may carry around its typed body
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Envelope {
+    pub player_uuid: Option<String>, // Public handle; `player_id` is the numeric one from before UUIDs
+    pub player_id: Option<u32>,
+    pub session_token: Option<String>,
+    pub client_time: Option<u64>, // Client clock when the command was issued, required from protocol v3
+    #[serde(default)]
+    pub reliable: bool, // Acked straight away and handled once, by `seq`
+    pub seq: Option<u64>,
+    #[serde(flatten)]
+    pub packet: InboundPacket,
+}
+
+impl Envelope {
+    /// Parse a JSON packet; the error says which type or field was wrong
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(data).map_err(|e| e.to_string())
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Packets clients send, by `type`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InboundPacket {
+    Join {
+        lobby_code: String, // Or the lobby's spectate code
+        player_name: Option<String>,
+        protocol_version: Option<u32>, // Newest version the client speaks
+        min_protocol_version: Option<u32>, // Oldest; the client is refused when the server only speaks older ones
+        capabilities: Option<u32>,
+    },
+    Reconnect,
+    Leave,
+    PositionUpdate {
+        position: Vec3,
+        rotation: Option<Vec3>,
+        yaw: Option<f32>, // Compact v2 rotation, used over `rotation` when present
+        pitch: Option<f32>,
+    },
+    Shoot {
+        origin: Vec3,
+        direction: Vec3,
+    },
+    Reload,
+    Throw {
+        origin: Vec3,
+        direction: Vec3,
+    },
+    RequestState,
+    WeaponSwitch {
+        weapon_id: u32,
+    },
+    SwitchTeam {
+        team_id: u32,
+    },
+    Ready {
+        #[serde(default = "default_true")]
+        ready: bool,
+    },
+    LoadingComplete,
+    Chat {
+        text: String,
+        #[serde(default)]
+        team_only: bool,
+    },
+    Voice {
+        payload: String,
+        #[serde(default)]
+        proximity: bool,
+    },
+    MutePlayer {
+        target_id: u32,
+        #[serde(default = "default_true")]
+        muted: bool,
+    },
+    OwnerAction(OwnerAction),
+    ChallengeResponse {
+        #[serde(default)]
+        nonce: String,
+        #[serde(default)]
+        signature: String,
+    },
+    Referee(RefereeAction),
+    Keepalive,
+    Ack {
+        #[serde(default)]
+        seqs: Vec<u64>,
+        ack: Option<u64>, // Ack header form: the newest seq plus a bitfield of the 32 before it
+        #[serde(default)]
+        ack_bits: u32,
+        snapshot_tick: Option<u64>, // Newest snapshot the client applied
+    },
+}
+
+impl InboundPacket {
+    /// Commands feed the player's clock estimate; these packets aren't commands
+    pub fn is_command(&self) -> bool {
+        !matches!(self, InboundPacket::Join { .. } | InboundPacket::Ack { .. } | InboundPacket::Reconnect)
+    }
+}
+
+/// Packets the packet handlers answer with, by `type`
+/// Notices (`code`, `params`, `message`) are flattened into the packet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutboundPacket {
+    Ack {
+        ack: u64,
+        ack_bits: u32,
+    },
+    SessionMoved {
+        player_id: u32,
+    },
+    Welcome {
+        player_id: u32,
+        player_uuid: Option<String>,
+        lobby_code: String,
+        protocol_version: u32, // Negotiated
+        min_protocol_version: u32, // Versions the server speaks
+        max_protocol_version: u32,
+        capabilities: u32, // Granted
+        features: Vec<&'static str>, // Names of the granted capabilities
+        #[serde(flatten)]
+        notice: Notice,
+    },
+    Error {
+        #[serde(flatten)]
+        notice: Notice,
+    },
+    PlayerStateUpdate {
+        player_id: u32,
+        health: u32,
+        max_health: u32,
+        ammo: u32,
+        max_ammo: u32,
+        is_reloading: bool,
+        weapon_id: u32,
+        lobby_code: String,
+        lobby_players: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::messages::MessageCode;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_envelope_and_body() {
+        let envelope = Envelope::parse(br#"{"type": "shoot", "player_id": 4, "client_time": 90, "reliable": true, "seq": 2,
+            "origin": {"x": 0, "y": 1.5, "z": 0}, "direction": {"x": 0, "y": 0, "z": -1}}"#).unwrap();
+        assert_eq!((envelope.player_id, envelope.client_time, envelope.reliable, envelope.seq), (Some(4), Some(90), true, Some(2)));
+        assert_eq!(
+            envelope.packet,
+            InboundPacket::Shoot { origin: Vec3 { x: 0.0, y: 1.5, z: 0.0 }, direction: Vec3 { x: 0.0, y: 0.0, z: -1.0 } }
+        );
+
+        let ready = Envelope::parse(br#"{"type": "ready", "player_uuid": "abc"}"#).unwrap();
+        assert_eq!(ready.packet, InboundPacket::Ready { ready: true });
+        let kick = Envelope::parse(br#"{"type": "owner_action", "player_id": 1, "action": "kick", "target_id": 2}"#).unwrap();
+        assert_eq!(kick.packet, InboundPacket::OwnerAction(OwnerAction::Kick { target_id: 2 }));
+        let throw = Envelope::parse(br#"{"type": "throw", "player_id": 1, "origin": {"x": 0, "y": 1, "z": 0}, "direction": {"x": 0, "y": 0.5, "z": -1}}"#).unwrap();
+        assert_eq!(throw.packet, InboundPacket::Throw { origin: Vec3 { x: 0.0, y: 1.0, z: 0.0 }, direction: Vec3 { x: 0.0, y: 0.5, z: -1.0 } });
+    }
+
+    #[test]
+    fn test_malformed_packets_say_what_is_wrong() {
+        assert!(Envelope::parse(br#"{"type": "teleport"}"#).unwrap_err().contains("unknown variant `teleport`"));
+        assert!(Envelope::parse(br#"{"type": "weapon_switch", "player_id": 1}"#).unwrap_err().contains("missing field `weapon_id`"));
+        assert!(Envelope::parse(br#"{"player_id": 1}"#).unwrap_err().contains("missing field `type`"));
+        assert!(Envelope::parse(br#"{"type": "chat", "text": 5}"#).is_err());
+    }
+
+    #[test]
+    fn test_outbound_packets_carry_their_type_and_notice() {
+        let error = OutboundPacket::Error { notice: Notice::new(MessageCode::LobbyNotFound, json!({"lobby_code": "alpha"}), false) };
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({"type": "error", "code": "ERR_LOBBY_NOT_FOUND", "params": {"lobby_code": "alpha"}})
+        );
+        assert_eq!(
+            serde_json::to_value(OutboundPacket::Ack { ack: 7, ack_bits: 1 }).unwrap(),
+            json!({"type": "ack", "ack": 7, "ack_bits": 1})
+        );
+    }
+}
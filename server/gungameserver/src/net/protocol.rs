@@ -0,0 +1,202 @@
+/// Original protocol: rotations travel as Euler triples {x, y, z}
+pub const PROTOCOL_V1_EULER: u32 = 1;
+
+/// Compact rotations: yaw/pitch instead of the Euler triple
+pub const PROTOCOL_V2_YAW_PITCH: u32 = 2;
+
+/// Timestamped commands: every JSON command carries `client_time` (client clock, epoch ms)
+pub const PROTOCOL_V3_TIMESTAMPS: u32 = 3;
+
+/// Newest protocol version this server speaks
+pub const CURRENT_PROTOCOL_VERSION: u32 = PROTOCOL_V3_TIMESTAMPS;
+
+/// Oldest protocol version this server still speaks
+pub const MIN_PROTOCOL_VERSION: u32 = PROTOCOL_V1_EULER;
+
+/// Capability flags exchanged in the UDP join handshake
+/// Clients send the bits they understand, the server answers with the subset both support
+pub const CAP_BINARY: u32 = 1 << 0; // Binary position packets
+pub const CAP_COMPRESSION: u32 = 1 << 1; // Compressed payloads
+pub const CAP_ENCRYPTION: u32 = 1 << 2; // Encrypted datagrams (session key from HTTP join)
+pub const CAP_BATCHING: u32 = 1 << 3; // Several messages per datagram
+pub const CAP_REDUCED_RATE: u32 = 1 << 4; // Position updates at a lower rate (weak connections)
+pub const CAP_RELIABLE: u32 = 1 << 5; // Client acks reliable events so they are resent when lost
+pub const CAP_SNAPSHOTS: u32 = 1 << 6; // Player state as snapshot deltas instead of per-field events
+pub const CAP_CHANNELS: u32 = 1 << 7; // Per-channel sequence numbers so stale movement and state packets can be dropped
+pub const CAP_VOICE: u32 = 1 << 8; // Voice chat frames are relayed to the client
+
+/// Capabilities this server implements
+/// Unknown or unimplemented bits requested by a client are never granted
+pub const SERVER_CAPABILITIES: u32 = CAP_BINARY | CAP_ENCRYPTION | CAP_BATCHING | CAP_REDUCED_RATE | CAP_RELIABLE | CAP_SNAPSHOTS | CAP_CHANNELS | CAP_VOICE;
+
+/// Human-readable names of the flags set in `capabilities` (for logs)
+pub fn capability_names(capabilities: u32) -> Vec<&'static str> {
+    [
+        (CAP_BINARY, "binary"),
+        (CAP_COMPRESSION, "compression"),
+        (CAP_ENCRYPTION, "encryption"),
+        (CAP_BATCHING, "batching"),
+        (CAP_REDUCED_RATE, "reduced_rate"),
+        (CAP_RELIABLE, "reliable"),
+        (CAP_SNAPSHOTS, "snapshots"),
+        (CAP_CHANNELS, "channels"),
+        (CAP_VOICE, "voice"),
+    ]
+    .iter()
+    .filter(|(flag, _)| capabilities & flag != 0)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+/// Ordered stream a server packet belongs to, each numbered separately per client
+/// Movement and state packets are superseded by newer ones, so clients drop them when they arrive late;
+/// event numbers only give the order (events must all be applied)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Movement, // Position updates
+    State, // Player state superseded by the next change: health, ammo, reload, snapshots
+    Event, // Everything else: joins, kills, match flow...
+}
+
+impl Channel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Movement => "movement",
+            Channel::State => "state",
+            Channel::Event => "event",
+        }
+    }
+
+    /// Channel of a JSON packet, from its `type`
+    pub fn of_packet(packet: &serde_json::Value) -> Self {
+        match packet.get("type").and_then(|t| t.as_str()) {
+            Some("position_update" | "positions") => Channel::Movement,
+            Some("player_state_update" | "reload_started" | "reload_finished" | "snapshot") => Channel::State,
+            _ => Channel::Event,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Channel::Movement => 0,
+            Channel::State => 1,
+            Channel::Event => 2,
+        }
+    }
+}
+
+/// Next sequence number of each channel for one client (0 = nothing sent yet)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelSequences([u64; 3]);
+
+impl ChannelSequences {
+    pub fn next(&mut self, channel: Channel) -> u64 {
+        let seq = &mut self.0[channel.index()];
+        *seq += 1;
+        *seq
+    }
+}
+
+/// Per-client protocol state negotiated at join time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientProtocol {
+    pub version: u32,
+    pub capabilities: u32,
+}
+
+impl Default for ClientProtocol {
+    fn default() -> Self {
+        Self {
+            version: PROTOCOL_V1_EULER,
+            capabilities: 0,
+        }
+    }
+}
+
+impl ClientProtocol {
+    /// Pick the version to use for a client that speaks `oldest` up to `newest`: the newest both speak
+    /// Clients that don't send a version (old Godot builds) get v1 and ones without `oldest` take any older one;
+    /// None when the client and server have no version in common
+    pub fn negotiate(newest: Option<u32>, oldest: Option<u32>) -> Option<u32> {
+        let version = newest.unwrap_or(PROTOCOL_V1_EULER).min(CURRENT_PROTOCOL_VERSION);
+        (version >= MIN_PROTOCOL_VERSION && version >= oldest.unwrap_or(0)).then_some(version)
+    }
+
+    /// Pick the capabilities to enable for a client that requested `requested`
+    /// Clients that don't send the field (old Godot builds) get none
+    pub fn negotiate_capabilities(requested: Option<u32>) -> u32 {
+        requested.unwrap_or(0) & SERVER_CAPABILITIES
+    }
+
+    /// Check whether a capability is enabled for this client
+    pub fn has(&self, capability: u32) -> bool {
+        self.capabilities & capability != 0
+    }
+
+    /// Whether rotations should be sent as yaw/pitch
+    pub fn uses_yaw_pitch(&self) -> bool {
+        self.version >= PROTOCOL_V2_YAW_PITCH
+    }
+
+    /// Whether commands from this client must carry `client_time`
+    pub fn requires_timestamps(&self) -> bool {
+        self.version >= PROTOCOL_V3_TIMESTAMPS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_version() {
+        assert_eq!(ClientProtocol::negotiate(None, None), Some(PROTOCOL_V1_EULER));
+        assert_eq!(ClientProtocol::negotiate(Some(2), None), Some(PROTOCOL_V2_YAW_PITCH));
+        assert_eq!(ClientProtocol::negotiate(Some(3), Some(1)), Some(PROTOCOL_V3_TIMESTAMPS));
+        assert_eq!(ClientProtocol::negotiate(Some(99), None), Some(CURRENT_PROTOCOL_VERSION));
+        // No version in common
+        assert_eq!(ClientProtocol::negotiate(Some(0), None), None);
+        assert_eq!(ClientProtocol::negotiate(Some(99), Some(CURRENT_PROTOCOL_VERSION + 1)), None);
+    }
+
+    #[test]
+    fn test_default_protocol_is_legacy() {
+        let protocol = ClientProtocol::default();
+        assert!(!protocol.uses_yaw_pitch());
+        assert!(!protocol.requires_timestamps());
+        assert!(!protocol.has(CAP_BINARY));
+        assert!(!protocol.has(CAP_REDUCED_RATE));
+    }
+
+    #[test]
+    fn test_negotiate_capabilities() {
+        assert_eq!(ClientProtocol::negotiate_capabilities(None), 0);
+        assert_eq!(
+            ClientProtocol::negotiate_capabilities(Some(CAP_BINARY | CAP_REDUCED_RATE)),
+            CAP_BINARY | CAP_REDUCED_RATE
+        );
+        // Unimplemented and unknown bits are dropped
+        assert_eq!(ClientProtocol::negotiate_capabilities(Some(CAP_COMPRESSION | 1 << 31)), 0);
+    }
+
+    #[test]
+    fn test_packet_channels() {
+        assert_eq!(Channel::of_packet(&serde_json::json!({"type": "position_update"})), Channel::Movement);
+        assert_eq!(Channel::of_packet(&serde_json::json!({"type": "positions"})), Channel::Movement);
+        assert_eq!(Channel::of_packet(&serde_json::json!({"type": "player_state_update"})), Channel::State);
+        assert_eq!(Channel::of_packet(&serde_json::json!({"type": "snapshot"})), Channel::State);
+        assert_eq!(Channel::of_packet(&serde_json::json!({"type": "player_killed"})), Channel::Event);
+
+        let mut sequences = ChannelSequences::default();
+        assert_eq!(sequences.next(Channel::State), 1);
+        assert_eq!(sequences.next(Channel::State), 2);
+        assert_eq!(sequences.next(Channel::Movement), 1);
+    }
+
+    #[test]
+    fn test_capability_names() {
+        assert_eq!(capability_names(CAP_BINARY | CAP_BATCHING), vec!["binary", "batching"]);
+        assert_eq!(capability_names(CAP_SNAPSHOTS | CAP_VOICE), vec!["snapshots", "voice"]);
+        assert!(capability_names(0).is_empty());
+    }
+}
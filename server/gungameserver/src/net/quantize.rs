@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+/// Fixed-point scale for positions (1 unit = 1 millimeter)
+pub const POSITION_SCALE: f32 = 1000.0;
+
+/// Fixed-point scale for rotations (1 unit = 1 milliradian)
+pub const ROTATION_SCALE: f32 = 1000.0;
+
+/// Quantized 3D vector stored as fixed-point integers
+/// Deterministic across platforms and compact on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct QuantizedVec3 {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl QuantizedVec3 {
+    /// Quantize a float vector at the given scale
+    /// Out-of-range values saturate and NaN becomes 0 (float->int `as` semantics)
+    pub fn from_f32(v: (f32, f32, f32), scale: f32) -> Self {
+        Self {
+            x: (v.0 * scale).round() as i32,
+            y: (v.1 * scale).round() as i32,
+            z: (v.2 * scale).round() as i32,
+        }
+    }
+
+    /// Convert back to floats at the given scale
+    pub fn to_f32(self, scale: f32) -> (f32, f32, f32) {
+        (
+            self.x as f32 / scale,
+            self.y as f32 / scale,
+            self.z as f32 / scale,
+        )
+    }
+}
+
+/// Quantize a position to millimeters
+pub fn quantize_position(position: (f32, f32, f32)) -> QuantizedVec3 {
+    QuantizedVec3::from_f32(position, POSITION_SCALE)
+}
+
+/// Quantize a rotation to milliradians
+pub fn quantize_rotation(rotation: (f32, f32, f32)) -> QuantizedVec3 {
+    QuantizedVec3::from_f32(rotation, ROTATION_SCALE)
+}
+
+/// Quantize an angle to milliradians, wrapped into [-PI, PI] so it fits an i16
+pub fn quantize_angle(radians: f32) -> i16 {
+    let wrapped = if radians.is_finite() {
+        (radians + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI
+    } else {
+        0.0
+    };
+    (wrapped * ROTATION_SCALE).round() as i16
+}
+
+/// Convert a quantized angle back to radians
+pub fn dequantize_angle(value: i16) -> f32 {
+    value as f32 / ROTATION_SCALE
+}
+
+/// Snap a position to the millimeter grid (quantize + dequantize)
+/// Used when lobbies store positions in quantized form
+pub fn snap_position(position: (f32, f32, f32)) -> (f32, f32, f32) {
+    quantize_position(position).to_f32(POSITION_SCALE)
+}
+
+/// Snap a rotation to the milliradian grid
+pub fn snap_rotation(rotation: (f32, f32, f32)) -> (f32, f32, f32) {
+    quantize_rotation(rotation).to_f32(ROTATION_SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_roundtrip() {
+        let q = quantize_position((1.2345, -2.0, 300.5));
+        assert_eq!(q, QuantizedVec3 { x: 1235, y: -2000, z: 300500 });
+        assert_eq!(q.to_f32(POSITION_SCALE), (1.235, -2.0, 300.5));
+    }
+
+    #[test]
+    fn test_quantize_non_finite() {
+        let q = quantize_position((f32::NAN, f32::INFINITY, f32::NEG_INFINITY));
+        assert_eq!(q, QuantizedVec3 { x: 0, y: i32::MAX, z: i32::MIN });
+    }
+
+    #[test]
+    fn test_quantize_angle_wraps() {
+        assert_eq!(quantize_angle(1.0), 1000);
+        assert_eq!(quantize_angle(-1.0), -1000);
+        assert_eq!(quantize_angle(1.0 + std::f32::consts::TAU), 1000);
+        assert_eq!(quantize_angle(f32::NAN), 0);
+        assert!((dequantize_angle(quantize_angle(3.0)) - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_snap_is_idempotent() {
+        let once = snap_position((10.00049, 5.1, -3.3333));
+        assert_eq!(snap_position(once), once);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::utils::config::Config;
+
+/// Strikes older than this are forgiven
+const STRIKE_MEMORY: Duration = Duration::from_secs(60);
+
+/// Sources silent this long are forgotten
+const IDLE_SOURCE: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Source {
+    tokens: f64,
+    refilled: Instant,
+    flooding: bool, // Out of tokens since the last allowed packet
+    strikes: u32,
+    last_strike: Instant,
+    banned_until: Option<Instant>,
+}
+
+/// Token bucket per source (a UDP address by default)
+/// Each source may send `packets_per_sec` with bursts of `burst`. Running dry counts as a strike,
+/// and a source with `ban_strikes` recent strikes is ignored for the ban duration
+#[derive(Debug)]
+pub struct RateLimiter<K = SocketAddr> {
+    packets_per_sec: f64,
+    burst: f64,
+    ban_strikes: u32, // 0 = never ban
+    ban_duration: Duration,
+    sources: Mutex<HashMap<K, Source>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config) -> Self {
+        Self::with_rate(
+            config.udp_rate_limit_pps as f64,
+            config.udp_rate_limit_burst,
+            config.udp_ban_strikes,
+            Duration::from_secs(config.udp_ban_secs),
+        )
+    }
+}
+
+impl<K: Hash + Eq + Clone + Display> RateLimiter<K> {
+    pub fn with_rate(packets_per_sec: f64, burst: u32, ban_strikes: u32, ban_duration: Duration) -> Self {
+        Self {
+            packets_per_sec,
+            burst: burst.max(1) as f64,
+            ban_strikes,
+            ban_duration,
+            sources: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a packet from `key` may be handled
+    pub fn allow(&self, key: K, now: Instant) -> bool {
+        if self.packets_per_sec <= 0.0 {
+            return true;
+        }
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        let source = sources.entry(key.clone()).or_insert(Source {
+            tokens: self.burst,
+            refilled: now,
+            flooding: false,
+            strikes: 0,
+            last_strike: now,
+            banned_until: None,
+        });
+
+        if let Some(until) = source.banned_until {
+            if now < until {
+                return false;
+            }
+            source.banned_until = None;
+        }
+        let elapsed = now.saturating_duration_since(source.refilled).as_secs_f64();
+        source.tokens = (source.tokens + elapsed * self.packets_per_sec).min(self.burst);
+        source.refilled = now;
+        if source.tokens >= 1.0 {
+            source.tokens -= 1.0;
+            source.flooding = false;
+            return true;
+        }
+
+        if !source.flooding {
+            source.flooding = true;
+            if now.saturating_duration_since(source.last_strike) > STRIKE_MEMORY {
+                source.strikes = 0;
+            }
+            source.strikes += 1;
+            source.last_strike = now;
+            if self.ban_strikes > 0 && source.strikes >= self.ban_strikes {
+                log::warn!("Banning source {} for {:?} (flooding)", key, self.ban_duration);
+                source.banned_until = Some(now + self.ban_duration);
+                source.strikes = 0;
+            }
+        }
+        false
+    }
+
+    /// Forget sources that went quiet and whose ban is over
+    pub fn cleanup(&self, now: Instant) {
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        sources.retain(|_, source| {
+            source.banned_until.is_some_and(|until| now < until) || now.saturating_duration_since(source.refilled) < IDLE_SOURCE
+        });
+    }
+
+    #[cfg(test)]
+    fn tracked(&self) -> usize {
+        self.sources.lock().unwrap().len()
+    }
+}
+
+/// Sign-in attempts per client IP and per username, so passwords can't be guessed at hashing speed
+#[derive(Debug)]
+pub struct LoginLimiter {
+    by_ip: RateLimiter<IpAddr>,
+    by_username: RateLimiter<String>,
+    last_cleanup: Mutex<Instant>,
+}
+
+impl LoginLimiter {
+    pub fn new(config: &Config) -> Self {
+        let per_sec = config.login_attempts_per_minute as f64 / 60.0;
+        Self {
+            by_ip: RateLimiter::with_rate(per_sec, config.login_attempt_burst, 0, Duration::ZERO),
+            by_username: RateLimiter::with_rate(per_sec, config.login_attempt_burst, 0, Duration::ZERO),
+            last_cleanup: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Whether a sign-in as `username` may be tried; `ip` is None when the peer address is unknown (bus)
+    pub fn allow(&self, ip: Option<IpAddr>, username: &str, now: Instant) -> bool {
+        {
+            let mut last_cleanup = self.last_cleanup.lock().unwrap_or_else(|e| e.into_inner());
+            if now.saturating_duration_since(*last_cleanup) >= IDLE_SOURCE {
+                self.by_ip.cleanup(now);
+                self.by_username.cleanup(now);
+                *last_cleanup = now;
+            }
+        }
+        // Usernames are matched ignoring case, so their attempts are too
+        ip.is_none_or(|ip| self.by_ip.allow(ip, now)) && self.by_username.allow(username.to_lowercase(), now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_floods_are_limited_then_banned() {
+        let limiter = RateLimiter::new(&Config {
+            udp_rate_limit_pps: 10,
+            udp_rate_limit_burst: 5,
+            udp_ban_strikes: 3,
+            udp_ban_secs: 30,
+            ..Config::default()
+        });
+        let flooder: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let player: SocketAddr = "10.0.0.2:5000".parse().unwrap();
+        let now = Instant::now();
+
+        // Burst, then the refill rate
+        assert_eq!((0..8).filter(|_| limiter.allow(flooder, now)).count(), 5);
+        assert!(limiter.allow(player, now));
+        assert!(limiter.allow(flooder, now + Duration::from_millis(100)));
+        assert!(!limiter.allow(flooder, now + Duration::from_millis(100)));
+
+        // Third strike: banned even after the bucket refills
+        let later = now + Duration::from_secs(5);
+        assert_eq!((0..8).filter(|_| limiter.allow(flooder, later)).count(), 5);
+        assert!(!limiter.allow(flooder, later + Duration::from_secs(10)));
+        assert!(limiter.allow(flooder, later + Duration::from_secs(31)));
+
+        limiter.cleanup(later + Duration::from_secs(31) + IDLE_SOURCE);
+        assert_eq!(limiter.tracked(), 0);
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, HashMap};
+use serde_json::Value;
+use crate::net::binary;
+use crate::net::protocol::{Channel, ChannelSequences};
+use crate::utils::config::Config;
+
+/// Unacked reliable packets kept per client; the oldest is dropped beyond this
+const MAX_PENDING_PER_CLIENT: usize = 256;
+
+/// Sequence numbers before `ack` an ack bitfield covers
+pub const ACK_WINDOW: u64 = 32;
+
+/// Sequence numbers confirmed by an ack header: `ack` itself, plus `ack - 1 - i` for every set bit `i`
+pub fn acked_seqs(ack: u64, ack_bits: u32) -> Vec<u64> {
+    let mut seqs = vec![ack];
+    seqs.extend((0..ACK_WINDOW).filter(|i| ack_bits & (1 << i) != 0 && ack > i + 1).map(|i| ack - 1 - i));
+    seqs
+}
+
+/// Reliable packets received from one client, to ack them and drop resent duplicates
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReceiveWindow {
+    latest: u64, // Newest sequence number received (0 = none yet)
+    bits: u32, // Bit i set = `latest - 1 - i` received
+}
+
+impl ReceiveWindow {
+    /// Record a received sequence number; false for duplicates
+    /// Packets older than the window are assumed already handled
+    pub fn receive(&mut self, seq: u64) -> bool {
+        if seq == 0 {
+            return false;
+        }
+        if seq > self.latest {
+            let shift = seq - self.latest;
+            self.bits = if shift > ACK_WINDOW {
+                0
+            } else {
+                let shifted = if shift == ACK_WINDOW { 0 } else { self.bits << shift };
+                if self.latest > 0 { shifted | 1 << (shift - 1) } else { shifted }
+            };
+            self.latest = seq;
+            return true;
+        }
+        let behind = self.latest - seq;
+        if behind == 0 || behind > ACK_WINDOW {
+            return false;
+        }
+        let bit = 1 << (behind - 1);
+        let fresh = self.bits & bit == 0;
+        self.bits |= bit;
+        fresh
+    }
+
+    /// `(ack, ack_bits)` header confirming everything in the window
+    pub fn ack_header(&self) -> (u64, u32) {
+        (self.latest, self.bits)
+    }
+}
+
+/// A reliable packet waiting for the client's ack
+#[derive(Debug, Clone)]
+struct Pending {
+    data: Vec<u8>,
+    sent_tick: u64,
+    resends: u32,
+}
+
+/// Fields `stamp` adds to every packet
+const STAMP_FIELDS: [&str; 4] = ["seq", "reliable", "channel", "channel_seq"];
+
+/// A JSON event serialized once, however many clients it is stamped for
+/// Stamp fields already in the packet are left out, so a stamped copy never repeats a key
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerializedPacket {
+    body: Vec<u8>,
+    channel: Channel,
+}
+
+impl SerializedPacket {
+    pub fn new(packet: &Value) -> Self {
+        let channel = Channel::of_packet(packet);
+        let body = match packet.as_object() {
+            Some(fields) if STAMP_FIELDS.iter().any(|key| fields.contains_key(*key)) => {
+                let mut fields = fields.clone();
+                fields.retain(|key, _| !STAMP_FIELDS.contains(&key.as_str()));
+                Value::Object(fields).to_string()
+            }
+            _ => packet.to_string(),
+        };
+        Self { body: body.into_bytes(), channel }
+    }
+}
+
+/// A serialized JSON object with extra fields added at the end
+/// The object must not already have any of them (see `SerializedPacket`)
+fn with_fields(body: &[u8], extra: &str) -> Option<Vec<u8>> {
+    let fields = body.strip_prefix(b"{")?.strip_suffix(b"}")?;
+    let mut data = Vec::with_capacity(body.len() + extra.len() + 2);
+    data.push(b'{');
+    data.extend_from_slice(fields);
+    if !fields.is_empty() && !extra.is_empty() {
+        data.push(b',');
+    }
+    data.extend_from_slice(extra.as_bytes());
+    data.push(b'}');
+    Some(data)
+}
+
+/// A serialized JSON object with `seq` (and `reliable`, and the channel's own sequence number) added at the end
+fn with_seq(body: &[u8], seq: u64, reliable: bool, channel: Option<(Channel, u64)>) -> Option<Vec<u8>> {
+    let mut extra = format!("\"seq\":{}", seq);
+    if reliable {
+        extra.push_str(",\"reliable\":true");
+    }
+    if let Some((channel, channel_seq)) = channel {
+        extra.push_str(&format!(",\"channel\":\"{}\",\"channel_seq\":{}", channel.as_str(), channel_seq));
+    }
+    with_fields(body, &extra)
+}
+
+/// Outgoing sequence numbers and unacked reliable packets of one client
+#[derive(Debug, Default)]
+struct ClientQueue {
+    next_seq: u64,
+    channels: ChannelSequences, // Only advanced for clients with `CAP_CHANNELS`
+    pending: BTreeMap<u64, Pending>, // Sequence number -> packet
+}
+
+/// Stamps every event a lobby sends with a per-client sequence number and resends
+/// reliable ones (kills, joins, weapon switches...) until the client acks them
+/// Owned by the lobby's tick loop
+#[derive(Debug)]
+pub struct ReliableSender {
+    clients: HashMap<u32, ClientQueue>,
+    resend_interval_ticks: u64,
+    max_resends: u32,
+    tick: u64,
+}
+
+impl ReliableSender {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            clients: HashMap::new(),
+            resend_interval_ticks: config.reliable_resend_interval_ticks.max(1),
+            max_resends: config.reliable_max_resends,
+            tick: 0,
+        }
+    }
+
+    /// Tick the next stamped packets count as sent at
+    pub fn start_tick(&mut self, tick: u64) {
+        self.tick = tick;
+    }
+
+    /// Copy of a packet for one client, with its next `seq`
+    /// `track` keeps it for resending until acked (reliable events to clients that ack)
+    /// `channels` also stamps the next sequence number of the packet's channel (`CAP_CHANNELS`)
+    pub fn stamp(&mut self, player_id: u32, packet: &SerializedPacket, track: bool, channels: bool) -> Option<Vec<u8>> {
+        let queue = self.clients.entry(player_id).or_default();
+        queue.next_seq += 1;
+        let seq = queue.next_seq;
+        let channel = channels.then(|| (packet.channel, queue.channels.next(packet.channel)));
+        let data = with_seq(&packet.body, seq, track, channel)?;
+
+        if track {
+            if queue.pending.len() >= MAX_PENDING_PER_CLIENT {
+                queue.pending.pop_first();
+            }
+            queue.pending.insert(seq, Pending { data: data.clone(), sent_tick: self.tick, resends: 0 });
+        }
+        Some(data)
+    }
+
+    /// Stamp an encoded position update (JSON or binary) with the client's next movement channel number
+    /// Position updates carry no `seq` and are never resent
+    pub fn stamp_movement(&mut self, player_id: u32, data: &[u8]) -> Option<Vec<u8>> {
+        let channel_seq = self.clients.entry(player_id).or_default().channels.next(Channel::Movement);
+        if binary::is_binary(data) {
+            return Some(binary::with_channel_seq(data, channel_seq));
+        }
+        with_fields(data, &format!("\"channel\":\"movement\",\"channel_seq\":{}", channel_seq))
+    }
+
+    /// Client confirmed it received these sequence numbers
+    pub fn ack(&mut self, player_id: u32, seqs: &[u64]) {
+        if let Some(queue) = self.clients.get_mut(&player_id) {
+            for seq in seqs {
+                queue.pending.remove(seq);
+            }
+        }
+    }
+
+    /// Reliable packets unacked for a resend interval, to send again this tick
+    /// Packets resent `max_resends` times without an ack are given up on
+    pub fn due_resends(&mut self) -> Vec<(u32, Vec<u8>)> {
+        let mut due = Vec::new();
+        for (player_id, queue) in &mut self.clients {
+            queue.pending.retain(|seq, pending| {
+                if self.tick < pending.sent_tick + self.resend_interval_ticks {
+                    return true;
+                }
+                if pending.resends >= self.max_resends {
+                    log::debug!("Giving up on packet {} to player {} after {} resends", seq, player_id, pending.resends);
+                    return false;
+                }
+                pending.resends += 1;
+                pending.sent_tick = self.tick;
+                due.push((*player_id, pending.data.clone()));
+                true
+            });
+        }
+        due
+    }
+
+    /// Unacked reliable packets of a client
+    #[cfg(test)]
+    pub fn pending(&self, player_id: u32) -> usize {
+        self.clients.get(&player_id).map(|q| q.pending.len()).unwrap_or(0)
+    }
+
+    /// Drop a client that left
+    pub fn forget(&mut self, player_id: u32) {
+        self.clients.remove(&player_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sender() -> ReliableSender {
+        ReliableSender::new(&Config { reliable_resend_interval_ticks: 5, reliable_max_resends: 2, ..Config::default() })
+    }
+
+    fn packet(value: serde_json::Value) -> SerializedPacket {
+        SerializedPacket::new(&value)
+    }
+
+    fn seq(data: &[u8]) -> u64 {
+        serde_json::from_slice::<serde_json::Value>(data).unwrap()["seq"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn test_sequence_numbers_per_client() {
+        let mut sender = sender();
+        let packet = packet(json!({"type": "shot_fired"}));
+        assert_eq!(seq(&sender.stamp(1, &packet, false, false).unwrap()), 1);
+        assert_eq!(seq(&sender.stamp(1, &packet, false, false).unwrap()), 2);
+        assert_eq!(seq(&sender.stamp(2, &packet, false, false).unwrap()), 1);
+        assert_eq!(sender.pending(1), 0);
+    }
+
+    #[test]
+    fn test_one_serialization_stamped_for_every_client() {
+        let mut sender = sender();
+        let packet = packet(json!({"type": "player_died", "player_id": 2}));
+        for player_id in 1..=8 {
+            let data = sender.stamp(player_id, &packet, player_id % 2 == 0, false).unwrap();
+            let stamped: serde_json::Value = serde_json::from_slice(&data).unwrap();
+            assert_eq!(stamped["player_id"], 2);
+            assert_eq!(stamped["seq"], 1);
+            assert_eq!(stamped.get("reliable").is_some(), player_id % 2 == 0);
+        }
+        assert_eq!(seq(&sender.stamp(1, &packet, false, false).unwrap()), 2);
+    }
+
+    #[test]
+    fn test_stamp_fields_are_never_repeated() {
+        let mut sender = sender();
+        let packet = packet(json!({"type": "chat", "seq": 99, "channel": "x", "text": "my seq"}));
+        let data = String::from_utf8(sender.stamp(1, &packet, true, true).unwrap()).unwrap();
+        assert_eq!(data.matches("\"seq\"").count(), 1);
+        assert_eq!(data.matches("\"channel\"").count(), 1);
+        let stamped: serde_json::Value = serde_json::from_str(&data).unwrap();
+        assert_eq!((stamped["seq"].as_u64(), stamped["channel"].as_str(), stamped["text"].as_str()), (Some(1), Some("event"), Some("my seq")));
+    }
+
+    #[test]
+    fn test_channel_sequence_numbers() {
+        let mut sender = sender();
+        let stamped = |data: Vec<u8>| serde_json::from_slice::<serde_json::Value>(&data).unwrap();
+        let health = packet(json!({"type": "player_state_update", "player_id": 2, "health": 80}));
+        let kill = packet(json!({"type": "player_killed", "victim_id": 2}));
+
+        let first = stamped(sender.stamp(1, &health, false, true).unwrap());
+        assert_eq!((first["channel"].as_str(), first["channel_seq"].as_u64()), (Some("state"), Some(1)));
+        let event = stamped(sender.stamp(1, &kill, true, true).unwrap());
+        assert_eq!((event["channel"].as_str(), event["channel_seq"].as_u64(), event["seq"].as_u64()), (Some("event"), Some(1), Some(2)));
+        assert_eq!(stamped(sender.stamp(1, &health, false, true).unwrap())["channel_seq"], 2);
+        // Clients without the capability get no channel fields
+        assert!(stamped(sender.stamp(2, &health, false, false).unwrap()).get("channel").is_none());
+
+        let position = serde_json::to_vec(&json!({"type": "position_update", "player_id": 2})).unwrap();
+        let moved = stamped(sender.stamp_movement(1, &position).unwrap());
+        assert_eq!((moved["channel"].as_str(), moved["channel_seq"].as_u64()), (Some("movement"), Some(1)));
+        assert!(moved.get("seq").is_none());
+
+        let encoded = binary::encode(&binary::BinaryPacket::position_update_2d(2, (1.0, 0.0, 1.0), 0.0)).unwrap();
+        let sequenced = sender.stamp_movement(1, &encoded).unwrap();
+        assert_eq!(sequenced[0], binary::SEQUENCED_MAGIC);
+        assert_eq!(u64::from_le_bytes(sequenced[1..9].try_into().unwrap()), 2);
+        assert_eq!(&sequenced[9..], &encoded[..]);
+
+        sender.forget(1);
+        assert_eq!(stamped(sender.stamp(1, &health, false, true).unwrap())["channel_seq"], 1);
+    }
+
+    #[test]
+    fn test_unacked_packets_are_resent_until_acked() {
+        let mut sender = sender();
+        sender.start_tick(1);
+        let kill = sender.stamp(1, &packet(json!({"type": "player_died"})), true, false).unwrap();
+        let join = sender.stamp(1, &packet(json!({"type": "player_joined"})), true, false).unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&kill).unwrap()["reliable"], true);
+
+        sender.start_tick(5);
+        assert!(sender.due_resends().is_empty());
+        sender.ack(1, &[seq(&join)]);
+
+        sender.start_tick(6);
+        assert_eq!(sender.due_resends(), vec![(1, kill.clone())]);
+        sender.start_tick(11);
+        assert_eq!(sender.due_resends().len(), 1);
+
+        // Given up after max_resends
+        sender.start_tick(16);
+        assert!(sender.due_resends().is_empty());
+        assert_eq!(sender.pending(1), 0);
+    }
+
+    #[test]
+    fn test_receive_window_drops_duplicates_and_acks_gaps() {
+        let mut window = ReceiveWindow::default();
+        assert!(window.receive(1));
+        assert!(window.receive(3)); // 2 lost
+        assert!(!window.receive(3));
+        assert!(!window.receive(1));
+        assert_eq!(window.ack_header(), (3, 0b10));
+        assert_eq!(acked_seqs(3, 0b10), vec![3, 1]);
+
+        assert!(window.receive(2)); // Late arrival
+        assert_eq!(acked_seqs(window.ack_header().0, window.ack_header().1), vec![3, 2, 1]);
+
+        assert!(window.receive(40));
+        assert_eq!(window.ack_header(), (40, 0));
+        assert!(!window.receive(5)); // Older than the window
+    }
+}
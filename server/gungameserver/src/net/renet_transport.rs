@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
+
+/// Addresses served at once; packets from further new addresses are dropped
+const MAX_CONNECTIONS: usize = 1024;
+
+/// Connections silent this long are dropped
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Connections {
+    server: RenetServer,
+    ids: HashMap<SocketAddr, ClientId>,
+    addrs: HashMap<ClientId, (SocketAddr, Instant)>, // Client -> address and when it was last heard from
+    next_id: ClientId,
+}
+
+/// Game packets carried as renet channel messages over the UDP port
+/// Every message is one packet of the JSON/binary protocol. Reliable events travel on the reliable
+/// ordered channel, everything else (positions, snapshots...) unreliably; renet handles acks,
+/// resends and fragmentation. Clients are identified by address (no netcode handshake)
+#[derive(Debug)]
+pub struct RenetTransport {
+    connections: Mutex<Connections>,
+}
+
+impl Default for RenetTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenetTransport {
+    pub fn new() -> Self {
+        Self {
+            connections: Mutex::new(Connections {
+                server: RenetServer::new(ConnectionConfig::default()),
+                ids: HashMap::new(),
+                addrs: HashMap::new(),
+                next_id: 1,
+            }),
+        }
+    }
+
+    /// Take a datagram from `addr` and return the complete messages it delivered
+    /// New addresses become connections
+    pub fn receive(&self, addr: SocketAddr, datagram: &[u8], now: Instant) -> Vec<Vec<u8>> {
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        let connections = &mut *connections;
+        let client_id = match connections.ids.get(&addr) {
+            Some(id) => *id,
+            None if connections.ids.len() >= MAX_CONNECTIONS => return Vec::new(),
+            None => {
+                let id = connections.next_id;
+                connections.next_id += 1;
+                connections.ids.insert(addr, id);
+                connections.server.add_connection(id);
+                id
+            }
+        };
+        connections.addrs.insert(client_id, (addr, now));
+        if connections.server.process_packet_from(datagram, client_id).is_err() {
+            return Vec::new();
+        }
+
+        let mut messages = Vec::new();
+        for channel in [DefaultChannel::ReliableOrdered, DefaultChannel::ReliableUnordered, DefaultChannel::Unreliable] {
+            let channel = u8::from(channel);
+            while let Some(message) = connections.server.receive_message(client_id, channel) {
+                messages.push(message.to_vec());
+            }
+        }
+        messages
+    }
+
+    /// Queue a packet for a client; false if `addr` isn't a renet connection
+    pub fn send(&self, addr: SocketAddr, data: &[u8]) -> bool {
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(client_id) = connections.ids.get(&addr).copied() else {
+            return false;
+        };
+        let channel = if is_reliable(data) { DefaultChannel::ReliableOrdered } else { DefaultChannel::Unreliable };
+        connections.server.send_message(client_id, channel, data.to_vec());
+        true
+    }
+
+    /// Advance renet by `elapsed` and collect the datagrams to send
+    /// Also returns the addresses whose connections ended
+    pub fn update(&self, elapsed: Duration, now: Instant) -> (Vec<(SocketAddr, Vec<u8>)>, Vec<SocketAddr>) {
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        let connections = &mut *connections;
+        connections.server.update(elapsed);
+
+        let idle: Vec<ClientId> = connections
+            .addrs
+            .iter()
+            .filter(|(_, (_, last_heard))| now.duration_since(*last_heard) >= CONNECTION_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect();
+        for client_id in idle {
+            connections.server.disconnect(client_id);
+        }
+        for client_id in connections.server.disconnections_id() {
+            connections.server.remove_connection(client_id);
+        }
+
+        let mut closed = Vec::new();
+        while let Some(event) = connections.server.get_event() {
+            if let ServerEvent::ClientDisconnected { client_id, reason } = event {
+                if let Some((addr, _)) = connections.addrs.remove(&client_id) {
+                    log::debug!("Renet connection {} closed: {}", addr, reason);
+                    connections.ids.remove(&addr);
+                    closed.push(addr);
+                }
+            }
+        }
+
+        let mut datagrams = Vec::new();
+        for (client_id, (addr, _)) in &connections.addrs {
+            if let Ok(packets) = connections.server.get_packets_to_send(*client_id) {
+                datagrams.extend(packets.into_iter().map(|packet| (*addr, packet)));
+            }
+        }
+        (datagrams, closed)
+    }
+}
+
+/// Reliable events are JSON packets marked `"reliable": true`
+fn is_reliable(data: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(data)
+        .ok()
+        .and_then(|packet| packet.get("reliable").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renet::RenetClient;
+
+    #[test]
+    fn test_messages_travel_both_ways() {
+        let transport = RenetTransport::new();
+        let addr: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let now = Instant::now();
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_connected();
+
+        client.send_message(DefaultChannel::ReliableOrdered, br#"{"type":"join"}"#.to_vec());
+        client.update(Duration::from_millis(10));
+        let mut received = Vec::new();
+        for packet in client.get_packets_to_send() {
+            received.extend(transport.receive(addr, &packet, now));
+        }
+        assert_eq!(received, vec![br#"{"type":"join"}"#.to_vec()]);
+
+        assert!(transport.send(addr, br#"{"type":"player_died","reliable":true}"#));
+        assert!(transport.send(addr, br#"{"type":"position_update"}"#));
+        assert!(!transport.send("10.0.0.2:5000".parse().unwrap(), b"{}"));
+        let (datagrams, closed) = transport.update(Duration::from_millis(10), now);
+        assert!(closed.is_empty());
+        for (to, packet) in datagrams {
+            assert_eq!(to, addr);
+            client.process_packet(&packet);
+        }
+        assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap().as_ref(), br#"{"type":"player_died","reliable":true}"#);
+        assert_eq!(client.receive_message(DefaultChannel::Unreliable).unwrap().as_ref(), br#"{"type":"position_update"}"#);
+
+        // Silent connections are dropped
+        let (_, closed) = transport.update(Duration::from_millis(10), now + CONNECTION_TIMEOUT);
+        assert_eq!(closed, vec![addr]);
+        assert!(!transport.send(addr, b"{}"));
+    }
+}
@@ -0,0 +1,73 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Bind `count` non-blocking UDP sockets to one address
+/// With more than one, each gets SO_REUSEPORT and the kernel spreads incoming datagrams over them
+/// by source address, so a client always lands on the same socket. A single socket is bound plainly,
+/// so no other process can share the port
+pub fn bind_shards(addr: SocketAddr, count: usize) -> io::Result<Vec<UdpSocket>> {
+    let mut sockets = Vec::with_capacity(count.max(1));
+    let mut addr = addr;
+    for _ in 0..count.max(1) {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        if count > 1 {
+            set_reuse_port(&socket)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        let socket: UdpSocket = socket.into();
+        addr = socket.local_addr()?; // Port 0 picks a port; the other shards join it
+        sockets.push(socket);
+    }
+    Ok(sockets)
+}
+
+#[cfg(unix)]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(unix))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "SO_REUSEPORT needs a Unix platform"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+    use std::time::Duration;
+
+    #[test]
+    fn test_shards_share_the_port_and_split_sources() {
+        let shards = bind_shards("127.0.0.1:0".parse().unwrap(), 4).unwrap();
+        let addr = shards[0].local_addr().unwrap();
+        assert!(shards.iter().all(|shard| shard.local_addr().unwrap() == addr));
+        assert!(UdpSocket::bind(addr).is_err()); // Only sockets with SO_REUSEPORT may join
+
+        // Each source sticks to one shard; many sources reach more than one
+        let clients: Vec<UdpSocket> = (0..32).map(|_| UdpSocket::bind("127.0.0.1:0").unwrap()).collect();
+        for client in &clients {
+            client.send_to(b"a", addr).unwrap();
+            client.send_to(b"b", addr).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        let mut shard_of = HashMap::new();
+        let mut buf = [0u8; 16];
+        for (shard, socket) in shards.iter().enumerate() {
+            while let Ok((_, from)) = socket.recv_from(&mut buf) {
+                assert_eq!(*shard_of.entry(from).or_insert(shard), shard);
+            }
+        }
+        assert_eq!(shard_of.len(), clients.len());
+        assert!(shard_of.values().collect::<HashSet<_>>().len() > 1);
+    }
+
+    #[test]
+    fn test_single_socket_is_exclusive() {
+        let shards = bind_shards("127.0.0.1:0".parse().unwrap(), 1).unwrap();
+        assert_eq!(shards.len(), 1);
+        assert!(UdpSocket::bind(shards[0].local_addr().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,500 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use crate::domain::gungame::{self, GameMode};
+use crate::domain::{bot, grenades, lobbies, logic, matches, mutators, owner, projectiles, referee};
+use crate::simulate::state_hash;
+use crate::state::commands::LobbyCommand;
+use crate::state::lobby::{Dimensions, Lobby};
+use crate::state::server_state::ServerState;
+use crate::tick::lobby_tick;
+use crate::utils::config::Config;
+use crate::utils::tunables::Tunables;
+use crate::utils::weapondb::WeaponDb;
+
+/// Identifies a replay file (first field of the header frame)
+pub const REPLAY_FORMAT: &str = "gungame-replay";
+
+/// Bumped whenever a field changes meaning; older versions are refused, not guessed at
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Record a state hash every this many ticks (one second at the default tick rate)
+pub const REPLAY_CHECKPOINT_TICKS: u64 = 50;
+
+/// Largest frame a replay file may hold
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// Lobby setup a replay starts from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub format: String,
+    pub version: u32,
+    pub lobby_code: String,
+    pub scene: String,
+    pub mode: String,
+    pub ladder: Vec<u32>, // Gun game only
+    pub max_players: u32,
+    pub team_count: u32,
+    pub dimensions: u8,
+    pub friendly_fire: bool,
+    pub hide_enemy_state: bool,
+    pub max_fov: f32,
+    pub aim_assist: bool,
+    pub tunables: Tunables, // As the lobby started; later live changes aren't recorded
+    pub tick_interval_ms: u64,
+    pub started_at: u64, // Seconds since the Unix epoch
+}
+
+/// A player entering the lobby outside the command stream (HTTP joins, bots, merges)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayPlayer {
+    pub player_id: u32,
+    pub name: String,
+    pub bot: bool,
+    pub team_id: Option<u32>,
+    pub position: (f32, f32, f32),
+    pub loading: bool, // Still behind the loading gate
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RosterChange {
+    Joined(ReplayPlayer),
+    Left { player_id: u32 },
+}
+
+/// One tick of a lobby: what changed, in the order the tick loop applied it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub tick: u64,
+    pub at_us: u64, // Tick time since the recording started
+    pub roster: Vec<RosterChange>, // Since the last tick, applied before the commands
+    pub commands: Vec<LobbyCommand>,
+    pub hash: Option<u64>, // State after combat, every `REPLAY_CHECKPOINT_TICKS` ticks
+    pub late_roster: Vec<RosterChange>, // Waves, timeouts and command joins, applied before the match flow
+}
+
+/// Writes one lobby's ticks to `<replay_dir>/<code>-<unix time>.replay`
+/// Frames go to a writer task, so the tick loop never waits on the disk
+pub struct ReplayRecorder {
+    started: Instant,
+    known: BTreeSet<u32>, // Players in the lobby as of the last roster change
+    frame: Option<ReplayFrame>,
+    frames: mpsc::UnboundedSender<ReplayFrame>,
+}
+
+impl ReplayRecorder {
+    /// Create the file, write the header and start the writer task
+    pub fn start(lobby: &Lobby, config: &Config, now: Instant) -> std::io::Result<Self> {
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = Path::new(&config.replay_dir).join(format!("{}-{}.replay", lobby.code, started_at));
+        std::fs::create_dir_all(&config.replay_dir)?;
+        let header = ReplayHeader {
+            format: REPLAY_FORMAT.to_string(),
+            version: REPLAY_VERSION,
+            lobby_code: lobby.code.clone(),
+            scene: lobby.scene.clone(),
+            mode: lobby.mode.as_str().to_string(),
+            ladder: match &lobby.mode {
+                GameMode::GunGame(game) => game.ladder.clone(),
+                _ => Vec::new(),
+            },
+            max_players: lobby.max_players,
+            team_count: lobby.team_count,
+            dimensions: lobby.dimensions.as_u8(),
+            friendly_fire: lobby.friendly_fire,
+            hide_enemy_state: lobby.hide_enemy_state,
+            max_fov: lobby.rules.integrity.max_fov,
+            aim_assist: lobby.rules.integrity.aim_assist,
+            tunables: (*lobby.tunables).clone(),
+            tick_interval_ms: config.lobby_tick_interval_ms(lobby.tick_rate_hz),
+            started_at,
+        };
+        let mut file = std::fs::File::create(&path)?;
+        write_frame(&mut file, &header)?;
+        log::info!("Recording lobby {} to {}", lobby.code, path.display());
+
+        let (frames, mut rx) = mpsc::unbounded_channel::<ReplayFrame>();
+        tokio::spawn(async move {
+            let mut writer = tokio::io::BufWriter::new(tokio::fs::File::from_std(file));
+            while let Some(frame) = rx.recv().await {
+                let mut data = Vec::new();
+                let written = match write_frame(&mut data, &frame) {
+                    Ok(()) => writer.write_all(&data).await,
+                    Err(e) => Err(e),
+                };
+                let flushed = if rx.is_empty() { writer.flush().await } else { Ok(()) };
+                if let Err(e) = written.and(flushed) {
+                    log::warn!("Replay {} stopped: {}", path.display(), e);
+                    return;
+                }
+            }
+            let _ = writer.flush().await;
+        });
+        Ok(Self { started: now, known: BTreeSet::new(), frame: None, frames })
+    }
+
+    /// Start a tick's frame: who came and went since the last one, and the tick's commands
+    pub fn begin_tick(&mut self, tick: u64, now: Instant, lobby: &Lobby, commands: &[LobbyCommand]) {
+        self.frame = Some(ReplayFrame {
+            tick,
+            at_us: now.saturating_duration_since(self.started).as_micros() as u64,
+            roster: self.roster_changes(lobby),
+            commands: commands.to_vec(),
+            hash: None,
+            late_roster: Vec::new(),
+        });
+    }
+
+    /// After combat: hash the state on checkpoint ticks
+    pub fn checkpoint(&mut self, lobby: &Lobby) {
+        if let Some(frame) = self.frame.as_mut().filter(|f| f.tick % REPLAY_CHECKPOINT_TICKS == 0) {
+            frame.hash = Some(state_hash(lobby));
+        }
+    }
+
+    /// Before the match flow: record the roster changes of the tick so far and hand the frame to the writer
+    pub fn end_tick(&mut self, lobby: &Lobby) {
+        let late_roster = self.roster_changes(lobby);
+        if let Some(mut frame) = self.frame.take() {
+            frame.late_roster = late_roster;
+            let _ = self.frames.send(frame);
+        }
+    }
+
+    fn roster_changes(&mut self, lobby: &Lobby) -> Vec<RosterChange> {
+        let current: BTreeSet<u32> = lobby.players.keys().copied().collect();
+        let mut changes: Vec<RosterChange> = self
+            .known
+            .difference(&current)
+            .map(|&player_id| RosterChange::Left { player_id })
+            .collect();
+        changes.extend(current.difference(&self.known).filter_map(|id| {
+            let player = lobby.players.get(id)?;
+            Some(RosterChange::Joined(ReplayPlayer {
+                player_id: player.id,
+                name: player.name.clone(),
+                bot: lobby.bots.contains_key(&player.id),
+                team_id: player.team_id,
+                position: player.position,
+                loading: player.is_loading(),
+            }))
+        }));
+        self.known = current;
+        changes
+    }
+}
+
+/// Length-prefixed bincode, as on the internal bus
+fn write_frame(writer: &mut impl std::io::Write, frame: &impl Serialize) -> std::io::Result<()> {
+    let data = bincode::serialize(frame).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(&data)
+}
+
+/// The next frame of `data` from `offset`; None at the end or on a frame cut short by a crash
+fn read_frame<T: serde::de::DeserializeOwned>(data: &[u8], offset: &mut usize) -> Result<Option<T>, String> {
+    let Some(len) = data.get(*offset..*offset + 4) else {
+        return Ok(None);
+    };
+    let len = u32::from_be_bytes(len.try_into().expect("4 bytes")) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(format!("Replay frame too large at byte {}", offset));
+    }
+    let Some(body) = data.get(*offset + 4..*offset + 4 + len) else {
+        return Ok(None);
+    };
+    *offset += 4 + len;
+    bincode::deserialize(body).map(Some).map_err(|e| format!("Invalid replay frame: {}", e))
+}
+
+/// A kill seen while re-simulating
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayKill {
+    pub tick: u64,
+    pub killer_id: u32,
+    pub victim_id: u32,
+    pub weapon_name: String,
+}
+
+/// Outcome of re-simulating a replay
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReplayReport {
+    pub ticks: u64,
+    pub kills: Vec<ReplayKill>,
+    pub checkpoints: usize, // Recorded hashes that matched
+    pub desync_at: Option<u64>, // First tick whose hash didn't match
+    pub players: BTreeMap<u32, (String, u32, u32, u32)>, // Id -> name, score, kills, deaths when the replay stopped
+}
+
+/// A recorded lobby, read back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    /// Parse a replay, refusing other formats and versions
+    /// A truncated last frame (the server died mid-write) is dropped
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut offset = 0;
+        let header: ReplayHeader = read_frame(data, &mut offset)?.ok_or("Not a replay file (no header)")?;
+        if header.format != REPLAY_FORMAT {
+            return Err(format!("Not a replay file (format {:?})", header.format));
+        }
+        if header.version != REPLAY_VERSION {
+            return Err(format!("Unsupported replay version {} (expected {})", header.version, REPLAY_VERSION));
+        }
+        let mut frames = Vec::new();
+        while let Some(frame) = read_frame(data, &mut offset)? {
+            frames.push(frame);
+        }
+        Ok(Self { header, frames })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&data)
+    }
+
+    /// Re-simulate the lobby from its recorded commands, up to tick `until`, checking the recorded hashes
+    /// Run with the config and data files the server had: lobby rules and scenes come from them
+    pub fn run(&self, weapons: &WeaponDb, config: &Config, until: Option<u64>) -> Result<ReplayReport, String> {
+        let header = &self.header;
+        let state = ServerState::new();
+        let mut lobby = crate::server::new_lobby(&state, header.lobby_code.clone(), header.max_players, header.scene.clone(), config);
+        lobby.mode = GameMode::parse(&header.mode, header.ladder.clone()).ok_or_else(|| format!("Unknown mode in replay: {}", header.mode))?;
+        if matches!(lobby.mode, GameMode::Horde(_)) {
+            log::warn!("Horde waves aren't re-simulated; enemies only come and go as recorded");
+        }
+        lobby.team_count = header.team_count;
+        lobby.dimensions = Dimensions::parse(header.dimensions).ok_or("Invalid dimensions in replay")?;
+        lobby.friendly_fire = header.friendly_fire;
+        lobby.hide_enemy_state = header.hide_enemy_state;
+        lobby.rules.integrity.max_fov = header.max_fov;
+        lobby.rules.integrity.aim_assist = header.aim_assist;
+        lobby.tunables = std::sync::Arc::new(header.tunables.clone());
+        let dt = Duration::from_millis(header.tick_interval_ms).as_secs_f32();
+
+        let start = Instant::now();
+        let mut report = ReplayReport::default();
+        for frame in self.frames.iter().take_while(|f| until.is_none_or(|until| f.tick <= until)) {
+            let now = start + Duration::from_micros(frame.at_us);
+            apply_roster(&mut lobby, weapons, &frame.roster, now);
+            for cmd in frame.commands.iter().cloned() {
+                replay_command(&mut lobby, weapons, &state, cmd, now);
+            }
+            for event in step(&mut lobby, weapons, dt, now) {
+                report.kills.push(ReplayKill {
+                    tick: frame.tick,
+                    killer_id: event.killer_id,
+                    victim_id: event.victim_id,
+                    weapon_name: event.weapon_name,
+                });
+            }
+            if let Some(hash) = frame.hash {
+                if hash == state_hash(&lobby) {
+                    report.checkpoints += 1;
+                } else if report.desync_at.is_none() {
+                    report.desync_at = Some(frame.tick);
+                }
+            }
+            apply_roster(&mut lobby, weapons, &frame.late_roster, now);
+            if let Err(e) = matches::advance(&mut lobby, weapons, now) {
+                log::debug!("Match flow failed at tick {}: {}", frame.tick, e);
+            }
+            mutators::advance(&mut lobby, weapons, now);
+            lobby.lobby_events.clear();
+            lobby.rematch_offers.clear();
+            lobby.clear_dirty();
+            report.ticks = frame.tick;
+        }
+        report.players = lobby
+            .players
+            .values()
+            .map(|p| (p.id, (p.name.clone(), p.score, p.kills, p.deaths)))
+            .collect();
+        Ok(report)
+    }
+}
+
+/// Players entering and leaving outside the command stream, as the live lobby saw them
+fn apply_roster(lobby: &mut Lobby, weapons: &WeaponDb, changes: &[RosterChange], now: Instant) {
+    for change in changes {
+        match change {
+            RosterChange::Left { player_id } => lobbies::remove_player(lobby, *player_id),
+            RosterChange::Joined(player) if !lobby.players.contains_key(&player.player_id) => {
+                let added = if player.bot {
+                    lobbies::add_bot(lobby, player.player_id, player.name.clone(), weapons)
+                } else {
+                    lobbies::add_player(lobby, player.player_id, player.name.clone(), WeaponDb::default_weapon_id(), weapons)
+                };
+                if let Err(e) = added {
+                    log::warn!("Replay could not add player {}: {}", player.player_id, e);
+                    continue;
+                }
+                if player.loading {
+                    let timeout = Duration::from_secs(lobby.tunables.loading_timeout_secs);
+                    lobbies::begin_loading(lobby, player.player_id, timeout, now);
+                }
+                if let Some(added) = lobby.players.get_mut(&player.player_id) {
+                    added.team_id = player.team_id;
+                    added.position = player.position;
+                }
+            }
+            RosterChange::Joined(_) => {}
+        }
+    }
+}
+
+/// The state changes the tick loop makes for a command (messages to clients aside)
+fn replay_command(lobby: &mut Lobby, weapons: &WeaponDb, state: &ServerState, cmd: LobbyCommand, now: Instant) {
+    let cmd = match cmd {
+        LobbyCommand::OwnerAction { player_id, action } => match owner::apply(lobby, &state.scenes, player_id, action) {
+            Ok(Some(kicked)) => LobbyCommand::Kick { player_id: kicked, reason: owner::KICK_REASON.to_string() },
+            _ => return,
+        },
+        LobbyCommand::Referee { player_id, action } => {
+            let _ = referee::apply(lobby, player_id, action, now);
+            return;
+        }
+        LobbyCommand::Ack { .. } | LobbyCommand::Announce { .. } | LobbyCommand::Shutdown { .. } | LobbyCommand::Chat { .. } | LobbyCommand::Voice { .. } => return,
+        cmd => cmd,
+    };
+    if cmd.needs_loaded_scene().is_some_and(|id| lobbies::is_loading(lobby, id)) {
+        return;
+    }
+    let joining = match &cmd {
+        LobbyCommand::PlayerJoin { player_id, .. } => Some(*player_id),
+        _ => None,
+    };
+    lobby_tick::process_command(lobby, weapons, cmd, None, now);
+    if let Some(player_id) = joining {
+        let timeout = Duration::from_secs(lobby.tunables.loading_timeout_secs);
+        lobbies::begin_loading(lobby, player_id, timeout, now);
+    }
+}
+
+/// The tick loop's simulation steps between commands and the match flow; returns the tick's kills
+fn step(lobby: &mut Lobby, weapons: &WeaponDb, dt: f32, now: Instant) -> Vec<logic::KillEvent> {
+    lobbies::expire_loading(lobby, now);
+    let bot_ids: Vec<u32> = if lobby.frozen { Vec::new() } else { lobby.bots.keys().copied().collect() };
+    let time_scale = lobby.time_scale;
+    for bot_id in bot_ids {
+        let _ = bot::step(lobby, weapons, bot_id, dt * time_scale, now);
+    }
+    lobby.history.record(&lobby.players, now);
+    projectiles::step_at(lobby, weapons, dt * time_scale, now);
+    grenades::step_at(lobby, weapons, dt * time_scale, now);
+    logic::update_reload_states_at(lobby, now);
+    logic::respawn_due_players_at(lobby, now);
+    lobby.shot_feed.clear();
+    lobby.damage_feed.clear();
+    lobby.projectile_feed.clear();
+    lobby.grenade_feed.clear();
+    let kills = std::mem::take(&mut lobby.kill_feed);
+    for event in &kills {
+        if let Ok(Some(gungame::Progress::Won { player_id })) = gungame::on_kill(lobby, weapons, event.killer_id) {
+            if matches!(lobby.match_state, crate::state::lobby::MatchState::InProgress { .. }) {
+                matches::end_match(lobby, Some(player_id), now);
+            }
+        }
+    }
+    kills
+}
+
+/// Kept as JSON inside binary encodings: internally tagged enums (owner and referee actions)
+/// can't be read back by bincode
+pub mod as_json {
+    use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T: Serialize, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&json)
+    }
+
+    pub fn deserialize<'de, T: DeserializeOwned, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let json = String::deserialize(deserializer)?;
+        serde_json::from_str(&json).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORWARD: (f32, f32, f32) = (0.0, 0.0, -1.0);
+
+    /// Record 120 ticks of player 1 shooting player 2 the way the tick loop does
+    /// Returns the file and the (tick, killer, victim) of each kill
+    async fn record(config: &Config) -> (std::path::PathBuf, Vec<(u64, u32, u32)>) {
+        let weapons = WeaponDb::load();
+        let state = ServerState::new();
+        let mut lobby = crate::server::new_lobby(&state, "alpha".to_string(), 4, "world".to_string(), config);
+        let start = Instant::now();
+        let mut recorder = ReplayRecorder::start(&lobby, config, start).unwrap();
+        for (id, position) in [(1, (0.0, 1.0, 0.0)), (2, (0.0, 1.0, -10.0))] {
+            lobbies::add_player(&mut lobby, id, format!("P{}", id), WeaponDb::default_weapon_id(), &weapons).unwrap();
+            lobby.players.get_mut(&id).unwrap().position = position;
+        }
+
+        let mut kills = Vec::new();
+        for tick in 1..=120 {
+            let now = start + Duration::from_millis(tick * 20);
+            let commands = match tick % 10 {
+                0 => vec![LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }],
+                _ => Vec::new(),
+            };
+            recorder.begin_tick(tick, now, &lobby, &commands);
+            for cmd in commands {
+                replay_command(&mut lobby, &weapons, &state, cmd, now);
+            }
+            kills.extend(step(&mut lobby, &weapons, 0.02, now).iter().map(|k| (tick, k.killer_id, k.victim_id)));
+            recorder.checkpoint(&lobby);
+            recorder.end_tick(&lobby);
+            matches::advance(&mut lobby, &weapons, now).unwrap();
+        }
+        drop(recorder);
+
+        // The writer task finishes the file once the recorder is gone
+        let dir = Path::new(&config.replay_dir);
+        let path = std::fs::read_dir(dir).unwrap().next().unwrap().unwrap().path();
+        for _ in 0..100 {
+            if Replay::load(&path).is_ok_and(|replay| replay.frames.len() == 120) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        (path, kills)
+    }
+
+    #[tokio::test]
+    async fn test_replay_reproduces_the_recorded_lobby() {
+        let dir = std::env::temp_dir().join(format!("gungame-replays-{}", uuid::Uuid::new_v4()));
+        let config = Config { replay_dir: dir.to_string_lossy().into_owned(), ..Config::default() };
+        let (path, kills) = record(&config).await;
+        assert!(!kills.is_empty());
+        let replay = Replay::load(&path).unwrap();
+        assert_eq!((replay.header.lobby_code.as_str(), replay.frames.len()), ("alpha", 120));
+
+        let report = replay.run(&WeaponDb::load(), &config, None).unwrap();
+        assert_eq!((report.ticks, report.checkpoints, report.desync_at), (120, 2, None));
+        assert_eq!(report.kills.iter().map(|k| (k.tick, k.killer_id, k.victim_id)).collect::<Vec<_>>(), kills);
+        assert_eq!(report.players[&1].2, kills.len() as u32);
+        assert!(replay.run(&WeaponDb::load(), &config, Some(kills[0].0 - 1)).unwrap().kills.is_empty());
+
+        // A dropped command shows up at the next checkpoint
+        let mut tampered = replay.clone();
+        tampered.frames[9].commands.clear();
+        assert_eq!(tampered.run(&WeaponDb::load(), &config, None).unwrap().desync_at, Some(50));
+
+        // A frame cut short by a crash is dropped, other files are refused
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(Replay::parse(&data[..data.len() - 3]).unwrap().frames.len(), 119);
+        assert!(Replay::parse(b"not a replay").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
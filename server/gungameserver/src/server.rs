@@ -1,109 +1,267 @@
 use axum::{
-    routing::{get, post},
+    extract::{Request, State},
+    routing::{get, post, put},
     Router,
 };
 use tower_http::cors::CorsLayer;
 use log::info;
 use tokio::net::{TcpListener, UdpSocket};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, Notify, RwLock};
 use crate::state::server_state::{ServerState, LobbyHandle};
-use crate::state::lobby::Lobby;
-use crate::handlers::http::{create_lobby, list_lobbies, join_lobby, get_lobby, get_lobby_leaderboard, get_global_leaderboard, AppState};
-use crate::handlers::udp::handle_udp_packet;
+use crate::state::warm_pool::WarmLobby;
+use crate::state::commands::LobbyCommand;
+use crate::state::lobby::{Lobby, LobbySpec, MatchRules};
+use crate::domain::{lobbies, teams};
+use crate::domain::horde::HordeRules;
+use crate::domain::anticheat::IntegrityRules;
+use crate::domain::mutators::{MatchRng, Mutator, MutatorSchedule};
+use crate::handlers::http::{create_lobby, admin_create_lobby, list_lobbies, join_lobby, spectate_lobby, matchmake, get_lobby, get_lobby_leaderboard, get_lobby_player, get_global_leaderboard, get_account_leaderboard, search_players, register_account, login_account, get_own_account, get_match, get_account_matches, get_match_demo, list_weapons, get_server_info, get_log_levels, set_log_level, get_tunables, set_tunables, reload_tunables, reload_data, get_balance_report, submit_report, list_reports, resolve_report, get_lobby_ladder, set_lobby_ladder, rematch, owner_action, admin_list_lobbies, admin_get_lobby, admin_delete_lobby, admin_kick, admin_broadcast, admin_close_empty_lobbies, admin_merge_lobby, set_join_freeze, set_time_scale, grant_weapon, admin_referee, get_audit_log, list_ladder_presets, save_ladder_preset, delete_ladder_preset, AppState};
+use crate::handlers::udp::{datagram_lobby, handle_datagram};
+use crate::handlers::websocket::websocket_upgrade;
 use crate::tick::lobby_tick::lobby_tick_loop;
+use crate::tick::outbound::OutboundQueue;
 use crate::utils::weapondb::WeaponDb;
-use crate::utils::config::Config;
-
-/// Start HTTP and UDP servers
-pub async fn start_servers(
+use crate::utils::config::{Config, Role, Transport};
+use crate::net::bus::{self, BusClient};
+use crate::net::renet_transport::RenetTransport;
+use crate::net::rate_limit::{LoginLimiter, RateLimiter};
+use crate::net::messages::{self, MessageCode};
+use crate::utils::scaling::ScalingSink;
+use crate::state::analytics::LobbySummary;
+use crate::state::snapshot::{LobbyListing, Snapshot};
+use crate::storage::{self, Storage};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Spawn the HTTP and UDP servers, and with `Role::Simulation` the bus web processes
+/// (`start_web_server`) forward the lobby API over
+/// The tasks only end on a fatal error; returns their handles
+pub async fn spawn_servers(
     state: Arc<ServerState>,
     weapons: Arc<WeaponDb>,
     config: Arc<Config>,
-    udp_socket: Arc<UdpSocket>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let http_server = init_http_server(state.clone(), weapons.clone(), config.clone(), udp_socket.clone());
-    let udp_server = init_udp_server(state.clone(), weapons.clone(), udp_socket.clone()).await?;
+    http_listener: TcpListener,
+    udp_sockets: Vec<Arc<UdpSocket>>,
+) -> Result<Vec<tokio::task::JoinHandle<()>>, Box<dyn std::error::Error>> {
+    let udp_socket = udp_sockets[0].clone();
+    let mut servers = Vec::new();
+    if config.role == Role::Simulation {
+        servers.push(init_bus_server(state.clone(), config.clone(), udp_socket.clone()).await?);
+    }
+    servers.push(init_http_server(state.clone(), config.clone(), udp_socket.clone(), http_listener));
+    servers.push(init_udp_server(state.clone(), weapons.clone(), config.clone(), udp_sockets).await?);
+    Ok(servers)
+}
 
-    tokio::try_join!(http_server, udp_server)?;
+/// Start the web tier: the lobby API on the HTTP port, every request forwarded over the bus
+/// to the simulation process at `bus_addr`
+pub async fn start_web_server(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error>> {
+    let http_addr = format!("0.0.0.0:{}", config.http_port);
+    let bus = BusClient::connect(config.bus_addr.clone());
+    let app = Router::new()
+        .fallback(|State(bus): State<BusClient>, request: Request| async move { bus.forward(request).await })
+        .layer(CorsLayer::permissive())
+        .with_state(bus);
+
+    let listener = TcpListener::bind(&http_addr).await?;
+    info!("Web tier serving the lobby API on {}, simulation tier at {}", http_addr, config.bus_addr);
+    axum::serve(listener, app).await?;
     Ok(())
 }
 
-/// Initialize HTTP server
-fn init_http_server(
+/// Serve the lobby API to web processes on the internal bus
+async fn init_bus_server(
     state: Arc<ServerState>,
-    weapons: Arc<WeaponDb>,
     config: Arc<Config>,
     udp_socket: Arc<UdpSocket>,
-) -> tokio::task::JoinHandle<()> {
-    let app_state = AppState {
-        state,
-        weapons,
-        config,
-        udp_socket,
-    };
-    
-    let app = Router::new()
+) -> Result<tokio::task::JoinHandle<()>, Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(&config.bus_addr).await?;
+    info!("Serving the lobby API on the bus at {}", config.bus_addr);
+    let login_limiter = Arc::new(LoginLimiter::new(&config));
+    let app = build_router(AppState { state, config, udp_socket, login_limiter });
+    Ok(tokio::spawn(bus::serve(listener, app)))
+}
+
+/// Build the HTTP API router
+pub fn build_router(app_state: AppState) -> Router {
+    Router::new()
         .route("/lobbies", post(create_lobby))
         .route("/lobbies", get(list_lobbies))
         .route("/lobbies/:code/join", post(join_lobby))
+        .route("/spectate/:spectate_code", post(spectate_lobby))
         .route("/lobbies/:code", get(get_lobby))
+        .route("/matchmake", post(matchmake))
         .route("/lobbies/:code/leaderboard", get(get_lobby_leaderboard))
+        .route("/lobbies/:code/players/:id", get(get_lobby_player))
+        .route("/lobbies/:code/ladder", get(get_lobby_ladder).put(set_lobby_ladder))
+        .route("/lobbies/:code/rematch", post(rematch))
+        .route("/lobbies/:code/owner", post(owner_action))
         .route("/leaderboard", get(get_global_leaderboard))
+        .route("/leaderboards", get(get_account_leaderboard))
+        .route("/players", get(search_players))
+        .route("/accounts/register", post(register_account))
+        .route("/accounts/login", post(login_account))
+        .route("/accounts/me", get(get_own_account))
+        .route("/players/:id/matches", get(get_account_matches))
+        .route("/matches/:id", get(get_match))
+        .route("/matches/:id/demo", get(get_match_demo))
+        .route("/weapons", get(list_weapons))
+        .route("/info", get(get_server_info))
+        .route("/reports", post(submit_report))
+        .route("/ws", get(websocket_upgrade))
+        .route("/admin/lobbies", post(admin_create_lobby).get(admin_list_lobbies))
+        .route("/admin/lobbies/:code", get(admin_get_lobby).delete(admin_delete_lobby))
+        .route("/admin/lobbies/close-empty", post(admin_close_empty_lobbies))
+        .route("/admin/lobbies/:code/merge", post(admin_merge_lobby))
+        .route("/admin/kick", post(admin_kick))
+        .route("/admin/broadcast", post(admin_broadcast))
+        .route("/admin/join-freeze", put(set_join_freeze))
+        .route("/admin/lobbies/:code/time-scale", put(set_time_scale))
+        .route("/admin/lobbies/:code/players/:id/weapons", post(grant_weapon))
+        .route("/admin/lobbies/:code/referee", post(admin_referee))
+        .route("/admin/audit", get(get_audit_log))
+        .route("/admin/log-levels", get(get_log_levels).put(set_log_level))
+        .route("/admin/tunables", get(get_tunables).put(set_tunables))
+        .route("/admin/tunables/reload", post(reload_tunables))
+        .route("/admin/reload", post(reload_data))
+        .route("/admin/balance-report", get(get_balance_report))
+        .route("/admin/ladders", get(list_ladder_presets))
+        .route("/admin/ladders/:name", put(save_ladder_preset).delete(delete_ladder_preset))
+        .route("/admin/reports", get(list_reports))
+        .route("/admin/reports/:id/resolve", post(resolve_report))
         .layer(CorsLayer::permissive())
-        .with_state(app_state);
+        .with_state(app_state)
+}
 
-    let http_addr = format!("0.0.0.0:{}", 8080);
-    info!("Starting HTTP server on {}", http_addr);
+/// Initialize HTTP server on a bound listener
+fn init_http_server(
+    state: Arc<ServerState>,
+    config: Arc<Config>,
+    udp_socket: Arc<UdpSocket>,
+    listener: TcpListener,
+) -> tokio::task::JoinHandle<()> {
+    let app = build_router(AppState {
+        state,
+        login_limiter: Arc::new(LoginLimiter::new(&config)),
+        config,
+        udp_socket,
+    });
 
-    tokio::spawn(async move {
-        let listener = match TcpListener::bind(&http_addr).await {
-            Ok(listener) => {
-                info!("HTTP server successfully bound to {}", http_addr);
-                listener
-            }
-            Err(e) => {
-                eprintln!("Failed to bind HTTP server to {}: {}", http_addr, e);
-                return;
-            }
-        };
+    if let Ok(addr) = listener.local_addr() {
+        info!("Starting HTTP server on {}", addr);
+    }
 
-        if let Err(e) = axum::serve(listener, app).await {
+    tokio::spawn(async move {
+        // Peer addresses identify WebSocket clients
+        if let Err(e) = axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await {
             eprintln!("HTTP server error: {}", e);
         }
     })
 }
 
+/// How often the rate limiter forgets quiet sources
+const RATE_LIMIT_CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reason in the `kicked` packet clients of a closed lobby get
+pub const LOBBY_CLOSED_REASON: &str = "Lobby closed";
+
 /// Initialize UDP server
+/// With the renet transport, datagrams are renet packets and each message they deliver is handled
+/// like a plain datagram; a flush task sends what renet queued every 10ms. Datagrams over their
+/// source's rate limit are dropped before anything else
+/// Each of `sockets` (SO_REUSEPORT shards of the UDP port) gets its own receive task and rate limiter;
+/// a source always lands on the same shard, so its limit holds
 async fn init_udp_server(
     state: Arc<ServerState>,
     weapons: Arc<WeaponDb>,
-    socket: Arc<UdpSocket>,
+    config: Arc<Config>,
+    sockets: Vec<Arc<UdpSocket>>,
 ) -> Result<tokio::task::JoinHandle<()>, Box<dyn std::error::Error>> {
-    let socket_clone = socket.clone();
-    let state_clone = state.clone();
-    let weapons_clone = weapons.clone();
+    let renet = (config.transport == Transport::Renet).then(|| Arc::new(RenetTransport::new()));
+    if let Some(renet) = &renet {
+        state.use_renet(renet.clone());
+        tokio::spawn(flush_renet(renet.clone(), state.clone(), sockets[0].clone()));
+    }
+    info!("UDP transport: {}, {} receiver(s)", config.transport.as_str(), sockets.len());
 
+    let receivers: Vec<_> = sockets
+        .into_iter()
+        .map(|socket| tokio::spawn(receive_datagrams(socket, state.clone(), weapons.clone(), RateLimiter::new(&config), renet.clone(), None)))
+        .collect();
     Ok(tokio::spawn(async move {
-        let mut buf = [0u8; 1024];
-
-        loop {
-            match socket_clone.recv_from(&mut buf).await {
-                Ok((len, addr)) => {
-                    let data = &buf[..len];
-                    if let Ok(packet) = serde_json::from_slice::<serde_json::Value>(data) {
-                        handle_udp_packet(packet, addr, &socket_clone, &state_clone, &weapons_clone).await;
+        for receiver in receivers {
+            let _ = receiver.await;
+        }
+    }))
+}
+
+/// Handle datagrams arriving on `socket` (replies go out through it) until the task is aborted
+/// A lobby's dedicated socket (`lobby`) only takes datagrams from that lobby's players
+async fn receive_datagrams(
+    socket: Arc<UdpSocket>,
+    state: Arc<ServerState>,
+    weapons: Arc<WeaponDb>,
+    limiter: RateLimiter,
+    renet: Option<Arc<RenetTransport>>,
+    lobby: Option<String>,
+) {
+    let mut buf = [0u8; 1500];
+    let mut last_cleanup = std::time::Instant::now();
+
+    loop {
+        match socket.recv_from(&mut buf).await {
+            Ok((len, addr)) => {
+                let now = std::time::Instant::now();
+                if now.duration_since(last_cleanup) >= RATE_LIMIT_CLEANUP_INTERVAL {
+                    limiter.cleanup(now);
+                    last_cleanup = now;
+                }
+                if !limiter.allow(addr, now) {
+                    continue;
+                }
+                if let Some(code) = &lobby {
+                    if datagram_lobby(&buf[..len], &state).await.as_deref() != Some(code.as_str()) {
+                        log::debug!("Dropping datagram from {} on lobby {}'s port: not one of its players", addr, code);
+                        continue;
                     }
                 }
-                Err(e) => {
-                    log::error!("UDP recv error: {}", e);
+                match &renet {
+                    Some(renet) => {
+                        for message in renet.receive(addr, &buf[..len], now) {
+                            handle_datagram(&message, addr, &socket, &state, &weapons).await;
+                        }
+                    }
+                    None => handle_datagram(&buf[..len], addr, &socket, &state, &weapons).await,
                 }
             }
+            Err(e) => {
+                log::error!("UDP recv error: {}", e);
+            }
         }
-    }))
+    }
+}
+
+/// Send the datagrams renet queued and forget connections it closed
+async fn flush_renet(renet: Arc<RenetTransport>, state: Arc<ServerState>, socket: Arc<UdpSocket>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(10));
+    let mut last = std::time::Instant::now();
+    loop {
+        interval.tick().await;
+        let now = std::time::Instant::now();
+        let (datagrams, closed) = renet.update(now - last, now);
+        last = now;
+        for (addr, datagram) in datagrams {
+            if let Err(e) = socket.send_to(&datagram, addr).await {
+                log::debug!("Failed to send renet packet to {}: {}", addr, e);
+            }
+        }
+        for addr in closed {
+            state.forget_client(&addr);
+        }
+    }
 }
 
-/// Create a new lobby and spawn its tick loop
+/// Create a new lobby with default settings and spawn its tick loop
 pub async fn create_lobby_with_tick(
     state: Arc<ServerState>,
     code: String,
@@ -117,20 +275,175 @@ pub async fn create_lobby_with_tick(
         return Err("Lobby already exists".into());
     }
 
-    // Create lobby
-    let lobby = Arc::new(RwLock::new(Lobby::new(code.clone(), max_players, scene.clone())));
+    let lobby = new_lobby(&state, code.clone(), max_players, scene, &config);
+    spawn_lobby(&state, lobby, weapons, config, socket, None);
+    Ok(())
+}
+
+/// Create a lobby with its mode, teams and bots set before its tick loop starts
+/// Persistent lobbies are remembered so `run_persistent_lobbies` can recreate them
+pub fn create_lobby_from_spec(
+    state: &Arc<ServerState>,
+    spec: LobbySpec,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    socket: Arc<UdpSocket>,
+) -> Result<(), &'static str> {
+    if state.lobby_exists(&spec.code) {
+        return Err("Lobby already exists");
+    }
+
+    let mut lobby = new_lobby(state, spec.code.clone(), spec.max_players, spec.scene.clone(), &config);
+    lobby.mode = spec.mode.clone();
+    lobby.team_count = spec.team_count;
+    lobby.dimensions = spec.dimensions;
+    lobby.persistent = spec.persistent;
+    lobby.hide_enemy_state = spec.hide_enemy_state;
+    lobby.rules.integrity = spec.integrity;
+    lobby.visibility = spec.visibility;
+    lobby.password = spec.password.clone();
+    lobby.hosted = spec.hosted;
+    lobby.record_replay = spec.record_replay;
+    lobby.bot_fill = spec.bot_fill;
+    lobby.tick_rate_hz = spec.tick_rate_hz;
+    teams::reset_scores(&mut lobby);
+    for i in 0..spec.bots {
+        let bot_id = state.next_player_id();
+        if let Err(e) = lobbies::add_bot(&mut lobby, bot_id, format!("Bot {}", i + 1), &weapons) {
+            log::warn!("Failed to add bot to lobby {}: {}", spec.code, e);
+        }
+    }
+    if spec.persistent {
+        state.persistent_lobbies.insert(spec.code.clone(), spec);
+    }
+    spawn_lobby(state, lobby, weapons, config, socket, None);
+    Ok(())
+}
+
+/// Pre-create a lobby for matchmaking: it joins the scene's warm pool with its tick loop
+/// asleep until the pool hands it out
+pub fn create_warm_lobby(
+    state: Arc<ServerState>,
+    scene: String,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    socket: Arc<UdpSocket>,
+) -> String {
+    let mut code = state.warm_pool.next_code();
+    while state.lobby_exists(&code) {
+        code = state.warm_pool.next_code();
+    }
+
+    // Pooled before it is registered, so the lobby browser never lists it
+    let lobby = new_lobby(&state, code.clone(), config.matchmake_max_players, scene.clone(), &config);
+    let wake = Arc::new(Notify::new());
+    state.warm_pool.add(&scene, WarmLobby { code: code.clone(), wake: wake.clone() });
+    spawn_lobby(&state, lobby, weapons, config, socket, Some(wake));
+    code
+}
+
+/// A lobby with the server config applied
+pub(crate) fn new_lobby(state: &ServerState, code: String, max_players: u32, scene: String, config: &Config) -> Lobby {
+    let mut new_lobby = Lobby::new(code.clone(), max_players, scene.clone());
+    new_lobby.quantize_positions = config.quantize_positions;
+    new_lobby.friendly_fire = config.friendly_fire;
+    new_lobby.hide_enemy_state = config.hide_enemy_state;
+    new_lobby.max_spectators = config.max_spectators;
+    new_lobby.tunables = state.tunables.get();
+    new_lobby.max_rewind = Duration::from_millis(config.max_rewind_ms);
+    new_lobby.spawn_points = state.scenes.spawn_points(&scene);
+    new_lobby.enemy_spawn_points = state.scenes.enemy_spawn_points(&scene);
+    if new_lobby.spawn_points.is_empty() {
+        log::warn!("Scene {} has no spawn points, lobby {} spawns at the origin", scene, code);
+    }
+    new_lobby.rules = MatchRules {
+        min_players: config.match_min_players,
+        ready_quorum: config.match_ready_quorum_percent.min(100),
+        countdown: std::time::Duration::from_secs(config.match_countdown_secs),
+        round_duration: std::time::Duration::from_secs(config.round_duration_secs),
+        rounds_per_match: config.rounds_per_match,
+        score_limit: config.score_limit,
+        intermission: std::time::Duration::from_secs(config.round_intermission_secs),
+        results_duration: std::time::Duration::from_secs(config.match_results_secs),
+        horde: HordeRules {
+            waves: config.horde_waves,
+            lives: config.horde_lives,
+            first_wave_enemies: config.horde_first_wave_enemies,
+            enemies_per_wave: config.horde_enemies_per_wave,
+            health_per_wave: config.horde_health_per_wave,
+            wave_break: std::time::Duration::from_secs(config.horde_wave_break_secs),
+        },
+        integrity: IntegrityRules { max_fov: config.max_client_fov, aim_assist: config.aim_assist_allowed },
+    };
+    let pool = config
+        .mutators
+        .iter()
+        .filter_map(|name| {
+            let mutator = Mutator::parse(name);
+            if mutator.is_none() {
+                log::warn!("Unknown mutator {} in config", name);
+            }
+            mutator
+        })
+        .collect();
+    new_lobby.mutators = MutatorSchedule::new(
+        pool,
+        Duration::from_secs(config.mutator_interval_secs),
+        Duration::from_secs(config.mutator_duration_secs),
+        MatchRng::for_lobby(&code, state.clock.now()),
+    );
+    new_lobby
+}
+
+/// Register a lobby and spawn its tick loop
+/// With `wake`, the tick loop sleeps until it is notified (warm pool lobbies)
+/// The lobby gets a UDP socket of its own when `lobby_udp_port_min` is set and a port is free
+fn spawn_lobby(
+    state: &Arc<ServerState>,
+    mut new_lobby: Lobby,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    socket: Arc<UdpSocket>,
+    wake: Option<Arc<Notify>>,
+) {
+    let code = new_lobby.code.clone();
+    let mut udp_tasks = Vec::new();
+    let socket = match state.lobby_ports.bind() {
+        Some((port, dedicated)) => match UdpSocket::from_std(dedicated) {
+            Ok(dedicated) => {
+                let dedicated = Arc::new(dedicated);
+                new_lobby.udp_port = Some(port);
+                let limiter = RateLimiter::new(&config);
+                let receiver = receive_datagrams(dedicated.clone(), state.clone(), weapons.clone(), limiter, None, Some(code.clone()));
+                udp_tasks.push(tokio::spawn(receiver));
+                dedicated
+            }
+            Err(e) => {
+                log::warn!("Lobby {} can't use UDP port {}: {}", code, port, e);
+                state.lobby_ports.release(port);
+                socket
+            }
+        },
+        None => socket,
+    };
+    let (outbound, sender) = OutboundQueue::spawn(socket.clone(), Some(state.clone()));
+    if new_lobby.udp_port.is_some() {
+        udp_tasks.push(sender);
+    }
+    let listing = Snapshot::new(LobbyListing::of(&new_lobby));
+    let lobby = Arc::new(RwLock::new(new_lobby));
 
     // Create command channel
     let (tx, rx) = mpsc::channel::<crate::state::commands::LobbyCommand>(1000);
 
     // Spawn tick loop
-    let tick_weapons = weapons.clone();
-    let tick_config = config.clone();
-    let tick_socket = socket.clone();
     let tick_lobby = lobby.clone();
     let tick_state = state.clone();
     let task_handle = tokio::spawn(async move {
-        lobby_tick_loop(tick_lobby, rx, tick_socket, tick_weapons, tick_config, Some(tick_state)).await;
+        if let Some(wake) = wake {
+            wake.notified().await;
+        }
+        lobby_tick_loop(tick_lobby, rx, outbound, weapons, config, Some(tick_state)).await;
     });
 
     // Create handle
@@ -138,12 +451,263 @@ pub async fn create_lobby_with_tick(
         lobby,
         command_tx: tx,
         task_handle,
+        udp_tasks,
+        socket: Some(socket),
+        listing,
     };
 
     // Insert into state
     state.insert_lobby(code, handle);
+}
 
-    Ok(())
+/// Close a lobby: stop its tick loop, tell its clients and end their sessions, then persist and emit its
+/// analytics summary
+pub async fn close_lobby(state: &ServerState, code: &str, config: &Config) -> Option<LobbySummary> {
+    let handle = state.remove_lobby(code)?;
+    handle.task_handle.abort();
+    let _ = handle.task_handle.await;
+    let lobby = handle.lobby.read().await;
+    if let Some(socket) = &handle.socket {
+        notify_lobby_closed(state, socket, &lobby).await;
+    }
+    // Nothing may read or write the lobby's dedicated socket once its port is back in the pool
+    for task in handle.udp_tasks {
+        task.abort();
+        let _ = task.await;
+    }
+    if let Some(port) = lobby.udp_port {
+        state.lobby_ports.release(port);
+    }
+
+    // Same cleanup as players leaving, so their IP slots and session credentials don't outlive the lobby
+    for player_id in lobby.players.keys().chain(lobby.spectators.keys()) {
+        if let Some(addr) = lobby.client_addresses.get(player_id) {
+            state.forget_client(addr);
+        }
+        state.unregister_player(*player_id);
+        state.end_session(*player_id);
+        state.ip_sessions.release(*player_id);
+    }
+    let summary = LobbySummary::from_lobby(&lobby, SystemTime::now());
+    drop(lobby);
+    info!(
+        "Closed lobby {} after {}s: peak {} players, {} shots, {} kills, {} bytes sent, {:.2}ms avg tick",
+        summary.code,
+        summary.duration_secs,
+        summary.peak_players,
+        summary.total_shots,
+        summary.total_kills,
+        summary.bytes_sent,
+        summary.average_tick_ms
+    );
+
+    if let Some(path) = &config.lobby_analytics_path {
+        if let Err(e) = summary.append_to(path) {
+            log::warn!("Failed to persist analytics for lobby {}: {}", code, e);
+        }
+    }
+    if let Some(target) = &config.lobby_analytics_target {
+        let delivered = match ScalingSink::parse(target) {
+            Ok(sink) => sink.emit(&summary).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = delivered {
+            log::warn!("Failed to emit analytics for lobby {}: {}", code, e);
+        }
+    }
+    Some(summary)
+}
+
+/// Tell everyone still connected to a closing lobby that they were removed from it
+async fn notify_lobby_closed(state: &ServerState, socket: &UdpSocket, lobby: &Lobby) {
+    for (player_id, addr) in &lobby.client_addresses {
+        let mut packet = serde_json::json!({"type": "kicked", "player_id": player_id});
+        messages::insert_message(&mut packet, MessageCode::Kicked, serde_json::json!({"reason": LOBBY_CLOSED_REASON}), state.message_text());
+        let data = packet.to_string().into_bytes();
+        let sealed = state.seal_outbound(addr, &data);
+        if let Err(e) = state.send_datagram(socket, *addr, sealed.as_deref().unwrap_or(&data)).await {
+            log::debug!("Failed to tell {} that lobby {} closed: {}", addr, lobby.code, e);
+        }
+    }
+}
+
+/// Merge an under-populated lobby into another on the same scene and mode, then close it
+/// The moved players keep their state and are sent `migrate_to` by the target's tick loop
+/// Returns the moved player and spectator ids
+pub async fn merge_lobbies(state: &ServerState, source: &str, target: &str, config: &Config) -> Result<Vec<u32>, &'static str> {
+    if source == target {
+        return Err("Can't merge a lobby into itself");
+    }
+    let source_lobby = state.get_lobby(source).ok_or("Lobby not found")?;
+    let target_lobby = state.get_lobby(target).ok_or("Target lobby not found")?;
+    if state.warm_pool.is_idle(target) {
+        return Err("Target lobby isn't running");
+    }
+
+    let moved = {
+        // Locked in code order so opposite merges can't deadlock
+        let (mut source_guard, mut target_guard) = if source < target {
+            let source_guard = source_lobby.write().await;
+            (source_guard, target_lobby.write().await)
+        } else {
+            let target_guard = target_lobby.write().await;
+            (source_lobby.write().await, target_guard)
+        };
+        let moved = lobbies::merge_into(&mut source_guard, &mut target_guard, Instant::now())?;
+        state.publish_change(&target_guard);
+        moved
+    };
+    for id in &moved {
+        state.register_player_lobby(*id, target);
+    }
+    close_lobby(state, source, config).await;
+    info!("Merged lobby {} into {}: {} players and spectators moved", source, target, moved.len());
+    Ok(moved)
+}
+
+/// How often shutdown checks whether the lobbies emptied
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shut down gracefully: stop taking lobbies and joins, send every client `server_shutdown`,
+/// wait up to `shutdown_grace_secs` for human players to leave, then checkpoint whoever stayed
+/// to `storage` (so they can reconnect after the restart) and close every lobby
+pub async fn graceful_shutdown(state: &ServerState, config: &Config, storage: Option<&Storage>) {
+    state.set_draining(true);
+    let grace = Duration::from_secs(config.shutdown_grace_secs);
+    let notified = state
+        .lobbies_where(|_| true)
+        .filter(|listing| state.inject(&listing.code, LobbyCommand::Shutdown { grace_secs: config.shutdown_grace_secs }))
+        .count();
+    info!("Shutting down: {} lobbies notified, waiting up to {:?} for players to leave", notified, grace);
+
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        let humans = human_player_count(state).await;
+        if humans == 0 {
+            break;
+        }
+        log::debug!("Shutdown waiting on {} players", humans);
+        tokio::time::sleep(SHUTDOWN_POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+    }
+    if let Some(storage) = storage {
+        match storage::checkpoint_lobbies(state, storage).await {
+            Ok(count) => info!("Checkpointed {} lobbies", count),
+            Err(e) => log::warn!("Final lobby checkpoint failed: {}", e),
+        }
+    }
+    close_all_lobbies(state, config).await;
+}
+
+/// Players across all lobbies, bots excluded
+async fn human_player_count(state: &ServerState) -> usize {
+    let lobbies: Vec<Arc<RwLock<Lobby>>> = state.iter_lobbies().map(|entry| entry.lobby.clone()).collect();
+    let mut count = 0;
+    for lobby in lobbies {
+        let lobby = lobby.read().await;
+        count += lobby.players.len().saturating_sub(lobby.bots.len());
+    }
+    count
+}
+
+/// Close every lobby (server shutdown)
+pub async fn close_all_lobbies(state: &ServerState, config: &Config) {
+    // Nothing is recreated past this point
+    state.persistent_lobbies.clear();
+    let codes: Vec<String> = state.iter_lobbies().map(|entry| entry.key().clone()).collect();
+    for code in codes {
+        close_lobby(state, &code, config).await;
+    }
+}
+
+/// Close lobbies that have had no human players for at least `idle_for`
+/// Persistent lobbies, idle warm pool lobbies and lobbies holding rematch slots are kept
+/// Returns the closed codes
+pub async fn close_empty_lobbies(state: &ServerState, config: &Config, idle_for: Duration) -> Vec<String> {
+    let lobbies: Vec<(String, Arc<RwLock<Lobby>>)> = state
+        .iter_lobbies()
+        .map(|entry| (entry.key().clone(), entry.lobby.clone()))
+        .collect();
+
+    let mut closed = Vec::new();
+    for (code, lobby) in lobbies {
+        if state.warm_pool.is_idle(&code) {
+            continue;
+        }
+        let idle = {
+            let lobby = lobby.read().await;
+            !lobby.persistent
+                && lobby.players.len() == lobby.bots.len()
+                && lobbies::held_reservations(&lobby, Instant::now()) == 0
+                && lobby.analytics.last_occupied.elapsed() >= idle_for
+        };
+        if idle && close_lobby(state, &code, config).await.is_some() {
+            closed.push(code);
+        }
+    }
+    closed
+}
+
+/// Periodically close lobbies that have had no human players for `empty_lobby_close_secs`
+pub async fn run_lobby_reaper(state: Arc<ServerState>, config: Arc<Config>) {
+    let timeout = Duration::from_secs(config.empty_lobby_close_secs);
+    let mut timer = tokio::time::interval(timeout.min(Duration::from_secs(30)));
+    loop {
+        timer.tick().await;
+        close_empty_lobbies(&state, &config, timeout).await;
+    }
+}
+
+/// Recreate persistent lobbies that were removed (e.g. closed by an operator or after a crash
+/// of their tick loop), so clients joining them by code keep working; idle while draining
+pub async fn run_persistent_lobbies(
+    state: Arc<ServerState>,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    socket: Arc<UdpSocket>,
+) {
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        timer.tick().await;
+        if state.is_draining() {
+            continue;
+        }
+        let missing: Vec<LobbySpec> = state
+            .persistent_lobbies
+            .iter()
+            .filter(|spec| !state.lobby_exists(spec.key()))
+            .map(|spec| spec.value().clone())
+            .collect();
+        for spec in missing {
+            let code = spec.code.clone();
+            match create_lobby_from_spec(&state, spec, weapons.clone(), config.clone(), socket.clone()) {
+                Ok(()) => log::warn!("Recreated persistent lobby {}", code),
+                Err(e) => log::warn!("Failed to recreate persistent lobby {}: {}", code, e),
+            }
+        }
+    }
+}
+
+/// Keep every `warm_pool_scenes` scene stocked with `warm_pool_size` idle lobbies
+/// Refills shortly after matchmaking takes one; stops creating lobbies while draining
+pub async fn run_warm_pool(
+    state: Arc<ServerState>,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    socket: Arc<UdpSocket>,
+) {
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        timer.tick().await;
+        if state.is_draining() {
+            continue;
+        }
+        for scene in &config.warm_pool_scenes {
+            while state.warm_pool.idle_count(scene) < config.warm_pool_size {
+                let code = create_warm_lobby(state.clone(), scene.clone(), weapons.clone(), config.clone(), socket.clone());
+                log::debug!("Pre-created lobby {} for scene {}", code, scene);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -157,6 +721,21 @@ mod integration_tests {
     use crate::state::commands::LobbyCommand;
     use crate::utils::weapondb::WeaponDb;
     use crate::utils::config::Config;
+    use crate::state::lobby::{Dimensions, LobbySpec, Visibility};
+    use crate::domain::anticheat::IntegrityRules;
+    use crate::domain::gungame::GameMode;
+    use crate::domain::clock::ManualClock;
+
+    /// Wait for the tick loop to bring the lobby to `done`; fails after a second
+    async fn wait_until(lobby: &tokio::sync::RwLock<Lobby>, done: impl Fn(&Lobby) -> bool) {
+        for _ in 0..1000 {
+            if done(&*lobby.read().await) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        panic!("lobby never got there");
+    }
 
     #[tokio::test]
     async fn test_full_lobby_lifecycle() {
@@ -195,6 +774,7 @@ mod integration_tests {
             name: "Player1".to_string(),
             addr: player1_addr,
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
 
         let player2_addr: std::net::SocketAddr = "127.0.0.1:9002".parse().unwrap();
         command_tx.send(LobbyCommand::PlayerJoin {
@@ -202,6 +782,7 @@ mod integration_tests {
             name: "Player2".to_string(),
             addr: player2_addr,
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 2 }).await.unwrap();
 
         tokio::time::sleep(Duration::from_millis(50)).await;
 
@@ -248,7 +829,9 @@ mod integration_tests {
 
         command_tx.send(LobbyCommand::Shoot {
             player_id: 1,
-            target_id: 2,
+            origin: (10.0, 5.0, 20.0),
+            direction: (-10.0, -4.0, -20.0), // Towards player 2 at (0, 1, 0)
+            client_time: None,
         }).await.unwrap();
 
         // Wait for tick to process (tick interval is 20ms, wait 2 ticks)
@@ -262,7 +845,11 @@ mod integration_tests {
         assert_eq!(player1.current_ammo, 19);
         drop(lobby);
 
-        // Weapon switch
+        // Weapon switch (to a granted weapon)
+        command_tx.send(LobbyCommand::GrantWeapon {
+            player_id: 1,
+            weapon_id: 2,
+        }).await.unwrap();
         command_tx.send(LobbyCommand::WeaponSwitch {
             player_id: 1,
             weapon_id: 2,
@@ -291,7 +878,8 @@ mod integration_tests {
 
     #[tokio::test]
     async fn test_combat_chain_scenario() {
-        let state = Arc::new(ServerState::new());
+        let clock = Arc::new(ManualClock::new(std::time::Instant::now()));
+        let state = Arc::new(ServerState::with_clock(clock.clone()));
         let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
         let weapons = Arc::new(WeaponDb::load());
         let config = Arc::new(Config::default());
@@ -316,6 +904,17 @@ mod integration_tests {
                 name: format!("Soldier{}", i),
                 addr: format!("127.0.0.1:{}", 9000 + i).parse().unwrap(),
             }).await.unwrap();
+            command_tx.send(LobbyCommand::LoadingComplete { player_id: i }).await.unwrap();
+        }
+        // Player 2 stands in front of player 1, player 3 off to the side
+        // (everyone spawns at the origin in this scene)
+        for (player_id, position) in [(2, (0.0, 1.0, -5.0)), (3, (5.0, 1.0, 5.0))] {
+            command_tx.send(LobbyCommand::PositionUpdate {
+                player_id,
+                position,
+                rotation: (0.0, 0.0, 0.0),
+                addr: format!("127.0.0.1:{}", 9000 + player_id).parse().unwrap(),
+            }).await.unwrap();
         }
         tokio::time::sleep(Duration::from_millis(100)).await;
 
@@ -324,10 +923,13 @@ mod integration_tests {
         for i in 0..5 {
             command_tx.send(LobbyCommand::Shoot {
                 player_id: 1,
-                target_id: 2,
+                origin: (0.0, 1.0, 0.0),
+                direction: (0.0, 0.0, -1.0),
+                client_time: None,
             }).await.unwrap();
-            // Wait for fire rate limit (250ms per shot for 4 shots/sec)
-            tokio::time::sleep(Duration::from_millis(260)).await;
+            wait_until(&lobby_arc, |lobby| lobby.players[&1].current_ammo == 19 - i).await;
+            // Past the fire rate limit (250ms per shot for 4 shots/sec) without waiting for it
+            clock.advance(Duration::from_millis(260));
         }
 
         let lobby = lobby_arc.read().await;
@@ -342,7 +944,8 @@ mod integration_tests {
 
     #[tokio::test]
     async fn test_reload_mechanic_flow() {
-        let state = Arc::new(ServerState::new());
+        let clock = Arc::new(ManualClock::new(std::time::Instant::now()));
+        let state = Arc::new(ServerState::with_clock(clock.clone()));
         let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
         let weapons = Arc::new(WeaponDb::load());
         let config = Arc::new(Config::default());
@@ -366,22 +969,21 @@ mod integration_tests {
             name: "Shooter".to_string(),
             addr: "127.0.0.1:9999".parse().unwrap(),
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
         tokio::time::sleep(Duration::from_millis(50)).await;
 
-        // Fire enough shots to empty ammo (20 shots with proper timing)
+        // Fire enough shots to empty ammo, stepping the clock past the fire rate limit between them
         for i in 0..20 {
             command_tx.send(LobbyCommand::Shoot {
                 player_id: 1,
-                target_id: 999,
+                origin: (0.0, 1.0, 0.0),
+                direction: (0.0, 0.0, -1.0),
+                client_time: None,
             }).await.unwrap();
-            // Wait for fire rate limit (250ms per shot for 4 shots/sec)
-            tokio::time::sleep(Duration::from_millis(300)).await;
+            wait_until(&lobby_arc, |lobby| lobby.players[&1].current_ammo == 19 - i).await;
+            clock.advance(Duration::from_millis(300));
         }
 
-        // Wait longer for all commands to be processed (account for tick timing)
-        // Each shot might take 1-2 ticks to process
-        tokio::time::sleep(Duration::from_millis(200)).await;
-
         let lobby = lobby_arc.read().await;
         let player = lobby.players.get(&1).unwrap();
         println!("Final ammo after 20 shots: {}", player.current_ammo);
@@ -393,12 +995,18 @@ mod integration_tests {
         command_tx.send(LobbyCommand::Reload {
             player_id: 1,
         }).await.unwrap();
-        tokio::time::sleep(Duration::from_millis(50)).await;
+        wait_until(&lobby_arc, |lobby| lobby.players[&1].is_reloading).await;
 
         let lobby = lobby_arc.read().await;
         let player = lobby.players.get(&1).unwrap();
         assert!(player.is_reloading);
         assert!(player.reload_end_time.is_some());
+        drop(lobby);
+
+        // The reload finishes as soon as the clock passes its end
+        clock.advance(Duration::from_secs(5));
+        wait_until(&lobby_arc, |lobby| !lobby.players[&1].is_reloading).await;
+        assert_eq!(lobby_arc.read().await.players[&1].current_ammo, 20);
     }
 
     #[tokio::test]
@@ -427,6 +1035,7 @@ mod integration_tests {
             name: "Switcher".to_string(),
             addr: "127.0.0.1:8888".parse().unwrap(),
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
         tokio::time::sleep(Duration::from_millis(50)).await;
 
         // Verify initial state (Golden Friend)
@@ -436,7 +1045,19 @@ mod integration_tests {
         assert_eq!(player.current_ammo, 20);
         drop(lobby);
 
-        // Switch to Prototype
+        // Not owned yet
+        command_tx.send(LobbyCommand::WeaponSwitch {
+            player_id: 1,
+            weapon_id: 2,
+        }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(lobby_arc.read().await.players.get(&1).unwrap().current_weapon_id, 1);
+
+        // Switch to Prototype once granted
+        command_tx.send(LobbyCommand::GrantWeapon {
+            player_id: 1,
+            weapon_id: 2,
+        }).await.unwrap();
         command_tx.send(LobbyCommand::WeaponSwitch {
             player_id: 1,
             weapon_id: 2,
@@ -476,6 +1097,7 @@ mod integration_tests {
             name: "Runner".to_string(),
             addr: "127.0.0.1:7777".parse().unwrap(),
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
         tokio::time::sleep(Duration::from_millis(50)).await;
 
         // Rapid position updates
@@ -530,6 +1152,7 @@ mod integration_tests {
             name: "HeartbeatPlayer".to_string(),
             addr: "127.0.0.1:6666".parse().unwrap(),
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
         tokio::time::sleep(Duration::from_millis(50)).await;
 
         // Get initial update time
@@ -579,6 +1202,7 @@ mod integration_tests {
             name: "UdpPlayer".to_string(),
             addr: "192.168.1.100:5000".parse().unwrap(),
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
         tokio::time::sleep(Duration::from_millis(50)).await;
 
         // Verify player exists
@@ -626,6 +1250,7 @@ mod integration_tests {
                 name: format!("Player{}", i),
                 addr: format!("127.0.0.1:{}", 8000 + i).parse().unwrap(),
             }).await.unwrap();
+            command_tx.send(LobbyCommand::LoadingComplete { player_id: i }).await.unwrap();
         }
         tokio::time::sleep(Duration::from_millis(100)).await;
 
@@ -676,6 +1301,7 @@ mod integration_tests {
             name: "DirtyPlayer".to_string(),
             addr: "127.0.0.1:5555".parse().unwrap(),
         }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
 
         // Wait for tick to process the join
         tokio::time::sleep(Duration::from_millis(50)).await;
@@ -708,4 +1334,349 @@ mod integration_tests {
         assert_ne!(player.position, initial_position, "Position should have changed");
         assert_eq!(player.position, (100.0, 50.0, 100.0), "Position should be new value");
     }
+
+    #[tokio::test]
+    async fn test_udp_join_enforces_sessions_per_ip() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config::default());
+        state.ip_sessions.configure(2, &[]);
+
+        super::create_lobby_with_tick(
+            state.clone(),
+            "IP_CAP".to_string(),
+            8,
+            "test".to_string(),
+            weapons.clone(),
+            config.clone(),
+            udp_socket.clone(),
+        ).await.unwrap();
+
+        let command_tx = state.get_lobby_tx("IP_CAP").unwrap();
+        let lobby_arc = state.get_lobby("IP_CAP").unwrap();
+
+        // Three players joined over HTTP, all behind one IP
+        for i in 1..=3 {
+            command_tx.send(LobbyCommand::PlayerJoin {
+                player_id: i,
+                name: format!("Farm{}", i),
+                addr: format!("127.0.0.1:{}", 9000 + i).parse().unwrap(),
+            }).await.unwrap();
+            command_tx.send(LobbyCommand::LoadingComplete { player_id: i }).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        lobby_arc.write().await.client_addresses.clear();
+
+        for i in 1..=3u32 {
+            let join = serde_json::json!({"type": "join", "lobby_code": "IP_CAP", "player_id": i});
+            let addr = format!("10.1.1.1:{}", 7000 + i).parse().unwrap();
+            crate::handlers::udp::handle_udp_packet(serde_json::from_value(join).unwrap(), addr, &udp_socket, &state, &weapons).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let lobby = lobby_arc.read().await;
+        assert!(lobby.client_addresses.contains_key(&1));
+        assert!(lobby.client_addresses.contains_key(&2));
+        assert!(!lobby.client_addresses.contains_key(&3));
+        drop(lobby);
+        assert_eq!(state.ip_sessions.rejections(), 1);
+
+        // A leave frees the slot
+        command_tx.send(LobbyCommand::PlayerLeave { player_id: 1 }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let join = serde_json::json!({"type": "join", "lobby_code": "IP_CAP", "player_id": 3});
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(join).unwrap(), "10.1.1.1:7003".parse().unwrap(), &udp_socket, &state, &weapons).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(lobby_arc.read().await.client_addresses.contains_key(&3));
+    }
+
+    #[tokio::test]
+    async fn test_udp_join_negotiates_protocol_version() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config::default());
+        super::create_lobby_with_tick(
+            state.clone(),
+            "PROTO".to_string(),
+            8,
+            "test".to_string(),
+            weapons.clone(),
+            config.clone(),
+            udp_socket.clone(),
+        ).await.unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = client.local_addr().unwrap();
+        // The lobby's tick loop sends to the same address, so skip its packets
+        async fn recv(client: &UdpSocket, wanted: &str) -> serde_json::Value {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (len, _) = tokio::time::timeout(Duration::from_secs(1), client.recv_from(&mut buf)).await.unwrap().unwrap();
+                let packet: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+                if packet["type"] == wanted {
+                    return packet;
+                }
+            }
+        }
+
+        let join = serde_json::json!({"type": "join", "lobby_code": "PROTO", "player_id": 1, "protocol_version": 7, "capabilities": 1 << 8});
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(join).unwrap(), addr, &udp_socket, &state, &weapons).await;
+        let welcome = recv(&client, "welcome").await;
+        assert_eq!(welcome["type"], "welcome");
+        assert_eq!(welcome["protocol_version"], crate::net::protocol::CURRENT_PROTOCOL_VERSION);
+        assert_eq!((welcome["min_protocol_version"].as_u64(), welcome["max_protocol_version"].as_u64()), (Some(1), Some(3)));
+        assert_eq!(welcome["features"], serde_json::json!(["voice"]));
+
+        // A client that only speaks newer versions is told which ones the server does
+        let join = serde_json::json!({"type": "join", "lobby_code": "PROTO", "player_id": 2, "protocol_version": 7, "min_protocol_version": 5});
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(join).unwrap(), addr, &udp_socket, &state, &weapons).await;
+        let error = recv(&client, "error").await;
+        assert_eq!(error["code"], "ERR_PROTOCOL_UNSUPPORTED");
+        assert_eq!(error["params"], serde_json::json!({"min_version": 1, "max_version": 3}));
+        assert_eq!(state.ip_sessions.session_count(&addr.ip()), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rebinding_requires_token_and_notifies_old_address() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let old_client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let old_addr = old_client.local_addr().unwrap();
+        let new_addr = "127.0.0.1:9".parse().unwrap();
+        let token = state.create_session_token(1);
+
+        // A spoofed first packet without the token doesn't bind the session to its address
+        let keepalive = serde_json::json!({"type": "keepalive", "player_id": 1});
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(keepalive.clone()).unwrap(), new_addr, &udp_socket, &state, &weapons).await;
+        assert!(state.authorize_source(1, new_addr, None, false).is_err());
+
+        let first = serde_json::json!({"type": "keepalive", "player_id": 1, "session_token": token});
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(first).unwrap(), old_addr, &udp_socket, &state, &weapons).await;
+        assert!(state.authorize_source(1, old_addr, None, false).is_ok());
+
+        // Without the token the new address can't take over the session
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(keepalive).unwrap(), new_addr, &udp_socket, &state, &weapons).await;
+        assert!(state.authorize_source(1, new_addr, None, false).is_err());
+
+        let keepalive = serde_json::json!({"type": "keepalive", "player_id": 1, "session_token": token});
+        crate::handlers::udp::handle_udp_packet(serde_json::from_value(keepalive).unwrap(), new_addr, &udp_socket, &state, &weapons).await;
+        assert!(state.authorize_source(1, new_addr, None, false).is_ok());
+
+        let mut buf = [0u8; 256];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(1), old_client.recv_from(&mut buf)).await.unwrap().unwrap();
+        let notice: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!(notice, serde_json::json!({"type": "session_moved", "player_id": 1}));
+    }
+
+    #[tokio::test]
+    async fn test_lobby_gets_dedicated_udp_port() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config { lobby_analytics_path: None, ..Config::default() });
+        let free = std::net::UdpSocket::bind("0.0.0.0:0").unwrap().local_addr().unwrap().port();
+        state.lobby_ports.configure(free, free);
+
+        for code in ["OWN", "SHARED"] {
+            super::create_lobby_with_tick(state.clone(), code.to_string(), 4, "test".to_string(), weapons.clone(), config.clone(), udp_socket.clone()).await.unwrap();
+        }
+        assert_eq!(state.lobby_listing("OWN").unwrap().udp_port, Some(free));
+        assert_eq!(state.lobby_listing("SHARED").unwrap().udp_port, None); // Range exhausted
+
+        // The lobby's clients talk to it on its own port
+        let command_tx = state.get_lobby_tx("OWN").unwrap();
+        command_tx.send(LobbyCommand::PlayerJoin { player_id: 1, name: "Ada".to_string(), addr: "127.0.0.1:1".parse().unwrap() }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(("127.0.0.1", free)).await.unwrap();
+        client.send(serde_json::json!({"type": "join", "lobby_code": "OWN", "player_id": 1}).to_string().as_bytes()).await.unwrap();
+        let mut buf = [0u8; 1500];
+        let mut replies = Vec::new();
+        while !replies.contains(&"udp_connected".to_string()) {
+            let len = tokio::time::timeout(Duration::from_secs(2), client.recv(&mut buf)).await.unwrap().unwrap();
+            let reply: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+            replies.push(reply["type"].as_str().unwrap_or_default().to_string());
+        }
+        assert_eq!(replies[0], "welcome"); // From the UDP handler, the tick loop sends the rest
+
+        // Other lobbies' players get nothing out of the port, not even acks
+        state.register_player_lobby(2, "SHARED");
+        let outsider = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        outsider.connect(("127.0.0.1", free)).await.unwrap();
+        for packet in [
+            serde_json::json!({"type": "keepalive", "player_id": 2, "reliable": true, "seq": 1}),
+            serde_json::json!({"type": "join", "lobby_code": "SHARED", "player_id": 2}),
+            serde_json::json!({"type": "keepalive", "player_id": 3, "reliable": true, "seq": 2}),
+        ] {
+            outsider.send(packet.to_string().as_bytes()).await.unwrap();
+        }
+        assert!(tokio::time::timeout(Duration::from_millis(200), outsider.recv(&mut buf)).await.is_err());
+
+        // Closing the lobby frees the port for the next one
+        super::close_lobby(&state, "OWN", &config).await.unwrap();
+        super::create_lobby_with_tick(state.clone(), "NEXT".to_string(), 4, "test".to_string(), weapons.clone(), config.clone(), udp_socket.clone()).await.unwrap();
+        assert_eq!(state.lobby_listing("NEXT").unwrap().udp_port, Some(free));
+    }
+
+    #[tokio::test]
+    async fn test_idle_lobby_ticks_slowly_until_a_client_connects() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config { lobby_analytics_path: None, ..Config::default() });
+        super::create_lobby_with_tick(state.clone(), "IDLE".to_string(), 4, "test".to_string(), weapons, config.clone(), udp_socket).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // A command wakes the lobby at once rather than at its next idle tick
+        let command_tx = state.get_lobby_tx("IDLE").unwrap();
+        command_tx.send(LobbyCommand::PlayerJoin { player_id: 1, name: "Ada".to_string(), addr: "127.0.0.1:8101".parse().unwrap() }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(state.lobby_listing("IDLE").unwrap().players.len(), 1);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // About 1 idle tick, then 50Hz for the last 300ms
+        let summary = super::close_lobby(&state, "IDLE", &config).await.unwrap();
+        assert!((5..25).contains(&summary.ticks), "{} ticks", summary.ticks);
+    }
+
+    #[tokio::test]
+    async fn test_close_lobby_records_analytics() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let path = std::env::temp_dir().join(format!("gungame-close-{}.jsonl", std::process::id()));
+        let config = Arc::new(Config {
+            lobby_analytics_path: Some(path.to_str().unwrap().to_string()),
+            ..Config::default()
+        });
+
+        super::create_lobby_with_tick(
+            state.clone(),
+            "ANALYTICS".to_string(),
+            4,
+            "test".to_string(),
+            weapons.clone(),
+            config.clone(),
+            udp_socket.clone(),
+        ).await.unwrap();
+        let command_tx = state.get_lobby_tx("ANALYTICS").unwrap();
+
+        for i in 1..=2 {
+            command_tx.send(LobbyCommand::PlayerJoin {
+                player_id: i,
+                name: format!("Player{}", i),
+                addr: format!("127.0.0.1:{}", 8100 + i).parse().unwrap(),
+            }).await.unwrap();
+            command_tx.send(LobbyCommand::LoadingComplete { player_id: i }).await.unwrap();
+        }
+        command_tx.send(LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: (0.0, 0.0, -1.0), client_time: None }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let summary = super::close_lobby(&state, "ANALYTICS", &config).await.unwrap();
+        assert!(!state.lobby_exists("ANALYTICS"));
+        assert_eq!(summary.peak_players, 2);
+        assert_eq!(summary.total_shots, 1);
+        assert!(summary.bytes_sent > 0); // Welcome messages and join broadcasts
+        assert!(summary.ticks > 0);
+        assert!(super::close_lobby(&state, "ANALYTICS", &config).await.is_none());
+
+        let persisted: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(persisted["code"], "ANALYTICS");
+        assert_eq!(persisted["total_shots"], 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_graceful_shutdown_notifies_and_waits_for_players() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config { shutdown_grace_secs: 30, ..Config::default() });
+
+        super::create_lobby_with_tick(state.clone(), "SHUTDOWN".to_string(), 4, "test".to_string(), weapons, config.clone(), udp_socket)
+            .await
+            .unwrap();
+        let command_tx = state.get_lobby_tx("SHUTDOWN").unwrap();
+        command_tx.send(LobbyCommand::PlayerJoin { player_id: 1, name: "Ada".to_string(), addr: client.local_addr().unwrap() }).await.unwrap();
+        command_tx.send(LobbyCommand::LoadingComplete { player_id: 1 }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let shutdown = tokio::spawn({
+            let (state, config) = (state.clone(), config.clone());
+            async move { super::graceful_shutdown(&state, &config, None).await }
+        });
+
+        // The client is told, and leaving lets shutdown finish long before the grace period
+        let mut buf = [0u8; 2048];
+        let notice = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                let (len, _) = client.recv_from(&mut buf).await.unwrap();
+                let Ok(packet) = serde_json::from_slice::<serde_json::Value>(&buf[..len]) else {
+                    continue;
+                };
+                if packet["type"] == "server_shutdown" {
+                    return packet;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(notice["grace_secs"], 30);
+        assert!(state.is_draining());
+        assert!(state.lobby_exists("SHUTDOWN"));
+
+        command_tx.send(LobbyCommand::PlayerLeave { player_id: 1 }).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(2), shutdown).await.unwrap().unwrap();
+        assert!(!state.lobby_exists("SHUTDOWN"));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_lobby_survives_cleanup() {
+        let state = Arc::new(ServerState::new());
+        let udp_socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config { lobby_analytics_path: None, empty_lobby_close_secs: 1, ..Config::default() });
+        let spec = LobbySpec {
+            code: "test".to_string(),
+            max_players: 8,
+            scene: "test_world".to_string(),
+            mode: GameMode::Ffa,
+            team_count: 0,
+            dimensions: Dimensions::Three,
+            bots: 2,
+            bot_fill: 0,
+            tick_rate_hz: None,
+            persistent: true,
+            hide_enemy_state: false,
+            integrity: IntegrityRules::default(),
+            visibility: Visibility::Public,
+            password: None,
+            hosted: false,
+            record_replay: false,
+        };
+        super::create_lobby_from_spec(&state, spec, weapons.clone(), config.clone(), udp_socket.clone()).unwrap();
+        super::create_lobby_with_tick(state.clone(), "EMPTY".to_string(), 4, "world".to_string(), weapons.clone(), config.clone(), udp_socket.clone()).await.unwrap();
+        for code in ["test", "EMPTY"] {
+            state.get_lobby(code).unwrap().write().await.analytics.last_occupied = std::time::Instant::now() - Duration::from_secs(2);
+        }
+
+        // The reaper closes empty lobbies but leaves the persistent one alone
+        let reaper = tokio::spawn(super::run_lobby_reaper(state.clone(), config.clone()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        reaper.abort();
+        assert!(!state.lobby_exists("EMPTY"));
+        assert!(state.lobby_exists("test"));
+
+        // Removed anyway: it comes back with its bots
+        super::close_lobby(&state, "test", &config).await.unwrap();
+        let recreate = tokio::spawn(super::run_persistent_lobbies(state.clone(), weapons, config.clone(), udp_socket));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        recreate.abort();
+        let lobby = state.get_lobby("test").unwrap();
+        assert_eq!(lobby.read().await.bots.len(), 2);
+        assert!(lobby.read().await.persistent);
+    }
 }
@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use crate::domain::bot;
+use crate::domain::gungame::{self, GameMode, GunGame, Progress};
+use crate::domain::collision::Aabb;
+use crate::domain::logic::{self, KillEvent};
+use crate::domain::lobbies;
+use crate::state::lobby::Lobby;
+use crate::utils::config::Config;
+use crate::utils::weapondb::WeaponDb;
+
+/// Radius of the circle bots spawn on
+const SPAWN_RADIUS: f32 = 30.0;
+
+/// Half the width of the square simulated arena
+const ARENA_HALF_SIZE: f32 = 40.0;
+
+/// Record a state hash every this many ticks
+pub const CHECKPOINT_TICKS: u64 = 1_000;
+
+/// Game mode for a simulated match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationMode {
+    /// Free for all, everyone keeps their weapon
+    Ffa,
+    /// Each kill advances the killer to the next weapon, ending with the knife
+    GunGame,
+}
+
+impl SimulationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SimulationMode::Ffa => "ffa",
+            SimulationMode::GunGame => "gun_game",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ffa" => Some(SimulationMode::Ffa),
+            "gun_game" => Some(SimulationMode::GunGame),
+            _ => None,
+        }
+    }
+}
+
+/// Options for `simulate-match`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationOptions {
+    pub bots: u32,
+    pub mode: SimulationMode,
+    pub ticks: u64,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            bots: 8,
+            mode: SimulationMode::Ffa,
+            ticks: 100_000,
+        }
+    }
+}
+
+impl SimulationOptions {
+    /// Parse `--bots N --mode ffa|gun_game --ticks N` (arguments after the subcommand)
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let value = iter.next().ok_or_else(|| format!("Missing value for {}", flag))?;
+            match flag.as_str() {
+                "--bots" => {
+                    options.bots = value.parse().map_err(|_| format!("Invalid bot count: {}", value))?;
+                }
+                "--ticks" => {
+                    options.ticks = value.parse().map_err(|_| format!("Invalid tick count: {}", value))?;
+                }
+                "--mode" => {
+                    options.mode = SimulationMode::parse(value)
+                        .ok_or_else(|| format!("Unknown mode: {} (expected ffa or gun_game)", value))?;
+                }
+                _ => return Err(format!("Unknown flag: {}", flag)),
+            }
+        }
+        if options.bots < 2 {
+            return Err("Need at least 2 bots".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// Balance statistics collected during a simulated match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationReport {
+    pub ticks: u64,
+    pub total_kills: u32,
+    pub kills_by_weapon: BTreeMap<String, u32>,
+    pub kill_times_by_weapon: BTreeMap<String, Vec<f32>>, // Seconds from first hit to kill
+    pub matches_won: u32, // Gun game only: players that finished the ladder
+    pub checkpoints: Vec<u64>, // State hash every CHECKPOINT_TICKS ticks
+    pub final_hash: u64, // State hash after the last tick
+}
+
+impl SimulationReport {
+    /// Average time-to-kill in seconds for a weapon
+    pub fn average_time_to_kill(&self, weapon: &str) -> Option<f32> {
+        let times = self.kill_times_by_weapon.get(weapon)?;
+        if times.is_empty() {
+            return None;
+        }
+        Some(times.iter().sum::<f32>() / times.len() as f32)
+    }
+
+    /// Print the report to stdout
+    pub fn print(&self, tick_interval: Duration) {
+        println!("Simulated {} ticks ({:.0}s of game time)", self.ticks, self.ticks as f32 * tick_interval.as_secs_f32());
+        println!("Total kills: {}", self.total_kills);
+        if self.matches_won > 0 {
+            println!("Ladders completed: {}", self.matches_won);
+        }
+        println!("{:<20} {:>8} {:>10} {:>10}", "weapon", "kills", "share", "avg ttk");
+        for (weapon, kills) in &self.kills_by_weapon {
+            let share = *kills as f32 / self.total_kills.max(1) as f32 * 100.0;
+            let ttk = self
+                .average_time_to_kill(weapon)
+                .map(|t| format!("{:.2}s", t))
+                .unwrap_or_else(|| "-".to_string());
+            println!("{:<20} {:>8} {:>9.1}% {:>10}", weapon, kills, share, ttk);
+        }
+    }
+}
+
+fn spawn_point(index: u32, count: u32) -> (f32, f32, f32) {
+    let angle = index as f32 * std::f32::consts::TAU / count as f32;
+    (SPAWN_RADIUS * angle.cos(), 1.0, SPAWN_RADIUS * angle.sin())
+}
+
+/// Run a headless bot-only match
+/// Fully deterministic: time is virtual and advances by one tick interval per tick
+pub fn run(options: &SimulationOptions, weapons: &WeaponDb, config: &Config) -> Result<SimulationReport, &'static str> {
+    let tick_interval = Duration::from_millis(config.tick_interval_ms());
+    let dt = tick_interval.as_secs_f32();
+
+    let mut lobby = Lobby::new("simulation".to_string(), options.bots, "simulation".to_string());
+    lobby.bounds = Some(Aabb {
+        min: (-ARENA_HALF_SIZE, 0.0, -ARENA_HALF_SIZE),
+        max: (ARENA_HALF_SIZE, 10.0, ARENA_HALF_SIZE),
+    });
+
+    if options.mode == SimulationMode::GunGame {
+        lobby.mode = GameMode::GunGame(GunGame::new(gungame::default_ladder(weapons)));
+    }
+    lobby.spawn_points = (0..options.bots).map(|index| spawn_point(index, options.bots)).collect();
+
+    let bot_ids: Vec<u32> = (1..=options.bots).collect();
+    for (index, &bot_id) in bot_ids.iter().enumerate() {
+        lobbies::add_bot(&mut lobby, bot_id, format!("Bot {}", bot_id), weapons)?;
+        lobby.players.get_mut(&bot_id).unwrap().position = spawn_point(index as u32, options.bots);
+        if options.mode == SimulationMode::GunGame {
+            // Knife kills would skip straight up the ladder
+            if let Some(brain) = lobby.bots.get_mut(&bot_id) {
+                brain.melee_switching = false;
+            }
+        }
+    }
+
+    let mut report = SimulationReport { ticks: options.ticks, ..Default::default() };
+    let mut first_hit: HashMap<u32, Instant> = HashMap::new();
+    let mut now = Instant::now();
+
+    for tick in 1..=options.ticks {
+        now += tick_interval;
+
+        for &bot_id in &bot_ids {
+            let health_before: Vec<(u32, u32)> = bot_ids
+                .iter()
+                .filter_map(|id| lobby.players.get(id).map(|p| (*id, p.current_health)))
+                .collect();
+
+            if let Ok(bot::BotAction::Attack { target_id }) = bot::step(&mut lobby, weapons, bot_id, dt, now) {
+                let before = health_before.iter().find(|(id, _)| *id == target_id).map(|(_, h)| *h).unwrap_or(0);
+                let target = lobby.players.get(&target_id).ok_or("Target not found")?;
+                if target.current_health < before {
+                    first_hit.entry(target_id).or_insert(now);
+                }
+            }
+            lobby.shot_feed.clear(); // Effects only, nothing to simulate
+            lobby.damage_feed.clear();
+            for event in std::mem::take(&mut lobby.kill_feed) {
+                let killer_id = event.killer_id;
+                record_kill(&mut report, &mut first_hit, event, now);
+                if let Some(Progress::Won { .. }) = gungame::on_kill(&mut lobby, weapons, killer_id)? {
+                    report.matches_won += 1;
+                }
+            }
+        }
+
+        logic::update_reload_states_at(&mut lobby, now);
+        logic::respawn_due_players_at(&mut lobby, now);
+
+        lobby.clear_dirty();
+        if tick % CHECKPOINT_TICKS == 0 {
+            report.checkpoints.push(state_hash(&lobby));
+        }
+    }
+
+    report.final_hash = state_hash(&lobby);
+    Ok(report)
+}
+
+/// FNV-1a hash of the simulated outcome (positions, health, weapons, scores)
+/// Stable across builds and platforms, unlike `DefaultHasher`
+pub fn state_hash(lobby: &Lobby) -> u64 {
+    let mut ids: Vec<&u32> = lobby.players.keys().collect();
+    ids.sort_unstable();
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |value: u32| {
+        for byte in value.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    for id in ids {
+        let player = &lobby.players[id];
+        feed(player.id);
+        feed(player.position.0.to_bits());
+        feed(player.position.1.to_bits());
+        feed(player.position.2.to_bits());
+        feed(player.current_health);
+        feed(player.current_weapon_id);
+        feed(player.current_ammo);
+        feed(player.kills);
+        feed(player.deaths);
+        feed(player.score);
+    }
+    hash
+}
+
+fn record_kill(report: &mut SimulationReport, first_hit: &mut HashMap<u32, Instant>, event: KillEvent, now: Instant) {
+    let time_to_kill = first_hit
+        .remove(&event.victim_id)
+        .map(|hit| now.saturating_duration_since(hit))
+        .unwrap_or_default()
+        .as_secs_f32();
+
+    report.total_kills += 1;
+    *report.kills_by_weapon.entry(event.weapon_name.clone()).or_insert(0) += 1;
+    report.kill_times_by_weapon.entry(event.weapon_name).or_default().push(time_to_kill);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = SimulationOptions::from_args(&args(&["--bots", "4", "--mode", "gun_game", "--ticks", "500"])).unwrap();
+        assert_eq!(options, SimulationOptions { bots: 4, mode: SimulationMode::GunGame, ticks: 500 });
+        assert_eq!(SimulationOptions::from_args(&[]).unwrap(), SimulationOptions::default());
+
+        assert!(SimulationOptions::from_args(&args(&["--bots"])).is_err());
+        assert!(SimulationOptions::from_args(&args(&["--mode", "ctf"])).is_err());
+        assert!(SimulationOptions::from_args(&args(&["--bots", "1"])).is_err());
+    }
+
+    #[test]
+    fn test_simulation_is_deterministic_and_produces_kills() {
+        let weapons = WeaponDb::load();
+        let config = Config::default();
+        let options = SimulationOptions { bots: 4, mode: SimulationMode::Ffa, ticks: 3_000 };
+
+        let first = run(&options, &weapons, &config).unwrap();
+        let second = run(&options, &weapons, &config).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.checkpoints.len(), 3);
+        assert!(first.total_kills > 0);
+        assert_eq!(first.kills_by_weapon.values().sum::<u32>(), first.total_kills);
+    }
+
+    #[test]
+    fn test_gun_game_uses_ladder() {
+        let weapons = WeaponDb::load();
+        let options = SimulationOptions { bots: 4, mode: SimulationMode::GunGame, ticks: 5_000 };
+        let report = run(&options, &weapons, &Config::default()).unwrap();
+        // Kills happen with more than the starting weapon
+        assert!(report.kills_by_weapon.len() > 1);
+    }
+}
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use crate::domain::logic::{KillEvent, ShotEvent};
+use crate::domain::projectiles::ProjectileEvent;
+use crate::state::lobby::Lobby;
+
+/// How one weapon performed in a lobby
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WeaponUsage {
+    pub shots: u64, // Shots, swings and launched projectiles
+    pub kills: u64,
+    pub pickups: u64, // Times it was taken from a weapon pickup
+    pub timed_kills: u64, // Kills whose victim's first hit was seen
+    pub kill_time_ms: u64, // Sum of those kills' time from first hit to death
+}
+
+/// Usage counters accumulated over a lobby's lifetime
+#[derive(Debug)]
+pub struct LobbyAnalytics {
+    pub opened_at: SystemTime,
+    pub last_occupied: Instant, // Last tick a human player was in the lobby
+    pub peak_players: usize,
+    pub total_shots: u64,
+    pub total_kills: u64,
+    pub bytes_sent: AtomicU64, // Atomic: broadcasts only hold a shared borrow of the lobby
+    pub ticks: u64,
+    pub total_tick_time: Duration,
+    pub weapons: BTreeMap<u32, WeaponUsage>, // Weapon id -> usage, for the balance report
+    first_hits: HashMap<u32, Instant>, // Victim -> first hit taken since their last death
+}
+
+impl LobbyAnalytics {
+    pub fn new() -> Self {
+        Self {
+            opened_at: SystemTime::now(),
+            last_occupied: Instant::now(),
+            peak_players: 0,
+            total_shots: 0,
+            total_kills: 0,
+            bytes_sent: AtomicU64::new(0),
+            ticks: 0,
+            total_tick_time: Duration::ZERO,
+            weapons: BTreeMap::new(),
+            first_hits: HashMap::new(),
+        }
+    }
+
+    pub fn record_tick(&mut self, elapsed: Duration) {
+        self.ticks += 1;
+        self.total_tick_time += elapsed;
+    }
+
+    pub fn record_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// Count one tick's shots, hits and kills per weapon
+pub fn record_weapon_usage(lobby: &mut Lobby, shots: &[ShotEvent], projectiles: &[ProjectileEvent], kills: &[KillEvent], now: Instant) {
+    let analytics = &mut lobby.analytics;
+    for shot in shots {
+        analytics.weapons.entry(shot.weapon_id).or_default().shots += 1;
+        if let Some(shooter) = lobby.players.get_mut(&shot.shooter_id) {
+            shooter.shots_fired += 1;
+            shooter.shots_hit += shot.hit as u32;
+        }
+        if let Some(target_id) = shot.target_id.filter(|_| shot.hit) {
+            analytics.first_hits.entry(target_id).or_insert(now);
+        }
+    }
+    for projectile in projectiles {
+        match projectile {
+            ProjectileEvent::Spawned { owner_id, weapon_id, .. } => {
+                analytics.weapons.entry(*weapon_id).or_default().shots += 1;
+                if let Some(owner) = lobby.players.get_mut(owner_id) {
+                    owner.shots_fired += 1;
+                }
+            }
+            ProjectileEvent::Hit { owner_id, target_id: Some(target_id), .. } => {
+                analytics.first_hits.entry(*target_id).or_insert(now);
+                if let Some(owner) = lobby.players.get_mut(owner_id) {
+                    owner.shots_hit += 1;
+                }
+            }
+            ProjectileEvent::Hit { .. } => {}
+        }
+    }
+    for kill in kills {
+        let usage = analytics.weapons.entry(kill.weapon_id).or_default();
+        usage.kills += 1;
+        if let Some(first) = analytics.first_hits.remove(&kill.victim_id) {
+            usage.timed_kills += 1;
+            usage.kill_time_ms += now.saturating_duration_since(first).as_millis() as u64;
+        }
+    }
+    let players = &lobby.players;
+    analytics.first_hits.retain(|id, _| players.get(id).is_some_and(|p| !p.is_dead));
+}
+
+impl Default for LobbyAnalytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Analytics record written when a lobby closes (capacity planning, weapon balance)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LobbySummary {
+    pub code: String,
+    pub scene: String,
+    pub opened_at: u64, // Unix seconds
+    pub closed_at: u64,
+    pub duration_secs: u64,
+    pub peak_players: usize,
+    pub total_shots: u64,
+    pub total_kills: u64,
+    pub bytes_sent: u64,
+    pub ticks: u64,
+    pub average_tick_ms: f32,
+    #[serde(default)]
+    pub weapons: BTreeMap<u32, WeaponUsage>, // Weapon id -> usage (missing from older records)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl LobbySummary {
+    pub fn from_lobby(lobby: &Lobby, closed_at: SystemTime) -> Self {
+        let analytics = &lobby.analytics;
+        let average_tick_ms = if analytics.ticks == 0 {
+            0.0
+        } else {
+            analytics.total_tick_time.as_secs_f32() * 1000.0 / analytics.ticks as f32
+        };
+
+        Self {
+            code: lobby.code.clone(),
+            scene: lobby.scene.clone(),
+            opened_at: unix_secs(analytics.opened_at),
+            closed_at: unix_secs(closed_at),
+            duration_secs: closed_at.duration_since(analytics.opened_at).map(|d| d.as_secs()).unwrap_or(0),
+            peak_players: analytics.peak_players,
+            total_shots: analytics.total_shots,
+            total_kills: analytics.total_kills,
+            bytes_sent: analytics.bytes_sent.load(Ordering::Relaxed),
+            ticks: analytics.ticks,
+            average_tick_ms,
+            weapons: analytics.weapons.clone(),
+        }
+    }
+
+    /// Append the summary as one JSON line
+    pub fn append_to(&self, path: &str) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        std::fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_from_lobby() {
+        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        lobby.analytics.opened_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        lobby.analytics.peak_players = 3;
+        lobby.analytics.total_shots = 40;
+        lobby.analytics.total_kills = 2;
+        lobby.analytics.record_bytes_sent(1_500);
+        lobby.analytics.record_bytes_sent(500);
+        lobby.analytics.record_tick(Duration::from_millis(2));
+        lobby.analytics.record_tick(Duration::from_millis(4));
+
+        let summary = LobbySummary::from_lobby(&lobby, SystemTime::UNIX_EPOCH + Duration::from_secs(1_600));
+        assert_eq!(summary.duration_secs, 600);
+        assert_eq!(summary.opened_at, 1_000);
+        assert_eq!(summary.closed_at, 1_600);
+        assert_eq!(summary.peak_players, 3);
+        assert_eq!(summary.bytes_sent, 2_000);
+        assert_eq!(summary.ticks, 2);
+        assert!((summary.average_tick_ms - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_weapon_usage_counts_shots_kills_and_time_to_kill() {
+        let mut lobby = crate::testutil::LobbyBuilder::new()
+            .with_player(crate::testutil::PlayerBuilder::new().id(1))
+            .with_player(crate::testutil::PlayerBuilder::new().id(2))
+            .build();
+        let t0 = crate::testutil::epoch();
+        let shot = |hit| ShotEvent { shooter_id: 1, target_id: Some(2), weapon_id: 1, hit };
+
+        record_weapon_usage(&mut lobby, &[shot(true), shot(false)], &[], &[], t0);
+        lobby.players.get_mut(&2).unwrap().is_dead = true;
+        let kill = KillEvent {
+            killer_id: 1,
+            killer_name: "A".to_string(),
+            victim_id: 2,
+            victim_name: "B".to_string(),
+            weapon_id: 1,
+            weapon_name: "Golden Friend".to_string(),
+            killer_new_killstreak: 1,
+        };
+        record_weapon_usage(&mut lobby, &[shot(true)], &[], &[kill], t0 + Duration::from_millis(750));
+
+        let usage = &lobby.analytics.weapons[&1];
+        assert_eq!(usage, &WeaponUsage { shots: 3, kills: 1, pickups: 0, timed_kills: 1, kill_time_ms: 750 });
+        assert_eq!(LobbySummary::from_lobby(&lobby, SystemTime::now()).weapons[&1], *usage);
+    }
+
+    #[test]
+    fn test_append_to_file() {
+        let path = std::env::temp_dir().join(format!("gungame-analytics-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        let summary = LobbySummary::from_lobby(&lobby, SystemTime::now());
+
+        summary.append_to(path).unwrap();
+        summary.append_to(path).unwrap();
+        let written = std::fs::read_to_string(path).unwrap();
+        assert_eq!(written.lines().count(), 2);
+        let first: serde_json::Value = serde_json::from_str(written.lines().next().unwrap()).unwrap();
+        assert_eq!(first["code"], "TEST");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use serde::Serialize;
+
+/// Entries kept; the oldest are dropped first
+pub const AUDIT_LEN: usize = 1000;
+
+/// One privileged action and who took it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub at: u64, // Unix seconds
+    pub actor: String, // e.g. "referee 7 (Ada)"
+    pub lobby_code: String,
+    pub action: String,
+    pub outcome: String, // "ok" or why it was refused
+}
+
+/// Recent admin actions taken inside lobbies, for review (kept in memory only)
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>, // Oldest first
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, actor: String, lobby_code: &str, action: String, outcome: Result<(), &str>) {
+        let at = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let entry = AuditEntry {
+            at,
+            actor,
+            lobby_code: lobby_code.to_string(),
+            action,
+            outcome: outcome.err().unwrap_or("ok").to_string(),
+        };
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == AUDIT_LEN {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Entries oldest first, optionally for one lobby
+    pub fn entries(&self, lobby_code: Option<&str>) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .iter()
+            .filter(|entry| lobby_code.is_none_or(|code| entry.lobby_code == code))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_keeps_the_latest_entries() {
+        let log = AuditLog::new();
+        log.record("referee 1 (Ada)".to_string(), "alpha", "freeze".to_string(), Ok(()));
+        log.record("referee 2 (Bob)".to_string(), "beta", "restart_round".to_string(), Err("No round in progress"));
+
+        let beta = log.entries(Some("beta"));
+        assert_eq!(beta.len(), 1);
+        assert_eq!(beta[0].outcome, "No round in progress");
+
+        for _ in 0..AUDIT_LEN {
+            log.record("referee 1 (Ada)".to_string(), "alpha", "freeze".to_string(), Ok(()));
+        }
+        assert_eq!(log.entries(None).len(), AUDIT_LEN);
+        assert!(log.entries(Some("beta")).is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use serde::Serialize;
+use crate::state::analytics::{LobbySummary, WeaponUsage};
+use crate::state::server_state::ServerState;
+use crate::utils::config::Config;
+use crate::utils::weapondb::WeaponDb;
+
+/// One weapon's performance over every recorded lobby
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeaponBalance {
+    pub weapon_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // None for weapons no longer defined
+    pub shots: u64,
+    pub kills: u64,
+    pub pickups: u64,
+    pub usage_share: f32, // Fraction of all shots
+    pub kills_per_pickup: Option<f32>, // None when never picked up
+    pub average_ttk_ms: Option<u64>, // First hit to death; None without timed kills
+    pub flags: Vec<&'static str>, // Why the weapon looks out of line, empty when it doesn't
+}
+
+/// Per-weapon statistics aggregated from the lobby analytics file, to guide weapon tuning
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceReport {
+    pub generated_at: u64, // Unix seconds
+    pub lobbies: usize, // Lobby summaries aggregated
+    pub weapons: Vec<WeaponBalance>, // Ordered by id
+}
+
+impl BalanceReport {
+    /// Aggregate lobby summaries and flag weapons outside the configured thresholds
+    pub fn build(summaries: &[LobbySummary], weapons: &WeaponDb, config: &Config, now: SystemTime) -> Self {
+        let mut totals: BTreeMap<u32, WeaponUsage> = BTreeMap::new();
+        for (weapon_id, usage) in summaries.iter().flat_map(|summary| &summary.weapons) {
+            let total = totals.entry(*weapon_id).or_default();
+            total.shots += usage.shots;
+            total.kills += usage.kills;
+            total.pickups += usage.pickups;
+            total.timed_kills += usage.timed_kills;
+            total.kill_time_ms += usage.kill_time_ms;
+        }
+        let all_shots: u64 = totals.values().map(|usage| usage.shots).sum();
+
+        let weapons = totals
+            .into_iter()
+            .map(|(weapon_id, usage)| {
+                let usage_share = if all_shots == 0 { 0.0 } else { usage.shots as f32 / all_shots as f32 };
+                let kills_per_pickup = (usage.pickups > 0).then(|| usage.kills as f32 / usage.pickups as f32);
+                let average_ttk_ms = (usage.timed_kills > 0).then(|| usage.kill_time_ms / usage.timed_kills);
+
+                let mut flags = Vec::new();
+                if usage.kills >= config.balance_min_kills {
+                    if usage_share > config.balance_max_usage_share {
+                        flags.push("overused");
+                    }
+                    if average_ttk_ms.is_some_and(|ttk| ttk < config.balance_min_average_ttk_ms) {
+                        flags.push("kills_too_fast");
+                    }
+                    if average_ttk_ms.is_some_and(|ttk| ttk > config.balance_max_average_ttk_ms) {
+                        flags.push("kills_too_slow");
+                    }
+                    if kills_per_pickup.is_some_and(|kills| kills > config.balance_max_kills_per_pickup) {
+                        flags.push("strong_pickup");
+                    }
+                }
+                WeaponBalance {
+                    weapon_id,
+                    name: weapons.get(weapon_id).map(|w| w.name.clone()),
+                    shots: usage.shots,
+                    kills: usage.kills,
+                    pickups: usage.pickups,
+                    usage_share,
+                    kills_per_pickup,
+                    average_ttk_ms,
+                    flags,
+                }
+            })
+            .collect();
+
+        let generated_at = now.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Self { generated_at, lobbies: summaries.len(), weapons }
+    }
+}
+
+/// Read a lobby analytics file (JSON lines); a missing file has no summaries
+/// Lines that don't parse are skipped
+pub fn read_summaries(path: &Path) -> Result<Vec<LobbySummary>, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    let summaries: Vec<LobbySummary> = lines.iter().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    if summaries.len() < lines.len() {
+        log::warn!("Skipped {} unreadable lobby summaries in {}", lines.len() - summaries.len(), path.display());
+    }
+    Ok(summaries)
+}
+
+/// Rebuild the balance report from the lobby analytics file and keep it for `GET /admin/balance-report`
+pub fn refresh(state: &ServerState, config: &Config) -> Result<Arc<Option<BalanceReport>>, String> {
+    let path = config.lobby_analytics_path.as_deref().ok_or("No lobby analytics file")?;
+    let summaries = read_summaries(Path::new(path))?;
+    let report = BalanceReport::build(&summaries, &state.weapons.get(), config, SystemTime::now());
+    state.balance_report.store(Some(report));
+    Ok(state.balance_report.load())
+}
+
+/// Periodically rebuild the balance report until the task is dropped
+pub async fn run_balance_reporter(state: Arc<ServerState>, config: Arc<Config>, interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+    loop {
+        timer.tick().await;
+        if let Err(e) = refresh(&state, &config) {
+            log::warn!("Failed to build the weapon balance report: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(weapons: &[(u32, WeaponUsage)]) -> LobbySummary {
+        LobbySummary {
+            code: "alpha".to_string(),
+            scene: "world".to_string(),
+            opened_at: 0,
+            closed_at: 60,
+            duration_secs: 60,
+            peak_players: 4,
+            total_shots: 0,
+            total_kills: 0,
+            bytes_sent: 0,
+            ticks: 0,
+            average_tick_ms: 0.0,
+            weapons: weapons.iter().cloned().collect(),
+        }
+    }
+
+    fn usage(shots: u64, kills: u64, pickups: u64, kill_time_ms: u64) -> WeaponUsage {
+        WeaponUsage { shots, kills, pickups, timed_kills: kills, kill_time_ms }
+    }
+
+    #[test]
+    fn test_build_aggregates_and_flags_outliers() {
+        let config = Config { balance_min_kills: 10, ..Config::default() };
+        let summaries = vec![
+            summary(&[(1, usage(300, 10, 0, 10 * 1500)), (2, usage(100, 10, 2, 10 * 100))]),
+            summary(&[(1, usage(300, 5, 0, 5 * 1500)), (3, usage(0, 2, 0, 0))]),
+        ];
+        let report = BalanceReport::build(&summaries, &WeaponDb::load(), &config, SystemTime::UNIX_EPOCH);
+        assert_eq!(report.lobbies, 2);
+
+        let pistol = &report.weapons[0];
+        assert_eq!((pistol.weapon_id, pistol.name.as_deref()), (1, Some("Golden Friend")));
+        assert_eq!((pistol.shots, pistol.kills, pistol.average_ttk_ms), (600, 15, Some(1500)));
+        assert!((pistol.usage_share - 600.0 / 700.0).abs() < 1e-4);
+        assert_eq!(pistol.kills_per_pickup, None);
+        assert_eq!(pistol.flags, vec!["overused"]);
+
+        let prototype = &report.weapons[1];
+        assert_eq!(prototype.kills_per_pickup, Some(5.0));
+        assert_eq!(prototype.flags, vec!["kills_too_fast", "strong_pickup"]);
+
+        // Too few kills to judge
+        assert!(report.weapons[2].flags.is_empty());
+    }
+
+    #[test]
+    fn test_read_summaries_skips_bad_lines() {
+        let dir = std::env::temp_dir().join(format!("balance-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lobby_analytics.jsonl");
+        assert_eq!(read_summaries(&path).unwrap(), Vec::new());
+
+        let line = serde_json::to_string(&summary(&[(1, usage(3, 1, 0, 900))])).unwrap();
+        std::fs::write(&path, format!("{}\nnot json\n\n{}\n", line, line)).unwrap();
+        let summaries = read_summaries(&path).unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].weapons[&1].kill_time_ms, 900);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
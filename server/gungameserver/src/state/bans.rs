@@ -0,0 +1,66 @@
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Source IPs banned by an admin, until their ban runs out
+/// Checked when a client connects over UDP; players already connected are kicked with the ban
+pub struct BanList {
+    bans: DashMap<IpAddr, Instant>,
+}
+
+impl BanList {
+    pub fn new() -> Self {
+        Self { bans: DashMap::new() }
+    }
+
+    /// Ban an IP for `duration` from `now`; a longer ban already in place is kept
+    pub fn ban(&self, ip: IpAddr, duration: Duration, now: Instant) {
+        let until = now + duration;
+        self.bans
+            .entry(ip)
+            .and_modify(|current| *current = (*current).max(until))
+            .or_insert(until);
+    }
+
+    /// Whether an IP is banned at `now` (expired bans are dropped)
+    pub fn is_banned(&self, ip: &IpAddr, now: Instant) -> bool {
+        let banned = self.bans.get(ip).is_some_and(|until| now < *until);
+        if !banned {
+            self.bans.remove_if(ip, |_, until| now >= *until);
+        }
+        banned
+    }
+
+    /// Bans still running at `now`
+    pub fn active(&self, now: Instant) -> usize {
+        self.bans.iter().filter(|entry| now < *entry.value()).count()
+    }
+}
+
+impl Default for BanList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CHEATER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    #[test]
+    fn test_bans_expire_and_longest_wins() {
+        let bans = BanList::new();
+        let now = crate::testutil::epoch();
+        bans.ban(CHEATER, Duration::from_secs(60), now);
+        bans.ban(CHEATER, Duration::from_secs(10), now);
+        assert!(bans.is_banned(&CHEATER, now + Duration::from_secs(30)));
+        assert!(!bans.is_banned(&IpAddr::V4(Ipv4Addr::LOCALHOST), now));
+        assert_eq!(bans.active(now), 1);
+
+        assert!(!bans.is_banned(&CHEATER, now + Duration::from_secs(60)));
+        assert_eq!(bans.active(now), 0);
+    }
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
-/// Command sent from network handlers to lobby tick loop
-#[derive(Debug, Clone)]
+/// Command sent from network handlers to lobby tick loop (and recorded in replays)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LobbyCommand {
     // Player management
     PlayerJoin {
@@ -14,7 +15,42 @@ pub enum LobbyCommand {
     PlayerLeave {
         player_id: u32,
     },
+    // Removed by the server (admin action); the player is told why before being dropped
+    Kick {
+        player_id: u32,
+        reason: String,
+    },
+    // Owner-only lobby change (kick, resize, scene, lock, hand over)
+    OwnerAction {
+        player_id: u32,
+        #[serde(with = "crate::replay::as_json")]
+        action: crate::domain::owner::OwnerAction,
+    },
+    // Referee-only in-match control (freeze, teleport, health/ammo, round restart), audited
+    Referee {
+        player_id: u32,
+        #[serde(with = "crate::replay::as_json")]
+        action: crate::domain::referee::RefereeAction,
+    },
+    // Game speed set by an admin (owners use `OwnerAction`)
+    SetTimeScale {
+        time_scale: f32,
+    },
+    // Server notice shown to everyone in the lobby
+    Announce {
+        text: String,
+    },
+    // The server is shutting down: clients are told to leave within the grace period
+    Shutdown {
+        grace_secs: u64,
+    },
     
+    // A disconnected (or about to be) player's client is back, proven by its session token
+    Reconnect {
+        player_id: u32,
+        addr: SocketAddr,
+    },
+
     // UDP-specific player connection (for clients connecting via UDP after HTTP join)
     // This acts like PlayerJoin but for players who already exist in the lobby
     UdpConnect {
@@ -32,23 +68,104 @@ pub enum LobbyCommand {
     },
     
     // Combat
+    // The server finds what the shot hits; clients never name a target
     Shoot {
         player_id: u32,
-        target_id: u32,
+        origin: (f32, f32, f32), // Eye position the shot was fired from
+        direction: (f32, f32, f32), // Aim direction (any length)
+        client_time: Option<u64>, // Client clock (ms) when fired, for lag compensation
     },
     Reload {
         player_id: u32,
     },
+    // Grenade thrown from the player's hand, simulated by the tick loop until it explodes
+    Throw {
+        player_id: u32,
+        origin: (f32, f32, f32),
+        direction: (f32, f32, f32),
+    },
     WeaponSwitch {
         player_id: u32,
         weapon_id: u32,
     },
+    // Echo of a verification challenge (see `challenge::answer`)
+    ChallengeResponse {
+        player_id: u32,
+        nonce: String,
+        signature: String,
+    },
+    // Weapon handed out by an admin (players collect them from pickups)
+    GrantWeapon {
+        player_id: u32,
+        weapon_id: u32,
+    },
+    SwitchTeam {
+        player_id: u32,
+        team_id: u32,
+    },
+    // Chat line to relay to the lobby (or the sender's team)
+    Chat {
+        player_id: u32,
+        text: String,
+        team_only: bool,
+    },
+    // Opaque voice frame to relay (never decoded), to everyone or only nearby players
+    Voice {
+        player_id: u32,
+        payload: String,
+        proximity: bool,
+    },
+    // Stop or resume hearing another player's voice
+    MutePlayer {
+        player_id: u32,
+        target_id: u32,
+        muted: bool,
+    },
+    // Ready (or not) for the next match to start
+    Ready {
+        player_id: u32,
+        ready: bool,
+    },
+    // The client finished loading the scene and can be seen and hit (see `lobbies::begin_loading`)
+    LoadingComplete {
+        player_id: u32,
+    },
     
+    // Client clock sample: a command's `client_time` and when the server received it (epoch ms)
+    ClockSample {
+        player_id: u32,
+        client_ms: u64,
+        server_ms: u64,
+    },
+
     // Keepalive
     Heartbeat {
         player_id: u32,
         addr: SocketAddr,  // Track UDP address for broadcasting
     },
+    
+    // Client received these reliable events (`seq` of each) and/or applied a snapshot
+    Ack {
+        player_id: u32,
+        seqs: Vec<u64>,
+        snapshot_tick: Option<u64>,
+    },
+}
+
+impl LobbyCommand {
+    /// The player a command acts for, when it only makes sense once their scene has loaded
+    pub fn needs_loaded_scene(&self) -> Option<u32> {
+        match self {
+            LobbyCommand::PositionUpdate { player_id, .. }
+            | LobbyCommand::Shoot { player_id, .. }
+            | LobbyCommand::Reload { player_id }
+            | LobbyCommand::Throw { player_id, .. }
+            | LobbyCommand::WeaponSwitch { player_id, .. }
+            | LobbyCommand::SwitchTeam { player_id, .. }
+            | LobbyCommand::Ready { player_id, .. } => Some(*player_id),
+            _ => None,
+        }
+    }
 }
 
 /// Coalesce commands from queue, keeping only latest position per player
@@ -127,7 +244,7 @@ mod tests {
         let (tx, mut rx) = mpsc::channel(100);
         let addr = test_addr();
         
-        tx.send(LobbyCommand::Shoot { player_id: 1, target_id: 2 }).await.unwrap();
+        tx.send(LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: (0.0, 0.0, -1.0), client_time: None }).await.unwrap();
         tx.send(LobbyCommand::PositionUpdate {
             player_id: 1,
             position: (1.0, 1.0, 1.0),
@@ -89,6 +89,20 @@ impl GlobalStats {
         all.into_iter().take(limit).collect()
     }
 
+    /// Up to `limit` recorded players whose name starts with `prefix` (ignoring case), most recently seen first
+    pub fn search_by_name(&self, prefix: &str, limit: usize) -> Vec<GlobalPlayerStats> {
+        let prefix = prefix.to_lowercase();
+        let mut found: Vec<_> = self
+            .players
+            .iter()
+            .filter(|entry| entry.value().name.to_lowercase().starts_with(&prefix))
+            .map(|entry| entry.value().clone())
+            .collect();
+        found.sort_by_key(|stats| std::cmp::Reverse(stats.last_seen));
+        found.truncate(limit);
+        found
+    }
+
     pub fn cleanup_old_entries(&self, max_age_secs: u64) -> usize {
         let now = SystemTime::now();
         let mut removed = 0;
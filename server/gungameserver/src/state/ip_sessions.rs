@@ -0,0 +1,155 @@
+use dashmap::mapref::entry::Entry;
+use dashmap::{DashMap, DashSet};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Tracks concurrent player sessions per source IP across all lobbies
+/// Many player ids from one IP usually means a bot farm, so joins past the cap are refused
+pub struct IpSessionLimiter {
+    max_per_ip: AtomicUsize, // 0 = unlimited
+    exempt: DashSet<IpAddr>, // Admin override (LAN cafes, NAT gateways, test rigs)
+    sessions: DashMap<IpAddr, HashSet<u32>>,
+    players: DashMap<u32, IpAddr>, // Where each session is counted
+    rejections: AtomicU64,
+}
+
+impl IpSessionLimiter {
+    pub fn new(max_per_ip: usize) -> Self {
+        Self {
+            max_per_ip: AtomicUsize::new(max_per_ip),
+            exempt: DashSet::new(),
+            sessions: DashMap::new(),
+            players: DashMap::new(),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    /// Apply the configured cap and exempt addresses
+    pub fn configure(&self, max_per_ip: usize, exempt: &[IpAddr]) {
+        self.max_per_ip.store(max_per_ip, Ordering::Relaxed);
+        self.exempt.clear();
+        for ip in exempt {
+            self.exempt.insert(*ip);
+        }
+    }
+
+    /// Register a player session from an IP
+    /// A player seen from a new IP (NAT rebinding) moves there instead of counting twice
+    pub fn try_acquire(&self, ip: IpAddr, player_id: u32) -> Result<(), &'static str> {
+        let max = self.max_per_ip.load(Ordering::Relaxed);
+        let limited = max > 0 && !self.exempt.contains(&ip);
+        // Checked and counted under the IP's entry lock, so racing joins can't both take the last slot
+        match self.sessions.entry(ip) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().contains(&player_id) {
+                    return Ok(());
+                }
+                if limited && entry.get().len() >= max {
+                    self.rejections.fetch_add(1, Ordering::Relaxed);
+                    return Err("Too many sessions from this address");
+                }
+                entry.get_mut().insert(player_id);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(HashSet::from([player_id]));
+            }
+        }
+        if let Some(previous) = self.players.insert(player_id, ip).filter(|previous| *previous != ip) {
+            self.remove(previous, player_id);
+        }
+        Ok(())
+    }
+
+    /// Drop a player's session (call when they leave)
+    pub fn release(&self, player_id: u32) {
+        if let Some((_, ip)) = self.players.remove(&player_id) {
+            self.remove(ip, player_id);
+        }
+    }
+
+    /// Uncount a session from an IP, dropping the IP once it has none
+    fn remove(&self, ip: IpAddr, player_id: u32) {
+        if let Entry::Occupied(mut entry) = self.sessions.entry(ip) {
+            entry.get_mut().remove(&player_id);
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Concurrent sessions from one IP
+    pub fn session_count(&self, ip: &IpAddr) -> usize {
+        self.sessions.get(ip).map(|ids| ids.len()).unwrap_or(0)
+    }
+
+    /// Total joins refused because of the cap
+    pub fn rejections(&self) -> u64 {
+        self.rejections.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const FARM: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const HOME: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn test_cap_and_release() {
+        let limiter = IpSessionLimiter::new(2);
+        assert!(limiter.try_acquire(FARM, 1).is_ok());
+        assert!(limiter.try_acquire(FARM, 2).is_ok());
+        assert!(limiter.try_acquire(FARM, 2).is_ok()); // Rejoin is not a new session
+        assert!(limiter.try_acquire(FARM, 3).is_err());
+        assert!(limiter.try_acquire(HOME, 3).is_ok());
+
+        limiter.release(1);
+        assert!(limiter.try_acquire(FARM, 4).is_ok());
+        assert_eq!(limiter.session_count(&FARM), 2);
+        assert_eq!(limiter.rejections(), 1);
+    }
+
+    #[test]
+    fn test_player_moving_ip_counts_once() {
+        let limiter = IpSessionLimiter::new(1);
+        assert!(limiter.try_acquire(HOME, 1).is_ok());
+        assert!(limiter.try_acquire(FARM, 1).is_ok());
+        assert_eq!(limiter.session_count(&HOME), 0);
+        assert_eq!(limiter.session_count(&FARM), 1);
+    }
+
+    #[test]
+    fn test_racing_joins_respect_the_cap() {
+        let limiter = std::sync::Arc::new(IpSessionLimiter::new(3));
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let limiter = limiter.clone();
+                std::thread::spawn(move || (0..50).filter(|i| limiter.try_acquire(FARM, thread * 50 + i).is_ok()).count())
+            })
+            .collect();
+        let acquired: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+        assert_eq!(acquired, 3);
+        assert_eq!(limiter.session_count(&FARM), 3);
+
+        for id in 0..400 {
+            limiter.release(id);
+        }
+        assert_eq!(limiter.session_count(&FARM), 0);
+        assert!(limiter.sessions.is_empty() && limiter.players.is_empty());
+    }
+
+    #[test]
+    fn test_exempt_and_unlimited() {
+        let limiter = IpSessionLimiter::new(1);
+        limiter.configure(1, &[FARM]);
+        assert!((1..=10).all(|id| limiter.try_acquire(FARM, id).is_ok()));
+        assert!(limiter.try_acquire(HOME, 11).is_ok());
+        assert!(limiter.try_acquire(HOME, 12).is_err());
+
+        limiter.configure(0, &[]);
+        assert!(limiter.try_acquire(HOME, 12).is_ok());
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Longest preset name
+pub const MAX_PRESET_NAME_LENGTH: usize = 32;
+
+/// Named gun game ladders admins can apply to lobbies
+/// Saved as one JSON object (name -> weapon ids), rewritten on every change
+#[derive(Debug, Default)]
+pub struct LadderPresets {
+    presets: Mutex<BTreeMap<String, Vec<u32>>>, // Name -> ladder
+    path: Mutex<Option<PathBuf>>, // None = kept in memory only
+}
+
+impl LadderPresets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Preset names follow lobby code rules
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_PRESET_NAME_LENGTH
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    }
+
+    /// Load the presets saved at `path` and save every later change there
+    /// A missing file starts with no presets
+    pub fn restore(&self, path: &Path) -> Result<(), String> {
+        let saved = match std::fs::read_to_string(path) {
+            Ok(saved) => serde_json::from_str(&saved).map_err(|e| format!("Invalid {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        *self.presets.lock().unwrap_or_else(|e| e.into_inner()) = saved;
+        *self.path.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec<u32>> {
+        self.presets.lock().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+    }
+
+    /// Every preset, by name
+    pub fn all(&self) -> BTreeMap<String, Vec<u32>> {
+        self.presets.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Create or replace a preset (the ladder is expected to be validated already)
+    pub fn save(&self, name: &str, ladder: Vec<u32>) -> std::io::Result<()> {
+        let mut presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        let mut changed = presets.clone();
+        changed.insert(name.to_string(), ladder);
+        self.persist(&changed)?;
+        *presets = changed;
+        Ok(())
+    }
+
+    /// Delete a preset; false if there was none
+    pub fn remove(&self, name: &str) -> std::io::Result<bool> {
+        let mut presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        if !presets.contains_key(name) {
+            return Ok(false);
+        }
+        let mut changed = presets.clone();
+        changed.remove(name);
+        self.persist(&changed)?;
+        *presets = changed;
+        Ok(true)
+    }
+
+    fn persist(&self, presets: &BTreeMap<String, Vec<u32>>) -> std::io::Result<()> {
+        let path = self.path.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(path) = path.as_ref() else {
+            return Ok(());
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(presets)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_survive_restart() {
+        let path = std::env::temp_dir().join(format!("gungame-ladders-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let presets = LadderPresets::new();
+        presets.restore(&path).unwrap();
+        presets.save("pistols", vec![1, 2, 0]).unwrap();
+        presets.save("snipers", vec![5, 0]).unwrap();
+        assert!(presets.remove("snipers").unwrap());
+        assert!(!presets.remove("snipers").unwrap());
+
+        let restored = LadderPresets::new();
+        restored.restore(&path).unwrap();
+        assert_eq!(restored.get("pistols"), Some(vec![1, 2, 0]));
+        assert_eq!(restored.all().len(), 1);
+        assert!(!LadderPresets::is_valid_name("no spaces"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,7 +1,27 @@
 use crate::utils::buffers::SmallPlayerVec;
-use std::collections::HashMap;
+use crate::domain::bot::BotBrain;
+use crate::domain::anticheat::{AimTracker, ClientSettings, IntegrityRules, RecoilTracker, ShotAudit, ViolationTracker};
+use crate::domain::chat::ChatLimiter;
+use crate::domain::challenge::ChallengeState;
+use crate::domain::clock::ClientClock;
+use crate::domain::collision::{Aabb, CollisionWorld};
+use crate::domain::grenades::{Grenade, GrenadeEvent};
+use crate::domain::gungame::GameMode;
+use crate::domain::history::MatchLog;
+use crate::domain::horde::HordeRules;
+use crate::domain::logic::{DamageEvent, KillEvent, ShotEvent};
+use crate::domain::mutators::MutatorSchedule;
+use crate::domain::owner::LobbyEvent;
+use crate::domain::pickups::Pickup;
+use crate::domain::projectiles::{Projectile, ProjectileEvent};
+use crate::domain::rewind::{PositionHistory, HISTORY_WINDOW};
+use crate::net::crypto::PasswordHash;
+use crate::state::analytics::LobbyAnalytics;
+use crate::utils::tunables::Tunables;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub type LobbyCode = String;
 
@@ -12,7 +32,7 @@ pub struct Player {
     pub name: String,
     pub position: (f32, f32, f32),
     pub rotation: (f32, f32, f32),
-    pub last_update: SystemTime,
+    pub last_update: Instant,
 
     // Health state
     pub current_health: u32,
@@ -22,30 +42,239 @@ pub struct Player {
     pub current_weapon_id: u32,
     pub current_ammo: u32,
     pub max_ammo: u32,
+    pub inventory: BTreeMap<u32, Magazine>, // Owned weapons (melee is always owned); the held one's entry is refreshed on switching away
 
     // Reload state
     pub is_reloading: bool,
-    pub reload_end_time: Option<SystemTime>,
+    pub reload_end_time: Option<Instant>,
 
     // Combat timing
-    pub last_shot_time: SystemTime,
+    pub last_shot_time: Option<Instant>, // None = hasn't fired yet
+    pub grenades_thrown: u32, // This life, limited by the `grenades_per_life` tunable
 
     // Kill tracking
     pub kills: u32,
     pub deaths: u32,
     pub score: u32,
     pub killstreak: u32,
+    pub shots_fired: u32, // This match, for account accuracy
+    pub shots_hit: u32,
+
+    // Account signed in with at join (see `net::auth`); None = guest, no lifetime stats
+    pub account_id: Option<i64>,
 
     // Inactivity warning state
-    pub warned_at: Option<SystemTime>,
+    pub warned_at: Option<Instant>,
+    pub connection: Connection,
 
     // Respawn state
     pub is_dead: bool,
-    pub respawn_time: Option<SystemTime>,
+    pub respawn_time: Option<Instant>,
+
+    // Team (None in lobbies without teams)
+    pub team_id: Option<u32>,
+
+    // Ready to start the next match (see `MatchRules::ready_quorum`)
+    pub ready: bool,
+
+    // Client still loading the scene: invulnerable, hidden from the others and its commands ignored
+    // until `loading_complete` or this deadline (see `lobbies::begin_loading`)
+    pub loading_until: Option<Instant>,
+
+    // Client clock estimate from command timestamps
+    pub clock: ClientClock,
+
+    // Anti-cheat
+    pub recoil: RecoilTracker, // Aim across the current automatic burst
+    pub attested: Option<ClientSettings>, // FOV and aim assist the client attested at join (see `anticheat::attest`)
+    pub aim: AimTracker, // Hits checked against the attested settings
+    pub violations: ViolationTracker,
+    pub shot_audit: ShotAudit, // Recent shots for player reports
+    pub challenge: ChallengeState, // Verification challenges (see `challenge::advance`)
+
+    pub chat: ChatLimiter,
+    pub muted: HashSet<u32>, // Players whose voice isn't relayed to this one
 }
 
-/// Player sync state for delta tracking
+/// Ammo kept for an owned weapon while it isn't held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Magazine {
+    pub ammo: u32,
+    pub max_ammo: u32,
+}
+
+impl Magazine {
+    pub fn full(max_ammo: u32) -> Self {
+        Magazine { ammo: max_ammo, max_ammo }
+    }
+}
+
+/// Whether a player's client can currently be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Connection {
+    #[default]
+    Connected,
+    /// Went silent at `since`; the slot is held for a `reconnect` until the reconnect window runs out
+    Disconnected { since: Instant },
+}
+
+/// Someone watching a lobby: receives every broadcast but has no combat state and takes no player slot
+#[derive(Debug, Clone)]
+pub struct Spectator {
+    pub id: u32,
+    pub name: String,
+    pub last_update: Instant, // Removed when silent for the inactivity timeout
+    pub referee: bool, // Joined through the admin API; may send referee actions
+}
+
+/// Random watch-only code, looked up apart from join codes
+pub fn new_spectate_code() -> LobbyCode {
+    format!("watch-{}", &uuid::Uuid::new_v4().simple().to_string()[..12])
+}
+
+/// Match lifecycle of a lobby, advanced by the tick loop (see `domain::matches`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchState {
+    /// Fewer than `min_players` or not enough of them ready; players can warm up, nothing is scored
+    WaitingForPlayers,
+    /// Enough players, the first round starts at `ends_at`
+    Countdown { ends_at: Instant },
+    /// Round `round` (1-based) is being played until `ends_at` or the score limit
+    InProgress { round: u32, ends_at: Instant },
+    /// Intermission after `round`; the next round starts at `next_at`
+    RoundEnd { round: u32, next_at: Instant },
+    /// Results screen; scores reset and a new match is set up at `restart_at`
+    MatchEnd { restart_at: Instant },
+}
+
+impl MatchState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchState::WaitingForPlayers => "waiting_for_players",
+            MatchState::Countdown { .. } => "countdown",
+            MatchState::InProgress { .. } => "in_progress",
+            MatchState::RoundEnd { .. } => "round_end",
+            MatchState::MatchEnd { .. } => "match_end",
+        }
+    }
+
+    /// Current (or just finished) round, 0 before the first round
+    pub fn round(&self) -> u32 {
+        match self {
+            MatchState::InProgress { round, .. } | MatchState::RoundEnd { round, .. } => *round,
+            _ => 0,
+        }
+    }
+}
+
+/// Whether the lobby browser lists a lobby
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    #[default]
+    Public,
+    Unlisted, // Only joinable by code
+}
+
+impl Visibility {
+    pub fn parse(visibility: &str) -> Option<Self> {
+        match visibility {
+            "public" => Some(Visibility::Public),
+            "unlisted" => Some(Visibility::Unlisted),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+        }
+    }
+}
+
+/// Whether a lobby plays in 3D or on the x/z plane (2D prototypes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dimensions {
+    Two, // Y is ignored: positions stay at `PLANE_HEIGHT`, shots and projectiles fly flat
+    #[default]
+    Three,
+}
+
+/// Height every position in a 2D lobby is kept at (standing height in 3D)
+pub const PLANE_HEIGHT: f32 = 1.0;
+
+impl Dimensions {
+    pub fn parse(dimensions: u8) -> Option<Self> {
+        match dimensions {
+            2 => Some(Dimensions::Two),
+            3 => Some(Dimensions::Three),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Dimensions::Two => 2,
+            Dimensions::Three => 3,
+        }
+    }
+
+    /// Drop the Y coordinate of a position in 2D lobbies
+    pub fn flatten(&self, position: (f32, f32, f32)) -> (f32, f32, f32) {
+        match self {
+            Dimensions::Two => (position.0, PLANE_HEIGHT, position.2),
+            Dimensions::Three => position,
+        }
+    }
+
+    /// Drop the vertical part of a direction in 2D lobbies (not normalized)
+    pub fn flatten_direction(&self, direction: (f32, f32, f32)) -> (f32, f32, f32) {
+        match self {
+            Dimensions::Two => (direction.0, 0.0, direction.2),
+            Dimensions::Three => direction,
+        }
+    }
+
+    /// Distance between positions, ignoring height in 2D lobbies
+    pub fn distance(&self, a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+        crate::domain::simulator::distance(self.flatten(a), self.flatten(b))
+    }
+}
+
+/// Match timing and limits for a lobby
 #[derive(Debug, Clone, PartialEq)]
+pub struct MatchRules {
+    pub min_players: usize, // Bots count
+    pub ready_quorum: u32, // Percent of human players that must be ready to start the countdown; 0 = no ready-up
+    pub countdown: Duration,
+    pub round_duration: Duration,
+    pub rounds_per_match: u32,
+    pub score_limit: u32, // A player reaching this score ends the match; 0 = no limit
+    pub intermission: Duration, // Between rounds
+    pub results_duration: Duration, // After the match before the next one is set up
+    pub horde: HordeRules, // Waves and lives in horde lobbies
+    pub integrity: IntegrityRules, // Client FOV and aim assist allowed
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            min_players: 2,
+            ready_quorum: 100,
+            countdown: Duration::from_secs(10),
+            round_duration: Duration::from_secs(300),
+            rounds_per_match: 3,
+            score_limit: 2500,
+            intermission: Duration::from_secs(5),
+            results_duration: Duration::from_secs(10),
+            horde: HordeRules::default(),
+            integrity: IntegrityRules::default(),
+        }
+    }
+}
+
+/// Player sync state for delta tracking
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct PlayerSyncState {
     pub id: u32,
     pub health: u32,
@@ -54,6 +283,8 @@ pub struct PlayerSyncState {
     pub current_ammo: u32,
     pub max_ammo: u32,
     pub is_reloading: bool,
+    pub team_id: Option<u32>,
+    pub weapons: Vec<u32>, // Owned weapon IDs, ascending
 }
 
 impl Player {
@@ -66,43 +297,156 @@ impl Player {
             current_ammo: self.current_ammo,
             max_ammo: self.max_ammo,
             is_reloading: self.is_reloading,
+            team_id: self.team_id,
+            weapons: self.inventory.keys().copied().collect(),
         }
     }
 
+    /// Yaw (rotation around Y) in radians
+    pub fn yaw(&self) -> f32 {
+        self.rotation.1
+    }
+
+    /// Pitch (rotation around X) in radians
+    pub fn pitch(&self) -> f32 {
+        self.rotation.0
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading_until.is_some()
+    }
+
+    /// Euler triple for a compact yaw/pitch rotation (roll is always 0)
+    pub fn rotation_from_yaw_pitch(yaw: f32, pitch: f32) -> (f32, f32, f32) {
+        (pitch, yaw, 0.0)
+    }
+
     pub fn new_player(id: u32, name: String, current_weapon_id: u32, ammo: u32) -> Self {
         Player {
             id,
             name,
             position: (0.0, 1.0, 0.0),
             rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
+            last_update: Instant::now(),
             current_health: 100,
             max_health: 100,
             current_weapon_id,
             current_ammo: ammo,
             max_ammo: ammo,
+            inventory: BTreeMap::from([(current_weapon_id, Magazine::full(ammo))]),
             is_reloading: false,
             reload_end_time: None,
-            last_shot_time: SystemTime::UNIX_EPOCH,
+            last_shot_time: None,
+            grenades_thrown: 0,
             kills: 0,
             deaths: 0,
             score: 0,
             killstreak: 0,
+            shots_fired: 0,
+            shots_hit: 0,
+            account_id: None,
             warned_at: None,
+            connection: Connection::Connected,
             is_dead: false,
             respawn_time: None,
+            team_id: None,
+            ready: false,
+            loading_until: None,
+            clock: ClientClock::default(),
+            recoil: RecoilTracker::default(),
+            attested: None,
+            aim: AimTracker::default(),
+            violations: ViolationTracker::default(),
+            shot_audit: ShotAudit::default(),
+            challenge: ChallengeState::default(),
+            chat: ChatLimiter::default(),
+            muted: HashSet::new(),
         }
     }
 }
 
+/// Settings a lobby is created with; kept for persistent lobbies to recreate them
+#[derive(Debug, Clone, PartialEq)]
+pub struct LobbySpec {
+    pub code: LobbyCode,
+    pub max_players: u32,
+    pub scene: String,
+    pub mode: GameMode,
+    pub team_count: u32,
+    pub dimensions: Dimensions,
+    pub bots: u32, // Server-driven players added on creation
+    pub bot_fill: u32, // Bots come and go to keep this many players in; 0 = off
+    pub tick_rate_hz: Option<u32>, // None = the server's `tick_rate_hz`
+    pub persistent: bool, // Never closed for being empty, recreated if it's ever removed
+    pub hide_enemy_state: bool, // Enemies only see each other's health bucket
+    pub integrity: IntegrityRules, // Client FOV and aim assist allowed
+    pub visibility: Visibility,
+    pub password: Option<PasswordHash>, // Required to join; None = open
+    pub hosted: bool, // Created by a player: the first human to join owns it
+    pub record_replay: bool, // Admin only: record the lobby for `replay` (not kept by checkpoints)
+}
+
 /// Lobby state - per-lobby partitioned state
 #[derive(Debug)]
 pub struct Lobby {
     pub code: LobbyCode,
     pub players: HashMap<u32, Player>,
-    pub client_addresses: HashMap<u32, SocketAddr>,
+    pub client_addresses: HashMap<u32, SocketAddr>, // Players and spectators
+    pub udp_port: Option<u16>, // Dedicated UDP port; None = the shared `udp_port`
     pub max_players: u32,
+    pub spectators: HashMap<u32, Spectator>,
+    pub max_spectators: u32, // Separate from `max_players`; 0 = no spectators
+    pub spectate_code: LobbyCode, // Shareable code that only admits spectators
     pub scene: String,
+    pub mode: GameMode,
+    pub dimensions: Dimensions,
+    pub time_scale: f32, // Game speed (0.5 = slow motion); fire rate, reloads, respawns and motion follow it
+    pub persistent: bool, // Exempt from empty-lobby cleanup and recreated if removed
+    pub record_replay: bool, // The tick loop writes every tick's commands to a replay file
+    pub match_state: MatchState,
+    pub rules: MatchRules,
+    pub quantize_positions: bool, // Store positions snapped to the millimeter grid
+    pub collision: CollisionWorld, // Static occluders for line-of-sight checks
+    pub bounds: Option<Aabb>, // Playable area (bots never leave it)
+    pub pickups: Vec<Pickup>,
+    pub bots: HashMap<u32, BotBrain>, // Player ID -> AI state for server-driven players
+    pub bot_fill: u32, // Players the tick loop tops up to with bots; 0 = off
+    pub fill_bots: BTreeSet<u32>, // Bots added for `bot_fill`, removed again as players join
+    pub tick_rate_hz: Option<u32>, // Overrides the server's `tick_rate_hz`
+    pub analytics: LobbyAnalytics, // Usage counters summarized when the lobby closes
+    pub match_log: MatchLog, // Kills and gun game progress of the match in progress, for match history
+    pub spawn_points: Vec<(f32, f32, f32)>, // Respawn locations; empty respawns at the origin
+    pub enemy_spawn_points: Vec<(f32, f32, f32)>, // Where horde waves spawn; empty uses `spawn_points`
+    pub kill_feed: Vec<KillEvent>, // Kills registered since the tick loop last broadcast them
+    pub shot_feed: Vec<ShotEvent>, // Shots fired since the tick loop last broadcast them
+    pub damage_feed: Vec<DamageEvent>, // Hits dealt since the tick loop last broadcast them
+    pub projectiles: Vec<Projectile>, // In flight, moved every tick
+    pub next_projectile_id: u32,
+    pub projectile_feed: Vec<ProjectileEvent>, // Launches and impacts since the tick loop last broadcast them
+    pub grenades: Vec<Grenade>, // Thrown and not yet exploded, moved every tick
+    pub next_grenade_id: u32,
+    pub grenade_feed: Vec<GrenadeEvent>, // Throws and explosions since the tick loop last broadcast them
+    pub team_count: u32, // Teams are numbered 1..=team_count; 0 = everyone for themselves
+    pub friendly_fire: bool, // Teammates can damage each other
+    pub hide_enemy_state: bool, // Enemies get a health bucket instead of exact health, ammo and reload state
+    pub visibility: Visibility,
+    pub password: Option<PasswordHash>, // Salted hash of the join password; None = open
+    pub team_scores: BTreeMap<u32, u32>, // Team ID -> score this match
+    pub team_scores_dirty: bool, // Team scores changed since the last state sync
+    pub history: PositionHistory, // Recent positions for lag compensated hits
+    pub max_rewind: Duration, // Furthest back a shot's timestamp is honoured
+    pub tunables: Arc<Tunables>, // Gameplay numbers; replaced when changed live
+    pub mutators: MutatorSchedule, // Timed lobby-wide rule changes
+    pub reservations: HashMap<String, Instant>, // Rematch slot token -> expiry; held slots count as taken
+    pub rematch: Option<LobbyCode>, // Lobby opened for a rematch of this one
+    pub rematch_offers: Vec<(u32, String)>, // Player ID -> reservation token, sent with `rematch` by the tick loop
+    pub hosted: bool, // Has an owner (player-created lobbies)
+    pub owner: Option<u32>, // Player who may kick, resize, change scene, lock and hand over the lobby
+    pub locked: bool, // Refuses every join
+    pub frozen: bool, // Referee freeze: players and bots can't move, shoot, reload or switch weapons
+    pub challenge_key: String, // Signs verification challenges; never leaves the server
+    pub lobby_events: Vec<LobbyEvent>, // Owner changes, broadcast by the tick loop
+    pub arrivals: Vec<(u32, LobbyCode)>, // Merged in from another lobby (id, lobby left); sent `migrate_to` by the tick loop
 
     // Delta tracking for efficient state sync
     pub dirty_players: SmallPlayerVec, // Players with state changes
@@ -115,8 +459,61 @@ impl Lobby {
             code,
             players: HashMap::new(),
             client_addresses: HashMap::new(),
+            udp_port: None,
             max_players,
+            spectators: HashMap::new(),
+            max_spectators: 0,
+            spectate_code: new_spectate_code(),
             scene,
+            mode: GameMode::Ffa,
+            dimensions: Dimensions::Three,
+            time_scale: 1.0,
+            persistent: false,
+            record_replay: false,
+            match_state: MatchState::WaitingForPlayers,
+            rules: MatchRules::default(),
+            quantize_positions: false,
+            collision: CollisionWorld::default(),
+            bounds: None,
+            pickups: Vec::new(),
+            bots: HashMap::new(),
+            bot_fill: 0,
+            fill_bots: BTreeSet::new(),
+            tick_rate_hz: None,
+            analytics: LobbyAnalytics::new(),
+            match_log: MatchLog::default(),
+            spawn_points: Vec::new(),
+            enemy_spawn_points: Vec::new(),
+            kill_feed: Vec::new(),
+            shot_feed: Vec::new(),
+            damage_feed: Vec::new(),
+            projectiles: Vec::new(),
+            next_projectile_id: 1,
+            projectile_feed: Vec::new(),
+            grenades: Vec::new(),
+            next_grenade_id: 1,
+            grenade_feed: Vec::new(),
+            team_count: 0,
+            friendly_fire: false,
+            hide_enemy_state: false,
+            visibility: Visibility::Public,
+            password: None,
+            team_scores: BTreeMap::new(),
+            team_scores_dirty: false,
+            history: PositionHistory::default(),
+            max_rewind: HISTORY_WINDOW,
+            tunables: Arc::new(Tunables::default()),
+            mutators: MutatorSchedule::default(),
+            reservations: HashMap::new(),
+            rematch: None,
+            rematch_offers: Vec::new(),
+            hosted: false,
+            owner: None,
+            locked: false,
+            frozen: false,
+            challenge_key: crate::net::crypto::generate_token(),
+            lobby_events: Vec::new(),
+            arrivals: Vec::new(),
             dirty_players: SmallPlayerVec::new(),
             last_sync_state: HashMap::new(),
         }
@@ -126,6 +523,11 @@ impl Lobby {
         Player::new_player(id, name, current_weapon_id, ammo)
     }
 
+    /// Real time a game-time duration takes at the lobby's time scale
+    pub fn scaled(&self, duration: Duration) -> Duration {
+        duration.div_f32(self.time_scale)
+    }
+
     /// Mark a player as dirty (state changed)
     pub fn mark_dirty(&mut self, player_id: u32) {
         if !self.dirty_players.contains(&player_id) {
@@ -142,6 +544,7 @@ impl Lobby {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::PlayerBuilder;
 
     #[test]
     fn test_lobby_creation() {
@@ -153,28 +556,7 @@ mod tests {
 
     #[test]
     fn test_player_to_sync_state() {
-        let player = Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::UNIX_EPOCH,
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
+        let player = PlayerBuilder::new().build();
 
         let sync = player.to_sync_state();
         assert_eq!(sync.id, 1);
@@ -182,6 +564,24 @@ mod tests {
         assert_eq!(sync.current_ammo, 20);
     }
 
+    #[test]
+    fn test_yaw_pitch_roundtrip() {
+        let mut player = Player::new_player(1, "Test".to_string(), 1, 20);
+        player.rotation = Player::rotation_from_yaw_pitch(1.25, -0.5);
+        assert_eq!(player.yaw(), 1.25);
+        assert_eq!(player.pitch(), -0.5);
+    }
+
+    #[test]
+    fn test_2d_lobbies_ignore_height() {
+        let flat = Dimensions::parse(2).unwrap();
+        assert_eq!(flat.flatten((3.0, 7.5, -4.0)), (3.0, PLANE_HEIGHT, -4.0));
+        assert_eq!(flat.flatten_direction((1.0, -1.0, 0.0)), (1.0, 0.0, 0.0));
+        assert_eq!(flat.distance((0.0, 0.0, 0.0), (3.0, 100.0, 4.0)), 5.0);
+        assert_eq!(Dimensions::Three.flatten((3.0, 7.5, -4.0)), (3.0, 7.5, -4.0));
+        assert_eq!(Dimensions::parse(4), None);
+    }
+
     #[test]
     fn test_dirty_tracking() {
         let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
@@ -0,0 +1,71 @@
+use std::collections::BTreeSet;
+use std::net::UdpSocket;
+use std::sync::Mutex;
+
+/// Dedicated UDP ports for lobbies, handed out from the configured range
+/// Ports held by another process are skipped; an exhausted range leaves lobbies on the shared socket
+pub struct LobbyPorts {
+    range: Mutex<Option<(u16, u16)>>, // Inclusive; None = every lobby shares the main socket
+    in_use: Mutex<BTreeSet<u16>>,
+}
+
+impl LobbyPorts {
+    pub fn new() -> Self {
+        Self { range: Mutex::new(None), in_use: Mutex::new(BTreeSet::new()) }
+    }
+
+    /// Apply the configured range; 0 for either end turns dedicated ports off
+    pub fn configure(&self, min: u16, max: u16) {
+        *self.range.lock().unwrap() = (min > 0 && max >= min).then_some((min, max));
+    }
+
+    /// Bind the first free port of the range (non-blocking, ready for tokio)
+    pub fn bind(&self) -> Option<(u16, UdpSocket)> {
+        let (min, max) = (*self.range.lock().unwrap())?;
+        let mut in_use = self.in_use.lock().unwrap();
+        for port in (min..=max).filter(|port| !in_use.contains(port)) {
+            let Ok(socket) = UdpSocket::bind(("0.0.0.0", port)) else {
+                continue;
+            };
+            if socket.set_nonblocking(true).is_err() {
+                continue;
+            }
+            in_use.insert(port);
+            return Some((port, socket));
+        }
+        None
+    }
+
+    /// Hand a closed lobby's port back
+    pub fn release(&self, port: u16) {
+        self.in_use.lock().unwrap().remove(&port);
+    }
+}
+
+impl Default for LobbyPorts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ports_are_reused_after_release() {
+        let ports = LobbyPorts::new();
+        assert!(ports.bind().is_none()); // Off until configured
+
+        let free = UdpSocket::bind("0.0.0.0:0").unwrap().local_addr().unwrap().port();
+        ports.configure(free, free);
+        let (port, socket) = ports.bind().unwrap();
+        assert_eq!((port, socket.local_addr().unwrap().port()), (free, free));
+        assert!(ports.bind().is_none()); // Exhausted
+
+        drop(socket);
+        ports.release(port);
+        assert!(ports.in_use.lock().unwrap().is_empty());
+        assert_eq!(ports.bind().map(|(port, _)| port), Some(free));
+    }
+}
@@ -3,3 +3,18 @@ pub mod commands;
 pub mod server_state;
 pub mod global_stats;
 
+pub mod ip_sessions;
+pub mod bans;
+pub mod session_binding;
+pub mod analytics;
+pub mod balance;
+pub mod telemetry;
+pub mod player_ids;
+pub mod player_directory;
+pub mod snapshot;
+pub mod reports;
+pub mod audit;
+pub mod name_index;
+pub mod ladder_presets;
+pub mod warm_pool;
+pub mod lobby_ports;
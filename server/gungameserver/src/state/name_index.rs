@@ -0,0 +1,79 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+use crate::state::lobby::LobbyCode;
+use crate::state::snapshot::PlayerListing;
+
+/// Players in open lobbies by lowercased name, kept in step with the published listings
+/// Sorted so a name prefix is a range scan rather than a walk over every lobby
+#[derive(Debug, Default)]
+pub struct NameIndex {
+    names: RwLock<BTreeMap<String, BTreeSet<(LobbyCode, u32)>>>,
+}
+
+impl NameIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a lobby's roster change; pass an empty roster on either side for a lobby opening or closing
+    pub fn update(&self, lobby_code: &str, before: &[PlayerListing], after: &[PlayerListing]) {
+        if before == after {
+            return;
+        }
+        let mut names = self.names.write().unwrap_or_else(|e| e.into_inner());
+        for player in before.iter().filter(|p| !after.contains(p)) {
+            let key = player.name.to_lowercase();
+            if let Some(entries) = names.get_mut(&key) {
+                entries.remove(&(lobby_code.to_string(), player.id));
+                if entries.is_empty() {
+                    names.remove(&key);
+                }
+            }
+        }
+        for player in after.iter().filter(|p| !before.contains(p)) {
+            names.entry(player.name.to_lowercase()).or_default().insert((lobby_code.to_string(), player.id));
+        }
+    }
+
+    /// Up to `limit` (lobby code, player id) pairs whose name starts with `prefix`, ignoring case
+    pub fn search(&self, prefix: &str, limit: usize) -> Vec<(LobbyCode, u32)> {
+        let prefix = prefix.to_lowercase();
+        let names = self.names.read().unwrap_or_else(|e| e.into_inner());
+        names
+            .range(prefix.clone()..)
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .flat_map(|(_, entries)| entries.iter().cloned())
+            .take(limit)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(id: u32, name: &str) -> PlayerListing {
+        PlayerListing { id, name: name.to_string(), team_id: None }
+    }
+
+    #[test]
+    fn test_search_by_prefix_follows_roster_changes() {
+        let index = NameIndex::new();
+        index.update("alpha", &[], &[listing(1, "Ada"), listing(2, "Adam")]);
+        index.update("beta", &[], &[listing(3, "ada"), listing(4, "Bob")]);
+
+        assert_eq!(index.search("ADA", 10), vec![
+            ("alpha".to_string(), 1),
+            ("beta".to_string(), 3),
+            ("alpha".to_string(), 2),
+        ]);
+        assert_eq!(index.search("ada", 1).len(), 1);
+        assert!(index.search("carol", 10).is_empty());
+
+        // Ada leaves alpha and Bob's lobby closes
+        index.update("alpha", &[listing(1, "Ada"), listing(2, "Adam")], &[listing(2, "Adam")]);
+        index.update("beta", &[listing(3, "ada"), listing(4, "Bob")], &[]);
+        assert_eq!(index.search("ad", 10), vec![("alpha".to_string(), 2)]);
+        assert!(index.search("b", 10).is_empty());
+    }
+}
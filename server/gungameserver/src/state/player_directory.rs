@@ -0,0 +1,85 @@
+use dashmap::DashMap;
+use uuid::Uuid;
+
+/// Maps public player UUIDs to the numeric handles used inside lobbies
+///
+/// Clients identify themselves with the UUID handed out on join; lobbies, commands
+/// and binary packets keep the compact u32 handle. Bots have no UUID.
+pub struct PlayerDirectory {
+    by_uuid: DashMap<Uuid, u32>,
+    by_handle: DashMap<u32, Uuid>,
+}
+
+impl PlayerDirectory {
+    pub fn new() -> Self {
+        Self {
+            by_uuid: DashMap::new(),
+            by_handle: DashMap::new(),
+        }
+    }
+
+    /// Give a handle a fresh UUID (replaces any previous one)
+    pub fn register(&self, handle: u32) -> Uuid {
+        let uuid = Uuid::new_v4();
+        if let Some(old) = self.by_handle.insert(handle, uuid) {
+            self.by_uuid.remove(&old);
+        }
+        self.by_uuid.insert(uuid, handle);
+        uuid
+    }
+
+    /// Give a handle back the UUID it had before a restart
+    pub fn restore(&self, handle: u32, uuid: Uuid) {
+        if let Some(old) = self.by_handle.insert(handle, uuid) {
+            self.by_uuid.remove(&old);
+        }
+        self.by_uuid.insert(uuid, handle);
+    }
+
+    /// Handle for a UUID string from the wire
+    pub fn resolve(&self, uuid: &str) -> Option<u32> {
+        let uuid = Uuid::parse_str(uuid).ok()?;
+        self.by_uuid.get(&uuid).map(|handle| *handle)
+    }
+
+    pub fn uuid(&self, handle: u32) -> Option<Uuid> {
+        self.by_handle.get(&handle).map(|uuid| *uuid)
+    }
+
+    /// Drop the mapping when the player leaves
+    pub fn forget(&self, handle: u32) {
+        if let Some((_, uuid)) = self.by_handle.remove(&handle) {
+            self.by_uuid.remove(&uuid);
+        }
+    }
+}
+
+impl Default for PlayerDirectory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_resolve() {
+        let directory = PlayerDirectory::new();
+        let uuid = directory.register(7);
+        assert_eq!(directory.resolve(&uuid.to_string()), Some(7));
+        assert_eq!(directory.uuid(7), Some(uuid));
+        assert_eq!(directory.resolve("not-a-uuid"), None);
+        assert_eq!(directory.resolve(&Uuid::new_v4().to_string()), None);
+    }
+
+    #[test]
+    fn test_forget_releases_both_directions() {
+        let directory = PlayerDirectory::new();
+        let uuid = directory.register(7);
+        directory.forget(7);
+        assert_eq!(directory.resolve(&uuid.to_string()), None);
+        assert_eq!(directory.uuid(7), None);
+    }
+}
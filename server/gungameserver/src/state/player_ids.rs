@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Ids reserved per write to the state file
+const RESERVATION_BLOCK: u32 = 1000;
+
+struct Allocation {
+    next: u32,
+    reserved_until: u32, // First id not yet covered by the state file
+    path: Option<PathBuf>,
+}
+
+/// Hands out player ids that stay unique across restarts
+///
+/// The state file holds the end of the current reservation block. After a restart
+/// allocation resumes there, so ids from the previous run (stale clients, recorded
+/// stats) are never reused. Skipping the unused rest of a block is the price of
+/// writing the file once per block instead of once per id.
+pub struct PlayerIdAllocator {
+    allocation: Mutex<Allocation>,
+}
+
+impl PlayerIdAllocator {
+    /// In-memory allocator starting at 1 (nothing persisted)
+    pub fn new() -> Self {
+        Self {
+            allocation: Mutex::new(Allocation { next: 1, reserved_until: 1, path: None }),
+        }
+    }
+
+    /// Resume from a state file (missing file = first run) and persist reservations there
+    pub fn restore(&self, path: &Path) -> Result<(), String> {
+        let saved = match std::fs::read_to_string(path) {
+            Ok(text) => text
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Corrupt player id state in {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 1,
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+
+        let mut allocation = self.allocation.lock().unwrap();
+        allocation.next = allocation.next.max(saved);
+        allocation.reserved_until = allocation.next;
+        allocation.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Never hand out `id` or anything below it again (ids restored from a checkpoint)
+    pub fn skip_past(&self, id: u32) {
+        let mut allocation = self.allocation.lock().unwrap();
+        allocation.next = allocation.next.max(id.saturating_add(1));
+    }
+
+    /// Next unused player id
+    pub fn allocate(&self) -> u32 {
+        let mut allocation = self.allocation.lock().unwrap();
+        if allocation.next == u32::MAX {
+            allocation.next = 1; // Wrapped: only reachable after ~4 billion joins
+        }
+        let id = allocation.next;
+        allocation.next += 1;
+
+        if id >= allocation.reserved_until {
+            allocation.reserved_until = id.saturating_add(RESERVATION_BLOCK);
+            if let Some(path) = &allocation.path {
+                if let Err(e) = persist(path, allocation.reserved_until) {
+                    log::warn!("Failed to persist player id state: {}", e);
+                }
+            }
+        }
+        id
+    }
+}
+
+impl Default for PlayerIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Atomically replace the state file
+fn persist(path: &Path, reserved_until: u32) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, reserved_until.to_string())?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gungame-{}-{}.state", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_ids_survive_restart() {
+        let path = state_file("ids-restart");
+        let _ = std::fs::remove_file(&path);
+
+        let first_run = PlayerIdAllocator::new();
+        first_run.restore(&path).unwrap();
+        let issued: Vec<u32> = (0..5).map(|_| first_run.allocate()).collect();
+        assert_eq!(issued, vec![1, 2, 3, 4, 5]);
+
+        // Restart: the new run resumes after the reserved block
+        let second_run = PlayerIdAllocator::new();
+        second_run.restore(&path).unwrap();
+        let next = second_run.allocate();
+        assert_eq!(next, 1 + RESERVATION_BLOCK);
+        assert!(issued.iter().all(|id| *id < next));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reserves_a_new_block_when_exhausted() {
+        let path = state_file("ids-block");
+        let allocator = PlayerIdAllocator::new();
+        allocator.restore(&path).unwrap();
+        for _ in 0..=RESERVATION_BLOCK {
+            allocator.allocate();
+        }
+        let saved: u32 = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(saved, 1 + 2 * RESERVATION_BLOCK);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_state_is_an_error() {
+        let path = state_file("ids-corrupt");
+        std::fs::write(&path, "not a number").unwrap();
+        assert!(PlayerIdAllocator::new().restore(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
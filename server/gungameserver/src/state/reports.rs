@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::domain::anticheat::AuditedShot;
+
+/// Reasons a player can be reported for
+pub const REPORT_REASONS: &[&str] = &["cheating", "griefing", "abusive_chat", "offensive_name", "other"];
+
+/// Longest free text note on a report or its resolution
+pub const MAX_REPORT_DETAILS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Open,
+    Dismissed, // Reviewed, no action
+    Actioned, // Reviewed, the reported player was dealt with
+}
+
+impl ReportStatus {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "open" => Some(ReportStatus::Open),
+            "dismissed" => Some(ReportStatus::Dismissed),
+            "actioned" => Some(ReportStatus::Actioned),
+            _ => None,
+        }
+    }
+}
+
+/// What the server knew about the reported player when the report came in
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReportEvidence {
+    pub violations: BTreeMap<String, u32>, // Anti-cheat flags by kind
+    pub recent_shots: Vec<AuditedShot>, // Oldest first
+}
+
+/// A report submitted by one player about another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerReport {
+    pub id: u64,
+    pub created_at: u64, // Unix seconds
+    pub reporter_uuid: String,
+    pub reporter_name: String,
+    pub reported_uuid: String,
+    pub reported_name: String,
+    pub reason: String, // One of REPORT_REASONS
+    pub details: Option<String>,
+    pub lobby_code: String,
+    pub match_id: Option<String>,
+    pub evidence: ReportEvidence,
+    pub status: ReportStatus,
+    pub resolution_note: Option<String>,
+}
+
+/// Player reports awaiting or after moderator review
+/// Every change is appended to a JSON lines file; the last line per report wins on restore
+#[derive(Debug, Default)]
+pub struct ReportStore {
+    reports: Mutex<BTreeMap<u64, PlayerReport>>, // Report ID -> report, oldest first
+    path: Mutex<Option<PathBuf>>, // None = kept in memory only
+}
+
+impl ReportStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the reports saved at `path` and persist every later change there
+    /// A missing file starts an empty store
+    pub fn restore(&self, path: &Path) -> Result<(), String> {
+        let saved = match std::fs::read_to_string(path) {
+            Ok(saved) => saved,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        for (number, line) in saved.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let report: PlayerReport = serde_json::from_str(line)
+                .map_err(|e| format!("Invalid report on line {} of {}: {}", number + 1, path.display(), e))?;
+            reports.insert(report.id, report);
+        }
+        *self.path.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Whether the reporter already has an open report about this player
+    pub fn has_open(&self, reporter_uuid: &str, reported_uuid: &str) -> bool {
+        self.reports.lock().unwrap_or_else(|e| e.into_inner()).values().any(|r| {
+            r.status == ReportStatus::Open && r.reporter_uuid == reporter_uuid && r.reported_uuid == reported_uuid
+        })
+    }
+
+    /// File a report; its `id` is assigned here and returned
+    pub fn submit(&self, mut report: PlayerReport) -> std::io::Result<u64> {
+        let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        report.id = reports.keys().next_back().map_or(1, |id| id + 1);
+        report.status = ReportStatus::Open;
+        self.persist(&report)?;
+        let id = report.id;
+        reports.insert(id, report);
+        Ok(id)
+    }
+
+    /// Reports with the given status, oldest first
+    pub fn with_status(&self, status: ReportStatus) -> Vec<PlayerReport> {
+        self.reports
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter(|r| r.status == status)
+            .cloned()
+            .collect()
+    }
+
+    /// Close an open report with a moderator decision
+    pub fn resolve(&self, id: u64, status: ReportStatus, note: Option<String>) -> Result<PlayerReport, &'static str> {
+        if status == ReportStatus::Open {
+            return Err("Resolution must close the report");
+        }
+        let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        let report = reports.get_mut(&id).ok_or("Report not found")?;
+        if report.status != ReportStatus::Open {
+            return Err("Report already resolved");
+        }
+
+        let mut resolved = report.clone();
+        resolved.status = status;
+        resolved.resolution_note = note;
+        self.persist(&resolved).map_err(|_| "Failed to save report")?;
+        *report = resolved.clone();
+        Ok(resolved)
+    }
+
+    fn persist(&self, report: &PlayerReport) -> std::io::Result<()> {
+        let path = self.path.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(path) = path.as_ref() else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_vec(report)?;
+        line.push(b'\n');
+        std::fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(reporter: &str, reported: &str) -> PlayerReport {
+        PlayerReport {
+            id: 0,
+            created_at: 1_700_000_000,
+            reporter_uuid: reporter.to_string(),
+            reporter_name: "Alice".to_string(),
+            reported_uuid: reported.to_string(),
+            reported_name: "Mallory".to_string(),
+            reason: "cheating".to_string(),
+            details: None,
+            lobby_code: "alpha".to_string(),
+            match_id: None,
+            evidence: ReportEvidence::default(),
+            status: ReportStatus::Open,
+            resolution_note: None,
+        }
+    }
+
+    #[test]
+    fn test_review_queue() {
+        let store = ReportStore::new();
+        assert_eq!(store.submit(report("a", "m")).unwrap(), 1);
+        assert_eq!(store.submit(report("b", "m")).unwrap(), 2);
+        assert!(store.has_open("a", "m"));
+
+        let resolved = store.resolve(1, ReportStatus::Actioned, Some("Banned".to_string())).unwrap();
+        assert_eq!(resolved.status, ReportStatus::Actioned);
+        assert_eq!(store.resolve(1, ReportStatus::Dismissed, None), Err("Report already resolved"));
+        assert_eq!(store.resolve(9, ReportStatus::Dismissed, None), Err("Report not found"));
+        assert!(!store.has_open("a", "m"));
+        assert_eq!(store.with_status(ReportStatus::Open).iter().map(|r| r.id).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_reports_survive_restart() {
+        let path = std::env::temp_dir().join(format!("gungame-reports-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let store = ReportStore::new();
+        store.restore(&path).unwrap();
+        store.submit(report("a", "m")).unwrap();
+        store.submit(report("b", "m")).unwrap();
+        store.resolve(1, ReportStatus::Dismissed, None).unwrap();
+
+        let restored = ReportStore::new();
+        restored.restore(&path).unwrap();
+        assert_eq!(restored.with_status(ReportStatus::Dismissed).len(), 1);
+        assert_eq!(restored.with_status(ReportStatus::Open).iter().map(|r| r.id).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(restored.submit(report("c", "m")).unwrap(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,10 +1,38 @@
 use dashmap::DashMap;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tokio::sync::{RwLock, mpsc};
+use tokio::net::UdpSocket;
 use tokio::task::JoinHandle;
-use crate::state::lobby::{Lobby, LobbyCode};
+use crate::state::lobby::{Lobby, LobbyCode, LobbySpec};
 use crate::state::global_stats::GlobalStats;
+use crate::state::ip_sessions::IpSessionLimiter;
+use crate::state::bans::BanList;
+use crate::state::telemetry::TelemetryBuffer;
+use crate::domain::chat::ChatFilter;
+use crate::domain::clock::{Clock, SystemClock};
+use crate::state::player_ids::PlayerIdAllocator;
+use crate::state::player_directory::PlayerDirectory;
+use crate::state::session_binding::{SessionBindings, SourceCheck};
+use crate::net::protocol::{ClientProtocol, CAP_BINARY};
+use crate::net::reliable::ReceiveWindow;
+use crate::net::renet_transport::RenetTransport;
+use crate::net::auth::TokenIssuer;
+use crate::storage::Storage;
+use crate::net::crypto::{self, Direction, SessionCipher};
+use crate::state::snapshot::{LobbyListing, PlayerListing, Snapshot};
+use crate::state::reports::ReportStore;
+use crate::state::audit::AuditLog;
+use crate::state::balance::BalanceReport;
+use crate::state::name_index::NameIndex;
+use crate::state::ladder_presets::LadderPresets;
+use crate::state::warm_pool::WarmPool;
+use crate::state::lobby_ports::LobbyPorts;
+use crate::utils::scenedb::SceneDb;
+use crate::utils::tunables::TunablesStore;
+use crate::utils::weapondb::WeaponStore;
 
 /// Maximum allowed lobby code length
 const MAX_LOBBY_CODE_LENGTH: usize = 32;
@@ -12,32 +40,124 @@ const MAX_LOBBY_CODE_LENGTH: usize = 32;
 /// Maximum allowed player name length
 const MAX_PLAYER_NAME_LENGTH: usize = 64;
 
+/// Closed lobbies remembered for `GET /lobbies?since=`; older removals need a full refresh
+const MAX_REMOVED_LOBBIES: usize = 1024;
+
+/// Weight of the newest tick when smoothing tick load
+const TICK_LOAD_SMOOTHING: f32 = 0.1;
+
 /// Handle to a lobby with its command queue and tick task
 pub struct LobbyHandle {
     pub lobby: Arc<RwLock<Lobby>>,
     pub command_tx: mpsc::Sender<crate::state::commands::LobbyCommand>,
     pub task_handle: JoinHandle<()>,
+    pub udp_tasks: Vec<JoinHandle<()>>,  // Receive and send tasks of the lobby's dedicated UDP socket, if it has one
+    pub socket: Option<Arc<UdpSocket>>,  // Where the lobby's packets go out; None = nobody to tell when it closes
+    pub listing: Snapshot<LobbyListing>,  // Browser view for HTTP reads, refreshed once per tick
 }
 
 /// Server state partitioned by lobby
 /// Uses DashMap for concurrent access without global locks
 pub struct ServerState {
     lobbies: DashMap<LobbyCode, LobbyHandle>,
-    next_player_id: AtomicU32,
+    player_ids: PlayerIdAllocator,  // Unique across restarts once restored from the state file
+    pub player_directory: PlayerDirectory,  // Public player UUID <-> numeric handle
+    pub scenes: SceneDb,  // Spawn points per scene, applied when a lobby is created
+    pub reports: ReportStore,  // Player reports awaiting moderator review
+    pub audit: AuditLog,  // Referee actions taken inside lobbies
+    pub ladder_presets: LadderPresets,  // Named gun game ladders
+    pub warm_pool: WarmPool,  // Pre-created lobbies for matchmaking
+    pub lobby_ports: LobbyPorts,  // Dedicated UDP ports lobbies can get instead of the shared socket
+    pub persistent_lobbies: DashMap<LobbyCode, LobbySpec>,  // Recreated whenever they are removed
+    pub tunables: TunablesStore,  // Gameplay numbers, changeable live
+    pub weapons: WeaponStore,  // Weapon definitions, reloadable live
+    pub balance_report: Snapshot<Option<BalanceReport>>,  // Latest weapon balance report, None until built
     pub global_stats: Arc<GlobalStats>,
     pub player_lobby_index: DashMap<u32, LobbyCode>,  // Player ID -> Lobby Code index for O(1) lookup
+    spectate_codes: DashMap<LobbyCode, LobbyCode>,  // Spectate code -> lobby code
+    player_names: NameIndex,  // Player name -> (lobby code, player ID), for cross-lobby search
+    pub ip_sessions: IpSessionLimiter,  // Concurrent sessions per source IP (bot farm cap)
+    pub bans: BanList,  // Source IPs banned by an admin
+    pub telemetry: TelemetryBuffer,  // Sampled gameplay events awaiting the next flush (opt-in)
+    client_protocols: DashMap<SocketAddr, ClientProtocol>,  // Negotiated protocol per client address
+    receive_windows: DashMap<SocketAddr, ReceiveWindow>,  // Reliable packets received per client address
+    web_clients: DashMap<SocketAddr, mpsc::Sender<Vec<u8>>>,  // WebSocket clients -> their connection's packet queue
+    renet: OnceLock<Arc<RenetTransport>>,  // Set when the UDP port speaks renet
+    storage: OnceLock<Arc<Storage>>,  // Set when `storage_path` is configured; accounts need it
+    account_tokens: OnceLock<TokenIssuer>,  // Signs account tokens (JWT)
+    session_ciphers: DashMap<u32, SessionCipher>,  // Player ID -> UDP session key (opt-in encryption)
+    encrypted_clients: DashMap<SocketAddr, u32>,  // Addresses that have sent encrypted datagrams
+    bindings: SessionBindings,  // Session token and bound UDP address per player
+    tick_loads: DashMap<LobbyCode, f32>,  // Smoothed fraction of the tick budget each lobby uses
+    draining: AtomicBool,  // Refuse new lobbies and joins ahead of scale-in
+    joins_frozen: AtomicBool,  // Admin freeze: refuse joins, running lobbies are unaffected
+    message_text: AtomicBool,  // Add English text next to message codes (debugging)
+    chat_filter: std::sync::RwLock<Arc<ChatFilter>>,  // Applied to every chat message before it is relayed
+    browser_revision: AtomicU64,  // Bumped on every lobby browser change (lobby created/closed, roster changed)
+    lobby_revisions: DashMap<LobbyCode, u64>,  // Revision each open lobby last changed at
+    removed_lobbies: DashMap<LobbyCode, u64>,  // Revision each closed lobby was removed at
+    removed_floor: AtomicU64,  // Removals at or below this revision have been forgotten
+    pub clock: Arc<dyn Clock>,  // Time the tick loops simulate at; tests swap in a `ManualClock`
+    started_at: Instant,
 }
 
 impl ServerState {
     pub fn new() -> Self {
         Self {
             lobbies: DashMap::new(),
-            next_player_id: AtomicU32::new(1),
+            player_ids: PlayerIdAllocator::new(),
+            player_directory: PlayerDirectory::new(),
+            scenes: SceneDb::load(),
+            reports: ReportStore::new(),
+            audit: AuditLog::new(),
+            ladder_presets: LadderPresets::new(),
+            warm_pool: WarmPool::new(),
+            lobby_ports: LobbyPorts::new(),
+            persistent_lobbies: DashMap::new(),
+            tunables: TunablesStore::new(),
+            weapons: WeaponStore::new(),
+            balance_report: Snapshot::default(),
             global_stats: Arc::new(GlobalStats::new()),
             player_lobby_index: DashMap::new(),
+            spectate_codes: DashMap::new(),
+            player_names: NameIndex::new(),
+            ip_sessions: IpSessionLimiter::new(0),
+            bans: BanList::new(),
+            telemetry: TelemetryBuffer::new(),
+            client_protocols: DashMap::new(),
+            receive_windows: DashMap::new(),
+            web_clients: DashMap::new(),
+            renet: OnceLock::new(),
+            storage: OnceLock::new(),
+            account_tokens: OnceLock::new(),
+            session_ciphers: DashMap::new(),
+            encrypted_clients: DashMap::new(),
+            bindings: SessionBindings::new(),
+            tick_loads: DashMap::new(),
+            draining: AtomicBool::new(false),
+            joins_frozen: AtomicBool::new(false),
+            message_text: AtomicBool::new(false),
+            chat_filter: std::sync::RwLock::new(Arc::new(ChatFilter::default())),
+            browser_revision: AtomicU64::new(0),
+            lobby_revisions: DashMap::new(),
+            removed_lobbies: DashMap::new(),
+            removed_floor: AtomicU64::new(0),
+            clock: Arc::new(SystemClock),
+            started_at: Instant::now(),
         }
     }
 
+    /// Server whose lobbies tick at the time `clock` gives
+    #[cfg(test)]
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..Self::new() }
+    }
+
+    /// Time since the server started
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
     /// Validate lobby code
     pub fn is_valid_lobby_code(code: &str) -> bool {
         !code.is_empty() && code.len() <= MAX_LOBBY_CODE_LENGTH && code.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
@@ -63,6 +183,233 @@ impl ServerState {
         self.player_lobby_index.get(&player_id).map(|entry| entry.value().clone())
     }
 
+    /// Remember that a client sent binary packets (it receives binary broadcasts back)
+    pub fn mark_binary_client(&self, addr: SocketAddr) {
+        self.client_protocols.entry(addr).or_default().capabilities |= CAP_BINARY;
+    }
+
+    /// Record the protocol version negotiated with a client
+    pub fn set_protocol_version(&self, addr: SocketAddr, version: u32) {
+        self.client_protocols.entry(addr).or_default().version = version;
+    }
+
+    /// Record the capability flags negotiated with a client
+    pub fn set_capabilities(&self, addr: SocketAddr, capabilities: u32) {
+        self.client_protocols.entry(addr).or_default().capabilities = capabilities;
+    }
+
+    /// Get the negotiated protocol for a client (legacy defaults if unknown)
+    pub fn client_protocol(&self, addr: &SocketAddr) -> ClientProtocol {
+        self.client_protocols.get(addr).map(|p| *p).unwrap_or_default()
+    }
+
+    /// Forget protocol state for a client address (call when it disconnects)
+    pub fn forget_client(&self, addr: &SocketAddr) {
+        self.client_protocols.remove(addr);
+        self.receive_windows.remove(addr);
+    }
+
+    /// Route packets for `addr` to a WebSocket connection's packet queue instead of UDP
+    pub fn register_web_client(&self, addr: SocketAddr, packets: mpsc::Sender<Vec<u8>>) {
+        self.web_clients.insert(addr, packets);
+    }
+
+    /// Forget a closed WebSocket connection
+    pub fn remove_web_client(&self, addr: &SocketAddr) {
+        self.web_clients.remove(addr);
+    }
+
+    /// Persist accounts and match stats in `storage` from now on
+    pub fn use_storage(&self, storage: Arc<Storage>) {
+        let _ = self.storage.set(storage);
+    }
+
+    pub fn storage(&self) -> Option<&Arc<Storage>> {
+        self.storage.get()
+    }
+
+    /// Sign and check account tokens with `issuer` from now on
+    pub fn use_account_tokens(&self, issuer: TokenIssuer) {
+        let _ = self.account_tokens.set(issuer);
+    }
+
+    pub fn account_tokens(&self) -> Option<&TokenIssuer> {
+        self.account_tokens.get()
+    }
+
+    /// Carry UDP traffic over renet from now on (queued here, flushed by the UDP server)
+    pub fn use_renet(&self, transport: Arc<RenetTransport>) {
+        let _ = self.renet.set(transport);
+    }
+
+    /// Send a packet to a client over its transport: its WebSocket if it connected with one, else
+    /// UDP (as a renet message when the UDP port speaks renet)
+    /// A full WebSocket queue drops the packet, like a lost datagram
+    pub async fn send_datagram(&self, socket: &UdpSocket, addr: SocketAddr, data: &[u8]) -> std::io::Result<usize> {
+        let Some(packets) = self.web_clients.get(&addr).map(|p| p.clone()) else {
+            return match self.renet.get() {
+                Some(renet) if renet.send(addr, data) => Ok(data.len()),
+                Some(_) => Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "No renet connection")),
+                None => socket.send_to(data, addr).await,
+            };
+        };
+        packets
+            .try_send(data.to_vec())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::WouldBlock, "WebSocket send queue full or closed"))?;
+        Ok(data.len())
+    }
+
+    /// Record a reliable packet from a client
+    /// Returns whether it is new (not a resend) and the ack header to reply with
+    pub fn receive_reliable(&self, addr: SocketAddr, seq: u64) -> (bool, (u64, u32)) {
+        let mut window = self.receive_windows.entry(addr).or_default();
+        let fresh = window.receive(seq);
+        (fresh, window.ack_header())
+    }
+
+    /// Create a UDP session key for a player (returned once over the HTTP join)
+    pub fn create_session_key(&self, player_id: u32) -> String {
+        let (cipher, key) = SessionCipher::generate();
+        self.session_ciphers.insert(player_id, cipher);
+        key
+    }
+
+    /// Decrypt a datagram from a client and bind its address to the session
+    /// Returns the authenticated player id and the plaintext
+    pub fn open_encrypted(&self, addr: SocketAddr, data: &[u8]) -> Result<(u32, Vec<u8>), &'static str> {
+        let player_id = crypto::peek_player_id(data).ok_or("Malformed encrypted packet")?;
+        let mut cipher = self.session_ciphers.get_mut(&player_id).ok_or("No session key for player")?;
+        let plaintext = cipher.open(Direction::ClientToServer, data)?;
+        drop(cipher);
+
+        self.encrypted_clients.insert(addr, player_id);
+        Ok((player_id, plaintext))
+    }
+
+    /// Encrypt an outgoing datagram if the client at `addr` talks encrypted
+    /// Returns None for plaintext clients (legacy fallback)
+    pub fn seal_outbound(&self, addr: &SocketAddr, data: &[u8]) -> Option<Vec<u8>> {
+        let player_id = *self.encrypted_clients.get(addr)?;
+        let mut cipher = self.session_ciphers.get_mut(&player_id)?;
+        cipher.seal(Direction::ServerToClient, player_id, data).ok()
+    }
+
+    /// Drop a player's UUID, session key, token and address binding (call when they leave)
+    pub fn end_session(&self, player_id: u32) {
+        self.player_directory.forget(player_id);
+        self.session_ciphers.remove(&player_id);
+        self.encrypted_clients.retain(|_, id| *id != player_id);
+        self.bindings.release(player_id);
+    }
+
+    /// Issue the session token a client presents when its UDP address changes
+    pub fn create_session_token(&self, player_id: u32) -> String {
+        self.bindings.issue_token(player_id)
+    }
+
+    /// Check that a packet from `addr` may act for a player, binding or rebinding the session
+    /// `encrypted` packets were already authenticated with the session key
+    pub fn authorize_source(
+        &self,
+        player_id: u32,
+        addr: SocketAddr,
+        token: Option<&str>,
+        encrypted: bool,
+    ) -> Result<SourceCheck, &'static str> {
+        let token_valid = match token {
+            Some(token) if self.bindings.verify_token(player_id, token) => true,
+            Some(_) => return Err("Invalid session token"),
+            None => false,
+        };
+
+        let check = self.bindings.check(player_id, addr, encrypted || token_valid)?;
+        match check {
+            SourceCheck::FirstBind => self.bindings.bind(player_id, addr),
+            SourceCheck::Rebind { previous } => {
+                self.ip_sessions.try_acquire(addr.ip(), player_id)?;
+                if let Some((_, protocol)) = self.client_protocols.remove(&previous) {
+                    self.client_protocols.insert(addr, protocol);
+                }
+                self.bindings.bind(player_id, addr);
+            }
+            SourceCheck::Unmanaged | SourceCheck::Bound => {}
+        }
+        Ok(check)
+    }
+
+    /// The session token issued to a player, for checkpointing
+    pub fn session_token(&self, player_id: u32) -> Option<String> {
+        self.bindings.token(player_id)
+    }
+
+    /// Reinstate a restored player's session, so their client can `reconnect` with its token
+    pub fn restore_session(&self, player_id: u32, token: String) {
+        self.player_ids.skip_past(player_id);
+        self.bindings.restore_token(player_id, token);
+    }
+
+    /// Whether `token` is the session token issued to the player on join
+    pub fn verify_session_token(&self, player_id: u32, token: &str) -> bool {
+        self.bindings.verify_token(player_id, token)
+    }
+
+    /// Record how much of its tick budget a lobby used this tick
+    pub fn record_tick_load(&self, lobby_code: &str, load: f32) {
+        self.tick_loads
+            .entry(lobby_code.to_string())
+            .and_modify(|smoothed| *smoothed += (load - *smoothed) * TICK_LOAD_SMOOTHING)
+            .or_insert(load);
+    }
+
+    /// Smoothed tick load of one lobby (None before its first tick)
+    pub fn tick_load(&self, lobby_code: &str) -> Option<f32> {
+        self.tick_loads.get(lobby_code).map(|load| *load)
+    }
+
+    /// Smoothed tick load of every lobby
+    pub fn tick_loads(&self) -> Vec<f32> {
+        self.tick_loads.iter().map(|entry| *entry.value()).collect()
+    }
+
+    /// Enter or leave drain mode
+    pub fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::Relaxed);
+    }
+
+    /// Whether the server is draining (no new lobbies or joins)
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Freeze or unfreeze joins across all lobbies
+    pub fn set_joins_frozen(&self, frozen: bool) {
+        self.joins_frozen.store(frozen, Ordering::Relaxed);
+    }
+
+    /// Whether joins are refused (frozen by an admin, or draining)
+    pub fn joins_frozen(&self) -> bool {
+        self.joins_frozen.load(Ordering::Relaxed) || self.is_draining()
+    }
+
+    /// Include English `message` text alongside message codes
+    pub fn set_message_text(&self, enabled: bool) {
+        self.message_text.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether packets carry English text next to message codes
+    pub fn message_text(&self) -> bool {
+        self.message_text.load(Ordering::Relaxed)
+    }
+
+    /// Replace the chat moderation filter; lobbies use it from their next message
+    pub fn set_chat_filter(&self, filter: ChatFilter) {
+        *self.chat_filter.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(filter);
+    }
+
+    pub fn chat_filter(&self) -> Arc<ChatFilter> {
+        self.chat_filter.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     /// Get command sender for a lobby (for UDP handlers)
     /// Returns None if lobby doesn't exist
     pub fn get_lobby_tx(&self, lobby_code: &str) -> Option<mpsc::Sender<crate::state::commands::LobbyCommand>> {
@@ -83,17 +430,80 @@ impl ServerState {
 
     /// Generate next player ID (lock-free)
     pub fn next_player_id(&self) -> u32 {
-        self.next_player_id.fetch_add(1, Ordering::Relaxed)
+        self.player_ids.allocate()
+    }
+
+    /// Resume player ids after the previous run's (call once at startup)
+    pub fn restore_player_ids(&self, path: &std::path::Path) -> Result<(), String> {
+        self.player_ids.restore(path)
     }
 
     /// Insert a new lobby handle
     pub fn insert_lobby(&self, code: LobbyCode, handle: LobbyHandle) {
-        self.lobbies.insert(code, handle);
+        self.removed_lobbies.remove(&code);
+        let listing = handle.listing.load();
+        self.spectate_codes.insert(listing.spectate_code.clone(), code.clone());
+        self.player_names.update(&code, &[], &listing.players);
+        self.lobbies.insert(code.clone(), handle);
+        self.touch_lobby(&code);
     }
 
     /// Remove a lobby (graceful shutdown)
     pub fn remove_lobby(&self, lobby_code: &str) -> Option<LobbyHandle> {
-        self.lobbies.remove(lobby_code).map(|(_, handle)| handle)
+        self.tick_loads.remove(lobby_code);
+        self.warm_pool.forget(lobby_code);
+        let handle = self.lobbies.remove(lobby_code).map(|(_, handle)| handle)?;
+        let listing = handle.listing.load();
+        self.spectate_codes.remove(&listing.spectate_code);
+        self.player_names.update(lobby_code, &listing.players, &[]);
+
+        self.lobby_revisions.remove(lobby_code);
+        let revision = self.browser_revision.fetch_add(1, Ordering::Relaxed) + 1;
+        self.removed_lobbies.insert(lobby_code.to_string(), revision);
+        if self.removed_lobbies.len() > MAX_REMOVED_LOBBIES {
+            let oldest = self.removed_lobbies.iter().map(|entry| (*entry.value(), entry.key().clone())).min();
+            if let Some((revision, code)) = oldest {
+                self.removed_lobbies.remove(&code);
+                self.removed_floor.fetch_max(revision, Ordering::Relaxed);
+            }
+        }
+        Some(handle)
+    }
+
+    /// Lobby a public spectate code watches
+    pub fn resolve_spectate_code(&self, spectate_code: &str) -> Option<LobbyCode> {
+        self.spectate_codes.get(spectate_code).map(|code| code.clone())
+    }
+
+    /// Record that a lobby's browser entry changed
+    /// Only once its new listing is published (see `publish_listing`), or a reader could pair the new ETag with the old listing
+    fn touch_lobby(&self, lobby_code: &str) {
+        let revision = self.browser_revision.fetch_add(1, Ordering::Relaxed) + 1;
+        self.lobby_revisions.insert(lobby_code.to_string(), revision);
+    }
+
+    /// Current lobby browser revision (also the `GET /lobbies` ETag)
+    pub fn browser_revision(&self) -> u64 {
+        self.browser_revision.load(Ordering::Relaxed)
+    }
+
+    /// Revision a lobby last changed at (0 if unknown)
+    pub fn lobby_revision(&self, lobby_code: &str) -> u64 {
+        self.lobby_revisions.get(lobby_code).map(|revision| *revision).unwrap_or(0)
+    }
+
+    /// Lobbies closed after `since`, or None if that's too old to diff from
+    pub fn removed_lobbies_since(&self, since: u64) -> Option<Vec<LobbyCode>> {
+        if since < self.removed_floor.load(Ordering::Relaxed) || since > self.browser_revision() {
+            return None;
+        }
+        let mut removed: Vec<LobbyCode> = self.removed_lobbies
+            .iter()
+            .filter(|entry| *entry.value() > since)
+            .map(|entry| entry.key().clone())
+            .collect();
+        removed.sort();
+        Some(removed)
     }
 
     /// Iterate over all lobbies (for cleanup tasks)
@@ -101,6 +511,68 @@ impl ServerState {
         self.lobbies.iter()
     }
 
+    /// Latest listings of the lobbies `filter` accepts
+    /// Collected up front, so callers can await without holding map shards
+    pub fn lobbies_where(&self, filter: impl Fn(&LobbyListing) -> bool) -> impl Iterator<Item = Arc<LobbyListing>> {
+        self.lobbies
+            .iter()
+            .map(|handle| handle.listing.load())
+            .filter(|listing| filter(listing))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Commands waiting on a lobby's queue, and how many it holds
+    pub fn command_queue_depth(&self, lobby_code: &str) -> Option<(usize, usize)> {
+        self.lobbies
+            .get(lobby_code)
+            .map(|handle| (handle.command_tx.max_capacity() - handle.command_tx.capacity(), handle.command_tx.max_capacity()))
+    }
+
+    /// Queue a command on a lobby's tick loop without waiting
+    /// False when the lobby is gone or its queue is full
+    pub fn inject(&self, lobby_code: &str, command: crate::state::commands::LobbyCommand) -> bool {
+        self.get_lobby_tx(lobby_code).is_some_and(|tx| tx.try_send(command).is_ok())
+    }
+
+    /// Publish a lobby's browser listing (tick loop, and HTTP handlers right after a change)
+    /// Call with the lobby lock held, so listings land in the order they were built; `changed` moves
+    /// the browser revision on once the listing is in place
+    pub fn publish_listing(&self, lobby_code: &str, listing: LobbyListing, changed: bool) {
+        if let Some(handle) = self.lobbies.get(lobby_code) {
+            self.player_names.update(lobby_code, &handle.listing.load().players, &listing.players);
+            handle.listing.store(listing);
+        }
+        if changed {
+            self.touch_lobby(lobby_code);
+        }
+    }
+
+    /// Publish the listing of a lobby that just changed, with its lock held
+    pub fn publish_change(&self, lobby: &Lobby) -> LobbyListing {
+        let listing = LobbyListing::of(lobby);
+        self.publish_listing(&lobby.code, listing.clone(), true);
+        listing
+    }
+
+    /// Players in open lobbies whose name starts with `prefix` (ignoring case), with their lobby's listing
+    pub fn search_players(&self, prefix: &str, limit: usize) -> Vec<(Arc<LobbyListing>, PlayerListing)> {
+        self.player_names
+            .search(prefix, limit)
+            .into_iter()
+            .filter_map(|(code, player_id)| {
+                let listing = self.lobby_listing(&code)?;
+                let player = listing.players.iter().find(|p| p.id == player_id)?.clone();
+                Some((listing, player))
+            })
+            .collect()
+    }
+
+    /// Latest published browser listing of a lobby, without touching the lobby lock
+    pub fn lobby_listing(&self, lobby_code: &str) -> Option<Arc<LobbyListing>> {
+        self.lobbies.get(lobby_code).map(|handle| handle.listing.load())
+    }
+
     /// Get lobby handle by code
     pub fn get_lobby_handle(&self, lobby_code: &str) -> Option<std::sync::Arc<tokio::sync::RwLock<crate::state::lobby::Lobby>>> {
         self.lobbies.get(lobby_code)
@@ -123,6 +595,12 @@ impl ServerState {
     }
 }
 
+impl Default for ServerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,6 +632,9 @@ mod tests {
             lobby: lobby.clone(),
             command_tx: tx,
             task_handle: handle,
+            udp_tasks: Vec::new(),
+            socket: None,
+            listing: Snapshot::default(),
         };
         
         let state = ServerState::new();
@@ -173,6 +654,9 @@ mod tests {
             lobby,
             command_tx: tx.clone(),
             task_handle: handle,
+            udp_tasks: Vec::new(),
+            socket: None,
+            listing: Snapshot::default(),
         };
         
         let state = ServerState::new();
@@ -186,6 +670,48 @@ mod tests {
         retrieved_tx.unwrap().send(LobbyCommand::Heartbeat { player_id: 1, addr }).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_browser_revisions() {
+        let state = ServerState::new();
+        let handle = || {
+            let (command_tx, _rx) = mpsc::channel(1);
+            LobbyHandle {
+                lobby: Arc::new(RwLock::new(Lobby::new("A".to_string(), 4, "world".to_string()))),
+                command_tx,
+                task_handle: tokio::spawn(async {}),
+                udp_tasks: Vec::new(),
+            socket: None,
+                listing: Snapshot::default(),
+            }
+        };
+
+        state.insert_lobby("A".to_string(), handle());
+        state.insert_lobby("B".to_string(), handle());
+        let start = state.browser_revision();
+        assert_eq!(start, 2);
+
+        // Only changes move the revision, and the listing is in place by then
+        let mut lobby = Lobby::new("A".to_string(), 4, "world".to_string());
+        state.publish_listing("A", LobbyListing::of(&lobby), false);
+        assert_eq!(state.browser_revision(), 2);
+        lobby.max_players = 6;
+        assert_eq!(state.publish_change(&lobby).max_players, 6);
+        assert_eq!(state.lobby_listing("A").unwrap().max_players, 6);
+        assert_eq!(state.lobby_revision("A"), 3);
+        assert_eq!(state.lobby_revision("B"), 2);
+
+        assert!(state.remove_lobby("B").is_some());
+        assert!(state.remove_lobby("B").is_none());
+        assert_eq!(state.browser_revision(), 4);
+        assert_eq!(state.removed_lobbies_since(start), Some(vec!["B".to_string()]));
+        assert_eq!(state.removed_lobbies_since(4), Some(vec![]));
+        assert_eq!(state.removed_lobbies_since(99), None);
+
+        // Re-creating a closed lobby clears its removal
+        state.insert_lobby("B".to_string(), handle());
+        assert_eq!(state.removed_lobbies_since(start), Some(vec![]));
+    }
+
     #[test]
     fn test_valid_lobby_code() {
         assert!(ServerState::is_valid_lobby_code("TEST123"));
@@ -220,5 +746,98 @@ mod tests {
         state.unregister_player(1);
         assert!(state.player_lobby_index.get(&1).is_none());
     }
-}
 
+    #[test]
+    fn test_binary_client_tracking() {
+        let state = ServerState::new();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9000);
+        assert!(!state.client_protocol(&addr).has(CAP_BINARY));
+
+        state.mark_binary_client(addr);
+        assert!(state.client_protocol(&addr).has(CAP_BINARY));
+
+        state.forget_client(&addr);
+        assert!(!state.client_protocol(&addr).has(CAP_BINARY));
+    }
+
+    #[test]
+    fn test_client_protocol_version() {
+        let state = ServerState::new();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9001);
+        assert_eq!(state.client_protocol(&addr), ClientProtocol::default());
+
+        state.set_protocol_version(addr, 2);
+        state.mark_binary_client(addr);
+        let protocol = state.client_protocol(&addr);
+        assert_eq!(protocol.version, 2);
+        assert!(protocol.has(CAP_BINARY));
+    }
+
+    #[test]
+    fn test_authorize_source_rebinding() {
+        let state = ServerState::new();
+        let home: SocketAddr = "203.0.113.7:5000".parse().unwrap();
+        let roaming: SocketAddr = "203.0.113.7:6000".parse().unwrap();
+        let token = state.create_session_token(1);
+
+        let spoofed: SocketAddr = "198.51.100.1:5000".parse().unwrap();
+        assert!(state.authorize_source(1, spoofed, None, false).is_err());
+        assert_eq!(state.authorize_source(1, home, Some(&token), false), Ok(SourceCheck::FirstBind));
+        state.mark_binary_client(home);
+        assert_eq!(state.authorize_source(1, home, None, false), Ok(SourceCheck::Bound));
+
+        // Hijack attempts from another address
+        assert!(state.authorize_source(1, roaming, None, false).is_err());
+        assert_eq!(state.authorize_source(1, roaming, Some("forged"), false), Err("Invalid session token"));
+
+        // Rebinding with the token moves the session and its negotiated protocol
+        assert_eq!(
+            state.authorize_source(1, roaming, Some(&token), false),
+            Ok(SourceCheck::Rebind { previous: home })
+        );
+        assert!(state.client_protocol(&roaming).has(CAP_BINARY));
+        assert!(state.authorize_source(1, home, None, false).is_err());
+
+        // Encrypted packets prove the session without the token
+        assert_eq!(state.authorize_source(1, home, None, true), Ok(SourceCheck::Rebind { previous: roaming }));
+
+        state.end_session(1);
+        assert_eq!(state.authorize_source(1, roaming, None, false), Ok(SourceCheck::Unmanaged));
+        assert_eq!(state.authorize_source(2, roaming, None, false), Ok(SourceCheck::Unmanaged));
+    }
+
+    #[test]
+    fn test_client_capabilities() {
+        let state = ServerState::new();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9003);
+        state.set_capabilities(addr, crate::net::protocol::CAP_REDUCED_RATE);
+        state.mark_binary_client(addr);
+
+        let protocol = state.client_protocol(&addr);
+        assert!(protocol.has(CAP_BINARY));
+        assert!(protocol.has(crate::net::protocol::CAP_REDUCED_RATE));
+    }
+
+    #[test]
+    fn test_encrypted_session() {
+        let state = ServerState::new();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 9002);
+        let key = state.create_session_key(5);
+        let mut client = SessionCipher::from_key(&crypto::decode_key(&key).unwrap());
+
+        // Plaintext until the client sends something encrypted
+        assert!(state.seal_outbound(&addr, b"hello").is_none());
+
+        let sealed = client.seal(Direction::ClientToServer, 5, b"{\"type\":\"keepalive\"}").unwrap();
+        let (player_id, plaintext) = state.open_encrypted(addr, &sealed).unwrap();
+        assert_eq!(player_id, 5);
+        assert_eq!(plaintext, b"{\"type\":\"keepalive\"}");
+
+        let reply = state.seal_outbound(&addr, b"hello").unwrap();
+        assert_eq!(client.open(Direction::ServerToClient, &reply).unwrap(), b"hello");
+
+        state.end_session(5);
+        assert!(state.seal_outbound(&addr, b"hello").is_none());
+        assert!(state.open_encrypted(addr, &client.seal(Direction::ClientToServer, 5, b"x").unwrap()).is_err());
+    }
+}
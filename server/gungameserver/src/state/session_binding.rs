@@ -0,0 +1,129 @@
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use crate::net::crypto;
+
+/// Outcome of checking a packet's source address against a player's session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceCheck {
+    /// Player has no session token (bots, legacy joins) - address isn't enforced
+    Unmanaged,
+    /// First address seen for this session, proven with the token or session key
+    FirstBind,
+    /// Packet came from the bound address
+    Bound,
+    /// Proven address change (NAT rebinding); the old address should be told
+    Rebind { previous: SocketAddr },
+}
+
+/// Binds each player session to one UDP source address
+///
+/// Rules:
+/// - The HTTP join issues a session token; players without one are unmanaged
+/// - Until a packet proves the session, either with the `session_token` or by being
+///   encrypted with the session key, nothing is accepted; that packet binds its source address
+/// - A packet from any other address is dropped unless it proves the session too
+/// - A wrong token is always rejected, even from the bound address
+pub struct SessionBindings {
+    tokens: DashMap<u32, String>,
+    addresses: DashMap<u32, SocketAddr>,
+}
+
+impl SessionBindings {
+    pub fn new() -> Self {
+        Self {
+            tokens: DashMap::new(),
+            addresses: DashMap::new(),
+        }
+    }
+
+    /// Issue a session token for a player (returned once over the HTTP join)
+    pub fn issue_token(&self, player_id: u32) -> String {
+        let token = crypto::generate_token();
+        self.tokens.insert(player_id, token.clone());
+        token
+    }
+
+    /// The player's session token, for checkpointing
+    pub fn token(&self, player_id: u32) -> Option<String> {
+        self.tokens.get(&player_id).map(|token| token.clone())
+    }
+
+    /// Reinstate a token issued before a restart (the address binds on the next packet)
+    pub fn restore_token(&self, player_id: u32, token: String) {
+        self.tokens.insert(player_id, token);
+    }
+
+    /// Whether `token` is the player's session token
+    pub fn verify_token(&self, player_id: u32, token: &str) -> bool {
+        self.tokens
+            .get(&player_id)
+            .map(|expected| crypto::tokens_match(&expected, token))
+            .unwrap_or(false)
+    }
+
+    /// Decide whether a packet from `addr` may act for the player
+    /// `proven` is true when the packet carried a valid token or session encryption
+    pub fn check(&self, player_id: u32, addr: SocketAddr, proven: bool) -> Result<SourceCheck, &'static str> {
+        if !self.tokens.contains_key(&player_id) {
+            return Ok(SourceCheck::Unmanaged);
+        }
+        match self.addresses.get(&player_id).map(|bound| *bound) {
+            None if proven => Ok(SourceCheck::FirstBind),
+            None => Err("First packet requires the session token"),
+            Some(bound) if bound == addr => Ok(SourceCheck::Bound),
+            Some(previous) if proven => Ok(SourceCheck::Rebind { previous }),
+            Some(_) => Err("Address change requires the session token"),
+        }
+    }
+
+    /// Bind the session to an address
+    pub fn bind(&self, player_id: u32, addr: SocketAddr) {
+        self.addresses.insert(player_id, addr);
+    }
+
+    /// Drop the session (call when the player leaves)
+    pub fn release(&self, player_id: u32) {
+        self.tokens.remove(&player_id);
+        self.addresses.remove(&player_id);
+    }
+}
+
+impl Default for SessionBindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([203, 0, 113, 7], port))
+    }
+
+    #[test]
+    fn test_binding_rules() {
+        let bindings = SessionBindings::new();
+        assert_eq!(bindings.check(1, addr(1000), false), Ok(SourceCheck::Unmanaged));
+
+        let token = bindings.issue_token(1);
+        assert!(bindings.verify_token(1, &token));
+        assert!(!bindings.verify_token(1, "forged"));
+        assert!(!bindings.verify_token(2, &token));
+
+        // A spoofed first packet can't claim the session before the real client does
+        assert!(bindings.check(1, addr(666), false).is_err());
+        assert_eq!(bindings.check(1, addr(1000), true), Ok(SourceCheck::FirstBind));
+        bindings.bind(1, addr(1000));
+        assert_eq!(bindings.check(1, addr(1000), false), Ok(SourceCheck::Bound));
+
+        // New source address: needs proof
+        assert!(bindings.check(1, addr(2000), false).is_err());
+        assert_eq!(bindings.check(1, addr(2000), true), Ok(SourceCheck::Rebind { previous: addr(1000) }));
+
+        bindings.release(1);
+        assert_eq!(bindings.check(1, addr(2000), false), Ok(SourceCheck::Unmanaged));
+        assert!(!bindings.verify_token(1, &token));
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, RwLock};
+use crate::state::lobby::{Lobby, Visibility};
+
+/// Read-mostly value replaced as a whole (the `ArcSwap` pattern)
+/// Readers only clone an `Arc`; the writer builds the next value before swapping it in,
+/// so neither side ever waits on the other's work
+#[derive(Debug, Default)]
+pub struct Snapshot<T> {
+    current: RwLock<Arc<T>>,
+}
+
+impl<T> Snapshot<T> {
+    pub fn new(value: T) -> Self {
+        Self { current: RwLock::new(Arc::new(value)) }
+    }
+
+    /// The latest stored value
+    pub fn load(&self) -> Arc<T> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the value; readers holding the old one keep it until they drop it
+    pub fn store(&self, value: T) {
+        let value = Arc::new(value);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = value;
+    }
+}
+
+/// Player entry of a lobby listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerListing {
+    pub id: u32,
+    pub name: String,
+    pub team_id: Option<u32>,
+}
+
+/// What the lobby browser shows about a lobby, published by its tick loop
+/// HTTP reads are served from it without touching the lobby lock
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LobbyListing {
+    pub code: String,
+    pub max_players: u32,
+    pub players: Vec<PlayerListing>, // Ordered by id
+    pub spectators: usize,
+    pub max_spectators: u32,
+    pub spectate_code: String,
+    pub udp_port: Option<u16>, // Dedicated port; None = the shared one
+    pub scene: String,
+    pub mode: &'static str,
+    pub match_state: &'static str,
+    pub round: u32,
+    pub teams: u32,
+    pub dimensions: u8,
+    pub unlisted: bool,
+    pub password_protected: bool,
+    pub owner_id: Option<u32>,
+    pub locked: bool,
+    pub max_fov: f32,
+    pub aim_assist: bool,
+    pub tick_rate_hz: Option<u32>, // None = the server's
+}
+
+impl LobbyListing {
+    pub fn of(lobby: &Lobby) -> Self {
+        let mut players: Vec<PlayerListing> = lobby
+            .players
+            .values()
+            .map(|p| PlayerListing { id: p.id, name: p.name.clone(), team_id: p.team_id })
+            .collect();
+        players.sort_by_key(|p| p.id);
+        Self {
+            code: lobby.code.clone(),
+            max_players: lobby.max_players,
+            players,
+            spectators: lobby.spectators.len(),
+            max_spectators: lobby.max_spectators,
+            spectate_code: lobby.spectate_code.clone(),
+            udp_port: lobby.udp_port,
+            scene: lobby.scene.clone(),
+            mode: lobby.mode.as_str(),
+            match_state: lobby.match_state.as_str(),
+            round: lobby.match_state.round(),
+            teams: lobby.team_count,
+            dimensions: lobby.dimensions.as_u8(),
+            unlisted: lobby.visibility == Visibility::Unlisted,
+            password_protected: lobby.password.is_some(),
+            owner_id: lobby.owner,
+            locked: lobby.locked,
+            max_fov: lobby.rules.integrity.max_fov,
+            aim_assist: lobby.rules.integrity.aim_assist,
+            tick_rate_hz: lobby.tick_rate_hz,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    #[test]
+    fn test_readers_keep_their_snapshot() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        let snapshot = Snapshot::new(LobbyListing::of(&lobby));
+        let before = snapshot.load();
+
+        lobby.players.insert(2, PlayerBuilder::new().id(2).build());
+        snapshot.store(LobbyListing::of(&lobby));
+
+        assert_eq!(before.players.len(), 1);
+        let after = snapshot.load();
+        assert_eq!(after.players.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!((after.code.as_str(), after.mode, after.match_state), ("TEST", "ffa", "waiting_for_players"));
+    }
+}
@@ -0,0 +1,250 @@
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use crate::state::server_state::ServerState;
+use crate::utils::scaling::ScalingSink;
+
+/// One anonymized gameplay event for balance analysis
+/// Never carries player names, ids, addresses or lobby codes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryEvent {
+    pub timestamp: u64, // Unix milliseconds
+    pub scene: String,
+    pub mode: &'static str,
+    #[serde(flatten)]
+    pub kind: TelemetryKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TelemetryKind {
+    /// A shot landed or missed
+    WeaponUsed { weapon_id: u32, hit: bool, bot: bool },
+    /// Distance is killer to victim; time to kill runs from the victim's first hit taken
+    Kill { weapon_id: u32, distance: f32, time_to_kill_ms: Option<u64>, killer_bot: bool, victim_bot: bool },
+    /// Human player samples per map cell (x/z grid) since the last heat event
+    Heat { cell_size: f32, cells: Vec<HeatCell> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatCell {
+    pub x: i32,
+    pub z: i32,
+    pub samples: u32,
+}
+
+impl TelemetryEvent {
+    pub fn new(scene: &str, mode: &'static str, kind: TelemetryKind) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self { timestamp, scene: scene.to_string(), mode, kind }
+    }
+}
+
+/// Sampled events waiting for the next flush
+/// Disabled (records nothing) until configured: telemetry is opt-in
+pub struct TelemetryBuffer {
+    enabled: AtomicBool,
+    sample_rate: Mutex<f32>, // Fraction of events kept
+    max_buffered: AtomicUsize, // Events past this between flushes are dropped
+    credit: Mutex<f32>, // Sampling accumulator: an event is kept each time it reaches 1
+    events: Mutex<Vec<TelemetryEvent>>,
+    dropped: AtomicU64,
+}
+
+impl TelemetryBuffer {
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            sample_rate: Mutex::new(1.0),
+            max_buffered: AtomicUsize::new(0),
+            credit: Mutex::new(0.0),
+            events: Mutex::new(Vec::new()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Start recording with a sample rate (clamped to 0.0-1.0) and buffer cap
+    pub fn configure(&self, sample_rate: f32, max_buffered: usize) {
+        *self.sample_rate.lock().unwrap() = sample_rate.clamp(0.0, 1.0);
+        self.max_buffered.store(max_buffered, Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Keep the event if it falls in the sample; returns whether it was kept
+    /// Sampling is evenly spaced rather than random, so a rate of 0.25 keeps exactly every fourth event
+    pub fn record(&self, event: TelemetryEvent) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        {
+            let rate = *self.sample_rate.lock().unwrap();
+            let mut credit = self.credit.lock().unwrap();
+            *credit += rate;
+            if *credit < 1.0 {
+                return false;
+            }
+            *credit -= 1.0;
+        }
+
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= self.max_buffered.load(Ordering::Relaxed) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        events.push(event);
+        true
+    }
+
+    /// Take everything buffered for a flush
+    pub fn drain(&self) -> Vec<TelemetryEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    /// Sampled events lost to a full buffer since startup
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for TelemetryBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Batch of events POSTed to a collector
+#[derive(Serialize)]
+struct TelemetryBatch<'a> {
+    events: &'a [TelemetryEvent],
+}
+
+/// Where telemetry batches go
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelemetrySink {
+    /// Append one JSON line per event
+    File(PathBuf),
+    /// POST each batch as `{"events": [...]}`
+    Collector(ScalingSink),
+}
+
+impl TelemetrySink {
+    /// Parse a target: `http://...` is a collector, anything else an NDJSON file
+    pub fn parse(target: &str) -> Result<Self, &'static str> {
+        Ok(match ScalingSink::parse(target)? {
+            ScalingSink::File(path) => Self::File(path),
+            sink => Self::Collector(sink),
+        })
+    }
+
+    pub async fn write_batch(&self, events: &[TelemetryEvent]) -> Result<(), String> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Self::File(path) => {
+                let mut lines = Vec::new();
+                for event in events {
+                    serde_json::to_writer(&mut lines, event).map_err(|e| e.to_string())?;
+                    lines.push(b'\n');
+                }
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(&lines))
+                    .map_err(|e| e.to_string())
+            }
+            Self::Collector(sink) => sink.emit(&TelemetryBatch { events }).await,
+        }
+    }
+}
+
+/// Write out whatever is buffered (also called once at shutdown)
+pub async fn flush_telemetry(state: &ServerState, sink: &TelemetrySink) {
+    let events = state.telemetry.drain();
+    if let Err(e) = sink.write_batch(&events).await {
+        log::warn!("Failed to write {} telemetry events: {}", events.len(), e);
+    }
+}
+
+/// Periodically flush telemetry batches until the task is dropped
+pub async fn run_telemetry_flusher(state: Arc<ServerState>, sink: TelemetrySink, interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+    let mut dropped = 0;
+    loop {
+        timer.tick().await;
+        flush_telemetry(&state, &sink).await;
+        let total = state.telemetry.dropped();
+        if total > dropped {
+            log::warn!("Telemetry buffer full: dropped {} events, lower the sample rate or flush interval", total - dropped);
+            dropped = total;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kill() -> TelemetryEvent {
+        TelemetryEvent::new(
+            "world",
+            "ffa",
+            TelemetryKind::Kill { weapon_id: 1, distance: 12.5, time_to_kill_ms: Some(800), killer_bot: false, victim_bot: true },
+        )
+    }
+
+    #[test]
+    fn test_buffer_is_opt_in_and_samples_evenly() {
+        let buffer = TelemetryBuffer::new();
+        assert!(!buffer.record(kill()));
+        assert!(buffer.drain().is_empty());
+
+        buffer.configure(0.25, 100);
+        let kept = (0..20).filter(|_| buffer.record(kill())).count();
+        assert_eq!(kept, 5);
+        assert_eq!(buffer.drain().len(), 5);
+        assert!(buffer.drain().is_empty());
+
+        buffer.configure(1.0, 2);
+        for _ in 0..3 {
+            buffer.record(kill());
+        }
+        assert_eq!(buffer.drain().len(), 2);
+        assert_eq!(buffer.dropped(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_write_batch_as_ndjson() {
+        let path = std::env::temp_dir().join(format!("gungame-telemetry-{}.jsonl", std::process::id()));
+        let sink = TelemetrySink::parse(path.to_str().unwrap()).unwrap();
+        let heat = TelemetryEvent::new(
+            "world",
+            "ffa",
+            TelemetryKind::Heat { cell_size: 10.0, cells: vec![HeatCell { x: 0, z: -1, samples: 3 }] },
+        );
+        sink.write_batch(&[kill(), heat]).await.unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = written.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "kill");
+        assert_eq!(lines[0]["time_to_kill_ms"], 800);
+        assert_eq!(lines[0]["scene"], "world");
+        assert_eq!(lines[1]["event"], "heat");
+        assert_eq!(lines[1]["cells"][0]["samples"], 3);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(matches!(TelemetrySink::parse("http://collector:9000/events"), Ok(TelemetrySink::Collector(_))));
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Notify;
+use crate::state::lobby::LobbyCode;
+
+/// A pre-created lobby whose tick loop sleeps until matchmaking hands it out
+#[derive(Debug, Clone)]
+pub struct WarmLobby {
+    pub code: LobbyCode,
+    pub wake: Arc<Notify>, // Starts the tick loop
+}
+
+/// Idle pre-created lobbies per scene, and the lobbies matchmaking has placed players in
+/// Idle lobbies are hidden from the lobby browser and never reaped
+#[derive(Debug, Default)]
+pub struct WarmPool {
+    idle: Mutex<HashMap<String, VecDeque<WarmLobby>>>, // Scene -> idle lobbies, oldest first
+    matched: Mutex<HashMap<LobbyCode, String>>, // Lobbies handed out by matchmaking -> scene
+    next_code: AtomicU64,
+}
+
+impl WarmPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Code for the next pre-created lobby
+    pub fn next_code(&self) -> LobbyCode {
+        format!("mm-{}", self.next_code.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    /// Add a freshly created, hibernated lobby to a scene's pool
+    pub fn add(&self, scene: &str, lobby: WarmLobby) {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner()).entry(scene.to_string()).or_default().push_back(lobby);
+    }
+
+    /// Take a scene's oldest idle lobby and wake its tick loop
+    /// The lobby counts as matched from now on
+    pub fn take(&self, scene: &str) -> Option<LobbyCode> {
+        let lobby = self.idle.lock().unwrap_or_else(|e| e.into_inner()).get_mut(scene)?.pop_front()?;
+        lobby.wake.notify_one();
+        self.matched.lock().unwrap_or_else(|e| e.into_inner()).insert(lobby.code.clone(), scene.to_string());
+        Some(lobby.code)
+    }
+
+    /// Lobbies of a scene matchmaking already placed players in
+    pub fn matched(&self, scene: &str) -> Vec<LobbyCode> {
+        let matched = self.matched.lock().unwrap_or_else(|e| e.into_inner());
+        let mut codes: Vec<LobbyCode> = matched.iter().filter(|(_, s)| *s == scene).map(|(code, _)| code.clone()).collect();
+        codes.sort();
+        codes
+    }
+
+    /// Idle lobbies waiting in a scene's pool
+    pub fn idle_count(&self, scene: &str) -> usize {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner()).get(scene).map_or(0, |pool| pool.len())
+    }
+
+    /// Whether a lobby is idle in the pool (not handed out yet)
+    pub fn is_idle(&self, code: &str) -> bool {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner()).values().any(|pool| pool.iter().any(|l| l.code == code))
+    }
+
+    /// Stop tracking a closed lobby
+    pub fn forget(&self, code: &str) {
+        for pool in self.idle.lock().unwrap_or_else(|e| e.into_inner()).values_mut() {
+            pool.retain(|l| l.code != code);
+        }
+        self.matched.lock().unwrap_or_else(|e| e.into_inner()).remove(code);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warm(pool: &WarmPool) -> WarmLobby {
+        WarmLobby { code: pool.next_code(), wake: Arc::new(Notify::new()) }
+    }
+
+    #[tokio::test]
+    async fn test_take_wakes_oldest_idle_lobby() {
+        let pool = WarmPool::new();
+        let first = warm(&pool);
+        let wake = first.wake.clone();
+        pool.add("world", first);
+        pool.add("world", warm(&pool));
+        assert_eq!(pool.idle_count("world"), 2);
+        assert!(pool.is_idle("mm-1"));
+
+        assert_eq!(pool.take("desert"), None);
+        assert_eq!(pool.take("world").as_deref(), Some("mm-1"));
+        wake.notified().await; // Permit stored by take
+        assert!(!pool.is_idle("mm-1"));
+        assert_eq!(pool.idle_count("world"), 1);
+        assert_eq!(pool.matched("world"), vec!["mm-1".to_string()]);
+
+        pool.forget("mm-1");
+        pool.forget("mm-2");
+        assert!(pool.matched("world").is_empty());
+        assert_eq!(pool.idle_count("world"), 0);
+    }
+}
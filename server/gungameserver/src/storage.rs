@@ -0,0 +1,886 @@
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use uuid::Uuid;
+use crate::domain::anticheat::IntegrityRules;
+use crate::domain::gungame::GameMode;
+use crate::domain::history::MatchRecord;
+use crate::domain::lobbies;
+use crate::domain::matches::AccountResult;
+use crate::net::crypto::PasswordHash;
+use crate::state::lobby::{Connection, Dimensions, Lobby, LobbySpec, Visibility};
+use crate::state::server_state::ServerState;
+use crate::utils::config::Config;
+use crate::utils::weapondb::WeaponDb;
+
+/// Tables, created on open (statements run in order, each idempotent)
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS lobbies (
+        code TEXT PRIMARY KEY,
+        max_players INTEGER NOT NULL,
+        scene TEXT NOT NULL,
+        mode TEXT NOT NULL,
+        team_count INTEGER NOT NULL,
+        dimensions INTEGER NOT NULL,
+        bots INTEGER NOT NULL,
+        bot_fill INTEGER NOT NULL,
+        tick_rate_hz INTEGER,
+        persistent INTEGER NOT NULL,
+        hide_enemy_state INTEGER NOT NULL,
+        max_fov REAL NOT NULL,
+        aim_assist INTEGER NOT NULL,
+        visibility TEXT NOT NULL,
+        password TEXT,
+        hosted INTEGER NOT NULL,
+        owner_id INTEGER,
+        saved_at INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS lobby_players (
+        lobby_code TEXT NOT NULL REFERENCES lobbies(code) ON DELETE CASCADE,
+        player_id INTEGER NOT NULL,
+        uuid TEXT NOT NULL,
+        name TEXT NOT NULL,
+        session_token TEXT NOT NULL,
+        team_id INTEGER,
+        score INTEGER NOT NULL,
+        kills INTEGER NOT NULL,
+        deaths INTEGER NOT NULL,
+        account_id INTEGER,
+        PRIMARY KEY (lobby_code, player_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS players (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        password TEXT NOT NULL,
+        kills INTEGER NOT NULL DEFAULT 0,
+        deaths INTEGER NOT NULL DEFAULT 0,
+        wins INTEGER NOT NULL DEFAULT 0,
+        matches INTEGER NOT NULL DEFAULT 0,
+        shots INTEGER NOT NULL DEFAULT 0,
+        hits INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL
+    )",
+    // Finished matches; `record` is the full MatchRecord as JSON
+    "CREATE TABLE IF NOT EXISTS matches (
+        id TEXT PRIMARY KEY,
+        lobby_code TEXT NOT NULL,
+        mode TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER NOT NULL,
+        record TEXT NOT NULL
+    )",
+    // One row per account per finished match, for leaderboards over a period and match history
+    "CREATE TABLE IF NOT EXISTS account_matches (
+        account_id INTEGER NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+        match_id TEXT NOT NULL REFERENCES matches(id) ON DELETE CASCADE,
+        place INTEGER NOT NULL,
+        score INTEGER NOT NULL,
+        kills INTEGER NOT NULL,
+        deaths INTEGER NOT NULL,
+        won INTEGER NOT NULL,
+        shots INTEGER NOT NULL,
+        hits INTEGER NOT NULL,
+        played_at INTEGER NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS account_matches_played_at ON account_matches (played_at)",
+    "CREATE INDEX IF NOT EXISTS account_matches_account ON account_matches (account_id, played_at)",
+];
+
+/// What a leaderboard ranks accounts by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    Kills,
+    Wins,
+    Kd, // Kills per death (kills when never killed)
+}
+
+impl LeaderboardMetric {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "kills" => Some(Self::Kills),
+            "wins" => Some(Self::Wins),
+            "kd" => Some(Self::Kd),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Kills => "kills",
+            Self::Wins => "wins",
+            Self::Kd => "kd",
+        }
+    }
+
+    fn order_by(&self) -> &'static str {
+        match self {
+            Self::Kills => "kills DESC",
+            Self::Wins => "wins DESC, kills DESC",
+            Self::Kd => "CAST(kills AS REAL) / MAX(deaths, 1) DESC, kills DESC",
+        }
+    }
+}
+
+/// Which matches a leaderboard counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardPeriod {
+    Weekly, // The last 7 days
+    AllTime,
+}
+
+impl LeaderboardPeriod {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "weekly" => Some(Self::Weekly),
+            "alltime" => Some(Self::AllTime),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Weekly => "weekly",
+            Self::AllTime => "alltime",
+        }
+    }
+}
+
+/// An account's totals over a leaderboard's period
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardRow {
+    pub account_id: i64,
+    pub username: String,
+    pub kills: u32,
+    pub deaths: u32,
+    pub wins: u32,
+}
+
+impl LeaderboardRow {
+    pub fn kd(&self) -> f32 {
+        self.kills as f32 / self.deaths.max(1) as f32
+    }
+}
+
+/// A match in an account's history, with how the account did in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountMatch {
+    pub match_id: String,
+    pub lobby_code: String,
+    pub mode: String,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub place: u32,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub won: bool,
+}
+
+/// A human player as checkpointed: enough to hold their slot and let their client `reconnect`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerRecord {
+    pub id: u32,
+    pub uuid: Uuid,
+    pub name: String,
+    pub session_token: String,
+    pub team_id: Option<u32>,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub account_id: Option<i64>, // Linked account, so match stats still count after the restart
+}
+
+/// A registered player account with lifetime stats
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Account {
+    pub account_id: i64,
+    pub username: String,
+    pub kills: u32,
+    pub deaths: u32,
+    pub wins: u32,
+    pub matches: u32,
+    pub shots: u32,
+    pub hits: u32,
+}
+
+impl Account {
+    /// Share of shots that hit (0 before the first shot)
+    pub fn accuracy(&self) -> f32 {
+        if self.shots > 0 {
+            self.hits as f32 / self.shots as f32
+        } else {
+            0.0
+        }
+    }
+
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            account_id: row.try_get("id")?,
+            username: row.try_get("username")?,
+            kills: row.try_get("kills")?,
+            deaths: row.try_get("deaths")?,
+            wins: row.try_get("wins")?,
+            matches: row.try_get("matches")?,
+            shots: row.try_get("shots")?,
+            hits: row.try_get("hits")?,
+        })
+    }
+}
+
+/// An open lobby as checkpointed
+/// `mode` is kept by name: gun game ladders are rebuilt from the config on restore
+#[derive(Debug, Clone, PartialEq)]
+pub struct LobbyRecord {
+    pub spec: LobbySpec,
+    pub owner: Option<u32>,
+    pub players: Vec<PlayerRecord>,
+}
+
+impl LobbyRecord {
+    /// Checkpoint of a lobby; players without a session token (bots, legacy joins) are left out
+    pub fn of(lobby: &Lobby, state: &ServerState) -> Self {
+        let mut players: Vec<PlayerRecord> = lobby
+            .players
+            .values()
+            .filter(|p| !lobby.bots.contains_key(&p.id))
+            .filter_map(|p| {
+                Some(PlayerRecord {
+                    id: p.id,
+                    uuid: state.player_directory.uuid(p.id)?,
+                    name: p.name.clone(),
+                    session_token: state.session_token(p.id)?,
+                    team_id: p.team_id,
+                    score: p.score,
+                    kills: p.kills,
+                    deaths: p.deaths,
+                    account_id: p.account_id,
+                })
+            })
+            .collect();
+        players.sort_by_key(|p| p.id);
+        let spec = LobbySpec {
+            code: lobby.code.clone(),
+            max_players: lobby.max_players,
+            scene: lobby.scene.clone(),
+            mode: lobby.mode.clone(),
+            team_count: lobby.team_count,
+            dimensions: lobby.dimensions,
+            bots: (lobby.bots.len() - lobby.fill_bots.len()) as u32,
+            bot_fill: lobby.bot_fill,
+            tick_rate_hz: lobby.tick_rate_hz,
+            persistent: lobby.persistent,
+            hide_enemy_state: lobby.hide_enemy_state,
+            integrity: lobby.rules.integrity,
+            visibility: lobby.visibility,
+            password: lobby.password.clone(),
+            hosted: lobby.hosted,
+            record_replay: false,
+        };
+        Self { spec, owner: lobby.owner, players }
+    }
+}
+
+/// SQLite database open lobbies are checkpointed to, so a restart brings them back
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    /// Open (creating if missing) the database at `path`
+    pub async fn open(path: &str) -> Result<Self, String> {
+        let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", path))
+            .map_err(|e| format!("Invalid storage path {}: {}", path, e))?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        Self::connect(options).await.map_err(|e| format!("Failed to open {}: {}", path, e))
+    }
+
+    /// Private in-memory database
+    #[cfg(test)]
+    pub async fn in_memory() -> Self {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:").unwrap().foreign_keys(true);
+        Self::connect(options).await.unwrap()
+    }
+
+    async fn connect(options: SqliteConnectOptions) -> Result<Self, sqlx::Error> {
+        // One connection: SQLite serializes writers anyway, and `:memory:` is per connection
+        let pool = SqlitePoolOptions::new().max_connections(1).connect_with(options).await?;
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+        Ok(Self { pool })
+    }
+
+    /// Replace the stored lobbies with `lobbies`, all at once
+    pub async fn checkpoint(&self, lobbies: &[LobbyRecord]) -> Result<(), sqlx::Error> {
+        let saved_at = unix_now();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM lobbies").execute(&mut *tx).await?;
+        for lobby in lobbies {
+            let spec = &lobby.spec;
+            sqlx::query(
+                "INSERT INTO lobbies (code, max_players, scene, mode, team_count, dimensions, bots, bot_fill, tick_rate_hz,
+                    persistent, hide_enemy_state, max_fov, aim_assist, visibility, password, hosted, owner_id, saved_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&spec.code)
+            .bind(spec.max_players)
+            .bind(&spec.scene)
+            .bind(spec.mode.as_str())
+            .bind(spec.team_count)
+            .bind(spec.dimensions.as_u8())
+            .bind(spec.bots)
+            .bind(spec.bot_fill)
+            .bind(spec.tick_rate_hz)
+            .bind(spec.persistent)
+            .bind(spec.hide_enemy_state)
+            .bind(spec.integrity.max_fov)
+            .bind(spec.integrity.aim_assist)
+            .bind(spec.visibility.as_str())
+            .bind(spec.password.as_ref().map(PasswordHash::to_stored))
+            .bind(spec.hosted)
+            .bind(lobby.owner)
+            .bind(saved_at)
+            .execute(&mut *tx)
+            .await?;
+
+            for player in &lobby.players {
+                sqlx::query(
+                    "INSERT INTO lobby_players (lobby_code, player_id, uuid, name, session_token, team_id, score, kills, deaths, account_id)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(&spec.code)
+                .bind(player.id)
+                .bind(player.uuid.to_string())
+                .bind(&player.name)
+                .bind(&player.session_token)
+                .bind(player.team_id)
+                .bind(player.score)
+                .bind(player.kills)
+                .bind(player.deaths)
+                .bind(player.account_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await
+    }
+
+    /// Lobbies of the last checkpoint, by code; rows that no longer parse are skipped
+    pub async fn load(&self, ladder: &[u32]) -> Result<Vec<LobbyRecord>, sqlx::Error> {
+        let rows = sqlx::query("SELECT * FROM lobbies ORDER BY code").fetch_all(&self.pool).await?;
+        let mut lobbies = Vec::with_capacity(rows.len());
+        for row in rows {
+            let code: String = row.try_get("code")?;
+            let mode: String = row.try_get("mode")?;
+            let (Some(mode), Some(dimensions), Some(visibility)) = (
+                GameMode::parse(&mode, ladder.to_vec()),
+                Dimensions::parse(row.try_get("dimensions")?),
+                Visibility::parse(&row.try_get::<String, _>("visibility")?),
+            ) else {
+                log::warn!("Skipping stored lobby {}: unknown mode, dimensions or visibility", code);
+                continue;
+            };
+            let password = match row.try_get::<Option<String>, _>("password")? {
+                Some(stored) => match PasswordHash::from_stored(&stored) {
+                    Some(password) => Some(password),
+                    None => {
+                        log::warn!("Skipping stored lobby {}: corrupt password hash", code);
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let spec = LobbySpec {
+                code: code.clone(),
+                max_players: row.try_get("max_players")?,
+                scene: row.try_get("scene")?,
+                mode,
+                team_count: row.try_get("team_count")?,
+                dimensions,
+                bots: row.try_get("bots")?,
+                bot_fill: row.try_get("bot_fill")?,
+                tick_rate_hz: row.try_get("tick_rate_hz")?,
+                persistent: row.try_get("persistent")?,
+                hide_enemy_state: row.try_get("hide_enemy_state")?,
+                integrity: IntegrityRules { max_fov: row.try_get("max_fov")?, aim_assist: row.try_get("aim_assist")? },
+                visibility,
+                password,
+                hosted: row.try_get("hosted")?,
+                record_replay: false,
+            };
+
+            let player_rows = sqlx::query("SELECT * FROM lobby_players WHERE lobby_code = ? ORDER BY player_id")
+                .bind(&code)
+                .fetch_all(&self.pool)
+                .await?;
+            let mut players = Vec::with_capacity(player_rows.len());
+            for row in player_rows {
+                let Ok(uuid) = Uuid::parse_str(&row.try_get::<String, _>("uuid")?) else {
+                    continue;
+                };
+                players.push(PlayerRecord {
+                    id: row.try_get("player_id")?,
+                    uuid,
+                    name: row.try_get("name")?,
+                    session_token: row.try_get("session_token")?,
+                    team_id: row.try_get("team_id")?,
+                    score: row.try_get("score")?,
+                    kills: row.try_get("kills")?,
+                    deaths: row.try_get("deaths")?,
+                    account_id: row.try_get("account_id")?,
+                });
+            }
+            lobbies.push(LobbyRecord { spec, owner: row.try_get("owner_id")?, players });
+        }
+        Ok(lobbies)
+    }
+}
+
+impl Storage {
+    /// Register an account; None if the username is taken (ignoring case)
+    pub async fn create_account(&self, username: &str, password: &PasswordHash) -> Result<Option<Account>, sqlx::Error> {
+        let created_at = unix_now();
+        let inserted = sqlx::query("INSERT INTO players (username, password, created_at) VALUES (?, ?, ?)")
+            .bind(username)
+            .bind(password.to_stored())
+            .bind(created_at)
+            .execute(&self.pool)
+            .await;
+        match inserted {
+            Ok(result) => self.account(result.last_insert_rowid()).await,
+            Err(e) if e.as_database_error().is_some_and(|e| e.is_unique_violation()) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The account with `username` (ignoring case), if `password` is its password
+    /// Unknown usernames are checked against a dummy hash, so they take as long as a wrong password
+    pub async fn login(&self, username: &str, password: &str) -> Result<Option<Account>, sqlx::Error> {
+        let Some(row) = sqlx::query("SELECT * FROM players WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?
+        else {
+            PasswordHash::dummy().verify_blocking(password.to_string()).await;
+            return Ok(None);
+        };
+        let Some(hash) = PasswordHash::from_stored(&row.try_get::<String, _>("password")?) else {
+            log::warn!("Account {} has a corrupt password hash", username);
+            return Ok(None);
+        };
+        if !hash.verify_blocking(password.to_string()).await {
+            return Ok(None);
+        }
+        Account::from_row(&row).map(Some)
+    }
+
+    pub async fn account(&self, account_id: i64) -> Result<Option<Account>, sqlx::Error> {
+        sqlx::query("SELECT * FROM players WHERE id = ?")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| Account::from_row(&row))
+            .transpose()
+    }
+
+    /// Keep a finished match in match history and add it to the lifetime stats of the accounts that played it
+    pub async fn record_match(&self, record: &MatchRecord, results: &[AccountResult]) -> Result<(), sqlx::Error> {
+        let json = serde_json::to_string(record).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO matches (id, lobby_code, mode, started_at, ended_at, record) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(&record.match_id)
+            .bind(&record.lobby_code)
+            .bind(&record.mode)
+            .bind(record.started_at as i64)
+            .bind(record.ended_at as i64)
+            .bind(json)
+            .execute(&mut *tx)
+            .await?;
+        for result in results {
+            sqlx::query(
+                "INSERT INTO account_matches (account_id, match_id, place, score, kills, deaths, won, shots, hits, played_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(result.account_id)
+            .bind(&record.match_id)
+            .bind(result.place)
+            .bind(result.score)
+            .bind(result.kills)
+            .bind(result.deaths)
+            .bind(result.won)
+            .bind(result.shots)
+            .bind(result.hits)
+            .bind(record.ended_at as i64)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                "UPDATE players SET kills = kills + ?, deaths = deaths + ?, wins = wins + ?, matches = matches + 1,
+                    shots = shots + ?, hits = hits + ?
+                 WHERE id = ?",
+            )
+            .bind(result.kills)
+            .bind(result.deaths)
+            .bind(result.won as u32)
+            .bind(result.shots)
+            .bind(result.hits)
+            .bind(result.account_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    /// A finished match by id; None if it was never recorded or no longer parses
+    pub async fn match_record(&self, match_id: &str) -> Result<Option<MatchRecord>, sqlx::Error> {
+        let json: Option<String> = sqlx::query_scalar("SELECT record FROM matches WHERE id = ?")
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(json.and_then(|json| match serde_json::from_str(&json) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log::warn!("Stored match {} no longer parses: {}", match_id, e);
+                None
+            }
+        }))
+    }
+
+    /// One page of the matches an account played, newest first, and how many it played in all
+    pub async fn account_match_history(
+        &self,
+        account_id: i64,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(u32, Vec<AccountMatch>), sqlx::Error> {
+        let total: u32 = sqlx::query_scalar("SELECT COUNT(*) FROM account_matches WHERE account_id = ?")
+            .bind(account_id)
+            .fetch_one(&self.pool)
+            .await?;
+        let rows = sqlx::query(
+            "SELECT a.*, m.lobby_code, m.mode, m.started_at, m.ended_at
+             FROM account_matches a JOIN matches m ON m.id = a.match_id
+             WHERE a.account_id = ? ORDER BY a.played_at DESC, m.rowid DESC LIMIT ? OFFSET ?",
+        )
+        .bind(account_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        let matches = rows
+            .iter()
+            .map(|row| {
+                Ok(AccountMatch {
+                    match_id: row.try_get("match_id")?,
+                    lobby_code: row.try_get("lobby_code")?,
+                    mode: row.try_get("mode")?,
+                    started_at: row.try_get("started_at")?,
+                    ended_at: row.try_get("ended_at")?,
+                    place: row.try_get("place")?,
+                    score: row.try_get("score")?,
+                    kills: row.try_get("kills")?,
+                    deaths: row.try_get("deaths")?,
+                    won: row.try_get("won")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()?;
+        Ok((total, matches))
+    }
+
+    /// One page of accounts ranked by `metric` over `period`, and how many accounts are ranked in all
+    /// Only accounts that finished a match in the period are ranked
+    pub async fn leaderboard(
+        &self,
+        metric: LeaderboardMetric,
+        period: LeaderboardPeriod,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(u32, Vec<LeaderboardRow>), sqlx::Error> {
+        let (source, since) = match period {
+            LeaderboardPeriod::AllTime => ("SELECT id AS account_id, username, kills, deaths, wins FROM players WHERE matches > 0", None),
+            LeaderboardPeriod::Weekly => (
+                "SELECT p.id AS account_id, p.username, SUM(m.kills) AS kills, SUM(m.deaths) AS deaths, SUM(m.won) AS wins
+                 FROM account_matches m JOIN players p ON p.id = m.account_id
+                 WHERE m.played_at >= ? GROUP BY p.id",
+                Some(unix_now() - WEEK_SECS),
+            ),
+        };
+        let count_sql = format!("SELECT COUNT(*) FROM ({})", source);
+        let mut count = sqlx::query_scalar(&count_sql);
+        if let Some(since) = since {
+            count = count.bind(since);
+        }
+        let total: u32 = count.fetch_one(&self.pool).await?;
+
+        let page_sql = format!("SELECT * FROM ({}) ORDER BY {}, account_id LIMIT ? OFFSET ?", source, metric.order_by());
+        let mut page = sqlx::query(&page_sql);
+        if let Some(since) = since {
+            page = page.bind(since);
+        }
+        let rows = page.bind(limit).bind(offset).fetch_all(&self.pool).await?;
+        let rows = rows
+            .iter()
+            .map(|row| {
+                Ok(LeaderboardRow {
+                    account_id: row.try_get("account_id")?,
+                    username: row.try_get("username")?,
+                    kills: row.try_get("kills")?,
+                    deaths: row.try_get("deaths")?,
+                    wins: row.try_get("wins")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()?;
+        Ok((total, rows))
+    }
+}
+
+/// Length of the weekly leaderboard window
+const WEEK_SECS: i64 = 7 * 24 * 60 * 60;
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64
+}
+
+/// Checkpoint every open lobby (warm pool lobbies are refilled on their own and left out)
+pub async fn checkpoint_lobbies(state: &ServerState, storage: &Storage) -> Result<usize, sqlx::Error> {
+    let lobbies: Vec<_> = state
+        .iter_lobbies()
+        .filter(|entry| !state.warm_pool.is_idle(entry.key()))
+        .map(|entry| entry.lobby.clone())
+        .collect();
+    let mut records = Vec::with_capacity(lobbies.len());
+    for lobby in lobbies {
+        let lobby = lobby.read().await;
+        records.push(LobbyRecord::of(&lobby, state));
+    }
+    records.sort_by(|a, b| a.spec.code.cmp(&b.spec.code));
+    storage.checkpoint(&records).await?;
+    Ok(records.len())
+}
+
+/// Reopen checkpointed lobbies (or rejoin players to lobbies that already exist)
+/// Players come back disconnected, holding their slot and stats until they `reconnect`
+/// with their old session token or the reconnect window runs out
+/// Run before other lobbies are created, so their bots never take a restored player's id
+pub async fn restore_lobbies(
+    state: &Arc<ServerState>,
+    records: Vec<LobbyRecord>,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    socket: Arc<UdpSocket>,
+) -> usize {
+    for player in records.iter().flat_map(|record| &record.players) {
+        state.restore_session(player.id, player.session_token.clone());
+    }
+
+    let now = Instant::now();
+    let mut restored = 0;
+    for record in records {
+        let code = record.spec.code.clone();
+        if !state.lobby_exists(&code) {
+            if let Err(e) = crate::server::create_lobby_from_spec(state, record.spec, weapons.clone(), config.clone(), socket.clone()) {
+                log::warn!("Failed to restore lobby {}: {}", code, e);
+                continue;
+            }
+        }
+        let Some(lobby) = state.get_lobby(&code) else {
+            continue;
+        };
+        let mut lobby = lobby.write().await;
+        for player in record.players {
+            if let Err(e) = lobbies::add_player(&mut lobby, player.id, player.name, WeaponDb::default_weapon_id(), &weapons) {
+                log::warn!("Failed to restore player {} to lobby {}: {}", player.id, code, e);
+                continue;
+            }
+            if let Some(restored) = lobby.players.get_mut(&player.id) {
+                restored.team_id = player.team_id;
+                restored.score = player.score;
+                restored.kills = player.kills;
+                restored.deaths = player.deaths;
+                restored.account_id = player.account_id;
+                restored.connection = Connection::Disconnected { since: now };
+            }
+            state.player_directory.restore(player.id, player.uuid);
+            state.register_player_lobby(player.id, &code);
+        }
+        if record.owner.is_some_and(|owner| lobby.players.contains_key(&owner)) {
+            lobby.owner = record.owner;
+        }
+        restored += 1;
+    }
+    restored
+}
+
+/// Checkpoint the open lobbies every `interval`
+pub async fn run_checkpointer(state: Arc<ServerState>, storage: Arc<Storage>, interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+    timer.tick().await; // The first tick fires immediately; nothing changed since the restore yet
+    loop {
+        timer.tick().await;
+        match checkpoint_lobbies(&state, &storage).await {
+            Ok(count) => log::debug!("Checkpointed {} lobbies", count),
+            Err(e) => log::warn!("Lobby checkpoint failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::gungame::GunGame;
+    use crate::domain::history;
+    use crate::testutil::{epoch, LobbyBuilder, PlayerBuilder};
+
+    /// A match that ended `ago` seconds back
+    fn finished_match(ago: i64) -> MatchRecord {
+        let mut record = history::finish(&mut LobbyBuilder::new().build(), None, epoch());
+        record.ended_at = (unix_now() - ago) as u64;
+        record
+    }
+
+    fn record(code: &str) -> LobbyRecord {
+        LobbyRecord {
+            spec: LobbySpec {
+                code: code.to_string(),
+                max_players: 6,
+                scene: "world".to_string(),
+                mode: GameMode::GunGame(GunGame::new(vec![1, 2])),
+                team_count: 2,
+                dimensions: Dimensions::Two,
+                bots: 1,
+                bot_fill: 3,
+                tick_rate_hz: Some(30),
+                persistent: false,
+                hide_enemy_state: true,
+                integrity: IntegrityRules { max_fov: 90.0, aim_assist: true },
+                visibility: Visibility::Unlisted,
+                password: Some(PasswordHash::new("hunter2")),
+                hosted: true,
+                record_replay: false,
+            },
+            owner: Some(7),
+            players: vec![PlayerRecord {
+                id: 7,
+                uuid: Uuid::new_v4(),
+                name: "Ada".to_string(),
+                session_token: "token".to_string(),
+                team_id: Some(1),
+                score: 300,
+                kills: 3,
+                deaths: 1,
+                account_id: None,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_round_trip() {
+        let storage = Storage::in_memory().await;
+        let lobbies = vec![record("alpha"), record("beta")];
+        storage.checkpoint(&lobbies).await.unwrap();
+        let loaded = storage.load(&[1, 2]).await.unwrap();
+        assert_eq!(loaded, lobbies);
+        assert!(loaded[0].spec.password.as_ref().unwrap().verify("hunter2"));
+
+        // Each checkpoint replaces the last one
+        storage.checkpoint(&lobbies[1..]).await.unwrap();
+        let loaded = storage.load(&[1, 2]).await.unwrap();
+        assert_eq!(loaded.iter().map(|l| l.spec.code.as_str()).collect::<Vec<_>>(), ["beta"]);
+        assert_eq!(loaded[0].players.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_register_login_and_record_matches() {
+        let storage = Storage::in_memory().await;
+        let ada = storage.create_account("Ada", &PasswordHash::new("hunter2")).await.unwrap().unwrap();
+        assert!(storage.create_account("ADA", &PasswordHash::new("other")).await.unwrap().is_none());
+        assert_eq!(storage.login("ada", "hunter2").await.unwrap(), Some(ada.clone()));
+        assert_eq!(storage.login("ada", "hunter3").await.unwrap(), None);
+        assert_eq!(storage.login("bob", "hunter2").await.unwrap(), None);
+
+        let result = AccountResult { account_id: ada.account_id, place: 1, score: 300, kills: 3, deaths: 1, won: true, shots: 10, hits: 4 };
+        storage.record_match(&finished_match(0), std::slice::from_ref(&result)).await.unwrap();
+        storage.record_match(&finished_match(0), &[AccountResult { won: false, ..result }]).await.unwrap();
+        let ada = storage.account(ada.account_id).await.unwrap().unwrap();
+        assert_eq!((ada.kills, ada.deaths, ada.wins, ada.matches), (6, 2, 1, 2));
+        assert_eq!(ada.accuracy(), 0.4);
+    }
+
+    #[tokio::test]
+    async fn test_leaderboards_rank_by_metric_and_period() {
+        let storage = Storage::in_memory().await;
+        let mut ids = Vec::new();
+        for name in ["ada", "bob", "cy", "idle"] {
+            ids.push(storage.create_account(name, &PasswordHash::new("password")).await.unwrap().unwrap().account_id);
+        }
+        let result = |i: usize, kills, deaths, won| AccountResult { account_id: ids[i], place: 1, score: 0, kills, deaths, won, shots: 0, hits: 0 };
+        storage.record_match(&finished_match(0), &[result(0, 10, 10, false), result(1, 6, 1, true), result(2, 8, 4, false)]).await.unwrap();
+        storage.record_match(&finished_match(WEEK_SECS + 60), &[result(0, 20, 0, true)]).await.unwrap();
+
+        let names = |rows: Vec<LeaderboardRow>| rows.into_iter().map(|row| row.username).collect::<Vec<_>>();
+        let (total, rows) = storage.leaderboard(LeaderboardMetric::Kills, LeaderboardPeriod::AllTime, 10, 0).await.unwrap();
+        assert_eq!(total, 3); // Accounts that never played aren't ranked
+        assert_eq!(names(rows), ["ada", "cy", "bob"]);
+        let (_, rows) = storage.leaderboard(LeaderboardMetric::Kills, LeaderboardPeriod::Weekly, 10, 0).await.unwrap();
+        assert_eq!(names(rows), ["ada", "cy", "bob"]);
+        assert_eq!(
+            storage.leaderboard(LeaderboardMetric::Kd, LeaderboardPeriod::Weekly, 10, 0).await.unwrap().1[0].kd(),
+            6.0
+        );
+        let (total, rows) = storage.leaderboard(LeaderboardMetric::Wins, LeaderboardPeriod::AllTime, 1, 1).await.unwrap();
+        assert_eq!((total, names(rows)), (3, vec!["bob".to_string()])); // ada's old win puts her first
+    }
+
+    #[tokio::test]
+    async fn test_match_history_by_match_and_account() {
+        let storage = Storage::in_memory().await;
+        let ada = storage.create_account("ada", &PasswordHash::new("password")).await.unwrap().unwrap().account_id;
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).with_player(PlayerBuilder::new().id(2)).build();
+        lobby.players.get_mut(&1).unwrap().account_id = Some(ada);
+        lobby.players.get_mut(&2).unwrap().score = 200;
+        history::begin(&mut lobby, epoch());
+        let latest = history::finish(&mut lobby, Some(2), epoch());
+        storage.record_match(&finished_match(3600), &[]).await.unwrap();
+        let older = finished_match(60);
+        let results = crate::domain::matches::account_results(&lobby, Some(2));
+        storage.record_match(&older, &results).await.unwrap();
+        storage.record_match(&latest, &results).await.unwrap();
+
+        assert_eq!(storage.match_record(&latest.match_id).await.unwrap(), Some(latest.clone()));
+        assert_eq!(storage.match_record("nope").await.unwrap(), None);
+        let (total, page) = storage.account_match_history(ada, 1, 0).await.unwrap();
+        assert_eq!(total, 2); // Only the matches ada played
+        assert_eq!((page[0].match_id.as_str(), page[0].place, page[0].won), (latest.match_id.as_str(), 2, false));
+        let (_, page) = storage.account_match_history(ada, 10, 1).await.unwrap();
+        assert_eq!(page.iter().map(|m| m.match_id.as_str()).collect::<Vec<_>>(), [older.match_id.as_str()]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_holds_slots_for_reconnect() {
+        let state = Arc::new(ServerState::new());
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let weapons = Arc::new(WeaponDb::load());
+        let config = Arc::new(Config::default());
+        let saved = record("alpha");
+        let uuid = saved.players[0].uuid;
+
+        assert_eq!(restore_lobbies(&state, vec![saved], weapons, config, socket).await, 1);
+        {
+            let lobby = state.get_lobby("alpha").unwrap();
+            let lobby = lobby.read().await;
+            let player = lobby.players.get(&7).unwrap();
+            assert!(matches!(player.connection, Connection::Disconnected { .. }));
+            assert_eq!((player.team_id, player.score, player.kills, player.deaths), (Some(1), 300, 3, 1));
+            assert_eq!(lobby.owner, Some(7));
+            assert_eq!(lobby.bots.len(), 1);
+            assert!(!lobby.bots.contains_key(&7));
+        }
+        assert!(state.verify_session_token(7, "token"));
+        assert_eq!(state.player_directory.resolve(&uuid.to_string()), Some(7));
+        assert_eq!(state.find_lobby_by_player(7).await.as_deref(), Some("alpha"));
+        assert!(state.next_player_id() > 7);
+    }
+}
@@ -0,0 +1,98 @@
+//! Builders for lobby and player state in unit tests
+//!
+//! `LobbyBuilder::new().with_player(PlayerBuilder::new().id(2).ammo(5)).build()`
+
+use crate::state::lobby::{Lobby, Player};
+
+/// Builds a `Player` starting from `Player::new_player` defaults
+/// (id 1, weapon 1, 20/20 ammo, full health, at (0, 1, 0) facing -Z)
+pub struct PlayerBuilder {
+    player: Player,
+}
+
+impl PlayerBuilder {
+    pub fn new() -> Self {
+        Self {
+            player: Player::new_player(1, "Test".to_string(), 1, 20),
+        }
+    }
+
+    pub fn id(mut self, id: u32) -> Self {
+        self.player.id = id;
+        self
+    }
+
+    /// Held weapon, owned in place of the starting one
+    pub fn weapon(mut self, weapon_id: u32) -> Self {
+        let magazine = self.player.inventory.remove(&self.player.current_weapon_id);
+        self.player.inventory.extend(magazine.map(|magazine| (weapon_id, magazine)));
+        self.player.current_weapon_id = weapon_id;
+        self
+    }
+
+    /// Current ammo (max ammo stays at the magazine size)
+    pub fn ammo(mut self, ammo: u32) -> Self {
+        self.player.current_ammo = ammo;
+        self
+    }
+
+    pub fn health(mut self, health: u32) -> Self {
+        self.player.current_health = health;
+        self
+    }
+
+    pub fn position(mut self, position: (f32, f32, f32)) -> Self {
+        self.player.position = position;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: (f32, f32, f32)) -> Self {
+        self.player.rotation = rotation;
+        self
+    }
+
+    pub fn build(self) -> Player {
+        self.player
+    }
+}
+
+impl Default for PlayerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds a 4-player "TEST" lobby on the "world" scene
+pub struct LobbyBuilder {
+    lobby: Lobby,
+}
+
+impl LobbyBuilder {
+    pub fn new() -> Self {
+        Self {
+            lobby: Lobby::new("TEST".to_string(), 4, "world".to_string()),
+        }
+    }
+
+    pub fn with_player(mut self, player: PlayerBuilder) -> Self {
+        let player = player.build();
+        self.lobby.players.insert(player.id, player);
+        self
+    }
+
+    pub fn build(self) -> Lobby {
+        self.lobby
+    }
+}
+
+impl Default for LobbyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fixed instant for tests that build times as offsets from a common start
+pub fn epoch() -> std::time::Instant {
+    static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    *EPOCH.get_or_init(std::time::Instant::now)
+}
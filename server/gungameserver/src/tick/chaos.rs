@@ -0,0 +1,266 @@
+//! Chaos harness for the lobby tick loop (test only)
+//!
+//! Drives a real `lobby_tick_loop` with random command bursts, malformed commands,
+//! client churn and clock jumps, checking lobby invariants between rounds.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
+use crate::domain::lobbies;
+use crate::state::commands::LobbyCommand;
+use crate::state::lobby::Lobby;
+use crate::state::server_state::ServerState;
+use crate::tick::lobby_tick::lobby_tick_loop;
+use crate::tick::outbound::OutboundQueue;
+use crate::utils::config::Config;
+use crate::utils::weapondb::WeaponDb;
+
+/// Player ids handed out by the harness; malformed commands use ids outside this range
+const MAX_PLAYER_ID: u32 = 24;
+
+/// Deterministic xorshift generator so failures reproduce from the seed
+struct ChaosRng(u64);
+
+impl ChaosRng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    fn coord(&mut self) -> f32 {
+        self.below(200) as f32 - 100.0
+    }
+
+    fn player_id(&mut self) -> u32 {
+        1 + self.below(MAX_PLAYER_ID as u64) as u32
+    }
+
+    fn addr(&mut self) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 40_000 + self.below(64) as u16))
+    }
+}
+
+/// A running tick loop under test
+struct ChaosLobby {
+    lobby: Arc<RwLock<Lobby>>,
+    tx: mpsc::Sender<LobbyCommand>,
+    handle: JoinHandle<()>,
+    weapons: Arc<WeaponDb>,
+    tick: Duration,
+}
+
+impl ChaosLobby {
+    async fn start(max_players: u32) -> Self {
+        let config = Config { tick_rate_hz: 500, ..Config::default() };
+        let tick = Duration::from_millis(config.tick_interval_ms());
+        let weapons = Arc::new(WeaponDb::load());
+        let lobby = Arc::new(RwLock::new(Lobby::new("CHAOS".to_string(), max_players, "world".to_string())));
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let (tx, rx) = mpsc::channel(1000);
+        let state = Arc::new(ServerState::new());
+        let (outbound, _sender) = OutboundQueue::spawn(socket, Some(state.clone()));
+
+        let handle = tokio::spawn(lobby_tick_loop(
+            lobby.clone(),
+            rx,
+            outbound,
+            weapons.clone(),
+            Arc::new(config),
+            Some(state),
+        ));
+
+        Self { lobby, tx, handle, weapons, tick }
+    }
+
+    /// Queue a command; a full channel is backpressure, not a failure
+    fn send(&self, cmd: LobbyCommand) {
+        let _ = self.tx.try_send(cmd);
+    }
+
+    /// Let the loop run a few ticks, then check it is still alive and consistent
+    async fn settle_and_check(&self, round: usize) {
+        tokio::time::sleep(self.tick * 3).await;
+        assert!(!self.handle.is_finished(), "tick loop died in round {}", round);
+        let lobby = self.lobby.read().await;
+        assert_invariants(&lobby, &self.weapons, round);
+    }
+}
+
+fn assert_invariants(lobby: &Lobby, weapons: &WeaponDb, round: usize) {
+    assert!(
+        lobby.players.len() <= lobby.max_players as usize,
+        "round {}: {} players in a lobby of {}", round, lobby.players.len(), lobby.max_players
+    );
+    for player_id in lobby.client_addresses.keys() {
+        assert!(lobby.players.contains_key(player_id), "round {}: address for missing player {}", round, player_id);
+    }
+    assert!(lobby.kill_feed.is_empty(), "round {}: kills left unbroadcast", round);
+    assert!(lobby.shot_feed.is_empty(), "round {}: shots left unbroadcast", round);
+    assert!(lobby.damage_feed.is_empty(), "round {}: hits left unbroadcast", round);
+    assert!(lobby.grenade_feed.is_empty(), "round {}: grenades left unbroadcast", round);
+    for player_id in lobby.bots.keys() {
+        assert!(lobby.players.contains_key(player_id), "round {}: brain for missing bot {}", round, player_id);
+    }
+    for (id, player) in &lobby.players {
+        assert_eq!(*id, player.id, "round {}: player keyed under the wrong id", round);
+        assert!(player.current_health <= player.max_health, "round {}: player {} overhealed", round, id);
+        assert!(player.current_ammo <= player.max_ammo, "round {}: player {} overfilled", round, id);
+        assert!(weapons.get(player.current_weapon_id).is_some(), "round {}: player {} holds unknown weapon", round, id);
+        assert!(player.killstreak <= player.kills, "round {}: player {} streak exceeds kills", round, id);
+        assert!(!player.is_dead || player.current_health == 0, "round {}: dead player {} has health", round, id);
+    }
+}
+
+/// Many valid commands from existing players in one tick
+fn command_burst(rng: &mut ChaosRng, chaos: &ChaosLobby) {
+    for _ in 0..(50 + rng.below(200)) {
+        let player_id = rng.player_id();
+        let cmd = match rng.below(6) {
+            0 => LobbyCommand::PositionUpdate {
+                player_id,
+                position: (rng.coord(), 1.0, rng.coord()),
+                rotation: (0.0, rng.coord() / 30.0, 0.0),
+                addr: rng.addr(),
+            },
+            1 => LobbyCommand::Shoot {
+                player_id,
+                origin: (rng.coord(), 1.0, rng.coord()),
+                direction: (rng.coord(), rng.coord() / 10.0, rng.coord()),
+                client_time: None,
+            },
+            2 => LobbyCommand::Reload { player_id },
+            3 => LobbyCommand::WeaponSwitch { player_id, weapon_id: 1 + rng.below(3) as u32 },
+            4 => LobbyCommand::Throw {
+                player_id,
+                origin: (rng.coord(), 1.0, rng.coord()),
+                direction: (rng.coord(), rng.coord() / 10.0, rng.coord()),
+            },
+            _ => LobbyCommand::Heartbeat { player_id, addr: rng.addr() },
+        };
+        chaos.send(cmd);
+    }
+}
+
+/// Commands a buggy or hostile client could produce
+fn malformed_commands(rng: &mut ChaosRng, chaos: &ChaosLobby) {
+    let garbage_id = match rng.below(3) {
+        0 => 0,
+        1 => u32::MAX,
+        _ => MAX_PLAYER_ID + 1 + rng.below(1000) as u32,
+    };
+    let garbage_f32 = match rng.below(4) {
+        0 => f32::NAN,
+        1 => f32::INFINITY,
+        2 => f32::NEG_INFINITY,
+        _ => f32::MAX,
+    };
+    let player_id = rng.player_id();
+
+    chaos.send(LobbyCommand::PositionUpdate {
+        player_id,
+        position: (garbage_f32, garbage_f32, garbage_f32),
+        rotation: (garbage_f32, garbage_f32, garbage_f32),
+        addr: rng.addr(),
+    });
+    chaos.send(LobbyCommand::Shoot { player_id, origin: (0.0, 1.0, 0.0), direction: (0.0, 0.0, 0.0), client_time: None });
+    chaos.send(LobbyCommand::Shoot {
+        player_id,
+        origin: (garbage_f32, garbage_f32, garbage_f32),
+        direction: (garbage_f32, 0.0, garbage_f32),
+        client_time: None,
+    });
+    chaos.send(LobbyCommand::Shoot { player_id: garbage_id, origin: (0.0, 1.0, 0.0), direction: (0.0, 0.0, -1.0), client_time: None });
+    chaos.send(LobbyCommand::WeaponSwitch { player_id, weapon_id: garbage_id });
+    chaos.send(LobbyCommand::Reload { player_id: garbage_id });
+    chaos.send(LobbyCommand::PlayerLeave { player_id: garbage_id });
+    chaos.send(LobbyCommand::UdpConnect { player_id: garbage_id, name: String::new(), addr: rng.addr() });
+    chaos.send(LobbyCommand::Heartbeat { player_id: garbage_id, addr: rng.addr() });
+    // Duplicate join for a player that may already exist
+    chaos.send(LobbyCommand::PlayerJoin { player_id, name: "dup".to_string(), addr: rng.addr() });
+}
+
+/// Mass joins and leaves, with occasional bots
+async fn client_churn(rng: &mut ChaosRng, chaos: &ChaosLobby) {
+    for _ in 0..(1 + rng.below(MAX_PLAYER_ID as u64)) {
+        let player_id = rng.player_id();
+        if rng.chance(50) {
+            chaos.send(LobbyCommand::PlayerJoin { player_id, name: format!("P{}", player_id), addr: rng.addr() });
+        } else {
+            chaos.send(LobbyCommand::PlayerLeave { player_id });
+        }
+    }
+    if rng.chance(20) {
+        let bot_id = rng.player_id();
+        let mut lobby = chaos.lobby.write().await;
+        let _ = lobbies::add_bot(&mut lobby, bot_id, format!("Bot {}", bot_id), &chaos.weapons);
+    }
+}
+
+/// Shift every stored timestamp far into the past or future, so timers see out-of-order times
+async fn clock_jump(rng: &mut ChaosRng, chaos: &ChaosLobby) {
+    let jump = Duration::from_secs(1 + rng.below(3600));
+    let backwards = rng.chance(50);
+    let shift = |t: Instant| {
+        if backwards { t.checked_add(jump) } else { t.checked_sub(jump) }.unwrap_or(t)
+    };
+
+    let mut lobby = chaos.lobby.write().await;
+    for player in lobby.players.values_mut() {
+        player.last_update = shift(player.last_update);
+        player.last_shot_time = player.last_shot_time.map(shift);
+        player.reload_end_time = player.reload_end_time.map(shift);
+        player.respawn_time = player.respawn_time.map(shift);
+        player.warned_at = player.warned_at.map(shift);
+    }
+    for pickup in lobby.pickups.iter_mut() {
+        pickup.available_at = shift(pickup.available_at);
+    }
+}
+
+async fn run_chaos(seed: u64, rounds: usize) {
+    let mut rng = ChaosRng::new(seed);
+    let chaos = ChaosLobby::start(16).await;
+
+    for round in 0..rounds {
+        match rng.below(4) {
+            0 => command_burst(&mut rng, &chaos),
+            1 => malformed_commands(&mut rng, &chaos),
+            2 => client_churn(&mut rng, &chaos).await,
+            _ => clock_jump(&mut rng, &chaos).await,
+        }
+        chaos.settle_and_check(round).await;
+    }
+
+    chaos.handle.abort();
+}
+
+#[tokio::test]
+async fn test_chaos_tick_loop_keeps_invariants() {
+    for seed in [1, 0xC0FFEE, 0xDEAD_BEEF] {
+        run_chaos(seed, 120).await;
+    }
+}
+
+#[tokio::test]
+async fn test_chaos_rng_is_deterministic() {
+    let mut a = ChaosRng::new(42);
+    let mut b = ChaosRng::new(42);
+    assert!((0..100).all(|_| a.next() == b.next()));
+}
@@ -7,7 +7,8 @@ pub fn collect_dirty_events(lobby: &mut Lobby) -> SmallEventVec {
     let mut events = SmallEventVec::new();
 
     for &player_id in &lobby.dirty_players {
-        if let Some(player) = lobby.players.get(&player_id) {
+        // Loading players are sent in full once they've loaded (see `lobbies::finish_loading`)
+        if let Some(player) = lobby.players.get(&player_id).filter(|p| !p.is_loading()) {
             let last = lobby.last_sync_state.get(&player_id);
 
             // Only include changed fields
@@ -65,6 +66,18 @@ pub fn collect_dirty_events(lobby: &mut Lobby) -> SmallEventVec {
                 });
             }
 
+            let weapons: Vec<u32> = player.inventory.keys().copied().collect();
+            if last.map(|l| l.weapons != weapons).unwrap_or(true) {
+                events.push(SyncEvent::InventoryChanged { player_id, weapons });
+            }
+
+            if last.map(|l| l.team_id != player.team_id).unwrap_or(player.team_id.is_some()) {
+                events.push(SyncEvent::TeamChanged {
+                    player_id,
+                    team_id: player.team_id,
+                });
+            }
+
             // Position changes are handled separately (more frequent)
             // Only sync position if it's a new player or significant change
 
@@ -75,6 +88,13 @@ pub fn collect_dirty_events(lobby: &mut Lobby) -> SmallEventVec {
         }
     }
 
+    if lobby.team_scores_dirty {
+        lobby.team_scores_dirty = false;
+        events.push(SyncEvent::TeamScoresChanged {
+            scores: lobby.team_scores.iter().map(|(team_id, score)| (*team_id, *score)).collect(),
+        });
+    }
+
     events
 }
 
@@ -99,36 +119,11 @@ pub fn collect_position_events(lobby: &Lobby, player_ids: &[u32]) -> SmallEventV
 mod tests {
     use super::*;
     use crate::state::lobby::Lobby;
-    use std::time::SystemTime;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
 
     #[test]
     fn test_collect_dirty_events() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
-
-        // Add player
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        lobby.players.insert(1, player);
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
         lobby.mark_dirty(1);
 
         let events = collect_dirty_events(&mut lobby);
@@ -138,31 +133,7 @@ mod tests {
 
     #[test]
     fn test_collect_dirty_events_no_changes() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
-
-        let mut player = crate::state::lobby::Player {
-            id: 1,
-            name: "Test".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        lobby.players.insert(1, player);
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
 
         // Set last sync state to match current
         lobby
@@ -177,6 +148,18 @@ mod tests {
         assert!(events.is_empty());
     }
 
+    #[test]
+    fn test_collect_dirty_events_inventory() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        lobby.last_sync_state.insert(1, lobby.players.get(&1).unwrap().to_sync_state());
+        lobby.players.get_mut(&1).unwrap().inventory.insert(2, crate::state::lobby::Magazine::full(8));
+        lobby.mark_dirty(1);
+
+        let events = collect_dirty_events(&mut lobby);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], SyncEvent::InventoryChanged { player_id: 1, weapons } if weapons == &vec![1, 2]));
+    }
+
     #[test]
     fn test_collect_position_events() {
         let lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
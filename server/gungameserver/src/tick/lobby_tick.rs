@@ -1,51 +1,283 @@
 use std::sync::Arc;
 use tokio::sync::{RwLock, mpsc};
-use tokio::net::UdpSocket;
 use tokio::time::{interval, Duration};
-use crate::state::lobby::Lobby;
+use crate::state::lobby::{Dimensions, Lobby, MatchState};
 use crate::state::commands::{LobbyCommand, drain_and_coalesce};
 use crate::state::server_state::ServerState;
+use crate::state::snapshot::LobbyListing;
+use crate::state::analytics;
 use crate::domain::lobbies;
 use crate::domain::logic;
+use crate::domain::bot;
+use crate::domain::gungame::{self, GameMode};
+use crate::domain::history;
+use crate::domain::matches::{self, MatchEvent};
+use crate::domain::mutators::{self, Mutator, MutatorEvent};
+use crate::domain::owner::{self, LobbyEvent};
+use crate::domain::horde::{self, HordeEvent};
+use crate::domain::teams;
+use crate::domain::chat::{self, ChatMessage};
+use crate::domain::voice;
+use crate::domain::referee;
+use crate::domain::challenge;
+use crate::domain::clock::{Clock, SystemClock};
+use crate::domain::simulator;
+use crate::domain::rewind;
+use crate::domain::anticheat::{self, ViolationKind};
+use crate::domain::projectiles::{self, ProjectileEvent};
+use crate::domain::grenades::{self, GrenadeEvent};
 use crate::tick::delta_sync;
+use crate::tick::outbound::{self, Outbound, OutboundQueue};
+use crate::tick::visibility::VisibilityTracker;
+use crate::tick::relevance::{self, RelevanceGrid};
+use crate::net::reliable::{ReliableSender, SerializedPacket};
+use crate::tick::snapshot::{LobbySnapshot, SnapshotSender};
+use crate::tick::state_visibility;
+use crate::tick::telemetry::TelemetryTracker;
 use crate::utils::weapondb::WeaponDb;
 use crate::utils::config::Config;
 use crate::utils::buffers::{SyncEvent, PacketBuffer};
+use crate::net::binary::{self, BinaryPacket};
+use crate::net::protocol::{ClientProtocol, CAP_BINARY, CAP_CHANNELS, CAP_REDUCED_RATE, CAP_RELIABLE, CAP_SNAPSHOTS, CAP_VOICE};
+use crate::net::messages::{self, MessageCode};
+use crate::replay::ReplayRecorder;
+use crate::state::lobby::Player;
 use serde_json::json;
 
 /// Per-lobby tick loop - processes commands and broadcasts updates
-/// Runs at fixed tick rate (50Hz by default)
+/// Runs at the lobby's tick rate (the server's 50Hz by default), and at `idle_tick_rate_hz` while
+/// no UDP client is connected, waking at once when a command arrives
 pub async fn lobby_tick_loop(
     lobby: Arc<RwLock<Lobby>>,
     mut command_rx: mpsc::Receiver<LobbyCommand>,
-    socket: Arc<UdpSocket>,
+    outbound_queue: OutboundQueue,
     weapons: Arc<WeaponDb>,
     config: Arc<Config>,
     server_state: Option<Arc<ServerState>>,
 ) {
-    let tick_interval = Duration::from_millis(config.tick_interval_ms());
-    let mut tick_timer = interval(tick_interval);
-    let mut send_buffer = PacketBuffer::default();
-    let lobby_code = lobby.read().await.code.clone();
-    
+    let idle_interval = (config.idle_tick_rate_hz > 0).then(|| Duration::from_millis(1000 / config.idle_tick_rate_hz as u64));
+    let mut ticker = LobbyTicker::new(&*lobby.read().await, outbound_queue, weapons, config, server_state);
+    let mut tick_timer = interval(ticker.tick_interval);
+    let mut idle = false; // Nobody was connected at the end of the last tick
+
     loop {
-        tick_timer.tick().await;
-        
-        // 1. Drain commands (coalesce positions - keep only latest)
-        let commands = drain_and_coalesce(&mut command_rx);
-        
-        // 2. Acquire lock ONCE per tick
+        // Idle lobbies sleep until their next idle tick or a command, whichever comes first.
+        // The simulation still steps by `tick_interval`, so bots and projectiles just slow down
+        let mut woken_by = None;
+        match idle_interval.filter(|_| idle) {
+            Some(idle_interval) => {
+                match tokio::time::timeout(idle_interval, command_rx.recv()).await {
+                    Ok(Some(command)) => woken_by = Some(command),
+                    Ok(None) => tokio::time::sleep(idle_interval).await, // No senders left to wake us
+                    Err(_) => {}
+                }
+                tick_timer.reset(); // Back to full rate without a burst of missed ticks
+            }
+            None => {
+                tick_timer.tick().await;
+            }
+        }
+        let tick_started = std::time::Instant::now();
+
+        // Drain commands (coalesce positions - keep only latest)
+        let mut commands = drain_and_coalesce(&mut command_rx);
+        if let Some(command) = woken_by {
+            commands.insert(0, command);
+        }
+
+        // Acquire the lock ONCE per tick
         let mut lobby_guard = lobby.write().await;
-        
-        // Track players that joined/left this tick
-        let mut players_joined: Vec<(u32, String)> = Vec::new();
-        let mut players_left: Vec<u32> = Vec::new();
-        let mut position_updates: Vec<u32> = Vec::new();
-        let kill_events: Vec<logic::KillEvent> = Vec::new();
-        let mut respawn_events: Vec<u32> = Vec::new();
-        
-        // 3. Process all commands
+        idle = ticker.run_tick(&mut lobby_guard, commands, tick_started);
+        drop(lobby_guard);
+
+        // Hand the tick's packets to the sender task, now that the lobby is unlocked
+        ticker.outbound_queue.flush(&ticker.outbound);
+
+        // Report tick saturation for auto-scaling
+        if let Some(ref state) = ticker.server_state {
+            state.record_tick_load(&ticker.lobby_code, tick_started.elapsed().as_secs_f32() / ticker.tick_interval.as_secs_f32());
+        }
+    }
+}
+
+/// What a lobby's tick loop keeps from one tick to the next
+struct LobbyTicker {
+    lobby_code: String,
+    tick_interval: Duration,
+    weapons: Arc<WeaponDb>,
+    config: Arc<Config>,
+    server_state: Option<Arc<ServerState>>,
+    clock: Arc<dyn Clock>,
+    tick: u64,
+    send_buffer: PacketBuffer,
+    visibility: VisibilityTracker,
+    reliable: ReliableSender,
+    snapshots: SnapshotSender,
+    outbound: Outbound,
+    outbound_queue: OutboundQueue,
+    telemetry: TelemetryTracker,
+    tunables_revision: Option<u64>,
+    weapons_revision: Option<u64>,
+    local_bot_id: u32, // Horde bot ids without a server to allocate them (counts down)
+    recorder: Option<ReplayRecorder>,
+}
+
+/// What happened during a tick, collected by the input and simulation phases for the broadcast phase
+#[derive(Default)]
+struct TickEvents {
+    players_joined: Vec<(u32, String)>,
+    players_left: Vec<u32>,
+    spectators_left: Vec<u32>, // Nobody is told; they were never players
+    players_loaded: Vec<u32>, // Announced once they can be seen
+    players_reconnected: Vec<u32>,
+    players_disconnected: Vec<u32>,
+    arrivals: Vec<(u32, String)>,
+    position_updates: Vec<u32>,
+    announcements: Vec<String>,
+    shutdown_notices: Vec<u64>, // Grace periods of `server_shutdown` packets
+    chat_messages: Vec<ChatMessage>,
+    chat_rejected: Vec<(u32, &'static str)>,
+    voice_frames: Vec<(u32, String, Vec<u32>)>, // Speaker, payload, listeners
+    respawns: Vec<u32>,
+    kills: Vec<logic::KillEvent>,
+    shots: Vec<logic::ShotEvent>,
+    damage: Vec<logic::DamageEvent>,
+    projectiles: Vec<ProjectileEvent>,
+    grenades: Vec<GrenadeEvent>,
+    match_winners: Vec<u32>,
+    match_events: Vec<MatchEvent>,
+    finished_matches: Vec<history::MatchRecord>,
+    horde_events: Vec<HordeEvent>,
+    challenges: Vec<(u32, challenge::Challenge)>,
+    countdown: Option<serde_json::Value>,
+    mutator_events: Vec<MutatorEvent>,
+    rematch_offers: Vec<(u32, String)>,
+    lobby_events: Vec<LobbyEvent>,
+    listing_changed: bool,
+}
+
+impl LobbyTicker {
+    fn new(
+        lobby: &Lobby,
+        outbound_queue: OutboundQueue,
+        weapons: Arc<WeaponDb>,
+        config: Arc<Config>,
+        server_state: Option<Arc<ServerState>>,
+    ) -> Self {
+        let clock: Arc<dyn Clock> = server_state.as_ref().map_or_else(|| Arc::new(SystemClock) as Arc<dyn Clock>, |state| state.clock.clone());
+        let recorder = if lobby.record_replay {
+            ReplayRecorder::start(lobby, &config, clock.now())
+                .map_err(|e| log::warn!("Not recording lobby {}: {}", lobby.code, e))
+                .ok()
+        } else {
+            None
+        };
+        Self {
+            lobby_code: lobby.code.clone(),
+            tick_interval: Duration::from_millis(config.lobby_tick_interval_ms(lobby.tick_rate_hz)),
+            weapons,
+            visibility: VisibilityTracker::new(&config),
+            reliable: ReliableSender::new(&config),
+            snapshots: SnapshotSender::new(&config),
+            outbound: Outbound::default(),
+            outbound_queue,
+            telemetry: TelemetryTracker::new(clock.now()),
+            config,
+            server_state,
+            clock,
+            tick: 0,
+            send_buffer: PacketBuffer::default(),
+            tunables_revision: None,
+            weapons_revision: None,
+            local_bot_id: u32::MAX,
+            recorder,
+        }
+    }
+
+    /// Run one tick against the locked lobby, queueing its packets on `outbound`
+    /// Returns whether the lobby is idle (no client connected)
+    fn run_tick(&mut self, lobby: &mut Lobby, commands: Vec<LobbyCommand>, tick_started: std::time::Instant) -> bool {
+        self.tick += 1;
+        self.reliable.start_tick(self.tick);
+        let now = self.clock.now(); // One time for the whole tick, so a replay can repeat it
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.begin_tick(self.tick, now, lobby, &commands);
+        }
+        self.pick_up_live_changes(lobby);
+
+        let mut events = TickEvents::default();
+        self.process_commands(lobby, commands, now, &mut events);
+        self.simulate(lobby, now, &mut events);
+        self.broadcast(lobby, &events, now);
+        self.finish_tick(lobby, events, now, tick_started)
+    }
+
+    /// Pick up tunables and weapons changed live
+    fn pick_up_live_changes(&mut self, lobby: &mut Lobby) {
+        let Some(ref state) = self.server_state else {
+            return;
+        };
+        if self.tunables_revision != Some(state.tunables.revision()) {
+            self.tunables_revision = Some(state.tunables.revision());
+            lobby.tunables = state.tunables.get();
+        }
+        if self.weapons_revision != Some(state.weapons.revision()) {
+            self.weapons_revision = Some(state.weapons.revision());
+            self.weapons = state.weapons.get();
+        }
+    }
+
+    /// Input phase: apply the tick's commands, answering joins, reconnects and kicks right away
+    fn process_commands(&mut self, lobby: &mut Lobby, commands: Vec<LobbyCommand>, now: std::time::Instant, events: &mut TickEvents) {
+        let server_state = self.server_state.as_deref();
+        let loading_timeout = Duration::from_secs(lobby.tunables.loading_timeout_secs);
         for cmd in commands {
+            // Owner actions are applied here; an owner's kick continues as a kick
+            let cmd = match cmd {
+                LobbyCommand::OwnerAction { player_id, action } => {
+                    let Some(state) = server_state else {
+                        continue;
+                    };
+                    match owner::apply(lobby, &state.scenes, player_id, action) {
+                        Ok(Some(kicked)) => LobbyCommand::Kick { player_id: kicked, reason: owner::KICK_REASON.to_string() },
+                        Ok(None) => continue,
+                        Err(e) => {
+                            log::debug!("Owner action by player {} refused: {}", player_id, e);
+                            continue;
+                        }
+                    }
+                }
+                // Referee actions are checked against the spectator's referee flag and always audited
+                LobbyCommand::Referee { player_id, action } => {
+                    let description = action.describe();
+                    let result = referee::apply(lobby, player_id, action, now);
+                    if let Some(state) = server_state {
+                        let name = lobby.spectators.get(&player_id).map_or("unknown", |s| s.name.as_str());
+                        state.audit.record(format!("referee {} ({})", player_id, name), &self.lobby_code, description.clone(), result.as_ref().map(|_| ()).map_err(|e| *e));
+                    }
+                    match result {
+                        Ok(event) => {
+                            log::info!("Referee {} in lobby {}: {}", player_id, self.lobby_code, description);
+                            events.match_events.extend(event);
+                        }
+                        Err(e) => log::debug!("Referee action by {} refused: {}", player_id, e),
+                    }
+                    continue;
+                }
+                cmd => cmd,
+            };
+
+            // Players still loading the scene can't act yet
+            if cmd.needs_loaded_scene().is_some_and(|id| lobbies::is_loading(lobby, id)) {
+                continue;
+            }
+            if let LobbyCommand::LoadingComplete { player_id } = cmd {
+                if lobbies::finish_loading(lobby, player_id) {
+                    events.players_loaded.push(player_id);
+                }
+                continue;
+            }
+
             // Extract info before processing (to avoid borrow issues)
             let join_info = if let LobbyCommand::PlayerJoin { player_id, ref name, addr } = &cmd {
                 Some((*player_id, name.clone(), *addr))
@@ -59,11 +291,17 @@ pub async fn lobby_tick_loop(
                 None
             };
             
-            let leave_id = if let LobbyCommand::PlayerLeave { player_id } = &cmd {
-                Some(*player_id)
+            let reconnect_info = if let LobbyCommand::Reconnect { player_id, addr } = &cmd {
+                Some((*player_id, *addr))
             } else {
                 None
             };
+
+            let leave_id = match &cmd {
+                LobbyCommand::PlayerLeave { player_id } | LobbyCommand::Kick { player_id, .. } => Some(*player_id),
+                _ => None,
+            };
+            let spectator_leaving = leave_id.is_some_and(|id| lobbies::is_spectator(lobby, id));
             
             let position_id = if let LobbyCommand::PositionUpdate { player_id, .. } = &cmd {
                 Some(*player_id)
@@ -71,120 +309,421 @@ pub async fn lobby_tick_loop(
                 None
             };
             
+            if let LobbyCommand::Ack { player_id, seqs, snapshot_tick } = &cmd {
+                self.reliable.ack(*player_id, seqs);
+                if let Some(tick) = snapshot_tick {
+                    self.snapshots.ack(*player_id, *tick);
+                }
+                continue;
+            }
+            if let LobbyCommand::Announce { text } = cmd {
+                events.announcements.push(text);
+                continue;
+            }
+            if let LobbyCommand::Shutdown { grace_secs } = cmd {
+                events.shutdown_notices.push(grace_secs);
+                continue;
+            }
+            if let LobbyCommand::Chat { player_id, text, team_only } = cmd {
+                let filter = server_state.map(|s| s.chat_filter()).unwrap_or_default();
+                match chat::send(lobby, &filter, player_id, &text, team_only, now) {
+                    Ok(message) => events.chat_messages.push(message),
+                    Err(e) => events.chat_rejected.push((player_id, e)),
+                }
+                continue;
+            }
+            if let LobbyCommand::Voice { player_id, payload, proximity } = cmd {
+                match voice::recipients(lobby, player_id, &payload, proximity) {
+                    Ok(listeners) => events.voice_frames.push((player_id, payload, listeners)),
+                    Err(e) => log::trace!("Voice frame from player {} dropped: {}", player_id, e),
+                }
+                continue;
+            }
+            if let LobbyCommand::Kick { player_id, reason } = &cmd {
+                if !lobby.players.contains_key(player_id) {
+                    continue;
+                }
+                send_kicked_message(lobby, &self.outbound, &mut self.reliable, *player_id, reason, server_state);
+            }
+            
             // Process the command
-            process_command(&mut lobby_guard, &weapons, cmd, server_state.as_deref());
+            process_command(lobby, &self.weapons, cmd, server_state, now);
             
             // Handle special cases that need broadcasting
             if let Some((player_id, name, addr)) = join_info {
-                players_joined.push((player_id, name.clone()));
+                lobbies::begin_loading(lobby, player_id, loading_timeout, now);
+                if !lobbies::is_loading(lobby, player_id) {
+                    events.players_joined.push((player_id, name.clone()));
+                }
                 // Send welcome message to new player with current lobby state
-                send_welcome_message(&lobby_guard, &socket, player_id, addr).await;
+                send_welcome_message(lobby, &self.outbound, &mut self.reliable, player_id, addr, server_state);
             }
             
             if let Some((player_id, name, addr)) = udp_connect_info {
-                players_joined.push((player_id, name.clone()));
+                // Spectators aren't announced to the lobby, loading players once they've loaded
+                if !lobbies::is_spectator(lobby, player_id) && !lobbies::is_loading(lobby, player_id) {
+                    events.players_joined.push((player_id, name.clone()));
+                }
                 // For UDP connect, player already has scene info from HTTP join
                 // Just send acknowledgment without scene info to avoid scene reload
-                send_udp_connected_message(&lobby_guard, &socket, player_id, addr).await;
+                send_udp_connected_message(lobby, &self.outbound, &mut self.reliable, player_id, addr, server_state);
                 log::debug!("Player {} ({}) UDP connected, broadcasting join to lobby", player_id, name);
             }
             
-            if let Some(player_id) = leave_id {
-                players_left.push(player_id);
+            if let Some((player_id, addr)) = reconnect_info {
+                if lobby.players.contains_key(&player_id) {
+                    // Start the client's streams over rather than resending what it missed
+                    self.reliable.forget(player_id);
+                    self.snapshots.forget(player_id);
+                    self.visibility.forget(player_id);
+                    send_reconnect_state(lobby, &self.outbound, &mut self.reliable, player_id, addr, server_state);
+                    events.players_reconnected.push(player_id);
+                }
+            }
+
+            match leave_id {
+                Some(player_id) if spectator_leaving => events.spectators_left.push(player_id),
+                Some(player_id) => events.players_left.push(player_id),
+                None => {}
             }
             
             if let Some(player_id) = position_id {
-                position_updates.push(player_id);
+                events.position_updates.push(player_id);
+            }
+        }
+    }
+
+    /// Simulation phase: loading, bots, projectiles, respawns, game modes, cleanup and match flow
+    fn simulate(&mut self, lobby: &mut Lobby, now: std::time::Instant, events: &mut TickEvents) {
+        let lobby_code = self.lobby_code.as_str();
+        let weapons = &self.weapons;
+
+        // Let in players whose client never reported the scene loaded
+        for player_id in lobbies::expire_loading(lobby, now) {
+            log::debug!("Player {} took too long to load in lobby {}, letting them in", player_id, lobby_code);
+            events.players_loaded.push(player_id);
+        }
+        for player_id in std::mem::take(&mut events.players_loaded) {
+            if let Some(player) = lobby.players.get(&player_id) {
+                events.players_joined.push((player_id, player.name.clone()));
+            }
+        }
+        
+        // Run bot AI (bots act through the same domain rules as clients)
+        let bot_ids: Vec<u32> = if lobby.frozen { Vec::new() } else { lobby.bots.keys().copied().collect() };
+        let dt = self.tick_interval.as_secs_f32() * lobby.time_scale;
+        for bot_id in bot_ids {
+            let before = lobby.players.get(&bot_id).map(|p| p.position);
+            if let Err(e) = bot::step(lobby, weapons, bot_id, dt, now) {
+                log::debug!("Bot {} step failed: {}", bot_id, e);
+            }
+            if lobby.players.get(&bot_id).map(|p| p.position) != before {
+                events.position_updates.push(bot_id);
             }
         }
         
-        // 4. Update reload timers
-        logic::update_reload_states(&mut lobby_guard);
+        // Remember where everyone is for lag compensated hits
+        lobby.history.record(&lobby.players, now);
+        
+        // Move projectiles and grenades in flight (hits and explosions land in the kill, projectile and grenade feeds)
+        projectiles::step_at(lobby, weapons, dt, now);
+        grenades::step_at(lobby, weapons, dt, now);
         
-        // 5. Check respawn timers for dead players
-        let now = std::time::SystemTime::now();
-        let mut players_to_respawn: Vec<u32> = Vec::new();
-        for (player_id, player) in &lobby_guard.players {
-            if player.is_dead {
-                if let Some(respawn_time) = player.respawn_time {
-                    if now >= respawn_time {
-                        players_to_respawn.push(*player_id);
+        // Update reload timers
+        logic::update_reload_states_at(lobby, now);
+        
+        // Respawn dead players whose timer ran out
+        events.respawns = logic::respawn_due_players_at(lobby, now);
+        for player_id in &events.respawns {
+            log::debug!("Player {} respawned in lobby {}", player_id, lobby_code);
+        }
+        events.kills = std::mem::take(&mut lobby.kill_feed);
+        events.shots = std::mem::take(&mut lobby.shot_feed);
+        events.damage = std::mem::take(&mut lobby.damage_feed);
+        events.projectiles = std::mem::take(&mut lobby.projectile_feed);
+        events.grenades = std::mem::take(&mut lobby.grenade_feed);
+
+        analytics::record_weapon_usage(lobby, &events.shots, &events.projectiles, &events.kills, now);
+
+        // Anonymized gameplay telemetry (only when the server opted in)
+        if let Some(state) = self.server_state.as_deref().filter(|state| state.telemetry.is_enabled()) {
+            for event in self.telemetry.observe(lobby, &events.shots, &events.projectiles, &events.kills, now) {
+                state.telemetry.record(event);
+            }
+        }
+
+        // Gun game: kills advance the killer along the weapon ladder
+        for event in &events.kills {
+            history::record_kill(lobby, event, now);
+            match gungame::on_kill(lobby, weapons, event.killer_id) {
+                Ok(Some(gungame::Progress::Advanced { player_id, level, weapon_id })) => {
+                    history::record_progress(lobby, player_id, level, weapon_id, now);
+                }
+                Ok(Some(gungame::Progress::Won { player_id })) => {
+                    log::info!("Player {} won the gun game in lobby {}", player_id, lobby_code);
+                    events.match_winners.push(player_id);
+                    if matches!(lobby.match_state, MatchState::InProgress { .. }) {
+                        events.match_events.push(matches::end_match(lobby, Some(player_id), now));
                     }
                 }
+                Ok(_) => {}
+                Err(e) => log::warn!("Gun game progression failed for player {}: {}", event.killer_id, e),
             }
         }
-        
-        // Respawn players and track events
-        for player_id in players_to_respawn {
-            if let Err(e) = logic::respawn_player(&mut lobby_guard, player_id) {
-                log::debug!("Respawn failed for player {}: {}", player_id, e);
-            } else {
-                respawn_events.push(player_id);
-                log::debug!("Player {} respawned in lobby {}", player_id, lobby_code);
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.checkpoint(lobby);
+        }
+
+        // Horde: deaths spend shared lives, waves of bots come and go
+        events.horde_events = events.kills
+            .iter()
+            .filter_map(|event| horde::on_death(lobby, event.victim_id))
+            .collect();
+        let local_bot_id = &mut self.local_bot_id;
+        let mut next_id = || match &self.server_state {
+            Some(state) => state.next_player_id(),
+            None => {
+                *local_bot_id -= 1;
+                *local_bot_id
+            }
+        };
+        match horde::advance(lobby, weapons, now, &mut next_id) {
+            Ok(horde_events) => {
+                for event in horde_events {
+                    match event {
+                        HordeEvent::EnemySpawned { player_id, name } => events.players_joined.push((player_id, name)),
+                        HordeEvent::EnemyRemoved { player_id } => events.players_left.push(player_id),
+                        HordeEvent::Ended { cleared, .. } => {
+                            if matches!(lobby.match_state, MatchState::InProgress { .. }) {
+                                let winner_id = if cleared { matches::leader(lobby) } else { None };
+                                events.match_events.push(matches::end_match(lobby, winner_id, now));
+                            }
+                            events.horde_events.push(event);
+                        }
+                        event => events.horde_events.push(event),
+                    }
+                }
             }
+            Err(e) => log::warn!("Horde wave failed in lobby {}: {}", lobby_code, e),
         }
-        
-        // 6. Cleanup inactive players periodically (every 5 seconds worth of ticks)
-        // Use a local counter that persists across ticks via closure
-        // For MVP, we'll do cleanup every tick (can be optimized later)
+
+        // Bot fill: bots keep small lobbies playable and make way as players join
+        if lobby.bot_fill > 0 {
+            let (added, removed) = lobbies::fill_bots(lobby, weapons, &mut next_id);
+            events.players_joined.extend(added);
+            events.players_left.extend(removed);
+        }
+
+        // Hold the slots of players who stopped sending, drop inactive players and spectators
+        let tunables = lobby.tunables.clone();
+        let (players_disconnected, expired) = lobbies::hold_disconnected(
+            lobby,
+            tunables.inactivity_timeout_secs,
+            tunables.reconnect_window_secs,
+            now,
+        );
+        events.players_left.extend(expired);
+        for player_id in &players_disconnected {
+            log::info!("Player {} disconnected from lobby {}, holding the slot", player_id, lobby_code);
+            self.visibility.forget(*player_id);
+            self.reliable.forget(*player_id);
+            self.snapshots.forget(*player_id);
+        }
+        events.players_disconnected = players_disconnected;
+        events.spectators_left.extend(lobbies::cleanup_spectators(lobby, tunables.inactivity_timeout_secs, now));
         let (removed, _warned) = lobbies::cleanup_inactive(
-            &mut lobby_guard,
-            config.player_inactivity_timeout_secs,
-            0.5, // Warn at 50% of timeout
+            lobby,
+            tunables.inactivity_timeout_secs,
+            tunables.inactivity_warning_fraction,
         );
-        if !removed.is_empty() {
-            for player_id in &removed {
-                players_left.push(*player_id);
+        events.players_left.extend(removed);
+        
+        // Players merged in from another lobby learn where they are now; the others see them join
+        events.arrivals = std::mem::take(&mut lobby.arrivals);
+        for (player_id, _) in &events.arrivals {
+            if let Some(player) = lobby.players.get(player_id) {
+                events.players_joined.push((*player_id, player.name.clone()));
             }
         }
+
+        // Verification challenges: clients that only implement part of the protocol stop answering
+        let (challenges, challenges_failed) = challenge::advance(lobby, now);
+        for player_id in &challenges_failed {
+            log::info!("Player {} missed a verification challenge in lobby {}", player_id, lobby_code);
+        }
+        events.challenges = challenges;
         
-        // 6. Broadcast player join/leave events
+        // Match flow: ready-up countdown, rounds and results
+        let previous_state = lobby.match_state.as_str();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.end_tick(lobby);
+        }
+        match matches::advance(lobby, weapons, now) {
+            Ok(match_events) => events.match_events.extend(match_events),
+            Err(e) => log::warn!("Match flow failed in lobby {}: {}", lobby_code, e),
+        }
+        for event in &events.match_events {
+            match event {
+                MatchEvent::RoundStart { round: 1 } => history::begin(lobby, now),
+                MatchEvent::MatchEnd { winner_id } => events.finished_matches.push(history::finish(lobby, *winner_id, now)),
+                _ => {}
+            }
+        }
+        events.countdown = countdown_packet(previous_state, lobby.match_state, now);
+        if events.match_events.iter().any(|e| matches!(e, MatchEvent::RoundStart { .. })) {
+            // Everyone was moved to a spawn point
+            events.respawns = lobby.players.keys().copied().collect();
+        }
+        events.mutator_events = mutators::advance(lobby, weapons, now);
+        events.rematch_offers = std::mem::take(&mut lobby.rematch_offers);
+        events.lobby_events = std::mem::take(&mut lobby.lobby_events);
+        if events.lobby_events.iter().any(|e| matches!(e, LobbyEvent::SceneChanged { .. })) {
+            // Everyone was moved to a spawn point on the new map
+            events.respawns = lobby.players.keys().copied().collect();
+        }
+
+        events.listing_changed = !events.players_joined.is_empty() || !events.players_left.is_empty()
+            || !events.lobby_events.is_empty() || previous_state != lobby.match_state.as_str();
+    }
+
+    /// Broadcast phase: tell the clients what the tick changed
+    fn broadcast(&mut self, lobby: &mut Lobby, events: &TickEvents, now: std::time::Instant) {
+        let server_state = self.server_state.as_deref();
+        let (outbound, reliable) = (&self.outbound, &mut self.reliable);
+        for (player_id, from) in &events.arrivals {
+            send_migrate_to(lobby, outbound, reliable, *player_id, from, &self.config, server_state);
+        }
+
+        // Player join/leave events
         log::debug!("Lobby {} has {} players and {} addresses", 
-            lobby_code, lobby_guard.players.len(), lobby_guard.client_addresses.len());
-        log::debug!("Players: {:?}", lobby_guard.players.keys().collect::<Vec<_>>());
-        log::debug!("Addresses: {:?}", lobby_guard.client_addresses.iter()
+            self.lobby_code, lobby.players.len(), lobby.client_addresses.len());
+        log::debug!("Players: {:?}", lobby.players.keys().collect::<Vec<_>>());
+        log::debug!("Addresses: {:?}", lobby.client_addresses.iter()
             .map(|(k, v)| (k, format!("{}", v)))
             .collect::<Vec<_>>());
         
-        if !players_joined.is_empty() {
-            log::debug!("Broadcasting player joins: {:?}", players_joined);
-            broadcast_player_join_events(&lobby_guard, &socket, &players_joined).await;
+        if !events.players_joined.is_empty() {
+            log::debug!("Broadcasting player joins: {:?}", events.players_joined);
+            broadcast_player_join_events(lobby, outbound, reliable, &events.players_joined, server_state);
         }
-        if !players_left.is_empty() {
-            log::debug!("Broadcasting player leaves: {:?}", players_left);
-            broadcast_player_leave_events(&lobby_guard, &socket, &players_left).await;
+        if !events.players_left.is_empty() {
+            log::debug!("Broadcasting player leaves: {:?}", events.players_left);
+            broadcast_player_leave_events(lobby, outbound, reliable, &events.players_left, server_state);
         }
-        
-        // 7. Broadcast position updates (every tick for players that moved)
-        if !position_updates.is_empty() {
-            // log::debug!("Broadcasting position updates for {} players: {:?}", position_updates.len(), position_updates);
-            broadcast_position_updates(&lobby_guard, &socket, &position_updates).await;
+        if !events.players_disconnected.is_empty() || !events.players_reconnected.is_empty() {
+            broadcast_connection_events(lobby, outbound, reliable, &events.players_disconnected, &events.players_reconnected, server_state);
         }
-        
-        // 8. Broadcast kill events
-        if !kill_events.is_empty() {
-            for kill_event in &kill_events {
-                broadcast_kill_event(&lobby_guard, &socket, kill_event).await;
+        for (player_id, issued) in &events.challenges {
+            send_challenge(lobby, outbound, reliable, *player_id, issued, now, server_state);
+        }
+
+        // Position updates (moved players, filtered by visibility)
+        for player_id in events.players_left.iter().chain(&events.spectators_left) {
+            self.visibility.forget(*player_id);
+            reliable.forget(*player_id);
+            self.snapshots.forget(*player_id);
+            if let Some(state) = server_state {
+                state.end_session(*player_id);
+                state.ip_sessions.release(*player_id);
             }
         }
+        broadcast_position_updates(
+            lobby,
+            outbound,
+            reliable,
+            &events.position_updates,
+            &mut self.visibility,
+            self.tick,
+            &self.config,
+            server_state,
+        );
         
-        // 9. Broadcast respawn events
-        if !respawn_events.is_empty() {
-            broadcast_respawn_events(&lobby_guard, &socket, &respawn_events).await;
+        // Shots (for effects), hits and deaths
+        let weapons = &self.weapons;
+        for shot in &events.shots {
+            broadcast_shot_event(lobby, outbound, reliable, weapons, shot, server_state);
+        }
+        for event in &events.damage {
+            broadcast_damage_event(lobby, outbound, reliable, event, server_state);
+        }
+        for event in &events.projectiles {
+            broadcast_projectile_event(lobby, outbound, reliable, event, server_state);
+        }
+        for event in &events.grenades {
+            broadcast_grenade_event(lobby, outbound, reliable, event, server_state);
+        }
+        for kill_event in &events.kills {
+            broadcast_death_event(lobby, outbound, reliable, kill_event, server_state);
+        }
+        for winner_id in &events.match_winners {
+            broadcast_match_won(lobby, outbound, reliable, *winner_id, server_state);
+        }
+        if let Some(packet) = &events.countdown {
+            broadcast_countdown(lobby, outbound, reliable, packet, server_state);
+        }
+        for event in &events.match_events {
+            broadcast_match_event(lobby, outbound, reliable, event, server_state);
+        }
+        for event in &events.mutator_events {
+            broadcast_mutator_event(lobby, outbound, reliable, event, server_state);
+        }
+        for event in &events.horde_events {
+            broadcast_horde_event(lobby, outbound, reliable, event, server_state);
+            if let HordeEvent::PlayerOut { player_id } = event {
+                send_spectate_offer(lobby, outbound, reliable, *player_id, server_state);
+            }
+        }
+        for event in &events.lobby_events {
+            broadcast_lobby_event(lobby, outbound, reliable, event, server_state);
+        }
+        for text in &events.announcements {
+            broadcast_announcement(lobby, outbound, reliable, text, server_state);
+        }
+        for grace_secs in &events.shutdown_notices {
+            broadcast_shutdown(lobby, outbound, reliable, *grace_secs, server_state);
+        }
+        for message in &events.chat_messages {
+            broadcast_chat_message(lobby, outbound, reliable, message, server_state);
+        }
+        for (player_id, reason) in &events.chat_rejected {
+            send_chat_rejected(lobby, outbound, reliable, *player_id, reason, server_state);
+        }
+        for (speaker_id, payload, listeners) in &events.voice_frames {
+            relay_voice(lobby, outbound, reliable, *speaker_id, payload, listeners, server_state);
+        }
+        if !events.rematch_offers.is_empty() {
+            send_rematch_offers(lobby, outbound, reliable, &events.rematch_offers, server_state);
         }
         
-        // 10. Delta sync - only send changes (health, ammo, weapon, reload)
-        let state_events = delta_sync::collect_dirty_events(&mut lobby_guard);
+        // Respawn events
+        if !events.respawns.is_empty() {
+            broadcast_respawn_events(lobby, outbound, reliable, &events.respawns, server_state);
+        }
         
-        // 11. Broadcast state events (reuse buffer)
+        // Delta sync - only send changes (health, ammo, weapon, reload), reusing the buffer
+        let state_events = delta_sync::collect_dirty_events(lobby);
         if !state_events.is_empty() {
-            broadcast_state_events(&lobby_guard, &socket, &state_events, &mut send_buffer).await;
+            broadcast_state_events(lobby, outbound, reliable, &state_events, &mut self.send_buffer, server_state);
         }
         
-        // 12. Record stats to global stats and clear dirty flags
-        if let Some(ref state) = server_state {
-            for player_id in &players_left {
-                if let Some(player) = lobby_guard.players.get(player_id) {
+        // Snapshot clients get state as a delta against their last acked snapshot
+        broadcast_snapshots(lobby, outbound, reliable, &mut self.snapshots, self.tick, server_state);
+        
+        // Resend reliable events the clients have not acked yet
+        for (player_id, data) in reliable.due_resends() {
+            if let Some(addr) = lobby.client_addresses.get(&player_id) {
+                send_to_client(outbound, lobby, *addr, &data);
+            }
+        }
+    }
+
+    /// Listing phase: record stats and finished matches, then publish the lobby's browser listing
+    /// Returns whether the lobby is idle (no client connected)
+    fn finish_tick(&mut self, lobby: &mut Lobby, events: TickEvents, now: std::time::Instant, tick_started: std::time::Instant) -> bool {
+        if let Some(ref state) = self.server_state {
+            for player_id in &events.players_left {
+                if let Some(player) = lobby.players.get(player_id) {
                     state.global_stats.record_session(
                         player.id,
                         &player.name,
@@ -194,18 +733,42 @@ pub async fn lobby_tick_loop(
                     );
                 }
             }
+            // Finished matches go to match history and count towards the accounts that played them (written off the tick)
+            if let Some(storage) = state.storage() {
+                for record in events.finished_matches {
+                    let results = matches::account_results(lobby, record.winner_id);
+                    let storage = storage.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = storage.record_match(&record, &results).await {
+                            log::warn!("Failed to record match {}: {}", record.match_id, e);
+                        }
+                    });
+                }
+            }
         }
         
-        lobby_guard.clear_dirty();
+        lobby.clear_dirty();
+        if lobby.players.len() > lobby.bots.len() {
+            lobby.analytics.last_occupied = now;
+        }
+        lobby.analytics.record_tick(tick_started.elapsed());
+
+        // Publish the browser listing HTTP reads are served from, before HTTP handlers waiting on
+        // the lock can publish theirs
+        if let Some(ref state) = self.server_state {
+            state.publish_listing(&self.lobby_code, LobbyListing::of(lobby), events.listing_changed);
+        }
+        lobby.client_addresses.is_empty()
     }
 }
 
 /// Process a single command
-fn process_command(
+pub(crate) fn process_command(
     lobby: &mut Lobby,
     weapons: &WeaponDb,
     cmd: LobbyCommand,
     server_state: Option<&ServerState>,
+    now: std::time::Instant,
 ) {
     match cmd {
         LobbyCommand::PlayerJoin { player_id, name, addr } => {
@@ -221,17 +784,33 @@ fn process_command(
                 state.register_player_lobby(player_id, &lobby.code);
             }
         }
-        LobbyCommand::PlayerLeave { player_id } => {
+        LobbyCommand::PlayerLeave { player_id } | LobbyCommand::Kick { player_id, .. } => {
+            let addr = lobby.client_addresses.get(&player_id).copied();
             lobbies::remove_player(lobby, player_id);
             if let Some(state) = server_state {
                 state.unregister_player(player_id);
+                if let Some(addr) = addr {
+                    state.forget_client(&addr);
+                }
+            }
+        }
+        LobbyCommand::Reconnect { player_id, addr } => {
+            match lobbies::reconnect(lobby, player_id, addr, now) {
+                Ok(()) => log::info!("Player {} reconnected to lobby {} from {}", player_id, lobby.code, addr),
+                Err(e) => log::debug!("Reconnect failed for player {}: {}", player_id, e),
             }
         }
         LobbyCommand::UdpConnect { player_id, name: _, addr } => {
-            if lobby.players.contains_key(&player_id) {
+            if lobbies::touch_spectator(lobby, player_id, addr, now) {
+                if let Some(state) = server_state {
+                    state.register_player_lobby(player_id, &lobby.code);
+                }
+                let name = lobby.spectators.get(&player_id).map(|s| s.name.as_str()).unwrap_or_default();
+                log::debug!("Spectator {} ({}) UDP connected from {}", player_id, name, addr);
+            } else if lobby.players.contains_key(&player_id) {
                 lobby.client_addresses.insert(player_id, addr);
                 if let Some(player) = lobby.players.get_mut(&player_id) {
-                    player.last_update = std::time::SystemTime::now();
+                    player.last_update = now;
                 }
                 if let Some(state) = server_state {
                     state.register_player_lobby(player_id, &lobby.code);
@@ -249,71 +828,272 @@ fn process_command(
             }
             if let Err(e) = lobbies::update_position(lobby, player_id, position, rotation) {
                 log::debug!("Position update failed for player {}: {}", player_id, e);
+                return;
             }
+            anticheat::observe_aim(lobby, player_id, now);
         }
-        LobbyCommand::Shoot { player_id, target_id } => {
-            match logic::try_shoot(lobby, weapons, player_id) {
+        LobbyCommand::Shoot { player_id, origin, direction, client_time } => {
+            if lobby.frozen {
+                log::debug!("Player {} can't shoot while the lobby is frozen", player_id);
+                return;
+            }
+            // The shot's direction is the freshest aim the client reported
+            let aim = simulator::normalize(direction).and_then(|d| simulator::yaw_pitch_towards((0.0, 0.0, 0.0), d));
+            if let (Some((yaw, pitch)), Some(player)) = (aim, lobby.players.get_mut(&player_id)) {
+                player.rotation = Player::rotation_from_yaw_pitch(yaw, pitch);
+            }
+            match logic::try_shoot_at(lobby, weapons, player_id, now) {
                 Ok(can_shoot) => {
                     if can_shoot {
-                        // Get weapon damage
-                        if let Some(player) = lobby.players.get(&player_id) {
-                            if let Some(weapon) = weapons.get(player.current_weapon_id) {
-                                let _ = logic::apply_damage(lobby, target_id, weapon.damage);
+                        if let Some(kind) = anticheat::observe_shot(lobby, weapons, player_id, now) {
+                            if let Some(violations) = lobby.players.get(&player_id).map(|p| &p.violations) {
+                                log::warn!("Player {} flagged for {} ({} times, {} violations total)",
+                                    player_id, kind.as_str(), violations.count(kind), violations.total());
+                            }
+                        }
+                        let fires_projectile = lobby.players.get(&player_id)
+                            .and_then(|p| weapons.get(p.current_weapon_id))
+                            .is_some_and(|w| w.projectile.is_some());
+                        if fires_projectile {
+                            // Simulated from here on by the tick loop
+                            if let Err(e) = projectiles::spawn(lobby, weapons, player_id, origin, direction, now) {
+                                log::debug!("Projectile from player {} not launched: {}", player_id, e);
+                            }
+                            return;
+                        }
+                        // Shot was fired - trace it against everyone where they were when it was fired
+                        let rewind_to = rewind_time(lobby, player_id, client_time, now);
+                        let hit = match logic::hitscan_at(lobby, weapons, player_id, origin, direction, rewind_to) {
+                            Ok(hit) => hit,
+                            Err(e) => {
+                                log::debug!("Hitscan failed for player {}: {}", player_id, e);
+                                None
+                            }
+                        };
+                        let Some(simulator::HitResult { player_id: target_id, distance, zone }) = hit else {
+                            anticheat::observe_aim(lobby, player_id, now);
+                            logic::record_shot(lobby, player_id, None, false);
+                            return;
+                        };
+                        // The hit is checked against the FOV and aim assist the shooter attested
+                        let positions = lobby.players.get(&player_id).zip(lobby.players.get(&target_id)).map(|(shooter, target)| {
+                            let target_position = rewind_to
+                                .and_then(|time| lobby.history.position_at(target_id, time))
+                                .unwrap_or(target.position);
+                            (logic::shot_origin(lobby, shooter.position, origin), target_position)
+                        });
+                        if let Some((shot_from, target_position)) = positions {
+                            if let Some(kind) = anticheat::observe_hit(lobby, player_id, shot_from, target_position, now) {
+                                log::warn!("Player {} flagged for {} on a hit against player {}", player_id, kind.as_str(), target_id);
                             }
                         }
+                        let allowed = teams::allows_damage(lobby, player_id, target_id);
+                        logic::record_shot(lobby, player_id, Some(target_id), allowed);
+                        if !allowed {
+                            log::debug!("Hit from player {} on teammate {} blocked: friendly fire is off", player_id, target_id);
+                            return;
+                        }
+                        // Kills land in the lobby's kill feed and are broadcast this tick
+                        if let Err(e) = logic::resolve_hit_at(lobby, weapons, player_id, target_id, zone, distance, now) {
+                            log::debug!("Hit from player {} on {} not applied: {}", player_id, target_id, e);
+                        }
                     }
                 }
                 Err(e) => log::debug!("Shoot failed for player {}: {}", player_id, e),
             }
         }
         LobbyCommand::Reload { player_id } => {
-            if let Err(e) = logic::start_reload(lobby, weapons, player_id) {
+            if lobby.frozen {
+                log::debug!("Player {} can't reload while the lobby is frozen", player_id);
+                return;
+            }
+            if let Err(e) = logic::start_reload_at(lobby, weapons, player_id, now) {
                 log::debug!("Reload failed for player {}: {}", player_id, e);
             }
         }
+        LobbyCommand::Throw { player_id, origin, direction } => {
+            if lobby.frozen {
+                log::debug!("Player {} can't throw while the lobby is frozen", player_id);
+                return;
+            }
+            // Simulated from here on by the tick loop
+            if let Err(e) = grenades::throw(lobby, player_id, origin, direction, now) {
+                log::debug!("Grenade from player {} not thrown: {}", player_id, e);
+            }
+        }
         LobbyCommand::WeaponSwitch { player_id, weapon_id } => {
+            if let GameMode::GunGame(game) = &lobby.mode {
+                if !game.allows_weapon(weapons, player_id, weapon_id) {
+                    log::debug!("Player {} can't switch to weapon {} in gun game", player_id, weapon_id);
+                    return;
+                }
+            }
+            if mutators::locks_weapons(lobby) {
+                log::debug!("Player {} can't switch weapons during weapon roulette", player_id);
+                return;
+            }
+            if lobby.frozen {
+                log::debug!("Player {} can't switch weapons while the lobby is frozen", player_id);
+                return;
+            }
             if let Err(e) = logic::switch_weapon(lobby, weapons, player_id, weapon_id) {
                 log::debug!("Weapon switch failed for player {}: {}", player_id, e);
             }
         }
+        LobbyCommand::LoadingComplete { player_id } => {
+            lobbies::finish_loading(lobby, player_id);
+        }
+        LobbyCommand::SwitchTeam { player_id, team_id } => {
+            if let Err(e) = teams::switch_team(lobby, player_id, team_id) {
+                log::debug!("Team switch failed for player {}: {}", player_id, e);
+            }
+        }
+        LobbyCommand::ChallengeResponse { player_id, nonce, signature } => {
+            if let Err(e) = challenge::answer(lobby, player_id, &nonce, &signature, now) {
+                log::info!("Player {} failed a verification challenge: {}", player_id, e);
+            }
+        }
+        LobbyCommand::GrantWeapon { player_id, weapon_id } => {
+            match logic::grant_weapon(lobby, weapons, player_id, weapon_id) {
+                Ok(()) => log::info!("Player {} was granted weapon {}", player_id, weapon_id),
+                Err(e) => log::debug!("Weapon grant failed for player {}: {}", player_id, e),
+            }
+        }
+        LobbyCommand::SetTimeScale { time_scale } => {
+            match lobbies::set_time_scale(lobby, time_scale, now) {
+                Ok(()) => log::info!("Lobby {} time scale set to {}", lobby.code, time_scale),
+                Err(e) => log::debug!("Time scale change refused in lobby {}: {}", lobby.code, e),
+            }
+        }
+        LobbyCommand::Ready { player_id, ready } => {
+            let was_ready = lobby.players.get(&player_id).is_some_and(|p| p.ready);
+            match matches::set_ready(lobby, player_id, ready) {
+                Ok(()) if was_ready != ready => lobby.lobby_events.push(LobbyEvent::ReadyChanged { player_id, ready }),
+                Ok(()) => {}
+                Err(e) => log::debug!("Ready change failed for player {}: {}", player_id, e),
+            }
+        }
+        LobbyCommand::ClockSample { player_id, client_ms, server_ms } => {
+            if let Some(player) = lobby.players.get_mut(&player_id) {
+                if let Err(e) = player.clock.observe(client_ms, server_ms) {
+                    player.violations.record(ViolationKind::ClockManipulation, now);
+                    log::warn!("Player {} clock violation #{}: {}", player_id, player.clock.violations, e);
+                }
+            }
+        }
         LobbyCommand::Heartbeat { player_id, addr } => {
+            if lobbies::touch_spectator(lobby, player_id, addr, now) {
+                return;
+            }
             // Update client address (ensures HTTP-joined players get their UDP address tracked)
             if lobby.players.contains_key(&player_id) {
                 lobby.client_addresses.insert(player_id, addr);
             }
             // Update last_update timestamp
             if let Some(player) = lobby.players.get_mut(&player_id) {
-                player.last_update = std::time::SystemTime::now();
+                player.last_update = now;
+            }
+        }
+        LobbyCommand::Ack { .. } => {
+            // Acks are handled by the tick loop's reliable sender
+        }
+        LobbyCommand::MutePlayer { player_id, target_id, muted } => {
+            if let Err(e) = voice::set_muted(lobby, player_id, target_id, muted) {
+                log::debug!("Mute by player {} failed: {}", player_id, e);
             }
         }
+        LobbyCommand::Announce { .. } | LobbyCommand::Shutdown { .. } | LobbyCommand::OwnerAction { .. } | LobbyCommand::Referee { .. } | LobbyCommand::Chat { .. } | LobbyCommand::Voice { .. } => {
+            // Handled by the tick loop
+        }
+    }
+}
+
+/// Server time a shot's `client_time` maps to, clamped to the lobby's `max_rewind`
+/// None (no rewind) without a timestamp or before the shooter's clock is estimated
+fn rewind_time(lobby: &Lobby, player_id: u32, client_time: Option<u64>, now: std::time::Instant) -> Option<std::time::Instant> {
+    let fired_at = lobby.players.get(&player_id)?.clock.to_server_time(client_time?)?;
+    let earliest = now.checked_sub(lobby.max_rewind.min(rewind::HISTORY_WINDOW)).unwrap_or(now);
+    Some(fired_at.clamp(earliest, now))
+}
+
+/// Queue a datagram for one client; the lobby's sender task sends it after the tick
+/// Counts the bytes towards the lobby's analytics
+fn send_to_client(outbound: &Outbound, lobby: &Lobby, addr: std::net::SocketAddr, data: &[u8]) {
+    outbound.push(addr, data.to_vec());
+    lobby.analytics.record_bytes_sent(data.len());
+}
+
+/// Send a JSON event to one client stamped with its next sequence number
+/// Reliable events are resent until acked, for clients that negotiated `CAP_RELIABLE`
+/// Clients with `CAP_CHANNELS` also get the packet's channel and that channel's next number
+#[allow(clippy::too_many_arguments)]
+fn send_event(
+    outbound: &Outbound,
+    lobby: &Lobby,
+    server_state: Option<&ServerState>,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    addr: std::net::SocketAddr,
+    packet: &serde_json::Value,
+    is_reliable: bool,
+) {
+    let packet = SerializedPacket::new(packet);
+    send_serialized_event(outbound, lobby, server_state, reliable, player_id, addr, &packet, is_reliable);
+}
+
+/// `send_event` for a packet going to several clients, serialized once beforehand
+#[allow(clippy::too_many_arguments)]
+fn send_serialized_event(
+    outbound: &Outbound,
+    lobby: &Lobby,
+    server_state: Option<&ServerState>,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    addr: std::net::SocketAddr,
+    packet: &SerializedPacket,
+    is_reliable: bool,
+) {
+    let protocol = server_state.map(|s| s.client_protocol(&addr)).unwrap_or_default();
+    if let Some(data) = reliable.stamp(player_id, packet, is_reliable && protocol.has(CAP_RELIABLE), protocol.has(CAP_CHANNELS)) {
+        send_to_client(outbound, lobby, addr, &data);
     }
 }
 
 /// Send welcome message to joining player with current lobby state
-async fn send_welcome_message(
+fn send_welcome_message(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     player_id: u32,
     addr: std::net::SocketAddr,
+    server_state: Option<&ServerState>,
 ) {
     // Send welcome message
-    let welcome_packet = json!({
+    let mut welcome_packet = json!({
         "type": "welcome",
-        "message": "Connected to lobby",
         "player_id": player_id,
-        "scene_load": true
+        "scene_load": true,
+        "time_scale": lobby.time_scale
     });
+    let message_text = server_state.map(|s| s.message_text()).unwrap_or(false);
+    messages::insert_message(&mut welcome_packet, MessageCode::Connected, json!({"lobby_code": lobby.code}), message_text);
 
-    if let Ok(data) = serde_json::to_vec(&welcome_packet) {
-        let _ = socket.send_to(&data, addr).await;
-    }
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &welcome_packet, true);
 
     // Send current player list to joining player
+    let players_packet = player_list_packet(lobby, player_id, server_state);
+
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &players_packet, true);
+}
+
+/// Everyone else in the lobby with where they are (sent on join, UDP connect and reconnect)
+fn player_list_packet(lobby: &Lobby, player_id: u32, server_state: Option<&ServerState>) -> serde_json::Value {
     let mut player_list = Vec::new();
     for player in lobby.players.values() {
-        if player.id != player_id {
+        if player.id != player_id && !player.is_loading() {
             player_list.push(json!({
                 "id": player.id,
+                "uuid": public_uuid(server_state, player.id),
                 "name": player.name,
                 "position": {
                     "x": player.position.0,
@@ -324,236 +1104,1023 @@ async fn send_welcome_message(
                     "x": player.rotation.0,
                     "y": player.rotation.1,
                     "z": player.rotation.2
-                }
+                },
+                "yaw": player.yaw(),
+                "pitch": player.pitch()
             }));
         }
     }
 
-    let players_packet = json!({
+    json!({
         "type": "player_list",
         "players": player_list,
         "notification": true
-    });
-
-    if let Ok(data) = serde_json::to_vec(&players_packet) {
-        let _ = socket.send_to(&data, addr).await;
-    }
+    })
 }
 
-/// Send UDP connection acknowledgment without scene info
-/// Used when player reconnects via UDP after HTTP join
-async fn send_udp_connected_message(
+/// Restore a reconnected client: its own state, the match and scores, then everyone's positions
+fn send_reconnect_state(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     player_id: u32,
     addr: std::net::SocketAddr,
+    server_state: Option<&ServerState>,
 ) {
-    let ack_packet = json!({
-        "type": "udp_connected",
+    let Some(player) = lobby.players.get(&player_id) else {
+        return;
+    };
+    let packet = json!({
+        "type": "reconnected",
         "player_id": player_id,
         "lobby_code": lobby.code,
-        "notification": true
+        "scene": lobby.scene,
+        "match_state": lobby.match_state.as_str(),
+        "round": lobby.match_state.round(),
+        "time_scale": lobby.time_scale,
+        "state": player.to_sync_state(),
+        "position": {"x": player.position.0, "y": player.position.1, "z": player.position.2},
+        "is_dead": player.is_dead,
+        "scores": scoreboard(lobby)
     });
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &packet, true);
 
-    if let Ok(data) = serde_json::to_vec(&ack_packet) {
-        let _ = socket.send_to(&data, addr).await;
-    }
+    let players_packet = player_list_packet(lobby, player_id, server_state);
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &players_packet, true);
+}
 
-    let mut player_list = Vec::new();
-    for player in lobby.players.values() {
-        if player.id != player_id {
-            player_list.push(json!({
-                "id": player.id,
-                "name": player.name,
-                "position": {
-                    "x": player.position.0,
-                    "y": player.position.1,
-                    "z": player.position.2
-                },
-                "rotation": {
-                    "x": player.rotation.0,
-                    "y": player.rotation.1,
-                    "z": player.rotation.2
-                }
-            }));
+/// Tell the others who dropped (their slot is held) and who came back
+fn broadcast_connection_events(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    disconnected: &[u32],
+    reconnected: &[u32],
+    server_state: Option<&ServerState>,
+) {
+    let packets = disconnected
+        .iter()
+        .map(|id| (*id, json!({"type": "player_disconnected", "player_id": id, "reconnect_window_secs": lobby.tunables.reconnect_window_secs})))
+        .chain(reconnected.iter().map(|id| (*id, json!({"type": "player_reconnected", "player_id": id}))));
+    for (subject_id, packet) in packets {
+        let packet = SerializedPacket::new(&packet);
+        for (player_id, addr) in &lobby.client_addresses {
+            if *player_id == subject_id {
+                continue;
+            }
+            send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
         }
     }
+}
 
-    let players_packet = json!({
-        "type": "player_list",
-        "players": player_list,
-        "notification": true
+/// Send UDP connection acknowledgment without scene info
+/// Used when player reconnects via UDP after HTTP join
+fn send_udp_connected_message(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    addr: std::net::SocketAddr,
+    server_state: Option<&ServerState>,
+) {
+    // Spectators may have come in through the public spectate code; never hand them the join code
+    let spectator = lobbies::is_spectator(lobby, player_id);
+    let ack_packet = json!({
+        "type": "udp_connected",
+        "player_id": player_id,
+        "lobby_code": if spectator { &lobby.spectate_code } else { &lobby.code },
+        "notification": true,
+        "spectator": spectator,
+        "time_scale": lobby.time_scale
     });
 
-    if let Ok(data) = serde_json::to_vec(&players_packet) {
-        let _ = socket.send_to(&data, addr).await;
-    }
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &ack_packet, true);
+
+    let players_packet = player_list_packet(lobby, player_id, server_state);
+
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &players_packet, true);
+}
+
+/// Tell a player merged in from another lobby where the match continues, then who is here
+/// Their player carried over, so the client keeps its state and only swaps lobby details
+fn send_migrate_to(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    from: &str,
+    config: &Config,
+    server_state: Option<&ServerState>,
+) {
+    let Some(addr) = lobby.client_addresses.get(&player_id).copied() else {
+        return;
+    };
+    let spectator = lobbies::is_spectator(lobby, player_id);
+    let packet = json!({
+        "type": "migrate_to",
+        "from": from,
+        "lobby_code": if spectator { &lobby.spectate_code } else { &lobby.code },
+        "scene": lobby.scene,
+        "mode": lobby.mode.as_str(),
+        "server_ip": "127.0.0.1",
+        "udp_port": lobby.udp_port.unwrap_or(config.udp_port),
+        "spectator": spectator,
+        "time_scale": lobby.time_scale
+    });
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &packet, true);
+
+    let players_packet = player_list_packet(lobby, player_id, server_state);
+    send_event(outbound, lobby, server_state, reliable, player_id, addr, &players_packet, true);
+}
+
+/// Public UUID of a player (None for bots and players that joined without HTTP)
+fn public_uuid(server_state: Option<&ServerState>, player_id: u32) -> Option<String> {
+    server_state?.player_directory.uuid(player_id).map(|uuid| uuid.to_string())
 }
 
 /// Broadcast player join events to all clients
-async fn broadcast_player_join_events(
+fn broadcast_player_join_events(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     players: &[(u32, String)],
+    server_state: Option<&ServerState>,
 ) {
     for (player_id, name) in players {
         log::debug!("Sending player_joined to others for player {} ({})", player_id, name);
         
-        let packet = json!({
+        let packet = SerializedPacket::new(&json!({
             "type": "player_joined",
             "player": {
                 "id": player_id,
+                "uuid": public_uuid(server_state, *player_id),
                 "name": name
             },
             "notification": true
-        });
+        }));
 
-        if let Ok(data) = serde_json::to_vec(&packet) {
-            // Send to all clients except the joining player
-            let recipients: Vec<(u32, std::net::SocketAddr)> = lobby.client_addresses.iter()
-                .filter(|(cid, _)| **cid != *player_id)
-                .map(|(cid, addr)| (*cid, *addr))
-                .collect();
-            
-            log::debug!("Sending to {} recipients: {:?}", recipients.len(), recipients);
-            
-            for (client_id, addr) in recipients {
-                log::debug!("Sending player_joined to client {} at {}", client_id, addr);
-                if let Err(e) = socket.send_to(&data, addr).await {
-                    log::debug!("Failed to send join event to {} ({}): {:?}", client_id, addr, e);
-                } else {
-                    log::debug!("Successfully sent player_joined to client {} at {}", client_id, addr);
-                }
-            }
+        // Send to all clients except the joining player
+        let recipients: Vec<(u32, std::net::SocketAddr)> = lobby.client_addresses.iter()
+            .filter(|(cid, _)| **cid != *player_id)
+            .map(|(cid, addr)| (*cid, *addr))
+            .collect();
+        
+        log::debug!("Sending to {} recipients: {:?}", recipients.len(), recipients);
+        
+        for (client_id, addr) in recipients {
+            log::debug!("Sending player_joined to client {} at {}", client_id, addr);
+            send_serialized_event(outbound, lobby, server_state, reliable, client_id, addr, &packet, true);
         }
     }
 }
 
 /// Broadcast player leave events to all clients
-async fn broadcast_player_leave_events(
+fn broadcast_player_leave_events(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     player_ids: &[u32],
+    server_state: Option<&ServerState>,
 ) {
     for player_id in player_ids {
-        let packet = json!({
+        let packet = SerializedPacket::new(&json!({
             "type": "player_left",
             "player_id": player_id
-        });
+        }));
+
+        // Send to all remaining clients
+        for (client_id, addr) in &lobby.client_addresses {
+            send_serialized_event(outbound, lobby, server_state, reliable, *client_id, *addr, &packet, true);
+        }
+    }
+}
 
-        if let Ok(data) = serde_json::to_vec(&packet) {
-            // Send to all remaining clients
-            for (_client_id, addr) in &lobby.client_addresses {
-                if let Err(e) = socket.send_to(&data, *addr).await {
-                    log::debug!("Failed to send leave event to {}: {:?}", addr, e);
+/// One player's entry in a `positions` packet, in the format a client negotiated
+#[derive(Debug, Clone)]
+enum PositionEntry {
+    Json(Vec<u8>), // Serialized object, without the packet's `type` and `tick`
+    Binary(BinaryPacket),
+}
+
+/// Encode a player's position entry in the format a client negotiated
+/// 2D lobbies send x/z and yaw only, whatever the client negotiated
+fn encode_position_entry(player_id: u32, player: &Player, protocol: ClientProtocol, dimensions: Dimensions) -> Option<PositionEntry> {
+    if dimensions == Dimensions::Two {
+        return if protocol.has(CAP_BINARY) {
+            Some(PositionEntry::Binary(BinaryPacket::position_update_2d(player_id, player.position, player.yaw())))
+        } else {
+            serde_json::to_vec(&json!({
+                "player_id": player_id,
+                "position": {"x": player.position.0, "z": player.position.2},
+                "yaw": player.yaw()
+            })).ok().map(PositionEntry::Json)
+        };
+    }
+
+    let position = json!({
+        "x": player.position.0,
+        "y": player.position.1,
+        "z": player.position.2
+    });
+
+    match (protocol.has(CAP_BINARY), protocol.uses_yaw_pitch()) {
+        (true, true) => Some(PositionEntry::Binary(BinaryPacket::position_update_yaw_pitch(
+            player_id,
+            player.position,
+            player.yaw(),
+            player.pitch(),
+        ))),
+        (true, false) => Some(PositionEntry::Binary(BinaryPacket::position_update(
+            player_id,
+            player.position,
+            player.rotation,
+        ))),
+        (false, true) => serde_json::to_vec(&json!({
+            "player_id": player_id,
+            "position": position,
+            "yaw": player.yaw(),
+            "pitch": player.pitch()
+        })).ok().map(PositionEntry::Json),
+        (false, false) => serde_json::to_vec(&json!({
+            "player_id": player_id,
+            "position": position,
+            "rotation": {
+                "x": player.rotation.0,
+                "y": player.rotation.1,
+                "z": player.rotation.2
+            }
+        })).ok().map(PositionEntry::Json),
+    }
+}
+
+/// Coalesce one client's position entries into `positions` packets of up to `MAX_BATCH_BYTES` each
+/// (one packet unless the lobby is big); JSON packets carry the server tick they were taken at
+fn encode_positions(entries: &[&PositionEntry], tick: u64) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    let mut json_packet: Vec<u8> = Vec::new();
+    let mut binary_updates: Vec<BinaryPacket> = Vec::new();
+    let mut binary_size = 0;
+    let json_prefix = format!(r#"{{"type":"positions","tick":{},"players":["#, tick);
+    let binary_overhead = 1 + 4 + 8; // Magic byte, variant and list length
+    for entry in entries {
+        match entry {
+            PositionEntry::Json(data) => {
+                if !json_packet.is_empty() && json_packet.len() + 1 + data.len() + 2 > outbound::MAX_BATCH_BYTES {
+                    json_packet.extend_from_slice(b"]}");
+                    packets.push(std::mem::take(&mut json_packet));
+                }
+                if json_packet.is_empty() {
+                    json_packet.extend_from_slice(json_prefix.as_bytes());
+                } else {
+                    json_packet.push(b',');
                 }
+                json_packet.extend_from_slice(data);
+            }
+            PositionEntry::Binary(update) => {
+                let size = bincode::serialized_size(update).unwrap_or_default() as usize;
+                if !binary_updates.is_empty() && binary_overhead + binary_size + size > outbound::MAX_BATCH_BYTES {
+                    packets.extend(binary::encode(&BinaryPacket::Positions(std::mem::take(&mut binary_updates))).ok());
+                    binary_size = 0;
+                }
+                binary_size += size;
+                binary_updates.push(update.clone());
             }
         }
     }
+    if !json_packet.is_empty() {
+        json_packet.extend_from_slice(b"]}");
+        packets.push(json_packet);
+    }
+    if !binary_updates.is_empty() {
+        packets.extend(binary::encode(&BinaryPacket::Positions(binary_updates)).ok());
+    }
+    packets
 }
 
 /// Broadcast position updates for players that moved
-async fn broadcast_position_updates(
+/// Each observer only gets targets within its relevance radius (the rest at the far rate)
+/// that the visibility tracker allows (occluded ones at a low rate),
+/// in the encoding it negotiated (JSON/binary, Euler/yaw-pitch)
+/// Reduced-rate clients get every allowed position only once per `reduced_rate_interval_ticks`
+/// Spectators and eliminated players get everyone's position, unculled
+/// Each observer's updates are coalesced into `positions` packets (see `encode_positions`)
+/// Clients with `CAP_CHANNELS` get each packet stamped with their movement channel number
+#[allow(clippy::too_many_arguments)]
+fn broadcast_position_updates(
     lobby: &Lobby,
-    socket: &UdpSocket,
-    player_ids: &[u32],
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    moved_ids: &[u32],
+    visibility: &mut VisibilityTracker,
+    tick: u64,
+    config: &Config,
+    server_state: Option<&ServerState>,
 ) {
-    for player_id in player_ids {
-        if let Some(player) = lobby.players.get(player_id) {
-            // log::debug!("Broadcasting position for player {}: ({}, {}, {})", 
-            //     player_id, player.position.0, player.position.1, player.position.2);
-            
-            let packet = json!({
-                "type": "position_update",
-                "player_id": player_id,
-                "position": {
-                    "x": player.position.0,
-                    "y": player.position.1,
-                    "z": player.position.2
-                },
-                "rotation": {
-                    "x": player.rotation.0,
-                    "y": player.rotation.1,
-                    "z": player.rotation.2
-                }
-            });
+    // Encode entries lazily, once per (target, protocol variant) in use
+    let mut encoded: Vec<(u32, ClientProtocol, PositionEntry)> = Vec::new();
+    let grid = RelevanceGrid::build(lobby, config.relevance_radius);
+    let far_due: Vec<u32> = lobby
+        .players
+        .keys()
+        .copied()
+        .filter(|id| relevance::far_update_due(*id, tick, config.far_update_interval_ticks))
+        .collect();
 
-            if let Ok(data) = serde_json::to_vec(&packet) {
-                // Send to all clients except the moving player
-                let recipients: Vec<(u32, std::net::SocketAddr)> = lobby.client_addresses.iter()
-                    .filter(|(cid, _)| **cid != *player_id)
-                    .map(|(cid, addr)| (*cid, *addr))
-                    .collect();
-                
-                // log::debug!("Sending position update to {} recipients: {:?}", recipients.len(), recipients);
-                
-            for (client_id, addr) in recipients {
-                // log::debug!("Sending position update to client {} at {}", client_id, addr);
-                if let Err(e) = socket.send_to(&data, addr).await {
-                    // log::debug!("Failed to send position update to {} ({}): {:?}", client_id, addr, e);
-                } else {
-                    // log::debug!("Successfully sent position update to client {} at {}", client_id, addr);
+    for (client_id, addr) in &lobby.client_addresses {
+        let protocol = server_state.map(|s| s.client_protocol(addr)).unwrap_or_default();
+        let reduced_rate = protocol.has(CAP_REDUCED_RATE);
+        if reduced_rate && !tick.is_multiple_of(config.reduced_rate_interval_ticks.max(1)) {
+            continue;
+        }
+
+        let mut targets: Vec<&Player> = Vec::new();
+        if lobbies::is_spectating(lobby, *client_id) {
+            // Spectators follow anyone: everyone who moved, the rest at the far rate, nothing culled
+            targets.extend(lobby.players.values().filter(|t| {
+                t.id != *client_id && (reduced_rate || moved_ids.contains(&t.id) || far_due.contains(&t.id))
+            }));
+        } else if let Some(observer) = lobby.players.get(client_id) {
+            let near = grid.near(observer.position);
+            let far = far_due.iter().filter(|id| !near.contains(id));
+            for (target_id, is_near) in near.iter().map(|id| (id, true)).chain(far.map(|id| (id, false))) {
+                let Some(target) = lobby.players.get(target_id).filter(|t| t.id != *client_id) else {
+                    continue;
+                };
+                // Reduced-rate clients may have missed moves since their last slot, and far targets
+                // since their last far update: resend current state
+                let moved = reduced_rate || !is_near || moved_ids.contains(&target.id);
+                if visibility.should_send(lobby, observer, target, moved, tick) {
+                    targets.push(target);
                 }
             }
-            }
         }
+
+        let mut indices = Vec::with_capacity(targets.len());
+        for target in targets {
+            let index = match encoded.iter().position(|(id, p, _)| *id == target.id && *p == protocol) {
+                Some(index) => index,
+                None => match encode_position_entry(target.id, target, protocol, lobby.dimensions) {
+                    Some(entry) => {
+                        encoded.push((target.id, protocol, entry));
+                        encoded.len() - 1
+                    }
+                    None => continue,
+                },
+            };
+            indices.push(index);
+        }
+        let entries: Vec<&PositionEntry> = indices.iter().map(|index| &encoded[*index].2).collect();
+        for packet in encode_positions(&entries, tick) {
+            let sequenced = if protocol.has(CAP_CHANNELS) { reliable.stamp_movement(*client_id, &packet) } else { None };
+            send_to_client(outbound, lobby, *addr, sequenced.as_deref().unwrap_or(&packet));
+        }
+    }
+}
+
+/// Shot effects packet, with the weapon's cosmetics from the weapon config
+fn shot_packet(weapons: &WeaponDb, shot: &logic::ShotEvent) -> serde_json::Value {
+    let cosmetics = weapons.get(shot.weapon_id).map(|w| w.cosmetics.clone()).unwrap_or_default();
+    json!({
+        "type": "shot_fired",
+        "player_id": shot.shooter_id,
+        "target_id": shot.target_id,
+        "weapon_id": shot.weapon_id,
+        "hit": shot.hit,
+        "muzzle_flash_id": cosmetics.muzzle_flash_id,
+        "sound_id": cosmetics.sound_id,
+        "tracer": cosmetics.tracer
+    })
+}
+
+/// Hit packet: the damage dealt and the weapon multiplier for where and how far away the target was hit
+fn damage_packet(event: &logic::DamageEvent) -> serde_json::Value {
+    json!({
+        "type": "player_damaged",
+        "player_id": event.victim_id,
+        "attacker_id": event.attacker_id,
+        "damage": event.damage,
+        "multiplier": event.multiplier,
+        "hit_zone": event.zone.as_str(),
+        "distance": event.distance
+    })
+}
+
+/// Projectile launch or impact packet
+fn projectile_packet(event: &ProjectileEvent) -> serde_json::Value {
+    let vector = |v: (f32, f32, f32)| json!({"x": v.0, "y": v.1, "z": v.2});
+    match event {
+        ProjectileEvent::Spawned { projectile_id, owner_id, weapon_id, position, velocity } => json!({
+            "type": "projectile_spawned",
+            "projectile_id": projectile_id,
+            "player_id": owner_id,
+            "weapon_id": weapon_id,
+            "position": vector(*position),
+            "velocity": vector(*velocity)
+        }),
+        ProjectileEvent::Hit { projectile_id, owner_id, weapon_id, target_id, position } => json!({
+            "type": "projectile_hit",
+            "projectile_id": projectile_id,
+            "player_id": owner_id,
+            "weapon_id": weapon_id,
+            "target_id": target_id,
+            "position": vector(*position)
+        }),
+    }
+}
+
+/// Grenade throw or explosion packet
+fn grenade_packet(lobby: &Lobby, event: &GrenadeEvent) -> serde_json::Value {
+    let vector = |v: (f32, f32, f32)| json!({"x": v.0, "y": v.1, "z": v.2});
+    match event {
+        GrenadeEvent::Thrown { grenade_id, owner_id, position, velocity, gravity, fuse_ms } => json!({
+            "type": "grenade_thrown",
+            "grenade_id": grenade_id,
+            "player_id": owner_id,
+            "position": vector(*position),
+            "velocity": vector(*velocity),
+            "gravity": gravity,
+            "fuse_ms": fuse_ms
+        }),
+        GrenadeEvent::Exploded { grenade_id, owner_id, position, affected } => json!({
+            "type": "explosion",
+            "grenade_id": grenade_id,
+            "player_id": owner_id,
+            "position": vector(*position),
+            "radius": lobby.tunables.grenade_radius,
+            "affected": affected.iter().map(|(player_id, damage)| json!({"player_id": player_id, "damage": damage})).collect::<Vec<_>>()
+        }),
+    }
+}
+
+/// Broadcast a grenade throw or explosion to everyone, the thrower included (grenades are server-simulated)
+fn broadcast_grenade_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &GrenadeEvent,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&grenade_packet(lobby, event));
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, false);
+    }
+}
+
+/// Broadcast a projectile launch or impact to everyone, the owner included (projectiles are server-simulated)
+fn broadcast_projectile_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &ProjectileEvent,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&projectile_packet(event));
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, false);
+    }
+}
+
+/// Broadcast a shot to everyone but the shooter (who already played it locally)
+fn broadcast_shot_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    weapons: &WeaponDb,
+    shot: &logic::ShotEvent,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&shot_packet(weapons, shot));
+    for (player_id, addr) in &lobby.client_addresses {
+        if *player_id == shot.shooter_id {
+            continue;
+        }
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, false);
+    }
+}
+
+/// Broadcast a hit to everyone; with `hide_enemy_state` only the attacker and the victim learn the damage
+fn broadcast_damage_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &logic::DamageEvent,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&damage_packet(event));
+    for (player_id, addr) in &lobby.client_addresses {
+        if lobby.hide_enemy_state && *player_id != event.attacker_id && *player_id != event.victim_id {
+            continue;
+        }
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, false);
     }
 }
 
 /// Broadcast kill event to all clients
-async fn broadcast_kill_event(
+fn broadcast_death_event(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     event: &logic::KillEvent,
+    server_state: Option<&ServerState>,
 ) {
-    let packet = json!({
-        "type": "player_killed",
+    let killer = lobby.players.get(&event.killer_id);
+    let victim = lobby.players.get(&event.victim_id);
+    let packet = SerializedPacket::new(&json!({
+        "type": "player_died",
         "killer_id": event.killer_id,
         "killer_name": event.killer_name,
         "victim_id": event.victim_id,
         "victim_name": event.victim_name,
         "weapon_id": event.weapon_id,
         "weapon_name": event.weapon_name,
-        "killer_killstreak": event.killer_new_killstreak
-    });
+        "killer_killstreak": event.killer_new_killstreak,
+        "killer_kills": killer.map(|p| p.kills).unwrap_or(0),
+        "victim_deaths": victim.map(|p| p.deaths).unwrap_or(0),
+        "respawn_in_ms": lobby.tunables.respawn_delay_ms
+    }));
+
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Broadcast the end of a gun game match (the ladder has been reset)
+fn broadcast_match_won(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    winner_id: u32,
+    server_state: Option<&ServerState>,
+) {
+    let ladder_length = match &lobby.mode {
+        GameMode::GunGame(game) => game.ladder.len(),
+        GameMode::Ffa | GameMode::Horde(_) => 0,
+    };
+    let packet = SerializedPacket::new(&json!({
+        "type": "match_won",
+        "player_id": winner_id,
+        "player_name": lobby.players.get(&winner_id).map(|p| p.name.clone()).unwrap_or_default(),
+        "ladder_length": ladder_length
+    }));
+
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Players by score, best first (horde wave bots aren't on it)
+fn scoreboard(lobby: &Lobby) -> Vec<serde_json::Value> {
+    let mut players: Vec<&Player> = lobby.players.values().filter(|p| !horde::is_enemy(lobby, p.id)).collect();
+    players.sort_by(|a, b| b.score.cmp(&a.score).then(a.id.cmp(&b.id)));
+    players
+        .iter()
+        .map(|p| json!({"player_id": p.id, "name": p.name, "score": p.score, "kills": p.kills, "deaths": p.deaths}))
+        .collect()
+}
+
+/// Match flow packet: round_start, round_end or match_end
+fn match_event_packet(lobby: &Lobby, event: &MatchEvent) -> serde_json::Value {
+    match event {
+        MatchEvent::RoundStart { round } => {
+            let mut packet = json!({
+                "type": "round_start",
+                "round": round,
+                "rounds_per_match": lobby.rules.rounds_per_match,
+                "duration_secs": lobby.rules.round_duration.as_secs(),
+                "score_limit": lobby.rules.score_limit
+            });
+            if let GameMode::GunGame(game) = &lobby.mode {
+                packet["ladder"] = json!(game.ladder);
+            }
+            packet
+        }
+        MatchEvent::RoundEnd { round, leader_id } => json!({
+            "type": "round_end",
+            "round": round,
+            "leader_id": leader_id,
+            "scores": scoreboard(lobby)
+        }),
+        MatchEvent::MatchEnd { winner_id } => json!({
+            "type": "match_end",
+            "winner_id": winner_id,
+            "scores": scoreboard(lobby),
+            "next_match_in_secs": lobby.rules.results_duration.as_secs()
+        }),
+    }
+}
+
+/// End-of-match summary: every player's placement and stats for the post-game screen
+fn match_summary_packet(lobby: &Lobby, winner_id: Option<u32>) -> serde_json::Value {
+    let players: Vec<serde_json::Value> = matches::placements(lobby)
+        .iter()
+        .filter_map(|placement| {
+            let p = lobby.players.get(&placement.player_id)?;
+            let accuracy = if p.shots_fired > 0 { p.shots_hit as f32 / p.shots_fired as f32 } else { 0.0 };
+            Some(json!({
+                "player_id": p.id,
+                "name": p.name,
+                "place": placement.place,
+                "team_id": p.team_id,
+                "score": p.score,
+                "kills": p.kills,
+                "deaths": p.deaths,
+                "accuracy": accuracy
+            }))
+        })
+        .collect();
+    json!({"type": "match_summary", "winner_id": winner_id, "players": players})
+}
+
+/// Broadcast a match flow transition to all clients (a match end is followed by its summary)
+fn broadcast_match_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &MatchEvent,
+    server_state: Option<&ServerState>,
+) {
+    let mut packets = vec![SerializedPacket::new(&match_event_packet(lobby, event))];
+    if let MatchEvent::MatchEnd { winner_id } = event {
+        packets.push(SerializedPacket::new(&match_summary_packet(lobby, *winner_id)));
+    }
+    for packet in &packets {
+        for (player_id, addr) in &lobby.client_addresses {
+            send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, packet, true);
+        }
+    }
+}
 
-    if let Ok(data) = serde_json::to_vec(&packet) {
-        for (_player_id, addr) in &lobby.client_addresses {
-            if let Err(e) = socket.send_to(&data, *addr).await {
-                log::debug!("Failed to send kill event to {}: {:?}", addr, e);
+/// countdown_start when the ready quorum started the countdown, countdown_cancelled when it was lost
+fn countdown_packet(previous_state: &str, state: MatchState, now: std::time::Instant) -> Option<serde_json::Value> {
+    match state {
+        MatchState::Countdown { ends_at } if previous_state != "countdown" => Some(json!({
+            "type": "countdown_start",
+            "ends_in_ms": ends_at.saturating_duration_since(now).as_millis() as u64
+        })),
+        MatchState::WaitingForPlayers if previous_state == "countdown" => Some(json!({"type": "countdown_cancelled"})),
+        _ => None,
+    }
+}
+
+/// Broadcast a countdown start or cancellation to all clients
+fn broadcast_countdown(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    packet: &serde_json::Value,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(packet);
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Mutator packet: mutator_started (with what it changes) or mutator_ended
+fn mutator_event_packet(event: &MutatorEvent) -> serde_json::Value {
+    match event {
+        MutatorEvent::Started { mutator, duration } => {
+            let mut packet = json!({
+                "type": "mutator_started",
+                "mutator": mutator.as_str(),
+                "duration_ms": duration.as_millis() as u64
+            });
+            match mutator {
+                Mutator::LowGravity => packet["gravity_scale"] = json!(mutators::LOW_GRAVITY_SCALE),
+                Mutator::DoubleDamage => packet["damage_multiplier"] = json!(2),
+                Mutator::WeaponRoulette => {}
             }
+            packet
+        }
+        MutatorEvent::Ended { mutator } => json!({
+            "type": "mutator_ended",
+            "mutator": mutator.as_str()
+        }),
+    }
+}
+
+/// Broadcast a mutator starting or ending to all clients
+fn broadcast_mutator_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &MutatorEvent,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&mutator_event_packet(event));
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Horde packet: wave_started, wave_cleared, horde_lives, player_out or horde_ended
+/// (wave bots coming and going are sent as player_joined and player_left)
+fn horde_event_packet(lobby: &Lobby, event: &HordeEvent) -> Option<serde_json::Value> {
+    let packet = match event {
+        HordeEvent::WaveStarted { wave, enemies } => json!({
+            "type": "wave_started",
+            "wave": wave,
+            "waves": lobby.rules.horde.waves,
+            "enemies": enemies
+        }),
+        HordeEvent::WaveCleared { wave, next_in } => json!({
+            "type": "wave_cleared",
+            "wave": wave,
+            "next_wave_in_ms": next_in.as_millis() as u64
+        }),
+        HordeEvent::LivesChanged { lives } => json!({"type": "horde_lives", "lives": lives}),
+        HordeEvent::PlayerOut { player_id } => json!({"type": "player_out", "player_id": player_id}),
+        HordeEvent::Ended { wave, cleared } => json!({"type": "horde_ended", "wave": wave, "cleared": cleared}),
+        HordeEvent::EnemySpawned { .. } | HordeEvent::EnemyRemoved { .. } => return None,
+    };
+    Some(packet)
+}
+
+/// Broadcast horde progress to all clients
+fn broadcast_horde_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &HordeEvent,
+    server_state: Option<&ServerState>,
+) {
+    let Some(packet) = horde_event_packet(lobby, event).map(|packet| SerializedPacket::new(&packet)) else {
+        return;
+    };
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Tell each player of the lobby where the rematch is, with the reservation holding their slot
+fn send_rematch_offers(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    offers: &[(u32, String)],
+    server_state: Option<&ServerState>,
+) {
+    let Some(code) = &lobby.rematch else {
+        return;
+    };
+    for (player_id, reservation) in offers {
+        let Some(addr) = lobby.client_addresses.get(player_id) else {
+            continue;
+        };
+        let packet = json!({
+            "type": "rematch_available",
+            "code": code,
+            "reservation": reservation
+        });
+        send_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Tell a player they are being removed; sent once, since they are forgotten right after
+fn send_kicked_message(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    reason: &str,
+    server_state: Option<&ServerState>,
+) {
+    let Some(addr) = lobby.client_addresses.get(&player_id) else {
+        return;
+    };
+    let mut packet = json!({"type": "kicked", "player_id": player_id});
+    let message_text = server_state.is_some_and(|s| s.message_text());
+    messages::insert_message(&mut packet, MessageCode::Kicked, json!({"reason": reason}), message_text);
+    send_event(outbound, lobby, server_state, reliable, player_id, *addr, &packet, false);
+}
+
+/// Offer an eliminated player the spectator camera: who is still playing to follow
+/// They already receive everything; from now on positions arrive unculled
+fn send_spectate_offer(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    server_state: Option<&ServerState>,
+) {
+    let Some(addr) = lobby.client_addresses.get(&player_id) else {
+        return;
+    };
+    let mut targets: Vec<u32> = lobby
+        .players
+        .values()
+        .filter(|p| p.id != player_id && !p.is_dead && !lobbies::is_spectating(lobby, p.id) && !horde::is_enemy(lobby, p.id))
+        .map(|p| p.id)
+        .collect();
+    targets.sort_unstable();
+    let packet = json!({"type": "spectate_offer", "player_id": player_id, "targets": targets});
+    send_event(outbound, lobby, server_state, reliable, player_id, *addr, &packet, true);
+}
+
+/// Broadcast an owner change of the lobby to all clients
+fn broadcast_lobby_event(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    event: &LobbyEvent,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&event.packet());
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Broadcast a server notice to all clients
+fn broadcast_announcement(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    text: &str,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&json!({"type": "announcement", "text": text}));
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Tell every client the server goes down in `grace_secs`
+fn broadcast_shutdown(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    grace_secs: u64,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&json!({"type": "server_shutdown", "grace_secs": grace_secs}));
+    for (player_id, addr) in &lobby.client_addresses {
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Relay a chat line; team chat only reaches the sender's team
+fn broadcast_chat_message(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    message: &ChatMessage,
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&json!({
+        "type": "chat_message",
+        "player_id": message.player_id,
+        "name": message.name,
+        "text": message.text,
+        "team_only": message.team_id.is_some()
+    }));
+    for (player_id, addr) in &lobby.client_addresses {
+        if message.team_id.is_some() && lobby.players.get(player_id).and_then(|p| p.team_id) != message.team_id {
+            continue;
+        }
+        send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, &packet, true);
+    }
+}
+
+/// Pass a voice frame on as sent to listeners that negotiated voice; lost frames aren't resent
+fn relay_voice(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    speaker_id: u32,
+    payload: &str,
+    listeners: &[u32],
+    server_state: Option<&ServerState>,
+) {
+    let packet = SerializedPacket::new(&json!({"type": "voice", "player_id": speaker_id, "payload": payload}));
+    for listener_id in listeners {
+        let Some(addr) = lobby.client_addresses.get(listener_id) else {
+            continue;
+        };
+        if server_state.is_some_and(|state| !state.client_protocol(addr).has(CAP_VOICE)) {
+            continue;
         }
+        send_serialized_event(outbound, lobby, server_state, reliable, *listener_id, *addr, &packet, false);
     }
 }
 
+/// Tell a player why their chat line wasn't relayed
+fn send_challenge(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    issued: &challenge::Challenge,
+    now: std::time::Instant,
+    server_state: Option<&ServerState>,
+) {
+    let Some(addr) = lobby.client_addresses.get(&player_id) else {
+        return;
+    };
+    let packet = json!({
+        "type": "challenge",
+        "nonce": issued.nonce,
+        "signature": issued.signature,
+        "deadline_ms": issued.deadline.saturating_duration_since(now).as_millis() as u64
+    });
+    send_event(outbound, lobby, server_state, reliable, player_id, *addr, &packet, true);
+}
+
+fn send_chat_rejected(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    player_id: u32,
+    reason: &str,
+    server_state: Option<&ServerState>,
+) {
+    let Some(addr) = lobby.client_addresses.get(&player_id) else {
+        return;
+    };
+    let mut packet = json!({"type": "chat_rejected"});
+    let message_text = server_state.is_some_and(|s| s.message_text());
+    messages::insert_message(&mut packet, MessageCode::ChatRejected, json!({"reason": reason}), message_text);
+    send_event(outbound, lobby, server_state, reliable, player_id, *addr, &packet, false);
+}
+
 /// Broadcast respawn events to all clients
-async fn broadcast_respawn_events(
+fn broadcast_respawn_events(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     player_ids: &[u32],
+    server_state: Option<&ServerState>,
 ) {
     for player_id in player_ids {
-        let packet = json!({
+        let Some(player) = lobby.players.get(player_id) else {
+            continue;
+        };
+        let packet = SerializedPacket::new(&json!({
             "type": "player_respawned",
-            "player_id": player_id
-        });
+            "player_id": player_id,
+            "position": {"x": player.position.0, "y": player.position.1, "z": player.position.2},
+            "health": player.current_health,
+            "ammo": player.current_ammo
+        }));
+        let reduced = SerializedPacket::new(&json!({
+            "type": "player_respawned",
+            "player_id": player_id,
+            "position": {"x": player.position.0, "y": player.position.1, "z": player.position.2},
+            "health_bucket": state_visibility::health_bucket(player.current_health, player.max_health)
+        }));
 
-        if let Ok(data) = serde_json::to_vec(&packet) {
-            for (_player_id, addr) in &lobby.client_addresses {
-                if let Err(e) = socket.send_to(&data, *addr).await {
-                    log::debug!("Failed to send respawn event to {}: {:?}", addr, e);
-                }
-            }
+        for (client_id, addr) in &lobby.client_addresses {
+            let packet = if state_visibility::sees_full_state(lobby, *client_id, *player_id) { &packet } else { &reduced };
+            send_serialized_event(outbound, lobby, server_state, reliable, *client_id, *addr, packet, true);
+        }
+    }
+}
+
+/// Whether the client at `addr` negotiated snapshot sync
+fn uses_snapshots(server_state: Option<&ServerState>, addr: &std::net::SocketAddr) -> bool {
+    server_state.is_some_and(|s| s.client_protocol(addr).has(CAP_SNAPSHOTS))
+}
+
+/// Record this tick's lobby snapshot and send each snapshot client its delta
+/// Snapshots are only built while some client uses them
+fn broadcast_snapshots(
+    lobby: &Lobby,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
+    snapshots: &mut SnapshotSender,
+    tick: u64,
+    server_state: Option<&ServerState>,
+) {
+    let clients: Vec<(u32, std::net::SocketAddr)> = lobby.client_addresses.iter()
+        .filter(|(_, addr)| uses_snapshots(server_state, addr))
+        .map(|(id, addr)| (*id, *addr))
+        .collect();
+    if clients.is_empty() {
+        return;
+    }
+
+    snapshots.record(LobbySnapshot::of(lobby, tick));
+    for (player_id, addr) in clients {
+        let Some(packet) = snapshots.packet_for(player_id) else {
+            continue;
+        };
+        // Queued as a snapshot: a backed up sender only sends the client's newest one
+        let channels = server_state.is_some_and(|s| s.client_protocol(&addr).has(CAP_CHANNELS));
+        if let Some(data) = reliable.stamp(player_id, &SerializedPacket::new(&packet), false, channels) {
+            lobby.analytics.record_bytes_sent(data.len());
+            outbound.push_snapshot(addr, data);
         }
     }
 }
 
 /// Broadcast state events to all clients in lobby
-async fn broadcast_state_events(
+fn broadcast_state_events(
     lobby: &Lobby,
-    socket: &UdpSocket,
+    outbound: &Outbound,
+    reliable: &mut ReliableSender,
     events: &[SyncEvent],
     buffer: &mut PacketBuffer,
+    server_state: Option<&ServerState>,
 ) {
     for event in events {
         let packet = match event {
@@ -598,6 +2165,13 @@ async fn broadcast_state_events(
                     })
                 }
             }
+            SyncEvent::InventoryChanged { player_id, weapons } => {
+                json!({
+                    "type": "inventory_changed",
+                    "player_id": player_id,
+                    "weapons": weapons
+                })
+            }
             SyncEvent::PositionChanged { .. } => {
                 // Position updates are handled separately
                 continue;
@@ -630,6 +2204,23 @@ async fn broadcast_state_events(
                     "killstreak": killstreak
                 })
             }
+            SyncEvent::TeamChanged { player_id, team_id } => {
+                json!({
+                    "type": "team_changed",
+                    "player_id": player_id,
+                    "team_id": team_id
+                })
+            }
+            SyncEvent::TeamScoresChanged { scores } => {
+                let scores: Vec<serde_json::Value> = scores
+                    .iter()
+                    .map(|(team_id, score)| json!({"team_id": team_id, "score": score}))
+                    .collect();
+                json!({
+                    "type": "team_scores",
+                    "scores": scores
+                })
+            }
             SyncEvent::PlayerKicked { player_id, reason } => {
                 json!({
                     "type": "player_kicked",
@@ -645,16 +2236,48 @@ async fn broadcast_state_events(
                 })
             }
         };
+        let packet = SerializedPacket::new(&packet);
 
         // Serialize to buffer
         buffer.clear();
-        if let Ok(data) = serde_json::to_vec(&packet) {
-            // Send to all clients in lobby
-            for (_player_id, addr) in &lobby.client_addresses {
-                if let Err(e) = socket.send_to(&data, *addr).await {
-                    log::debug!("Failed to send event to {}: {:?}", addr, e);
-                }
+        // Health, ammo and reload state are superseded by the next change; the rest must arrive
+        let is_reliable = !matches!(
+            event,
+            SyncEvent::HealthChanged { .. } | SyncEvent::AmmoChanged { .. } | SyncEvent::MaxAmmoChanged { .. }
+                | SyncEvent::ReloadStateChanged { .. } | SyncEvent::InactivityWarning { .. }
+        );
+        // Snapshot clients get player state from snapshots
+        let in_snapshot = matches!(
+            event,
+            SyncEvent::HealthChanged { .. } | SyncEvent::AmmoChanged { .. } | SyncEvent::MaxAmmoChanged { .. }
+                | SyncEvent::WeaponChanged { .. } | SyncEvent::ReloadStateChanged { .. } | SyncEvent::ScoreChanged { .. }
+                | SyncEvent::TeamChanged { .. } | SyncEvent::TeamScoresChanged { .. }
+        );
+        // Enemies of a lobby hiding enemy state get a health bucket and no ammo or reload state
+        let (subject_id, reduced) = match event {
+            SyncEvent::HealthChanged { player_id, health } => {
+                let max_health = lobby.players.get(player_id).map_or(0, |p| p.max_health);
+                let bucket = state_visibility::health_bucket(*health, max_health);
+                (Some(*player_id), Some(json!({"type": "player_state_update", "player_id": player_id, "health_bucket": bucket})))
             }
+            SyncEvent::AmmoChanged { player_id, .. } | SyncEvent::MaxAmmoChanged { player_id, .. }
+                | SyncEvent::ReloadStateChanged { player_id, .. } | SyncEvent::InventoryChanged { player_id, .. } => (Some(*player_id), None),
+            _ => (None, None),
+        };
+        let reduced = reduced.map(|reduced| SerializedPacket::new(&reduced));
+        // Send to all clients in lobby
+        for (player_id, addr) in &lobby.client_addresses {
+            if in_snapshot && uses_snapshots(server_state, addr) {
+                continue;
+            }
+            let packet = match subject_id {
+                Some(subject_id) if !state_visibility::sees_full_state(lobby, *player_id, subject_id) => match &reduced {
+                    Some(reduced) => reduced,
+                    None => continue,
+                },
+                _ => &packet,
+            };
+            send_serialized_event(outbound, lobby, server_state, reliable, *player_id, *addr, packet, is_reliable);
         }
     }
 }
@@ -663,8 +2286,12 @@ async fn broadcast_state_events(
 mod tests {
     use super::*;
     use crate::state::lobby::Lobby;
+    use crate::domain::clock;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
+    const FORWARD: (f32, f32, f32) = (0.0, 0.0, -1.0); // Default facing
+
     #[test]
     fn test_process_command_player_join() {
         let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
@@ -676,7 +2303,7 @@ mod tests {
             addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080),
         };
         
-        process_command(&mut lobby, &weapons, cmd, None);
+        process_command(&mut lobby, &weapons, cmd, None, std::time::Instant::now());
         
         assert!(lobby.players.contains_key(&1));
         assert!(lobby.client_addresses.contains_key(&1));
@@ -684,67 +2311,421 @@ mod tests {
 
     #[test]
     fn test_process_command_shoot() {
-        let mut lobby = Lobby::new("TEST".to_string(), 4, "world".to_string());
+        // Shooter and target overlap, so the hit is always in front
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
         let weapons = WeaponDb::load();
         
-        // Add shooter and target
-        let mut shooter = crate::state::lobby::Player {
-            id: 1,
-            name: "Shooter".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: std::time::SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: std::time::SystemTime::now() - std::time::Duration::from_secs(1),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        
-        let mut target = crate::state::lobby::Player {
-            id: 2,
-            name: "Target".to_string(),
-            position: (0.0, 1.0, 0.0),
-            rotation: (0.0, 0.0, 0.0),
-            last_update: std::time::SystemTime::now(),
-            current_health: 100,
-            max_health: 100,
-            current_weapon_id: 1,
-            current_ammo: 20,
-            max_ammo: 20,
-            is_reloading: false,
-            reload_end_time: None,
-            last_shot_time: std::time::SystemTime::now(),
-            kills: 0,
-            deaths: 0,
-            score: 0,
-            killstreak: 0,
-            warned_at: None,
-            is_dead: false,
-            respawn_time: None,
-        };
-        
-        lobby.players.insert(1, shooter);
-        lobby.players.insert(2, target);
-        
-        let cmd = LobbyCommand::Shoot { player_id: 1, target_id: 2 };
-        process_command(&mut lobby, &weapons, cmd, None);
+        let cmd = LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None };
+        process_command(&mut lobby, &weapons, cmd, None, std::time::Instant::now());
         
         let shooter = lobby.players.get(&1).unwrap();
         assert_eq!(shooter.current_ammo, 19);
         
         let target = lobby.players.get(&2).unwrap();
         assert_eq!(target.current_health, 80); // 100 - 20 damage
+
+        assert_eq!(lobby.shot_feed, vec![logic::ShotEvent { shooter_id: 1, target_id: Some(2), weapon_id: 1, hit: true }]);
+        let packet = shot_packet(&weapons, &lobby.shot_feed[0]);
+        assert_eq!(packet["type"], "shot_fired");
+        assert_eq!(packet["sound_id"], "shot_pistol");
+        assert_eq!(packet["muzzle_flash_id"], "flash_small");
+
+        let packet = damage_packet(&lobby.damage_feed[0]);
+        assert_eq!((packet["type"].as_str(), packet["player_id"].as_u64()), (Some("player_damaged"), Some(2)));
+        assert_eq!((packet["attacker_id"].as_u64(), packet["damage"].as_u64()), (Some(1), Some(20)));
+        assert_eq!((packet["multiplier"].as_f64(), packet["hit_zone"].as_str()), (Some(1.0), Some("body")));
     }
-}
 
+    #[test]
+    fn test_shots_pass_through_loading_players() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        let weapons = WeaponDb::load();
+        lobbies::begin_loading(&mut lobby, 2, Duration::from_secs(15), std::time::Instant::now());
+
+        let cmd = LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None };
+        process_command(&mut lobby, &weapons, cmd, None, std::time::Instant::now());
+        assert_eq!(lobby.players[&2].current_health, 100);
+        assert_eq!(lobby.shot_feed[0].target_id, None);
+        assert!(LobbySnapshot::of(&lobby, 1).players.keys().eq([&1]));
+        let reload = LobbyCommand::Reload { player_id: 2 };
+        assert_eq!(reload.needs_loaded_scene(), Some(2)); // Held back by the tick loop while loading
+
+        process_command(&mut lobby, &weapons, LobbyCommand::LoadingComplete { player_id: 2 }, None, std::time::Instant::now());
+        assert!(!lobbies::is_loading(&lobby, 2));
+        assert_eq!(LobbySnapshot::of(&lobby, 1).players.len(), 2);
+    }
+
+    #[test]
+    fn test_process_command_lethal_shot_registers_kill() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -10.0)).health(20))
+            .build();
+        let weapons = WeaponDb::load();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+
+        let victim = lobby.players.get(&2).unwrap();
+        assert!(victim.is_dead);
+        assert_eq!(victim.deaths, 1);
+        assert_eq!(lobby.players.get(&1).unwrap().kills, 1);
+        assert_eq!(lobby.kill_feed.len(), 1);
+        assert_eq!(lobby.kill_feed[0].victim_id, 2);
+    }
+
+    #[test]
+    fn test_projectile_weapons_launch_instead_of_hitscan() {
+        let mut rocket = WeaponDb::load().get(2).unwrap().clone();
+        rocket.id = 10;
+        rocket.projectile = Some(crate::utils::weapondb::ProjectileSpec { speed: 50.0, gravity: 0.0, lifetime_secs: 2.0 });
+        let weapons = WeaponDb::load().with_weapon(rocket);
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().weapon(10).ammo(8))
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -5.0)))
+            .build();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+
+        // Nothing is hit until the projectile gets there
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 100);
+        assert!(lobby.shot_feed.is_empty());
+        assert_eq!(lobby.projectiles.len(), 1);
+        let packet = projectile_packet(&lobby.projectile_feed[0]);
+        assert_eq!(packet["type"], "projectile_spawned");
+        assert_eq!(packet["velocity"]["z"], -50.0);
+
+        projectiles::step_at(&mut lobby, &weapons, 0.1, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 70);
+        let packet = projectile_packet(&lobby.projectile_feed[1]);
+        assert_eq!((packet["type"].as_str(), packet["target_id"].as_u64()), (Some("projectile_hit"), Some(2)));
+    }
+
+    #[test]
+    fn test_thrown_grenades_explode_after_their_fuse() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -2.0)))
+            .build();
+        Arc::make_mut(&mut lobby.tunables).grenade_gravity = 0.0;
+        let now = std::time::Instant::now();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::Throw { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD }, None, now);
+        let packet = grenade_packet(&lobby, &lobby.grenade_feed[0]);
+        assert_eq!((packet["type"].as_str(), packet["fuse_ms"].as_u64()), (Some("grenade_thrown"), Some(2500)));
+        assert_eq!(packet["velocity"]["z"], -15.0);
+
+        // Frozen lobbies take no throws
+        lobby.frozen = true;
+        process_command(&mut lobby, &weapons, LobbyCommand::Throw { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD }, None, now);
+        assert_eq!(lobby.grenades.len(), 1);
+
+        grenades::step_at(&mut lobby, &weapons, 0.1, now + Duration::from_millis(2500));
+        let packet = grenade_packet(&lobby, &lobby.grenade_feed[1]);
+        assert_eq!((packet["type"].as_str(), packet["radius"].as_f64()), (Some("explosion"), Some(5.0)));
+        assert_eq!(packet["position"]["z"], 0.0);
+        assert_eq!(packet["affected"], json!([{"player_id": 2, "damage": 48}]));
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 52);
+    }
+
+    #[test]
+    fn test_shoot_respects_friendly_fire() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        lobby.team_count = 2;
+        for player in lobby.players.values_mut() {
+            player.team_id = Some(1);
+        }
+        let weapons = WeaponDb::load();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 100);
+        assert!(!lobby.shot_feed[0].hit);
+
+        lobby.friendly_fire = true;
+        lobby.players.get_mut(&1).unwrap().last_shot_time = None;
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 80);
+    }
+
+    #[test]
+    fn test_gun_game_restricts_weapon_switch() {
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new().build();
+        lobby.mode = GameMode::GunGame(gungame::GunGame::new(gungame::default_ladder(&weapons)));
+        lobbies::add_player(&mut lobby, 1, "Ada".to_string(), 1, &weapons).unwrap();
+        logic::grant_weapon(&mut lobby, &weapons, 1, 2).unwrap();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::WeaponSwitch { player_id: 1, weapon_id: 2 }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&1).unwrap().current_weapon_id, 1);
+
+        process_command(&mut lobby, &weapons, LobbyCommand::WeaponSwitch { player_id: 1, weapon_id: 3 }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&1).unwrap().current_weapon_id, 3);
+    }
+
+    #[test]
+    fn test_process_command_shoot_target_behind() {
+        // Shooter faces -Z, target stands behind at +Z
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, 10.0)))
+            .build();
+        let weapons = WeaponDb::load();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+
+        // Shot is fired (ammo spent) but the hit is rejected
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 19);
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 100);
+    }
+
+    #[test]
+    fn test_shot_is_checked_where_the_target_was_when_fired() {
+        // Target was in front 200ms ago and has since run behind the shooter
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -10.0)))
+            .build();
+        let weapons = WeaponDb::load();
+        let now = std::time::Instant::now();
+        lobby.history.record(&lobby.players, now - std::time::Duration::from_millis(200));
+        lobby.players.get_mut(&2).unwrap().position = (0.0, 1.0, 10.0);
+        lobby.history.record(&lobby.players, now);
+
+        // Shooter's clock reads 10_000 now; the shot was fired at 9_800 on it
+        let sample = LobbyCommand::ClockSample { player_id: 1, client_ms: 10_000, server_ms: clock::server_ms(now) };
+        process_command(&mut lobby, &weapons, sample, None, std::time::Instant::now());
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: Some(9_800) }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 80);
+
+        // Without a timestamp the target's current position counts
+        lobby.players.get_mut(&1).unwrap().last_shot_time = None;
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 18);
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 80);
+    }
+
+    #[test]
+    fn test_high_ping_shots_rewind_up_to_max_rewind() {
+        // Target stood in front until 700ms ago, then stepped aside
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -10.0)))
+            .build();
+        let weapons = WeaponDb::load();
+        let now = std::time::Instant::now();
+        for ms in (0..=900).rev().step_by(20) {
+            if ms == 680 {
+                lobby.players.get_mut(&2).unwrap().position = (5.0, 1.0, -10.0);
+            }
+            lobby.history.record(&lobby.players, now - std::time::Duration::from_millis(ms));
+        }
+        let sample = LobbyCommand::ClockSample { player_id: 1, client_ms: 10_000, server_ms: clock::server_ms(now) };
+        process_command(&mut lobby, &weapons, sample, None, std::time::Instant::now());
+
+        // Fired 750ms ago on a 1.5s round trip
+        let shoot = LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: Some(9_250) };
+        process_command(&mut lobby, &weapons, shoot.clone(), None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 80);
+
+        // Lobbies can rewind less: the shot is then checked 500ms back, after the target moved
+        lobby.max_rewind = std::time::Duration::from_millis(500);
+        lobby.players.get_mut(&1).unwrap().last_shot_time = None;
+        process_command(&mut lobby, &weapons, shoot, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&1).unwrap().current_ammo, 18);
+        assert_eq!(lobby.players.get(&2).unwrap().current_health, 80);
+    }
+
+    #[test]
+    fn test_no_recoil_burst_is_flagged() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -10.0)))
+            .build();
+        let weapons = WeaponDb::load();
+
+        for _ in 0..5 {
+            lobby.players.get_mut(&1).unwrap().last_shot_time = None;
+            let direction = simulator::facing_direction(0.0, 0.1);
+            let cmd = LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction, client_time: None };
+            process_command(&mut lobby, &weapons, cmd, None, std::time::Instant::now());
+        }
+
+        let shooter = lobby.players.get(&1).unwrap();
+        assert!((shooter.pitch() - 0.1).abs() < 1e-5);
+        assert_eq!(shooter.violations.count(ViolationKind::NoRecoil), 1);
+    }
+
+    /// Encode a single entry as a `positions` packet
+    fn encode_position(player: &Player, protocol: ClientProtocol, dimensions: Dimensions) -> Vec<u8> {
+        let entry = encode_position_entry(1, player, protocol, dimensions).unwrap();
+        encode_positions(&[&entry], 7).remove(0)
+    }
+
+    #[test]
+    fn test_encode_position_update_formats() {
+        let player = PlayerBuilder::new().rotation(Player::rotation_from_yaw_pitch(1.5, 0.25)).build();
+
+        let legacy: serde_json::Value = serde_json::from_slice(&encode_position(&player, ClientProtocol::default(), Dimensions::Three)).unwrap();
+        assert_eq!(legacy["type"], "positions");
+        assert_eq!(legacy["tick"], 7);
+        assert_eq!(legacy["players"][0]["rotation"]["y"], 1.5);
+        assert!(legacy["players"][0].get("yaw").is_none());
+
+        let v2 = ClientProtocol { version: 2, capabilities: 0 };
+        let compact: serde_json::Value = serde_json::from_slice(&encode_position(&player, v2, Dimensions::Three)).unwrap();
+        assert_eq!(compact["players"][0]["yaw"], 1.5);
+        assert_eq!(compact["players"][0]["pitch"], 0.25);
+        assert!(compact["players"][0].get("rotation").is_none());
+
+        let binary_v2 = ClientProtocol { version: 2, capabilities: CAP_BINARY };
+        let BinaryPacket::Positions(updates) = binary::decode(&encode_position(&player, binary_v2, Dimensions::Three)).unwrap() else {
+            panic!("expected a binary positions packet");
+        };
+        assert!(matches!(updates[..], [BinaryPacket::PositionUpdateYawPitch { yaw: 1500, pitch: 250, .. }]));
+    }
+
+    #[test]
+    fn test_2d_position_updates_drop_height() {
+        let player = PlayerBuilder::new().position((4.0, 1.0, -2.0)).rotation(Player::rotation_from_yaw_pitch(1.5, 0.25)).build();
+
+        let v2 = ClientProtocol { version: 2, capabilities: 0 };
+        let json: serde_json::Value = serde_json::from_slice(&encode_position(&player, v2, Dimensions::Two)).unwrap();
+        assert_eq!(json["players"][0]["position"], json!({"x": 4.0, "z": -2.0}));
+        assert_eq!(json["players"][0]["yaw"], 1.5);
+        assert!(json["players"][0].get("pitch").is_none());
+
+        let binary_v2 = ClientProtocol { version: 2, capabilities: CAP_BINARY };
+        let data = encode_position(&player, binary_v2, Dimensions::Two);
+        assert_eq!(
+            binary::decode(&data).unwrap(),
+            BinaryPacket::Positions(vec![BinaryPacket::PositionUpdate2d { player_id: 1, x: 4000, z: -2000, yaw: 1500 }])
+        );
+    }
+
+    #[test]
+    fn test_positions_coalesce_up_to_the_mtu() {
+        let player = PlayerBuilder::new().build();
+        for protocol in [ClientProtocol::default(), ClientProtocol { version: 2, capabilities: CAP_BINARY }] {
+            let entries: Vec<PositionEntry> =
+                (1..=40).map(|id| encode_position_entry(id, &player, protocol, Dimensions::Three).unwrap()).collect();
+            let packets = encode_positions(&entries.iter().collect::<Vec<_>>(), 7);
+            assert!(packets.iter().all(|packet| packet.len() <= outbound::MAX_BATCH_BYTES));
+
+            let ids: Vec<u32> = if protocol.has(CAP_BINARY) {
+                assert_eq!(packets.len(), 1); // 40 binary updates fit one datagram
+                packets
+                    .iter()
+                    .flat_map(|packet| match binary::decode(packet).unwrap() {
+                        BinaryPacket::Positions(updates) => updates.iter().map(BinaryPacket::player_id).collect(),
+                        _ => Vec::new(),
+                    })
+                    .collect()
+            } else {
+                assert!(packets.len() > 1);
+                packets
+                    .iter()
+                    .flat_map(|packet| {
+                        let packet: serde_json::Value = serde_json::from_slice(packet).unwrap();
+                        packet["players"].as_array().unwrap().iter().map(|p| p["player_id"].as_u64().unwrap() as u32).collect::<Vec<_>>()
+                    })
+                    .collect()
+            };
+            assert_eq!(ids, (1..=40).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_match_event_packets() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        lobby.players.get_mut(&2).unwrap().score = 300;
+
+        let start = match_event_packet(&lobby, &MatchEvent::RoundStart { round: 1 });
+        assert_eq!(start["type"], "round_start");
+        assert_eq!(start["rounds_per_match"], 3);
+        assert_eq!(start["duration_secs"], 300);
+        assert!(start.get("ladder").is_none());
+        lobby.mode = GameMode::GunGame(gungame::GunGame::new(vec![2, 1, 3]));
+        let start = match_event_packet(&lobby, &MatchEvent::RoundStart { round: 1 });
+        assert_eq!(start["ladder"], json!([2, 1, 3]));
+
+        let end = match_event_packet(&lobby, &MatchEvent::MatchEnd { winner_id: Some(2) });
+        assert_eq!(end["type"], "match_end");
+        assert_eq!(end["winner_id"], 2);
+        assert_eq!(end["scores"][0]["player_id"], 2); // Best score first
+        assert_eq!(end["scores"][1]["player_id"], 1);
+
+        let player = lobby.players.get_mut(&2).unwrap();
+        (player.shots_fired, player.shots_hit) = (4, 1);
+        let summary = match_summary_packet(&lobby, Some(2));
+        assert_eq!(summary["type"], "match_summary");
+        assert_eq!(summary["players"][0], json!({
+            "player_id": 2, "name": "Test", "place": 1, "team_id": null, "score": 300, "kills": 0, "deaths": 0, "accuracy": 0.25
+        }));
+        assert_eq!(summary["players"][1]["place"], 2);
+    }
+
+    #[test]
+    fn test_ready_up_and_countdown_packets() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        let weapons = WeaponDb::load();
+        process_command(&mut lobby, &weapons, LobbyCommand::Ready { player_id: 1, ready: true }, None, std::time::Instant::now());
+        process_command(&mut lobby, &weapons, LobbyCommand::Ready { player_id: 1, ready: true }, None, std::time::Instant::now());
+        assert_eq!(lobby.lobby_events, vec![LobbyEvent::ReadyChanged { player_id: 1, ready: true }]);
+
+        let now = std::time::Instant::now();
+        let counting = MatchState::Countdown { ends_at: now + Duration::from_secs(10) };
+        let start = countdown_packet("waiting_for_players", counting, now).unwrap();
+        assert_eq!(start["type"], "countdown_start");
+        assert_eq!(start["ends_in_ms"], 10_000);
+        assert!(countdown_packet("countdown", counting, now).is_none());
+        assert_eq!(countdown_packet("countdown", MatchState::WaitingForPlayers, now).unwrap()["type"], "countdown_cancelled");
+    }
+
+    #[test]
+    fn test_frozen_lobby_holds_players_still() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        let weapons = WeaponDb::load();
+        logic::grant_weapon(&mut lobby, &weapons, 1, 2).unwrap();
+        lobby.frozen = true;
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+
+        process_command(&mut lobby, &weapons, LobbyCommand::PositionUpdate { player_id: 1, position: (9.0, 1.0, 9.0), rotation: (0.0, 0.0, 0.0), addr }, None, std::time::Instant::now());
+        process_command(&mut lobby, &weapons, LobbyCommand::Shoot { player_id: 1, origin: (0.0, 1.0, 0.0), direction: FORWARD, client_time: None }, None, std::time::Instant::now());
+        process_command(&mut lobby, &weapons, LobbyCommand::WeaponSwitch { player_id: 1, weapon_id: 2 }, None, std::time::Instant::now());
+        let player = lobby.players.get(&1).unwrap();
+        assert_eq!((player.position, player.current_ammo, player.current_weapon_id), ((0.0, 1.0, 0.0), 20, 1));
+
+        lobby.frozen = false;
+        process_command(&mut lobby, &weapons, LobbyCommand::WeaponSwitch { player_id: 1, weapon_id: 2 }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&1).unwrap().current_weapon_id, 2);
+    }
+
+    #[test]
+    fn test_weapon_roulette_locks_weapon_switches() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        let weapons = WeaponDb::load();
+        let now = std::time::Instant::now();
+        lobby.mutators.active = Some(mutators::ActiveMutator { mutator: Mutator::WeaponRoulette, ends_at: now });
+        logic::grant_weapon(&mut lobby, &weapons, 1, 2).unwrap();
+
+        process_command(&mut lobby, &weapons, LobbyCommand::WeaponSwitch { player_id: 1, weapon_id: 2 }, None, std::time::Instant::now());
+        assert_eq!(lobby.players.get(&1).unwrap().current_weapon_id, 1);
+
+        let started = mutator_event_packet(&MutatorEvent::Started { mutator: Mutator::LowGravity, duration: Duration::from_secs(30) });
+        assert_eq!(started["mutator"], "low_gravity");
+        assert_eq!(started["duration_ms"], 30000);
+        assert!(started["gravity_scale"].as_f64().unwrap() < 1.0);
+    }
+}
@@ -1,3 +1,11 @@
 pub mod delta_sync;
 pub mod lobby_tick;
+pub mod outbound;
+pub mod relevance;
+pub mod snapshot;
+pub mod state_visibility;
+pub mod telemetry;
+pub mod visibility;
 
+#[cfg(test)]
+mod chaos;
@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use crate::net::binary;
+use crate::net::protocol::CAP_BATCHING;
+use crate::state::server_state::ServerState;
+
+/// Largest batch datagram: a 1500 byte MTU less IP/UDP headers, the encryption overhead and some slack
+pub const MAX_BATCH_BYTES: usize = 1200;
+
+/// Ticks of datagrams the sender task may fall behind by before the tick loop holds them back
+pub const QUEUE_TICKS: usize = 4;
+
+/// Held back datagrams past which a tick's datagrams are dropped; reliable events are resent until acked
+pub const MAX_BACKLOG: usize = 4096;
+
+const BATCH_PREFIX: &[u8] = br#"{"type":"batch","packets":["#;
+const BATCH_SUFFIX: &[u8] = b"]}";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Queued {
+    addr: SocketAddr,
+    data: Vec<u8>,
+    snapshot: bool, // Replaced by the client's next snapshot if it hasn't been sent by then
+}
+
+/// Datagrams a tick queued, in order, handed to the lobby's sender task once the tick lets go of the lobby
+#[derive(Debug, Default)]
+pub struct Outbound {
+    datagrams: Mutex<Vec<Queued>>,
+}
+
+impl Outbound {
+    pub fn push(&self, addr: SocketAddr, data: Vec<u8>) {
+        self.datagrams.lock().unwrap().push(Queued { addr, data, snapshot: false });
+    }
+
+    /// Queue a snapshot delta, which the client's next snapshot makes obsolete
+    pub fn push_snapshot(&self, addr: SocketAddr, data: Vec<u8>) {
+        self.datagrams.lock().unwrap().push(Queued { addr, data, snapshot: true });
+    }
+
+    fn take(&self) -> Vec<Queued> {
+        std::mem::take(&mut *self.datagrams.lock().unwrap())
+    }
+}
+
+/// Hands each tick's datagrams to the lobby's sender task over a bounded channel
+/// While the sender is behind, ticks wait here: a client's unsent snapshot gives way to its next one,
+/// and past `MAX_BACKLOG` datagrams whole ticks are dropped
+pub struct OutboundQueue {
+    tx: mpsc::Sender<Vec<(SocketAddr, Vec<u8>)>>,
+    backlog: Vec<Queued>,
+}
+
+impl OutboundQueue {
+    /// Start a lobby's sender task, which ends once the queue is dropped
+    pub fn spawn(socket: Arc<UdpSocket>, server_state: Option<Arc<ServerState>>) -> (Self, JoinHandle<()>) {
+        let (tx, rx) = mpsc::channel(QUEUE_TICKS);
+        let sender = tokio::spawn(run_sender(socket, server_state, rx));
+        (Self { tx, backlog: Vec::new() }, sender)
+    }
+
+    /// Queue the datagrams a tick pushed to `outbound`, after any still held back
+    pub fn flush(&mut self, outbound: &Outbound) {
+        let datagrams = outbound.take();
+        if !self.backlog.is_empty() {
+            let newer: HashSet<SocketAddr> = datagrams.iter().filter(|d| d.snapshot).map(|d| d.addr).collect();
+            self.backlog.retain(|d| !(d.snapshot && newer.contains(&d.addr)));
+        }
+        if self.backlog.len() + datagrams.len() > MAX_BACKLOG {
+            log::warn!("Lobby sender is {} datagrams behind, dropping {} more", self.backlog.len(), datagrams.len());
+        } else {
+            self.backlog.extend(datagrams);
+        }
+        if self.backlog.is_empty() {
+            return;
+        }
+        match self.tx.try_reserve() {
+            Ok(permit) => permit.send(std::mem::take(&mut self.backlog).into_iter().map(|d| (d.addr, d.data)).collect()),
+            Err(TrySendError::Full(())) => {}
+            Err(TrySendError::Closed(())) => self.backlog.clear(),
+        }
+    }
+}
+
+/// A client's JSON packets waiting to share a datagram
+#[derive(Default)]
+struct OpenBatch {
+    packets: Vec<Vec<u8>>,
+    size: usize, // Of the batch datagram so far
+}
+
+impl OpenBatch {
+    /// One packet goes out as it is, several wrapped in a `batch` packet
+    fn finish(mut self) -> Option<Vec<u8>> {
+        match self.packets.len() {
+            0 => None,
+            1 => self.packets.pop(),
+            _ => {
+                let mut datagram = Vec::with_capacity(self.size);
+                datagram.extend_from_slice(BATCH_PREFIX);
+                for (i, packet) in self.packets.iter().enumerate() {
+                    if i > 0 {
+                        datagram.push(b',');
+                    }
+                    datagram.extend_from_slice(packet);
+                }
+                datagram.extend_from_slice(BATCH_SUFFIX);
+                Some(datagram)
+            }
+        }
+    }
+}
+
+/// Pack each batching client's JSON packets into `{"type": "batch", "packets": [...]}` datagrams of up to
+/// `MAX_BATCH_BYTES`; binary packets, packets too big to share and other clients' packets go out one per datagram
+/// A client's datagrams keep the order its packets were queued in
+pub fn batch(datagrams: Vec<(SocketAddr, Vec<u8>)>, batching: impl Fn(&SocketAddr) -> bool) -> Vec<(SocketAddr, Vec<u8>)> {
+    let mut out = Vec::with_capacity(datagrams.len());
+    let mut open: HashMap<SocketAddr, OpenBatch> = HashMap::new();
+    let overhead = BATCH_PREFIX.len() + BATCH_SUFFIX.len();
+    for (addr, data) in datagrams {
+        if !batching(&addr) || binary::is_binary(&data) || data.len() + overhead > MAX_BATCH_BYTES {
+            if let Some(datagram) = open.remove(&addr).and_then(OpenBatch::finish) {
+                out.push((addr, datagram));
+            }
+            out.push((addr, data));
+            continue;
+        }
+        let batch = open.entry(addr).or_default();
+        if batch.size + 1 + data.len() > MAX_BATCH_BYTES {
+            if let Some(datagram) = std::mem::take(batch).finish() {
+                out.push((addr, datagram));
+            }
+        }
+        batch.size = if batch.packets.is_empty() { overhead } else { batch.size + 1 } + data.len();
+        batch.packets.push(data);
+    }
+    out.extend(open.into_iter().filter_map(|(addr, batch)| batch.finish().map(|datagram| (addr, datagram))));
+    out
+}
+
+/// Send each tick's datagrams, batched and sealed for the clients that negotiated it, until the tick loop ends
+pub async fn run_sender(
+    socket: Arc<UdpSocket>,
+    server_state: Option<Arc<ServerState>>,
+    mut ticks: mpsc::Receiver<Vec<(SocketAddr, Vec<u8>)>>,
+) {
+    while let Some(datagrams) = ticks.recv().await {
+        let datagrams = batch(datagrams, |addr| {
+            server_state.as_ref().is_some_and(|state| state.client_protocol(addr).has(CAP_BATCHING))
+        });
+        for (addr, data) in datagrams {
+            let sent = match &server_state {
+                Some(state) => {
+                    let sealed = state.seal_outbound(&addr, &data);
+                    state.send_datagram(&socket, addr, sealed.as_deref().unwrap_or(&data)).await
+                }
+                None => socket.send_to(&data, addr).await,
+            };
+            if let Err(e) = sent {
+                log::debug!("Failed to send to {}: {:?}", addr, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn packet(n: usize) -> Vec<u8> {
+        serde_json::json!({"type": "player_killed", "seq": n}).to_string().into_bytes()
+    }
+
+    #[test]
+    fn test_batches_per_client_in_order() {
+        let binary = vec![binary::BINARY_MAGIC, 1, 2];
+        let queued = vec![
+            (addr(1), packet(1)),
+            (addr(2), packet(1)),
+            (addr(1), packet(2)),
+            (addr(2), packet(2)),
+            (addr(1), binary.clone()),
+            (addr(1), packet(3)),
+        ];
+        let mut sent = batch(queued, |addr| addr.port() == 1);
+        sent.sort_by_key(|(addr, _)| addr.port()); // Stable: each client's order is kept
+
+        let batched: serde_json::Value = serde_json::from_slice(&sent[0].1).unwrap();
+        assert_eq!(batched["type"], "batch");
+        assert_eq!(batched["packets"][1]["seq"], 2);
+        assert_eq!(sent[1], (addr(1), binary));
+        assert_eq!(sent[2], (addr(1), packet(3))); // Alone: sent as it is
+        assert_eq!(&sent[3..], &[(addr(2), packet(1)), (addr(2), packet(2))]); // No batching negotiated
+    }
+
+    #[test]
+    fn test_batches_stay_under_the_mtu() {
+        let queued: Vec<_> = (0..100).map(|n| (addr(1), packet(n))).collect();
+        let sent = batch(queued, |_| true);
+        assert!(sent.len() > 1);
+        assert!(sent.iter().all(|(_, datagram)| datagram.len() <= MAX_BATCH_BYTES));
+        let packets: Vec<serde_json::Value> = sent
+            .iter()
+            .flat_map(|(_, datagram)| serde_json::from_slice::<serde_json::Value>(datagram).unwrap()["packets"].as_array().unwrap().clone())
+            .collect();
+        assert_eq!(packets.len(), 100);
+        assert!(packets.iter().enumerate().all(|(n, packet)| packet["seq"] == n));
+    }
+
+    #[test]
+    fn test_full_queue_holds_ticks_back_and_replaces_snapshots() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut queue = OutboundQueue { tx, backlog: Vec::new() };
+        let outbound = Outbound::default();
+        outbound.push(addr(1), packet(1));
+        queue.flush(&outbound);
+
+        // The sender hasn't taken the first tick: the next ones wait, keeping events but only the latest snapshot
+        outbound.push_snapshot(addr(1), packet(2));
+        outbound.push_snapshot(addr(2), packet(2));
+        outbound.push(addr(1), packet(3));
+        queue.flush(&outbound);
+        outbound.push_snapshot(addr(1), packet(4));
+        queue.flush(&outbound);
+        assert_eq!(rx.try_recv().unwrap(), vec![(addr(1), packet(1))]);
+        assert!(rx.try_recv().is_err());
+
+        queue.flush(&outbound);
+        assert_eq!(rx.try_recv().unwrap(), vec![(addr(2), packet(2)), (addr(1), packet(3)), (addr(1), packet(4))]);
+    }
+
+    #[test]
+    fn test_backlog_is_bounded() {
+        let (tx, _rx) = mpsc::channel(1);
+        let mut queue = OutboundQueue { tx, backlog: Vec::new() };
+        let outbound = Outbound::default();
+        for tick in 0..MAX_BACKLOG {
+            outbound.push(addr(1), packet(tick));
+            queue.flush(&outbound);
+        }
+        assert_eq!(queue.backlog.len(), MAX_BACKLOG - 1);
+        for n in 0..2 {
+            outbound.push(addr(1), packet(n));
+        }
+        queue.flush(&outbound);
+        assert_eq!(queue.backlog.len(), MAX_BACKLOG - 1);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use crate::state::lobby::{Dimensions, Lobby};
+
+type Position = (f32, f32, f32);
+
+/// Players bucketed into square x/z cells one relevance radius wide, rebuilt every tick
+/// Everyone within the radius of a position is in the 3x3 cells around it
+#[derive(Debug)]
+pub struct RelevanceGrid {
+    radius: f32, // 0 = everyone is relevant
+    dimensions: Dimensions, // 2D lobbies ignore height
+    cells: HashMap<(i32, i32), Vec<(u32, Position)>>, // Cell -> players in it
+}
+
+impl RelevanceGrid {
+    pub fn build(lobby: &Lobby, radius: f32) -> Self {
+        let mut grid = Self { radius: radius.max(0.0), dimensions: lobby.dimensions, cells: HashMap::new() };
+        for player in lobby.players.values() {
+            grid.cells.entry(grid.cell(player.position)).or_default().push((player.id, player.position));
+        }
+        grid
+    }
+
+    fn cell(&self, position: Position) -> (i32, i32) {
+        if self.radius <= 0.0 {
+            return (0, 0);
+        }
+        ((position.0 / self.radius).floor() as i32, (position.2 / self.radius).floor() as i32)
+    }
+
+    /// Players within the relevance radius of a position
+    pub fn near(&self, position: Position) -> Vec<u32> {
+        if self.radius <= 0.0 {
+            return self.cells.values().flatten().map(|(id, _)| *id).collect();
+        }
+        let (cx, cz) = self.cell(position);
+        let mut ids = Vec::new();
+        for x in cx.saturating_sub(1)..=cx.saturating_add(1) {
+            for z in cz.saturating_sub(1)..=cz.saturating_add(1) {
+                let Some(players) = self.cells.get(&(x, z)) else {
+                    continue;
+                };
+                ids.extend(
+                    players
+                        .iter()
+                        .filter(|(_, p)| self.dimensions.distance(position, *p) <= self.radius)
+                        .map(|(id, _)| *id),
+                );
+            }
+        }
+        ids
+    }
+}
+
+/// Whether a player outside an observer's relevance radius is due a low-rate update this tick
+/// Staggered by player id so far updates don't all land on the same tick
+pub fn far_update_due(player_id: u32, tick: u64, interval_ticks: u64) -> bool {
+    (tick + player_id as u64).is_multiple_of(interval_ticks.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    #[test]
+    fn test_near_only_returns_players_in_radius() {
+        let lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().position((0.0, 1.0, 0.0)))
+            .with_player(PlayerBuilder::new().id(2).position((-90.0, 1.0, 20.0))) // Neighbouring cell, in range
+            .with_player(PlayerBuilder::new().id(3).position((190.0, 1.0, 0.0))) // Neighbouring cell, out of range
+            .with_player(PlayerBuilder::new().id(4).position((0.0, 1.0, -450.0)))
+            .build();
+
+        let mut near = RelevanceGrid::build(&lobby, 100.0).near((0.0, 1.0, 0.0));
+        near.sort();
+        assert_eq!(near, vec![1, 2]);
+
+        let mut everyone = RelevanceGrid::build(&lobby, 0.0).near((0.0, 1.0, 0.0));
+        everyone.sort();
+        assert_eq!(everyone, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_far_updates_are_staggered() {
+        let due: Vec<u64> = (0..20).filter(|&tick| far_update_due(3, tick, 10)).collect();
+        assert_eq!(due, vec![7, 17]);
+        assert!(far_update_due(4, 6, 10));
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use crate::state::lobby::{Lobby, Player};
+use crate::tick::state_visibility;
+use crate::utils::config::Config;
+
+/// Snapshots kept as delta baselines (~1.3s at 50Hz); older acks get a full snapshot
+const SNAPSHOT_HISTORY: usize = 64;
+
+/// What snapshot clients know about a player (positions travel in their own packets)
+/// Enemies of a lobby hiding enemy state get `health_bucket` instead of the exact fields
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerSnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_health: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_bucket: Option<u32>,
+    pub weapon_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ammo: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ammo: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reloading: Option<bool>,
+    pub dead: bool,
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub killstreak: u32,
+    pub team_id: Option<u32>,
+}
+
+impl PlayerSnapshot {
+    pub fn of(player: &Player) -> Self {
+        Self {
+            health: Some(player.current_health),
+            max_health: Some(player.max_health),
+            health_bucket: None,
+            weapon_id: player.current_weapon_id,
+            ammo: Some(player.current_ammo),
+            max_ammo: Some(player.max_ammo),
+            reloading: Some(player.is_reloading),
+            dead: player.is_dead,
+            score: player.score,
+            kills: player.kills,
+            deaths: player.deaths,
+            killstreak: player.killstreak,
+            team_id: player.team_id,
+        }
+    }
+
+    /// What an enemy may see: health as a bucket, no ammo or reload state
+    fn reduced(&self) -> Self {
+        let bucket = state_visibility::health_bucket(self.health.unwrap_or(0), self.max_health.unwrap_or(0));
+        Self {
+            health: None,
+            max_health: None,
+            health_bucket: Some(bucket),
+            ammo: None,
+            max_ammo: None,
+            reloading: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// Full game state of a lobby at one tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct LobbySnapshot {
+    pub tick: u64,
+    pub players: BTreeMap<u32, PlayerSnapshot>,
+    pub team_scores: BTreeMap<u32, u32>, // Team ID -> score
+    pub hide_enemy_state: bool,
+}
+
+impl LobbySnapshot {
+    pub fn of(lobby: &Lobby, tick: u64) -> Self {
+        Self {
+            tick,
+            // Players still loading the scene aren't in the world yet
+            players: lobby
+                .players
+                .iter()
+                .filter(|(_, player)| !player.is_loading())
+                .map(|(id, player)| (*id, PlayerSnapshot::of(player)))
+                .collect(),
+            team_scores: lobby.team_scores.clone(),
+            hide_enemy_state: lobby.hide_enemy_state,
+        }
+    }
+
+    /// The snapshot as `viewer_id` may see it (enemies reduced when the lobby hides their state)
+    pub fn view_for(&self, viewer_id: u32) -> LobbySnapshot {
+        let viewer = (viewer_id, self.players.get(&viewer_id).and_then(|p| p.team_id));
+        let players = self
+            .players
+            .iter()
+            .map(|(id, player)| {
+                let hidden = state_visibility::hides_state_between(self.hide_enemy_state, viewer, (*id, player.team_id));
+                (*id, if hidden { player.reduced() } else { player.clone() })
+            })
+            .collect();
+        LobbySnapshot { players, ..self.clone() }
+    }
+
+    /// Same game state, whatever the tick
+    fn same_state(&self, other: &LobbySnapshot) -> bool {
+        self.players == other.players && self.team_scores == other.team_scores
+    }
+}
+
+/// Fields of `current` that differ from `baseline` (all of them without one)
+fn player_delta(baseline: Option<&PlayerSnapshot>, current: &PlayerSnapshot) -> Map<String, Value> {
+    let Value::Object(mut fields) = json!(current) else {
+        return Map::new();
+    };
+    if let Some(Value::Object(old)) = baseline.map(|b| json!(b)) {
+        fields.retain(|key, value| old.get(key) != Some(value));
+    }
+    fields
+}
+
+/// The `snapshot` packet taking a client from `baseline` to `current`
+/// None when nothing changed since the baseline
+pub fn delta(baseline: Option<&LobbySnapshot>, current: &LobbySnapshot) -> Option<Value> {
+    let mut players = Map::new();
+    for (id, player) in &current.players {
+        let fields = player_delta(baseline.and_then(|b| b.players.get(id)), player);
+        if !fields.is_empty() {
+            players.insert(id.to_string(), Value::Object(fields));
+        }
+    }
+    let removed: Vec<u32> = baseline
+        .map(|b| b.players.keys().filter(|id| !current.players.contains_key(id)).copied().collect())
+        .unwrap_or_default();
+    let scores_changed = baseline.is_none_or(|b| b.team_scores != current.team_scores);
+    if baseline.is_some() && players.is_empty() && removed.is_empty() && !scores_changed {
+        return None;
+    }
+
+    let mut packet = json!({
+        "type": "snapshot",
+        "tick": current.tick,
+        "baseline": baseline.map(|b| b.tick),
+        "players": players,
+    });
+    if !removed.is_empty() {
+        packet["removed"] = json!(removed);
+    }
+    if scores_changed && !current.team_scores.is_empty() {
+        packet["team_scores"] = current
+            .team_scores
+            .iter()
+            .map(|(team_id, score)| json!({"team_id": team_id, "score": score}))
+            .collect();
+    }
+    Some(packet)
+}
+
+/// Snapshot delivery state of one client
+#[derive(Debug, Default)]
+struct ClientSnapshots {
+    acked: Option<u64>, // Newest tick the client confirmed
+    last_sent: Option<u64>, // Tick of the last snapshot sent
+}
+
+/// Per-tick lobby snapshots and what each snapshot client has acked
+/// Clients get deltas against their last acked snapshot, so lost packets need no resend:
+/// the next delta covers them. Owned by the lobby's tick loop
+#[derive(Debug)]
+pub struct SnapshotSender {
+    history: VecDeque<LobbySnapshot>, // Oldest first
+    clients: HashMap<u32, ClientSnapshots>,
+    resend_interval_ticks: u64,
+}
+
+impl SnapshotSender {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            history: VecDeque::with_capacity(SNAPSHOT_HISTORY),
+            clients: HashMap::new(),
+            resend_interval_ticks: config.reliable_resend_interval_ticks.max(1),
+        }
+    }
+
+    /// Remember this tick's snapshot
+    pub fn record(&mut self, snapshot: LobbySnapshot) {
+        if self.history.len() >= SNAPSHOT_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+    }
+
+    /// Client confirmed it applied the snapshot of `tick`
+    pub fn ack(&mut self, player_id: u32, tick: u64) {
+        let client = self.clients.entry(player_id).or_default();
+        if client.acked.is_none_or(|acked| tick > acked) && client.last_sent.is_some_and(|sent| tick <= sent) {
+            client.acked = Some(tick);
+        }
+    }
+
+    /// Packet to send a client this tick, if any
+    /// Unchanged state is only repeated every resend interval until the client acks it
+    pub fn packet_for(&mut self, player_id: u32) -> Option<Value> {
+        let current = self.history.back()?;
+        let client = self.clients.entry(player_id).or_default();
+        let baseline = client.acked.and_then(|tick| self.history.iter().rev().find(|s| s.tick == tick));
+        let packet = if current.hide_enemy_state {
+            delta(baseline.map(|b| b.view_for(player_id)).as_ref(), &current.view_for(player_id))?
+        } else {
+            delta(baseline, current)?
+        };
+
+        if let Some(sent) = client.last_sent {
+            let unchanged = self.history.iter().rev().find(|s| s.tick == sent).is_some_and(|s| s.same_state(current));
+            if unchanged && current.tick < sent + self.resend_interval_ticks {
+                return None;
+            }
+        }
+        client.last_sent = Some(current.tick);
+        Some(packet)
+    }
+
+    /// Drop a client that left
+    pub fn forget(&mut self, player_id: u32) {
+        self.clients.remove(&player_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    fn sender() -> SnapshotSender {
+        SnapshotSender::new(&Config { reliable_resend_interval_ticks: 5, ..Config::default() })
+    }
+
+    #[test]
+    fn test_delta_only_carries_changes() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).with_player(PlayerBuilder::new().id(2)).build();
+        let before = LobbySnapshot::of(&lobby, 1);
+
+        let full = delta(None, &before).unwrap();
+        assert_eq!(full["baseline"], Value::Null);
+        assert_eq!(full["players"]["2"]["health"], 100);
+
+        lobby.players.get_mut(&1).unwrap().current_health = 80;
+        lobby.players.remove(&2);
+        let after = LobbySnapshot::of(&lobby, 2);
+        let packet = delta(Some(&before), &after).unwrap();
+        assert_eq!(packet["baseline"], 1);
+        assert_eq!(packet["players"], json!({"1": {"health": 80}}));
+        assert_eq!(packet["removed"], json!([2]));
+
+        assert_eq!(delta(Some(&after), &LobbySnapshot::of(&lobby, 3)), None);
+    }
+
+    #[test]
+    fn test_enemies_get_health_buckets_in_snapshots() {
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .with_player(PlayerBuilder::new().id(3))
+            .build();
+        for (id, team) in [(1, 1), (2, 1), (3, 2)] {
+            lobby.players.get_mut(&id).unwrap().team_id = Some(team);
+        }
+        lobby.hide_enemy_state = true;
+        let mut sender = sender();
+        sender.record(LobbySnapshot::of(&lobby, 1));
+        let packet = sender.packet_for(1).unwrap();
+        assert_eq!(packet["players"]["2"]["ammo"], 20);
+        assert_eq!(packet["players"]["3"]["health_bucket"], 4);
+        assert!(packet["players"]["3"].get("health").is_none());
+        assert!(packet["players"]["3"].get("ammo").is_none());
+        assert!(packet["players"]["3"].get("reloading").is_none());
+        sender.ack(1, 1);
+
+        // Enemy ammo and small health changes don't reach the viewer at all
+        let enemy = lobby.players.get_mut(&3).unwrap();
+        enemy.current_ammo = 5;
+        enemy.current_health = 90;
+        sender.record(LobbySnapshot::of(&lobby, 2));
+        assert!(sender.packet_for(1).is_none());
+        lobby.players.get_mut(&3).unwrap().current_health = 40;
+        sender.record(LobbySnapshot::of(&lobby, 3));
+        assert_eq!(sender.packet_for(1).unwrap()["players"], json!({"3": {"health_bucket": 2}}));
+    }
+
+    #[test]
+    fn test_deltas_are_against_the_last_acked_snapshot() {
+        let mut lobby = LobbyBuilder::new().with_player(PlayerBuilder::new()).build();
+        let mut sender = sender();
+
+        sender.record(LobbySnapshot::of(&lobby, 1));
+        assert_eq!(sender.packet_for(1).unwrap()["baseline"], Value::Null);
+        // Unacked and unchanged: repeated only after the resend interval
+        sender.record(LobbySnapshot::of(&lobby, 2));
+        assert!(sender.packet_for(1).is_none());
+        sender.ack(1, 1);
+
+        lobby.players.get_mut(&1).unwrap().current_ammo = 19;
+        sender.record(LobbySnapshot::of(&lobby, 3));
+        lobby.players.get_mut(&1).unwrap().current_ammo = 18;
+        sender.record(LobbySnapshot::of(&lobby, 4));
+        // Snapshot 3 was lost: the delta still starts from tick 1
+        let packet = sender.packet_for(1).unwrap();
+        assert_eq!(packet["baseline"], 1);
+        assert_eq!(packet["players"], json!({"1": {"ammo": 18}}));
+
+        sender.ack(1, 4);
+        sender.record(LobbySnapshot::of(&lobby, 5));
+        assert!(sender.packet_for(1).is_none());
+        sender.ack(1, 99); // Never sent
+        sender.record(LobbySnapshot::of(&lobby, 6));
+        assert!(sender.packet_for(1).is_none());
+    }
+}
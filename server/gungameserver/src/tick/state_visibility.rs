@@ -0,0 +1,54 @@
+use crate::state::lobby::Lobby;
+
+/// Enemies see health in this many steps (plus 0 for dead)
+pub const HEALTH_BUCKETS: u32 = 4;
+
+/// Health rounded up to a bucket: 0 dead, HEALTH_BUCKETS at full health
+pub fn health_bucket(health: u32, max_health: u32) -> u32 {
+    if health == 0 || max_health == 0 {
+        return 0;
+    }
+    (health.min(max_health) * HEALTH_BUCKETS).div_ceil(max_health)
+}
+
+/// Whether `viewer` gets `subject`'s exact health, ammo and reload state
+/// Always true for themselves and teammates; enemies only when the lobby doesn't hide their state
+pub fn sees_full_state(lobby: &Lobby, viewer_id: u32, subject_id: u32) -> bool {
+    let team = |id| lobby.players.get(&id).and_then(|p| p.team_id);
+    !hides_state_between(lobby.hide_enemy_state, (viewer_id, team(viewer_id)), (subject_id, team(subject_id)))
+}
+
+/// Same rule on player ids and team ids (as recorded in snapshots)
+pub fn hides_state_between(hide_enemy_state: bool, viewer: (u32, Option<u32>), subject: (u32, Option<u32>)) -> bool {
+    hide_enemy_state && viewer.0 != subject.0 && (viewer.1.is_none() || viewer.1 != subject.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    #[test]
+    fn test_enemies_only_see_health_buckets() {
+        assert_eq!(health_bucket(100, 100), 4);
+        assert_eq!(health_bucket(51, 100), 3);
+        assert_eq!(health_bucket(1, 100), 1);
+        assert_eq!(health_bucket(0, 100), 0);
+
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2))
+            .with_player(PlayerBuilder::new().id(3))
+            .build();
+        for (id, team) in [(1, 1), (2, 1), (3, 2)] {
+            lobby.players.get_mut(&id).unwrap().team_id = Some(team);
+        }
+        assert!(sees_full_state(&lobby, 1, 3));
+        lobby.hide_enemy_state = true;
+        assert!(sees_full_state(&lobby, 1, 1));
+        assert!(sees_full_state(&lobby, 1, 2));
+        assert!(!sees_full_state(&lobby, 1, 3));
+        assert!(!hides_state_between(true, (1, Some(1)), (2, Some(1))));
+        assert!(hides_state_between(true, (1, None), (2, None)));
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use crate::domain::logic::{KillEvent, ShotEvent};
+use crate::domain::projectiles::ProjectileEvent;
+use crate::state::lobby::{Lobby, MatchState};
+use crate::state::telemetry::{HeatCell, TelemetryEvent, TelemetryKind};
+
+/// Width of a heat map cell in world units
+pub const HEAT_CELL_SIZE: f32 = 10.0;
+/// How often living human players are sampled into the heat map
+const HEAT_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the accumulated heat map is emitted
+const HEAT_EMIT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Per-lobby state behind gameplay telemetry: first hits for time-to-kill and the heat map
+#[derive(Debug)]
+pub struct TelemetryTracker {
+    first_hits: HashMap<u32, Instant>, // Victim -> first hit taken since their last death
+    heat: BTreeMap<(i32, i32), u32>, // Cell -> samples
+    last_sample: Instant,
+    last_emit: Instant,
+}
+
+impl TelemetryTracker {
+    pub fn new(now: Instant) -> Self {
+        Self { first_hits: HashMap::new(), heat: BTreeMap::new(), last_sample: now, last_emit: now }
+    }
+
+    /// Turn one tick's shots, projectile hits and kills into events
+    pub fn observe(
+        &mut self,
+        lobby: &Lobby,
+        shots: &[ShotEvent],
+        projectiles: &[ProjectileEvent],
+        kills: &[KillEvent],
+        now: Instant,
+    ) -> Vec<TelemetryEvent> {
+        let mode = lobby.mode.as_str();
+        let event = |kind| TelemetryEvent::new(&lobby.scene, mode, kind);
+        let mut events = Vec::new();
+
+        let hits = shots
+            .iter()
+            .map(|shot| (shot.shooter_id, shot.weapon_id, shot.target_id.filter(|_| shot.hit)))
+            .chain(projectiles.iter().filter_map(|projectile| match projectile {
+                ProjectileEvent::Hit { owner_id, weapon_id, target_id, .. } => Some((*owner_id, *weapon_id, *target_id)),
+                ProjectileEvent::Spawned { .. } => None,
+            }));
+        for (shooter_id, weapon_id, target_id) in hits {
+            if let Some(target_id) = target_id {
+                self.first_hits.entry(target_id).or_insert(now);
+            }
+            let bot = lobby.bots.contains_key(&shooter_id);
+            events.push(event(TelemetryKind::WeaponUsed { weapon_id, hit: target_id.is_some(), bot }));
+        }
+
+        for kill in kills {
+            let distance = match (lobby.players.get(&kill.killer_id), lobby.players.get(&kill.victim_id)) {
+                (Some(killer), Some(victim)) => lobby.dimensions.distance(killer.position, victim.position),
+                _ => 0.0,
+            };
+            let time_to_kill_ms = self
+                .first_hits
+                .remove(&kill.victim_id)
+                .map(|first| now.saturating_duration_since(first).as_millis() as u64);
+            events.push(event(TelemetryKind::Kill {
+                weapon_id: kill.weapon_id,
+                distance,
+                time_to_kill_ms,
+                killer_bot: lobby.bots.contains_key(&kill.killer_id),
+                victim_bot: lobby.bots.contains_key(&kill.victim_id),
+            }));
+        }
+        self.first_hits.retain(|id, _| lobby.players.get(id).map(|p| !p.is_dead).unwrap_or(false));
+
+        if now.saturating_duration_since(self.last_sample) >= HEAT_SAMPLE_INTERVAL {
+            self.last_sample = now;
+            if matches!(lobby.match_state, MatchState::InProgress { .. }) {
+                let humans = lobby.players.values().filter(|p| !p.is_dead && !lobby.bots.contains_key(&p.id));
+                for player in humans {
+                    let cell = ((player.position.0 / HEAT_CELL_SIZE).floor() as i32, (player.position.2 / HEAT_CELL_SIZE).floor() as i32);
+                    *self.heat.entry(cell).or_insert(0) += 1;
+                }
+            }
+        }
+        if now.saturating_duration_since(self.last_emit) >= HEAT_EMIT_INTERVAL {
+            self.last_emit = now;
+            if !self.heat.is_empty() {
+                let cells = std::mem::take(&mut self.heat)
+                    .into_iter()
+                    .map(|((x, z), samples)| HeatCell { x, z, samples })
+                    .collect();
+                events.push(event(TelemetryKind::Heat { cell_size: HEAT_CELL_SIZE, cells }));
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{epoch, LobbyBuilder, PlayerBuilder};
+
+    #[test]
+    fn test_kill_distance_time_to_kill_and_heat() {
+        let t0 = epoch();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().id(1).position((0.0, 1.0, 0.0)))
+            .with_player(PlayerBuilder::new().id(2).position((3.0, 1.0, 4.0)))
+            .build();
+        lobby.match_state = MatchState::InProgress { round: 1, ends_at: t0 + Duration::from_secs(300) };
+        let mut tracker = TelemetryTracker::new(t0);
+
+        let shot = |hit| ShotEvent { shooter_id: 1, target_id: Some(2), weapon_id: 1, hit };
+        let events = tracker.observe(&lobby, &[shot(true)], &[], &[], t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, TelemetryKind::WeaponUsed { weapon_id: 1, hit: true, bot: false });
+        tracker.observe(&lobby, &[shot(false)], &[], &[], t0 + Duration::from_millis(200));
+
+        lobby.players.get_mut(&2).unwrap().is_dead = true;
+        let kill = KillEvent {
+            killer_id: 1,
+            killer_name: "A".to_string(),
+            victim_id: 2,
+            victim_name: "B".to_string(),
+            weapon_id: 1,
+            weapon_name: "Pistol".to_string(),
+            killer_new_killstreak: 1,
+        };
+        let events = tracker.observe(&lobby, &[], &[], &[kill], t0 + Duration::from_millis(600));
+        assert!(matches!(
+            events[0].kind,
+            TelemetryKind::Kill { time_to_kill_ms: Some(600), killer_bot: false, victim_bot: false, distance, .. } if (distance - 5.0).abs() < 1e-4
+        ));
+
+        // Player 1 sampled once a second for the 30s heat window; player 2 is dead
+        for second in 1..=30 {
+            let events = tracker.observe(&lobby, &[], &[], &[], t0 + Duration::from_secs(second));
+            if second == 30 {
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].kind, TelemetryKind::Heat { cell_size: HEAT_CELL_SIZE, cells: vec![HeatCell { x: 0, z: 0, samples: 30 }] });
+            } else {
+                assert!(events.is_empty());
+            }
+        }
+    }
+}
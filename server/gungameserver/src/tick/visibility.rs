@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use crate::domain::simulator;
+use crate::state::lobby::{Lobby, Player};
+use crate::utils::config::Config;
+
+/// Per-lobby visibility state for interest filtering of position updates
+/// Keyed by (observer_id, target_id)
+#[derive(Debug)]
+pub struct VisibilityTracker {
+    enabled: bool,
+    max_distance: f32,
+    half_fov_radians: f32,
+    grace_ticks: u64,
+    occluded_interval_ticks: u64,
+    last_visible: HashMap<(u32, u32), u64>,
+    last_sent: HashMap<(u32, u32), u64>,
+}
+
+impl VisibilityTracker {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            enabled: config.visibility_culling,
+            max_distance: config.visibility_max_distance,
+            half_fov_radians: (config.visibility_fov_degrees / 2.0).to_radians(),
+            grace_ticks: config.visibility_grace_ticks,
+            occluded_interval_ticks: config.occluded_update_interval_ticks.max(1),
+            last_visible: HashMap::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Check whether `target` is plausibly visible to `observer`
+    /// (within distance, inside the observer's FOV and not occluded by world geometry)
+    pub fn is_visible(&self, lobby: &Lobby, observer: &Player, target: &Player) -> bool {
+        if lobby.dimensions.distance(observer.position, target.position) > self.max_distance {
+            return false;
+        }
+
+        if self.half_fov_radians < std::f32::consts::PI {
+            let facing = simulator::facing_direction(observer.yaw(), observer.pitch());
+            if let Some(angle) = simulator::aim_angle(facing, observer.position, target.position) {
+                if angle > self.half_fov_radians {
+                    return false;
+                }
+            }
+        }
+
+        simulator::check_line_of_sight(&lobby.collision, observer.position, target.position)
+    }
+
+    /// Decide whether `observer` should receive `target`'s position this tick
+    ///
+    /// - visible targets are sent whenever they move, plus once when they (re)appear
+    /// - recently visible targets keep updating during the grace window
+    /// - occluded targets are only sent at the low occluded rate
+    pub fn should_send(
+        &mut self,
+        lobby: &Lobby,
+        observer: &Player,
+        target: &Player,
+        moved: bool,
+        tick: u64,
+    ) -> bool {
+        if !self.enabled {
+            return moved;
+        }
+
+        let pair = (observer.id, target.id);
+        let recently_visible = self
+            .last_visible
+            .get(&pair)
+            .map(|&t| tick.saturating_sub(t) <= self.grace_ticks)
+            .unwrap_or(false);
+
+        let send = if self.is_visible(lobby, observer, target) {
+            self.last_visible.insert(pair, tick);
+            // Force a fresh position when a target comes into view
+            moved || !recently_visible
+        } else if recently_visible {
+            moved
+        } else {
+            let due = self
+                .last_sent
+                .get(&pair)
+                .map(|&t| tick.saturating_sub(t) >= self.occluded_interval_ticks)
+                .unwrap_or(true);
+            moved && due
+        };
+
+        if send {
+            self.last_sent.insert(pair, tick);
+        }
+        send
+    }
+
+    /// Drop all state involving a player (call when they leave)
+    pub fn forget(&mut self, player_id: u32) {
+        self.last_visible.retain(|(o, t), _| *o != player_id && *t != player_id);
+        self.last_sent.retain(|(o, t), _| *o != player_id && *t != player_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::collision::{Aabb, CollisionWorld};
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+
+    fn setup() -> (Lobby, VisibilityTracker) {
+        // Target in front of the observer (facing -Z)
+        let lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new())
+            .with_player(PlayerBuilder::new().id(2).position((0.0, 1.0, -20.0)))
+            .build();
+        (lobby, VisibilityTracker::new(&Config::default()))
+    }
+
+    fn wall() -> CollisionWorld {
+        CollisionWorld { occluders: vec![Aabb { min: (-5.0, 0.0, -11.0), max: (5.0, 5.0, -10.0) }] }
+    }
+
+    fn send(tracker: &mut VisibilityTracker, lobby: &Lobby, moved: bool, tick: u64) -> bool {
+        let observer = lobby.players.get(&1).unwrap();
+        let target = lobby.players.get(&2).unwrap();
+        tracker.should_send(lobby, observer, target, moved, tick)
+    }
+
+    #[test]
+    fn test_visible_target_sent_every_move() {
+        let (lobby, mut tracker) = setup();
+        for tick in 0..5 {
+            assert!(send(&mut tracker, &lobby, true, tick));
+        }
+        assert!(!send(&mut tracker, &lobby, false, 5));
+    }
+
+    #[test]
+    fn test_occluded_target_sent_at_low_rate() {
+        let (mut lobby, mut tracker) = setup();
+        lobby.collision = wall();
+
+        let sent: Vec<u64> = (0..60).filter(|&tick| send(&mut tracker, &lobby, true, tick)).collect();
+        assert_eq!(sent, vec![0, 25, 50]);
+    }
+
+    #[test]
+    fn test_grace_window_then_reappear() {
+        let (mut lobby, mut tracker) = setup();
+        assert!(send(&mut tracker, &lobby, true, 0));
+
+        // Target steps behind a wall: still updated during the grace window
+        lobby.collision = wall();
+        assert!(send(&mut tracker, &lobby, true, 10));
+        // Grace expired and low-rate slot not due yet
+        assert!(!send(&mut tracker, &lobby, true, 30));
+
+        // Wall removed: target reappears and is sent even without moving
+        lobby.collision = CollisionWorld::default();
+        assert!(send(&mut tracker, &lobby, false, 31));
+    }
+
+    #[test]
+    fn test_out_of_range_and_behind() {
+        let (mut lobby, tracker) = setup();
+        lobby.players.get_mut(&2).unwrap().position = (0.0, 1.0, -500.0);
+        assert!(!tracker.is_visible(&lobby, lobby.players.get(&1).unwrap(), lobby.players.get(&2).unwrap()));
+
+        lobby.players.get_mut(&2).unwrap().position = (0.0, 1.0, 20.0);
+        assert!(!tracker.is_visible(&lobby, lobby.players.get(&1).unwrap(), lobby.players.get(&2).unwrap()));
+    }
+
+    #[test]
+    fn test_disabled_passes_moves_through() {
+        let (lobby, _) = setup();
+        let config = Config { visibility_culling: false, ..Config::default() };
+        let mut tracker = VisibilityTracker::new(&config);
+        assert!(send(&mut tracker, &lobby, true, 0));
+        assert!(!send(&mut tracker, &lobby, false, 1));
+    }
+
+    #[test]
+    fn test_forget_player() {
+        let (lobby, mut tracker) = setup();
+        send(&mut tracker, &lobby, true, 0);
+        tracker.forget(2);
+        assert!(tracker.last_visible.is_empty());
+        assert!(tracker.last_sent.is_empty());
+    }
+}
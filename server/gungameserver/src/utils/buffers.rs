@@ -27,6 +27,10 @@ pub enum SyncEvent {
         player_id: u32,
         is_reloading: bool,
     },
+    InventoryChanged {
+        player_id: u32,
+        weapons: Vec<u32>,
+    },
     PositionChanged {
         player_id: u32,
         position: (f32, f32, f32),
@@ -51,6 +55,13 @@ pub enum SyncEvent {
         deaths: u32,
         killstreak: u32,
     },
+    TeamChanged {
+        player_id: u32,
+        team_id: Option<u32>,
+    },
+    TeamScoresChanged {
+        scores: Vec<(u32, u32)>, // (team ID, score)
+    },
     PlayerKicked {
         player_id: u32,
         reason: String,
@@ -1,11 +1,183 @@
+use std::net::IpAddr;
+use crate::domain::mutators::Mutator;
+
+/// How game packets travel over the UDP port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    #[default]
+    JsonUdp, // One packet per datagram
+    Renet, // Packets as renet channel messages (reliable events on a reliable channel)
+}
+
+impl Transport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transport::JsonUdp => "json-udp",
+            Transport::Renet => "renet",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json-udp" => Some(Transport::JsonUdp),
+            "renet" => Some(Transport::Renet),
+            _ => None,
+        }
+    }
+}
+
+/// Which services this process runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Role {
+    #[default]
+    All, // Lobby API and simulation in one process
+    Web, // Lobby API only, forwarded over the bus to a simulation process
+    Simulation, // UDP, WebSocket and lobby tick loops; the lobby API is served on the bus
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::All => "all",
+            Role::Web => "web",
+            Role::Simulation => "simulation",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "all" => Some(Role::All),
+            "web" => Some(Role::Web),
+            "simulation" => Some(Role::Simulation),
+            _ => None,
+        }
+    }
+}
+
 /// Server configuration - immutable after load
+/// Loaded by `Config::load` (utils/config_loader.rs); new fields go in its settings list too
 #[derive(Debug, Clone)]
 pub struct Config {
     pub http_port: u16,
     pub udp_port: u16,
+    pub transport: Transport, // What the UDP port speaks; WebSocket clients are unaffected
+    pub udp_receivers: usize, // Receive tasks on the UDP port, each on its own SO_REUSEPORT socket (Unix)
+    pub role: Role, // Services run by this process (see `server::spawn_servers`)
+    pub bus_addr: String, // Internal bus between web and simulation processes: simulation listens, web connects
     pub tick_rate_hz: u32,
-    pub player_inactivity_timeout_secs: u64,
+    pub lobby_tick_rate_min_hz: u32, // Lowest tick rate a lobby may be created with (`tick_rate_hz` in the request)
+    pub lobby_tick_rate_max_hz: u32, // Highest tick rate a lobby may be created with
+    pub idle_tick_rate_hz: u32, // Tick rate of lobbies without connected UDP clients; 0 = always full rate
     pub max_lobbies: usize,
+    pub test_lobby_bots: u32, // Bots added to the default "test" lobby
+    pub quantize_positions: bool, // Snap stored positions to the millimeter grid
+    pub udp_encryption: bool, // Hand out UDP session keys to clients that ask on join
+    pub max_sessions_per_ip: usize, // Concurrent players from one IP across lobbies; 0 = unlimited
+    pub udp_rate_limit_pps: u32, // Packets per second accepted from one UDP source address; 0 = unlimited
+    pub udp_rate_limit_burst: u32, // Packets a source may send at once before the rate applies
+    pub udp_ban_strikes: u32, // Times a source may exceed its rate within a minute before it is banned; 0 = never
+    pub udp_ban_secs: u64, // How long banned sources are ignored
+    pub lobby_udp_port_min: u16, // Lobbies get their own UDP socket from this range; 0 = all share `udp_port`
+    pub lobby_udp_port_max: u16, // Last port of the range (inclusive); lobbies past it share `udp_port`
+    pub session_limit_exempt_ips: Vec<IpAddr>, // Admin override for known shared addresses
+    pub message_text: bool, // Send English text next to message codes (debugging only)
+    pub player_id_state_path: Option<String>, // Keeps player ids unique across restarts; None = restart at 1
+    pub reports_path: Option<String>, // Player reports, JSON lines; None = kept in memory only
+    pub storage_path: Option<String>, // SQLite checkpoint of open lobbies, restored on startup; None = lost on restart
+    pub checkpoint_secs: u64, // How often open lobbies are checkpointed to `storage_path`
+    pub tunables_path: Option<String>, // Gameplay tunables (JSON), reloadable live; None = built-in defaults
+    pub weapons_path: Option<String>, // Weapon definitions (JSON); None or no file = the bundled shared/data/weapons.json
+    pub gun_game_ladder: Vec<u32>, // Weapon ids in order; the knife is appended if missing. Empty = all guns by id
+    pub ladder_presets_path: Option<String>, // Named gun game ladders (JSON); None = kept in memory only
+    pub friendly_fire: bool, // Teammates can damage each other in team lobbies
+    pub hide_enemy_state: bool, // Default for lobbies: enemies see a health bucket, not exact health/ammo/reload
+    pub max_client_fov: f32, // Default for lobbies: widest FOV (degrees) a joining client may attest
+    pub aim_assist_allowed: bool, // Default for lobbies: clients may attest aim assistance
+    pub max_spectators: u32, // Spectators per lobby, on top of its player slots; 0 = no spectating
+    pub chat_blocked_words: Vec<String>, // Masked in chat messages (whole words, any case)
+    pub max_rewind_ms: u64, // Lag compensation: furthest back shots are checked (capped at 1000); 0 = no rewind
+    pub admin_token: Option<String>, // Bearer token for /admin endpoints; None disables them
+    pub account_token_secret: Option<String>, // Signs account tokens (JWT); None = random per start, logins don't survive restarts
+    pub account_token_ttl_secs: u64, // How long an account token stays valid
+    pub login_attempts_per_minute: u32, // Sign-ins allowed per client IP and per username; 0 = unlimited
+    pub login_attempt_burst: u32, // Sign-ins one IP or username may try at once before the rate applies
+    pub behind_tls_proxy: bool, // The HTTP API is only reachable through a TLS-terminating proxy; silences the plaintext warning
+
+    // Logging
+    pub log_level: log::LevelFilter, // Default level
+    pub log_module_levels: Vec<(String, log::LevelFilter)>, // Per-module overrides, e.g. ("handlers::udp", Trace)
+    pub log_stdout: bool,
+    pub log_file: Option<String>,
+    pub log_json: bool, // One JSON object per line instead of text, for log ingestion
+
+    // Visibility culling (anti wall-hack)
+    pub visibility_culling: bool,
+    pub visibility_max_distance: f32,
+    pub visibility_fov_degrees: f32,
+    pub visibility_grace_ticks: u64, // Keep sending this long after a target was last visible
+    pub occluded_update_interval_ticks: u64, // Low-rate updates for hidden players
+    pub relevance_radius: f32, // Players beyond this only get each other's positions at the far rate; 0 = no limit
+    pub far_update_interval_ticks: u64, // Low-rate updates for players outside the relevance radius
+    pub reduced_rate_interval_ticks: u64, // Position update interval for reduced-rate clients
+    pub reliable_resend_interval_ticks: u64, // Resend unacked reliable events after this many ticks
+    pub reliable_max_resends: u32, // Give up on an unacked event after this many resends
+
+    // Auto-scaling signals
+    pub scaling_signal_target: Option<String>, // http://host:port/path or a file path; None disables
+    pub scaling_signal_interval_secs: u64,
+
+    // Lobby analytics
+    pub lobby_analytics_path: Option<String>, // JSON lines file summaries are appended to
+    pub lobby_analytics_target: Option<String>, // Also deliver summaries here (http:// or file)
+    pub empty_lobby_close_secs: u64, // Close lobbies without human players this long; 0 = never
+    pub shutdown_grace_secs: u64, // On SIGTERM/Ctrl+C, how long clients get to leave before lobbies close
+
+    // Weapon balance report (built from the lobby analytics file)
+    pub balance_report_secs: u64, // Rebuild interval; 0 = only when requested
+    pub balance_min_kills: u64, // Weapons with fewer kills are reported but never flagged
+    pub balance_max_usage_share: f32, // Flag weapons firing more than this fraction of all shots
+    pub balance_min_average_ttk_ms: u64, // Flag weapons killing faster than this on average
+    pub balance_max_average_ttk_ms: u64, // Flag weapons killing slower than this on average
+    pub balance_max_kills_per_pickup: f32, // Flag pickup weapons scoring more kills per pickup than this
+
+    // Gameplay telemetry (opt-in, anonymized)
+    pub telemetry_target: Option<String>, // NDJSON file or http://host:port/path collector; None disables
+    pub telemetry_sample_rate: f32, // Fraction of events kept, 0.0-1.0
+    pub telemetry_flush_secs: u64, // Batch interval
+    pub telemetry_max_buffered: usize, // Events held between flushes; more are dropped
+
+    // Matchmaking
+    pub warm_pool_scenes: Vec<String>, // Scenes kept ready for `POST /matchmake`
+    pub warm_pool_size: usize, // Idle pre-created lobbies per scene; 0 = create on demand
+    pub matchmake_max_players: u32, // Capacity of matchmade lobbies
+    pub rematch_reservation_secs: u64, // Slots of a rematch lobby are held this long for the old lobby's players
+
+    // Match demos
+    pub demo_dir: String, // `<match id>.demo.json` files served by GET /matches/:id/demo
+    pub replay_dir: String, // `<lobby code>-<unix time>.replay` files of lobbies created with `record_replay`
+
+    // Match flow
+    pub match_min_players: usize, // Players (bots included) needed to start the countdown
+    pub match_ready_quorum_percent: u32, // Humans that must send `ready` before the countdown; 0 = start without ready-up
+    pub match_countdown_secs: u64,
+    pub round_duration_secs: u64,
+    pub rounds_per_match: u32,
+    pub score_limit: u32, // Ends the match early; 0 = no limit
+    pub round_intermission_secs: u64,
+    pub match_results_secs: u64, // Results shown before the next match is set up
+
+    // Horde mode (PvE waves)
+    pub horde_waves: u32, // Clearing the last wave wins the match
+    pub horde_lives: u32, // Respawns shared by the players
+    pub horde_first_wave_enemies: u32,
+    pub horde_enemies_per_wave: u32, // Added each wave
+    pub horde_health_per_wave: u32, // Enemy max health added each wave
+    pub horde_wave_break_secs: u64,
+
+    // Mutators
+    pub mutators: Vec<String>, // low_gravity, double_damage, weapon_roulette; empty disables them
+    pub mutator_interval_secs: u64, // Between one mutator ending and the next starting
+    pub mutator_duration_secs: u64,
 }
 
 impl Default for Config {
@@ -13,9 +185,102 @@ impl Default for Config {
         Self {
             http_port: 8080,
             udp_port: 8081,
+            transport: Transport::JsonUdp,
+            udp_receivers: 1,
+            role: Role::All,
+            bus_addr: "127.0.0.1:8090".to_string(),
             tick_rate_hz: 50, // 20ms per tick
-            player_inactivity_timeout_secs: 15,
+            lobby_tick_rate_min_hz: 10,
+            lobby_tick_rate_max_hz: 128,
+            idle_tick_rate_hz: 1,
             max_lobbies: 1000,
+            test_lobby_bots: 0,
+            quantize_positions: true,
+            udp_encryption: true,
+            max_sessions_per_ip: 8,
+            udp_rate_limit_pps: 120,
+            udp_rate_limit_burst: 60,
+            udp_ban_strikes: 5,
+            udp_ban_secs: 60,
+            lobby_udp_port_min: 0,
+            lobby_udp_port_max: 0,
+            session_limit_exempt_ips: Vec::new(),
+            message_text: false,
+            player_id_state_path: Some("player_ids.state".to_string()),
+            reports_path: Some("reports.jsonl".to_string()),
+            storage_path: Some("gungame.db".to_string()),
+            checkpoint_secs: 30,
+            tunables_path: Some("tunables.json".to_string()),
+            weapons_path: Some("weapons.json".to_string()),
+            gun_game_ladder: Vec::new(),
+            ladder_presets_path: Some("ladder_presets.json".to_string()),
+            friendly_fire: false,
+            hide_enemy_state: false,
+            max_client_fov: 110.0,
+            aim_assist_allowed: false,
+            max_spectators: 8,
+            chat_blocked_words: Vec::new(),
+            max_rewind_ms: 1000,
+            admin_token: None,
+            account_token_secret: None,
+            account_token_ttl_secs: 7 * 24 * 60 * 60,
+            login_attempts_per_minute: 10,
+            login_attempt_burst: 5,
+            behind_tls_proxy: false,
+            log_level: log::LevelFilter::Info,
+            log_module_levels: Vec::new(),
+            log_stdout: true,
+            log_file: Some("gungame.log".to_string()),
+            log_json: false,
+            visibility_culling: true,
+            visibility_max_distance: 150.0,
+            visibility_fov_degrees: 220.0,
+            visibility_grace_ticks: 25, // 0.5s at 50Hz
+            occluded_update_interval_ticks: 25, // 2Hz at 50Hz
+            relevance_radius: 100.0,
+            far_update_interval_ticks: 10, // 5Hz at 50Hz
+            reduced_rate_interval_ticks: 5, // 10Hz at 50Hz
+            reliable_resend_interval_ticks: 10, // 200ms at 50Hz
+            reliable_max_resends: 10,
+            scaling_signal_target: None,
+            scaling_signal_interval_secs: 10,
+            lobby_analytics_path: Some("lobby_analytics.jsonl".to_string()),
+            lobby_analytics_target: None,
+            empty_lobby_close_secs: 300,
+            shutdown_grace_secs: 10,
+            balance_report_secs: 600,
+            balance_min_kills: 20,
+            balance_max_usage_share: 0.5,
+            balance_min_average_ttk_ms: 250,
+            balance_max_average_ttk_ms: 4000,
+            balance_max_kills_per_pickup: 3.0,
+            telemetry_target: None,
+            telemetry_sample_rate: 1.0,
+            telemetry_flush_secs: 30,
+            telemetry_max_buffered: 50_000,
+            warm_pool_scenes: vec!["world".to_string()],
+            warm_pool_size: 2,
+            matchmake_max_players: 8,
+            rematch_reservation_secs: 60,
+            demo_dir: "demos".to_string(),
+            replay_dir: "replays".to_string(),
+            match_min_players: 2,
+            match_ready_quorum_percent: 100,
+            match_countdown_secs: 10,
+            round_duration_secs: 300,
+            rounds_per_match: 3,
+            score_limit: 2500,
+            round_intermission_secs: 5,
+            match_results_secs: 10,
+            horde_waves: 5,
+            horde_lives: 5,
+            horde_first_wave_enemies: 3,
+            horde_enemies_per_wave: 2,
+            horde_health_per_wave: 20,
+            horde_wave_break_secs: 8,
+            mutators: Vec::new(),
+            mutator_interval_secs: 120,
+            mutator_duration_secs: 30,
         }
     }
 }
@@ -28,6 +293,75 @@ impl Config {
     pub fn tick_interval_ms(&self) -> u64 {
         1000 / self.tick_rate_hz as u64
     }
+
+    /// Tick interval of a lobby with its own tick rate (None = the server's)
+    pub fn lobby_tick_interval_ms(&self, tick_rate_hz: Option<u32>) -> u64 {
+        1000 / tick_rate_hz.unwrap_or(self.tick_rate_hz).max(1) as u64
+    }
+
+    /// Reject settings the server can't run with
+    pub fn validate(&self) -> Result<(), String> {
+        if self.http_port == 0 || self.udp_port == 0 {
+            return Err("http_port and udp_port must be set".to_string());
+        }
+        if !(1..=1000).contains(&self.tick_rate_hz) {
+            return Err(format!("tick_rate_hz must be 1-1000, got {}", self.tick_rate_hz));
+        }
+        if self.lobby_tick_rate_min_hz == 0 || self.lobby_tick_rate_min_hz > self.lobby_tick_rate_max_hz || self.lobby_tick_rate_max_hz > 1000 {
+            return Err("lobby_tick_rate_min_hz..lobby_tick_rate_max_hz must be within 1-1000".to_string());
+        }
+        if self.idle_tick_rate_hz > self.tick_rate_hz {
+            return Err("idle_tick_rate_hz can't be above tick_rate_hz".to_string());
+        }
+        if self.lobby_udp_port_min > 0 {
+            if self.lobby_udp_port_max < self.lobby_udp_port_min {
+                return Err("lobby_udp_port_max is below lobby_udp_port_min".to_string());
+            }
+            if (self.lobby_udp_port_min..=self.lobby_udp_port_max).contains(&self.udp_port) {
+                return Err("lobby_udp_port_min..lobby_udp_port_max can't include udp_port".to_string());
+            }
+            if self.transport == Transport::Renet {
+                return Err("Dedicated lobby UDP ports need the json-udp transport".to_string());
+            }
+        }
+        if !(1..=64).contains(&self.udp_receivers) {
+            return Err(format!("udp_receivers must be 1-64, got {}", self.udp_receivers));
+        }
+        if self.max_lobbies == 0 {
+            return Err("max_lobbies must be at least 1".to_string());
+        }
+        if self.match_ready_quorum_percent > 100 {
+            return Err(format!("match_ready_quorum_percent must be 0-100, got {}", self.match_ready_quorum_percent));
+        }
+        if self.rounds_per_match == 0 {
+            return Err("rounds_per_match must be at least 1".to_string());
+        }
+        if !(self.visibility_fov_degrees > 0.0 && self.visibility_fov_degrees <= 360.0) {
+            return Err(format!("visibility_fov_degrees must be 0-360, got {}", self.visibility_fov_degrees));
+        }
+        if !(self.max_client_fov > 0.0 && self.max_client_fov <= 360.0) {
+            return Err(format!("max_client_fov must be 0-360, got {}", self.max_client_fov));
+        }
+        if !(0.0..=1.0).contains(&self.telemetry_sample_rate) {
+            return Err(format!("telemetry_sample_rate must be 0.0-1.0, got {}", self.telemetry_sample_rate));
+        }
+        if !(0.0..=1.0).contains(&self.balance_max_usage_share) {
+            return Err(format!("balance_max_usage_share must be 0.0-1.0, got {}", self.balance_max_usage_share));
+        }
+        if self.balance_min_average_ttk_ms > self.balance_max_average_ttk_ms {
+            return Err("balance_min_average_ttk_ms is above balance_max_average_ttk_ms".to_string());
+        }
+        if let Some(name) = self.mutators.iter().find(|name| Mutator::parse(name).is_none()) {
+            return Err(format!("mutators: unknown mutator {}", name));
+        }
+        if self.admin_token.as_deref() == Some("") {
+            return Err("admin_token can't be empty".to_string());
+        }
+        if self.account_token_secret.as_ref().is_some_and(|secret| secret.len() < 32) {
+            return Err("account_token_secret must be at least 32 characters".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -46,6 +380,22 @@ mod tests {
     fn test_tick_interval() {
         let config = Config::default();
         assert_eq!(config.tick_interval_ms(), 20);
+        assert_eq!(config.lobby_tick_interval_ms(Some(10)), 100);
+        assert_eq!(config.lobby_tick_interval_ms(None), 20);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Config::default().validate(), Ok(()));
+        assert!(Config { tick_rate_hz: 0, ..Config::default() }.validate().is_err());
+        assert!(Config { telemetry_sample_rate: 1.5, ..Config::default() }.validate().is_err());
+        assert!(Config { mutators: vec!["moon_boots".to_string()], ..Config::default() }.validate().is_err());
+        assert_eq!(Config { lobby_udp_port_min: 9000, lobby_udp_port_max: 9099, ..Config::default() }.validate(), Ok(()));
+        assert!(Config { lobby_udp_port_min: 8000, lobby_udp_port_max: 8100, ..Config::default() }.validate().is_err()); // Has udp_port
+        assert!(Config { lobby_udp_port_min: 9000, lobby_udp_port_max: 8999, ..Config::default() }.validate().is_err());
+        assert!(Config { lobby_tick_rate_min_hz: 60, lobby_tick_rate_max_hz: 30, ..Config::default() }.validate().is_err());
+        assert!(Config { lobby_tick_rate_max_hz: 2000, ..Config::default() }.validate().is_err());
+        assert!(Config { idle_tick_rate_hz: 100, ..Config::default() }.validate().is_err());
     }
 }
 
@@ -0,0 +1,497 @@
+use std::net::IpAddr;
+use std::path::Path;
+use crate::utils::config::{Config, Role, Transport};
+
+/// Config file read when neither `--config` nor `GUNGAME_CONFIG` names one
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Prefix of environment variables that override settings, e.g. `GUNGAME_HTTP_PORT`
+pub const ENV_PREFIX: &str = "GUNGAME_";
+
+/// A setting's value before it is converted to the field's type
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawValue {
+    Text(String), // Environment variables and flags; lists are comma separated
+    List(Vec<String>), // TOML arrays
+}
+
+/// A `Config` field type that can be read from and written to a config file
+trait Setting: Sized {
+    fn parse(raw: RawValue) -> Result<Self, String>;
+    fn render(&self) -> String;
+}
+
+fn text(raw: RawValue) -> Result<String, String> {
+    match raw {
+        RawValue::Text(text) => Ok(text),
+        RawValue::List(_) => Err("expected a single value, not a list".to_string()),
+    }
+}
+
+fn items(raw: RawValue) -> Vec<String> {
+    match raw {
+        RawValue::Text(text) if text.trim().is_empty() => Vec::new(),
+        RawValue::Text(text) => text.split(',').map(|item| item.trim().to_string()).collect(),
+        RawValue::List(items) => items,
+    }
+}
+
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+fn render_list(items: impl IntoIterator<Item = String>) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(", "))
+}
+
+macro_rules! number_setting {
+    ($($ty:ty),*) => {$(
+        impl Setting for $ty {
+            fn parse(raw: RawValue) -> Result<Self, String> {
+                let text = text(raw)?;
+                text.trim().parse().map_err(|_| format!("invalid number: {}", text))
+            }
+
+            fn render(&self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+
+number_setting!(u16, u32, u64, usize);
+
+impl Setting for f32 {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        let text = text(raw)?;
+        match text.trim().parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("invalid number: {}", text)),
+        }
+    }
+
+    fn render(&self) -> String {
+        // `{:?}` keeps the decimal point so the value reads back as a float
+        format!("{:?}", self)
+    }
+}
+
+impl Setting for bool {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        match text(raw)?.trim() {
+            "true" | "1" | "yes" | "on" => Ok(true),
+            "false" | "0" | "no" | "off" => Ok(false),
+            other => Err(format!("expected true or false, got {}", other)),
+        }
+    }
+
+    fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Setting for String {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        text(raw)
+    }
+
+    fn render(&self) -> String {
+        quote(self)
+    }
+}
+
+/// An empty value unsets the setting
+impl Setting for Option<String> {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        let text = text(raw)?;
+        Ok((!text.is_empty()).then_some(text))
+    }
+
+    fn render(&self) -> String {
+        quote(self.as_deref().unwrap_or(""))
+    }
+}
+
+impl Setting for Vec<String> {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        Ok(items(raw).into_iter().filter(|item| !item.is_empty()).collect())
+    }
+
+    fn render(&self) -> String {
+        render_list(self.iter().map(|item| quote(item)))
+    }
+}
+
+impl Setting for Vec<u32> {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        items(raw).iter().map(|item| item.parse().map_err(|_| format!("invalid number: {}", item))).collect()
+    }
+
+    fn render(&self) -> String {
+        render_list(self.iter().map(u32::to_string))
+    }
+}
+
+impl Setting for Vec<IpAddr> {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        items(raw).iter().map(|item| item.parse().map_err(|_| format!("invalid IP address: {}", item))).collect()
+    }
+
+    fn render(&self) -> String {
+        render_list(self.iter().map(|ip| quote(&ip.to_string())))
+    }
+}
+
+impl Setting for Transport {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        let text = text(raw)?;
+        Transport::parse(text.trim()).ok_or_else(|| format!("unknown transport {} (expected json-udp or renet)", text))
+    }
+
+    fn render(&self) -> String {
+        quote(self.as_str())
+    }
+}
+
+impl Setting for Role {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        let text = text(raw)?;
+        Role::parse(text.trim()).ok_or_else(|| format!("unknown role {} (expected all, web or simulation)", text))
+    }
+
+    fn render(&self) -> String {
+        quote(self.as_str())
+    }
+}
+
+impl Setting for log::LevelFilter {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        let text = text(raw)?;
+        text.trim().parse().map_err(|_| format!("unknown log level: {}", text))
+    }
+
+    fn render(&self) -> String {
+        quote(&self.as_str().to_lowercase())
+    }
+}
+
+/// `"module=level"` entries
+impl Setting for Vec<(String, log::LevelFilter)> {
+    fn parse(raw: RawValue) -> Result<Self, String> {
+        items(raw)
+            .iter()
+            .map(|item| {
+                let (module, level) = item.split_once('=').ok_or_else(|| format!("expected module=level, got {}", item))?;
+                let level = level.trim().parse().map_err(|_| format!("unknown log level: {}", level))?;
+                Ok((module.trim().to_string(), level))
+            })
+            .collect()
+    }
+
+    fn render(&self) -> String {
+        render_list(self.iter().map(|(module, level)| quote(&format!("{}={}", module, level.as_str().to_lowercase()))))
+    }
+}
+
+/// Settings that can be written in the config file, environment and flags
+/// Listing every `Config` field here is checked by the compiler
+macro_rules! settings {
+    ($($field:ident),* $(,)?) => {
+        /// Server command line: `--config <path>`, `--print-config`, and `--<setting> <value>` for every setting
+        #[derive(Debug, Default, clap::Parser)]
+        #[command(name = "gungameserver", about = "Authoritative game server for GunGame")]
+        pub struct Flags {
+            /// Config file (default: GUNGAME_CONFIG, else config.toml when it exists)
+            #[arg(long)]
+            pub config: Option<String>,
+            /// Print the merged settings in config file form and exit
+            #[arg(long)]
+            pub print_config: bool,
+            $(
+                #[arg(long, value_name = "VALUE", help = concat!("Override the `", stringify!($field), "` setting"))]
+                $field: Option<String>,
+            )*
+        }
+
+        impl Flags {
+            /// Settings given on the command line, by name
+            fn settings(self) -> Vec<(&'static str, String)> {
+                let mut settings = Vec::new();
+                $(
+                    if let Some(value) = self.$field {
+                        settings.push((stringify!($field), value));
+                    }
+                )*
+                settings
+            }
+        }
+
+        impl Config {
+            /// Overwrite one setting by name
+            pub fn set(&mut self, key: &str, raw: RawValue) -> Result<(), String> {
+                match key {
+                    $(stringify!($field) => self.$field = Setting::parse(raw).map_err(|e| format!("{}: {}", key, e))?,)*
+                    _ => return Err(format!("Unknown setting: {}", key)),
+                }
+                Ok(())
+            }
+
+            /// Every setting by name with its TOML value
+            fn entries(&self) -> Vec<(&'static str, String)> {
+                let Config { $($field),* } = self;
+                vec![$((stringify!($field), $field.render())),*]
+            }
+        }
+    };
+}
+
+settings!(
+    http_port, udp_port, transport, udp_receivers, role, bus_addr, tick_rate_hz, lobby_tick_rate_min_hz,
+    lobby_tick_rate_max_hz, idle_tick_rate_hz, max_lobbies, test_lobby_bots, quantize_positions,
+    udp_encryption, max_sessions_per_ip, udp_rate_limit_pps, udp_rate_limit_burst, udp_ban_strikes,
+    udp_ban_secs, lobby_udp_port_min, lobby_udp_port_max, session_limit_exempt_ips, message_text, player_id_state_path, reports_path,
+    storage_path, checkpoint_secs, tunables_path, weapons_path, gun_game_ladder, ladder_presets_path,
+    friendly_fire, hide_enemy_state, max_client_fov, aim_assist_allowed, max_spectators, chat_blocked_words,
+    max_rewind_ms, admin_token, account_token_secret, account_token_ttl_secs, login_attempts_per_minute,
+    login_attempt_burst, behind_tls_proxy,
+    log_level, log_module_levels, log_stdout, log_file, log_json,
+    visibility_culling, visibility_max_distance, visibility_fov_degrees, visibility_grace_ticks,
+    occluded_update_interval_ticks, relevance_radius, far_update_interval_ticks,
+    reduced_rate_interval_ticks, reliable_resend_interval_ticks, reliable_max_resends,
+    scaling_signal_target, scaling_signal_interval_secs,
+    lobby_analytics_path, lobby_analytics_target, empty_lobby_close_secs, shutdown_grace_secs,
+    balance_report_secs, balance_min_kills, balance_max_usage_share, balance_min_average_ttk_ms,
+    balance_max_average_ttk_ms, balance_max_kills_per_pickup,
+    telemetry_target, telemetry_sample_rate, telemetry_flush_secs, telemetry_max_buffered,
+    warm_pool_scenes, warm_pool_size, matchmake_max_players, rematch_reservation_secs,
+    demo_dir, replay_dir,
+    match_min_players, match_ready_quorum_percent, match_countdown_secs, round_duration_secs,
+    rounds_per_match, score_limit, round_intermission_secs, match_results_secs,
+    horde_waves, horde_lives, horde_first_wave_enemies, horde_enemies_per_wave, horde_health_per_wave,
+    horde_wave_break_secs,
+    mutators, mutator_interval_secs, mutator_duration_secs,
+);
+
+impl Flags {
+    /// Parse command line arguments (without the program name)
+    /// Setting names may use `_` between words as well as `-`
+    pub fn parse_args(args: &[String]) -> Result<Self, clap::Error> {
+        let args = args.iter().map(|arg| match arg.strip_prefix("--") {
+            Some(flag) => match flag.split_once('=') {
+                Some((name, value)) => format!("--{}={}", name.replace('_', "-"), value),
+                None => format!("--{}", flag.replace('_', "-")),
+            },
+            None => arg.clone(),
+        });
+        <Self as clap::Parser>::try_parse_from(std::iter::once("gungameserver".to_string()).chain(args))
+    }
+}
+
+impl Config {
+    /// Defaults, then the config file, then `GUNGAME_*` environment variables, then flags
+    /// The file is `--config <path>`, else `GUNGAME_CONFIG`, else `config.toml` when it exists
+    /// Flags are `--<setting> <value>` or `--<setting>=<value>`, with `-` or `_` between words
+    pub fn load(args: &[String], env: impl IntoIterator<Item = (String, String)>) -> Result<Self, String> {
+        let flags = Flags::parse_args(args).map_err(|e| e.to_string().trim_end().to_string())?;
+        Self::from_flags(flags, env)
+    }
+
+    /// `load`, with the command line already parsed
+    pub fn from_flags(flags: Flags, env: impl IntoIterator<Item = (String, String)>) -> Result<Self, String> {
+        let env: Vec<(String, String)> = env.into_iter().filter(|(key, _)| key.starts_with(ENV_PREFIX)).collect();
+
+        let explicit_path = flags
+            .config
+            .clone()
+            .or_else(|| env.iter().find(|(key, _)| key == "GUNGAME_CONFIG").map(|(_, path)| path.clone()));
+
+        let mut config = Config::default();
+        match &explicit_path {
+            Some(path) => config.apply_file(Path::new(path), true)?,
+            None => config.apply_file(Path::new(DEFAULT_CONFIG_PATH), false)?,
+        }
+        for (key, value) in &env {
+            if key == "GUNGAME_CONFIG" {
+                continue;
+            }
+            let setting = key[ENV_PREFIX.len()..].to_lowercase();
+            config.set(&setting, RawValue::Text(value.clone())).map_err(|e| format!("{}: {}", key, e))?;
+        }
+        for (key, value) in flags.settings() {
+            config.set(key, RawValue::Text(value)).map_err(|e| format!("--{}: {}", key.replace('_', "-"), e))?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Apply a TOML config file; a missing file is only an error when `required`
+    pub fn apply_file(&mut self, path: &Path, required: bool) -> Result<(), String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => return Ok(()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        for (key, value) in parse_toml(&contents).map_err(|e| format!("Invalid {}: {}", path.display(), e))? {
+            self.set(&key, value).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// The settings as a config file, for `--print-config`; the admin token and account token secret are left out
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in self.entries() {
+            let hidden = match key {
+                "admin_token" => self.admin_token.is_some(),
+                "account_token_secret" => self.account_token_secret.is_some(),
+                _ => false,
+            };
+            let line = if hidden {
+                format!("# {} is set (hidden)", key)
+            } else {
+                format!("{} = {}", key, value)
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Top-level `key = value` pairs of a TOML document
+/// Settings are strings, numbers, booleans and arrays of those; tables aren't used
+fn parse_toml(contents: &str) -> Result<Vec<(String, RawValue)>, String> {
+    let table: toml::Table = contents.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let raw = match value {
+                toml::Value::Array(items) => RawValue::List(items.into_iter().map(scalar).collect::<Option<_>>().ok_or_else(|| {
+                    format!("{}: array items must be strings, numbers or booleans", key)
+                })?),
+                value => RawValue::Text(scalar(value).ok_or_else(|| {
+                    format!("{}: expected a string, number, boolean or array (settings are top-level keys)", key)
+                })?),
+            };
+            Ok((key, raw))
+        })
+        .collect()
+}
+
+/// A string, number or boolean as setting text
+fn scalar(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(text) => Some(text),
+        toml::Value::Integer(n) => Some(n.to_string()),
+        toml::Value::Float(n) => Some(n.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_toml_settings() {
+        let mut pairs = parse_toml(
+            "# Server\nhttp_port = 9000 # comment\nadmin_token = \"a#b\\\"c\"\nlog_file = ''\n\
+             mutators = [\n  \"low_gravity\", # first\n  'double_damage',\n]\ntelemetry_max_buffered = 50_000\n\
+             max_client_fov = 90.5\n",
+        )
+        .unwrap();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(pairs, vec![
+            ("admin_token".to_string(), RawValue::Text("a#b\"c".to_string())),
+            ("http_port".to_string(), RawValue::Text("9000".to_string())),
+            ("log_file".to_string(), RawValue::Text(String::new())),
+            ("max_client_fov".to_string(), RawValue::Text("90.5".to_string())),
+            ("mutators".to_string(), RawValue::List(vec!["low_gravity".to_string(), "double_damage".to_string()])),
+            ("telemetry_max_buffered".to_string(), RawValue::Text("50000".to_string())),
+        ]);
+
+        assert!(parse_toml("[server]\nhttp_port = 1").unwrap_err().contains("top-level keys"));
+        assert!(parse_toml("mutators = [[\"a\"]]").unwrap_err().contains("array items"));
+        assert!(parse_toml("http_port = 1\nhttp_port = 2").is_err());
+        assert!(parse_toml("mutators = [\"a\"").is_err());
+        assert!(parse_toml("demo_dir = \"open").is_err());
+        assert!(parse_toml("demo_dir = demos").is_err());
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = std::env::temp_dir().join(format!("config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("server.toml");
+        std::fs::write(&path, "http_port = 9000\nudp_port = 9001\ntick_rate_hz = 30\nlog_file = \"\"\n\
+                               log_module_levels = [\"handlers::udp=trace\"]\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let config = Config::load(
+            &args(&["--config", path, "--tick_rate_hz", "60", "--print-config", "--transport=renet", "--role", "simulation"]),
+            env(&[("GUNGAME_UDP_PORT", "9100"), ("GUNGAME_TICK_RATE_HZ", "40"), ("HOME", "/root")]),
+        )
+        .unwrap();
+        assert_eq!(config.http_port, 9000); // File
+        assert_eq!(config.udp_port, 9100); // Environment over file
+        assert_eq!(config.tick_rate_hz, 60); // Flag over environment
+        assert_eq!(config.transport, Transport::Renet);
+        assert_eq!(config.role, Role::Simulation);
+        assert_eq!(config.log_file, None);
+        assert_eq!(config.log_module_levels, vec![("handlers::udp".to_string(), log::LevelFilter::Trace)]);
+        assert_eq!(config.max_lobbies, Config::default().max_lobbies);
+
+        // The file can also come from the environment
+        let config = Config::load(&[], env(&[("GUNGAME_CONFIG", path)])).unwrap();
+        assert_eq!(config.http_port, 9000);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_rejects_bad_settings() {
+        let missing = Config::load(&args(&["--config", "/nonexistent/gungame.toml"]), Vec::new()).unwrap_err();
+        assert!(missing.contains("Failed to read"), "{}", missing);
+        assert!(Config::load(&args(&["--htp-port", "1"]), Vec::new()).unwrap_err().contains("--htp-port"));
+        assert!(Config::load(&args(&["--http-port"]), Vec::new()).unwrap_err().contains("a value is required"));
+        assert!(Config::load(&args(&["stray"]), Vec::new()).unwrap_err().contains("stray"));
+        assert!(Config::load(&[], env(&[("GUNGAME_HTTP_PORT", "http")])).unwrap_err().contains("GUNGAME_HTTP_PORT"));
+        assert!(Config::load(&args(&["--tick-rate-hz", "0"]), Vec::new()).unwrap_err().contains("tick_rate_hz"));
+        assert!(Config::load(&args(&["--mutators", "low_gravity,moon_boots"]), Vec::new()).unwrap_err().contains("moon_boots"));
+        assert!(Config::load(&args(&["--role", "database"]), Vec::new()).unwrap_err().contains("unknown role"));
+    }
+
+    #[test]
+    fn test_printed_config_reads_back() {
+        let config = Config {
+            admin_token: Some("secret".to_string()),
+            account_token_secret: Some("another secret that is long enough".to_string()),
+            chat_blocked_words: vec!["a \"quoted\" word".to_string()],
+            session_limit_exempt_ips: vec!["10.0.0.1".parse().unwrap()],
+            gun_game_ladder: vec![1, 2, 3],
+            log_module_levels: vec![("net".to_string(), log::LevelFilter::Debug)],
+            ..Config::default()
+        };
+        let printed = config.to_toml();
+        assert!(!printed.contains("\"secret\"") && !printed.contains("another secret"));
+        assert!(printed.contains("scaling_signal_target = \"\"\n"));
+
+        let mut read_back = Config::default();
+        for (key, value) in parse_toml(&printed).unwrap() {
+            read_back.set(&key, value).unwrap();
+        }
+        let printed = printed
+            .replace("# admin_token is set (hidden)", "admin_token = \"\"")
+            .replace("# account_token_secret is set (hidden)", "account_token_secret = \"\"");
+        assert_eq!(read_back.to_toml(), printed);
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::RwLock;
+use log::LevelFilter;
+use crate::utils::config::Config;
+
+/// Crate prefix of log targets; module levels are matched without it (`handlers::udp`)
+const CRATE_TARGET: &str = "gungameserver::";
+
+/// Log level per module, falling back to a default level
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLevels {
+    pub default: LevelFilter,
+    pub modules: Vec<(String, LevelFilter)>, // Module path -> level, e.g. ("handlers::udp", Trace)
+}
+
+/// Levels the running logger filters with, changed live by the admin endpoint
+static LEVELS: RwLock<LogLevels> = RwLock::new(LogLevels::new(LevelFilter::Info));
+
+impl LogLevels {
+    pub const fn new(default: LevelFilter) -> Self {
+        Self { default, modules: Vec::new() }
+    }
+
+    /// Level of a log target: the most specific module prefix wins
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        let target = target.strip_prefix(CRATE_TARGET).unwrap_or(target);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    /// Set a module's level; `None` drops the override so the module uses the default again
+    pub fn set_module(&mut self, module: &str, level: Option<LevelFilter>) {
+        let module = module.strip_prefix(CRATE_TARGET).unwrap_or(module);
+        self.modules.retain(|(m, _)| m != module);
+        if let Some(level) = level {
+            self.modules.push((module.to_string(), level));
+            self.modules.sort();
+        }
+    }
+
+    /// Most verbose level anything is logged at
+    pub fn max(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+}
+
+/// Parse a level name (`off`, `error`, `warn`, `info`, `debug`, `trace`), case insensitive
+pub fn parse_level(name: &str) -> Result<LevelFilter, &'static str> {
+    name.parse().map_err(|_| "Unknown log level")
+}
+
+/// Current log levels
+pub fn levels() -> LogLevels {
+    LEVELS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Replace the log levels of the running logger
+pub fn set_levels(levels: LogLevels) {
+    // Records above the max level are dropped by the `log` macros before reaching the filter
+    log::set_max_level(levels.max());
+    *LEVELS.write().unwrap_or_else(|e| e.into_inner()) = levels;
+}
+
+/// Install the logger: stdout and/or `log_file`, as text or JSON lines
+pub fn setup(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let mut levels = LogLevels::new(config.log_level);
+    for (module, level) in &config.log_module_levels {
+        levels.set_module(module, Some(*level));
+    }
+
+    let json = config.log_json;
+    let mut dispatch = fern::Dispatch::new()
+        .format(move |out, message, record| {
+            if json {
+                out.finish(format_args!(
+                    "{}",
+                    serde_json::json!({
+                        "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                        "level": record.level().as_str(),
+                        "target": record.target(),
+                        "message": message.to_string(),
+                    })
+                ))
+            } else {
+                out.finish(format_args!(
+                    "{}[{}][{}] {}",
+                    chrono::Utc::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                    record.target(),
+                    record.level(),
+                    message
+                ))
+            }
+        })
+        .level(LevelFilter::Trace)
+        .filter(|metadata| {
+            let levels = LEVELS.read().unwrap_or_else(|e| e.into_inner());
+            metadata.level() <= levels.level_for(metadata.target())
+        });
+    if config.log_stdout {
+        dispatch = dispatch.chain(std::io::stdout());
+    }
+    if let Some(path) = &config.log_file {
+        dispatch = dispatch.chain(fern::log_file(path)?);
+    }
+    dispatch.apply()?;
+
+    set_levels(levels);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_most_specific_module_wins() {
+        let mut levels = LogLevels::new(LevelFilter::Info);
+        levels.set_module("handlers", Some(LevelFilter::Warn));
+        levels.set_module("gungameserver::handlers::udp", Some(LevelFilter::Trace));
+
+        assert_eq!(levels.level_for("gungameserver::handlers::udp"), LevelFilter::Trace);
+        assert_eq!(levels.level_for("gungameserver::handlers::http"), LevelFilter::Warn);
+        assert_eq!(levels.level_for("gungameserver::handlers_extra"), LevelFilter::Info);
+        assert_eq!(levels.level_for("gungameserver::tick::lobby_tick"), LevelFilter::Info);
+        assert_eq!(levels.max(), LevelFilter::Trace);
+
+        levels.set_module("handlers::udp", None);
+        assert_eq!(levels.level_for("gungameserver::handlers::udp"), LevelFilter::Warn);
+        assert_eq!(levels.max(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("TRACE"), Ok(LevelFilter::Trace));
+        assert_eq!(parse_level("off"), Ok(LevelFilter::Off));
+        assert!(parse_level("loud").is_err());
+    }
+}
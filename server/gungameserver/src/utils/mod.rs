@@ -1,4 +1,9 @@
 pub mod weapondb;
+pub mod scenedb;
 pub mod config;
+pub mod config_loader;
 pub mod buffers;
+pub mod logging;
+pub mod tunables;
 
+pub mod scaling;
@@ -0,0 +1,265 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::state::server_state::ServerState;
+
+/// Give up on a slow scaling endpoint after this long
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tick time as a fraction of the tick budget (1.0 = tick loop fully saturated)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TickSaturation {
+    pub average: f32,
+    pub max: f32,
+}
+
+/// Snapshot of server load for external auto-scaling
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScalingSignal {
+    pub timestamp: u64,
+    pub lobbies: usize,
+    pub players: usize, // Human players, bots excluded
+    pub bots: usize,
+    pub tick_saturation: TickSaturation,
+    pub draining: bool, // Refusing new lobbies and joins; scale in once players reaches 0
+    pub session_limit_rejections: u64, // UDP joins refused by the per-IP session cap since startup
+}
+
+impl ScalingSignal {
+    /// Collect the current signal from server state
+    pub async fn collect(state: &ServerState) -> Self {
+        let lobbies: Vec<_> = state.iter_lobbies().map(|entry| entry.lobby.clone()).collect();
+
+        let mut players = 0;
+        let mut bots = 0;
+        for lobby in &lobbies {
+            let lobby = lobby.read().await;
+            bots += lobby.bots.len();
+            players += lobby.players.len().saturating_sub(lobby.bots.len());
+        }
+
+        let loads = state.tick_loads();
+        let tick_saturation = if loads.is_empty() {
+            TickSaturation::default()
+        } else {
+            TickSaturation {
+                average: loads.iter().sum::<f32>() / loads.len() as f32,
+                max: loads.iter().copied().fold(0.0, f32::max),
+            }
+        };
+
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            lobbies: lobbies.len(),
+            players,
+            bots,
+            tick_saturation,
+            draining: state.is_draining(),
+            session_limit_rejections: state.ip_sessions.rejections(),
+        }
+    }
+}
+
+/// Where scaling signals and lobby summaries are delivered
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalingSink {
+    /// POST the signal as JSON to `http://host:port/path`
+    Http { host: String, path: String },
+    /// Atomically replace a file with the latest signal
+    File(PathBuf),
+}
+
+impl ScalingSink {
+    /// Parse a target: `http://...` is an endpoint, anything else a file path
+    pub fn parse(target: &str) -> Result<Self, &'static str> {
+        if target.starts_with("https://") {
+            return Err("https scaling endpoints are not supported, use http or a file");
+        }
+        match target.strip_prefix("http://") {
+            Some(rest) => {
+                let (authority, path) = match rest.find('/') {
+                    Some(i) => (&rest[..i], &rest[i..]),
+                    None => (rest, "/"),
+                };
+                if authority.is_empty() {
+                    return Err("Missing host in scaling endpoint");
+                }
+                let host = if authority.contains(':') {
+                    authority.to_string()
+                } else {
+                    format!("{}:80", authority)
+                };
+                Ok(Self::Http { host, path: path.to_string() })
+            }
+            None if target.is_empty() => Err("Empty scaling target"),
+            None => Ok(Self::File(PathBuf::from(target))),
+        }
+    }
+
+    /// Deliver one record (scaling signal or lobby summary)
+    pub async fn emit<T: Serialize>(&self, record: &T) -> Result<(), String> {
+        let body = serde_json::to_vec(record).map_err(|e| e.to_string())?;
+        match self {
+            Self::File(path) => {
+                let tmp = path.with_extension("tmp");
+                std::fs::write(&tmp, &body).map_err(|e| e.to_string())?;
+                std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+            }
+            Self::Http { host, path } => tokio::time::timeout(HTTP_TIMEOUT, post_json(host, path, &body))
+                .await
+                .map_err(|_| format!("Timed out posting to {}", host))?,
+        }
+    }
+}
+
+/// Minimal HTTP/1.1 POST (one request per connection)
+async fn post_json(host: &str, path: &str, body: &[u8]) -> Result<(), String> {
+    let mut stream = TcpStream::connect(host).await.map_err(|e| e.to_string())?;
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path,
+        host,
+        body.len()
+    )
+    .into_bytes();
+    request.extend_from_slice(body);
+    stream.write_all(&request).await.map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.map_err(|e| e.to_string())?;
+    let status_line = String::from_utf8_lossy(&response);
+    let status = status_line.split_whitespace().nth(1).unwrap_or("");
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(format!("Scaling endpoint {} answered {:?}", host, status))
+    }
+}
+
+/// Periodically emit scaling signals until the task is dropped
+pub async fn run_scaling_reporter(state: Arc<ServerState>, sink: ScalingSink, interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+    loop {
+        timer.tick().await;
+        let signal = ScalingSignal::collect(&state).await;
+        if let Err(e) = sink.emit(&signal).await {
+            log::warn!("Failed to emit scaling signal: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::lobbies;
+    use crate::state::server_state::LobbyHandle;
+    use crate::state::snapshot::Snapshot;
+    use crate::testutil::{LobbyBuilder, PlayerBuilder};
+    use crate::utils::weapondb::WeaponDb;
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, RwLock};
+
+    fn signal() -> ScalingSignal {
+        ScalingSignal {
+            timestamp: 1,
+            lobbies: 2,
+            players: 5,
+            bots: 1,
+            tick_saturation: TickSaturation { average: 0.25, max: 0.5 },
+            draining: true,
+            session_limit_rejections: 3,
+        }
+    }
+
+    #[test]
+    fn test_parse_sink() {
+        assert_eq!(
+            ScalingSink::parse("http://scaler:9000/signals").unwrap(),
+            ScalingSink::Http { host: "scaler:9000".to_string(), path: "/signals".to_string() }
+        );
+        assert_eq!(
+            ScalingSink::parse("http://scaler").unwrap(),
+            ScalingSink::Http { host: "scaler:80".to_string(), path: "/".to_string() }
+        );
+        assert_eq!(
+            ScalingSink::parse("/var/run/gungame/scaling.json").unwrap(),
+            ScalingSink::File(PathBuf::from("/var/run/gungame/scaling.json"))
+        );
+        assert!(ScalingSink::parse("https://scaler").is_err());
+        assert!(ScalingSink::parse("http:///signals").is_err());
+        assert!(ScalingSink::parse("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_collect_counts_players_and_saturation() {
+        let state = ServerState::new();
+        assert_eq!(ScalingSignal::collect(&state).await.tick_saturation, TickSaturation::default());
+
+        let weapons = WeaponDb::load();
+        let mut lobby = LobbyBuilder::new()
+            .with_player(PlayerBuilder::new().id(1))
+            .with_player(PlayerBuilder::new().id(2))
+            .build();
+        lobbies::add_bot(&mut lobby, 3, "Bot".to_string(), &weapons).unwrap();
+        let (command_tx, _rx) = mpsc::channel(1);
+        state.insert_lobby("A".to_string(), LobbyHandle {
+            lobby: Arc::new(RwLock::new(lobby)),
+            command_tx,
+            task_handle: tokio::spawn(async {}),
+            udp_tasks: Vec::new(),
+            socket: None,
+            listing: Snapshot::default(),
+        });
+
+        state.record_tick_load("A", 0.2);
+        state.record_tick_load("B", 0.6);
+        state.set_draining(true);
+
+        let signal = ScalingSignal::collect(&state).await;
+        assert_eq!(signal.lobbies, 1);
+        assert_eq!(signal.players, 2);
+        assert_eq!(signal.bots, 1);
+        assert!((signal.tick_saturation.average - 0.4).abs() < 1e-6);
+        assert!((signal.tick_saturation.max - 0.6).abs() < 1e-6);
+        assert!(signal.draining);
+    }
+
+    #[tokio::test]
+    async fn test_emit_to_file() {
+        let path = std::env::temp_dir().join(format!("gungame-scaling-{}.json", std::process::id()));
+        ScalingSink::File(path.clone()).emit(&signal()).await.unwrap();
+
+        let written: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["players"], 5);
+        assert_eq!(written["tick_saturation"]["max"], 0.5);
+        assert_eq!(written["draining"], true);
+        assert_eq!(written["session_limit_rejections"], 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_emit_to_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let len = stream.read(&mut request).await.unwrap();
+            stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+
+        let sink = ScalingSink::Http { host, path: "/signals".to_string() };
+        sink.emit(&signal()).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /signals HTTP/1.1"));
+        assert!(request.contains("\"lobbies\":2"));
+    }
+}
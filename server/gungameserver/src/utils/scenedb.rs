@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Scene asset shared with the client (shared/data/scenes.json)
+const SCENES_JSON: &str = include_str!("../../../../shared/data/scenes.json");
+
+/// A playable scene and where players spawn in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneData {
+    pub name: String,
+    pub spawn_points: Vec<(f32, f32, f32)>,
+    #[serde(default)]
+    pub enemy_spawn_points: Vec<(f32, f32, f32)>, // Horde waves; none = the player spawn points
+}
+
+#[derive(Deserialize)]
+struct SceneFile {
+    data: Vec<SceneData>,
+}
+
+/// Immutable scene database - loaded once at startup
+#[derive(Debug, Clone)]
+pub struct SceneDb {
+    scenes: HashMap<String, SceneData>,
+}
+
+impl SceneDb {
+    /// Load the scenes bundled from the shared scene asset
+    pub fn load() -> Self {
+        Self::from_json(SCENES_JSON).expect("shared/data/scenes.json is invalid")
+    }
+
+    /// Parse a scene asset (`{"data": [{"name", "spawn_points"}]}`)
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let file: SceneFile = serde_json::from_str(json)?;
+        let scenes = file.data.into_iter().map(|scene| (scene.name.clone(), scene)).collect();
+        Ok(Self { scenes })
+    }
+
+    /// Get scene by name
+    pub fn get(&self, name: &str) -> Option<&SceneData> {
+        self.scenes.get(name)
+    }
+
+    /// Spawn points of a scene (empty for unknown scenes, which spawn at the origin)
+    pub fn spawn_points(&self, name: &str) -> Vec<(f32, f32, f32)> {
+        self.get(name).map(|scene| scene.spawn_points.clone()).unwrap_or_default()
+    }
+
+    /// Where horde waves spawn in a scene (empty for unknown scenes or scenes without any)
+    pub fn enemy_spawn_points(&self, name: &str) -> Vec<(f32, f32, f32)> {
+        self.get(name).map(|scene| scene.enemy_spawn_points.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_scenes_have_spawn_points() {
+        let scenes = SceneDb::load();
+        let world = scenes.get("world").unwrap();
+        assert!(world.spawn_points.len() >= 4);
+        assert!(!world.enemy_spawn_points.is_empty());
+        assert!(scenes.spawn_points("nowhere").is_empty());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let scenes = SceneDb::from_json(r#"{"data": [{"name": "arena", "spawn_points": [[1.0, 1.0, 2.0]]}]}"#).unwrap();
+        assert_eq!(scenes.spawn_points("arena"), vec![(1.0, 1.0, 2.0)]);
+        assert!(SceneDb::from_json("{}").is_err());
+    }
+}
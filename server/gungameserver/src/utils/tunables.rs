@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Gameplay numbers tuned during playtests, loaded from a JSON file and changeable live
+/// Missing fields keep their defaults
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tunables {
+    pub inactivity_timeout_secs: u64, // Players silent this long are disconnected (removed without a reconnect window)
+    pub reconnect_window_secs: u64, // Disconnected players keep their slot this long for a `reconnect`; 0 removes them
+    pub inactivity_warning_fraction: f64, // Warn after this fraction of the timeout
+    pub respawn_delay_ms: u64, // Time dead players wait before respawning
+    pub max_damage: u32, // Largest single hit the server accepts
+    pub hitbox_radius: f32, // Sphere hitbox around each player's position
+    pub max_shot_origin_offset: f32, // Furthest a shot may start from the shooter's server position
+    pub bot_move_speed: f32, // Units per second
+    pub pickup_respawn_secs: u64, // Time a taken pickup stays gone
+    pub challenge_interval_secs: u64, // How often clients get a verification challenge; 0 = never
+    pub challenge_timeout_ms: u64, // Time a client has to answer one
+    pub loading_timeout_secs: u64, // Joining players who never send `loading_complete` are let in after this long; 0 = no loading gate
+    pub grenades_per_life: u32, // Throws a player gets between respawns; 0 = no grenades
+    pub grenade_damage: u32, // Explosion damage at the center, falling off to none at the radius
+    pub grenade_radius: f32,
+    pub grenade_fuse_ms: u64, // Time from the throw to the explosion
+    pub grenade_throw_speed: f32, // Units per second
+    pub grenade_gravity: f32, // Downward acceleration, units per second squared
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Self {
+            inactivity_timeout_secs: 15,
+            reconnect_window_secs: 30,
+            inactivity_warning_fraction: 0.5,
+            respawn_delay_ms: 3000,
+            max_damage: 100,
+            hitbox_radius: 0.5,
+            max_shot_origin_offset: 2.0,
+            bot_move_speed: 5.0,
+            pickup_respawn_secs: 20,
+            challenge_interval_secs: 0,
+            challenge_timeout_ms: 2000,
+            loading_timeout_secs: 15,
+            grenades_per_life: 2,
+            grenade_damage: 80,
+            grenade_radius: 5.0,
+            grenade_fuse_ms: 2500,
+            grenade_throw_speed: 15.0,
+            grenade_gravity: 9.8,
+        }
+    }
+}
+
+impl Tunables {
+    pub fn respawn_delay(&self) -> Duration {
+        Duration::from_millis(self.respawn_delay_ms)
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.inactivity_timeout_secs == 0 {
+            return Err("inactivity_timeout_secs must be positive");
+        }
+        if !(0.0..=1.0).contains(&self.inactivity_warning_fraction) {
+            return Err("inactivity_warning_fraction must be between 0 and 1");
+        }
+        if self.challenge_timeout_ms == 0 {
+            return Err("challenge_timeout_ms must be positive");
+        }
+        if self.max_damage == 0 {
+            return Err("max_damage must be positive");
+        }
+        let distances = [self.hitbox_radius, self.max_shot_origin_offset, self.bot_move_speed, self.grenade_radius, self.grenade_throw_speed];
+        if distances.iter().any(|d| !d.is_finite() || *d <= 0.0) {
+            return Err("Distances and speeds must be positive");
+        }
+        if !self.grenade_gravity.is_finite() || self.grenade_gravity < 0.0 {
+            return Err("grenade_gravity must not be negative");
+        }
+        if self.grenade_damage == 0 || self.grenade_fuse_ms == 0 {
+            return Err("grenade_damage and grenade_fuse_ms must be positive");
+        }
+        Ok(())
+    }
+
+    /// Parse and validate a tunables file's contents
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let tunables: Tunables = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        tunables.validate()?;
+        Ok(tunables)
+    }
+
+    /// Apply the fields of a JSON object over these tunables
+    pub fn merged(&self, changes: &serde_json::Value) -> Result<Self, String> {
+        let mut value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        let (Some(fields), Some(changes)) = (value.as_object_mut(), changes.as_object()) else {
+            return Err("Expected a JSON object".to_string());
+        };
+        for (key, change) in changes {
+            fields.insert(key.clone(), change.clone());
+        }
+        Self::from_json(&value.to_string())
+    }
+}
+
+/// The tunables in effect; lobbies pick up changes on their next tick
+#[derive(Debug, Default)]
+pub struct TunablesStore {
+    current: RwLock<Arc<Tunables>>,
+    revision: AtomicU64, // Bumped on every change
+    path: Mutex<Option<PathBuf>>, // File `reload` reads
+}
+
+impl TunablesStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load tunables from `path` and remember it for reloads
+    /// A missing file keeps the defaults
+    pub fn load(&self, path: &Path) -> Result<Arc<Tunables>, String> {
+        *self.path.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.to_path_buf());
+        self.reload()
+    }
+
+    /// Re-read the tunables file
+    pub fn reload(&self) -> Result<Arc<Tunables>, String> {
+        let tunables = self.read()?;
+        Ok(self.set(tunables))
+    }
+
+    /// Whether a tunables file was loaded (and `reload` has one to read)
+    pub fn has_file(&self) -> bool {
+        self.path.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    /// Parse the tunables file without applying it
+    pub fn read(&self) -> Result<Tunables, String> {
+        let path = self.path.lock().unwrap_or_else(|e| e.into_inner()).clone().ok_or("No tunables file")?;
+        match std::fs::read_to_string(&path) {
+            Ok(json) => Tunables::from_json(&json).map_err(|e| format!("Invalid {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Tunables::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Replace the tunables in effect
+    pub fn set(&self, tunables: Tunables) -> Arc<Tunables> {
+        let tunables = Arc::new(tunables);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = tunables.clone();
+        self.revision.fetch_add(1, Ordering::Relaxed);
+        tunables
+    }
+
+    pub fn get(&self) -> Arc<Tunables> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Changes so far; compare to notice a change
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_partial_files_and_changes_keep_defaults() {
+        let tunables = Tunables::from_json(r#"{"respawn_delay_ms": 1500}"#).unwrap();
+        assert_eq!(tunables.respawn_delay(), Duration::from_millis(1500));
+        assert_eq!(tunables.max_damage, 100);
+
+        let changed = tunables.merged(&json!({"max_damage": 150})).unwrap();
+        assert_eq!(changed.max_damage, 150);
+        assert_eq!(changed.respawn_delay_ms, 1500);
+
+        assert!(tunables.merged(&json!({"max_speed": 9.0})).is_err());
+        assert!(tunables.merged(&json!({"hitbox_radius": -1.0})).is_err());
+    }
+
+    #[test]
+    fn test_reload_reads_the_file_again() {
+        let path = std::env::temp_dir().join(format!("gungame-tunables-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let store = TunablesStore::new();
+        assert_eq!(*store.load(&path).unwrap(), Tunables::default());
+        let revision = store.revision();
+
+        std::fs::write(&path, r#"{"bot_move_speed": 7.5}"#).unwrap();
+        assert_eq!(store.reload().unwrap().bot_move_speed, 7.5);
+        assert!(store.revision() > revision);
+
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(store.reload().is_err());
+        assert_eq!(store.get().bot_move_speed, 7.5);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,18 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Deserialize, Serialize};
+use crate::domain::simulator::HitZone;
+
+/// Weapon asset shared with the client (shared/data/weapons.json)
+const WEAPONS_JSON: &str = include_str!("../../../../shared/data/weapons.json");
+
+/// Largest damage a single shot of a weapon may deal before multipliers
+const MAX_WEAPON_DAMAGE: u32 = 1000;
+
+/// Largest headshot multiplier a weapon may have
+const MAX_HEADSHOT_MULTIPLIER: f32 = 10.0;
 
 /// Weapon data structure matching client weapon.json
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,7 +23,146 @@ pub struct WeaponData {
     pub fire_rate: f32,
     pub range: f32,
     pub reload_time: f32,
-    pub ammo: u32,
+    pub ammo: u32, // Magazine size, 0 for melee weapons
+    #[serde(default)]
+    pub melee: bool,
+    #[serde(default = "default_headshot_multiplier")]
+    pub headshot_multiplier: f32, // Damage factor for hits on the upper part of the hitbox
+    #[serde(default = "default_leg_multiplier")]
+    pub leg_multiplier: f32, // Damage factor for hits on the lower part of the hitbox
+    #[serde(default)]
+    pub falloff: Option<DamageFalloff>, // None = full damage over the whole range
+    #[serde(default)]
+    pub cosmetics: WeaponCosmetics,
+    #[serde(default)]
+    pub recoil: Option<RecoilPattern>, // Automatic weapons only
+    #[serde(default)]
+    pub projectile: Option<ProjectileSpec>, // None = hitscan
+}
+
+/// Damage lost over distance: full up to `start`, dropping linearly to `min_multiplier` at `end` and beyond
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DamageFalloff {
+    pub start: f32,
+    pub end: f32,
+    pub min_multiplier: f32,
+}
+
+impl DamageFalloff {
+    /// Damage factor for a hit `distance` away
+    pub fn multiplier(&self, distance: f32) -> f32 {
+        let progress = ((distance - self.start) / (self.end - self.start)).clamp(0.0, 1.0);
+        1.0 - progress * (1.0 - self.min_multiplier)
+    }
+}
+
+/// Flight of a weapon's projectile (rockets, grenades), simulated by the server every tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectileSpec {
+    pub speed: f32, // Units per second at launch
+    pub gravity: f32, // Downward acceleration, units per second squared (0 = straight line)
+    pub lifetime_secs: f32, // Removed without a hit after this long
+}
+
+/// Aim climb an automatic weapon forces on every shot of a burst
+/// Clients apply the kick to their camera; the server checks reported aim follows it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoilPattern {
+    pub kick_deg: f32, // Upward pitch per shot
+    pub tolerance_deg: f32, // Allowed deviation of the average climb per shot (player compensation)
+}
+
+/// Client presentation of a weapon, no effect on gameplay
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WeaponCosmetics {
+    pub scene_path: String, // Godot scene of the held weapon
+    pub position_offset: [f32; 3], // Held weapon offset from the camera
+    pub muzzle_flash_id: Option<String>, // None for melee
+    pub sound_id: String, // Played on every shot or swing
+    pub tracer: Option<TracerSettings>,
+}
+
+/// Bullet tracer drawn from the muzzle to the hit point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TracerSettings {
+    pub color: String, // #RRGGBB
+    pub width: f32,
+    pub speed: f32, // Units per second
+}
+
+fn default_headshot_multiplier() -> f32 {
+    1.0
+}
+
+fn default_leg_multiplier() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct WeaponFile {
+    data: Vec<WeaponData>,
+}
+
+impl WeaponData {
+    /// Melee weapons have no magazine and never reload
+    pub fn is_melee(&self) -> bool {
+        self.melee
+    }
+
+    /// Damage factor for a hit in `zone`, `distance` away
+    pub fn damage_multiplier(&self, zone: HitZone, distance: f32) -> f32 {
+        let zone_multiplier = match zone {
+            HitZone::Head => self.headshot_multiplier,
+            HitZone::Body => 1.0,
+            HitZone::Legs => self.leg_multiplier,
+        };
+        zone_multiplier * self.falloff.as_ref().map_or(1.0, |falloff| falloff.multiplier(distance))
+    }
+
+    /// Damage of a hit, before lobby-wide multipliers
+    pub fn hit_damage(&self, zone: HitZone, distance: f32) -> u32 {
+        (self.damage as f32 * self.damage_multiplier(zone, distance)).round() as u32
+    }
+
+    /// Reject numbers the simulation can't work with
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty");
+        }
+        if self.damage == 0 || self.damage > MAX_WEAPON_DAMAGE {
+            return Err("damage must be between 1 and 1000");
+        }
+        if !self.fire_rate.is_finite() || self.fire_rate <= 0.0 {
+            return Err("fire_rate must be positive");
+        }
+        if !self.range.is_finite() || self.range <= 0.0 {
+            return Err("range must be positive");
+        }
+        if !self.reload_time.is_finite() || self.reload_time < 0.0 {
+            return Err("reload_time must not be negative");
+        }
+        if !self.headshot_multiplier.is_finite() || !(1.0..=MAX_HEADSHOT_MULTIPLIER).contains(&self.headshot_multiplier) {
+            return Err("headshot_multiplier must be between 1 and 10");
+        }
+        if !self.leg_multiplier.is_finite() || self.leg_multiplier <= 0.0 || self.leg_multiplier > 1.0 {
+            return Err("leg_multiplier must be above 0 and at most 1");
+        }
+        if let Some(falloff) = &self.falloff {
+            if !falloff.start.is_finite() || !falloff.end.is_finite() || falloff.start < 0.0 || falloff.end <= falloff.start {
+                return Err("falloff must start at 0 or later and end after it starts");
+            }
+            if !falloff.min_multiplier.is_finite() || falloff.min_multiplier <= 0.0 || falloff.min_multiplier > 1.0 {
+                return Err("falloff min_multiplier must be above 0 and at most 1");
+            }
+        }
+        if self.melee != (self.ammo == 0) {
+            return Err("melee weapons must have ammo 0 and guns a positive ammo");
+        }
+        if self.melee && self.projectile.is_some() {
+            return Err("melee weapons can't fire projectiles");
+        }
+        Ok(())
+    }
 }
 
 /// Immutable weapon database - loaded once at startup
@@ -21,42 +173,35 @@ pub struct WeaponDb {
 }
 
 impl WeaponDb {
-    /// Load weapon database with hardcoded data
-    /// In production, this would load from a config file
+    /// Load the weapons bundled from the shared weapon asset
     pub fn load() -> Self {
-        let mut weapons = HashMap::new();
+        Self::from_json(WEAPONS_JSON).expect("shared/data/weapons.json is invalid")
+    }
 
-        weapons.insert(1, WeaponData {
-            id: 1,
-            name: "Golden Friend".to_string(),
-            damage: 20,
-            fire_rate: 4.0,
-            range: 100.0,
-            reload_time: 1.0,
-            ammo: 20,
-        });
-
-        weapons.insert(2, WeaponData {
-            id: 2,
-            name: "Prototype".to_string(),
-            damage: 30,
-            fire_rate: 2.0,
-            range: 150.0,
-            reload_time: 1.5,
-            ammo: 8,
-        });
-
-        weapons.insert(3, WeaponData {
-            id: 3,
-            name: "Combat Knife".to_string(),
-            damage: 50,
-            fire_rate: 1.5,
-            range: 3.0,
-            reload_time: 0.0,
-            ammo: 0, // Melee weapon, no ammo limit
-        });
-
-        Self { weapons }
+    /// Load a weapons file, or the bundled weapons if there is none at `path`
+    pub fn load_file(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Self::from_json(&json).map_err(|e| format!("Invalid {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::load()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Parse and validate a weapon asset (`{"data": [WeaponData]}`)
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: WeaponFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if file.data.is_empty() {
+            return Err("No weapons".to_string());
+        }
+        let mut weapons = HashMap::new();
+        for weapon in file.data {
+            weapon.validate().map_err(|e| format!("weapon {}: {}", weapon.id, e))?;
+            let id = weapon.id;
+            if weapons.insert(id, weapon).is_some() {
+                return Err(format!("weapon {}: duplicate id", id));
+            }
+        }
+        Ok(Self { weapons })
     }
 
     /// Get weapon by ID
@@ -69,10 +214,96 @@ impl WeaponDb {
         self.weapons.contains_key(&id)
     }
 
+    /// Iterate over all weapons (unordered)
+    pub fn iter(&self) -> impl Iterator<Item = &WeaponData> {
+        self.weapons.values()
+    }
+
+    /// All weapons ordered by id
+    pub fn sorted(&self) -> Vec<&WeaponData> {
+        let mut weapons: Vec<&WeaponData> = self.weapons.values().collect();
+        weapons.sort_by_key(|w| w.id);
+        weapons
+    }
+
+    /// Get the melee weapon (lowest id if several)
+    pub fn melee_weapon(&self) -> Option<&WeaponData> {
+        self.weapons.values().filter(|w| w.is_melee()).min_by_key(|w| w.id)
+    }
+
     /// Get default weapon ID (Golden Friend)
     pub fn default_weapon_id() -> u32 {
         1
     }
+
+    /// Add or replace a weapon (test setups for weapons the bundled set lacks)
+    #[cfg(test)]
+    pub fn with_weapon(mut self, weapon: WeaponData) -> Self {
+        self.weapons.insert(weapon.id, weapon);
+        self
+    }
+}
+
+/// The weapons in effect; lobbies pick up changes on their next tick
+#[derive(Debug)]
+pub struct WeaponStore {
+    current: RwLock<Arc<WeaponDb>>,
+    revision: AtomicU64, // Bumped on every change
+    path: Mutex<Option<PathBuf>>, // File `read` parses; None = the bundled weapons
+}
+
+impl WeaponStore {
+    pub fn new() -> Self {
+        Self {
+            current: RwLock::new(Arc::new(WeaponDb::load())),
+            revision: AtomicU64::new(0),
+            path: Mutex::new(None),
+        }
+    }
+
+    /// Load weapons from `path` and remember it for reloads
+    pub fn load(&self, path: &Path) -> Result<Arc<WeaponDb>, String> {
+        *self.path.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.to_path_buf());
+        let weapons = self.read()?;
+        Ok(self.set(weapons))
+    }
+
+    /// Parse the weapons file without applying it
+    /// Weapons can be changed but not removed while lobbies may still hold them
+    pub fn read(&self) -> Result<WeaponDb, String> {
+        let path = self.path.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let weapons = match path {
+            Some(path) => WeaponDb::load_file(&path)?,
+            None => WeaponDb::load(),
+        };
+        if let Some(missing) = self.get().sorted().into_iter().find(|w| !weapons.contains(w.id)) {
+            return Err(format!("weapon {}: can't be removed while the server runs", missing.id));
+        }
+        Ok(weapons)
+    }
+
+    /// Replace the weapons in effect
+    pub fn set(&self, weapons: WeaponDb) -> Arc<WeaponDb> {
+        let weapons = Arc::new(weapons);
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = weapons.clone();
+        self.revision.fetch_add(1, Ordering::Relaxed);
+        weapons
+    }
+
+    pub fn get(&self) -> Arc<WeaponDb> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Changes so far; compare to notice a change
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for WeaponStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -114,6 +345,69 @@ mod tests {
         assert_eq!(knife.ammo, 0);
         assert_eq!(knife.reload_time, 0.0);
         assert_eq!(knife.damage, 50);
+        assert!(knife.is_melee());
+        assert!(!db.get(1).unwrap().is_melee());
     }
-}
 
+    #[test]
+    fn test_bundled_weapons_match_the_shared_asset() {
+        let db = WeaponDb::load();
+        let pistol = db.get(1).unwrap();
+        assert_eq!(pistol.recoil, Some(RecoilPattern { kick_deg: 1.5, tolerance_deg: 1.5 }));
+        assert_eq!(pistol.hit_damage(HitZone::Body, 5.0), 20);
+        assert_eq!(pistol.hit_damage(HitZone::Head, 5.0), 30);
+        assert_eq!(pistol.hit_damage(HitZone::Legs, 5.0), 15);
+        // Past the falloff: half damage, still multiplied by the zone
+        assert_eq!(pistol.falloff, Some(DamageFalloff { start: 20.0, end: 60.0, min_multiplier: 0.5 }));
+        assert_eq!(pistol.hit_damage(HitZone::Body, 40.0), 15);
+        assert_eq!(pistol.hit_damage(HitZone::Body, 90.0), 10);
+        assert_eq!(pistol.hit_damage(HitZone::Head, 90.0), 15);
+        assert_eq!(db.get(3).unwrap().hit_damage(HitZone::Legs, 2.0), 50); // Knives don't fall off
+        assert_eq!(db.melee_weapon().map(|w| w.id), Some(3));
+    }
+
+    #[test]
+    fn test_from_json_rejects_bad_weapons() {
+        let weapon = |overrides: serde_json::Value| {
+            let mut weapon = serde_json::json!({"id": 4, "name": "Test", "damage": 10, "fire_rate": 1.0, "range": 10.0, "reload_time": 1.0, "ammo": 5});
+            weapon.as_object_mut().unwrap().extend(overrides.as_object().unwrap().clone());
+            serde_json::json!({"data": [weapon]}).to_string()
+        };
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({}))).is_ok());
+        assert_eq!(WeaponDb::from_json(&weapon(serde_json::json!({"fire_rate": 0.0}))).unwrap_err(), "weapon 4: fire_rate must be positive");
+        assert_eq!(WeaponDb::from_json(&weapon(serde_json::json!({"damage": 0}))).unwrap_err(), "weapon 4: damage must be between 1 and 1000");
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({"headshot_multiplier": 0.5}))).is_err());
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({"leg_multiplier": 1.5}))).is_err());
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({"falloff": {"start": 10.0, "end": 20.0, "min_multiplier": 0.5}}))).is_ok());
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({"falloff": {"start": 20.0, "end": 10.0, "min_multiplier": 0.5}}))).is_err());
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({"falloff": {"start": 10.0, "end": 20.0, "min_multiplier": 0.0}}))).is_err());
+        assert!(WeaponDb::from_json(&weapon(serde_json::json!({"melee": true}))).is_err());
+        assert!(WeaponDb::from_json(r#"{"data": []}"#).is_err());
+        assert!(WeaponDb::from_json("not json").is_err());
+
+        let twice = r#"{"data": [
+            {"id": 4, "name": "A", "damage": 10, "fire_rate": 1.0, "range": 10.0, "reload_time": 1.0, "ammo": 5},
+            {"id": 4, "name": "B", "damage": 10, "fire_rate": 1.0, "range": 10.0, "reload_time": 1.0, "ammo": 5}
+        ]}"#;
+        assert_eq!(WeaponDb::from_json(twice).unwrap_err(), "weapon 4: duplicate id");
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = std::env::temp_dir().join(format!("weapons-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("weapons.json");
+
+        // No file: the bundled weapons
+        assert_eq!(WeaponDb::load_file(&path).unwrap().weapons.len(), 3);
+
+        std::fs::write(&path, r#"{"data": [{"id": 9, "name": "Bat", "damage": 40, "fire_rate": 1.0, "range": 2.0, "reload_time": 0.0, "ammo": 0, "melee": true}]}"#).unwrap();
+        let db = WeaponDb::load_file(&path).unwrap();
+        assert!(db.get(9).unwrap().is_melee());
+
+        std::fs::write(&path, r#"{"data": [{"id": 9, "name": "Bat", "damage": 40, "fire_rate": 0.0, "range": 2.0, "reload_time": 0.0, "ammo": 0, "melee": true}]}"#).unwrap();
+        let error = WeaponDb::load_file(&path).unwrap_err();
+        assert!(error.starts_with("Invalid ") && error.ends_with("weapon 9: fire_rate must be positive"), "{}", error);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}